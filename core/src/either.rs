@@ -18,11 +18,11 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::muxing::StreamMuxerEvent;
+use crate::muxing::{StreamMuxerEvent, SubstreamStats};
 use crate::{
     muxing::StreamMuxer,
     transport::{ListenerId, Transport, TransportError, TransportEvent},
-    Multiaddr,
+    Endpoint, Multiaddr,
 };
 use either::Either;
 use futures::prelude::*;
@@ -85,6 +85,18 @@ where
             future::Either::Right(inner) => inner.poll(cx).map_err(Either::Right),
         }
     }
+
+    fn substream_stats(&self, substream: &Self::Substream) -> Option<SubstreamStats> {
+        match (self, substream) {
+            (future::Either::Left(inner), future::Either::Left(substream)) => {
+                inner.substream_stats(substream)
+            }
+            (future::Either::Right(inner), future::Either::Right(substream)) => {
+                inner.substream_stats(substream)
+            }
+            _ => None,
+        }
+    }
 }
 
 /// Implements `Future` and dispatches all method calls to either `First` or `Second`.
@@ -210,6 +222,27 @@ where
         }
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        use TransportError::*;
+        match self {
+            Either::Left(a) => match a.dial_from(addr, local_addr, role_override) {
+                Ok(connec) => Ok(EitherFuture::First(connec)),
+                Err(MultiaddrNotSupported(addr)) => Err(MultiaddrNotSupported(addr)),
+                Err(Other(err)) => Err(Other(Either::Left(err))),
+            },
+            Either::Right(b) => match b.dial_from(addr, local_addr, role_override) {
+                Ok(connec) => Ok(EitherFuture::Second(connec)),
+                Err(MultiaddrNotSupported(addr)) => Err(MultiaddrNotSupported(addr)),
+                Err(Other(err)) => Err(Other(Either::Right(err))),
+            },
+        }
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         match self {
             Either::Left(a) => a.address_translation(server, observed),
@@ -51,9 +51,13 @@
 //! implementation of `StreamMuxer` to control everything that happens on the wire.
 
 use futures::{task::Context, task::Poll, AsyncRead, AsyncWrite};
+use instant::Instant;
 use multiaddr::Multiaddr;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 pub use self::boxed::StreamMuxerBox;
 pub use self::boxed::SubstreamBox;
@@ -109,6 +113,18 @@ pub trait StreamMuxer {
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Result<StreamMuxerEvent, Self::Error>>;
+
+    /// Returns the statistics of the given substream of this [`StreamMuxer`], if tracked
+    /// by the muxer itself, e.g. to include its framing overhead in the bytes of the substream.
+    ///
+    /// The returned statistics are expected to be kept up to date by the muxer.
+    ///
+    /// The default implementation returns `None`, in which case a [`StreamMuxerBox`]
+    /// tracks the bytes read from and written to the substream, see [`SubstreamBox::stats`].
+    fn substream_stats(&self, substream: &Self::Substream) -> Option<SubstreamStats> {
+        let _ = substream;
+        None
+    }
 }
 
 /// An event produced by a [`StreamMuxer`].
@@ -118,6 +134,90 @@ pub enum StreamMuxerEvent {
     AddressChange(Multiaddr),
 }
 
+/// Statistics of a single substream, shared by all clones and kept up to date
+/// while the substream is in use.
+#[derive(Debug, Clone)]
+pub struct SubstreamStats {
+    inner: Arc<SubstreamCounters>,
+}
+
+#[derive(Debug)]
+struct SubstreamCounters {
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    opened: Instant,
+    closed: Mutex<Option<Instant>>,
+}
+
+impl SubstreamStats {
+    /// Creates the statistics of a substream opened now.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(SubstreamCounters {
+                bytes_received: AtomicU64::new(0),
+                bytes_sent: AtomicU64::new(0),
+                opened: Instant::now(),
+                closed: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Records bytes received on the substream.
+    pub fn record_received(&self, bytes: usize) {
+        self.inner
+            .bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records bytes sent on the substream.
+    pub fn record_sent(&self, bytes: usize) {
+        self.inner
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records that the substream was dropped, ending its open duration.
+    pub fn record_closed(&self) {
+        self.inner
+            .closed
+            .lock()
+            .expect("lock not to be poisoned")
+            .get_or_insert_with(Instant::now);
+    }
+
+    /// Returns the number of bytes received on the substream.
+    pub fn bytes_received(&self) -> u64 {
+        self.inner.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes sent on the substream.
+    pub fn bytes_sent(&self) -> u64 {
+        self.inner.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the substream was dropped.
+    pub fn is_closed(&self) -> bool {
+        self.inner
+            .closed
+            .lock()
+            .expect("lock not to be poisoned")
+            .is_some()
+    }
+
+    /// Returns the duration the substream has been open for, i.e. until it was
+    /// dropped or, if it is still open, until now.
+    pub fn open_duration(&self) -> Duration {
+        let closed = *self.inner.closed.lock().expect("lock not to be poisoned");
+        closed.unwrap_or_else(Instant::now) - self.inner.opened
+    }
+}
+
+impl Default for SubstreamStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Extension trait for [`StreamMuxer`].
 pub trait StreamMuxerExt: StreamMuxer + Sized {
     /// Convenience function for calling [`StreamMuxer::poll_inbound`] for [`StreamMuxer`]s that are `Unpin`.
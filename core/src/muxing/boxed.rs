@@ -1,5 +1,5 @@
-use crate::muxing::{StreamMuxer, StreamMuxerEvent};
-use futures::{AsyncRead, AsyncWrite};
+use crate::muxing::{StreamMuxer, StreamMuxerEvent, SubstreamStats};
+use futures::{ready, AsyncRead, AsyncWrite};
 use pin_project::pin_project;
 use std::error::Error;
 use std::fmt;
@@ -23,7 +23,13 @@ impl fmt::Debug for StreamMuxerBox {
 ///
 /// A [`SubstreamBox`] erases the concrete type it is given and only retains its `AsyncRead`
 /// and `AsyncWrite` capabilities.
-pub struct SubstreamBox(Pin<Box<dyn AsyncReadWrite + Send>>);
+pub struct SubstreamBox {
+    inner: Pin<Box<dyn AsyncReadWrite + Send>>,
+    stats: SubstreamStats,
+    /// Whether the bytes read and written are recorded in `stats`, as opposed to
+    /// `stats` being tracked by the [`StreamMuxer`] of the substream.
+    record_transfers: bool,
+}
 
 #[pin_project]
 struct Wrap<T>
@@ -47,22 +53,22 @@ where
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Result<Self::Substream, Self::Error>> {
-        self.project()
-            .inner
-            .poll_inbound(cx)
-            .map_ok(SubstreamBox::new)
-            .map_err(into_io_error)
+        let mut inner = self.project().inner;
+        let substream = ready!(inner.as_mut().poll_inbound(cx)).map_err(into_io_error)?;
+        let stats = inner.as_ref().get_ref().substream_stats(&substream);
+
+        Poll::Ready(Ok(SubstreamBox::with_stats(substream, stats)))
     }
 
     fn poll_outbound(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Result<Self::Substream, Self::Error>> {
-        self.project()
-            .inner
-            .poll_outbound(cx)
-            .map_ok(SubstreamBox::new)
-            .map_err(into_io_error)
+        let mut inner = self.project().inner;
+        let substream = ready!(inner.as_mut().poll_outbound(cx)).map_err(into_io_error)?;
+        let stats = inner.as_ref().get_ref().substream_stats(&substream);
+
+        Poll::Ready(Ok(SubstreamBox::with_stats(substream, stats)))
     }
 
     #[inline]
@@ -136,18 +142,64 @@ impl StreamMuxer for StreamMuxerBox {
     ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
         self.project().poll(cx)
     }
+
+    fn substream_stats(&self, substream: &Self::Substream) -> Option<SubstreamStats> {
+        Some(substream.stats())
+    }
 }
 
 impl SubstreamBox {
     /// Construct a new [`SubstreamBox`] from something that implements [`AsyncRead`] and [`AsyncWrite`].
     pub fn new<S: AsyncRead + AsyncWrite + Send + 'static>(stream: S) -> Self {
-        Self(Box::pin(stream))
+        Self::with_stats(stream, None)
+    }
+
+    /// Construct a new [`SubstreamBox`] with the statistics tracked by the [`StreamMuxer`]
+    /// of the substream, if any.
+    fn with_stats<S: AsyncRead + AsyncWrite + Send + 'static>(
+        stream: S,
+        stats: Option<SubstreamStats>,
+    ) -> Self {
+        Self {
+            inner: Box::pin(stream),
+            record_transfers: stats.is_none(),
+            stats: stats.unwrap_or_default(),
+        }
+    }
+
+    /// Returns the statistics of the substream.
+    ///
+    /// Unless tracked by the [`StreamMuxer`] of the substream, see
+    /// [`StreamMuxer::substream_stats`], these are the bytes read from and written to
+    /// this [`SubstreamBox`].
+    pub fn stats(&self) -> SubstreamStats {
+        self.stats.clone()
+    }
+
+    /// Records the bytes transferred by a successful read or write in the statistics,
+    /// unless tracked by the [`StreamMuxer`] of the substream.
+    fn record(
+        &self,
+        poll: Poll<io::Result<usize>>,
+        record: impl FnOnce(&SubstreamStats, usize),
+    ) -> Poll<io::Result<usize>> {
+        if let (true, Poll::Ready(Ok(bytes))) = (self.record_transfers, &poll) {
+            record(&self.stats, *bytes);
+        }
+
+        poll
+    }
+}
+
+impl Drop for SubstreamBox {
+    fn drop(&mut self) {
+        self.stats.record_closed();
     }
 }
 
 impl fmt::Debug for SubstreamBox {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "SubstreamBox({})", self.0.type_name())
+        write!(f, "SubstreamBox({})", self.inner.type_name())
     }
 }
 
@@ -174,7 +226,8 @@ impl AsyncRead for SubstreamBox {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<std::io::Result<usize>> {
-        self.0.as_mut().poll_read(cx, buf)
+        let poll = self.inner.as_mut().poll_read(cx, buf);
+        self.record(poll, SubstreamStats::record_received)
     }
 
     fn poll_read_vectored(
@@ -182,7 +235,8 @@ impl AsyncRead for SubstreamBox {
         cx: &mut Context<'_>,
         bufs: &mut [IoSliceMut<'_>],
     ) -> Poll<std::io::Result<usize>> {
-        self.0.as_mut().poll_read_vectored(cx, bufs)
+        let poll = self.inner.as_mut().poll_read_vectored(cx, bufs);
+        self.record(poll, SubstreamStats::record_received)
     }
 }
 
@@ -192,7 +246,8 @@ impl AsyncWrite for SubstreamBox {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<std::io::Result<usize>> {
-        self.0.as_mut().poll_write(cx, buf)
+        let poll = self.inner.as_mut().poll_write(cx, buf);
+        self.record(poll, SubstreamStats::record_sent)
     }
 
     fn poll_write_vectored(
@@ -200,14 +255,42 @@ impl AsyncWrite for SubstreamBox {
         cx: &mut Context<'_>,
         bufs: &[IoSlice<'_>],
     ) -> Poll<std::io::Result<usize>> {
-        self.0.as_mut().poll_write_vectored(cx, bufs)
+        let poll = self.inner.as_mut().poll_write_vectored(cx, bufs);
+        self.record(poll, SubstreamStats::record_sent)
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
-        self.0.as_mut().poll_flush(cx)
+        self.inner.as_mut().poll_flush(cx)
     }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
-        self.0.as_mut().poll_close(cx)
+        self.inner.as_mut().poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::{AsyncReadExt, AsyncWriteExt, Cursor};
+
+    #[test]
+    fn substream_box_records_transfers() {
+        futures::executor::block_on(async {
+            let mut substream = SubstreamBox::new(Cursor::new(vec![1, 2, 3]));
+            let stats = substream.stats();
+
+            let mut buf = [0; 2];
+            substream.read_exact(&mut buf).await.unwrap();
+            substream.write_all(&[4, 5, 6, 7]).await.unwrap();
+
+            assert_eq!(stats.bytes_received(), 2);
+            assert_eq!(stats.bytes_sent(), 4);
+            assert!(!stats.is_closed());
+
+            drop(substream);
+            assert!(stats.is_closed());
+            let open_duration = stats.open_duration();
+            assert_eq!(stats.open_duration(), open_duration);
+        })
     }
 }
@@ -48,7 +48,7 @@ pub mod upgrade;
 mod boxed;
 mod optional;
 
-use crate::ConnectedPoint;
+use crate::{ConnectedPoint, Endpoint};
 
 pub use self::boxed::Boxed;
 pub use self::choice::OrTransport;
@@ -141,6 +141,26 @@ pub trait Transport {
         addr: Multiaddr,
     ) -> Result<Self::Dial, TransportError<Self::Error>>;
 
+    /// As [`Transport::dial`] but binds the outgoing connection to the given local address.
+    ///
+    /// This allows selecting the network interface or source address of the connection on
+    /// multi-homed hosts. `role_override` has the same meaning as for
+    /// [`Transport::dial_as_listener`].
+    ///
+    /// Implementations should return [`TransportError::Other`] if `local_addr` can not be
+    /// used to reach `addr`, e.g. because the two addresses belong to different IP families.
+    ///
+    /// The default implementation returns [`TransportError::MultiaddrNotSupported`].
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let _ = (local_addr, role_override);
+        Err(TransportError::MultiaddrNotSupported(addr))
+    }
+
     /// Poll for [`TransportEvent`]s.
     ///
     /// A [`TransportEvent::Incoming`] should be produced whenever a connection is received at the lowest
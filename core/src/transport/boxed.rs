@@ -18,7 +18,10 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::transport::{ListenerId, Transport, TransportError, TransportEvent};
+use crate::{
+    transport::{ListenerId, Transport, TransportError, TransportEvent},
+    Endpoint,
+};
 use futures::{prelude::*, stream::FusedStream};
 use multiaddr::Multiaddr;
 use std::{
@@ -60,6 +63,12 @@ trait Abstract<O> {
     fn remove_listener(&mut self, id: ListenerId) -> bool;
     fn dial(&mut self, addr: Multiaddr) -> Result<Dial<O>, TransportError<io::Error>>;
     fn dial_as_listener(&mut self, addr: Multiaddr) -> Result<Dial<O>, TransportError<io::Error>>;
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Dial<O>, TransportError<io::Error>>;
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr>;
     fn poll(
         self: Pin<&mut Self>,
@@ -100,6 +109,18 @@ where
         Ok(Box::pin(fut) as Dial<_>)
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Dial<O>, TransportError<io::Error>> {
+        let fut = Transport::dial_from(self, addr, local_addr, role_override)
+            .map(|r| r.map_err(box_err))
+            .map_err(|e| e.map(box_err))?;
+        Ok(Box::pin(fut) as Dial<_>)
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         Transport::address_translation(self, server, observed)
     }
@@ -154,6 +175,15 @@ impl<O> Transport for Boxed<O> {
         self.inner.dial_as_listener(addr)
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.inner.dial_from(addr, local_addr, role_override)
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         self.inner.address_translation(server, observed)
     }
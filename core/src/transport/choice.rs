@@ -20,6 +20,7 @@
 
 use crate::either::EitherFuture;
 use crate::transport::{ListenerId, Transport, TransportError, TransportEvent};
+use crate::Endpoint;
 use either::Either;
 use futures::future;
 use multiaddr::Multiaddr;
@@ -159,6 +160,31 @@ where
         Err(TransportError::MultiaddrNotSupported(addr))
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let addr = match self.0.dial_from(addr, local_addr, role_override) {
+            Ok(connec) => return Ok(EitherFuture::First(connec)),
+            Err(TransportError::MultiaddrNotSupported(addr)) => addr,
+            Err(TransportError::Other(err)) => {
+                return Err(TransportError::Other(Either::Left(err)))
+            }
+        };
+
+        let addr = match self.1.dial_from(addr, local_addr, role_override) {
+            Ok(connec) => return Ok(EitherFuture::Second(connec)),
+            Err(TransportError::MultiaddrNotSupported(addr)) => addr,
+            Err(TransportError::Other(err)) => {
+                return Err(TransportError::Other(Either::Right(err)))
+            }
+        };
+
+        Err(TransportError::MultiaddrNotSupported(addr))
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         if let Some(addr) = self.0.address_translation(server, observed) {
             Some(addr)
@@ -21,6 +21,7 @@
 use crate::{
     multiaddr::{Multiaddr, Protocol},
     transport::{ListenerId, TransportError, TransportEvent},
+    Endpoint,
 };
 use std::{
     pin::Pin,
@@ -336,6 +337,34 @@ impl<T: crate::Transport + Unpin> crate::Transport for Transport<T> {
         }
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        match addr.iter().next() {
+            Some(Protocol::Ip4(a)) => {
+                if !ipv4_global::is_global(a) {
+                    tracing::debug!(ip=?a, "Not dialing non global IP address");
+                    return Err(TransportError::MultiaddrNotSupported(addr));
+                }
+                self.inner.dial_from(addr, local_addr, role_override)
+            }
+            Some(Protocol::Ip6(a)) => {
+                if !ipv6_global::is_global(a) {
+                    tracing::debug!(ip=?a, "Not dialing non global IP address");
+                    return Err(TransportError::MultiaddrNotSupported(addr));
+                }
+                self.inner.dial_from(addr, local_addr, role_override)
+            }
+            _ => {
+                tracing::debug!(address=%addr, "Not dialing unsupported Multiaddress");
+                Err(TransportError::MultiaddrNotSupported(addr))
+            }
+        }
+    }
+
     fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         self.inner.address_translation(listen, observed)
     }
@@ -100,6 +100,25 @@ where
         })
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let future = self
+            .transport
+            .dial_from(addr.clone(), local_addr, role_override)?;
+        let p = ConnectedPoint::Dialer {
+            address: addr,
+            role_override,
+        };
+        Ok(MapFuture {
+            inner: future,
+            args: Some((self.fun.clone(), p)),
+        })
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         self.transport.address_translation(server, observed)
     }
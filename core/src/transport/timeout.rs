@@ -26,7 +26,7 @@
 
 use crate::{
     transport::{ListenerId, TransportError, TransportEvent},
-    Multiaddr, Transport,
+    Endpoint, Multiaddr, Transport,
 };
 use futures::prelude::*;
 use futures_timer::Delay;
@@ -124,6 +124,22 @@ where
         })
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let dial = self
+            .inner
+            .dial_from(addr, local_addr, role_override)
+            .map_err(|err| err.map(TransportTimeoutError::Other))?;
+        Ok(Timeout {
+            inner: dial,
+            timer: Delay::new(self.outgoing_timeout),
+        })
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         self.inner.address_translation(server, observed)
     }
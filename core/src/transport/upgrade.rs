@@ -23,7 +23,7 @@
 pub use crate::upgrade::Version;
 
 use crate::{
-    connection::ConnectedPoint,
+    connection::{ConnectedPoint, Endpoint},
     muxing::{StreamMuxer, StreamMuxerBox},
     transport::{
         and_then::AndThen, boxed::boxed, timeout::TransportTimeout, ListenerId, Transport,
@@ -350,6 +350,15 @@ where
         self.0.dial_as_listener(addr)
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.0.dial_from(addr, local_addr, role_override)
+    }
+
     fn listen_on(
         &mut self,
         id: ListenerId,
@@ -433,6 +442,22 @@ where
         })
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let future = self
+            .inner
+            .dial_from(addr, local_addr, role_override)
+            .map_err(|err| err.map(TransportUpgradeError::Transport))?;
+        Ok(DialUpgradeFuture {
+            future: Box::pin(future),
+            upgrade: future::Either::Left(Some(self.upgrade.clone())),
+        })
+    }
+
     fn listen_on(
         &mut self,
         id: ListenerId,
@@ -119,7 +119,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     kad::QueryResult::GetRecord(Err(err)) => {
                         eprintln!("Failed to get record: {err:?}");
                     }
-                    kad::QueryResult::PutRecord(Ok(kad::PutRecordOk { key })) => {
+                    kad::QueryResult::PutRecord(Ok(kad::PutRecordOk { key, .. })) => {
                         println!(
                             "Successfully put record {:?}",
                             std::str::from_utf8(key.as_ref()).unwrap()
@@ -198,6 +198,7 @@ fn handle_input_line(kademlia: &mut kad::Behaviour<MemoryStore>, line: String) {
                 value,
                 publisher: None,
                 expires: None,
+                sequence: None,
             };
             kademlia
                 .put_record(record, kad::Quorum::One)
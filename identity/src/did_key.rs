@@ -0,0 +1,222 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Encoding of public keys as [did:key] identifiers and multibase strings.
+//!
+//! [did:key]: https://w3c-ccg.github.io/did-method-key/
+
+#[cfg(feature = "ecdsa")]
+use crate::ecdsa;
+#[cfg(feature = "ed25519")]
+use crate::ed25519;
+use crate::error::DecodingError;
+use crate::keypair::{PublicKey, PublicKeyInner};
+#[cfg(all(feature = "rsa", not(target_arch = "wasm32")))]
+use crate::rsa;
+#[cfg(feature = "secp256k1")]
+use crate::secp256k1;
+
+/// The prefix of a did:key identifier.
+const DID_KEY_PREFIX: &str = "did:key:";
+
+/// The multibase prefix of base58btc, the only base used by did:key.
+const BASE58_BTC: char = 'z';
+
+/// The multicodec codes of the public key types.
+///
+/// See <https://github.com/multiformats/multicodec/blob/master/table.csv>.
+const ED25519_PUB: u64 = 0xed;
+const SECP256K1_PUB: u64 = 0xe7;
+const P256_PUB: u64 = 0x1200;
+const RSA_PUB: u64 = 0x1205;
+
+impl PublicKey {
+    /// Encodes the public key as a multibase string, i.e. the base58btc encoding of the
+    /// public key prefixed with its multicodec code.
+    ///
+    /// This is the `publicKeyMultibase` form of verification methods, e.g. of the `Multikey`
+    /// type. Ed25519 keys are encoded as raw bytes, Secp256k1 and ECDSA keys as compressed
+    /// points, and RSA keys as DER-encoded PKCS#1 structures.
+    pub fn to_multibase(&self) -> String {
+        let (code, key) = match &self.publickey {
+            #[cfg(feature = "ed25519")]
+            PublicKeyInner::Ed25519(key) => (ED25519_PUB, key.to_bytes().to_vec()),
+            #[cfg(all(feature = "rsa", not(target_arch = "wasm32")))]
+            PublicKeyInner::Rsa(key) => (RSA_PUB, key.encode_pkcs1()),
+            #[cfg(feature = "secp256k1")]
+            PublicKeyInner::Secp256k1(key) => (SECP256K1_PUB, key.to_bytes().to_vec()),
+            #[cfg(feature = "ecdsa")]
+            PublicKeyInner::Ecdsa(key) => (P256_PUB, key.to_bytes_compressed()),
+        };
+
+        let mut buf = Vec::with_capacity(key.len() + 2);
+        encode_varint(code, &mut buf);
+        buf.extend_from_slice(&key);
+
+        format!("{BASE58_BTC}{}", bs58::encode(buf).into_string())
+    }
+
+    /// Decodes a public key from a multibase string, see [`PublicKey::to_multibase`].
+    #[allow(unused_variables)]
+    pub fn try_from_multibase(s: &str) -> Result<PublicKey, DecodingError> {
+        let encoded = s
+            .strip_prefix(BASE58_BTC)
+            .ok_or_else(|| DecodingError::bad_multibase("unsupported multibase encoding"))?;
+        let bytes = bs58::decode(encoded)
+            .into_vec()
+            .map_err(|_| DecodingError::bad_multibase("invalid base58btc encoding"))?;
+        let (code, key) = decode_varint(&bytes)
+            .ok_or_else(|| DecodingError::bad_multibase("invalid multicodec prefix"))?;
+
+        match code {
+            #[cfg(feature = "ed25519")]
+            ED25519_PUB => ed25519::PublicKey::try_from_bytes(key).map(PublicKey::from),
+            #[cfg(all(feature = "rsa", not(target_arch = "wasm32")))]
+            RSA_PUB => rsa::PublicKey::try_decode_pkcs1(key).map(PublicKey::from),
+            #[cfg(feature = "secp256k1")]
+            SECP256K1_PUB => secp256k1::PublicKey::try_from_bytes(key).map(PublicKey::from),
+            #[cfg(feature = "ecdsa")]
+            P256_PUB => ecdsa::PublicKey::try_from_bytes(key).map(PublicKey::from),
+            #[cfg(not(feature = "ed25519"))]
+            ED25519_PUB => Err(DecodingError::missing_feature("ed25519")),
+            #[cfg(any(not(feature = "rsa"), target_arch = "wasm32"))]
+            RSA_PUB => Err(DecodingError::missing_feature("rsa")),
+            #[cfg(not(feature = "secp256k1"))]
+            SECP256K1_PUB => Err(DecodingError::missing_feature("secp256k1")),
+            #[cfg(not(feature = "ecdsa"))]
+            P256_PUB => Err(DecodingError::missing_feature("ecdsa")),
+            _ => Err(DecodingError::bad_multibase("unsupported multicodec")),
+        }
+    }
+
+    /// Encodes the public key as a [did:key] identifier, i.e. `did:key:` followed by
+    /// the multibase encoding of the key, see [`PublicKey::to_multibase`].
+    ///
+    /// [did:key]: https://w3c-ccg.github.io/did-method-key/
+    pub fn to_did_key(&self) -> String {
+        format!("{DID_KEY_PREFIX}{}", self.to_multibase())
+    }
+
+    /// Decodes a public key from a [did:key] identifier, see [`PublicKey::to_did_key`].
+    ///
+    /// A fragment, e.g. of the identifier of the verification method, is ignored.
+    ///
+    /// [did:key]: https://w3c-ccg.github.io/did-method-key/
+    pub fn try_from_did_key(did: &str) -> Result<PublicKey, DecodingError> {
+        let multibase = did
+            .strip_prefix(DID_KEY_PREFIX)
+            .ok_or_else(|| DecodingError::bad_multibase("missing `did:key:` prefix"))?;
+        let multibase = multibase
+            .split_once('#')
+            .map_or(multibase, |(multibase, _)| multibase);
+
+        Self::try_from_multibase(multibase)
+    }
+}
+
+/// Appends `n` encoded as an unsigned varint to `buf`.
+fn encode_varint(mut n: u64, buf: &mut Vec<u8>) {
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+/// Decodes an unsigned varint at the start of `buf`, returning it and the remaining bytes.
+fn decode_varint(buf: &[u8]) -> Option<(u64, &[u8])> {
+    let mut n = 0u64;
+    // Multicodec codes are limited to 9 bytes.
+    for (i, byte) in buf.iter().enumerate().take(9) {
+        n |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((n, &buf[i + 1..]));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "ed25519")]
+    fn ed25519_did_key_test_vector() {
+        // From the did:key specification.
+        let did = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp";
+        let key = PublicKey::try_from_did_key(did).unwrap();
+
+        assert_eq!(key.key_type(), crate::KeyType::Ed25519);
+        assert_eq!(key.to_did_key(), did);
+        assert_eq!(
+            PublicKey::try_from_did_key(&format!(
+                "{did}#z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp"
+            ))
+            .unwrap(),
+            key
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "secp256k1")]
+    fn secp256k1_did_key_test_vector() {
+        // From the did:key specification.
+        let did = "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme";
+        let key = PublicKey::try_from_did_key(did).unwrap();
+
+        assert_eq!(key.key_type(), crate::KeyType::Secp256k1);
+        assert_eq!(key.to_did_key(), did);
+    }
+
+    #[test]
+    #[cfg(feature = "ecdsa")]
+    fn ecdsa_did_key_test_vector() {
+        // From the did:key specification.
+        let did = "did:key:zDnaerDaTF5BXEavCrfRZEk316dpbLsfPDZ3WJ5hRTPFU2169";
+        let key = PublicKey::try_from_did_key(did).unwrap();
+
+        assert_eq!(key.key_type(), crate::KeyType::Ecdsa);
+        assert_eq!(key.to_did_key(), did);
+    }
+
+    #[test]
+    #[cfg(all(feature = "rsa", not(target_arch = "wasm32")))]
+    fn rsa_multibase_roundtrip() {
+        let mut pkcs8 = *include_bytes!("test/rsa-2048.pk8");
+        let key = crate::Keypair::rsa_from_pkcs8(&mut pkcs8).unwrap().public();
+
+        let multibase = key.to_multibase();
+        assert!(multibase.starts_with("z4MX"));
+        assert_eq!(PublicKey::try_from_multibase(&multibase).unwrap(), key);
+    }
+
+    #[test]
+    fn rejects_malformed_identifiers() {
+        for did in [
+            "did:web:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+            "did:key:f6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+            "did:key:z0OIl",
+            "did:key:z",
+        ] {
+            assert!(PublicKey::try_from_did_key(did).is_err(), "{did}");
+        }
+    }
+}
@@ -172,6 +172,11 @@ impl PublicKey {
         self.0.to_encoded_point(false).as_bytes().to_owned()
     }
 
+    /// Convert a public key into a byte buffer containing raw components of the key with compression.
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        self.0.to_encoded_point(true).as_bytes().to_owned()
+    }
+
     /// Encode a public key into a DER encoded byte buffer as defined by SEC1 standard.
     pub fn encode_der(&self) -> Vec<u8> {
         let buf = self.to_bytes();
@@ -77,6 +77,22 @@ impl DecodingError {
         }
     }
 
+    #[cfg(all(
+        feature = "did-key",
+        any(
+            feature = "ecdsa",
+            feature = "secp256k1",
+            feature = "ed25519",
+            feature = "rsa"
+        )
+    ))]
+    pub(crate) fn bad_multibase(reason: &'static str) -> Self {
+        Self {
+            msg: format!("failed to decode public key from multibase: {reason}"),
+            source: None,
+        }
+    }
+
     #[cfg(all(feature = "rsa", not(target_arch = "wasm32")))]
     pub(crate) fn encoding_unsupported(key_type: &'static str) -> Self {
         Self {
@@ -107,7 +123,10 @@ pub struct SigningError {
 
 /// An error during encoding of key material.
 impl SigningError {
-    #[cfg(all(feature = "rsa", not(target_arch = "wasm32")))]
+    #[cfg(any(
+        all(feature = "rsa", not(target_arch = "wasm32")),
+        all(feature = "webcrypto", target_arch = "wasm32")
+    ))]
     pub(crate) fn new<S: ToString>(msg: S) -> Self {
         Self {
             msg: msg.to_string(),
@@ -115,7 +134,10 @@ impl SigningError {
         }
     }
 
-    #[cfg(all(feature = "rsa", not(target_arch = "wasm32")))]
+    #[cfg(any(
+        all(feature = "rsa", not(target_arch = "wasm32")),
+        all(feature = "webcrypto", target_arch = "wasm32")
+    ))]
     pub(crate) fn source(self, source: impl Error + Send + Sync + 'static) -> Self {
         Self {
             source: Some(Box::new(source)),
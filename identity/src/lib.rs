@@ -58,10 +58,24 @@ pub mod rsa;
 #[cfg(feature = "secp256k1")]
 pub mod secp256k1;
 
+#[cfg(all(feature = "webcrypto", target_arch = "wasm32"))]
+pub mod webcrypto;
+
+#[cfg(all(
+    feature = "did-key",
+    any(
+        feature = "ecdsa",
+        feature = "secp256k1",
+        feature = "ed25519",
+        feature = "rsa"
+    )
+))]
+mod did_key;
 mod error;
 mod keypair;
 #[cfg(feature = "peerid")]
 mod peer_id;
+mod signer;
 
 #[cfg(any(
     feature = "ecdsa",
@@ -112,6 +126,7 @@ pub use error::{DecodingError, OtherVariantError, SigningError};
 pub use keypair::{Keypair, PublicKey};
 #[cfg(feature = "peerid")]
 pub use peer_id::{ParseError, PeerId};
+pub use signer::{AsyncSigner, SignFuture};
 
 /// The type of key a `KeyPair` is holding.
 #[derive(Debug, PartialEq, Eq)]
@@ -100,6 +100,21 @@ impl PublicKey {
         self.0.clone()
     }
 
+    /// Decode an RSA public key from a DER-encoded PKCS#1 RSAPublicKey
+    /// structure. See also `encode_pkcs1`.
+    pub fn try_decode_pkcs1(der: &[u8]) -> Result<PublicKey, DecodingError> {
+        let seq =
+            Sequence::decode(der).map_err(|e| DecodingError::failed_to_parse("RSA PKCS#1", e))?;
+        // The modulus and the public exponent.
+        if seq.len() != 2 {
+            return Err(DecodingError::failed_to_parse::<Asn1DerError, _>(
+                "RSA PKCS#1",
+                None,
+            ));
+        }
+        Ok(PublicKey(der.to_vec()))
+    }
+
     /// Encode the RSA public key in DER as a X.509 SubjectPublicKeyInfo structure,
     /// as defined in [RFC5280].
     ///
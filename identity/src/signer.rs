@@ -0,0 +1,47 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::error::SigningError;
+use crate::{Keypair, PublicKey};
+use std::future::{self, Future};
+use std::pin::Pin;
+
+/// A future resolving to a signature, see [`AsyncSigner::sign`].
+pub type SignFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<u8>, SigningError>> + 'a>>;
+
+/// Signs messages with a key that is not necessarily held in memory, e.g. a key
+/// kept by the browser's WebCrypto API.
+pub trait AsyncSigner {
+    /// Get the public key of the signer.
+    fn public(&self) -> PublicKey;
+
+    /// Sign a message, producing a signature that can be verified using [`AsyncSigner::public`].
+    fn sign<'a>(&'a self, msg: &'a [u8]) -> SignFuture<'a>;
+}
+
+impl AsyncSigner for Keypair {
+    fn public(&self) -> PublicKey {
+        Keypair::public(self)
+    }
+
+    fn sign<'a>(&'a self, msg: &'a [u8]) -> SignFuture<'a> {
+        Box::pin(future::ready(Keypair::sign(self, msg)))
+    }
+}
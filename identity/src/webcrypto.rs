@@ -0,0 +1,138 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! ECDSA P-256 keys held by the browser's [WebCrypto API](https://developer.mozilla.org/en-US/docs/Web/API/SubtleCrypto).
+//!
+//! Private keys are generated as non-extractable, thus they never enter JS-accessible memory.
+//! The underlying [`CryptoKey`]s can be persisted in IndexedDB and be restored via
+//! [`Keypair::from_crypto_keys`].
+
+use crate::error::SigningError;
+use crate::signer::{AsyncSigner, SignFuture};
+use crate::{ecdsa, PublicKey};
+use js_sys::{Array, Promise, Reflect, Uint8Array};
+use p256::ecdsa::Signature;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Crypto, CryptoKey, EcKeyGenParams, EcdsaParams, SubtleCrypto};
+
+const ALGORITHM: &str = "ECDSA";
+const NAMED_CURVE: &str = "P-256";
+const HASH: &str = "SHA-256";
+
+/// An ECDSA P-256 keypair whose private key is held by WebCrypto.
+#[derive(Debug, Clone)]
+pub struct Keypair {
+    private_key: CryptoKey,
+    public_key: CryptoKey,
+    public: ecdsa::PublicKey,
+}
+
+impl Keypair {
+    /// Generate a new keypair whose private key cannot be exported.
+    pub async fn generate() -> Result<Keypair, SigningError> {
+        let usages = Array::of2(&JsValue::from_str("sign"), &JsValue::from_str("verify"));
+        let pair = call(subtle_crypto()?.generate_key_with_object(
+            &EcKeyGenParams::new(ALGORITHM, NAMED_CURVE),
+            false,
+            &usages,
+        ))
+        .await?;
+
+        Keypair::from_crypto_keys(get_key(&pair, "privateKey")?, get_key(&pair, "publicKey")?).await
+    }
+
+    /// Restore a keypair from its WebCrypto keys, e.g. after loading them from IndexedDB.
+    pub async fn from_crypto_keys(
+        private_key: CryptoKey,
+        public_key: CryptoKey,
+    ) -> Result<Keypair, SigningError> {
+        let raw = call(subtle_crypto()?.export_key("raw", &public_key)).await?;
+        let public = ecdsa::PublicKey::try_from_bytes(&Uint8Array::new(&raw).to_vec())
+            .map_err(|e| SigningError::new("invalid WebCrypto public key").source(e))?;
+
+        Ok(Keypair {
+            private_key,
+            public_key,
+            public,
+        })
+    }
+
+    /// Get the WebCrypto private and public key of this keypair, e.g. to persist them in IndexedDB.
+    pub fn crypto_keys(&self) -> (&CryptoKey, &CryptoKey) {
+        (&self.private_key, &self.public_key)
+    }
+
+    /// Get the public key of this keypair.
+    pub fn public(&self) -> PublicKey {
+        self.public.clone().into()
+    }
+
+    /// Sign a message, producing a DER-encoded signature like [`ecdsa::Keypair::sign`].
+    pub async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SigningError> {
+        let params = EcdsaParams::new(ALGORITHM, &JsValue::from_str(HASH));
+        let raw =
+            call(subtle_crypto()?.sign_with_object_and_u8_array(&params, &self.private_key, msg))
+                .await?;
+
+        // WebCrypto produces signatures in IEEE P1363 format whereas libp2p uses DER.
+        let signature = Signature::from_slice(&Uint8Array::new(&raw).to_vec())
+            .map_err(|e| SigningError::new("invalid WebCrypto signature").source(e))?;
+
+        Ok(signature.to_der().as_bytes().to_vec())
+    }
+}
+
+impl AsyncSigner for Keypair {
+    fn public(&self) -> PublicKey {
+        Keypair::public(self)
+    }
+
+    fn sign<'a>(&'a self, msg: &'a [u8]) -> SignFuture<'a> {
+        Box::pin(Keypair::sign(self, msg))
+    }
+}
+
+/// Get the [`SubtleCrypto`] of the current window or worker.
+fn subtle_crypto() -> Result<SubtleCrypto, SigningError> {
+    let crypto = Reflect::get(&js_sys::global(), &JsValue::from_str("crypto")).map_err(js_error)?;
+    if crypto.is_undefined() {
+        return Err(SigningError::new("WebCrypto is not available"));
+    }
+
+    Ok(crypto.unchecked_into::<Crypto>().subtle())
+}
+
+async fn call(promise: Result<Promise, JsValue>) -> Result<JsValue, SigningError> {
+    JsFuture::from(promise.map_err(js_error)?)
+        .await
+        .map_err(js_error)
+}
+
+fn get_key(pair: &JsValue, field: &str) -> Result<CryptoKey, SigningError> {
+    Reflect::get(pair, &JsValue::from_str(field))
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(js_error)
+}
+
+fn js_error(e: JsValue) -> SigningError {
+    SigningError::new(format!("WebCrypto operation failed: {e:?}"))
+}
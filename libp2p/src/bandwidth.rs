@@ -20,7 +20,7 @@
 
 #![allow(deprecated)]
 
-use crate::core::muxing::{StreamMuxer, StreamMuxerEvent};
+use crate::core::muxing::{StreamMuxer, StreamMuxerEvent, SubstreamStats};
 
 use futures::{
     io::{IoSlice, IoSliceMut},
@@ -100,6 +100,10 @@ where
         let this = self.project();
         this.inner.poll_close(cx)
     }
+
+    fn substream_stats(&self, substream: &Self::Substream) -> Option<SubstreamStats> {
+        self.inner.substream_stats(&substream.inner)
+    }
 }
 
 /// Allows obtaining the average bandwidth of the streams.
@@ -130,6 +130,32 @@ mod tests {
             .build();
     }
 
+    #[test]
+    #[cfg(all(
+        feature = "tokio",
+        feature = "tcp",
+        feature = "pnet",
+        feature = "noise",
+        feature = "yamux",
+        feature = "quic"
+    ))]
+    fn tcp_pnet_quic() {
+        let _ = SwarmBuilder::with_new_identity()
+            .with_tokio()
+            .with_tcp_pnet(
+                Default::default(),
+                libp2p_pnet::PnetConfig::new(libp2p_pnet::PreSharedKey::new([0; 32])),
+                libp2p_pnet::HandshakeStats::default(),
+                libp2p_noise::Config::new,
+                libp2p_yamux::Config::default,
+            )
+            .unwrap()
+            .with_quic()
+            .with_behaviour(|_| libp2p_swarm::dummy::Behaviour)
+            .unwrap()
+            .build();
+    }
+
     #[test]
     #[cfg(all(feature = "async-std", feature = "quic"))]
     fn async_std_quic() {
@@ -91,6 +91,94 @@ macro_rules! impl_tcp_builder {
                     phantom: PhantomData,
                 })
             }
+
+            /// Adds a TCP based transport whose connections are protected by the pre-shared key
+            /// of a private network, see [`libp2p_pnet`]. Other transports, e.g. QUIC, are not
+            /// affected and remain public.
+            ///
+            /// The outcomes of the private network handshakes are counted in `pnet_stats`, see
+            /// [`libp2p_pnet::HandshakeStats`]. Otherwise like [`SwarmBuilder::with_tcp`].
+            ///
+            /// ``` rust
+            /// # use libp2p::SwarmBuilder;
+            /// # use std::error::Error;
+            /// # async fn build_swarm() -> Result<(), Box<dyn Error>> {
+            /// let psk = libp2p_pnet::PreSharedKey::new([0; 32]);
+            /// let pnet_stats = libp2p_pnet::HandshakeStats::default();
+            /// let swarm = SwarmBuilder::with_new_identity()
+            ///     .with_tokio()
+            ///     .with_tcp_pnet(
+            ///         Default::default(),
+            ///         libp2p_pnet::PnetConfig::new(psk),
+            ///         pnet_stats.clone(),
+            ///         libp2p_noise::Config::new,
+            ///         libp2p_yamux::Config::default,
+            ///     )?
+            ///     .with_quic()
+            /// # ;
+            /// # Ok(())
+            /// # }
+            /// ```
+            #[cfg(feature = "pnet")]
+            pub fn with_tcp_pnet<SecUpgrade, SecStream, SecError, MuxUpgrade, MuxStream, MuxError>(
+                self,
+                tcp_config: libp2p_tcp::Config,
+                pnet_config: libp2p_pnet::PnetConfig,
+                pnet_stats: libp2p_pnet::HandshakeStats,
+                security_upgrade: SecUpgrade,
+                multiplexer_upgrade: MuxUpgrade,
+            ) -> Result<
+                SwarmBuilder<$providerPascalCase, QuicPhase<impl AuthenticatedMultiplexedTransport>>,
+            SecUpgrade::Error,
+            >
+            where
+                SecStream: futures::AsyncRead + futures::AsyncWrite + Unpin + Send + 'static,
+                SecError: std::error::Error + Send + Sync + 'static,
+                SecUpgrade: IntoSecurityUpgrade<libp2p_pnet::PnetOutput<libp2p_tcp::$path::TcpStream>>,
+                SecUpgrade::Upgrade: InboundConnectionUpgrade<Negotiated<libp2p_pnet::PnetOutput<libp2p_tcp::$path::TcpStream>>, Output = (libp2p_identity::PeerId, SecStream), Error = SecError> + OutboundConnectionUpgrade<Negotiated<libp2p_pnet::PnetOutput<libp2p_tcp::$path::TcpStream>>, Output = (libp2p_identity::PeerId, SecStream), Error = SecError> + Clone + Send + 'static,
+                <SecUpgrade::Upgrade as InboundConnectionUpgrade<Negotiated<libp2p_pnet::PnetOutput<libp2p_tcp::$path::TcpStream>>>>::Future: Send,
+                <SecUpgrade::Upgrade as OutboundConnectionUpgrade<Negotiated<libp2p_pnet::PnetOutput<libp2p_tcp::$path::TcpStream>>>>::Future: Send,
+                <<<SecUpgrade as IntoSecurityUpgrade<libp2p_pnet::PnetOutput<libp2p_tcp::$path::TcpStream>>>::Upgrade as UpgradeInfo>::InfoIter as IntoIterator>::IntoIter: Send,
+                <<SecUpgrade as IntoSecurityUpgrade<libp2p_pnet::PnetOutput<libp2p_tcp::$path::TcpStream>>>::Upgrade as UpgradeInfo>::Info: Send,
+
+                MuxStream: StreamMuxer + Send + 'static,
+                MuxStream::Substream: Send + 'static,
+                MuxStream::Error: Send + Sync + 'static,
+                MuxUpgrade: IntoMultiplexerUpgrade<SecStream>,
+                MuxUpgrade::Upgrade: InboundConnectionUpgrade<Negotiated<SecStream>, Output = MuxStream, Error = MuxError> + OutboundConnectionUpgrade<Negotiated<SecStream>, Output = MuxStream, Error = MuxError> + Clone + Send + 'static,
+                <MuxUpgrade::Upgrade as InboundConnectionUpgrade<Negotiated<SecStream>>>::Future: Send,
+                <MuxUpgrade::Upgrade as OutboundConnectionUpgrade<Negotiated<SecStream>>>::Future: Send,
+                MuxError: std::error::Error + Send + Sync + 'static,
+                <<<MuxUpgrade as IntoMultiplexerUpgrade<SecStream>>::Upgrade as UpgradeInfo>::InfoIter as IntoIterator>::IntoIter: Send,
+                <<MuxUpgrade as IntoMultiplexerUpgrade<SecStream>>::Upgrade as UpgradeInfo>::Info: Send,
+            {
+                Ok(SwarmBuilder {
+                    phase: QuicPhase {
+                        transport: libp2p_tcp::$path::Transport::new(tcp_config)
+                            .and_then(move |socket, endpoint| {
+                                let source = match endpoint {
+                                    libp2p_core::ConnectedPoint::Listener { send_back_addr, .. } => {
+                                        send_back_addr.iter().find_map(|p| match p {
+                                            libp2p_core::multiaddr::Protocol::Ip4(ip) => Some(ip.into()),
+                                            libp2p_core::multiaddr::Protocol::Ip6(ip) => Some(ip.into()),
+                                            _ => None,
+                                        })
+                                    }
+                                    libp2p_core::ConnectedPoint::Dialer { .. } => None,
+                                };
+                                pnet_config.handshake_observed(socket, source, pnet_stats.clone())
+                            })
+                            .upgrade(libp2p_core::upgrade::Version::V1Lazy)
+                            .authenticate(
+                                security_upgrade.into_security_upgrade(&self.keypair)?,
+                            )
+                            .multiplex(multiplexer_upgrade.into_multiplexer_upgrade())
+                            .map(|(p, c), _| (p, StreamMuxerBox::new(c))),
+                    },
+                    keypair: self.keypair,
+                    phantom: PhantomData,
+                })
+            }
         }
     };
 }
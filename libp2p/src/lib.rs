@@ -56,6 +56,9 @@ pub use libp2p_dcutr as dcutr;
 #[cfg(not(target_arch = "wasm32"))]
 #[doc(inline)]
 pub use libp2p_dns as dns;
+#[cfg(feature = "dns-discovery")]
+#[doc(inline)]
+pub use libp2p_dns_discovery as dns_discovery;
 #[cfg(feature = "floodsub")]
 #[doc(inline)]
 pub use libp2p_floodsub as floodsub;
@@ -68,6 +71,9 @@ pub use libp2p_identify as identify;
 #[cfg(feature = "kad")]
 #[doc(inline)]
 pub use libp2p_kad as kad;
+#[cfg(feature = "kad-proxy")]
+#[doc(inline)]
+pub use libp2p_kad_proxy as kad_proxy;
 #[cfg(feature = "mdns")]
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "mdns")))]
@@ -84,6 +90,9 @@ pub use libp2p_metrics as metrics;
 #[cfg(feature = "noise")]
 #[doc(inline)]
 pub use libp2p_noise as noise;
+#[cfg(feature = "padding")]
+#[doc(inline)]
+pub use libp2p_padding as padding;
 #[cfg(feature = "ping")]
 #[doc(inline)]
 pub use libp2p_ping as ping;
@@ -126,6 +135,9 @@ pub use libp2p_uds as uds;
 #[cfg(not(target_arch = "wasm32"))]
 #[doc(inline)]
 pub use libp2p_upnp as upnp;
+#[cfg(feature = "warm-standby")]
+#[doc(inline)]
+pub use libp2p_warm_standby as warm_standby;
 #[cfg(feature = "websocket")]
 #[cfg(not(target_arch = "wasm32"))]
 #[doc(inline)]
@@ -6,9 +6,9 @@ use futures::{
     ready,
 };
 use libp2p_core::{
-    muxing::{StreamMuxer, StreamMuxerEvent},
+    muxing::{StreamMuxer, StreamMuxerEvent, SubstreamStats},
     transport::{ListenerId, TransportError, TransportEvent},
-    Multiaddr,
+    Endpoint, Multiaddr,
 };
 use libp2p_identity::PeerId;
 use prometheus_client::{
@@ -107,6 +107,21 @@ where
             })))
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let metrics = ConnectionMetrics::from_family_and_addr(&self.metrics, &addr);
+        Ok(self
+            .transport
+            .dial_from(addr.clone(), local_addr, role_override)?
+            .map_ok(Box::new(|(peer_id, stream_muxer)| {
+                (peer_id, Muxer::new(stream_muxer, metrics))
+            })))
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         self.transport.address_translation(server, observed)
     }
@@ -235,6 +250,10 @@ where
         let this = self.project();
         this.inner.poll_close(cx)
     }
+
+    fn substream_stats(&self, substream: &Self::Substream) -> Option<SubstreamStats> {
+        self.inner.substream_stats(&substream.inner)
+    }
 }
 
 /// Wraps around an [`AsyncRead`] + [`AsyncWrite`] and logs the bandwidth that goes through it.
@@ -0,0 +1,261 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Connection churn and peer-set stability, derived from the connection events of a
+//! [`Swarm`](libp2p_swarm::Swarm) at the time the metrics are collected.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use instant::Instant;
+use libp2p_identity::PeerId;
+use prometheus_client::collector::Collector;
+use prometheus_client::encoding::{DescriptorEncoder, EncodeMetric};
+use prometheus_client::metrics::gauge::ConstGauge;
+use prometheus_client::metrics::MetricType;
+use prometheus_client::registry::Unit;
+
+const MINUTE: Duration = Duration::from_secs(60);
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// Number of most recently closed connections the median lifetime is computed from.
+const MAX_LIFETIMES: usize = 1024;
+
+#[derive(Debug)]
+pub(crate) struct Churn {
+    /// Establishment times of the connections opened within the last minute.
+    opened: VecDeque<Instant>,
+    /// Closing times of the connections closed within the last minute.
+    closed: VecDeque<Instant>,
+    /// Lifetimes of the most recently closed connections.
+    lifetimes: VecDeque<Duration>,
+    /// Peers a connection was established with and when that last happened.
+    seen: HashMap<PeerId, Instant>,
+    /// Currently connected peers.
+    connected: HashSet<PeerId>,
+    /// Peers connected at any time during the current interval.
+    interval: HashSet<PeerId>,
+    interval_start: Instant,
+    /// Peers connected during the last two completed intervals, the most recent last.
+    completed: [Option<HashSet<PeerId>>; 2],
+}
+
+impl Churn {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            opened: VecDeque::new(),
+            closed: VecDeque::new(),
+            lifetimes: VecDeque::new(),
+            seen: HashMap::new(),
+            connected: HashSet::new(),
+            interval: HashSet::new(),
+            interval_start: now,
+            completed: [None, None],
+        }
+    }
+
+    pub(crate) fn on_connection_established(&mut self, peer: PeerId, now: Instant) {
+        self.advance(now);
+        self.opened.push_back(now);
+        self.seen.insert(peer, now);
+        self.connected.insert(peer);
+        self.interval.insert(peer);
+    }
+
+    /// Records a closed connection, `remaining` being the number of connections to the
+    /// peer that are still established.
+    pub(crate) fn on_connection_closed(
+        &mut self,
+        peer: PeerId,
+        remaining: u32,
+        lifetime: Duration,
+        now: Instant,
+    ) {
+        self.advance(now);
+        self.closed.push_back(now);
+        if self.lifetimes.len() == MAX_LIFETIMES {
+            self.lifetimes.pop_front();
+        }
+        self.lifetimes.push_back(lifetime);
+        if remaining == 0 {
+            self.connected.remove(&peer);
+        }
+    }
+
+    /// Drops what is outside of the windows and completes the elapsed intervals.
+    fn advance(&mut self, now: Instant) {
+        prune(&mut self.opened, now, MINUTE);
+        prune(&mut self.closed, now, MINUTE);
+        self.seen
+            .retain(|_, last_seen| now.saturating_duration_since(*last_seen) < HOUR);
+
+        let intervals = (now.saturating_duration_since(self.interval_start).as_secs()
+            / MINUTE.as_secs()) as u32;
+        // Without events in between, intervals beyond the third all saw the connected peers only.
+        for _ in 0..intervals.min(3) {
+            let interval = std::mem::replace(&mut self.interval, self.connected.clone());
+            self.completed = [self.completed[1].take(), Some(interval)];
+        }
+        self.interval_start += MINUTE * intervals;
+    }
+
+    fn median_lifetime(&self) -> Option<Duration> {
+        if self.lifetimes.is_empty() {
+            return None;
+        }
+        let mut lifetimes = self.lifetimes.iter().copied().collect::<Vec<_>>();
+        lifetimes.sort_unstable();
+        let mid = lifetimes.len() / 2;
+        Some(if lifetimes.len() % 2 == 0 {
+            (lifetimes[mid - 1] + lifetimes[mid]) / 2
+        } else {
+            lifetimes[mid]
+        })
+    }
+
+    /// Jaccard index of the peers connected during the last two completed intervals.
+    fn stability(&self) -> Option<f64> {
+        let [Some(previous), Some(last)] = &self.completed else {
+            return None;
+        };
+        let union = previous.union(last).count();
+        if union == 0 {
+            return Some(1.0);
+        }
+        Some(previous.intersection(last).count() as f64 / union as f64)
+    }
+}
+
+fn prune(times: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while times
+        .front()
+        .is_some_and(|t| now.saturating_duration_since(*t) >= window)
+    {
+        times.pop_front();
+    }
+}
+
+/// Exports the [`Churn`] recorded by the swarm [`Metrics`](crate::swarm::Metrics).
+#[derive(Debug)]
+pub(crate) struct ChurnCollector(pub(crate) Arc<Mutex<Churn>>);
+
+impl Collector for ChurnCollector {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), std::fmt::Error> {
+        let mut churn = self.0.lock().expect("lock not to be poisoned");
+        churn.advance(Instant::now());
+
+        ConstGauge::new(churn.opened.len() as i64).encode(encoder.encode_descriptor(
+            "connections_opened_per_minute",
+            "Number of connections established within the last minute",
+            None,
+            MetricType::Gauge,
+        )?)?;
+
+        ConstGauge::new(churn.closed.len() as i64).encode(encoder.encode_descriptor(
+            "connections_closed_per_minute",
+            "Number of connections closed within the last minute",
+            None,
+            MetricType::Gauge,
+        )?)?;
+
+        if let Some(lifetime) = churn.median_lifetime() {
+            ConstGauge::new(lifetime.as_secs_f64()).encode(encoder.encode_descriptor(
+                "connections_median_lifetime",
+                "Median lifetime of the most recently closed connections",
+                Some(&Unit::Seconds),
+                MetricType::Gauge,
+            )?)?;
+        }
+
+        ConstGauge::new(churn.seen.len() as i64).encode(encoder.encode_descriptor(
+            "peers_seen_per_hour",
+            "Number of unique peers a connection was established with within the last hour",
+            None,
+            MetricType::Gauge,
+        )?)?;
+
+        if let Some(stability) = churn.stability() {
+            ConstGauge::new(stability).encode(encoder.encode_descriptor(
+                "peer_set_stability",
+                "Jaccard index of the peers connected during the last two one-minute intervals",
+                None,
+                MetricType::Gauge,
+            )?)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn churn_is_windowed() {
+        let start = Instant::now();
+        let mut churn = Churn::new(start);
+        let (a, b) = (PeerId::random(), PeerId::random());
+
+        churn.on_connection_established(a, start);
+        churn.on_connection_established(b, start + Duration::from_secs(30));
+        churn.on_connection_closed(
+            a,
+            0,
+            Duration::from_secs(40),
+            start + Duration::from_secs(40),
+        );
+        assert_eq!(churn.opened.len(), 2);
+        assert_eq!(churn.closed.len(), 1);
+
+        churn.advance(start + Duration::from_secs(80));
+        assert_eq!(churn.opened.len(), 1);
+        assert_eq!(churn.closed.len(), 1);
+        assert_eq!(churn.seen.len(), 2);
+
+        churn.advance(start + HOUR + Duration::from_secs(10));
+        assert!(churn.opened.is_empty() && churn.closed.is_empty());
+        assert_eq!(churn.seen.len(), 1);
+        assert_eq!(churn.median_lifetime(), Some(Duration::from_secs(40)));
+    }
+
+    #[test]
+    fn stability_compares_last_two_intervals() {
+        let start = Instant::now();
+        let mut churn = Churn::new(start);
+        let (a, b, c) = (PeerId::random(), PeerId::random(), PeerId::random());
+
+        churn.on_connection_established(a, start);
+        churn.on_connection_established(b, start);
+        assert_eq!(churn.stability(), None);
+
+        // Second interval: `b` leaves, `c` joins.
+        churn.on_connection_closed(b, 0, MINUTE, start + MINUTE);
+        churn.on_connection_established(c, start + MINUTE);
+        churn.advance(start + MINUTE * 2);
+        // {a, b} vs. {a, b, c}, as `b` was connected at the start of the second interval.
+        assert_eq!(churn.stability(), Some(2.0 / 3.0));
+
+        // No events for a while: the intervals saw the connected peers {a, c} only.
+        churn.advance(start + MINUTE * 10);
+        assert_eq!(churn.stability(), Some(1.0));
+    }
+}
@@ -18,11 +18,30 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
+use libp2p_identity::PeerId;
+use prometheus_client::collector::Collector;
+use prometheus_client::encoding::{
+    DescriptorEncoder, EncodeLabelSet, EncodeLabelValue, EncodeMetric,
+};
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::ConstGauge;
 use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::metrics::MetricType;
 use prometheus_client::registry::{Registry, Unit};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+/// Number of peers and key prefixes whose inbound requests are counted, the least
+/// active ones being replaced by new ones.
+const MAX_TRACKED: usize = 128;
+
+/// Number of most active peers and key prefixes exported.
+const MAX_EXPORTED: usize = 10;
+
+/// Number of leading key bytes by which the inbound requests are attributed to keys.
+const KEY_PREFIX_LEN: usize = 8;
 
 pub(crate) struct Metrics {
     query_result_get_record_ok: Counter,
@@ -42,6 +61,7 @@ pub(crate) struct Metrics {
     routing_updated: Family<RoutingUpdated, Counter>,
 
     inbound_requests: Family<InboundRequest, Counter>,
+    inbound_requests_throttled: Family<InboundRequestThrottled, Counter>,
 }
 
 impl Metrics {
@@ -137,6 +157,13 @@ impl Metrics {
             inbound_requests.clone(),
         );
 
+        let inbound_requests_throttled = Family::default();
+        sub_registry.register(
+            "inbound_requests_throttled",
+            "Number of inbound requests dropped due to a rate limit",
+            inbound_requests_throttled.clone(),
+        );
+
         Self {
             query_result_get_record_ok,
             query_result_get_record_error,
@@ -155,6 +182,7 @@ impl Metrics {
             routing_updated,
 
             inbound_requests,
+            inbound_requests_throttled,
         }
     }
 }
@@ -183,7 +211,10 @@ impl super::Recorder<libp2p_kad::Event> for Metrics {
                         Ok(libp2p_kad::GetRecordOk::FoundRecord(_)) => {
                             self.query_result_get_record_ok.inc();
                         }
-                        Ok(libp2p_kad::GetRecordOk::FinishedWithNoAdditionalRecord { .. }) => {}
+                        Ok(
+                            libp2p_kad::GetRecordOk::FinishedWithNoAdditionalRecord { .. }
+                            | libp2p_kad::GetRecordOk::AgreementReached { .. },
+                        ) => {}
                         Err(error) => {
                             self.query_result_get_record_error
                                 .get_or_create(&error.into())
@@ -253,6 +284,20 @@ impl super::Recorder<libp2p_kad::Event> for Metrics {
             libp2p_kad::Event::InboundRequest { request } => {
                 self.inbound_requests.get_or_create(&request.into()).inc();
             }
+            libp2p_kad::Event::InboundRequestThrottled { request, limit, .. } => {
+                self.inbound_requests_throttled
+                    .get_or_create(&InboundRequestThrottled {
+                        request: match request {
+                            libp2p_kad::ThrottledRequest::PutRecord => Request::PutRecord,
+                            libp2p_kad::ThrottledRequest::AddProvider => Request::AddProvider,
+                        },
+                        limit: match limit {
+                            libp2p_kad::RateLimit::PerPeer => RateLimit::PerPeer,
+                            libp2p_kad::RateLimit::Global => RateLimit::Global,
+                        },
+                    })
+                    .inc();
+            }
             _ => {}
         }
     }
@@ -272,7 +317,11 @@ enum QueryType {
     RepublishProvider,
     GetRecord,
     PutRecord,
+    PutRecordPeerFailed,
     RepublishRecord,
+    GetProviderSummary,
+    Crawl,
+    Refresh,
 }
 
 impl From<&libp2p_kad::QueryResult> for QueryResult {
@@ -299,9 +348,21 @@ impl From<&libp2p_kad::QueryResult> for QueryResult {
             libp2p_kad::QueryResult::PutRecord(_) => QueryResult {
                 r#type: QueryType::PutRecord,
             },
+            libp2p_kad::QueryResult::PutRecordPeerFailed(_) => QueryResult {
+                r#type: QueryType::PutRecordPeerFailed,
+            },
             libp2p_kad::QueryResult::RepublishRecord(_) => QueryResult {
                 r#type: QueryType::RepublishRecord,
             },
+            libp2p_kad::QueryResult::GetProviderSummary(_) => QueryResult {
+                r#type: QueryType::GetProviderSummary,
+            },
+            libp2p_kad::QueryResult::Crawl(_) => QueryResult {
+                r#type: QueryType::Crawl,
+            },
+            libp2p_kad::QueryResult::Refresh(_) => QueryResult {
+                r#type: QueryType::Refresh,
+            },
         }
     }
 }
@@ -362,6 +423,7 @@ struct GetProvidersResult {
 #[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Debug)]
 enum GetProvidersError {
     Timeout,
+    QuorumFailed,
 }
 
 impl From<&libp2p_kad::GetProvidersError> for GetProvidersResult {
@@ -370,6 +432,9 @@ impl From<&libp2p_kad::GetProvidersError> for GetProvidersResult {
             libp2p_kad::GetProvidersError::Timeout { .. } => GetProvidersResult {
                 error: GetProvidersError::Timeout,
             },
+            libp2p_kad::GetProvidersError::QuorumFailed { .. } => GetProvidersResult {
+                error: GetProvidersError::QuorumFailed,
+            },
         }
     }
 }
@@ -401,11 +466,26 @@ impl From<&libp2p_kad::InboundRequest> for InboundRequest {
                 libp2p_kad::InboundRequest::AddProvider { .. } => Request::AddProvider,
                 libp2p_kad::InboundRequest::GetRecord { .. } => Request::GetRecord,
                 libp2p_kad::InboundRequest::PutRecord { .. } => Request::PutRecord,
+                libp2p_kad::InboundRequest::GetProviderSummary { .. } => {
+                    Request::GetProviderSummary
+                }
             },
         }
     }
 }
 
+#[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
+struct InboundRequestThrottled {
+    request: Request,
+    limit: RateLimit,
+}
+
+#[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Debug)]
+enum RateLimit {
+    PerPeer,
+    Global,
+}
+
 #[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Debug)]
 enum Request {
     FindNode,
@@ -413,4 +493,191 @@ enum Request {
     AddProvider,
     GetRecord,
     PutRecord,
+    GetProviderSummary,
+}
+
+/// Per-request metrics of the inbound requests answered by a Kademlia
+/// [`Behaviour`](libp2p_kad::Behaviour), see [`register_kad_inbound_requests`](crate::register_kad_inbound_requests).
+#[derive(Clone)]
+pub struct InboundRequests {
+    responses: Family<InboundResponse, Counter>,
+    top: Arc<Mutex<TopRequesters>>,
+}
+
+impl InboundRequests {
+    pub(crate) fn new(registry: &mut Registry) -> Self {
+        let sub_registry = registry.sub_registry_with_prefix("kad");
+
+        let responses = Family::default();
+        sub_registry.register(
+            "inbound_request_responses",
+            "Number of answered inbound requests by whether they were served from the local store",
+            responses.clone(),
+        );
+
+        let top = Arc::new(Mutex::new(TopRequesters {
+            peers: TopN::default(),
+            key_prefixes: TopN::default(),
+        }));
+        sub_registry.register_collector(Box::new(TopRequestersCollector(top.clone())));
+
+        Self { responses, top }
+    }
+}
+
+impl libp2p_kad::InboundRequestObserver for InboundRequests {
+    fn on_inbound_request(&self, info: &libp2p_kad::InboundRequestInfo) {
+        self.responses
+            .get_or_create(&InboundResponse {
+                request: match info.request {
+                    libp2p_kad::InboundRequestType::FindNode => Request::FindNode,
+                    libp2p_kad::InboundRequestType::GetRecord => Request::GetRecord,
+                    libp2p_kad::InboundRequestType::GetProviders => Request::GetProvider,
+                    libp2p_kad::InboundRequestType::PutRecord => Request::PutRecord,
+                    libp2p_kad::InboundRequestType::AddProvider => Request::AddProvider,
+                },
+                served_from: if info.served_from_store {
+                    ServedFrom::Store
+                } else {
+                    ServedFrom::CloserPeers
+                },
+            })
+            .inc();
+
+        let mut top = self.top.lock().expect("lock not to be poisoned");
+        top.peers.increment(info.peer);
+        let key_prefix = info
+            .key_prefix(KEY_PREFIX_LEN)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        top.key_prefixes.increment(key_prefix);
+    }
+}
+
+#[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
+struct InboundResponse {
+    request: Request,
+    served_from: ServedFrom,
+}
+
+#[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Debug)]
+enum ServedFrom {
+    Store,
+    CloserPeers,
+}
+
+#[derive(Debug)]
+struct TopRequesters {
+    peers: TopN<PeerId>,
+    key_prefixes: TopN<String>,
+}
+
+/// Approximate counts of the most frequent items, keeping at most [`MAX_TRACKED`] items.
+///
+/// A new item replaces the least frequent one and inherits its count, overestimating the
+/// count of the new item but never underestimating that of a frequent one.
+#[derive(Debug)]
+struct TopN<T> {
+    counts: HashMap<T, u64>,
+}
+
+impl<T> Default for TopN<T> {
+    fn default() -> Self {
+        Self {
+            counts: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> TopN<T> {
+    fn increment(&mut self, item: T) {
+        if let Some(count) = self.counts.get_mut(&item) {
+            *count += 1;
+            return;
+        }
+        let mut count = 0;
+        if self.counts.len() >= MAX_TRACKED {
+            let least = self
+                .counts
+                .iter()
+                .min_by_key(|(_, count)| **count)
+                .map(|(item, count)| (item.clone(), *count))
+                .expect("tracked items not to be empty");
+            self.counts.remove(&least.0);
+            count = least.1;
+        }
+        self.counts.insert(item, count + 1);
+    }
+
+    /// Returns the [`MAX_EXPORTED`] most frequent items, most frequent first.
+    fn top(&self) -> Vec<(&T, u64)> {
+        let mut top = self
+            .counts
+            .iter()
+            .map(|(item, count)| (item, *count))
+            .collect::<Vec<_>>();
+        top.sort_unstable_by_key(|(_, count)| std::cmp::Reverse(*count));
+        top.truncate(MAX_EXPORTED);
+        top
+    }
+}
+
+#[derive(Debug)]
+struct TopRequestersCollector(Arc<Mutex<TopRequesters>>);
+
+impl Collector for TopRequestersCollector {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), std::fmt::Error> {
+        let top = self.0.lock().expect("lock not to be poisoned");
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "inbound_requests_top_peers",
+                "Approximate number of answered inbound requests of the most active peers",
+                None,
+                MetricType::Gauge,
+            )?;
+            for (peer, count) in top.peers.top() {
+                let labels = [("peer", peer.to_string())];
+                let metric_encoder = family_encoder.encode_family(&labels)?;
+                ConstGauge::new(count as i64).encode(metric_encoder)?;
+            }
+        }
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "inbound_requests_top_key_prefixes",
+                "Approximate number of answered inbound requests for the most requested key prefixes",
+                None,
+                MetricType::Gauge,
+            )?;
+            for (prefix, count) in top.key_prefixes.top() {
+                let labels = [("key_prefix", prefix.clone())];
+                let metric_encoder = family_encoder.encode_family(&labels)?;
+                ConstGauge::new(count as i64).encode(metric_encoder)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_n_keeps_frequent_items() {
+        let mut top = TopN::default();
+        for _ in 0..10 {
+            top.increment(u64::MAX);
+        }
+        for i in 0..MAX_TRACKED as u64 * 2 {
+            top.increment(i);
+        }
+
+        assert_eq!(top.counts.len(), MAX_TRACKED);
+        assert_eq!(top.top()[0], (&u64::MAX, 10));
+        assert_eq!(top.top().len(), MAX_EXPORTED);
+    }
 }
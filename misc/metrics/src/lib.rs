@@ -28,6 +28,7 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
 mod bandwidth;
+mod churn;
 #[cfg(feature = "dcutr")]
 mod dcutr;
 #[cfg(feature = "gossipsub")]
@@ -41,9 +42,13 @@ mod ping;
 mod protocol_stack;
 #[cfg(feature = "relay")]
 mod relay;
+#[cfg(feature = "request-response")]
+mod request_response;
 mod swarm;
 
 pub use bandwidth::Transport as BandwidthTransport;
+#[cfg(feature = "kad")]
+pub use kad::InboundRequests as KadInboundRequests;
 pub use prometheus_client::registry::Registry;
 
 /// Set of Swarm and protocol metrics derived from emitted events.
@@ -60,6 +65,8 @@ pub struct Metrics {
     ping: ping::Metrics,
     #[cfg(feature = "relay")]
     relay: relay::Metrics,
+    #[cfg(feature = "request-response")]
+    request_response: request_response::Metrics,
     swarm: swarm::Metrics,
 }
 
@@ -87,11 +94,69 @@ impl Metrics {
             ping: ping::Metrics::new(sub_registry),
             #[cfg(feature = "relay")]
             relay: relay::Metrics::new(sub_registry),
+            #[cfg(feature = "request-response")]
+            request_response: request_response::Metrics::new(sub_registry),
             swarm: swarm::Metrics::new(sub_registry),
         }
     }
 }
 
+/// Registers the saturation of the internal channels of a [`Swarm`](libp2p_swarm::Swarm),
+/// i.e. how often and how long its senders waited for capacity, by channel.
+///
+/// ```
+/// use prometheus_client::registry::Registry;
+/// use libp2p_swarm::ChannelStats;
+/// let mut registry = Registry::default();
+/// // Obtained via `Swarm::channel_stats`.
+/// let stats = ChannelStats::default();
+/// libp2p_metrics::register_channel_stats(&mut registry, stats);
+/// ```
+pub fn register_channel_stats(registry: &mut Registry, stats: libp2p_swarm::ChannelStats) {
+    registry
+        .sub_registry_with_prefix("libp2p")
+        .sub_registry_with_prefix("swarm")
+        .register_collector(Box::new(swarm::Channels(stats)));
+}
+
+/// Registers the number and age of the pending connections of a [`Swarm`](libp2p_swarm::Swarm),
+/// i.e. the connections being dialed or upgraded, by direction.
+///
+/// ```
+/// use prometheus_client::registry::Registry;
+/// use libp2p_swarm::PendingStats;
+/// let mut registry = Registry::default();
+/// // Obtained via `Swarm::pending_stats`.
+/// let stats = PendingStats::default();
+/// libp2p_metrics::register_pending_stats(&mut registry, stats);
+/// ```
+pub fn register_pending_stats(registry: &mut Registry, stats: libp2p_swarm::PendingStats) {
+    registry
+        .sub_registry_with_prefix("libp2p")
+        .sub_registry_with_prefix("swarm")
+        .register_collector(Box::new(swarm::Pending(stats)));
+}
+
+/// Registers per-request metrics of the inbound requests answered by a Kademlia
+/// [`Behaviour`](libp2p_kad::Behaviour), attributing them to the most active peers and the
+/// most requested key prefixes.
+///
+/// The returned observer is to be set via
+/// [`Config::set_inbound_request_observer`](libp2p_kad::Config::set_inbound_request_observer).
+///
+/// ```
+/// use prometheus_client::registry::Registry;
+/// let mut registry = Registry::default();
+/// let mut config = libp2p_kad::Config::new(libp2p_kad::PROTOCOL_NAME);
+/// config.set_inbound_request_observer(libp2p_metrics::register_kad_inbound_requests(
+///     &mut registry,
+/// ));
+/// ```
+#[cfg(feature = "kad")]
+pub fn register_kad_inbound_requests(registry: &mut Registry) -> KadInboundRequests {
+    kad::InboundRequests::new(registry.sub_registry_with_prefix("libp2p"))
+}
+
 /// Recorder that can record Swarm and protocol events.
 pub trait Recorder<Event> {
     /// Record the given event.
@@ -140,6 +205,22 @@ impl Recorder<libp2p_relay::Event> for Metrics {
     }
 }
 
+/// Records the [`Event::InboundRequestTiming`](libp2p_request_response::Event::InboundRequestTiming)
+/// and [`Event::OutboundRequestTiming`](libp2p_request_response::Event::OutboundRequestTiming)
+/// events, emitted if enabled via
+/// [`Config::with_timing_events`](libp2p_request_response::Config::with_timing_events).
+#[cfg(feature = "request-response")]
+impl<TRequest, TResponse, TChannelResponse>
+    Recorder<libp2p_request_response::Event<TRequest, TResponse, TChannelResponse>> for Metrics
+{
+    fn record(
+        &self,
+        event: &libp2p_request_response::Event<TRequest, TResponse, TChannelResponse>,
+    ) {
+        self.request_response.record(event)
+    }
+}
+
 impl<TBvEv> Recorder<libp2p_swarm::SwarmEvent<TBvEv>> for Metrics {
     fn record(&self, event: &libp2p_swarm::SwarmEvent<TBvEv>) {
         self.swarm.record(event);
@@ -0,0 +1,129 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::{Registry, Unit};
+use std::time::Duration;
+
+pub(crate) struct Metrics {
+    inbound_request_duration: Family<InboundLabels, Histogram>,
+    outbound_request_duration: Family<OutboundLabels, Histogram>,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &mut Registry) -> Self {
+        let sub_registry = registry.sub_registry_with_prefix("request_response");
+
+        let inbound_request_duration: Family<_, _> =
+            Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.001, 2.0, 15)));
+        sub_registry.register_with_unit(
+            "inbound_request_duration",
+            "Time spent in the phases of an inbound request whose response has been sent",
+            Unit::Seconds,
+            inbound_request_duration.clone(),
+        );
+
+        let outbound_request_duration: Family<_, _> =
+            Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.001, 2.0, 15)));
+        sub_registry.register_with_unit(
+            "outbound_request_duration",
+            "Time spent in the phases of an outbound request whose response has been received",
+            Unit::Seconds,
+            outbound_request_duration.clone(),
+        );
+
+        Self {
+            inbound_request_duration,
+            outbound_request_duration,
+        }
+    }
+
+    fn observe_inbound(&self, protocol: &str, phase: InboundPhase, duration: Duration) {
+        self.inbound_request_duration
+            .get_or_create(&InboundLabels {
+                protocol: protocol.to_owned(),
+                phase,
+            })
+            .observe(duration.as_secs_f64());
+    }
+
+    fn observe_outbound(&self, protocol: &str, phase: OutboundPhase, duration: Duration) {
+        self.outbound_request_duration
+            .get_or_create(&OutboundLabels {
+                protocol: protocol.to_owned(),
+                phase,
+            })
+            .observe(duration.as_secs_f64());
+    }
+}
+
+impl<TRequest, TResponse, TChannelResponse>
+    super::Recorder<libp2p_request_response::Event<TRequest, TResponse, TChannelResponse>>
+    for Metrics
+{
+    fn record(
+        &self,
+        event: &libp2p_request_response::Event<TRequest, TResponse, TChannelResponse>,
+    ) {
+        match event {
+            libp2p_request_response::Event::InboundRequestTiming { timing, .. } => {
+                self.observe_inbound(&timing.protocol, InboundPhase::Queued, timing.queued);
+                self.observe_inbound(&timing.protocol, InboundPhase::Sending, timing.sending);
+            }
+            libp2p_request_response::Event::OutboundRequestTiming { timing, .. } => {
+                self.observe_outbound(
+                    &timing.protocol,
+                    OutboundPhase::Negotiation,
+                    timing.negotiation,
+                );
+                self.observe_outbound(&timing.protocol, OutboundPhase::Sending, timing.sending);
+                self.observe_outbound(&timing.protocol, OutboundPhase::Remote, timing.remote);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
+struct InboundLabels {
+    protocol: String,
+    phase: InboundPhase,
+}
+
+#[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Debug)]
+enum InboundPhase {
+    Queued,
+    Sending,
+}
+
+#[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
+struct OutboundLabels {
+    protocol: String,
+    phase: OutboundPhase,
+}
+
+#[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Debug)]
+enum OutboundPhase {
+    Negotiation,
+    Sending,
+    Remote,
+}
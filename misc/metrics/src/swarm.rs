@@ -21,13 +21,19 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+use crate::churn::{Churn, ChurnCollector};
 use crate::protocol_stack;
 use instant::Instant;
-use libp2p_swarm::{ConnectionId, DialError, SwarmEvent};
-use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
-use prometheus_client::metrics::counter::Counter;
+use libp2p_swarm::{ChannelStats, ConnectionId, DialError, PendingStats, SwarmEvent};
+use prometheus_client::collector::Collector;
+use prometheus_client::encoding::{
+    DescriptorEncoder, EncodeLabelSet, EncodeLabelValue, EncodeMetric,
+};
+use prometheus_client::metrics::counter::{ConstCounter, Counter};
 use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::ConstGauge;
 use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::metrics::MetricType;
 use prometheus_client::registry::{Registry, Unit};
 
 pub(crate) struct Metrics {
@@ -52,6 +58,7 @@ pub(crate) struct Metrics {
     outgoing_connection_error: Family<OutgoingConnectionErrorLabels, Counter>,
 
     connections: Arc<Mutex<HashMap<ConnectionId, Instant>>>,
+    churn: Arc<Mutex<Churn>>,
 }
 
 impl Metrics {
@@ -165,6 +172,9 @@ impl Metrics {
             connections_duration.clone(),
         );
 
+        let churn = Arc::new(Mutex::new(Churn::new(Instant::now())));
+        sub_registry.register_collector(Box::new(ChurnCollector(churn.clone())));
+
         Self {
             connections_incoming,
             connections_incoming_error,
@@ -181,6 +191,7 @@ impl Metrics {
             connections_establishment_duration,
             connections_duration,
             connections: Default::default(),
+            churn,
         }
     }
 }
@@ -190,6 +201,7 @@ impl<TBvEv> super::Recorder<SwarmEvent<TBvEv>> for Metrics {
         match event {
             SwarmEvent::Behaviour(_) => {}
             SwarmEvent::ConnectionEstablished {
+                peer_id,
                 endpoint,
                 established_in: time_taken,
                 connection_id,
@@ -207,10 +219,16 @@ impl<TBvEv> super::Recorder<SwarmEvent<TBvEv>> for Metrics {
                     .lock()
                     .expect("lock not to be poisoned")
                     .insert(*connection_id, Instant::now());
+                self.churn
+                    .lock()
+                    .expect("lock not to be poisoned")
+                    .on_connection_established(*peer_id, Instant::now());
             }
             SwarmEvent::ConnectionClosed {
+                peer_id,
                 endpoint,
                 connection_id,
+                num_established,
                 cause,
                 ..
             } => {
@@ -221,15 +239,20 @@ impl<TBvEv> super::Recorder<SwarmEvent<TBvEv>> for Metrics {
                     },
                     cause: cause.as_ref().map(Into::into),
                 };
-                self.connections_duration.get_or_create(&labels).observe(
-                    self.connections
-                        .lock()
-                        .expect("lock not to be poisoned")
-                        .remove(connection_id)
-                        .expect("closed connection to previously be established")
-                        .elapsed()
-                        .as_secs_f64(),
-                );
+                let lifetime = self
+                    .connections
+                    .lock()
+                    .expect("lock not to be poisoned")
+                    .remove(connection_id)
+                    .expect("closed connection to previously be established")
+                    .elapsed();
+                self.connections_duration
+                    .get_or_create(&labels)
+                    .observe(lifetime.as_secs_f64());
+                self.churn
+                    .lock()
+                    .expect("lock not to be poisoned")
+                    .on_connection_closed(*peer_id, *num_established, lifetime, Instant::now());
             }
             SwarmEvent::IncomingConnection { send_back_addr, .. } => {
                 self.connections_incoming
@@ -446,3 +469,109 @@ impl From<&libp2p_swarm::ListenError> for IncomingConnectionError {
         }
     }
 }
+
+/// Exports the [`ChannelStats`] of a [`Swarm`](libp2p_swarm::Swarm).
+#[derive(Debug)]
+pub(crate) struct Channels(pub(crate) ChannelStats);
+
+impl Collector for Channels {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), std::fmt::Error> {
+        let channels = [
+            ("notify_handler", self.0.notify_handler()),
+            ("connection_events", self.0.connection_events()),
+            (
+                "pending_connection_events",
+                self.0.pending_connection_events(),
+            ),
+        ];
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "channel_blocked",
+                "Number of times a sender had to wait for capacity in an internal channel",
+                None,
+                MetricType::Counter,
+            )?;
+            for (channel, saturation) in channels {
+                let labels = [("channel", channel)];
+                let metric_encoder = family_encoder.encode_family(&labels)?;
+                ConstCounter::new(saturation.num_blocked).encode(metric_encoder)?;
+            }
+        }
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "channel_blocked_duration",
+                "Time senders spent waiting for capacity in an internal channel",
+                Some(&Unit::Seconds),
+                MetricType::Counter,
+            )?;
+            for (channel, saturation) in channels {
+                let labels = [("channel", channel)];
+                let metric_encoder = family_encoder.encode_family(&labels)?;
+                ConstCounter::new(saturation.blocked_duration.as_secs_f64())
+                    .encode(metric_encoder)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Exports the [`PendingStats`] of a [`Swarm`](libp2p_swarm::Swarm).
+#[derive(Debug)]
+pub(crate) struct Pending(pub(crate) PendingStats);
+
+impl Collector for Pending {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), std::fmt::Error> {
+        let directions = [
+            ("outgoing", self.0.outgoing()),
+            ("incoming", self.0.incoming()),
+        ];
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "pending_connections",
+                "Number of connections being dialed or upgraded",
+                None,
+                MetricType::Gauge,
+            )?;
+            for (direction, pending) in directions {
+                let labels = [("direction", direction)];
+                let metric_encoder = family_encoder.encode_family(&labels)?;
+                ConstGauge::new(pending.num_pending as i64).encode(metric_encoder)?;
+            }
+        }
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "pending_connections_oldest_age",
+                "Time the oldest connection being dialed or upgraded has been pending for",
+                Some(&Unit::Seconds),
+                MetricType::Gauge,
+            )?;
+            for (direction, pending) in directions {
+                let labels = [("direction", direction)];
+                let metric_encoder = family_encoder.encode_family(&labels)?;
+                let age = pending.oldest_age.unwrap_or_default();
+                ConstGauge::new(age.as_secs_f64()).encode(metric_encoder)?;
+            }
+        }
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "pending_connections_denied",
+                "Number of connections denied as the limit of pending connections was reached",
+                None,
+                MetricType::Counter,
+            )?;
+            for (direction, pending) in directions {
+                let labels = [("direction", direction)];
+                let metric_encoder = family_encoder.encode_family(&labels)?;
+                ConstCounter::new(pending.num_denied).encode(metric_encoder)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,490 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Warm standby connections to critical peers.
+//!
+//! Latency-critical applications cannot afford to establish a connection at the moment they
+//! need to send a request. The [`Behaviour`] pre-establishes a configurable number of
+//! connections to each of a set of peers and keeps them alive. Connections that close or are
+//! reported as unhealthy via [`Behaviour::report_unhealthy`] are replaced automatically, with
+//! an exponential backoff between failed dials.
+//!
+//! A peer is _ready_ once all of its standby connections are established, see
+//! [`Behaviour::is_ready`] and [`Event::Ready`].
+//!
+//! > **Note**: The connections are only kept alive by the local node. The remote peers
+//! > may still close them, e.g. due to their idle connection timeout, in which case they
+//! > are replaced.
+
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+mod handler;
+
+pub use handler::Handler;
+
+use futures::FutureExt;
+use futures_timer::Delay;
+use instant::Instant;
+use libp2p_core::{Endpoint, Multiaddr};
+use libp2p_identity::PeerId;
+use libp2p_swarm::{
+    behaviour::{ConnectionClosed, ConnectionEstablished, DialFailure},
+    dial_opts::{DialOpts, PeerCondition},
+    CloseConnection, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, NotifyHandler,
+    THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::num::NonZeroUsize;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// The configuration of a [`Behaviour`].
+#[derive(Debug, Clone)]
+pub struct Config {
+    connections_per_peer: NonZeroUsize,
+    health_check_interval: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Config {
+    /// Sets the number of connections to keep to each standby peer.
+    ///
+    /// Defaults to 1.
+    pub fn with_connections_per_peer(mut self, connections: NonZeroUsize) -> Self {
+        self.connections_per_peer = connections;
+        self
+    }
+
+    /// Sets the interval in which missing connections are detected and re-dialed,
+    /// if not already replaced right away.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn with_health_check_interval(mut self, interval: Duration) -> Self {
+        self.health_check_interval = interval;
+        self
+    }
+
+    /// Sets the backoff after the first failed dial of a peer, which doubles with every
+    /// subsequent failure up to the given maximum.
+    ///
+    /// Defaults to 1 second, doubling up to 1 minute.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            connections_per_peer: NonZeroUsize::MIN,
+            health_check_interval: Duration::from_secs(10),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+/// The standby state of a peer.
+struct StandbyPeer {
+    addresses: Vec<Multiaddr>,
+    /// The healthy connections to the peer.
+    connections: HashSet<ConnectionId>,
+    /// The connections reported unhealthy that are being closed.
+    unhealthy: HashSet<ConnectionId>,
+    /// The dials for new connections in progress.
+    pending_dials: HashSet<ConnectionId>,
+    ready: bool,
+    /// The backoff applied after the next failed dial.
+    backoff: Duration,
+    /// No new connections are dialed before this instant.
+    next_dial: Instant,
+}
+
+/// A [`NetworkBehaviour`] that keeps warm standby connections to a set of peers.
+///
+/// See the [crate documentation](crate) for details.
+pub struct Behaviour {
+    config: Config,
+
+    peers: HashMap<PeerId, StandbyPeer>,
+    /// All established connections, also to peers that are not standby peers.
+    connected: HashMap<PeerId, HashSet<ConnectionId>>,
+
+    events: VecDeque<ToSwarm<Event, THandlerInEvent<Self>>>,
+    health_check: Delay,
+    /// Fires at the end of the earliest backoff of a peer with missing connections.
+    retry: Option<(Instant, Delay)>,
+    waker: Option<Waker>,
+}
+
+impl Behaviour {
+    pub fn new(config: Config) -> Self {
+        Self {
+            health_check: Delay::new(config.health_check_interval),
+            config,
+            peers: Default::default(),
+            connected: Default::default(),
+            events: Default::default(),
+            retry: None,
+            waker: None,
+        }
+    }
+
+    /// Adds a peer to keep standby connections to.
+    ///
+    /// If the peer is already a standby peer, its addresses are replaced. If no addresses
+    /// are given, the addresses of the peer are looked up through the other behaviours
+    /// when dialing.
+    pub fn add_peer(&mut self, peer_id: PeerId, addresses: Vec<Multiaddr>) {
+        if let Some(peer) = self.peers.get_mut(&peer_id) {
+            peer.addresses = addresses;
+            return;
+        }
+
+        // Adopt the connections that are already established.
+        let connections = self.connected.get(&peer_id).cloned().unwrap_or_default();
+        for connection in &connections {
+            self.events.push_back(ToSwarm::NotifyHandler {
+                peer_id,
+                handler: NotifyHandler::One(*connection),
+                event: true,
+            });
+        }
+        self.peers.insert(
+            peer_id,
+            StandbyPeer {
+                addresses,
+                connections,
+                unhealthy: HashSet::new(),
+                pending_dials: HashSet::new(),
+                ready: false,
+                backoff: self.config.initial_backoff,
+                next_dial: Instant::now(),
+            },
+        );
+        self.update_readiness(peer_id);
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Removes a standby peer, returning whether it was one.
+    ///
+    /// Its connections are no longer kept alive but are not closed either.
+    pub fn remove_peer(&mut self, peer_id: &PeerId) -> bool {
+        let Some(peer) = self.peers.remove(peer_id) else {
+            return false;
+        };
+        for connection in peer.connections.into_iter().chain(peer.unhealthy) {
+            self.events.push_back(ToSwarm::NotifyHandler {
+                peer_id: *peer_id,
+                handler: NotifyHandler::One(connection),
+                event: false,
+            });
+        }
+
+        true
+    }
+
+    /// Returns the standby peers.
+    pub fn peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.peers.keys()
+    }
+
+    /// Returns whether all standby connections to the peer are established.
+    pub fn is_ready(&self, peer_id: &PeerId) -> bool {
+        self.peers.get(peer_id).is_some_and(|peer| peer.ready)
+    }
+
+    /// Returns whether all standby connections to all standby peers are established.
+    pub fn all_ready(&self) -> bool {
+        self.peers.values().all(|peer| peer.ready)
+    }
+
+    /// Returns the number of healthy connections to the peer.
+    pub fn num_connections(&self, peer_id: &PeerId) -> usize {
+        self.peers
+            .get(peer_id)
+            .map_or(0, |peer| peer.connections.len())
+    }
+
+    /// Reports a connection to a standby peer as unhealthy, e.g. because a ping timed out.
+    ///
+    /// The connection is closed and replaced by a new one. Returns whether the connection
+    /// is a connection to a standby peer.
+    pub fn report_unhealthy(&mut self, peer_id: PeerId, connection: ConnectionId) -> bool {
+        let Some(peer) = self.peers.get_mut(&peer_id) else {
+            return false;
+        };
+        if !peer.connections.remove(&connection) {
+            return false;
+        }
+        tracing::debug!(peer=%peer_id, %connection, "Closing unhealthy standby connection");
+        peer.unhealthy.insert(connection);
+        self.events.push_back(ToSwarm::CloseConnection {
+            peer_id,
+            connection: CloseConnection::One(connection),
+        });
+        self.update_readiness(peer_id);
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+
+        true
+    }
+
+    /// Updates whether the peer is ready, reporting a change via an [`Event`].
+    fn update_readiness(&mut self, peer_id: PeerId) {
+        let Some(peer) = self.peers.get_mut(&peer_id) else {
+            return;
+        };
+        let connections = peer.connections.len();
+        let ready = connections >= self.config.connections_per_peer.get();
+        match (peer.ready, ready) {
+            (false, true) => self
+                .events
+                .push_back(ToSwarm::GenerateEvent(Event::Ready { peer_id })),
+            (true, false) => self
+                .events
+                .push_back(ToSwarm::GenerateEvent(Event::Degraded {
+                    peer_id,
+                    connections,
+                })),
+            _ => {}
+        }
+        peer.ready = ready;
+    }
+
+    /// Dials the missing connections of all standby peers that are not backing off.
+    ///
+    /// Returns the earliest end of a backoff of a peer with missing connections.
+    fn dial_missing_connections(&mut self) -> Option<Instant> {
+        let now = Instant::now();
+        let mut next_retry = None;
+        for (peer_id, peer) in &mut self.peers {
+            let established = peer.connections.len() + peer.pending_dials.len();
+            let missing = self
+                .config
+                .connections_per_peer
+                .get()
+                .saturating_sub(established);
+            if missing == 0 {
+                continue;
+            }
+            if now < peer.next_dial {
+                next_retry =
+                    Some(next_retry.map_or(peer.next_dial, |t: Instant| t.min(peer.next_dial)));
+                continue;
+            }
+            for _ in 0..missing {
+                let opts = if peer.addresses.is_empty() {
+                    DialOpts::peer_id(*peer_id)
+                        .condition(PeerCondition::Always)
+                        .build()
+                } else {
+                    DialOpts::peer_id(*peer_id)
+                        .addresses(peer.addresses.clone())
+                        .condition(PeerCondition::Always)
+                        .build()
+                };
+                peer.pending_dials.insert(opts.connection_id());
+                self.events.push_back(ToSwarm::Dial { opts });
+            }
+        }
+
+        next_retry
+    }
+
+    fn on_connection_established(
+        &mut self,
+        ConnectionEstablished {
+            peer_id,
+            connection_id,
+            ..
+        }: ConnectionEstablished,
+    ) {
+        self.connected
+            .entry(peer_id)
+            .or_default()
+            .insert(connection_id);
+
+        let Some(peer) = self.peers.get_mut(&peer_id) else {
+            return;
+        };
+        if peer.pending_dials.remove(&connection_id) {
+            peer.backoff = self.config.initial_backoff;
+        }
+        peer.connections.insert(connection_id);
+        self.update_readiness(peer_id);
+    }
+
+    fn on_connection_closed(
+        &mut self,
+        ConnectionClosed {
+            peer_id,
+            connection_id,
+            ..
+        }: ConnectionClosed,
+    ) {
+        if let Some(connections) = self.connected.get_mut(&peer_id) {
+            connections.remove(&connection_id);
+            if connections.is_empty() {
+                self.connected.remove(&peer_id);
+            }
+        }
+
+        let Some(peer) = self.peers.get_mut(&peer_id) else {
+            return;
+        };
+        peer.unhealthy.remove(&connection_id);
+        if peer.connections.remove(&connection_id) {
+            tracing::debug!(peer=%peer_id, connection=%connection_id, "Standby connection closed, replacing it");
+        }
+        self.update_readiness(peer_id);
+    }
+
+    fn on_dial_failure(
+        &mut self,
+        DialFailure {
+            peer_id,
+            connection_id,
+            error,
+        }: DialFailure,
+    ) {
+        let Some(peer_id) = peer_id else {
+            return;
+        };
+        let Some(peer) = self.peers.get_mut(&peer_id) else {
+            return;
+        };
+        if !peer.pending_dials.remove(&connection_id) {
+            return;
+        }
+
+        let retry_in = peer.backoff;
+        tracing::debug!(peer=%peer_id, "Dialing standby connection failed, retrying in {retry_in:?}: {error}");
+        peer.next_dial = Instant::now() + retry_in;
+        peer.backoff = (peer.backoff * 2).min(self.config.max_backoff);
+        self.events
+            .push_back(ToSwarm::GenerateEvent(Event::DialFailed {
+                peer_id,
+                retry_in,
+            }));
+    }
+}
+
+/// The events produced by the [`Behaviour`].
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// All standby connections to the peer are established.
+    Ready { peer_id: PeerId },
+    /// The peer has fewer healthy connections than configured and is no longer ready.
+    ///
+    /// Replacements for the missing connections are dialed.
+    Degraded { peer_id: PeerId, connections: usize },
+    /// Dialing a standby connection to the peer failed.
+    ///
+    /// The dial is retried after the given backoff.
+    DialFailed { peer_id: PeerId, retry_in: Duration },
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = Handler;
+    type ToSwarm = Event;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        peer: PeerId,
+        _: &Multiaddr,
+        _: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(Handler::new(self.peers.contains_key(&peer)))
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        peer: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(Handler::new(self.peers.contains_key(&peer)))
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        void::unreachable(event)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(e) => self.on_connection_established(e),
+            FromSwarm::ConnectionClosed(e) => self.on_connection_closed(e),
+            FromSwarm::DialFailure(e) => self.on_dial_failure(e),
+            _ => {}
+        }
+    }
+
+    #[tracing::instrument(level = "trace", name = "NetworkBehaviour::poll", skip(self, cx))]
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        while self.health_check.poll_unpin(cx).is_ready() {
+            self.health_check.reset(self.config.health_check_interval);
+        }
+
+        if let Some((_, retry)) = self.retry.as_mut() {
+            if retry.poll_unpin(cx).is_ready() {
+                self.retry = None;
+            }
+        }
+
+        if let Some(at) = self.dial_missing_connections() {
+            if self
+                .retry
+                .as_ref()
+                .map_or(true, |(current, _)| at < *current)
+            {
+                let mut retry = Delay::new(at.saturating_duration_since(Instant::now()));
+                let _ = retry.poll_unpin(cx);
+                self.retry = Some((at, retry));
+            }
+        }
+
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(event);
+        }
+
+        self.waker = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_swarm::{Swarm, SwarmEvent};
+use libp2p_swarm_test::SwarmExt;
+use libp2p_warm_standby::{Behaviour, Config, Event};
+use std::num::NonZeroUsize;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+#[async_std::test]
+async fn establishes_standby_connections() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut client = new_swarm(2);
+    let mut server = new_swarm(2);
+    let (address, _) = server.listen().await;
+    let server_id = *server.local_peer_id();
+    async_std::task::spawn(server.loop_on_next());
+
+    client.behaviour_mut().add_peer(server_id, vec![address]);
+    assert!(!client.behaviour().is_ready(&server_id));
+
+    match client.next_behaviour_event().await {
+        Event::Ready { peer_id } => assert_eq!(peer_id, server_id),
+        e => panic!("Unexpected event: {e:?}"),
+    }
+    assert!(client.behaviour().all_ready());
+    assert_eq!(client.behaviour().num_connections(&server_id), 2);
+}
+
+#[async_std::test]
+async fn replaces_unhealthy_connections() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut client = new_swarm(1);
+    let mut server = new_swarm(1);
+    let (address, _) = server.listen().await;
+    let server_id = *server.local_peer_id();
+    async_std::task::spawn(server.loop_on_next());
+
+    client.behaviour_mut().add_peer(server_id, vec![address]);
+    let connection = client
+        .wait(|e| match e {
+            SwarmEvent::ConnectionEstablished { connection_id, .. } => Some(connection_id),
+            _ => None,
+        })
+        .await;
+    client
+        .wait(|e| match e {
+            SwarmEvent::Behaviour(Event::Ready { .. }) => Some(()),
+            _ => None,
+        })
+        .await;
+
+    assert!(client
+        .behaviour_mut()
+        .report_unhealthy(server_id, connection));
+    match client.next_behaviour_event().await {
+        Event::Degraded {
+            peer_id,
+            connections,
+        } => {
+            assert_eq!(peer_id, server_id);
+            assert_eq!(connections, 0);
+        }
+        e => panic!("Unexpected event: {e:?}"),
+    }
+    match client.next_behaviour_event().await {
+        Event::Ready { peer_id } => assert_eq!(peer_id, server_id),
+        e => panic!("Unexpected event: {e:?}"),
+    }
+    assert!(client.is_connected(&server_id));
+}
+
+#[async_std::test]
+async fn backs_off_after_failed_dials() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut client = Swarm::new_ephemeral(|_| {
+        Behaviour::new(
+            Config::default().with_backoff(Duration::from_millis(10), Duration::from_millis(15)),
+        )
+    });
+    let mut server = new_swarm(1);
+    let (address, _) = server.listen().await;
+    // The server is never polled again and thus never accepts the connection.
+    let server_id = *server.local_peer_id();
+    drop(server);
+
+    client.behaviour_mut().add_peer(server_id, vec![address]);
+
+    let mut backoffs = Vec::new();
+    while backoffs.len() < 3 {
+        if let Event::DialFailed { peer_id, retry_in } = client.next_behaviour_event().await {
+            assert_eq!(peer_id, server_id);
+            backoffs.push(retry_in);
+        }
+    }
+    assert_eq!(
+        backoffs,
+        vec![
+            Duration::from_millis(10),
+            Duration::from_millis(15),
+            Duration::from_millis(15)
+        ]
+    );
+}
+
+fn new_swarm(connections_per_peer: usize) -> Swarm<Behaviour> {
+    Swarm::new_ephemeral(|_| {
+        Behaviour::new(
+            Config::default()
+                .with_connections_per_peer(NonZeroUsize::new(connections_per_peer).unwrap()),
+        )
+    })
+}
@@ -20,6 +20,7 @@
 
 mod as_client;
 mod as_server;
+mod audit;
 
 use crate::protocol::{AutoNatCodec, DialRequest, DialResponse, ResponseError};
 use crate::DEFAULT_PROTOCOL_NAME;
@@ -27,6 +28,9 @@ use as_client::AsClient;
 pub use as_client::{OutboundProbeError, OutboundProbeEvent};
 use as_server::AsServer;
 pub use as_server::{InboundProbeError, InboundProbeEvent};
+use audit::AuditLog;
+pub use audit::{AuditRecord, AuditVerdict};
+use futures::channel::mpsc;
 use futures_timer::Delay;
 use instant::Instant;
 use libp2p_core::{multiaddr::Protocol, ConnectedPoint, Endpoint, Multiaddr};
@@ -82,6 +86,15 @@ pub struct Config {
     /// private ip address. Note that this does not apply for servers that are added via
     /// [`Behaviour::add_server`].
     pub only_global_ips: bool,
+    /// Max number of served dial-back requests that are kept in the audit log.
+    /// Records are still passed to subscribers if set to 0.
+    pub audit_log_capacity: usize,
+    /// Number of distinct target IPs a client may request within
+    /// [`Config::scan_detection_period`] before it is reported via [`Event::ScanSuspected`].
+    /// Set to 0 to disable scan detection.
+    pub scan_detection_threshold: usize,
+    /// Period over which the target IPs requested by a client are tracked.
+    pub scan_detection_period: Duration,
 }
 
 impl Default for Config {
@@ -99,6 +112,9 @@ impl Default for Config {
             throttle_clients_peer_max: 3,
             throttle_clients_period: Duration::from_secs(1),
             only_global_ips: true,
+            audit_log_capacity: 1024,
+            scan_detection_threshold: 16,
+            scan_detection_period: Duration::from_secs(60),
         }
     }
 }
@@ -143,6 +159,14 @@ pub enum Event {
         /// New status.
         new: NatStatus,
     },
+    /// A client requested dial-backs to an unusual number of distinct IPs within
+    /// [`Config::scan_detection_period`], which suggests it is using the local peer to scan.
+    ScanSuspected {
+        /// Peer that sent the dial-back requests.
+        peer: PeerId,
+        /// Number of distinct IPs requested within the period.
+        distinct_ips: usize,
+    },
 }
 
 /// [`NetworkBehaviour`] for AutoNAT.
@@ -204,6 +228,9 @@ pub struct Behaviour {
     // Recent probes done for clients
     throttled_clients: Vec<(PeerId, Instant)>,
 
+    // Served dial-back requests.
+    audit_log: AuditLog,
+
     last_probe: Option<Instant>,
 
     pending_actions: VecDeque<ToSwarm<<Self as NetworkBehaviour>::ToSwarm, THandlerInEvent<Self>>>,
@@ -226,6 +253,11 @@ impl Behaviour {
             local_peer_id,
             inner,
             schedule_probe: Delay::new(config.boot_delay),
+            audit_log: AuditLog::new(
+                config.audit_log_capacity,
+                config.scan_detection_threshold,
+                config.scan_detection_period,
+            ),
             config,
             servers: HashSet::new(),
             ongoing_inbound: HashMap::default(),
@@ -285,6 +317,18 @@ impl Behaviour {
         self.as_client().on_new_address();
     }
 
+    /// Dial-back requests served for clients, oldest first.
+    /// Bounded by [`Config::audit_log_capacity`].
+    pub fn audit_log(&self) -> impl Iterator<Item = &AuditRecord> {
+        self.audit_log.records()
+    }
+
+    /// Subscribe to the records of served dial-back requests.
+    /// Records are dropped for a subscriber that does not keep up.
+    pub fn subscribe_audit_log(&mut self) -> mpsc::Receiver<AuditRecord> {
+        self.audit_log.subscribe()
+    }
+
     fn as_client(&mut self) -> AsClient {
         AsClient {
             inner: &mut self.inner,
@@ -311,6 +355,7 @@ impl Behaviour {
             connected: &self.connected,
             probe_id: &mut self.probe_id,
             throttled_clients: &mut self.throttled_clients,
+            audit_log: &mut self.audit_log,
             ongoing_inbound: &mut self.ongoing_inbound,
         }
     }
@@ -439,7 +484,9 @@ impl NetworkBehaviour for Behaviour {
                         | request_response::Event::InboundFailure { .. } => {
                             self.as_server().handle_event(event)
                         }
-                        request_response::Event::ResponseSent { .. } => VecDeque::new(),
+                        request_response::Event::ResponseSent { .. }
+                        | request_response::Event::InboundRequestTiming { .. }
+                        | request_response::Event::OutboundRequestTiming { .. } => VecDeque::new(),
                     };
 
                     self.pending_actions.extend(actions);
@@ -19,6 +19,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use super::{
+    audit::{AuditLog, AuditVerdict},
     Action, AutoNatCodec, Config, DialRequest, DialResponse, Event, HandleInnerEvent, ProbeId,
     ResponseError,
 };
@@ -80,6 +81,7 @@ pub(crate) struct AsServer<'a> {
     pub(crate) connected: &'a HashMap<PeerId, HashMap<ConnectionId, Option<Multiaddr>>>,
     pub(crate) probe_id: &'a mut ProbeId,
     pub(crate) throttled_clients: &'a mut Vec<(PeerId, Instant)>,
+    pub(crate) audit_log: &'a mut AuditLog,
     #[allow(clippy::type_complexity)]
     pub(crate) ongoing_inbound: &'a mut HashMap<
         PeerId,
@@ -108,6 +110,23 @@ impl<'a> HandleInnerEvent for AsServer<'a> {
                     },
             } => {
                 let probe_id = self.probe_id.next();
+                let mut actions = VecDeque::new();
+                if let Some(distinct_ips) = self.audit_log.on_request(
+                    probe_id,
+                    peer,
+                    request.addresses.clone(),
+                    Instant::now(),
+                ) {
+                    tracing::debug!(
+                        %peer,
+                        "Peer requested dial-backs to {} distinct IPs",
+                        distinct_ips
+                    );
+                    actions.push_back(ToSwarm::GenerateEvent(Event::ScanSuspected {
+                        peer,
+                        distinct_ips,
+                    }));
+                }
                 match self.resolve_inbound_request(peer, request) {
                     Ok(addrs) => {
                         tracing::debug!(
@@ -120,7 +139,7 @@ impl<'a> HandleInnerEvent for AsServer<'a> {
                             .insert(peer, (probe_id, request_id, addrs.clone(), channel));
                         self.throttled_clients.push((peer, Instant::now()));
 
-                        VecDeque::from([
+                        actions.extend([
                             ToSwarm::GenerateEvent(Event::InboundProbe(
                                 InboundProbeEvent::Request {
                                     probe_id,
@@ -137,7 +156,8 @@ impl<'a> HandleInnerEvent for AsServer<'a> {
                                     .addresses(addrs)
                                     .build(),
                             },
-                        ])
+                        ]);
+                        actions
                     }
                     Err((status_text, error)) => {
                         tracing::debug!(
@@ -146,19 +166,29 @@ impl<'a> HandleInnerEvent for AsServer<'a> {
                             "Reject inbound dial request from peer"
                         );
 
+                        self.audit_log.on_verdict(
+                            probe_id,
+                            peer,
+                            AuditVerdict::Refused {
+                                error: error.clone(),
+                                status_text: status_text.clone(),
+                            },
+                        );
+
                         let response = DialResponse {
                             result: Err(error.clone()),
                             status_text: Some(status_text),
                         };
                         let _ = self.inner.send_response(channel, response);
 
-                        VecDeque::from([ToSwarm::GenerateEvent(Event::InboundProbe(
+                        actions.push_back(ToSwarm::GenerateEvent(Event::InboundProbe(
                             InboundProbeEvent::Error {
                                 probe_id,
                                 peer,
                                 error: InboundProbeError::Response(error),
                             },
-                        ))])
+                        )));
+                        actions
                     }
                 }
             }
@@ -179,6 +209,8 @@ impl<'a> HandleInnerEvent for AsServer<'a> {
                     }
                     _ => self.probe_id.next(),
                 };
+                self.audit_log
+                    .on_verdict(probe_id, peer, AuditVerdict::InboundFailure);
 
                 VecDeque::from([ToSwarm::GenerateEvent(Event::InboundProbe(
                     InboundProbeEvent::Error {
@@ -213,6 +245,8 @@ impl<'a> AsServer<'a> {
         );
 
         let (probe_id, _, _, channel) = self.ongoing_inbound.remove(peer).unwrap();
+        self.audit_log
+            .on_verdict(probe_id, *peer, AuditVerdict::Reachable(address.clone()));
         let response = DialResponse {
             result: Ok(address.clone()),
             status_text: None,
@@ -245,6 +279,12 @@ impl<'a> AsServer<'a> {
             ),
         };
 
+        self.audit_log.on_verdict(
+            probe_id,
+            peer.expect("PeerId is present."),
+            AuditVerdict::DialFailed,
+        );
+
         let response_error = ResponseError::DialError;
         let response = DialResponse {
             result: Err(response_error.clone()),
@@ -0,0 +1,289 @@
+// Copyright 2021 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Audit log of the dial-back requests served in the server role.
+
+use super::{ProbeId, ResponseError};
+use futures::channel::mpsc;
+use instant::Instant;
+use libp2p_core::{multiaddr::Protocol, Multiaddr};
+use libp2p_identity::PeerId;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::IpAddr,
+    time::Duration,
+};
+
+/// Buffer size of the channel handed out by [`super::Behaviour::subscribe_audit_log`].
+const SUBSCRIBER_BUFFER: usize = 64;
+
+/// Outcome of a dial-back request served by the local peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditVerdict {
+    /// The client was dialed back successfully on the given address.
+    Reachable(Multiaddr),
+    /// The request was refused without dialing the client.
+    Refused {
+        error: ResponseError,
+        status_text: String,
+    },
+    /// None of the addresses of the client could be dialed.
+    DialFailed,
+    /// Receiving the request or sending the response failed.
+    InboundFailure,
+}
+
+/// Entry in the audit log of served dial-back requests.
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+    pub probe_id: ProbeId,
+    /// Peer that sent the dial-back request.
+    pub client: PeerId,
+    /// Addresses as requested by the client, before they were filtered or rewritten.
+    pub requested_addresses: Vec<Multiaddr>,
+    /// Encoded size of the requested addresses in bytes.
+    pub request_bytes: usize,
+    pub verdict: AuditVerdict,
+    /// When the request was received.
+    pub received_at: Instant,
+}
+
+/// Request that was received but for which no verdict is known yet.
+struct PendingRecord {
+    requested_addresses: Vec<Multiaddr>,
+    received_at: Instant,
+}
+
+pub(crate) struct AuditLog {
+    records: VecDeque<AuditRecord>,
+    capacity: usize,
+    pending: HashMap<ProbeId, PendingRecord>,
+    subscribers: Vec<mpsc::Sender<AuditRecord>>,
+
+    scan_threshold: usize,
+    scan_period: Duration,
+    // Target IPs requested by each client within the scan detection period.
+    requested_ips: HashMap<PeerId, VecDeque<(IpAddr, Instant)>>,
+    // Clients that were reported as scanning, and when.
+    reported: HashMap<PeerId, Instant>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(capacity: usize, scan_threshold: usize, scan_period: Duration) -> Self {
+        Self {
+            records: VecDeque::new(),
+            capacity,
+            pending: HashMap::new(),
+            subscribers: Vec::new(),
+            scan_threshold,
+            scan_period,
+            requested_ips: HashMap::new(),
+            reported: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn records(&self) -> impl Iterator<Item = &AuditRecord> {
+        self.records.iter()
+    }
+
+    pub(crate) fn subscribe(&mut self) -> mpsc::Receiver<AuditRecord> {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER);
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Track a newly received dial-back request.
+    ///
+    /// Returns the number of distinct target IPs requested by the client within the scan
+    /// detection period if it reached the threshold and was not reported in that period yet.
+    pub(crate) fn on_request(
+        &mut self,
+        probe_id: ProbeId,
+        client: PeerId,
+        requested_addresses: Vec<Multiaddr>,
+        now: Instant,
+    ) -> Option<usize> {
+        let distinct_ips = self.track_ips(client, &requested_addresses, now);
+        self.pending.insert(
+            probe_id,
+            PendingRecord {
+                requested_addresses,
+                received_at: now,
+            },
+        );
+
+        if self.scan_threshold == 0 || distinct_ips < self.scan_threshold {
+            return None;
+        }
+        match self.reported.get(&client) {
+            Some(at) if now.duration_since(*at) < self.scan_period => None,
+            _ => {
+                self.reported.insert(client, now);
+                Some(distinct_ips)
+            }
+        }
+    }
+
+    /// Record the verdict for a request that was previously passed to [`AuditLog::on_request`].
+    pub(crate) fn on_verdict(&mut self, probe_id: ProbeId, client: PeerId, verdict: AuditVerdict) {
+        let (requested_addresses, received_at) = match self.pending.remove(&probe_id) {
+            Some(pending) => (pending.requested_addresses, pending.received_at),
+            None => (Vec::new(), Instant::now()),
+        };
+        let record = AuditRecord {
+            probe_id,
+            client,
+            request_bytes: requested_addresses.iter().map(|a| a.len()).sum(),
+            requested_addresses,
+            verdict,
+            received_at,
+        };
+
+        self.subscribers
+            .retain_mut(|tx| match tx.try_send(record.clone()) {
+                Ok(()) => true,
+                Err(e) if e.is_full() => {
+                    tracing::debug!("Audit log subscriber is full, dropping record");
+                    true
+                }
+                Err(_) => false,
+            });
+
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    fn track_ips(&mut self, client: PeerId, addresses: &[Multiaddr], now: Instant) -> usize {
+        let period = self.scan_period;
+        self.requested_ips.retain(|_, ips| match ips.back() {
+            Some((_, at)) => now.duration_since(*at) < period,
+            None => false,
+        });
+        self.reported
+            .retain(|_, at| now.duration_since(*at) < period);
+
+        let ips = self.requested_ips.entry(client).or_default();
+        while let Some((_, at)) = ips.front() {
+            if now.duration_since(*at) < period {
+                break;
+            }
+            ips.pop_front();
+        }
+        ips.extend(
+            addresses
+                .iter()
+                .filter_map(|a| match a.iter().next() {
+                    Some(Protocol::Ip4(ip)) => Some(IpAddr::V4(ip)),
+                    Some(Protocol::Ip6(ip)) => Some(IpAddr::V6(ip)),
+                    _ => None,
+                })
+                .map(|ip| (ip, now)),
+        );
+
+        ips.iter().map(|(ip, _)| ip).collect::<HashSet<_>>().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn addr(octet: u8) -> Multiaddr {
+        Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(1, 2, 3, octet)))
+            .with(Protocol::Tcp(4001))
+    }
+
+    #[test]
+    fn records_are_bounded_by_capacity() {
+        let mut log = AuditLog::new(2, 0, Duration::from_secs(60));
+        let client = PeerId::random();
+        let mut probe_id = ProbeId(0);
+
+        for i in 0..3 {
+            let id = probe_id.next();
+            log.on_request(id, client, vec![addr(i)], Instant::now());
+            log.on_verdict(id, client, AuditVerdict::DialFailed);
+        }
+
+        let ids = log.records().map(|r| r.probe_id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![ProbeId(1), ProbeId(2)]);
+        let record = log.records().last().unwrap();
+        assert_eq!(record.requested_addresses, vec![addr(2)]);
+        assert_eq!(record.request_bytes, addr(2).len());
+    }
+
+    #[test]
+    fn subscribers_receive_records() {
+        let mut log = AuditLog::new(0, 0, Duration::from_secs(60));
+        let mut rx = log.subscribe();
+        let client = PeerId::random();
+
+        log.on_request(ProbeId(0), client, vec![addr(1)], Instant::now());
+        log.on_verdict(ProbeId(0), client, AuditVerdict::Reachable(addr(1)));
+
+        let record = rx.try_next().unwrap().unwrap();
+        assert_eq!(record.client, client);
+        assert_eq!(record.verdict, AuditVerdict::Reachable(addr(1)));
+        assert_eq!(log.records().count(), 0);
+
+        drop(rx);
+        log.on_verdict(ProbeId(1), client, AuditVerdict::InboundFailure);
+        assert!(log.subscribers.is_empty());
+    }
+
+    #[test]
+    fn scan_is_reported_once_per_period() {
+        let period = Duration::from_secs(60);
+        let mut log = AuditLog::new(16, 3, period);
+        let client = PeerId::random();
+        let now = Instant::now();
+
+        assert_eq!(
+            log.on_request(ProbeId(0), client, vec![addr(1), addr(1)], now),
+            None
+        );
+        assert_eq!(log.on_request(ProbeId(1), client, vec![addr(2)], now), None);
+        assert_eq!(
+            log.on_request(ProbeId(2), client, vec![addr(3)], now),
+            Some(3)
+        );
+        assert_eq!(log.on_request(ProbeId(3), client, vec![addr(4)], now), None);
+
+        // Other clients are tracked separately.
+        assert_eq!(
+            log.on_request(ProbeId(4), PeerId::random(), vec![addr(5)], now),
+            None
+        );
+
+        // Requests outside of the period are forgotten.
+        let later = now + period;
+        assert_eq!(
+            log.on_request(ProbeId(5), client, vec![addr(1)], later),
+            None
+        );
+    }
+}
@@ -27,8 +27,8 @@ mod protocol;
 
 pub use self::{
     behaviour::{
-        Behaviour, Config, Event, InboundProbeError, InboundProbeEvent, NatStatus,
-        OutboundProbeError, OutboundProbeEvent, ProbeId,
+        AuditRecord, AuditVerdict, Behaviour, Config, Event, InboundProbeError, InboundProbeEvent,
+        NatStatus, OutboundProbeError, OutboundProbeEvent, ProbeId,
     },
     protocol::{ResponseError, DEFAULT_PROTOCOL_NAME},
 };
@@ -19,7 +19,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use libp2p_autonat::{
-    Behaviour, Config, Event, InboundProbeError, InboundProbeEvent, ResponseError,
+    AuditVerdict, Behaviour, Config, Event, InboundProbeError, InboundProbeEvent, ResponseError,
 };
 use libp2p_core::{multiaddr::Protocol, ConnectedPoint, Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
@@ -97,6 +97,7 @@ async fn test_dial_back() {
                 concurrent_dial_errors,
                 established_in: _,
                 connection_id: _,
+                ..
             } => {
                 assert_eq!(peer_id, client_id);
                 assert_eq!(num_established, NonZeroU32::new(2).unwrap());
@@ -175,6 +176,13 @@ async fn test_dial_error() {
         }
         other => panic!("Unexpected behaviour event: {other:?}."),
     }
+
+    let records = server.behaviour().audit_log().collect::<Vec<_>>();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].probe_id, request_probe_id);
+    assert_eq!(records[0].client, client_id);
+    assert_eq!(records[0].verdict, AuditVerdict::DialFailed);
+    assert!(records[0].request_bytes > 0);
 }
 
 #[async_std::test]
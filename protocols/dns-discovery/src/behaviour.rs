@@ -0,0 +1,291 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{Config, Publisher, Update};
+use futures::{future::BoxFuture, FutureExt};
+use futures_timer::Delay;
+use libp2p_core::{multiaddr::Protocol, Endpoint, Multiaddr};
+use libp2p_identity::PeerId;
+use libp2p_swarm::{
+    behaviour::{ExternalAddrConfirmed, ExternalAddrExpired},
+    dummy, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler, THandlerInEvent,
+    THandlerOutEvent, ToSwarm,
+};
+use std::{
+    collections::BTreeSet,
+    task::{Context, Poll, Waker},
+};
+
+/// The maximum length of a single TXT character string.
+const MAX_TXT_STRING_LEN: usize = 255;
+
+/// The events produced by the [`Behaviour`].
+#[derive(Debug)]
+pub enum Event<E> {
+    /// The records have been published.
+    Published { name: String, records: Vec<String> },
+    /// Publishing the records failed. The update is retried after
+    /// [`Config::with_retry_interval`].
+    PublishFailed { name: String, error: E },
+}
+
+/// A [`NetworkBehaviour`] that publishes the external addresses of the local node as
+/// dnsaddr TXT records.
+pub struct Behaviour<P>
+where
+    P: Publisher,
+{
+    config: Config,
+    local_peer_id: PeerId,
+    publisher: P,
+
+    /// The confirmed external addresses of the local node.
+    external_addresses: BTreeSet<Multiaddr>,
+    /// The records that were last published successfully.
+    published: Option<Vec<String>>,
+    /// The update currently in progress, together with the records it publishes.
+    in_flight: Option<(Vec<String>, BoxFuture<'static, Result<(), P::Error>>)>,
+    /// Delays the next update, either for debouncing or after a failure.
+    next_update: Option<Delay>,
+    /// Whether an update is due, i.e. the external addresses changed or a republish was
+    /// requested since the last successful update.
+    update_due: bool,
+
+    waker: Option<Waker>,
+}
+
+impl<P> Behaviour<P>
+where
+    P: Publisher,
+{
+    /// Creates a new [`Behaviour`] publishing the addresses of `local_peer_id` via `publisher`.
+    pub fn new(local_peer_id: PeerId, publisher: P, config: Config) -> Self {
+        Self {
+            config,
+            local_peer_id,
+            publisher,
+            external_addresses: Default::default(),
+            published: None,
+            in_flight: None,
+            next_update: None,
+            update_due: false,
+            waker: None,
+        }
+    }
+
+    /// Returns the records that were last published successfully.
+    pub fn published_records(&self) -> Option<&[String]> {
+        self.published.as_deref()
+    }
+
+    /// Publishes the current records immediately, even if they have not changed.
+    ///
+    /// This can be used to restore records that have been modified externally.
+    /// Nothing is published as long as the local node has no external addresses.
+    pub fn republish(&mut self) {
+        self.published = None;
+        self.next_update = None;
+        self.update_due = true;
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Computes the records for the current set of external addresses.
+    fn records(&self) -> Vec<String> {
+        self.external_addresses
+            .iter()
+            .filter_map(|addr| {
+                let addr = with_peer_id(addr.clone(), self.local_peer_id)?;
+                let record = format!("dnsaddr={addr}");
+
+                if record.len() > MAX_TXT_STRING_LEN {
+                    tracing::warn!(address=%addr, "Address too long for a TXT record, skipping");
+                    return None;
+                }
+
+                Some(record)
+            })
+            .collect()
+    }
+
+    fn schedule_update(&mut self) {
+        self.next_update = Some(Delay::new(self.config.debounce));
+        self.update_due = true;
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Appends the `/p2p` suffix for `peer_id` to `addr`.
+///
+/// Returns `None` if `addr` already ends with a different peer ID.
+fn with_peer_id(mut addr: Multiaddr, peer_id: PeerId) -> Option<Multiaddr> {
+    match addr.iter().last() {
+        Some(Protocol::P2p(p)) if p == peer_id => Some(addr),
+        Some(Protocol::P2p(_)) => None,
+        _ => {
+            addr.push(Protocol::P2p(peer_id));
+            Some(addr)
+        }
+    }
+}
+
+impl<P> NetworkBehaviour for Behaviour<P>
+where
+    P: Publisher,
+{
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = Event<P::Error>;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        let changed = match event {
+            FromSwarm::ExternalAddrConfirmed(ExternalAddrConfirmed { addr }) => {
+                self.external_addresses.insert(addr.clone())
+            }
+            FromSwarm::ExternalAddrExpired(ExternalAddrExpired { addr }) => {
+                self.external_addresses.remove(addr)
+            }
+            _ => false,
+        };
+
+        if changed {
+            self.schedule_update();
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        void::unreachable(event)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        loop {
+            if let Some((records, update)) = self.in_flight.as_mut() {
+                let Poll::Ready(result) = update.poll_unpin(cx) else {
+                    return Poll::Pending;
+                };
+                let records = std::mem::take(records);
+                self.in_flight = None;
+                let name = self.config.record_name();
+
+                return match result {
+                    Ok(()) => {
+                        tracing::debug!(%name, ?records, "Published dnsaddr records");
+                        self.published = Some(records.clone());
+                        Poll::Ready(ToSwarm::GenerateEvent(Event::Published { name, records }))
+                    }
+                    Err(error) => {
+                        tracing::debug!(%name, "Failed to publish dnsaddr records: {error}");
+                        self.next_update = Some(Delay::new(self.config.retry_interval));
+                        self.update_due = true;
+                        Poll::Ready(ToSwarm::GenerateEvent(Event::PublishFailed { name, error }))
+                    }
+                };
+            }
+
+            self.waker = Some(cx.waker().clone());
+
+            if !self.update_due {
+                return Poll::Pending;
+            }
+
+            if let Some(delay) = self.next_update.as_mut() {
+                if delay.poll_unpin(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.next_update = None;
+            }
+
+            self.update_due = false;
+            let records = self.records();
+            if self.published.as_ref() == Some(&records) {
+                return Poll::Pending;
+            }
+            // An empty update removes all records of the name, including those of other
+            // nodes, thus it is only sent to withdraw records published by the local node.
+            if records.is_empty() && self.published.as_ref().map_or(true, Vec::is_empty) {
+                return Poll::Pending;
+            }
+
+            let update = self.publisher.publish(Update {
+                name: self.config.record_name(),
+                records: records.clone(),
+                ttl: self.config.ttl,
+            });
+            self.in_flight = Some((records, update));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_local_peer_id() {
+        let peer_id = PeerId::random();
+        let addr = "/ip4/1.2.3.4/tcp/4001".parse::<Multiaddr>().unwrap();
+
+        let expected = addr.clone().with(Protocol::P2p(peer_id));
+        assert_eq!(with_peer_id(addr, peer_id), Some(expected.clone()));
+        assert_eq!(with_peer_id(expected.clone(), peer_id), Some(expected));
+    }
+
+    #[test]
+    fn skips_address_of_other_peer() {
+        let addr = "/ip4/1.2.3.4/tcp/4001"
+            .parse::<Multiaddr>()
+            .unwrap()
+            .with(Protocol::P2p(PeerId::random()));
+
+        assert_eq!(with_peer_id(addr, PeerId::random()), None);
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Publishing of a node's external addresses via DNS.
+//!
+//! The [`Behaviour`] keeps the [dnsaddr] TXT records of a domain in sync with the
+//! confirmed external addresses of the local node. Whenever an external address is
+//! confirmed or expires, the full set of records is handed to a [`Publisher`], which
+//! performs the actual update, e.g. through an RFC 2136 dynamic update or the API of a
+//! DNS provider.
+//! Nothing is published before the first external address is confirmed, and the records
+//! are only removed once all addresses published by the local node expired.
+//!
+//! Other nodes can then dial `/dnsaddr/<domain>` through `libp2p-dns` to reach the node.
+//!
+//! [dnsaddr]: https://github.com/multiformats/multiaddr/blob/master/protocols/DNSADDR.md
+
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+mod behaviour;
+
+pub use behaviour::{Behaviour, Event};
+
+use futures::future::BoxFuture;
+use std::time::Duration;
+
+/// A set of TXT records that should replace all existing TXT records of a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    /// The fully qualified name of the records, e.g. `_dnsaddr.example.com`.
+    pub name: String,
+    /// The TXT records, e.g. `dnsaddr=/ip4/1.2.3.4/tcp/4001/p2p/12D3KooW...`.
+    ///
+    /// An empty list means that all TXT records of the name should be removed.
+    pub records: Vec<String>,
+    /// The time-to-live of the records.
+    pub ttl: Duration,
+}
+
+/// Performs updates of DNS records on behalf of the [`Behaviour`].
+///
+/// Implementations can talk to an authoritative name server via RFC 2136 dynamic updates or
+/// to the HTTP API of a DNS provider.
+pub trait Publisher: Send + 'static {
+    type Error: std::error::Error + Send + 'static;
+
+    /// Replaces the TXT records of [`Update::name`] with [`Update::records`].
+    ///
+    /// The [`Behaviour`] issues at most one update at a time.
+    fn publish(&mut self, update: Update) -> BoxFuture<'static, Result<(), Self::Error>>;
+}
+
+/// Configuration for the [`Behaviour`].
+#[derive(Debug, Clone)]
+pub struct Config {
+    domain: String,
+    ttl: Duration,
+    debounce: Duration,
+    retry_interval: Duration,
+}
+
+impl Config {
+    /// Creates a new configuration publishing records for `domain`.
+    ///
+    /// The records are published under `_dnsaddr.<domain>`.
+    pub fn new(domain: impl Into<String>) -> Self {
+        Self {
+            domain: domain.into(),
+            ttl: Duration::from_secs(300),
+            debounce: Duration::from_secs(1),
+            retry_interval: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets the time-to-live of the published records.
+    ///
+    /// Defaults to 5 minutes.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets how long to wait for further address changes before publishing.
+    ///
+    /// Defaults to 1 second.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Sets how long to wait before retrying a failed update.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// The name under which the records are published.
+    pub(crate) fn record_name(&self) -> String {
+        format!("_dnsaddr.{}", self.domain.trim_end_matches('.'))
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::future::{self, BoxFuture, FutureExt};
+use libp2p_dns_discovery::{Behaviour, Config, Event, Publisher, Update};
+use libp2p_swarm::Swarm;
+use libp2p_swarm_test::SwarmExt;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Clone, Default)]
+struct MemoryPublisher {
+    updates: Arc<Mutex<Vec<Update>>>,
+    fail: Arc<Mutex<bool>>,
+}
+
+#[derive(Debug)]
+struct PublishError;
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "publish failed")
+    }
+}
+
+impl std::error::Error for PublishError {}
+
+impl Publisher for MemoryPublisher {
+    type Error = PublishError;
+
+    fn publish(&mut self, update: Update) -> BoxFuture<'static, Result<(), Self::Error>> {
+        if *self.fail.lock().unwrap() {
+            return future::ready(Err(PublishError)).boxed();
+        }
+        self.updates.lock().unwrap().push(update);
+
+        future::ready(Ok(())).boxed()
+    }
+}
+
+fn config() -> Config {
+    Config::new("example.com")
+        .with_debounce(Duration::from_millis(10))
+        .with_retry_interval(Duration::from_millis(10))
+}
+
+#[async_std::test]
+async fn publishes_and_withdraws_external_addresses() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let publisher = MemoryPublisher::default();
+    let mut swarm = Swarm::new_ephemeral(|key| {
+        Behaviour::new(key.public().to_peer_id(), publisher.clone(), config())
+    });
+    let peer_id = *swarm.local_peer_id();
+
+    swarm.add_external_address("/ip4/1.2.3.4/tcp/4001".parse().unwrap());
+    swarm.add_external_address("/ip6/::1/udp/4001/quic-v1".parse().unwrap());
+
+    let Event::Published { name, records } = swarm.next_behaviour_event().await else {
+        panic!("Expected records to be published")
+    };
+    assert_eq!(name, "_dnsaddr.example.com");
+    assert_eq!(
+        records,
+        vec![
+            format!("dnsaddr=/ip4/1.2.3.4/tcp/4001/p2p/{peer_id}"),
+            format!("dnsaddr=/ip6/::1/udp/4001/quic-v1/p2p/{peer_id}"),
+        ]
+    );
+
+    swarm.remove_external_address(&"/ip4/1.2.3.4/tcp/4001".parse().unwrap());
+
+    let Event::Published { records, .. } = swarm.next_behaviour_event().await else {
+        panic!("Expected records to be published")
+    };
+    assert_eq!(
+        records,
+        vec![format!("dnsaddr=/ip6/::1/udp/4001/quic-v1/p2p/{peer_id}")]
+    );
+    assert_eq!(publisher.updates.lock().unwrap().len(), 2);
+}
+
+#[async_std::test]
+async fn retries_failed_update() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let publisher = MemoryPublisher::default();
+    *publisher.fail.lock().unwrap() = true;
+    let mut swarm = Swarm::new_ephemeral(|key| {
+        Behaviour::new(key.public().to_peer_id(), publisher.clone(), config())
+    });
+
+    swarm.add_external_address("/ip4/1.2.3.4/tcp/4001".parse().unwrap());
+
+    let Event::PublishFailed { .. } = swarm.next_behaviour_event().await else {
+        panic!("Expected update to fail")
+    };
+    assert!(swarm.behaviour().published_records().is_none());
+
+    *publisher.fail.lock().unwrap() = false;
+
+    let Event::Published { records, .. } = swarm.next_behaviour_event().await else {
+        panic!("Expected records to be published")
+    };
+    assert_eq!(
+        swarm.behaviour().published_records(),
+        Some(records.as_slice())
+    );
+}
+
+#[async_std::test]
+async fn publishes_nothing_without_external_addresses() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let publisher = MemoryPublisher::default();
+    let mut swarm = Swarm::new_ephemeral(|key| {
+        Behaviour::new(key.public().to_peer_id(), publisher.clone(), config())
+    });
+
+    swarm.behaviour_mut().republish();
+    let event =
+        async_std::future::timeout(Duration::from_millis(100), swarm.next_behaviour_event()).await;
+    assert!(event.is_err(), "Unexpected event: {event:?}");
+    assert!(publisher.updates.lock().unwrap().is_empty());
+
+    // Once published, the records are withdrawn when all addresses expired.
+    let addr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+    swarm.add_external_address(addr);
+    let Event::Published { .. } = swarm.next_behaviour_event().await else {
+        panic!("Expected records to be published")
+    };
+    swarm.remove_external_address(&"/ip4/1.2.3.4/tcp/4001".parse().unwrap());
+    let Event::Published { records, .. } = swarm.next_behaviour_event().await else {
+        panic!("Expected records to be published")
+    };
+    assert!(records.is_empty());
+}
@@ -20,6 +20,7 @@
 
 use std::{
     cmp::{max, Ordering},
+    collections::hash_map::Entry,
     collections::HashSet,
     collections::VecDeque,
     collections::{BTreeSet, HashMap},
@@ -29,13 +30,16 @@ use std::{
     time::Duration,
 };
 
+use futures::channel::oneshot;
 use futures::StreamExt;
 use futures_ticker::Ticker;
 use prometheus_client::registry::Registry;
 use rand::{seq::SliceRandom, thread_rng};
 
 use instant::Instant;
-use libp2p_core::{multiaddr::Protocol::Ip4, multiaddr::Protocol::Ip6, Endpoint, Multiaddr};
+use libp2p_core::{
+    multiaddr::Protocol::Ip4, multiaddr::Protocol::Ip6, Endpoint, Multiaddr, PeerRecord,
+};
 use libp2p_identity::Keypair;
 use libp2p_identity::PeerId;
 use libp2p_swarm::{
@@ -46,7 +50,8 @@ use libp2p_swarm::{
 };
 
 use crate::backoff::BackoffStorage;
-use crate::config::{Config, ValidationMode};
+use crate::choke::Choking;
+use crate::config::{Config, ValidationMode, ValidationQueueOverflow};
 use crate::gossip_promises::GossipPromises;
 use crate::handler::{Handler, HandlerEvent, HandlerIn};
 use crate::mcache::MessageCache;
@@ -61,7 +66,7 @@ use crate::types::{
     ControlAction, Message, MessageAcceptance, MessageId, PeerInfo, RawMessage, Subscription,
     SubscriptionAction,
 };
-use crate::types::{PeerConnections, PeerKind, RpcOut};
+use crate::types::{PeerConnections, PeerKind, PublishHandle, PublishId, RpcOut};
 use crate::{rpc_proto::proto, TopicScoreParams};
 use crate::{PublishError, SubscriptionError, ValidationError};
 use instant::SystemTime;
@@ -144,6 +149,24 @@ pub enum Event {
     },
     /// A peer that does not support gossipsub has connected.
     GossipsubNotSupported { peer_id: PeerId },
+    /// The send queue to a peer has been backed up for longer than
+    /// [`Config::slow_peer_detection_time`]. Until the peer recovers, messages larger than
+    /// [`Config::slow_peer_max_forward_size`] are announced to it via IHAVE instead of being sent.
+    SlowPeer { peer_id: PeerId },
+    /// A peer previously reported via [`Event::SlowPeer`] has caught up with its send queue.
+    SlowPeerRecovered { peer_id: PeerId },
+    /// At least [`Config::mesh_downgrade_threshold`] of the mesh peers of a topic use an older
+    /// protocol than gossipsub v1.1, making features like peer exchange and scoring less effective.
+    ///
+    /// The event is emitted again only after the fraction dropped below the threshold in between.
+    MeshDowngraded {
+        /// The topic of the mesh.
+        topic: TopicHash,
+        /// The number of mesh peers on an older protocol than gossipsub v1.1.
+        downgraded_peers: usize,
+        /// The number of peers in the mesh.
+        mesh_peers: usize,
+    },
 }
 
 /// A data structure for storing configuration for publishing messages. See [`MessageAuthenticity`]
@@ -161,6 +184,33 @@ enum PublishConfig {
     Anonymous,
 }
 
+/// A message published via [`Behaviour::publish_awaitable`] whose delivery is being tracked.
+struct PendingPublish {
+    /// The number of recipients the message has to be sent to.
+    min_recipients: usize,
+    /// The number of recipients the message was sent to so far.
+    recipients: usize,
+    /// The recipients whose delivery is yet to be confirmed, by the connection the message was
+    /// handed to.
+    awaiting: HashMap<PeerId, ConnectionId>,
+    sender: oneshot::Sender<Result<usize, PublishError>>,
+}
+
+impl PendingPublish {
+    /// The outcome of the publication, once known.
+    fn outcome(&self) -> Option<Result<usize, PublishError>> {
+        if self.recipients >= self.min_recipients {
+            return Some(Ok(self.recipients));
+        }
+        if self.awaiting.is_empty() {
+            return Some(Err(PublishError::InsufficientRecipients {
+                recipients: self.recipients,
+            }));
+        }
+        None
+    }
+}
+
 /// A strictly linearly increasing sequence number.
 ///
 /// We start from the current time as unix timestamp in milliseconds.
@@ -287,9 +337,21 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// Message cache for the last few heartbeats.
     mcache: MessageCache,
 
+    /// The received messages awaiting validation, oldest first, together with the peer they were
+    /// received from. Only tracked if [`Config::max_pending_validations`] is set.
+    pending_validations: VecDeque<(MessageId, PeerId, TopicHash)>,
+
     /// Heartbeat interval stream.
     heartbeat: Ticker,
 
+    /// The current time between heartbeats, scaled between [`Config::heartbeat_interval`] and
+    /// [`Config::max_heartbeat_interval`].
+    heartbeat_interval: Duration,
+
+    /// Whether there was activity since the last heartbeat, keeping the heartbeat interval at
+    /// its minimum.
+    heartbeat_activity: bool,
+
     /// Number of heartbeats since the beginning of time; this allows us to amortize some resource
     /// clean up -- eg backoff clean up.
     heartbeat_ticks: u64,
@@ -300,6 +362,10 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// be removed from this list which may result in a true outbound rediscovery.
     px_peers: HashSet<PeerId>,
 
+    /// The signed peer records of connected peers, proposed to pruned peers in peer exchange,
+    /// see [`Behaviour::add_signed_peer_record`].
+    signed_peer_records: HashMap<PeerId, PeerRecord>,
+
     /// Set of connected outbound peers (we only consider true outbound peers found through
     /// discovery and not by PX).
     outbound_peers: HashSet<PeerId>,
@@ -322,6 +388,23 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// our own messages back if the messages are anonymous or use a random author.
     published_message_ids: DuplicateCache<MessageId>,
 
+    /// Peers considered slow, together with the connections that reported a backed up send
+    /// queue.
+    slow_peers: HashMap<PeerId, HashSet<ConnectionId>>,
+
+    /// The mesh peers choked by and choking the local node, see
+    /// [`Config::choke_duplicate_ratio`].
+    choking: Choking,
+
+    /// Topics whose mesh was last reported via [`Event::MeshDowngraded`].
+    downgraded_meshes: HashSet<TopicHash>,
+
+    /// Messages published via [`Behaviour::publish_awaitable`] whose delivery is being tracked.
+    pending_publishes: HashMap<PublishId, PendingPublish>,
+
+    /// The id of the next message published via [`Behaviour::publish_awaitable`].
+    next_publish_id: u64,
+
     /// The filter used to handle message subscriptions.
     subscription_filter: F,
 
@@ -454,13 +537,22 @@ where
                 config.heartbeat_interval(),
                 config.backoff_slack(),
             ),
-            mcache: MessageCache::new(config.history_gossip(), config.history_length()),
+            mcache: MessageCache::new(
+                config.history_gossip(),
+                config.history_length(),
+                config.max_provenance_peers(),
+                config.iwant_followup_time(),
+            ),
+            pending_validations: VecDeque::new(),
             heartbeat: Ticker::new_with_next(
                 config.heartbeat_interval(),
                 config.heartbeat_initial_delay(),
             ),
+            heartbeat_interval: config.heartbeat_interval(),
+            heartbeat_activity: false,
             heartbeat_ticks: 0,
             px_peers: HashSet::new(),
+            signed_peer_records: HashMap::new(),
             outbound_peers: HashSet::new(),
             peer_score: None,
             count_received_ihave: HashMap::new(),
@@ -468,6 +560,11 @@ where
             pending_iwant_msgs: HashSet::new(),
             connected_peers: HashMap::new(),
             published_message_ids: DuplicateCache::new(config.published_message_ids_cache_time()),
+            slow_peers: HashMap::new(),
+            choking: Choking::new(config.duplicate_cache_time()),
+            downgraded_meshes: HashSet::new(),
+            pending_publishes: HashMap::new(),
+            next_publish_id: 0,
             config,
             subscription_filter,
             data_transform,
@@ -510,6 +607,15 @@ where
         self.connected_peers.iter().map(|(k, v)| (k, &v.kind))
     }
 
+    /// Returns the number of connected peers per protocol they negotiated.
+    pub fn peer_protocol_counts(&self) -> HashMap<PeerKind, usize> {
+        let mut counts = HashMap::new();
+        for connections in self.connected_peers.values() {
+            *counts.entry(connections.kind.clone()).or_default() += 1;
+        }
+        counts
+    }
+
     /// Returns the gossipsub score for a given peer, if one exists.
     pub fn peer_score(&self, peer_id: &PeerId) -> Option<f64> {
         self.peer_score
@@ -543,6 +649,7 @@ where
         // call JOIN(topic)
         // this will add new peers to the mesh for the topic
         self.join(&topic_hash);
+        self.on_heartbeat_activity();
         tracing::debug!(%topic, "Subscribed to topic");
         Ok(true)
     }
@@ -570,6 +677,7 @@ where
         // call LEAVE(topic)
         // this will remove the topic from the mesh
         self.leave(&topic_hash);
+        self.on_heartbeat_activity();
 
         tracing::debug!(topic=%topic_hash, "Unsubscribed from topic");
         Ok(true)
@@ -581,9 +689,57 @@ where
         topic: impl Into<TopicHash>,
         data: impl Into<Vec<u8>>,
     ) -> Result<MessageId, PublishError> {
-        let data = data.into();
-        let topic = topic.into();
+        self.publish_message(topic.into(), data.into(), None)
+            .map(|(msg_id, _)| msg_id)
+    }
+
+    /// Publishes a message like [`Behaviour::publish`], returning a [`PublishHandle`] that
+    /// resolves once the message was sent to at least `min_recipients` peers, or once that is
+    /// no longer possible.
+    ///
+    /// A message counts as sent to a peer once it was flushed to the connection to the peer.
+    /// Slow peers the message is only announced to are not counted as recipients.
+    pub fn publish_awaitable(
+        &mut self,
+        topic: impl Into<TopicHash>,
+        data: impl Into<Vec<u8>>,
+        min_recipients: usize,
+    ) -> Result<PublishHandle, PublishError> {
+        let id = PublishId(self.next_publish_id);
+        self.next_publish_id += 1;
+
+        let (message_id, awaiting) = self.publish_message(topic.into(), data.into(), Some(id))?;
+        let (sender, receiver) = oneshot::channel();
+        let pending = PendingPublish {
+            min_recipients,
+            recipients: 0,
+            awaiting,
+            sender,
+        };
+        match pending.outcome() {
+            Some(outcome) => {
+                let _ = pending.sender.send(outcome);
+            }
+            None => {
+                self.pending_publishes.insert(id, pending);
+            }
+        }
 
+        Ok(PublishHandle {
+            message_id,
+            receiver,
+        })
+    }
+
+    /// Publishes a message, tracking its delivery under the given id if any.
+    ///
+    /// Returns the id of the message and the connections the tracked message was handed to.
+    fn publish_message(
+        &mut self,
+        topic: TopicHash,
+        data: Vec<u8>,
+        publish_id: Option<PublishId>,
+    ) -> Result<(MessageId, HashMap<PeerId, ConnectionId>), PublishError> {
         // Transform the data before building a raw_message.
         let transformed_data = self
             .data_transform
@@ -644,7 +800,8 @@ where
                         } else {
                             // We have no fanout peers, select mesh_n of them and add them to the fanout
                             let mesh_n = self.config.mesh_n();
-                            let new_peers = get_random_peers(
+                            let new_peers = get_fanout_peers(
+                                &self.config,
                                 &self.topic_peers,
                                 &self.connected_peers,
                                 &topic_hash,
@@ -711,18 +868,48 @@ where
         }
 
         // Send to peers we know are subscribed to the topic.
+        let mut tracked = HashMap::new();
         for peer_id in recipient_peers.iter() {
+            if self.announce_to_slow_peer(peer_id, &msg_id, &raw_message)
+                || self.announce_to_choking_peer(peer_id, &msg_id, &raw_message)
+            {
+                continue;
+            }
             tracing::trace!(peer=%peer_id, "Sending message to peer");
-            self.send_message(*peer_id, RpcOut::Publish(raw_message.clone()));
+            let rpc = RpcOut::Publish(raw_message.clone());
+            let connection = self
+                .connected_peers
+                .get(peer_id)
+                .and_then(|c| c.connections.first().copied());
+            match (publish_id, connection) {
+                (Some(id), Some(connection_id)) => {
+                    self.send_tracked_message(*peer_id, connection_id, rpc, id);
+                    tracked.insert(*peer_id, connection_id);
+                }
+                _ => self.send_message(*peer_id, rpc),
+            }
         }
 
         tracing::debug!(message=%msg_id, "Published message");
+        self.on_heartbeat_activity();
 
         if let Some(metrics) = self.metrics.as_mut() {
             metrics.register_published_message(&topic_hash);
         }
 
-        Ok(msg_id)
+        Ok((msg_id, tracked))
+    }
+
+    /// Triggers a heartbeat on the next poll of the behaviour, regardless of the time since the
+    /// last heartbeat, e.g. to repair the mesh right after a device resumes from sleep.
+    pub fn heartbeat_now(&mut self) {
+        self.heartbeat = Ticker::new_with_next(self.heartbeat_interval, Duration::ZERO);
+    }
+
+    /// The current time between heartbeats. This is [`Config::heartbeat_interval`] unless
+    /// [`Config::max_heartbeat_interval`] is set and the node is idle.
+    pub fn heartbeat_interval(&self) -> Duration {
+        self.heartbeat_interval
     }
 
     /// This function should be called when [`Config::validate_messages()`] is `true` after
@@ -750,6 +937,10 @@ where
         propagation_source: &PeerId,
         acceptance: MessageAcceptance,
     ) -> Result<bool, PublishError> {
+        if self.config.max_pending_validations().is_some() {
+            self.pending_validations.retain(|(id, ..)| id != msg_id);
+        }
+
         let reject_reason = match acceptance {
             MessageAcceptance::Accept => {
                 let (raw_message, originating_peers) = match self.mcache.validate(msg_id) {
@@ -818,6 +1009,26 @@ where
         self.check_explicit_peer_connection(peer_id);
     }
 
+    /// Adds the signed peer record of a connected peer, e.g. as reported by identify, to be
+    /// proposed along with the peer in the peer exchange of PRUNE control messages.
+    ///
+    /// A record is kept until the peer disconnects or a record with a higher sequence number
+    /// is added. Returns whether the record was added.
+    pub fn add_signed_peer_record(&mut self, record: PeerRecord) -> bool {
+        let peer_id = record.peer_id();
+        if !self.connected_peers.contains_key(&peer_id) {
+            tracing::debug!(peer=%peer_id, "Ignoring signed peer record of unconnected peer");
+            return false;
+        }
+        match self.signed_peer_records.get(&peer_id) {
+            Some(known) if known.seq() >= record.seq() => false,
+            _ => {
+                self.signed_peer_records.insert(peer_id, record);
+                true
+            }
+        }
+    }
+
     /// This removes the peer from explicitly connected peers, note that this does not disconnect
     /// the peer.
     pub fn remove_explicit_peer(&mut self, peer_id: &PeerId) {
@@ -1032,6 +1243,7 @@ where
         if let Some((peer_score, ..)) = &mut self.peer_score {
             peer_score.prune(peer, topic_hash.clone());
         }
+        self.choking.remove_from_mesh(topic_hash, peer);
 
         match self.connected_peers.get(peer).map(|v| &v.kind) {
             Some(PeerKind::Floodsub) => {
@@ -1061,7 +1273,10 @@ where
                 |p| p != peer && !self.score_below_threshold(p, |_| 0.0).0,
             )
             .into_iter()
-            .map(|p| PeerInfo { peer_id: Some(p) })
+            .map(|p| PeerInfo {
+                peer_id: Some(p),
+                signed_peer_record: self.signed_peer_records.get(&p).cloned(),
+            })
             .collect()
         } else {
             Vec::new()
@@ -1086,6 +1301,7 @@ where
     /// Gossipsub LEAVE(topic) - Notifies mesh\[topic\] peers with PRUNE messages.
     fn leave(&mut self, topic_hash: &TopicHash) {
         tracing::debug!(topic=%topic_hash, "Running LEAVE for topic");
+        self.choking.leave(topic_hash);
 
         // If our mesh contains the topic, send prune to peers and delete it from the mesh
         if let Some((_, peers)) = self.mesh.remove_entry(topic_hash) {
@@ -1217,7 +1433,19 @@ where
                 continue;
             }
 
-            for id in ids.into_iter().filter(want_message) {
+            for id in ids {
+                // Remember that the peer has the message so that it is not forwarded to them.
+                if self.duplicate_cache.contains(&id) {
+                    self.mcache.observe_duplicate(&id, peer_id);
+                } else {
+                    self.mcache.observe_announcement(&id, peer_id);
+                    self.choking.on_announcement(&topic, peer_id);
+                }
+
+                if !want_message(&id) {
+                    continue;
+                }
+
                 // have not seen this message and are not currently requesting it
                 if iwant_ids.insert(id) {
                     // Register the IWANT metric
@@ -1318,10 +1546,61 @@ where
         tracing::debug!(peer=%peer_id, "Completed IWANT handling for peer");
     }
 
+    /// Handles an IDONTWANT control message. Remembers that the peer has the messages, such that
+    /// they are not forwarded to the peer.
+    fn handle_idontwant(&mut self, peer_id: &PeerId, message_ids: Vec<MessageId>) {
+        tracing::trace!(peer=%peer_id, "Handling IDONTWANT for peer");
+
+        for id in message_ids.into_iter().take(self.config.max_ihave_length()) {
+            if self.duplicate_cache.contains(&id) {
+                self.mcache.observe_duplicate(&id, peer_id);
+            } else {
+                self.mcache.observe_announcement(&id, peer_id);
+            }
+        }
+    }
+
+    /// Announces a received message of at least [`Config::idontwant_message_size_threshold`]
+    /// via IDONTWANT to the gossipsub v1.2 mesh peers of its topic, except its source.
+    fn send_idontwant(
+        &mut self,
+        msg_id: &MessageId,
+        message: &RawMessage,
+        propagation_source: &PeerId,
+    ) {
+        if message.raw_protobuf_len() < self.config.idontwant_message_size_threshold() {
+            return;
+        }
+        let Some(mesh_peers) = self.mesh.get(&message.topic) else {
+            return;
+        };
+
+        let recipients = mesh_peers
+            .iter()
+            .filter(|peer| {
+                *peer != propagation_source
+                    && self
+                        .connected_peers
+                        .get(peer)
+                        .is_some_and(|connections| connections.kind == PeerKind::Gossipsubv1_2)
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        for peer in recipients {
+            self.send_message(
+                peer,
+                RpcOut::Control(ControlAction::IDontWant {
+                    message_ids: vec![msg_id.clone()],
+                }),
+            );
+        }
+    }
+
     /// Handles GRAFT control messages. If subscribed to the topic, adds the peer to mesh, if not,
     /// responds with PRUNE messages.
     fn handle_graft(&mut self, peer_id: &PeerId, topics: Vec<TopicHash>) {
         tracing::debug!(peer=%peer_id, "Handling GRAFT message for peer");
+        self.on_heartbeat_activity();
 
         let mut to_prune_topics = HashSet::new();
 
@@ -1504,6 +1783,7 @@ where
                 if let Some((peer_score, ..)) = &mut self.peer_score {
                     peer_score.prune(peer_id, topic_hash.clone());
                 }
+                self.choking.remove_from_mesh(topic_hash, peer_id);
 
                 update_backoff = true;
 
@@ -1529,6 +1809,26 @@ where
         }
     }
 
+    /// Handles CHOKE and UNCHOKE control messages of mesh peers.
+    fn handle_choke(&mut self, peer_id: &PeerId, topic_hash: &TopicHash, choked: bool) {
+        if !self.is_mesh_peer(topic_hash, peer_id) {
+            tracing::debug!(
+                peer=%peer_id,
+                topic=%topic_hash,
+                "CHOKE: Ignoring choke of peer not in the mesh"
+            );
+            return;
+        }
+        tracing::debug!(peer=%peer_id, topic=%topic_hash, %choked, "Handling CHOKE for peer");
+        self.choking.set_choked_by(topic_hash, *peer_id, choked);
+    }
+
+    fn is_mesh_peer(&self, topic_hash: &TopicHash, peer_id: &PeerId) -> bool {
+        self.mesh
+            .get(topic_hash)
+            .is_some_and(|peers| peers.contains(peer_id))
+    }
+
     /// Handles PRUNE control messages. Removes peer from the mesh.
     fn handle_prune(
         &mut self,
@@ -1536,6 +1836,7 @@ where
         prune_data: Vec<(TopicHash, Vec<PeerInfo>, Option<u64>)>,
     ) {
         tracing::debug!(peer=%peer_id, "Handling PRUNE message for peer");
+        self.on_heartbeat_activity();
         let (below_threshold, score) =
             self.score_below_threshold(peer_id, |pst| pst.accept_px_threshold);
         for (topic_hash, px, backoff) in prune_data {
@@ -1555,12 +1856,6 @@ where
                         continue;
                     }
 
-                    // NOTE: We cannot dial any peers from PX currently as we typically will not
-                    // know their multiaddr. Until SignedRecords are spec'd this
-                    // remains a stub. By default `config.prune_peers()` is set to zero and
-                    // this is skipped. If the user modifies this, this will only be able to
-                    // dial already known peers (from an external discovery mechanism for
-                    // example).
                     if self.config.prune_peers() > 0 {
                         self.px_connect(px);
                     }
@@ -1572,11 +1867,9 @@ where
 
     fn px_connect(&mut self, mut px: Vec<PeerInfo>) {
         let n = self.config.prune_peers();
-        // Ignore peerInfo with no ID
-        //
-        //TODO: Once signed records are spec'd: Can we use peerInfo without any IDs if they have a
-        // signed peer record?
-        px.retain(|p| p.peer_id.is_some());
+        // Only dial peers proposed with a signed peer record, verified when decoding, such that
+        // the proposing peer cannot make us dial arbitrary peer IDs at arbitrary addresses.
+        px.retain(|p| p.peer_id.is_some() && p.signed_peer_record.is_some());
         if px.len() > n {
             // only use at most prune_peers many random peers
             let mut rng = thread_rng();
@@ -1585,15 +1878,15 @@ where
         }
 
         for p in px {
-            // TODO: Once signed records are spec'd: extract signed peer record if given and handle
-            // it, see https://github.com/libp2p/specs/pull/217
-            if let Some(peer_id) = p.peer_id {
+            if let (Some(peer_id), Some(record)) = (p.peer_id, p.signed_peer_record) {
                 // mark as px peer
                 self.px_peers.insert(peer_id);
 
-                // dial peer
+                // dial peer at the addresses of its signed peer record
                 self.events.push_back(ToSwarm::Dial {
-                    opts: DialOpts::peer_id(peer_id).build(),
+                    opts: DialOpts::peer_id(peer_id)
+                        .addresses(record.addresses().to_vec())
+                        .build(),
                 });
             }
         }
@@ -1721,8 +2014,21 @@ where
                 peer_score.duplicated_message(propagation_source, &msg_id, &message.topic);
             }
             self.mcache.observe_duplicate(&msg_id, propagation_source);
+            if self.config.choke_duplicate_ratio().is_some()
+                && self.is_mesh_peer(&message.topic, propagation_source)
+            {
+                self.choking
+                    .on_duplicate(&msg_id, &message.topic, propagation_source);
+            }
             return;
         }
+        if self.config.choke_duplicate_ratio().is_some() {
+            let mesh_peer = self
+                .is_mesh_peer(&message.topic, propagation_source)
+                .then_some(propagation_source);
+            self.choking
+                .on_first_delivery(&msg_id, &message.topic, mesh_peer);
+        }
         tracing::debug!(
             message=%msg_id,
             "Put message in duplicate_cache and resolve promises"
@@ -1733,6 +2039,14 @@ where
             metrics.msg_recvd(&message.topic);
         }
 
+        let awaits_validation =
+            self.config.validate_messages() && self.mesh.contains_key(&message.topic);
+        if awaits_validation
+            && !self.admit_for_validation(&msg_id, propagation_source, &message.topic)
+        {
+            return;
+        }
+
         // Tells score that message arrived (but is maybe not fully validated yet).
         // Consider the message as delivered for gossip promises.
         if let Some((peer_score, .., gossip_promises)) = &mut self.peer_score {
@@ -1745,7 +2059,15 @@ where
 
         // Dispatch the message to the user if we are subscribed to any of the topics
         if self.mesh.contains_key(&message.topic) {
+            self.send_idontwant(&msg_id, &raw_message, propagation_source);
             tracing::debug!("Sending received message to user");
+            if awaits_validation && self.config.max_pending_validations().is_some() {
+                self.pending_validations.push_back((
+                    msg_id.clone(),
+                    *propagation_source,
+                    message.topic.clone(),
+                ));
+            }
             self.events
                 .push_back(ToSwarm::GenerateEvent(Event::Message {
                     propagation_source: *propagation_source,
@@ -1762,12 +2084,13 @@ where
 
         // forward the message to mesh peers, if no validation is required
         if !self.config.validate_messages() {
+            let originating_peers = self.mcache.take_known_peers(&msg_id);
             if self
                 .forward_msg(
                     &msg_id,
                     raw_message,
                     Some(propagation_source),
-                    HashSet::new(),
+                    originating_peers,
                 )
                 .is_err()
             {
@@ -1777,6 +2100,70 @@ where
         }
     }
 
+    /// Checks whether a received message can await validation without exceeding
+    /// [`Config::max_pending_validations`], applying the [`Config::validation_queue_overflow`]
+    /// otherwise. Returns whether the message is to be handled further.
+    fn admit_for_validation(
+        &mut self,
+        msg_id: &MessageId,
+        propagation_source: &PeerId,
+        topic: &TopicHash,
+    ) -> bool {
+        let Some(max) = self.config.max_pending_validations() else {
+            return true;
+        };
+        if self.pending_validations.len() < max {
+            return true;
+        }
+
+        match self.config.validation_queue_overflow() {
+            ValidationQueueOverflow::DropOldest => {
+                let Some((oldest, source, oldest_topic)) = self.pending_validations.pop_front()
+                else {
+                    // A limit of zero admits no message.
+                    return false;
+                };
+                tracing::debug!(
+                    message=%oldest,
+                    "Validation queue full, dropping oldest message awaiting validation"
+                );
+                self.mcache.remove(&oldest);
+                if let Some((peer_score, ..)) = &mut self.peer_score {
+                    peer_score.reject_message(
+                        &source,
+                        &oldest,
+                        &oldest_topic,
+                        RejectReason::ValidationIgnored,
+                    );
+                }
+                if let Some(metrics) = self.metrics.as_mut() {
+                    metrics.register_dropped_before_validation(&oldest_topic);
+                }
+                true
+            }
+            overflow @ (ValidationQueueOverflow::DropNew
+            | ValidationQueueOverflow::PenalizeSender) => {
+                tracing::debug!(
+                    message=%msg_id,
+                    source=%propagation_source,
+                    "Validation queue full, dropping received message"
+                );
+                if let Some(metrics) = self.metrics.as_mut() {
+                    metrics.register_dropped_before_validation(topic);
+                }
+                if overflow == ValidationQueueOverflow::PenalizeSender {
+                    if let Some((peer_score, ..)) = &mut self.peer_score {
+                        if let Some(metrics) = self.metrics.as_mut() {
+                            metrics.register_score_penalty(Penalty::ValidationQueueOverflow);
+                        }
+                        peer_score.add_penalty(propagation_source, 1);
+                    }
+                }
+                false
+            }
+        }
+    }
+
     // Handles invalid messages received.
     fn handle_invalid_message(
         &mut self,
@@ -1872,12 +2259,10 @@ where
 
                     // if the mesh needs peers add the peer to the mesh
                     if !self.explicit_peers.contains(propagation_source)
-                        && matches!(
-                            self.connected_peers
-                                .get(propagation_source)
-                                .map(|v| &v.kind),
-                            Some(PeerKind::Gossipsubv1_1) | Some(PeerKind::Gossipsub)
-                        )
+                        && self
+                            .connected_peers
+                            .get(propagation_source)
+                            .is_some_and(|v| v.kind.is_gossipsub())
                         && !Self::score_below_threshold_from_scores(
                             &self.peer_score,
                             propagation_source,
@@ -1995,6 +2380,53 @@ where
         }
     }
 
+    /// Counts the mesh peers on an older protocol than gossipsub v1.1 and emits
+    /// [`Event::MeshDowngraded`] for meshes that now reach [`Config::mesh_downgrade_threshold`].
+    fn check_mesh_downgrades(&mut self) {
+        let threshold = self.config.mesh_downgrade_threshold();
+        if threshold.is_none() && self.metrics.is_none() {
+            return;
+        }
+
+        let mut downgraded_meshes = HashSet::new();
+        for (topic, peers) in &self.mesh {
+            let downgraded_peers = peers
+                .iter()
+                .filter(|peer| {
+                    self.connected_peers
+                        .get(peer)
+                        .is_some_and(|connections| !connections.kind.is_gossipsub_v1_1())
+                })
+                .count();
+
+            if let Some(m) = self.metrics.as_mut() {
+                m.set_mesh_downgraded_peers(topic, downgraded_peers);
+            }
+
+            let Some(threshold) = threshold else {
+                continue;
+            };
+            if peers.is_empty() || (downgraded_peers as f64) < threshold * peers.len() as f64 {
+                continue;
+            }
+            if !self.downgraded_meshes.contains(topic) {
+                tracing::debug!(
+                    %topic,
+                    "{downgraded_peers} of {} mesh peers use an older protocol than gossipsub v1.1",
+                    peers.len()
+                );
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::MeshDowngraded {
+                        topic: topic.clone(),
+                        downgraded_peers,
+                        mesh_peers: peers.len(),
+                    }));
+            }
+            downgraded_meshes.insert(topic.clone());
+        }
+        self.downgraded_meshes = downgraded_meshes;
+    }
+
     /// Heartbeat function which shifts the memcache and updates the mesh.
     fn heartbeat(&mut self) {
         tracing::debug!("Starting heartbeat");
@@ -2338,7 +2770,8 @@ where
                 );
                 let needed_peers = self.config.mesh_n() - peers.len();
                 let explicit_peers = &self.explicit_peers;
-                let new_peers = get_random_peers(
+                let new_peers = get_fanout_peers(
+                    &self.config,
                     &self.topic_peers,
                     &self.connected_peers,
                     topic_hash,
@@ -2380,24 +2813,81 @@ where
             })
         }
 
+        self.check_mesh_downgrades();
+
         self.emit_gossip();
 
         // send graft/prunes
         if !to_graft.is_empty() | !to_prune.is_empty() {
+            self.heartbeat_activity = true;
             self.send_graft_prune(to_graft, to_prune, no_px);
         }
 
+        self.choke_mesh_peers();
+
         // piggyback pooled control messages
         self.flush_control_pool();
 
         // shift the memcache
         self.mcache.shift();
 
+        // Messages that expired from the memcache no longer await validation.
+        if !self.pending_validations.is_empty() {
+            let mcache = &self.mcache;
+            self.pending_validations
+                .retain(|(id, ..)| mcache.contains(id));
+        }
+        if let Some(metrics) = self.metrics.as_mut() {
+            if self.config.max_pending_validations().is_some() {
+                let mut awaiting_validation = self
+                    .mesh
+                    .keys()
+                    .map(|topic| (topic, 0))
+                    .collect::<HashMap<_, _>>();
+                for (.., topic) in &self.pending_validations {
+                    *awaiting_validation.entry(topic).or_default() += 1;
+                }
+                for (topic, count) in awaiting_validation {
+                    metrics.set_messages_awaiting_validation(topic, count);
+                }
+            }
+        }
+
         tracing::debug!("Completed Heartbeat");
         if let Some(metrics) = self.metrics.as_mut() {
             let duration = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
             metrics.observe_heartbeat_duration(duration);
         }
+
+        self.scale_heartbeat_interval();
+    }
+
+    /// Doubles the time between heartbeats up to [`Config::max_heartbeat_interval`] if there was
+    /// no activity since the last heartbeat.
+    fn scale_heartbeat_interval(&mut self) {
+        let Some(max_interval) = self.config.max_heartbeat_interval() else {
+            return;
+        };
+        if std::mem::take(&mut self.heartbeat_activity) {
+            return;
+        }
+        let interval = (self.heartbeat_interval * 2).min(max_interval);
+        if interval != self.heartbeat_interval {
+            tracing::debug!(?interval, "Slowing down heartbeat of idle node");
+            self.heartbeat_interval = interval;
+            self.heartbeat = Ticker::new_with_next(interval, interval);
+        }
+    }
+
+    /// Records activity, resetting the time between heartbeats to [`Config::heartbeat_interval`].
+    fn on_heartbeat_activity(&mut self) {
+        self.heartbeat_activity = true;
+        let interval = self.config.heartbeat_interval();
+        if self.heartbeat_interval != interval {
+            tracing::debug!(?interval, "Speeding up heartbeat on activity");
+            self.heartbeat_interval = interval;
+            self.heartbeat = Ticker::new_with_next(interval, interval);
+        }
     }
 
     /// Emits gossip - Send IHAVE messages to a random set of gossip peers. This is applied to mesh
@@ -2468,6 +2958,31 @@ where
         }
     }
 
+    /// Chokes the mesh peers delivering mostly duplicates and unchokes the choked peers
+    /// announcing messages first, see [`Config::choke_duplicate_ratio`].
+    fn choke_mesh_peers(&mut self) {
+        let Some(heuristics) = self.config.choke_heuristics() else {
+            return;
+        };
+        let (to_choke, to_unchoke) = self.choking.evaluate(&self.mesh, &heuristics);
+        for (peer, topic_hash) in to_choke {
+            tracing::debug!(%peer, topic=%topic_hash, "Choking mesh peer");
+            Self::control_pool_add(
+                &mut self.control_pool,
+                peer,
+                ControlAction::Choke { topic_hash },
+            );
+        }
+        for (peer, topic_hash) in to_unchoke {
+            tracing::debug!(%peer, topic=%topic_hash, "Unchoking mesh peer");
+            Self::control_pool_add(
+                &mut self.control_pool,
+                peer,
+                ControlAction::Unchoke { topic_hash },
+            );
+        }
+    }
+
     /// Handles multiple GRAFT/PRUNE messages and coalesces them into chunked gossip control
     /// messages.
     fn send_graft_prune(
@@ -2605,6 +3120,11 @@ where
             let event = RpcOut::Forward(message.clone());
 
             for peer in recipient_peers.iter() {
+                if self.announce_to_slow_peer(peer, msg_id, &message)
+                    || self.announce_to_choking_peer(peer, msg_id, &message)
+                {
+                    continue;
+                }
                 tracing::debug!(%peer, message=%msg_id, "Sending message to peer");
                 self.send_message(*peer, event.clone());
             }
@@ -2615,6 +3135,89 @@ where
         }
     }
 
+    /// Announces a message to a slow peer via IHAVE instead of sending it, if the message is larger
+    /// than [`Config::slow_peer_max_forward_size`].
+    ///
+    /// Returns true if the message was announced and must not be sent to the peer.
+    fn announce_to_slow_peer(
+        &mut self,
+        peer_id: &PeerId,
+        msg_id: &MessageId,
+        message: &RawMessage,
+    ) -> bool {
+        if !self.slow_peers.contains_key(peer_id)
+            || message.raw_protobuf_len() <= self.config.slow_peer_max_forward_size()
+        {
+            return false;
+        }
+
+        tracing::debug!(peer=%peer_id, message=%msg_id, "Announcing message to slow peer");
+        Self::control_pool_add(
+            &mut self.control_pool,
+            *peer_id,
+            ControlAction::IHave {
+                topic_hash: message.topic.clone(),
+                message_ids: vec![msg_id.clone()],
+            },
+        );
+        true
+    }
+
+    /// Announces a message to a mesh peer choking the local node on the topic of the message via
+    /// IHAVE instead of sending it.
+    ///
+    /// Returns true if the message was announced and must not be sent to the peer.
+    fn announce_to_choking_peer(
+        &mut self,
+        peer_id: &PeerId,
+        msg_id: &MessageId,
+        message: &RawMessage,
+    ) -> bool {
+        if !self.choking.is_choked_by(&message.topic, peer_id) {
+            return false;
+        }
+
+        tracing::debug!(peer=%peer_id, message=%msg_id, "Announcing message to choking peer");
+        Self::control_pool_add(
+            &mut self.control_pool,
+            *peer_id,
+            ControlAction::IHave {
+                topic_hash: message.topic.clone(),
+                message_ids: vec![msg_id.clone()],
+            },
+        );
+        true
+    }
+
+    /// Handles a connection reporting whether the send queue to the peer is backed up.
+    fn on_slow_peer(&mut self, peer_id: PeerId, connection_id: ConnectionId, slow: bool) {
+        if slow {
+            let connections = self.slow_peers.entry(peer_id).or_default();
+            connections.insert(connection_id);
+            if connections.len() > 1 {
+                return;
+            }
+
+            tracing::debug!(peer=%peer_id, "[Penalty] Peer is slow, penalizing");
+            if let Some((peer_score, ..)) = &mut self.peer_score {
+                if let Some(metrics) = self.metrics.as_mut() {
+                    metrics.register_score_penalty(Penalty::SlowPeer);
+                }
+                peer_score.add_penalty(&peer_id, 1);
+            }
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::SlowPeer { peer_id }));
+        } else if let Entry::Occupied(mut connections) = self.slow_peers.entry(peer_id) {
+            connections.get_mut().remove(&connection_id);
+            if connections.get().is_empty() {
+                connections.remove();
+                tracing::debug!(peer=%peer_id, "Slow peer recovered");
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::SlowPeerRecovered { peer_id }));
+            }
+        }
+    }
+
     /// Constructs a [`RawMessage`] performing message signing if required.
     pub(crate) fn build_raw_message(
         &mut self,
@@ -2638,6 +3241,7 @@ where
                         topic: topic.clone().into_string(),
                         signature: None,
                         key: None,
+                        topic_alias: None,
                     };
 
                     let mut buf = Vec::with_capacity(message.get_size());
@@ -2745,6 +3349,46 @@ where
         });
     }
 
+    /// Send a message published via [`Behaviour::publish_awaitable`] to a peer over the given
+    /// connection, which reports whether it was sent.
+    fn send_tracked_message(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        rpc: RpcOut,
+        id: PublishId,
+    ) {
+        if let Some(m) = self.metrics.as_mut() {
+            if let RpcOut::Publish(ref message) = rpc {
+                m.msg_sent(&message.topic, message.raw_protobuf_len());
+            }
+        }
+
+        self.events.push_back(ToSwarm::NotifyHandler {
+            peer_id,
+            event: HandlerIn::TrackedMessage { rpc, id },
+            handler: NotifyHandler::One(connection_id),
+        });
+    }
+
+    /// Handles a connection reporting whether a message published via
+    /// [`Behaviour::publish_awaitable`] was sent to the peer.
+    fn on_message_sent(&mut self, peer_id: PeerId, id: PublishId, sent: bool) {
+        let Entry::Occupied(mut entry) = self.pending_publishes.entry(id) else {
+            return;
+        };
+        let pending = entry.get_mut();
+        if pending.awaiting.remove(&peer_id).is_none() {
+            return;
+        }
+        if sent {
+            pending.recipients += 1;
+        }
+        if let Some(outcome) = pending.outcome() {
+            let _ = entry.remove().sender.send(outcome);
+        }
+    }
+
     fn on_connection_established(
         &mut self,
         ConnectionEstablished {
@@ -2809,6 +3453,7 @@ where
         }
 
         tracing::debug!(peer=%peer_id, "New peer connected");
+        self.on_heartbeat_activity();
         // We need to send our subscriptions to the newly-connected node.
         for topic_hash in self.mesh.clone().into_keys() {
             self.send_message(peer_id, RpcOut::Subscribe(topic_hash));
@@ -2825,6 +3470,24 @@ where
             ..
         }: ConnectionClosed,
     ) {
+        if let Entry::Occupied(mut connections) = self.slow_peers.entry(peer_id) {
+            connections.get_mut().remove(&connection_id);
+            if connections.get().is_empty() {
+                connections.remove();
+            }
+        }
+
+        // Messages handed to the connection that have not been reported as sent are lost.
+        let unsent = self
+            .pending_publishes
+            .iter()
+            .filter(|(_, p)| p.awaiting.get(&peer_id) == Some(&connection_id))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in unsent {
+            self.on_message_sent(peer_id, id, false);
+        }
+
         // Remove IP from peer scoring system
         if let Some((peer_score, ..)) = &mut self.peer_score {
             if let Some(ip) = get_ip_addr(endpoint.get_remote_address()) {
@@ -2870,6 +3533,7 @@ where
         } else {
             // remove from mesh, topic_peers, peer_topic and the fanout
             tracing::debug!(peer=%peer_id, "Peer disconnected");
+            self.on_heartbeat_activity();
             {
                 let Some(topics) = self.peer_topics.get(&peer_id) else {
                     debug_assert!(
@@ -2919,9 +3583,11 @@ where
                 }
             }
 
-            // Forget px and outbound status for this peer
+            // Forget px and outbound status and the signed peer record of this peer
             self.px_peers.remove(&peer_id);
             self.outbound_peers.remove(&peer_id);
+            self.signed_peer_records.remove(&peer_id);
+            self.choking.remove_peer(&peer_id);
 
             // Remove peer from peer_topics and connected_peers
             // NOTE: It is possible the peer has already been removed from all mappings if it does not
@@ -3002,7 +3668,10 @@ where
         _: &Multiaddr,
         _: &Multiaddr,
     ) -> Result<THandler<Self>, ConnectionDenied> {
-        Ok(Handler::new(self.config.protocol_config()))
+        Ok(Handler::new(
+            self.config.protocol_config(),
+            self.config.slow_peer_detection(),
+        ))
     }
 
     fn handle_established_outbound_connection(
@@ -3012,16 +3681,25 @@ where
         _: &Multiaddr,
         _: Endpoint,
     ) -> Result<THandler<Self>, ConnectionDenied> {
-        Ok(Handler::new(self.config.protocol_config()))
+        Ok(Handler::new(
+            self.config.protocol_config(),
+            self.config.slow_peer_detection(),
+        ))
     }
 
     fn on_connection_handler_event(
         &mut self,
         propagation_source: PeerId,
-        _connection_id: ConnectionId,
+        connection_id: ConnectionId,
         handler_event: THandlerOutEvent<Self>,
     ) {
         match handler_event {
+            HandlerEvent::SlowPeer(slow) => {
+                self.on_slow_peer(propagation_source, connection_id, slow);
+            }
+            HandlerEvent::MessageSent { id, sent } => {
+                self.on_message_sent(propagation_source, id, sent);
+            }
             HandlerEvent::PeerKind(kind) => {
                 // We have identified the protocol this peer is using
 
@@ -3127,6 +3805,15 @@ where
                             peers,
                             backoff,
                         } => prune_msgs.push((topic_hash, peers, backoff)),
+                        ControlAction::IDontWant { message_ids } => {
+                            self.handle_idontwant(&propagation_source, message_ids)
+                        }
+                        ControlAction::Choke { topic_hash } => {
+                            self.handle_choke(&propagation_source, &topic_hash, true)
+                        }
+                        ControlAction::Unchoke { topic_hash } => {
+                            self.handle_choke(&propagation_source, &topic_hash, false)
+                        }
                     }
                 }
                 if !ihave_msgs.is_empty() {
@@ -3277,9 +3964,8 @@ fn get_random_peers_dynamic(
             .copied()
             .filter(|p| {
                 f(p) && match connected_peers.get(p) {
-                    Some(connections) if connections.kind == PeerKind::Gossipsub => true,
-                    Some(connections) if connections.kind == PeerKind::Gossipsubv1_1 => true,
-                    _ => false,
+                    Some(connections) => connections.kind.is_gossipsub(),
+                    None => false,
                 }
             })
             .collect(),
@@ -3314,6 +4000,40 @@ fn get_random_peers(
     get_random_peers_dynamic(topic_peers, connected_peers, topic_hash, |_| n, f)
 }
 
+/// Helper function to get a set of `n` fanout peers for a `topic_hash` filtered by the
+/// function `f`, preferring the peers ranked highest by [`Config::fanout_peer_rank`].
+fn get_fanout_peers(
+    config: &Config,
+    topic_peers: &HashMap<TopicHash, BTreeSet<PeerId>>,
+    connected_peers: &HashMap<PeerId, PeerConnections>,
+    topic_hash: &TopicHash,
+    n: usize,
+    f: impl FnMut(&PeerId) -> bool,
+) -> BTreeSet<PeerId> {
+    if !config.has_fanout_peer_rank_fn() {
+        return get_random_peers(topic_peers, connected_peers, topic_hash, n, f);
+    }
+
+    let mut candidates =
+        get_random_peers_dynamic(topic_peers, connected_peers, topic_hash, |len| len, f)
+            .into_iter()
+            .collect::<Vec<_>>();
+    // shuffle the candidates first, such that equally ranked peers are selected at random
+    candidates.shuffle(&mut thread_rng());
+    let mut ranked = candidates
+        .into_iter()
+        .map(|p| {
+            (
+                config.fanout_peer_rank(topic_hash, &p).unwrap_or_default(),
+                p,
+            )
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    ranked.into_iter().take(n).map(|(_, p)| p).collect()
+}
+
 /// Validates the combination of signing, privacy and message validation to ensure the
 /// configuration will not reject published messages.
 fn validate_config(
@@ -3358,6 +4078,7 @@ impl<C: DataTransform, F: TopicSubscriptionFilter> fmt::Debug for Behaviour<C, F
             .field("fanout_last_pub", &self.fanout_last_pub)
             .field("mcache", &self.mcache)
             .field("heartbeat", &self.heartbeat)
+            .field("heartbeat_interval", &self.heartbeat_interval)
             .finish()
     }
 }
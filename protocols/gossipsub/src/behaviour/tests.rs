@@ -27,6 +27,7 @@ use async_std::net::Ipv4Addr;
 use byteorder::{BigEndian, ByteOrder};
 use libp2p_core::ConnectedPoint;
 use rand::Rng;
+use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 
 #[derive(Default, Debug)]
@@ -339,6 +340,7 @@ fn proto_to_message(rpc: &proto::RPC) -> Rpc {
                             //TODO signedPeerRecord, see https://github.com/libp2p/specs/pull/217
                             PeerInfo {
                                 peer_id: Some(peer_id),
+                                signed_peer_record: None,
                             })
                 })
                 .collect::<Vec<PeerInfo>>();
@@ -761,6 +763,50 @@ fn test_fanout() {
     );
 }
 
+#[test]
+fn test_fanout_prefers_ranked_peers() {
+    let preferred = Arc::new(Mutex::new(HashSet::new()));
+    let config = ConfigBuilder::default()
+        .flood_publish(false)
+        .fanout_peer_rank_fn({
+            let preferred = preferred.clone();
+            move |_, peer| {
+                if preferred.lock().unwrap().contains(peer) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        })
+        .build()
+        .unwrap();
+
+    let fanout_topic = String::from("test_fanout");
+    let (mut gs, peers, _) = inject_nodes1()
+        .peer_no(20)
+        .topics(vec![fanout_topic.clone()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    let expected = peers
+        .iter()
+        .rev()
+        .take(gs.config.mesh_n())
+        .copied()
+        .collect::<BTreeSet<_>>();
+    preferred.lock().unwrap().extend(expected.iter().copied());
+
+    gs.unsubscribe(&Topic::new(fanout_topic.clone())).unwrap();
+    gs.publish(Topic::new(fanout_topic.clone()), vec![0; 42])
+        .unwrap();
+
+    assert_eq!(
+        gs.fanout.get(&TopicHash::from_raw(fanout_topic)),
+        Some(&expected),
+        "Fanout should contain the highest ranked peers"
+    );
+}
+
 #[test]
 /// Test the gossipsub NetworkBehaviour peer connection logic.
 fn test_inject_connected() {
@@ -1542,6 +1588,256 @@ fn do_forward_messages_to_explicit_peers() {
     );
 }
 
+#[test]
+fn do_not_forward_messages_to_peers_that_announced_them() {
+    let (mut gs, _, topic_hashes) = inject_nodes1()
+        .peer_no(20)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .create_network();
+
+    let mesh_peers = gs.mesh[&topic_hashes[0]]
+        .iter()
+        .copied()
+        .collect::<Vec<_>>();
+    let (announcing_peer, propagation_source) = (mesh_peers[0], mesh_peers[1]);
+
+    let message = RawMessage {
+        source: Some(PeerId::random()),
+        data: vec![12],
+        sequence_number: Some(0),
+        topic: topic_hashes[0].clone(),
+        signature: None,
+        key: None,
+        validated: true,
+    };
+    let msg_id = gs.config.message_id(
+        &gs.data_transform
+            .inbound_transform(message.clone())
+            .unwrap(),
+    );
+
+    gs.handle_ihave(
+        &announcing_peer,
+        vec![(topic_hashes[0].clone(), vec![msg_id.clone()])],
+    );
+    gs.handle_received_message(message, &propagation_source);
+
+    let forwarded_to = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                peer_id,
+                event: HandlerIn::Message(RpcOut::Forward(_)),
+                ..
+            } => Some(*peer_id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(forwarded_to.len(), mesh_peers.len() - 2);
+    assert!(!forwarded_to.contains(&announcing_peer));
+    assert!(!forwarded_to.contains(&propagation_source));
+}
+
+#[test]
+fn do_not_forward_validated_messages_to_peers_that_sent_duplicates() {
+    let config = ConfigBuilder::default()
+        .validate_messages()
+        .build()
+        .unwrap();
+    let (mut gs, _, topic_hashes) = inject_nodes1()
+        .peer_no(20)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let mesh_peers = gs.mesh[&topic_hashes[0]]
+        .iter()
+        .copied()
+        .collect::<Vec<_>>();
+
+    let message = RawMessage {
+        source: Some(PeerId::random()),
+        data: vec![12],
+        sequence_number: Some(0),
+        topic: topic_hashes[0].clone(),
+        signature: None,
+        key: None,
+        validated: false,
+    };
+    let msg_id = gs.config.message_id(
+        &gs.data_transform
+            .inbound_transform(message.clone())
+            .unwrap(),
+    );
+
+    // The message is received from the first mesh peer, then advertised and sent by others
+    // while it is being validated.
+    gs.handle_received_message(message.clone(), &mesh_peers[0]);
+    gs.handle_ihave(
+        &mesh_peers[1],
+        vec![(topic_hashes[0].clone(), vec![msg_id.clone()])],
+    );
+    gs.handle_received_message(message, &mesh_peers[2]);
+
+    gs.report_message_validation_result(&msg_id, &mesh_peers[0], MessageAcceptance::Accept)
+        .unwrap();
+
+    let forwarded_to = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                peer_id,
+                event: HandlerIn::Message(RpcOut::Forward(_)),
+                ..
+            } => Some(*peer_id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(forwarded_to.len(), mesh_peers.len() - 3);
+    assert!(mesh_peers[..3].iter().all(|p| !forwarded_to.contains(p)));
+}
+
+fn received_message_ids<D: DataTransform, F: TopicSubscriptionFilter>(
+    gs: &Behaviour<D, F>,
+) -> Vec<MessageId> {
+    gs.events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::GenerateEvent(Event::Message { message_id, .. }) => Some(message_id.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn validation_queue_overflow_drops_new_messages() {
+    let config = ConfigBuilder::default()
+        .validate_messages()
+        .max_pending_validations(Some(2))
+        .build()
+        .unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let mut seq = 0;
+    for _ in 0..3 {
+        gs.handle_received_message(random_message(&mut seq, &topics), &peers[0]);
+    }
+    let ids = received_message_ids(&gs);
+    assert_eq!(ids.len(), 2);
+
+    // Validating a message makes room for the next one.
+    gs.report_message_validation_result(&ids[0], &peers[0], MessageAcceptance::Accept)
+        .unwrap();
+    gs.handle_received_message(random_message(&mut seq, &topics), &peers[0]);
+    assert_eq!(received_message_ids(&gs).len(), 3);
+}
+
+#[test]
+fn validation_queue_overflow_drops_oldest_messages() {
+    let config = ConfigBuilder::default()
+        .validate_messages()
+        .max_pending_validations(Some(2))
+        .validation_queue_overflow(ValidationQueueOverflow::DropOldest)
+        .build()
+        .unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let mut seq = 0;
+    for _ in 0..3 {
+        gs.handle_received_message(random_message(&mut seq, &topics), &peers[0]);
+    }
+    let ids = received_message_ids(&gs);
+    assert_eq!(ids.len(), 3);
+
+    // The oldest message was dropped from the cache, the others can still be validated.
+    assert!(!gs
+        .report_message_validation_result(&ids[0], &peers[0], MessageAcceptance::Accept)
+        .unwrap());
+    for id in &ids[1..] {
+        assert!(gs
+            .report_message_validation_result(id, &peers[0], MessageAcceptance::Accept)
+            .unwrap());
+    }
+}
+
+#[test]
+fn validation_queue_overflow_penalizes_sender() {
+    let config = ConfigBuilder::default()
+        .validate_messages()
+        .max_pending_validations(Some(1))
+        .validation_queue_overflow(ValidationQueueOverflow::PenalizeSender)
+        .build()
+        .unwrap();
+    let peer_score_params = PeerScoreParams {
+        behaviour_penalty_weight: -1.0,
+        ..Default::default()
+    };
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .scoring(Some((peer_score_params, PeerScoreThresholds::default())))
+        .create_network();
+
+    let mut seq = 0;
+    gs.handle_received_message(random_message(&mut seq, &topics), &peers[0]);
+    gs.handle_received_message(random_message(&mut seq, &topics), &peers[1]);
+
+    assert_eq!(received_message_ids(&gs).len(), 1);
+    let peer_score = &gs.peer_score.as_ref().unwrap().0;
+    assert_eq!(peer_score.score(&peers[0]), 0.0);
+    assert!(peer_score.score(&peers[1]) < 0.0);
+}
+
+#[test]
+fn validation_queue_depth_is_recorded() {
+    let config = ConfigBuilder::default()
+        .validate_messages()
+        .max_pending_validations(Some(4))
+        .build()
+        .unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    let mut registry = Registry::default();
+    gs.metrics = Some(Metrics::new(&mut registry, MetricsConfig::default()));
+
+    let mut seq = 0;
+    for _ in 0..3 {
+        gs.handle_received_message(random_message(&mut seq, &topics), &peers[0]);
+    }
+    let ids = received_message_ids(&gs);
+    gs.report_message_validation_result(&ids[0], &peers[0], MessageAcceptance::Ignore)
+        .unwrap();
+    gs.heartbeat();
+
+    let mut encoded = String::new();
+    prometheus_client::encoding::text::encode(&mut encoded, &registry).unwrap();
+    assert!(encoded.lines().any(|line| line
+        .starts_with("messages_awaiting_validation_per_topic{")
+        && line.ends_with(" 2")));
+}
+
 #[test]
 fn explicit_peers_not_added_to_mesh_on_subscribe() {
     let (mut gs, peers, _) = inject_nodes1()
@@ -1759,6 +2055,7 @@ fn test_connect_to_px_peers_on_handle_prune() {
     for _ in 0..config.prune_peers() + 5 {
         px.push(PeerInfo {
             peer_id: Some(PeerId::random()),
+            signed_peer_record: None,
         });
     }
 
@@ -1838,6 +2135,123 @@ fn test_send_px_and_backoff_in_prune() {
     );
 }
 
+#[test]
+fn test_send_signed_peer_records_in_px() {
+    let config = ConfigBuilder::default()
+        .do_px()
+        .prune_peers(16)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    // Only records of connected peers are kept, the most recent one per peer.
+    let key = Keypair::generate_ed25519();
+    let record = PeerRecord::new(&key, vec!["/ip4/1.2.3.4/tcp/1234".parse().unwrap()]).unwrap();
+    assert!(!gs.add_signed_peer_record(record));
+
+    let key = Keypair::generate_ed25519();
+    let peer = key.public().to_peer_id();
+    gs.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+        peer_id: peer,
+        connection_id: ConnectionId::new_unchecked(0),
+        endpoint: &ConnectedPoint::Dialer {
+            address: Multiaddr::empty(),
+            role_override: Endpoint::Dialer,
+        },
+        failed_addresses: &[],
+        other_established: 0,
+    }));
+    gs.on_connection_handler_event(
+        peer,
+        ConnectionId::new_unchecked(0),
+        HandlerEvent::PeerKind(PeerKind::Gossipsubv1_1),
+    );
+    gs.handle_received_subscriptions(
+        &[Subscription {
+            action: SubscriptionAction::Subscribe,
+            topic_hash: topics[0].clone(),
+        }],
+        &peer,
+    );
+    let record = PeerRecord::new(&key, vec!["/ip4/1.2.3.4/tcp/1234".parse().unwrap()]).unwrap();
+    assert!(gs.add_signed_peer_record(record.clone()));
+    assert!(!gs.add_signed_peer_record(record.clone()));
+
+    gs.send_graft_prune(
+        HashMap::new(),
+        vec![(peers[0], vec![topics[0].clone()])]
+            .into_iter()
+            .collect(),
+        HashSet::new(),
+    );
+
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| peer_id == &peers[0]
+            && match m {
+                ControlAction::Prune { peers, .. } => peers.iter().any(|p| {
+                    p.peer_id == Some(peer) && p.signed_peer_record.as_ref() == Some(&record)
+                }),
+                _ => false,
+            }),
+        1
+    );
+
+    // The record is forgotten once the peer disconnects.
+    disconnect_peer(&mut gs, &peer);
+    assert!(!gs.signed_peer_records.contains_key(&peer));
+}
+
+#[test]
+fn test_connect_to_px_peers_with_signed_peer_records() {
+    let config = ConfigBuilder::default().prune_peers(16).build().unwrap();
+
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .create_network();
+
+    let key = Keypair::generate_ed25519();
+    let record = PeerRecord::new(&key, vec!["/ip4/1.2.3.4/tcp/1234".parse().unwrap()]).unwrap();
+
+    gs.handle_prune(
+        &peers[0],
+        vec![(
+            topics[0].clone(),
+            vec![
+                PeerInfo {
+                    peer_id: Some(record.peer_id()),
+                    signed_peer_record: Some(record.clone()),
+                },
+                // Peers without a signed peer record are not dialed.
+                PeerInfo {
+                    peer_id: Some(PeerId::random()),
+                    signed_peer_record: None,
+                },
+            ],
+            Some(config.prune_backoff().as_secs()),
+        )],
+    );
+
+    let dials: Vec<_> = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::Dial { opts } => opts.get_peer_id(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(dials, vec![record.peer_id()]);
+    assert!(gs.px_peers.contains(&record.peer_id()));
+}
+
 #[test]
 fn test_prune_backoffed_peer_on_graft() {
     let config: Config = Config::default();
@@ -2416,6 +2830,7 @@ fn test_ignore_px_from_negative_scored_peer() {
     //handle prune from single peer with px peers
     let px = vec![PeerInfo {
         peer_id: Some(PeerId::random()),
+        signed_peer_record: None,
     }];
 
     gs.handle_prune(
@@ -2998,6 +3413,7 @@ fn test_ignore_px_from_peers_below_accept_px_threshold() {
     // Handle prune from peer peers[0] with px peers
     let px = vec![PeerInfo {
         peer_id: Some(PeerId::random()),
+        signed_peer_record: None,
     }];
     gs.handle_prune(
         &peers[0],
@@ -3018,8 +3434,11 @@ fn test_ignore_px_from_peers_below_accept_px_threshold() {
     );
 
     //handle prune from peer peers[1] with px peers
+    let key = Keypair::generate_ed25519();
+    let record = PeerRecord::new(&key, vec!["/ip4/1.2.3.4/tcp/1234".parse().unwrap()]).unwrap();
     let px = vec![PeerInfo {
-        peer_id: Some(PeerId::random()),
+        peer_id: Some(record.peer_id()),
+        signed_peer_record: Some(record),
     }];
     gs.handle_prune(
         &peers[1],
@@ -5108,3 +5527,435 @@ fn test_graft_without_subscribe() {
     // We unsubscribe from the topic.
     let _ = gs.unsubscribe(&Topic::new(topic));
 }
+
+#[test]
+fn test_slow_peer_is_announced_large_messages() {
+    // The node should:
+    // - Report the slow peer and apply a behavioural penalty
+    // - Announce large messages to the slow peer via IHAVE instead of sending them
+    // - Keep sending small messages to the slow peer
+    let config = ConfigBuilder::default()
+        .slow_peer_queue_threshold(Some(10))
+        .slow_peer_max_forward_size(500)
+        .build()
+        .unwrap();
+
+    let topic = String::from("test_slow_peer");
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![topic.clone()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .scoring(Some((
+            PeerScoreParams::default(),
+            PeerScoreThresholds::default(),
+        )))
+        .create_network();
+    let slow_peer = peers[0];
+
+    gs.on_connection_handler_event(
+        slow_peer,
+        ConnectionId::new_unchecked(0),
+        HandlerEvent::SlowPeer(true),
+    );
+    assert!(gs.events.iter().any(|e| matches!(
+        e,
+        ToSwarm::GenerateEvent(Event::SlowPeer { peer_id }) if *peer_id == slow_peer
+    )));
+    assert!(gs.peer_score.as_ref().unwrap().0.score(&slow_peer) < 0.0);
+
+    let published_to = |gs: &mut Behaviour<_, _>| {
+        gs.events
+            .drain(..)
+            .filter_map(|e| match e {
+                ToSwarm::NotifyHandler {
+                    peer_id,
+                    event: HandlerIn::Message(RpcOut::Publish(_)),
+                    ..
+                } => Some(peer_id),
+                _ => None,
+            })
+            .collect::<HashSet<_>>()
+    };
+
+    let msg_id = gs
+        .publish(Topic::new(topic.clone()), vec![0; 1000])
+        .unwrap();
+    let recipients = published_to(&mut gs);
+    assert!(!recipients.contains(&slow_peer));
+    assert_eq!(recipients.len(), 2);
+    assert!(gs.control_pool[&slow_peer].iter().any(|c| matches!(
+        c,
+        ControlAction::IHave { topic_hash, message_ids }
+            if *topic_hash == topic_hashes[0] && message_ids == &vec![msg_id.clone()]
+    )));
+
+    gs.publish(Topic::new(topic.clone()), vec![1; 10]).unwrap();
+    assert!(published_to(&mut gs).contains(&slow_peer));
+
+    gs.on_connection_handler_event(
+        slow_peer,
+        ConnectionId::new_unchecked(0),
+        HandlerEvent::SlowPeer(false),
+    );
+    assert!(gs.events.iter().any(|e| matches!(
+        e,
+        ToSwarm::GenerateEvent(Event::SlowPeerRecovered { peer_id }) if *peer_id == slow_peer
+    )));
+
+    gs.publish(Topic::new(topic), vec![2; 1000]).unwrap();
+    assert!(published_to(&mut gs).contains(&slow_peer));
+}
+
+#[test]
+fn test_mesh_downgraded_event() {
+    let config = ConfigBuilder::default()
+        .mesh_downgrade_threshold(Some(0.5))
+        .build()
+        .unwrap();
+    let (mut gs, _, topic_hashes) = inject_nodes1()
+        .peer_no(0)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    for kind in [
+        PeerKind::Gossipsub,
+        PeerKind::Gossipsub,
+        PeerKind::Gossipsubv1_1,
+    ] {
+        add_peer_with_addr_and_kind(
+            &mut gs,
+            &topic_hashes,
+            false,
+            false,
+            Multiaddr::empty(),
+            Some(kind),
+        );
+    }
+    assert_eq!(
+        gs.peer_protocol_counts(),
+        HashMap::from([(PeerKind::Gossipsub, 2), (PeerKind::Gossipsubv1_1, 1)])
+    );
+
+    let downgraded_events = |gs: &mut Behaviour<_, _>| {
+        gs.events
+            .drain(..)
+            .filter_map(|e| match e {
+                ToSwarm::GenerateEvent(Event::MeshDowngraded {
+                    topic,
+                    downgraded_peers,
+                    mesh_peers,
+                }) => Some((topic, downgraded_peers, mesh_peers)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    gs.heartbeat();
+    assert_eq!(gs.mesh[&topic_hashes[0]].len(), 3);
+    assert_eq!(
+        downgraded_events(&mut gs),
+        vec![(topic_hashes[0].clone(), 2, 3)]
+    );
+
+    // The event is not repeated while the mesh stays downgraded.
+    gs.heartbeat();
+    assert!(downgraded_events(&mut gs).is_empty());
+}
+
+#[test]
+fn test_heartbeat_interval_scales_while_idle() {
+    let config = ConfigBuilder::default()
+        .heartbeat_interval(Duration::from_secs(1))
+        .max_heartbeat_interval(Some(Duration::from_secs(4)))
+        .build()
+        .unwrap();
+    let (mut gs, _, _) = inject_nodes1()
+        .peer_no(0)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    // Subscribing is activity, keeping the interval at its minimum.
+    gs.heartbeat();
+    assert_eq!(gs.heartbeat_interval(), Duration::from_secs(1));
+
+    // The interval doubles with each idle heartbeat, up to the maximum.
+    for expected in [2, 4, 4] {
+        gs.heartbeat();
+        assert_eq!(gs.heartbeat_interval(), Duration::from_secs(expected));
+    }
+
+    gs.subscribe(&Topic::new("topic2")).unwrap();
+    assert_eq!(gs.heartbeat_interval(), Duration::from_secs(1));
+    gs.heartbeat();
+    assert_eq!(gs.heartbeat_interval(), Duration::from_secs(1));
+}
+
+#[test]
+fn test_publish_awaitable_resolves_once_sent_to_min_recipients() {
+    use futures::FutureExt;
+
+    let topic = String::from("test_publish_awaitable");
+    let (mut gs, _, _) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![topic.clone()])
+        .to_subscribe(true)
+        .create_network();
+
+    let tracked = |gs: &mut Behaviour| {
+        gs.events
+            .drain(..)
+            .filter_map(|e| match e {
+                ToSwarm::NotifyHandler {
+                    peer_id,
+                    event: HandlerIn::TrackedMessage { id, .. },
+                    handler: NotifyHandler::One(connection_id),
+                } => Some((peer_id, connection_id, id)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut handle = gs
+        .publish_awaitable(Topic::new(topic.clone()), vec![1; 10], 2)
+        .unwrap();
+    let sends = tracked(&mut gs);
+    assert_eq!(sends.len(), 3);
+
+    let (peer, connection_id, id) = sends[0];
+    gs.on_connection_handler_event(
+        peer,
+        connection_id,
+        HandlerEvent::MessageSent { id, sent: true },
+    );
+    let (peer, connection_id, id) = sends[1];
+    gs.on_connection_handler_event(
+        peer,
+        connection_id,
+        HandlerEvent::MessageSent { id, sent: false },
+    );
+    assert!((&mut handle).now_or_never().is_none());
+
+    let (peer, connection_id, id) = sends[2];
+    gs.on_connection_handler_event(
+        peer,
+        connection_id,
+        HandlerEvent::MessageSent { id, sent: true },
+    );
+    assert_eq!(handle.now_or_never().unwrap().unwrap(), 2);
+    assert!(gs.pending_publishes.is_empty());
+
+    // Messages not sent before the connection closes count as failed.
+    let handle = gs
+        .publish_awaitable(Topic::new(topic), vec![2; 10], 3)
+        .unwrap();
+    let sends = tracked(&mut gs);
+    let (peer, connection_id, id) = sends[0];
+    gs.on_connection_handler_event(
+        peer,
+        connection_id,
+        HandlerEvent::MessageSent { id, sent: true },
+    );
+    disconnect_peer(&mut gs, &sends[1].0);
+    disconnect_peer(&mut gs, &sends[2].0);
+    assert!(matches!(
+        handle.now_or_never().unwrap(),
+        Err(PublishError::InsufficientRecipients { recipients: 1 })
+    ));
+}
+
+#[test]
+fn test_choke_mesh_peer_delivering_duplicates() {
+    let config = ConfigBuilder::default()
+        .choke_duplicate_ratio(Some(0.5))
+        .choke_min_deliveries(4)
+        .build()
+        .unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    assert_eq!(gs.mesh[&topics[0]].len(), 2);
+
+    // The second peer only delivers duplicates.
+    let mut seq = 0;
+    for _ in 0..4 {
+        let message = random_message(&mut seq, &topics);
+        gs.handle_received_message(message.clone(), &peers[0]);
+        gs.handle_received_message(message, &peers[1]);
+    }
+    gs.heartbeat();
+
+    let choked = |gs: &Behaviour, peer: PeerId| {
+        count_control_msgs(gs, |peer_id, m| {
+            *peer_id == peer
+                && matches!(m, ControlAction::Choke { topic_hash } if *topic_hash == topics[0])
+        })
+    };
+    assert_eq!(choked(&gs, peers[0]), 0);
+    assert_eq!(choked(&gs, peers[1]), 1);
+
+    // The choked peer is unchoked once it announces messages not received yet.
+    flush_events(&mut gs);
+    for _ in 0..gs.config.unchoke_min_announcements() {
+        let id = gs.config.message_id(&Message {
+            source: None,
+            data: vec![seq as u8],
+            sequence_number: Some(seq),
+            topic: topics[0].clone(),
+        });
+        seq += 1;
+        gs.handle_ihave(&peers[1], vec![(topics[0].clone(), vec![id])]);
+    }
+    gs.heartbeat();
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| *peer_id == peers[1]
+            && matches!(m, ControlAction::Unchoke { topic_hash } if *topic_hash == topics[0])),
+        1
+    );
+}
+
+#[test]
+fn test_announce_messages_to_choking_peer() {
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .create_network();
+    let choking_peer = peers[0];
+
+    // A choke of a peer not in the mesh is ignored.
+    gs.handle_choke(&PeerId::random(), &topics[0], true);
+    gs.handle_choke(&choking_peer, &topics[0], true);
+
+    let forwarded_to = |gs: &mut Behaviour| {
+        gs.events
+            .drain(..)
+            .filter_map(|e| match e {
+                ToSwarm::NotifyHandler {
+                    peer_id,
+                    event: HandlerIn::Message(RpcOut::Forward(_)),
+                    ..
+                } => Some(peer_id),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut seq = 0;
+    let message = random_message(&mut seq, &topics);
+    gs.handle_received_message(message, &PeerId::random());
+    assert_eq!(forwarded_to(&mut gs), vec![peers[1]]);
+    assert!(gs.control_pool[&choking_peer]
+        .iter()
+        .any(|c| matches!(c, ControlAction::IHave { topic_hash, .. } if *topic_hash == topics[0])));
+
+    gs.handle_choke(&choking_peer, &topics[0], false);
+    gs.handle_received_message(random_message(&mut seq, &topics), &PeerId::random());
+    assert_eq!(forwarded_to(&mut gs).len(), 2);
+}
+
+#[test]
+fn test_send_idontwant_for_large_messages() {
+    let config = ConfigBuilder::default()
+        .idontwant_message_size_threshold(100)
+        .build()
+        .unwrap();
+    let (mut gs, _, topics) = inject_nodes1()
+        .peer_no(0)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let v1_2_peers = (0..2)
+        .map(|_| {
+            add_peer_with_addr_and_kind(
+                &mut gs,
+                &topics,
+                false,
+                false,
+                Multiaddr::empty(),
+                Some(PeerKind::Gossipsubv1_2),
+            )
+        })
+        .collect::<Vec<_>>();
+    let v1_1_peer = add_peer(&mut gs, &topics, false, false);
+    for peer in v1_2_peers.iter().chain([&v1_1_peer]) {
+        gs.mesh.get_mut(&topics[0]).unwrap().insert(*peer);
+    }
+
+    let idontwant_sent_to = |gs: &mut Behaviour| {
+        gs.events
+            .drain(..)
+            .filter_map(|e| match e {
+                ToSwarm::NotifyHandler {
+                    peer_id,
+                    event:
+                        HandlerIn::Message(RpcOut::Control(ControlAction::IDontWant { message_ids })),
+                    ..
+                } => Some((peer_id, message_ids)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Only the v1.2 mesh peers other than the source are told about a large message.
+    let mut seq = 0;
+    let mut message = random_message(&mut seq, &topics);
+    message.data = vec![0; 200];
+    let msg_id = gs.config.message_id(
+        &gs.data_transform
+            .inbound_transform(message.clone())
+            .unwrap(),
+    );
+    gs.handle_received_message(message, &v1_2_peers[0]);
+    assert_eq!(
+        idontwant_sent_to(&mut gs),
+        vec![(v1_2_peers[1], vec![msg_id])]
+    );
+
+    // Small messages are not announced.
+    gs.handle_received_message(random_message(&mut seq, &topics), &v1_2_peers[0]);
+    assert!(idontwant_sent_to(&mut gs).is_empty());
+}
+
+#[test]
+fn test_do_not_forward_to_peers_sending_idontwant() {
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .create_network();
+
+    let mut seq = 0;
+    let message = random_message(&mut seq, &topics);
+    let msg_id = gs.config.message_id(
+        &gs.data_transform
+            .inbound_transform(message.clone())
+            .unwrap(),
+    );
+    // The peer tells us it has the message before we receive it.
+    gs.handle_idontwant(&peers[0], vec![msg_id]);
+    gs.handle_received_message(message, &peers[1]);
+
+    let forwarded_to = gs
+        .events
+        .drain(..)
+        .filter_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                peer_id,
+                event: HandlerIn::Message(RpcOut::Forward(_)),
+                ..
+            } => Some(peer_id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(forwarded_to, vec![peers[2]]);
+}
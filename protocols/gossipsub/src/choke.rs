@@ -0,0 +1,304 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The state of the choking extension (episub).
+//!
+//! A node chokes a mesh peer of a topic that mostly delivers messages the node already
+//! received from other peers. A choked peer stays in the mesh but announces the messages of
+//! the topic via IHAVE instead of sending them, until it is unchoked again.
+
+use crate::time_cache::{Entry, TimeCache};
+use crate::topic::TopicHash;
+use crate::types::MessageId;
+use instant::Instant;
+use libp2p_identity::PeerId;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::Duration;
+
+/// The deliveries of a mesh peer on a topic since the peer was last evaluated.
+#[derive(Debug, Default, Clone)]
+struct DeliveryStats {
+    /// The number of messages first delivered by the peer.
+    first: usize,
+    /// The number of messages delivered by the peer after another peer.
+    duplicates: usize,
+    /// The total time between the first delivery and the delivery by the peer of duplicates.
+    duplicate_delay: Duration,
+    /// The number of messages announced by the peer while choked that were not received yet.
+    announced_first: usize,
+}
+
+impl DeliveryStats {
+    fn deliveries(&self) -> usize {
+        self.first + self.duplicates
+    }
+
+    fn duplicate_ratio(&self) -> f64 {
+        self.duplicates as f64 / self.deliveries().max(1) as f64
+    }
+
+    fn mean_duplicate_delay(&self) -> Duration {
+        self.duplicate_delay / self.duplicates.max(1) as u32
+    }
+}
+
+/// The heuristics deciding which mesh peers are choked, see [`crate::Config::choke_duplicate_ratio`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChokeHeuristics {
+    pub(crate) duplicate_ratio: f64,
+    pub(crate) min_deliveries: usize,
+    pub(crate) max_choked_peers: usize,
+    pub(crate) unchoke_min_announcements: usize,
+}
+
+pub(crate) struct Choking {
+    /// When the messages were first received, to measure the delay of duplicates.
+    first_seen: TimeCache<MessageId, Instant>,
+    /// The deliveries of the mesh peers per topic.
+    stats: HashMap<TopicHash, HashMap<PeerId, DeliveryStats>>,
+    /// The mesh peers choked by the local node per topic.
+    choked: HashMap<TopicHash, HashSet<PeerId>>,
+    /// The mesh peers choking the local node per topic.
+    choked_by: HashMap<TopicHash, HashSet<PeerId>>,
+}
+
+impl Choking {
+    pub(crate) fn new(first_seen_ttl: Duration) -> Self {
+        Self {
+            first_seen: TimeCache::new(first_seen_ttl),
+            stats: HashMap::new(),
+            choked: HashMap::new(),
+            choked_by: HashMap::new(),
+        }
+    }
+
+    /// Records a message first delivered by a peer, `mesh_peer` if the peer is a mesh peer of
+    /// the topic.
+    pub(crate) fn on_first_delivery(
+        &mut self,
+        id: &MessageId,
+        topic: &TopicHash,
+        mesh_peer: Option<&PeerId>,
+    ) {
+        if let Entry::Vacant(entry) = self.first_seen.entry(id.clone()) {
+            entry.insert(Instant::now());
+        }
+        if let Some(peer) = mesh_peer {
+            self.peer_stats(topic, peer).first += 1;
+        }
+    }
+
+    /// Records a message delivered by a mesh peer after another peer.
+    pub(crate) fn on_duplicate(&mut self, id: &MessageId, topic: &TopicHash, peer: &PeerId) {
+        let delay = self
+            .first_seen
+            .get(id)
+            .map(|first_seen| first_seen.elapsed())
+            .unwrap_or_default();
+        let stats = self.peer_stats(topic, peer);
+        stats.duplicates += 1;
+        stats.duplicate_delay += delay;
+    }
+
+    /// Records a message not received yet that is announced by a choked mesh peer.
+    pub(crate) fn on_announcement(&mut self, topic: &TopicHash, peer: &PeerId) {
+        if self.is_choked(topic, peer) {
+            self.peer_stats(topic, peer).announced_first += 1;
+        }
+    }
+
+    fn peer_stats(&mut self, topic: &TopicHash, peer: &PeerId) -> &mut DeliveryStats {
+        self.stats
+            .entry(topic.clone())
+            .or_default()
+            .entry(*peer)
+            .or_default()
+    }
+
+    /// Returns whether the local node chokes the mesh peer on the topic.
+    pub(crate) fn is_choked(&self, topic: &TopicHash, peer: &PeerId) -> bool {
+        self.choked
+            .get(topic)
+            .is_some_and(|peers| peers.contains(peer))
+    }
+
+    /// Returns whether the mesh peer chokes the local node on the topic.
+    pub(crate) fn is_choked_by(&self, topic: &TopicHash, peer: &PeerId) -> bool {
+        self.choked_by
+            .get(topic)
+            .is_some_and(|peers| peers.contains(peer))
+    }
+
+    /// Handles a CHOKE or UNCHOKE of a mesh peer.
+    pub(crate) fn set_choked_by(&mut self, topic: &TopicHash, peer: PeerId, choked: bool) {
+        if choked {
+            self.choked_by
+                .entry(topic.clone())
+                .or_default()
+                .insert(peer);
+        } else if let Some(peers) = self.choked_by.get_mut(topic) {
+            peers.remove(&peer);
+        }
+    }
+
+    /// Evaluates the deliveries of the mesh peers, returning the peers to choke and to unchoke
+    /// per topic.
+    ///
+    /// Peers are choked once they delivered enough messages, starting with the ones delivering
+    /// the most duplicates the latest. Choked peers are unchoked once they announced enough
+    /// messages before they were received from other peers.
+    pub(crate) fn evaluate(
+        &mut self,
+        mesh: &HashMap<TopicHash, BTreeSet<PeerId>>,
+        heuristics: &ChokeHeuristics,
+    ) -> (Vec<(PeerId, TopicHash)>, Vec<(PeerId, TopicHash)>) {
+        let mut to_choke = Vec::new();
+        let mut to_unchoke = Vec::new();
+
+        for (topic, peers) in mesh {
+            let Some(stats) = self.stats.get_mut(topic) else {
+                continue;
+            };
+            let choked = self.choked.entry(topic.clone()).or_default();
+
+            let unchoked = choked
+                .iter()
+                .filter(|peer| {
+                    stats.get(peer).map_or(0, |s| s.announced_first)
+                        >= heuristics.unchoke_min_announcements
+                })
+                .copied()
+                .collect::<Vec<_>>();
+            for peer in unchoked {
+                choked.remove(&peer);
+                stats.remove(&peer);
+                to_unchoke.push((peer, topic.clone()));
+            }
+
+            let mut candidates = peers
+                .iter()
+                .filter(|peer| !choked.contains(peer))
+                .filter_map(|peer| Some((*peer, stats.get(peer)?)))
+                .filter(|(_, s)| {
+                    s.deliveries() >= heuristics.min_deliveries
+                        && s.duplicate_ratio() >= heuristics.duplicate_ratio
+                })
+                .map(|(peer, s)| (peer, s.duplicate_ratio(), s.mean_duplicate_delay()))
+                .collect::<Vec<_>>();
+            candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(b.2.cmp(&a.2)));
+
+            let num_choke = heuristics.max_choked_peers.saturating_sub(choked.len());
+            for (peer, ..) in candidates.into_iter().take(num_choke) {
+                choked.insert(peer);
+                to_choke.push((peer, topic.clone()));
+            }
+
+            // Start a new evaluation for the peers that delivered enough messages.
+            stats.retain(|peer, s| {
+                peers.contains(peer)
+                    && (choked.contains(peer) || s.deliveries() < heuristics.min_deliveries)
+            });
+        }
+
+        (to_choke, to_unchoke)
+    }
+
+    /// Forgets the state of a peer that left the mesh of a topic.
+    pub(crate) fn remove_from_mesh(&mut self, topic: &TopicHash, peer: &PeerId) {
+        for state in [&mut self.choked, &mut self.choked_by] {
+            if let Some(peers) = state.get_mut(topic) {
+                peers.remove(peer);
+            }
+        }
+        if let Some(stats) = self.stats.get_mut(topic) {
+            stats.remove(peer);
+        }
+    }
+
+    /// Forgets the state of a topic the local node left.
+    pub(crate) fn leave(&mut self, topic: &TopicHash) {
+        self.choked.remove(topic);
+        self.choked_by.remove(topic);
+        self.stats.remove(topic);
+    }
+
+    /// Forgets the state of a disconnected peer.
+    pub(crate) fn remove_peer(&mut self, peer: &PeerId) {
+        for state in [&mut self.choked, &mut self.choked_by] {
+            for peers in state.values_mut() {
+                peers.remove(peer);
+            }
+        }
+        for stats in self.stats.values_mut() {
+            stats.remove(peer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heuristics() -> ChokeHeuristics {
+        ChokeHeuristics {
+            duplicate_ratio: 0.8,
+            min_deliveries: 10,
+            max_choked_peers: 1,
+            unchoke_min_announcements: 2,
+        }
+    }
+
+    #[test]
+    fn peers_delivering_mostly_duplicates_are_choked() {
+        let topic = TopicHash::from_raw("topic");
+        let (fast, slow, slower) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let mesh = HashMap::from([(topic.clone(), BTreeSet::from([fast, slow, slower]))]);
+        let mut choking = Choking::new(Duration::from_secs(60));
+
+        for i in 0..10 {
+            let id = MessageId::from(vec![i]);
+            choking.on_first_delivery(&id, &topic, Some(&fast));
+            choking.on_duplicate(&id, &topic, &slow);
+            choking.on_duplicate(&id, &topic, &slower);
+            choking
+                .stats
+                .get_mut(&topic)
+                .unwrap()
+                .get_mut(&slower)
+                .unwrap()
+                .duplicate_delay += Duration::from_millis(10);
+        }
+
+        // Only the peer with the highest delay is choked, as at most one peer is.
+        let (to_choke, to_unchoke) = choking.evaluate(&mesh, &heuristics());
+        assert_eq!(to_choke, vec![(slower, topic.clone())]);
+        assert!(to_unchoke.is_empty());
+        assert!(choking.is_choked(&topic, &slower));
+
+        // The choked peer is unchoked once it announces messages first.
+        choking.on_announcement(&topic, &slower);
+        choking.on_announcement(&topic, &slow);
+        assert_eq!(choking.evaluate(&mesh, &heuristics()), (vec![], vec![]));
+        choking.on_announcement(&topic, &slower);
+        let (_, to_unchoke) = choking.evaluate(&mesh, &heuristics());
+        assert_eq!(to_unchoke, vec![(slower, topic.clone())]);
+        assert!(!choking.is_choked(&topic, &slower));
+    }
+}
@@ -22,8 +22,10 @@ use std::borrow::Cow;
 use std::sync::Arc;
 use std::time::Duration;
 
+use crate::choke::ChokeHeuristics;
 use crate::error::ConfigBuilderError;
 use crate::protocol::{ProtocolConfig, ProtocolId, FLOODSUB_PROTOCOL};
+use crate::topic::TopicHash;
 use crate::types::{Message, MessageId, PeerKind};
 
 use libp2p_identity::PeerId;
@@ -52,11 +54,27 @@ pub enum ValidationMode {
     None,
 }
 
+/// The behaviour when a message is received while the queue of messages awaiting validation is
+/// full, see [`Config::max_pending_validations`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationQueueOverflow {
+    /// This is the default setting. The received message is dropped without being validated.
+    #[default]
+    DropNew,
+    /// The message waiting for validation the longest is dropped in favour of the received
+    /// message. Validation results reported for the dropped message are ignored.
+    DropOldest,
+    /// Like [`ValidationQueueOverflow::DropNew`], but the peer the message was received from is
+    /// additionally penalized with a behavioural penalty, if peer scoring is enabled.
+    PenalizeSender,
+}
+
 /// Selector for custom Protocol Id
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Version {
     V1_0,
     V1_1,
+    V1_2,
 }
 
 /// Configuration parameters that define the performance of the gossipsub network.
@@ -73,6 +91,7 @@ pub struct Config {
     gossip_factor: f64,
     heartbeat_initial_delay: Duration,
     heartbeat_interval: Duration,
+    max_heartbeat_interval: Option<Duration>,
     fanout_ttl: Duration,
     check_explicit_peers_ticks: u64,
     duplicate_cache_time: Duration,
@@ -94,7 +113,20 @@ pub struct Config {
     max_ihave_length: usize,
     max_ihave_messages: usize,
     iwant_followup_time: Duration,
+    max_provenance_peers: usize,
+    idontwant_message_size_threshold: usize,
     published_message_ids_cache_time: Duration,
+    slow_peer_queue_threshold: Option<usize>,
+    slow_peer_detection_time: Duration,
+    slow_peer_max_forward_size: usize,
+    max_pending_validations: Option<usize>,
+    validation_queue_overflow: ValidationQueueOverflow,
+    mesh_downgrade_threshold: Option<f64>,
+    fanout_peer_rank_fn: Option<Arc<dyn Fn(&TopicHash, &PeerId) -> f64 + Send + Sync + 'static>>,
+    choke_duplicate_ratio: Option<f64>,
+    choke_min_deliveries: usize,
+    max_choked_peers: usize,
+    unchoke_min_announcements: usize,
 }
 
 impl Config {
@@ -102,6 +134,11 @@ impl Config {
         self.protocol.clone()
     }
 
+    pub(crate) fn slow_peer_detection(&self) -> Option<(usize, Duration)> {
+        self.slow_peer_queue_threshold
+            .map(|threshold| (threshold, self.slow_peer_detection_time))
+    }
+
     // Overlay network parameters.
     /// Number of heartbeats to keep in the `memcache` (default is 5).
     pub fn history_length(&self) -> usize {
@@ -161,6 +198,15 @@ impl Config {
         self.heartbeat_interval
     }
 
+    /// The maximum time between heartbeats while the node is idle. If this is set, the time
+    /// between heartbeats doubles with each heartbeat without activity, i.e. without publishing,
+    /// subscription changes or mesh changes, up to this maximum, and falls back to
+    /// [`Config::heartbeat_interval`] on activity. This saves power on constrained nodes, at the
+    /// cost of slower mesh maintenance and gossip while idle. The default is None.
+    pub fn max_heartbeat_interval(&self) -> Option<Duration> {
+        self.max_heartbeat_interval
+    }
+
     /// Time to live for fanout peers (default is 60 seconds).
     pub fn fanout_ttl(&self) -> Duration {
         self.fanout_ttl
@@ -227,8 +273,8 @@ impl Config {
     /// Whether Peer eXchange is enabled; this should be enabled in bootstrappers and other well
     /// connected/trusted nodes. The default is false.
     ///
-    /// Note: Peer exchange is not implemented today, see
-    /// <https://github.com/libp2p/rust-libp2p/issues/2398>.
+    /// Pruned peers are proposed the signed peer records of other peers added via
+    /// [`crate::Behaviour::add_signed_peer_record`].
     pub fn do_px(&self) -> bool {
         self.do_px
     }
@@ -237,8 +283,9 @@ impl Config {
     /// When we prune a peer that's eligible for PX (has a good score, etc), we will try to
     /// send them signed peer records for up to `prune_peers` other peers that we
     /// know of. It is recommended that this value is larger than `mesh_n_high` so that the pruned
-    /// peer can reliably form a full mesh. The default is typically 16 however until signed
-    /// records are spec'd this is disabled and set to 0.
+    /// peer can reliably form a full mesh. At most as many peers received in peer exchange are
+    /// dialed, and only if they come with a verified signed peer record. The default is 0, i.e.
+    /// peer exchange is disabled.
     pub fn prune_peers(&self) -> usize {
         self.prune_peers
     }
@@ -341,15 +388,132 @@ impl Config {
         self.iwant_followup_time
     }
 
+    /// The maximum number of peers to remember per message as already having the message, i.e.
+    /// peers that sent us a duplicate or advertised the message via IHAVE or IDONTWANT. These
+    /// peers are excluded when forwarding the message. The default is 32.
+    pub fn max_provenance_peers(&self) -> usize {
+        self.max_provenance_peers
+    }
+
+    /// The size in bytes from which received messages are announced via IDONTWANT to the
+    /// gossipsub v1.2 mesh peers of their topic, such that the peers don't send the messages
+    /// they have not sent yet. The size is the one of the encoded message. The default is 1000.
+    pub fn idontwant_message_size_threshold(&self) -> usize {
+        self.idontwant_message_size_threshold
+    }
+
     /// Enable support for flooodsub peers. Default false.
     pub fn support_floodsub(&self) -> bool {
         self.protocol.protocol_ids.contains(&FLOODSUB_PROTOCOL)
     }
 
+    /// Whether topics are compacted on the wire for peers supporting it, see
+    /// [`ConfigBuilder::compact_topics`].
+    pub fn compact_topics(&self) -> bool {
+        self.protocol.compact_topics
+    }
+
     /// Published message ids time cache duration. The default is 10 seconds.
     pub fn published_message_ids_cache_time(&self) -> Duration {
         self.published_message_ids_cache_time
     }
+
+    /// The number of queued outbound RPCs to a peer at which its send queue is considered backed
+    /// up. A peer whose send queue stays backed up for [`Config::slow_peer_detection_time`] is
+    /// considered slow, see [`Config::slow_peer_max_forward_size`]. If this is unset, slow peers
+    /// are not detected. The default is None.
+    pub fn slow_peer_queue_threshold(&self) -> Option<usize> {
+        self.slow_peer_queue_threshold
+    }
+
+    /// The time the send queue of a peer needs to stay backed up for the peer to be considered
+    /// slow. The default is 5 seconds.
+    pub fn slow_peer_detection_time(&self) -> Duration {
+        self.slow_peer_detection_time
+    }
+
+    /// The maximum size in bytes of messages that are still published and forwarded to slow
+    /// peers. Larger messages are announced to slow peers via IHAVE instead, letting them request
+    /// the messages via IWANT. The default is 1024 bytes.
+    pub fn slow_peer_max_forward_size(&self) -> usize {
+        self.slow_peer_max_forward_size
+    }
+
+    /// The maximum number of received messages awaiting validation via
+    /// [`crate::Behaviour::report_message_validation_result()`] if [`Config::validate_messages`]
+    /// is set. Messages received beyond this limit are handled according to
+    /// [`Config::validation_queue_overflow`]. If this is unset, the number of messages awaiting
+    /// validation is only bounded by the `memcache`. The default is None.
+    pub fn max_pending_validations(&self) -> Option<usize> {
+        self.max_pending_validations
+    }
+
+    /// The behaviour when a message is received while [`Config::max_pending_validations`]
+    /// messages await validation. The default is [`ValidationQueueOverflow::DropNew`].
+    pub fn validation_queue_overflow(&self) -> ValidationQueueOverflow {
+        self.validation_queue_overflow
+    }
+
+    /// The fraction of the mesh peers of a topic on an older protocol than gossipsub v1.1 at
+    /// which [`crate::Event::MeshDowngraded`] is emitted, e.g. as features like peer exchange
+    /// and scoring are ineffective in such a mesh. If this is unset, no such events are emitted.
+    /// The default is None.
+    pub fn mesh_downgrade_threshold(&self) -> Option<f64> {
+        self.mesh_downgrade_threshold
+    }
+
+    /// The rank of a candidate for the fanout peers of a topic, see
+    /// [`ConfigBuilder::fanout_peer_rank_fn`]. `None` if fanout peers are selected at random.
+    pub fn fanout_peer_rank(&self, topic: &TopicHash, peer: &PeerId) -> Option<f64> {
+        self.fanout_peer_rank_fn
+            .as_ref()
+            .map(|rank_fn| rank_fn(topic, peer))
+    }
+
+    pub(crate) fn has_fanout_peer_rank_fn(&self) -> bool {
+        self.fanout_peer_rank_fn.is_some()
+    }
+
+    /// The fraction of the messages delivered by a mesh peer of a topic that need to be
+    /// duplicates for the peer to be choked, i.e. asked to announce the messages of the topic
+    /// via IHAVE instead of sending them, while staying in the mesh. If this is unset, mesh peers
+    /// are not choked. The default is None.
+    ///
+    /// Choking is an extension (episub) ignored by peers not supporting it. Note that choked
+    /// peers deliver fewer messages, which counts against them if the mesh message deliveries of
+    /// the peer score are used.
+    pub fn choke_duplicate_ratio(&self) -> Option<f64> {
+        self.choke_duplicate_ratio
+    }
+
+    /// The number of messages a mesh peer needs to deliver before it is considered for choking,
+    /// see [`Config::choke_duplicate_ratio`]. The default is 32.
+    pub fn choke_min_deliveries(&self) -> usize {
+        self.choke_min_deliveries
+    }
+
+    /// The maximum number of mesh peers of a topic choked at the same time. Of the peers
+    /// exceeding [`Config::choke_duplicate_ratio`], the ones delivering duplicates the latest
+    /// are choked first. The default is 2.
+    pub fn max_choked_peers(&self) -> usize {
+        self.max_choked_peers
+    }
+
+    /// The number of messages not yet received a choked mesh peer needs to announce via IHAVE
+    /// to be unchoked. The default is 4.
+    pub fn unchoke_min_announcements(&self) -> usize {
+        self.unchoke_min_announcements
+    }
+
+    pub(crate) fn choke_heuristics(&self) -> Option<ChokeHeuristics> {
+        self.choke_duplicate_ratio
+            .map(|duplicate_ratio| ChokeHeuristics {
+                duplicate_ratio,
+                min_deliveries: self.choke_min_deliveries,
+                max_choked_peers: self.max_choked_peers,
+                unchoke_min_announcements: self.unchoke_min_announcements,
+            })
+    }
 }
 
 impl Default for Config {
@@ -382,6 +546,7 @@ impl Default for ConfigBuilder {
                 gossip_factor: 0.25,
                 heartbeat_initial_delay: Duration::from_secs(5),
                 heartbeat_interval: Duration::from_secs(1),
+                max_heartbeat_interval: None,
                 fanout_ttl: Duration::from_secs(60),
                 check_explicit_peers_ticks: 300,
                 duplicate_cache_time: Duration::from_secs(60),
@@ -402,7 +567,7 @@ impl Default for ConfigBuilder {
                 }),
                 allow_self_origin: false,
                 do_px: false,
-                prune_peers: 0, // NOTE: Increasing this currently has little effect until Signed records are implemented.
+                prune_peers: 0,
                 prune_backoff: Duration::from_secs(60),
                 unsubscribe_backoff: Duration::from_secs(10),
                 backoff_slack: 1,
@@ -416,7 +581,20 @@ impl Default for ConfigBuilder {
                 max_ihave_length: 5000,
                 max_ihave_messages: 10,
                 iwant_followup_time: Duration::from_secs(3),
+                max_provenance_peers: 32,
+                idontwant_message_size_threshold: 1000,
                 published_message_ids_cache_time: Duration::from_secs(10),
+                slow_peer_queue_threshold: None,
+                slow_peer_detection_time: Duration::from_secs(5),
+                slow_peer_max_forward_size: 1024,
+                max_pending_validations: None,
+                validation_queue_overflow: ValidationQueueOverflow::default(),
+                mesh_downgrade_threshold: None,
+                fanout_peer_rank_fn: None,
+                choke_duplicate_ratio: None,
+                choke_min_deliveries: 32,
+                max_choked_peers: 2,
+                unchoke_min_announcements: 4,
             },
             invalid_protocol: false,
         }
@@ -433,7 +611,8 @@ impl From<Config> for ConfigBuilder {
 }
 
 impl ConfigBuilder {
-    /// The protocol id prefix to negotiate this protocol (default is `/meshsub/1.1.0` and `/meshsub/1.0.0`).
+    /// The protocol id prefix to negotiate this protocol (default is `/meshsub/1.2.0`, `/meshsub/1.1.0`
+    /// and `/meshsub/1.0.0`).
     pub fn protocol_id_prefix(
         &mut self,
         protocol_id_prefix: impl Into<Cow<'static, str>>,
@@ -441,18 +620,26 @@ impl ConfigBuilder {
         let cow = protocol_id_prefix.into();
 
         match (
+            StreamProtocol::try_from_owned(format!("{}/1.2.0", cow)),
             StreamProtocol::try_from_owned(format!("{}/1.1.0", cow)),
             StreamProtocol::try_from_owned(format!("{}/1.0.0", cow)),
         ) {
-            (Ok(p1), Ok(p2)) => {
+            (Ok(p0), Ok(p1), Ok(p2)) => {
                 self.config.protocol.protocol_ids = vec![
+                    ProtocolId {
+                        protocol: p0,
+                        kind: PeerKind::Gossipsubv1_2,
+                        compact_topics: false,
+                    },
                     ProtocolId {
                         protocol: p1,
                         kind: PeerKind::Gossipsubv1_1,
+                        compact_topics: false,
                     },
                     ProtocolId {
                         protocol: p2,
                         kind: PeerKind::Gossipsub,
+                        compact_topics: false,
                     },
                 ]
             }
@@ -477,9 +664,11 @@ impl ConfigBuilder {
                 self.config.protocol.protocol_ids = vec![ProtocolId {
                     protocol,
                     kind: match custom_id_version {
+                        Version::V1_2 => PeerKind::Gossipsubv1_2,
                         Version::V1_1 => PeerKind::Gossipsubv1_1,
                         Version::V1_0 => PeerKind::Gossipsub,
                     },
+                    compact_topics: false,
                 }]
             }
             _ => {
@@ -558,6 +747,19 @@ impl ConfigBuilder {
         self
     }
 
+    /// The maximum time between heartbeats while the node is idle. If this is set, the time
+    /// between heartbeats doubles with each heartbeat without activity, i.e. without publishing,
+    /// subscription changes or mesh changes, up to this maximum, and falls back to
+    /// [`Config::heartbeat_interval`] on activity. This saves power on constrained nodes, at the
+    /// cost of slower mesh maintenance and gossip while idle. The default is None.
+    pub fn max_heartbeat_interval(
+        &mut self,
+        max_heartbeat_interval: Option<Duration>,
+    ) -> &mut Self {
+        self.config.max_heartbeat_interval = max_heartbeat_interval;
+        self
+    }
+
     /// The number of heartbeat ticks until we recheck the connection to explicit peers and
     /// reconnecting if necessary (default 300).
     pub fn check_explicit_peers_ticks(&mut self, check_explicit_peers_ticks: u64) -> &mut Self {
@@ -621,8 +823,8 @@ impl ConfigBuilder {
     /// Enables Peer eXchange. This should be enabled in bootstrappers and other well
     /// connected/trusted nodes. The default is false.
     ///
-    /// Note: Peer exchange is not implemented today, see
-    /// <https://github.com/libp2p/rust-libp2p/issues/2398>.
+    /// Pruned peers are proposed the signed peer records of other peers added via
+    /// [`crate::Behaviour::add_signed_peer_record`].
     pub fn do_px(&mut self) -> &mut Self {
         self.config.do_px = true;
         self
@@ -631,9 +833,11 @@ impl ConfigBuilder {
     /// Controls the number of peers to include in prune Peer eXchange.
     ///
     /// When we prune a peer that's eligible for PX (has a good score, etc), we will try to
-    /// send them signed peer records for up to [`Self::prune_peers] other peers that we
+    /// send them signed peer records for up to [`Self::prune_peers`] other peers that we
     /// know of. It is recommended that this value is larger than [`Self::mesh_n_high`] so that the
-    /// pruned peer can reliably form a full mesh. The default is 16.
+    /// pruned peer can reliably form a full mesh. At most as many peers received in peer exchange
+    /// are dialed, and only if they come with a verified signed peer record. The default is 0,
+    /// i.e. peer exchange is disabled.
     pub fn prune_peers(&mut self, prune_peers: usize) -> &mut Self {
         self.config.prune_peers = prune_peers;
         self
@@ -758,6 +962,34 @@ impl ConfigBuilder {
         self
     }
 
+    /// The maximum number of peers to remember per message as already having the message, i.e.
+    /// peers that sent us a duplicate or advertised the message via IHAVE or IDONTWANT. These
+    /// peers are excluded when forwarding the message. The default is 32.
+    pub fn max_provenance_peers(&mut self, max_provenance_peers: usize) -> &mut Self {
+        self.config.max_provenance_peers = max_provenance_peers;
+        self
+    }
+
+    /// The size in bytes from which received messages are announced via IDONTWANT to the
+    /// gossipsub v1.2 mesh peers of their topic, such that the peers don't send the messages
+    /// they have not sent yet. The size is the one of the encoded message. The default is 1000.
+    pub fn idontwant_message_size_threshold(&mut self, threshold: usize) -> &mut Self {
+        self.config.idontwant_message_size_threshold = threshold;
+        self
+    }
+
+    /// Enables compact topics on the wire (disabled by default).
+    ///
+    /// Each gossipsub protocol is additionally offered with a `/compact-topics` suffix. On streams
+    /// negotiated with such a protocol, the topic of a message is sent in full only the first time,
+    /// together with a short alias which replaces the topic in subsequent messages. This reduces
+    /// the per-message overhead for long topic names. Peers without the extension negotiate the
+    /// regular protocols and receive full topics.
+    pub fn compact_topics(&mut self) -> &mut Self {
+        self.config.protocol.compact_topics = true;
+        self
+    }
+
     /// Enable support for flooodsub peers.
     pub fn support_floodsub(&mut self) -> &mut Self {
         if self
@@ -782,6 +1014,105 @@ impl ConfigBuilder {
         self
     }
 
+    /// The number of queued outbound RPCs to a peer at which its send queue is considered backed
+    /// up. A peer whose send queue stays backed up for [`Config::slow_peer_detection_time`] is
+    /// considered slow, see [`Config::slow_peer_max_forward_size`]. If this is unset, slow peers
+    /// are not detected. The default is None.
+    pub fn slow_peer_queue_threshold(&mut self, threshold: Option<usize>) -> &mut Self {
+        self.config.slow_peer_queue_threshold = threshold;
+        self
+    }
+
+    /// The time the send queue of a peer needs to stay backed up for the peer to be considered
+    /// slow. The default is 5 seconds.
+    pub fn slow_peer_detection_time(&mut self, slow_peer_detection_time: Duration) -> &mut Self {
+        self.config.slow_peer_detection_time = slow_peer_detection_time;
+        self
+    }
+
+    /// The maximum size in bytes of messages that are still published and forwarded to slow
+    /// peers. Larger messages are announced to slow peers via IHAVE instead, letting them request
+    /// the messages via IWANT. The default is 1024 bytes.
+    pub fn slow_peer_max_forward_size(&mut self, slow_peer_max_forward_size: usize) -> &mut Self {
+        self.config.slow_peer_max_forward_size = slow_peer_max_forward_size;
+        self
+    }
+
+    /// The maximum number of received messages awaiting validation via
+    /// [`crate::Behaviour::report_message_validation_result()`] if [`Config::validate_messages`]
+    /// is set. Messages received beyond this limit are handled according to
+    /// [`Config::validation_queue_overflow`]. If this is unset, the number of messages awaiting
+    /// validation is only bounded by the `memcache`. The default is None.
+    pub fn max_pending_validations(&mut self, max: Option<usize>) -> &mut Self {
+        self.config.max_pending_validations = max;
+        self
+    }
+
+    /// The behaviour when a message is received while [`Config::max_pending_validations`]
+    /// messages await validation. The default is [`ValidationQueueOverflow::DropNew`].
+    pub fn validation_queue_overflow(&mut self, overflow: ValidationQueueOverflow) -> &mut Self {
+        self.config.validation_queue_overflow = overflow;
+        self
+    }
+
+    /// The fraction of the mesh peers of a topic on an older protocol than gossipsub v1.1 at
+    /// which [`crate::Event::MeshDowngraded`] is emitted, e.g. as features like peer exchange
+    /// and scoring are ineffective in such a mesh. If this is unset, no such events are emitted.
+    /// The default is None.
+    pub fn mesh_downgrade_threshold(&mut self, threshold: Option<f64>) -> &mut Self {
+        self.config.mesh_downgrade_threshold = threshold;
+        self
+    }
+
+    /// A user-defined function ranking the candidates for the fanout peers of a topic, i.e. the
+    /// peers messages are published to on topics without a mesh. Candidates with a higher rank
+    /// are selected first, e.g. based on their latency, stake or region, ties are broken at
+    /// random. Candidates still need to satisfy the publish threshold of the peer score.
+    ///
+    /// By default, fanout peers are selected at random.
+    pub fn fanout_peer_rank_fn<F>(&mut self, rank_fn: F) -> &mut Self
+    where
+        F: Fn(&TopicHash, &PeerId) -> f64 + Send + Sync + 'static,
+    {
+        self.config.fanout_peer_rank_fn = Some(Arc::new(rank_fn));
+        self
+    }
+
+    /// The fraction of the messages delivered by a mesh peer of a topic that need to be
+    /// duplicates for the peer to be choked, i.e. asked to announce the messages of the topic
+    /// via IHAVE instead of sending them, while staying in the mesh. If this is unset, mesh peers
+    /// are not choked. The default is None.
+    ///
+    /// Choking is an extension (episub) ignored by peers not supporting it. Note that choked
+    /// peers deliver fewer messages, which counts against them if the mesh message deliveries of
+    /// the peer score are used.
+    pub fn choke_duplicate_ratio(&mut self, ratio: Option<f64>) -> &mut Self {
+        self.config.choke_duplicate_ratio = ratio;
+        self
+    }
+
+    /// The number of messages a mesh peer needs to deliver before it is considered for choking,
+    /// see [`Config::choke_duplicate_ratio`]. The default is 32.
+    pub fn choke_min_deliveries(&mut self, min_deliveries: usize) -> &mut Self {
+        self.config.choke_min_deliveries = min_deliveries;
+        self
+    }
+
+    /// The maximum number of mesh peers of a topic choked at the same time. Of the peers
+    /// exceeding [`Config::choke_duplicate_ratio`], the ones delivering duplicates the latest
+    /// are choked first. The default is 2.
+    pub fn max_choked_peers(&mut self, max_choked_peers: usize) -> &mut Self {
+        self.config.max_choked_peers = max_choked_peers;
+        self
+    }
+
+    /// The number of messages not yet received a choked mesh peer needs to announce via IHAVE
+    /// to be unchoked. The default is 4.
+    pub fn unchoke_min_announcements(&mut self, min_announcements: usize) -> &mut Self {
+        self.config.unchoke_min_announcements = min_announcements;
+        self
+    }
+
     /// Constructs a [`Config`] from the given configuration and validates the settings.
     pub fn build(&self) -> Result<Config, ConfigBuilderError> {
         // check all constraints on config
@@ -813,6 +1144,14 @@ impl ConfigBuilder {
             return Err(ConfigBuilderError::InvalidProtocol);
         }
 
+        if self
+            .config
+            .max_heartbeat_interval
+            .is_some_and(|max| max < self.config.heartbeat_interval)
+        {
+            return Err(ConfigBuilderError::HeartbeatIntervalRangeInvalid);
+        }
+
         Ok(self.config.clone())
     }
 }
@@ -831,6 +1170,7 @@ impl std::fmt::Debug for Config {
         let _ = builder.field("gossip_factor", &self.gossip_factor);
         let _ = builder.field("heartbeat_initial_delay", &self.heartbeat_initial_delay);
         let _ = builder.field("heartbeat_interval", &self.heartbeat_interval);
+        let _ = builder.field("max_heartbeat_interval", &self.max_heartbeat_interval);
         let _ = builder.field("fanout_ttl", &self.fanout_ttl);
         let _ = builder.field("duplicate_cache_time", &self.duplicate_cache_time);
         let _ = builder.field("validate_messages", &self.validate_messages);
@@ -848,10 +1188,29 @@ impl std::fmt::Debug for Config {
         let _ = builder.field("max_ihave_length", &self.max_ihave_length);
         let _ = builder.field("max_ihave_messages", &self.max_ihave_messages);
         let _ = builder.field("iwant_followup_time", &self.iwant_followup_time);
+        let _ = builder.field("max_provenance_peers", &self.max_provenance_peers);
+        let _ = builder.field(
+            "idontwant_message_size_threshold",
+            &self.idontwant_message_size_threshold,
+        );
         let _ = builder.field(
             "published_message_ids_cache_time",
             &self.published_message_ids_cache_time,
         );
+        let _ = builder.field("slow_peer_queue_threshold", &self.slow_peer_queue_threshold);
+        let _ = builder.field("slow_peer_detection_time", &self.slow_peer_detection_time);
+        let _ = builder.field(
+            "slow_peer_max_forward_size",
+            &self.slow_peer_max_forward_size,
+        );
+        let _ = builder.field("max_pending_validations", &self.max_pending_validations);
+        let _ = builder.field("validation_queue_overflow", &self.validation_queue_overflow);
+        let _ = builder.field("mesh_downgrade_threshold", &self.mesh_downgrade_threshold);
+        let _ = builder.field("fanout_peer_rank_fn", &self.fanout_peer_rank_fn.is_some());
+        let _ = builder.field("choke_duplicate_ratio", &self.choke_duplicate_ratio);
+        let _ = builder.field("choke_min_deliveries", &self.choke_min_deliveries);
+        let _ = builder.field("max_choked_peers", &self.max_choked_peers);
+        let _ = builder.field("unchoke_min_announcements", &self.unchoke_min_announcements);
         builder.finish()
     }
 }
@@ -925,19 +1284,25 @@ mod test {
 
         let protocol_ids = protocol_config.protocol_info();
 
-        assert_eq!(protocol_ids.len(), 2);
+        assert_eq!(protocol_ids.len(), 3);
 
         assert_eq!(
             protocol_ids[0].protocol,
-            StreamProtocol::new("/purple/1.1.0")
+            StreamProtocol::new("/purple/1.2.0")
         );
-        assert_eq!(protocol_ids[0].kind, PeerKind::Gossipsubv1_1);
+        assert_eq!(protocol_ids[0].kind, PeerKind::Gossipsubv1_2);
 
         assert_eq!(
             protocol_ids[1].protocol,
+            StreamProtocol::new("/purple/1.1.0")
+        );
+        assert_eq!(protocol_ids[1].kind, PeerKind::Gossipsubv1_1);
+
+        assert_eq!(
+            protocol_ids[2].protocol,
             StreamProtocol::new("/purple/1.0.0")
         );
-        assert_eq!(protocol_ids[1].kind, PeerKind::Gossipsub);
+        assert_eq!(protocol_ids[2].kind, PeerKind::Gossipsub);
     }
 
     #[test]
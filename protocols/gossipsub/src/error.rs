@@ -36,6 +36,12 @@ pub enum PublishError {
     MessageTooLarge,
     /// The compression algorithm failed.
     TransformFailed(std::io::Error),
+    /// The message was sent to fewer peers than requested via
+    /// [`crate::Behaviour::publish_awaitable`].
+    InsufficientRecipients {
+        /// The number of peers the message was sent to.
+        recipients: usize,
+    },
 }
 
 impl std::fmt::Display for PublishError {
@@ -136,6 +142,8 @@ pub enum ConfigBuilderError {
     UnsubscribeBackoffIsZero,
     /// Invalid protocol
     InvalidProtocol,
+    /// The inequality doesn't hold heartbeat_interval <= max_heartbeat_interval
+    HeartbeatIntervalRangeInvalid,
 }
 
 impl std::error::Error for ConfigBuilderError {}
@@ -151,6 +159,7 @@ impl std::fmt::Display for ConfigBuilderError {
             Self::MeshOutboundInvalid => write!(f, "The inequality doesn't hold mesh_outbound_min <= self.config.mesh_n / 2"),
             Self::UnsubscribeBackoffIsZero => write!(f, "unsubscribe_backoff is zero"),
             Self::InvalidProtocol => write!(f, "Invalid protocol"),
+            Self::HeartbeatIntervalRangeInvalid => write!(f, "The inequality doesn't hold heartbeat_interval <= max_heartbeat_interval"),
         }
     }
 }
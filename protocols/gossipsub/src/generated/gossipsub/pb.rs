@@ -104,6 +104,7 @@ pub struct Message {
     pub topic: String,
     pub signature: Option<Vec<u8>>,
     pub key: Option<Vec<u8>>,
+    pub topic_alias: Option<u64>,
 }
 
 impl<'a> MessageRead<'a> for Message {
@@ -117,6 +118,7 @@ impl<'a> MessageRead<'a> for Message {
                 Ok(34) => msg.topic = r.read_string(bytes)?.to_owned(),
                 Ok(42) => msg.signature = Some(r.read_bytes(bytes)?.to_owned()),
                 Ok(50) => msg.key = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(800) => msg.topic_alias = Some(r.read_uint64(bytes)?),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -134,6 +136,7 @@ impl MessageWrite for Message {
         + 1 + sizeof_len((&self.topic).len())
         + self.signature.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
         + self.key.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.topic_alias.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
@@ -143,6 +146,7 @@ impl MessageWrite for Message {
         w.write_with_tag(34, |w| w.write_string(&**&self.topic))?;
         if let Some(ref s) = self.signature { w.write_with_tag(42, |w| w.write_bytes(&**s))?; }
         if let Some(ref s) = self.key { w.write_with_tag(50, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.topic_alias { w.write_with_tag(800, |w| w.write_uint64(*s))?; }
         Ok(())
     }
 }
@@ -154,6 +158,9 @@ pub struct ControlMessage {
     pub iwant: Vec<gossipsub::pb::ControlIWant>,
     pub graft: Vec<gossipsub::pb::ControlGraft>,
     pub prune: Vec<gossipsub::pb::ControlPrune>,
+    pub idontwant: Vec<gossipsub::pb::ControlIDontWant>,
+    pub choke: Vec<gossipsub::pb::ControlChoke>,
+    pub unchoke: Vec<gossipsub::pb::ControlUnChoke>,
 }
 
 impl<'a> MessageRead<'a> for ControlMessage {
@@ -165,6 +172,9 @@ impl<'a> MessageRead<'a> for ControlMessage {
                 Ok(18) => msg.iwant.push(r.read_message::<gossipsub::pb::ControlIWant>(bytes)?),
                 Ok(26) => msg.graft.push(r.read_message::<gossipsub::pb::ControlGraft>(bytes)?),
                 Ok(34) => msg.prune.push(r.read_message::<gossipsub::pb::ControlPrune>(bytes)?),
+                Ok(42) => msg.idontwant.push(r.read_message::<gossipsub::pb::ControlIDontWant>(bytes)?),
+                Ok(802) => msg.choke.push(r.read_message::<gossipsub::pb::ControlChoke>(bytes)?),
+                Ok(810) => msg.unchoke.push(r.read_message::<gossipsub::pb::ControlUnChoke>(bytes)?),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -180,6 +190,9 @@ impl MessageWrite for ControlMessage {
         + self.iwant.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
         + self.graft.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
         + self.prune.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.idontwant.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.choke.iter().map(|s| 2 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.unchoke.iter().map(|s| 2 + sizeof_len((s).get_size())).sum::<usize>()
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
@@ -187,6 +200,9 @@ impl MessageWrite for ControlMessage {
         for s in &self.iwant { w.write_with_tag(18, |w| w.write_message(s))?; }
         for s in &self.graft { w.write_with_tag(26, |w| w.write_message(s))?; }
         for s in &self.prune { w.write_with_tag(34, |w| w.write_message(s))?; }
+        for s in &self.idontwant { w.write_with_tag(42, |w| w.write_message(s))?; }
+        for s in &self.choke { w.write_with_tag(802, |w| w.write_message(s))?; }
+        for s in &self.unchoke { w.write_with_tag(810, |w| w.write_message(s))?; }
         Ok(())
     }
 }
@@ -331,6 +347,102 @@ impl MessageWrite for ControlPrune {
     }
 }
 
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ControlIDontWant {
+    pub message_ids: Vec<Vec<u8>>,
+}
+
+impl<'a> MessageRead<'a> for ControlIDontWant {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.message_ids.push(r.read_bytes(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ControlIDontWant {
+    fn get_size(&self) -> usize {
+        0
+        + self.message_ids.iter().map(|s| 1 + sizeof_len((s).len())).sum::<usize>()
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        for s in &self.message_ids { w.write_with_tag(10, |w| w.write_bytes(&**s))?; }
+        Ok(())
+    }
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ControlChoke {
+    pub topic_id: Option<String>,
+}
+
+impl<'a> MessageRead<'a> for ControlChoke {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.topic_id = Some(r.read_string(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ControlChoke {
+    fn get_size(&self) -> usize {
+        0
+        + self.topic_id.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.topic_id { w.write_with_tag(10, |w| w.write_string(&**s))?; }
+        Ok(())
+    }
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ControlUnChoke {
+    pub topic_id: Option<String>,
+}
+
+impl<'a> MessageRead<'a> for ControlUnChoke {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.topic_id = Some(r.read_string(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ControlUnChoke {
+    fn get_size(&self) -> usize {
+        0
+        + self.topic_id.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.topic_id { w.write_with_tag(10, |w| w.write_string(&**s))?; }
+        Ok(())
+    }
+}
+
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct PeerInfo {
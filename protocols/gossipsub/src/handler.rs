@@ -20,7 +20,7 @@
 
 use crate::protocol::{GossipsubCodec, ProtocolConfig};
 use crate::rpc_proto::proto;
-use crate::types::{PeerKind, RawMessage, Rpc, RpcOut};
+use crate::types::{PeerKind, PublishId, RawMessage, Rpc, RpcOut};
 use crate::ValidationError;
 use asynchronous_codec::Framed;
 use futures::future::Either;
@@ -35,8 +35,10 @@ use libp2p_swarm::handler::{
 use libp2p_swarm::Stream;
 use smallvec::SmallVec;
 use std::{
+    collections::VecDeque,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 /// The event emitted by the Handler. This informs the behaviour of various events created
@@ -55,6 +57,12 @@ pub enum HandlerEvent {
     /// An inbound or outbound substream has been established with the peer and this informs over
     /// which protocol. This message only occurs once per connection.
     PeerKind(PeerKind),
+    /// The send queue of the connection has been backed up for longer than the slow peer
+    /// detection time (`true`) or has been drained again (`false`).
+    SlowPeer(bool),
+    /// A message sent via [`HandlerIn::TrackedMessage`] was either flushed to the peer (`true`)
+    /// or dropped (`false`).
+    MessageSent { id: PublishId, sent: bool },
 }
 
 /// A message sent from the behaviour to the handler.
@@ -63,6 +71,9 @@ pub enum HandlerEvent {
 pub enum HandlerIn {
     /// A gossipsub message to send.
     Message(RpcOut),
+    /// A gossipsub message to send, reporting whether it was sent via
+    /// [`HandlerEvent::MessageSent`].
+    TrackedMessage { rpc: RpcOut, id: PublishId },
     /// The peer has joined the mesh.
     JoinedMesh,
     /// The peer has left the mesh.
@@ -94,8 +105,13 @@ pub struct EnabledHandler {
     /// The single long-lived inbound substream.
     inbound_substream: Option<InboundSubstreamState>,
 
-    /// Queue of values that we want to send to the remote.
-    send_queue: SmallVec<[proto::RPC; 16]>,
+    /// Queue of values that we want to send to the remote, together with the id of the
+    /// publication to report on once sent, if any.
+    send_queue: SmallVec<[(proto::RPC, Option<PublishId>); 16]>,
+
+    /// Tracked messages that were flushed (`true`) or dropped (`false`) and are yet to be
+    /// reported to the behaviour.
+    sent_messages: VecDeque<(PublishId, bool)>,
 
     /// Flag indicating that an outbound substream is being established to prevent duplicate
     /// requests.
@@ -120,6 +136,16 @@ pub struct EnabledHandler {
     /// Keeps track of whether this connection is for a peer in the mesh. This is used to make
     /// decisions about the keep alive state for this connection.
     in_mesh: bool,
+
+    /// The send queue length at which the queue is considered backed up and the time it has
+    /// to stay backed up for the peer to be reported as slow, if slow peer detection is enabled.
+    slow_peer_detection: Option<(usize, Duration)>,
+
+    /// Since when the send queue has been backed up.
+    send_queue_backed_up_since: Option<Instant>,
+
+    /// Whether the peer has been reported as slow to the behaviour.
+    slow_peer_reported: bool,
 }
 
 pub enum DisabledHandler {
@@ -129,10 +155,26 @@ pub enum DisabledHandler {
     ProtocolUnsupported {
         /// Keeps track on whether we have sent the peer kind to the behaviour.
         peer_kind_sent: bool,
+        /// Tracked messages that were not sent and are yet to be reported to the behaviour.
+        dropped_messages: VecDeque<PublishId>,
     },
     /// The maximum number of inbound or outbound substream attempts have happened and thereby the
     /// handler has been disabled.
-    MaxSubstreamAttempts,
+    MaxSubstreamAttempts {
+        /// Tracked messages that were not sent and are yet to be reported to the behaviour.
+        dropped_messages: VecDeque<PublishId>,
+    },
+}
+
+impl DisabledHandler {
+    fn dropped_messages(&mut self) -> &mut VecDeque<PublishId> {
+        match self {
+            DisabledHandler::ProtocolUnsupported {
+                dropped_messages, ..
+            }
+            | DisabledHandler::MaxSubstreamAttempts { dropped_messages } => dropped_messages,
+        }
+    }
 }
 
 /// State of the inbound substream, opened either by us or by the remote.
@@ -146,20 +188,28 @@ enum InboundSubstreamState {
 }
 
 /// State of the outbound substream, opened either by us or by the remote.
+#[allow(clippy::large_enum_variant)]
 enum OutboundSubstreamState {
     /// Waiting for the user to send a message. The idle state for an outbound substream.
     WaitingOutput(Framed<Stream, GossipsubCodec>),
     /// Waiting to send a message to the remote.
-    PendingSend(Framed<Stream, GossipsubCodec>, proto::RPC),
+    PendingSend(
+        Framed<Stream, GossipsubCodec>,
+        proto::RPC,
+        Option<PublishId>,
+    ),
     /// Waiting to flush the substream so that the data arrives to the remote.
-    PendingFlush(Framed<Stream, GossipsubCodec>),
+    PendingFlush(Framed<Stream, GossipsubCodec>, Option<PublishId>),
     /// An error occurred during processing.
     Poisoned,
 }
 
 impl Handler {
     /// Builds a new [`Handler`].
-    pub fn new(protocol_config: ProtocolConfig) -> Self {
+    pub fn new(
+        protocol_config: ProtocolConfig,
+        slow_peer_detection: Option<(usize, Duration)>,
+    ) -> Self {
         Handler::Enabled(EnabledHandler {
             listen_protocol: protocol_config,
             inbound_substream: None,
@@ -168,10 +218,14 @@ impl Handler {
             outbound_substream_attempts: 0,
             inbound_substream_attempts: 0,
             send_queue: SmallVec::new(),
+            sent_messages: VecDeque::new(),
             peer_kind: None,
             peer_kind_sent: false,
             last_io_activity: Instant::now(),
             in_mesh: false,
+            slow_peer_detection,
+            send_queue_backed_up_since: None,
+            slow_peer_reported: false,
         })
     }
 }
@@ -212,6 +266,49 @@ impl EnabledHandler {
         self.outbound_substream = Some(OutboundSubstreamState::WaitingOutput(substream));
     }
 
+    /// Takes the ids of the tracked messages that have not been sent yet, e.g. when the handler
+    /// is about to be disabled.
+    fn take_unsent_messages(&mut self) -> VecDeque<PublishId> {
+        let mut ids = self
+            .sent_messages
+            .drain(..)
+            .filter_map(|(id, sent)| (!sent).then_some(id))
+            .collect::<VecDeque<_>>();
+        match &self.outbound_substream {
+            Some(OutboundSubstreamState::PendingSend(_, _, id))
+            | Some(OutboundSubstreamState::PendingFlush(_, id)) => ids.extend(id),
+            _ => {}
+        }
+        ids.extend(self.send_queue.drain(..).filter_map(|(_, id)| id));
+        ids
+    }
+
+    /// Checks whether the slow peer state changed, i.e. whether the send queue has been backed up
+    /// for longer than the detection time or has been drained after the peer was reported as slow.
+    ///
+    /// This is checked whenever messages are queued or sent.
+    fn poll_slow_peer(&mut self) -> Option<bool> {
+        let (threshold, detection_time) = self.slow_peer_detection?;
+
+        if self.send_queue.len() < threshold {
+            self.send_queue_backed_up_since = None;
+            if self.slow_peer_reported {
+                self.slow_peer_reported = false;
+                return Some(false);
+            }
+            return None;
+        }
+
+        let since = *self
+            .send_queue_backed_up_since
+            .get_or_insert_with(Instant::now);
+        if !self.slow_peer_reported && since.elapsed() >= detection_time {
+            self.slow_peer_reported = true;
+            return Some(true);
+        }
+        None
+    }
+
     fn poll(
         &mut self,
         cx: &mut Context<'_>,
@@ -250,10 +347,10 @@ impl EnabledHandler {
             ) {
                 // outbound idle state
                 Some(OutboundSubstreamState::WaitingOutput(substream)) => {
-                    if let Some(message) = self.send_queue.pop() {
+                    if let Some((message, id)) = self.send_queue.pop() {
                         self.send_queue.shrink_to_fit();
                         self.outbound_substream =
-                            Some(OutboundSubstreamState::PendingSend(substream, message));
+                            Some(OutboundSubstreamState::PendingSend(substream, message, id));
                         continue;
                     }
 
@@ -261,18 +358,19 @@ impl EnabledHandler {
                         Some(OutboundSubstreamState::WaitingOutput(substream));
                     break;
                 }
-                Some(OutboundSubstreamState::PendingSend(mut substream, message)) => {
+                Some(OutboundSubstreamState::PendingSend(mut substream, message, id)) => {
                     match Sink::poll_ready(Pin::new(&mut substream), cx) {
                         Poll::Ready(Ok(())) => {
                             match Sink::start_send(Pin::new(&mut substream), message) {
                                 Ok(()) => {
                                     self.outbound_substream =
-                                        Some(OutboundSubstreamState::PendingFlush(substream))
+                                        Some(OutboundSubstreamState::PendingFlush(substream, id))
                                 }
                                 Err(e) => {
                                     tracing::debug!(
                                         "Failed to send message on outbound stream: {e}"
                                     );
+                                    self.sent_messages.extend(id.map(|id| (id, false)));
                                     self.outbound_substream = None;
                                     break;
                                 }
@@ -280,31 +378,34 @@ impl EnabledHandler {
                         }
                         Poll::Ready(Err(e)) => {
                             tracing::debug!("Failed to send message on outbound stream: {e}");
+                            self.sent_messages.extend(id.map(|id| (id, false)));
                             self.outbound_substream = None;
                             break;
                         }
                         Poll::Pending => {
                             self.outbound_substream =
-                                Some(OutboundSubstreamState::PendingSend(substream, message));
+                                Some(OutboundSubstreamState::PendingSend(substream, message, id));
                             break;
                         }
                     }
                 }
-                Some(OutboundSubstreamState::PendingFlush(mut substream)) => {
+                Some(OutboundSubstreamState::PendingFlush(mut substream, id)) => {
                     match Sink::poll_flush(Pin::new(&mut substream), cx) {
                         Poll::Ready(Ok(())) => {
                             self.last_io_activity = Instant::now();
+                            self.sent_messages.extend(id.map(|id| (id, true)));
                             self.outbound_substream =
                                 Some(OutboundSubstreamState::WaitingOutput(substream))
                         }
                         Poll::Ready(Err(e)) => {
                             tracing::debug!("Failed to flush outbound stream: {e}");
+                            self.sent_messages.extend(id.map(|id| (id, false)));
                             self.outbound_substream = None;
                             break;
                         }
                         Poll::Pending => {
                             self.outbound_substream =
-                                Some(OutboundSubstreamState::PendingFlush(substream));
+                                Some(OutboundSubstreamState::PendingFlush(substream, id));
                             break;
                         }
                     }
@@ -319,6 +420,18 @@ impl EnabledHandler {
             }
         }
 
+        if let Some(slow) = self.poll_slow_peer() {
+            return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                HandlerEvent::SlowPeer(slow),
+            ));
+        }
+
+        if let Some((id, sent)) = self.sent_messages.pop_front() {
+            return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                HandlerEvent::MessageSent { id, sent },
+            ));
+        }
+
         loop {
             match std::mem::replace(
                 &mut self.inbound_substream,
@@ -409,7 +522,10 @@ impl ConnectionHandler for Handler {
     fn on_behaviour_event(&mut self, message: HandlerIn) {
         match self {
             Handler::Enabled(handler) => match message {
-                HandlerIn::Message(m) => handler.send_queue.push(m.into_protobuf()),
+                HandlerIn::Message(m) => handler.send_queue.push((m.into_protobuf(), None)),
+                HandlerIn::TrackedMessage { rpc, id } => {
+                    handler.send_queue.push((rpc.into_protobuf(), Some(id)))
+                }
                 HandlerIn::JoinedMesh => {
                     handler.in_mesh = true;
                 }
@@ -417,8 +533,11 @@ impl ConnectionHandler for Handler {
                     handler.in_mesh = false;
                 }
             },
-            Handler::Disabled(_) => {
+            Handler::Disabled(handler) => {
                 tracing::debug!(?message, "Handler is disabled. Dropping message");
+                if let HandlerIn::TrackedMessage { id, .. } = message {
+                    handler.dropped_messages().push_back(id);
+                }
             }
         }
     }
@@ -436,17 +555,24 @@ impl ConnectionHandler for Handler {
     > {
         match self {
             Handler::Enabled(handler) => handler.poll(cx),
-            Handler::Disabled(DisabledHandler::ProtocolUnsupported { peer_kind_sent }) => {
-                if !*peer_kind_sent {
-                    *peer_kind_sent = true;
+            Handler::Disabled(handler) => {
+                if let DisabledHandler::ProtocolUnsupported { peer_kind_sent, .. } = handler {
+                    if !*peer_kind_sent {
+                        *peer_kind_sent = true;
+                        return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                            HandlerEvent::PeerKind(PeerKind::NotSupported),
+                        ));
+                    }
+                }
+
+                if let Some(id) = handler.dropped_messages().pop_front() {
                     return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
-                        HandlerEvent::PeerKind(PeerKind::NotSupported),
+                        HandlerEvent::MessageSent { id, sent: false },
                     ));
                 }
 
                 Poll::Pending
             }
-            Handler::Disabled(DisabledHandler::MaxSubstreamAttempts) => Poll::Pending,
         }
     }
 
@@ -468,7 +594,9 @@ impl ConnectionHandler for Handler {
                         tracing::warn!(
                             "The maximum number of inbound substreams attempts has been exceeded"
                         );
-                        *self = Handler::Disabled(DisabledHandler::MaxSubstreamAttempts);
+                        *self = Handler::Disabled(DisabledHandler::MaxSubstreamAttempts {
+                            dropped_messages: handler.take_unsent_messages(),
+                        });
                         return;
                     }
                 }
@@ -482,7 +610,9 @@ impl ConnectionHandler for Handler {
                         tracing::warn!(
                             "The maximum number of outbound substream attempts has been exceeded"
                         );
-                        *self = Handler::Disabled(DisabledHandler::MaxSubstreamAttempts);
+                        *self = Handler::Disabled(DisabledHandler::MaxSubstreamAttempts {
+                            dropped_messages: handler.take_unsent_messages(),
+                        });
                         return;
                     }
                 }
@@ -518,6 +648,7 @@ impl ConnectionHandler for Handler {
                         );
                         *self = Handler::Disabled(DisabledHandler::ProtocolUnsupported {
                             peer_kind_sent: false,
+                            dropped_messages: handler.take_unsent_messages(),
                         });
                     }
                     ConnectionEvent::DialUpgradeError(DialUpgradeError {
@@ -95,6 +95,7 @@
 
 mod backoff;
 mod behaviour;
+mod choke;
 mod config;
 mod error;
 mod gossip_promises;
@@ -111,7 +112,7 @@ mod transform;
 mod types;
 
 pub use self::behaviour::{Behaviour, Event, MessageAuthenticity};
-pub use self::config::{Config, ConfigBuilder, ValidationMode, Version};
+pub use self::config::{Config, ConfigBuilder, ValidationMode, ValidationQueueOverflow, Version};
 pub use self::error::{ConfigBuilderError, PublishError, SubscriptionError, ValidationError};
 pub use self::metrics::Config as MetricsConfig;
 pub use self::peer_score::{
@@ -125,7 +126,7 @@ pub use self::subscription_filter::{
 };
 pub use self::topic::{Hasher, Topic, TopicHash};
 pub use self::transform::{DataTransform, IdentityTransform};
-pub use self::types::{Message, MessageAcceptance, MessageId, RawMessage};
+pub use self::types::{Message, MessageAcceptance, MessageId, PublishHandle, RawMessage};
 
 #[deprecated(note = "Will be removed from the public API.")]
 pub type Rpc = self::types::Rpc;
@@ -18,6 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use crate::time_cache::TimeCache;
 use crate::topic::TopicHash;
 use crate::types::{MessageId, RawMessage};
 use libp2p_identity::PeerId;
@@ -26,6 +27,7 @@ use std::fmt::Debug;
 use std::{
     collections::{HashMap, HashSet},
     fmt,
+    time::Duration,
 };
 
 /// CacheEntry stored in the history.
@@ -36,9 +38,13 @@ pub(crate) struct CacheEntry {
 }
 
 /// MessageCache struct holding history of messages.
-#[derive(Clone)]
 pub(crate) struct MessageCache {
+    /// The cached messages together with the peers known to have them.
     msgs: HashMap<MessageId, (RawMessage, HashSet<PeerId>)>,
+    /// Peers that advertised a message via IHAVE before we received it.
+    announced: TimeCache<MessageId, HashSet<PeerId>>,
+    /// The maximum number of peers tracked per message.
+    max_provenance: usize,
     /// For every message and peer the number of times this peer asked for the message
     iwant_counts: HashMap<MessageId, HashMap<PeerId, u32>>,
     history: Vec<Vec<CacheEntry>>,
@@ -60,10 +66,17 @@ impl fmt::Debug for MessageCache {
 
 /// Implementation of the MessageCache.
 impl MessageCache {
-    pub(crate) fn new(gossip: usize, history_capacity: usize) -> Self {
+    pub(crate) fn new(
+        gossip: usize,
+        history_capacity: usize,
+        max_provenance: usize,
+        announcement_ttl: Duration,
+    ) -> Self {
         MessageCache {
             gossip,
             msgs: HashMap::default(),
+            announced: TimeCache::new(announcement_ttl),
+            max_provenance,
             iwant_counts: HashMap::default(),
             history: vec![Vec::new(); history_capacity],
         }
@@ -83,7 +96,9 @@ impl MessageCache {
                     mid: message_id.clone(),
                     topic: msg.topic.clone(),
                 };
-                entry.insert((msg, HashSet::default()));
+                // Peers that announced the message already have it.
+                let known_peers = self.announced.remove(message_id).unwrap_or_default();
+                entry.insert((msg, known_peers));
                 self.history[0].push(cache_entry);
 
                 tracing::trace!(message=?message_id, "Put message in mcache");
@@ -101,8 +116,39 @@ impl MessageCache {
                 return;
             }
 
-            originating_peers.insert(*source);
+            if originating_peers.len() < self.max_provenance {
+                originating_peers.insert(*source);
+            }
+        }
+    }
+
+    /// Keeps track of peers that advertised a message we have not received yet.
+    ///
+    /// Once the message is put into the cache, these peers are treated like peers that sent us
+    /// a duplicate.
+    pub(crate) fn observe_announcement(&mut self, message_id: &MessageId, source: &PeerId) {
+        if self.msgs.contains_key(message_id) {
+            self.observe_duplicate(message_id, source);
+            return;
         }
+
+        let announcing_peers = self.announced.entry(message_id.clone()).or_default();
+        if announcing_peers.len() < self.max_provenance {
+            announcing_peers.insert(*source);
+        }
+    }
+
+    /// Takes the peers known to have the message with `message_id`.
+    pub(crate) fn take_known_peers(&mut self, message_id: &MessageId) -> HashSet<PeerId> {
+        self.msgs
+            .get_mut(message_id)
+            .map(|(_, known_peers)| std::mem::take(known_peers))
+            .unwrap_or_default()
+    }
+
+    /// Returns whether the message with `message_id` is in the cache.
+    pub(crate) fn contains(&self, message_id: &MessageId) -> bool {
+        self.msgs.contains_key(message_id)
     }
 
     /// Get a message with `message_id`
@@ -250,7 +296,7 @@ mod tests {
     }
 
     fn new_cache(gossip_size: usize, history: usize) -> MessageCache {
-        MessageCache::new(gossip_size, history)
+        MessageCache::new(gossip_size, history, 32, Duration::from_secs(3))
     }
 
     #[test]
@@ -127,6 +127,10 @@ pub(crate) struct Metrics {
     ignored_messages: Family<TopicHash, Counter>,
     /// The number of messages rejected by the application (validation result).
     rejected_messages: Family<TopicHash, Counter>,
+    /// The number of messages dropped before validation because the validation queue was full.
+    dropped_before_validation: Family<TopicHash, Counter>,
+    /// The number of received messages awaiting validation if the validation queue is bounded.
+    messages_awaiting_validation: Family<TopicHash, Gauge>,
 
     /* Metrics regarding mesh state */
     /// Number of peers in our mesh. This metric should be updated with the count of peers for a
@@ -136,6 +140,8 @@ pub(crate) struct Metrics {
     mesh_peer_inclusion_events: Family<InclusionLabel, Counter>,
     /// Number of times we remove peers in a topic mesh for different reasons.
     mesh_peer_churn_events: Family<ChurnLabel, Counter>,
+    /// Number of peers in our mesh that use an older protocol than gossipsub v1.1.
+    mesh_downgraded_peer_counts: Family<TopicHash, Gauge>,
 
     /* Metrics regarding messages sent/received */
     /// Number of gossip messages sent to each topic.
@@ -217,6 +223,16 @@ impl Metrics {
             "Number of ignored messages received for each topic"
         );
 
+        let dropped_before_validation = register_family!(
+            "dropped_before_validation_per_topic",
+            "Number of messages dropped before validation for each topic because the validation queue was full"
+        );
+
+        let messages_awaiting_validation = register_family!(
+            "messages_awaiting_validation_per_topic",
+            "Number of received messages awaiting validation for each topic, if the validation queue is bounded"
+        );
+
         let rejected_messages = register_family!(
             "rejected_messages_per_topic",
             "Number of rejected messages received for each topic"
@@ -234,6 +250,10 @@ impl Metrics {
             "mesh_peer_churn_events",
             "Number of times a peer gets removed from our mesh for different reasons"
         );
+        let mesh_downgraded_peer_counts = register_family!(
+            "mesh_downgraded_peer_counts",
+            "Number of peers in each topic in our mesh that use an older protocol than gossipsub v1.1"
+        );
         let topic_msg_sent_counts = register_family!(
             "topic_msg_sent_counts",
             "Number of gossip messages sent to each topic"
@@ -312,9 +332,12 @@ impl Metrics {
             accepted_messages,
             ignored_messages,
             rejected_messages,
+            dropped_before_validation,
+            messages_awaiting_validation,
             mesh_peer_counts,
             mesh_peer_inclusion_events,
             mesh_peer_churn_events,
+            mesh_downgraded_peer_counts,
             topic_msg_sent_counts,
             topic_msg_sent_bytes,
             topic_msg_published,
@@ -421,6 +444,16 @@ impl Metrics {
         }
     }
 
+    /// Register the current number of peers in our mesh for this topic that use an older
+    /// protocol than gossipsub v1.1.
+    pub(crate) fn set_mesh_downgraded_peers(&mut self, topic: &TopicHash, count: usize) {
+        if self.register_topic(topic).is_ok() {
+            self.mesh_downgraded_peer_counts
+                .get_or_create(topic)
+                .set(count as i64);
+        }
+    }
+
     /// Register that an invalid message was received on a specific topic.
     pub(crate) fn register_invalid_message(&mut self, topic: &TopicHash) {
         if self.register_topic(topic).is_ok() {
@@ -428,6 +461,22 @@ impl Metrics {
         }
     }
 
+    /// Register a message dropped before validation because the validation queue was full.
+    pub(crate) fn register_dropped_before_validation(&mut self, topic: &TopicHash) {
+        if self.register_topic(topic).is_ok() {
+            self.dropped_before_validation.get_or_create(topic).inc();
+        }
+    }
+
+    /// Register the current number of received messages awaiting validation for this topic.
+    pub(crate) fn set_messages_awaiting_validation(&mut self, topic: &TopicHash, count: usize) {
+        if self.register_topic(topic).is_ok() {
+            self.messages_awaiting_validation
+                .get_or_create(topic)
+                .set(count as i64);
+        }
+    }
+
     /// Register a score penalty.
     pub(crate) fn register_score_penalty(&mut self, penalty: Penalty) {
         self.scoring_penalties
@@ -567,6 +616,10 @@ pub(crate) enum Penalty {
     MessageDeficit,
     /// Too many peers under one IP address.
     IPColocation,
+    /// A peer did not keep up with the messages sent to it.
+    SlowPeer,
+    /// A peer sent a message while the validation queue was full.
+    ValidationQueueOverflow,
 }
 
 /// Label for the mesh inclusion event metrics.
@@ -30,28 +30,45 @@ use asynchronous_codec::{Decoder, Encoder, Framed};
 use byteorder::{BigEndian, ByteOrder};
 use bytes::BytesMut;
 use futures::prelude::*;
-use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
+use libp2p_core::{InboundUpgrade, OutboundUpgrade, PeerRecord, SignedEnvelope, UpgradeInfo};
 use libp2p_identity::{PeerId, PublicKey};
 use libp2p_swarm::StreamProtocol;
 use quick_protobuf::Writer;
+use std::collections::HashMap;
 use std::pin::Pin;
 use void::Void;
 
 pub(crate) const SIGNING_PREFIX: &[u8] = b"libp2p-pubsub:";
 
+pub(crate) const GOSSIPSUB_1_2_0_PROTOCOL: ProtocolId = ProtocolId {
+    protocol: StreamProtocol::new("/meshsub/1.2.0"),
+    kind: PeerKind::Gossipsubv1_2,
+    compact_topics: false,
+};
 pub(crate) const GOSSIPSUB_1_1_0_PROTOCOL: ProtocolId = ProtocolId {
     protocol: StreamProtocol::new("/meshsub/1.1.0"),
     kind: PeerKind::Gossipsubv1_1,
+    compact_topics: false,
 };
 pub(crate) const GOSSIPSUB_1_0_0_PROTOCOL: ProtocolId = ProtocolId {
     protocol: StreamProtocol::new("/meshsub/1.0.0"),
     kind: PeerKind::Gossipsub,
+    compact_topics: false,
 };
 pub(crate) const FLOODSUB_PROTOCOL: ProtocolId = ProtocolId {
     protocol: StreamProtocol::new("/floodsub/1.0.0"),
     kind: PeerKind::Floodsub,
+    compact_topics: false,
 };
 
+/// The suffix of the gossipsub protocols with the compact topics extension,
+/// see [`ConfigBuilder::compact_topics`](crate::ConfigBuilder::compact_topics).
+const COMPACT_TOPICS_SUFFIX: &str = "/compact-topics";
+
+/// The maximum number of topic aliases per stream with the compact topics extension.
+/// Messages for further topics carry the full topic.
+const MAX_TOPIC_ALIASES: usize = 1024;
+
 /// Implementation of [`InboundUpgrade`] and [`OutboundUpgrade`] for the Gossipsub protocol.
 #[derive(Debug, Clone)]
 pub struct ProtocolConfig {
@@ -61,6 +78,8 @@ pub struct ProtocolConfig {
     pub(crate) max_transmit_size: usize,
     /// Determines the level of validation to be done on incoming messages.
     pub(crate) validation_mode: ValidationMode,
+    /// Whether to offer the gossipsub protocols with the compact topics extension.
+    pub(crate) compact_topics: bool,
 }
 
 impl Default for ProtocolConfig {
@@ -68,7 +87,12 @@ impl Default for ProtocolConfig {
         Self {
             max_transmit_size: 65536,
             validation_mode: ValidationMode::Strict,
-            protocol_ids: vec![GOSSIPSUB_1_1_0_PROTOCOL, GOSSIPSUB_1_0_0_PROTOCOL],
+            protocol_ids: vec![
+                GOSSIPSUB_1_2_0_PROTOCOL,
+                GOSSIPSUB_1_1_0_PROTOCOL,
+                GOSSIPSUB_1_0_0_PROTOCOL,
+            ],
+            compact_topics: false,
         }
     }
 }
@@ -80,6 +104,8 @@ pub struct ProtocolId {
     pub protocol: StreamProtocol,
     /// The type of protocol we support
     pub kind: PeerKind,
+    /// Whether the protocol includes the compact topics extension.
+    pub compact_topics: bool,
 }
 
 impl AsRef<str> for ProtocolId {
@@ -93,7 +119,25 @@ impl UpgradeInfo for ProtocolConfig {
     type InfoIter = Vec<Self::Info>;
 
     fn protocol_info(&self) -> Self::InfoIter {
-        self.protocol_ids.clone()
+        if !self.compact_topics {
+            return self.protocol_ids.clone();
+        }
+        // Prefer the protocols with the compact topics extension, falling back to the
+        // regular protocols for peers without the extension.
+        self.protocol_ids
+            .iter()
+            .filter(|id| id.kind != PeerKind::Floodsub)
+            .map(|id| ProtocolId {
+                protocol: StreamProtocol::try_from_owned(format!(
+                    "{}{COMPACT_TOPICS_SUFFIX}",
+                    id.protocol
+                ))
+                .expect("protocol with suffix to start with a slash"),
+                kind: id.kind.clone(),
+                compact_topics: true,
+            })
+            .chain(self.protocol_ids.iter().cloned())
+            .collect()
     }
 }
 
@@ -109,7 +153,8 @@ where
         Box::pin(future::ok((
             Framed::new(
                 socket,
-                GossipsubCodec::new(self.max_transmit_size, self.validation_mode),
+                GossipsubCodec::new(self.max_transmit_size, self.validation_mode)
+                    .with_compact_topics(protocol_id.compact_topics),
             ),
             protocol_id.kind,
         )))
@@ -128,7 +173,8 @@ where
         Box::pin(future::ok((
             Framed::new(
                 socket,
-                GossipsubCodec::new(self.max_transmit_size, self.validation_mode),
+                GossipsubCodec::new(self.max_transmit_size, self.validation_mode)
+                    .with_compact_topics(protocol_id.compact_topics),
             ),
             protocol_id.kind,
         )))
@@ -142,6 +188,12 @@ pub struct GossipsubCodec {
     validation_mode: ValidationMode,
     /// The codec to handle common encoding/decoding of protobuf messages
     codec: quick_protobuf_codec::Codec<proto::RPC>,
+    /// Whether the stream uses the compact topics extension.
+    compact_topics: bool,
+    /// The aliases of the topics sent on the stream.
+    outbound_aliases: HashMap<TopicHash, u64>,
+    /// The topics of the aliases received on the stream.
+    inbound_aliases: HashMap<u64, String>,
 }
 
 impl GossipsubCodec {
@@ -150,7 +202,71 @@ impl GossipsubCodec {
         GossipsubCodec {
             validation_mode,
             codec,
+            compact_topics: false,
+            outbound_aliases: HashMap::new(),
+            inbound_aliases: HashMap::new(),
+        }
+    }
+
+    /// Enables the compact topics extension, see
+    /// [`ConfigBuilder::compact_topics`](crate::ConfigBuilder::compact_topics).
+    pub fn with_compact_topics(mut self, compact_topics: bool) -> GossipsubCodec {
+        self.compact_topics = compact_topics;
+        self
+    }
+
+    /// Replaces the topics of the given messages with their aliases, defining
+    /// new aliases as needed. Returns the newly defined aliases, which are only
+    /// to be used for subsequent messages once sent.
+    fn compact_topics(&self, messages: &mut [proto::Message]) -> Vec<(TopicHash, u64)> {
+        let mut new_aliases: Vec<(TopicHash, u64)> = Vec::new();
+        for message in messages {
+            if message.topic.is_empty() {
+                // An empty topic refers to an alias, hence can't define one.
+                continue;
+            }
+            let topic = TopicHash::from_raw(message.topic.as_str());
+            if let Some(alias) = self.outbound_aliases.get(&topic) {
+                message.topic = String::new();
+                message.topic_alias = Some(*alias);
+            } else if let Some((_, alias)) = new_aliases.iter().find(|(t, _)| t == &topic) {
+                // The alias is defined by an earlier message in the same RPC.
+                message.topic_alias = Some(*alias);
+            } else if self.outbound_aliases.len() + new_aliases.len() < MAX_TOPIC_ALIASES {
+                let alias = (self.outbound_aliases.len() + new_aliases.len()) as u64;
+                message.topic_alias = Some(alias);
+                new_aliases.push((topic, alias));
+            }
         }
+        new_aliases
+    }
+
+    /// Restores the topics of the given messages from their aliases, learning
+    /// new aliases as they are defined. Messages with an unknown alias are dropped.
+    fn resolve_topics(&mut self, messages: &mut Vec<proto::Message>) {
+        messages.retain_mut(|message| {
+            let Some(alias) = message.topic_alias.take() else {
+                return true;
+            };
+            if !message.topic.is_empty() {
+                if self.inbound_aliases.len() < MAX_TOPIC_ALIASES {
+                    self.inbound_aliases
+                        .entry(alias)
+                        .or_insert_with(|| message.topic.clone());
+                }
+                return true;
+            }
+            match self.inbound_aliases.get(&alias) {
+                Some(topic) => {
+                    message.topic = topic.clone();
+                    true
+                }
+                None => {
+                    tracing::debug!(%alias, "Dropping message with unknown topic alias");
+                    false
+                }
+            }
+        });
     }
 
     /// Verifies a gossipsub message. This returns either a success or failure. All errors
@@ -214,8 +330,14 @@ impl Encoder for GossipsubCodec {
     type Item<'a> = proto::RPC;
     type Error = quick_protobuf_codec::Error;
 
-    fn encode(&mut self, item: Self::Item<'_>, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        self.codec.encode(item, dst)
+    fn encode(&mut self, mut item: Self::Item<'_>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if !self.compact_topics {
+            return self.codec.encode(item, dst);
+        }
+        let new_aliases = self.compact_topics(&mut item.publish);
+        self.codec.encode(item, dst)?;
+        self.outbound_aliases.extend(new_aliases);
+        Ok(())
     }
 }
 
@@ -224,9 +346,12 @@ impl Decoder for GossipsubCodec {
     type Error = quick_protobuf_codec::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let Some(rpc) = self.codec.decode(src)? else {
+        let Some(mut rpc) = self.codec.decode(src)? else {
             return Ok(None);
         };
+        if self.compact_topics {
+            self.resolve_topics(&mut rpc.publish);
+        }
         // Store valid messages.
         let mut messages = Vec::with_capacity(rpc.publish.len());
         // Store any invalid messages.
@@ -450,14 +575,19 @@ impl Decoder for GossipsubCodec {
                     .peers
                     .into_iter()
                     .filter_map(|info| {
-                        info.peer_id
+                        let peer_id = info
+                            .peer_id
                             .as_ref()
-                            .and_then(|id| PeerId::from_bytes(id).ok())
-                            .map(|peer_id|
-                                    //TODO signedPeerRecord, see https://github.com/libp2p/specs/pull/217
-                                    PeerInfo {
-                                        peer_id: Some(peer_id),
-                                    })
+                            .and_then(|id| PeerId::from_bytes(id).ok());
+                        let signed_peer_record = info
+                            .signed_peer_record
+                            .and_then(|record| verify_peer_record(&record, peer_id));
+                        let peer_id =
+                            peer_id.or(signed_peer_record.as_ref().map(PeerRecord::peer_id))?;
+                        Some(PeerInfo {
+                            peer_id: Some(peer_id),
+                            signed_peer_record,
+                        })
                     })
                     .collect::<Vec<PeerInfo>>();
 
@@ -469,10 +599,40 @@ impl Decoder for GossipsubCodec {
                 });
             }
 
+            let idontwant_msgs =
+                rpc_control
+                    .idontwant
+                    .into_iter()
+                    .map(|idontwant| ControlAction::IDontWant {
+                        message_ids: idontwant
+                            .message_ids
+                            .into_iter()
+                            .map(MessageId::from)
+                            .collect::<Vec<_>>(),
+                    });
+
+            let choke_msgs = rpc_control
+                .choke
+                .into_iter()
+                .map(|choke| ControlAction::Choke {
+                    topic_hash: TopicHash::from_raw(choke.topic_id.unwrap_or_default()),
+                });
+
+            let unchoke_msgs =
+                rpc_control
+                    .unchoke
+                    .into_iter()
+                    .map(|unchoke| ControlAction::Unchoke {
+                        topic_hash: TopicHash::from_raw(unchoke.topic_id.unwrap_or_default()),
+                    });
+
             control_msgs.extend(ihave_msgs);
             control_msgs.extend(iwant_msgs);
             control_msgs.extend(graft_msgs);
             control_msgs.extend(prune_msgs);
+            control_msgs.extend(idontwant_msgs);
+            control_msgs.extend(choke_msgs);
+            control_msgs.extend(unchoke_msgs);
         }
 
         Ok(Some(HandlerEvent::Message {
@@ -497,6 +657,30 @@ impl Decoder for GossipsubCodec {
     }
 }
 
+/// Decodes and verifies a signed peer record of a peer exchange, dropping it if it is
+/// invalid or not signed by the proposed peer.
+fn verify_peer_record(bytes: &[u8], peer_id: Option<PeerId>) -> Option<PeerRecord> {
+    let record = match SignedEnvelope::from_protobuf_encoding(bytes)
+        .map_err(|e| e.to_string())
+        .and_then(|envelope| PeerRecord::from_signed_envelope(envelope).map_err(|e| e.to_string()))
+    {
+        Ok(record) => record,
+        Err(e) => {
+            tracing::debug!(peer=?peer_id, "Dropping invalid signed peer record in PX: {e}");
+            return None;
+        }
+    };
+    if peer_id.is_some_and(|peer_id| peer_id != record.peer_id()) {
+        tracing::debug!(
+            peer=?peer_id,
+            signer=%record.peer_id(),
+            "Dropping signed peer record in PX signed by another peer"
+        );
+        return None;
+    }
+    Some(record)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -599,6 +783,130 @@ mod tests {
         QuickCheck::new().quickcheck(prop as fn(_) -> _)
     }
 
+    #[test]
+    /// Test that the control messages of the choking extension can be encoded and decoded.
+    fn encode_decode_choke() {
+        let topic_hash = TopicHash::from_raw("topic");
+        let control_msgs = vec![
+            ControlAction::Choke {
+                topic_hash: topic_hash.clone(),
+            },
+            ControlAction::Unchoke { topic_hash },
+        ];
+        let rpc = Rpc {
+            messages: vec![],
+            subscriptions: vec![],
+            control_msgs: control_msgs.clone(),
+        };
+
+        let mut codec = GossipsubCodec::new(u32::MAX as usize, ValidationMode::Strict);
+        let mut buf = BytesMut::new();
+        codec.encode(rpc.into_protobuf(), &mut buf).unwrap();
+        match codec.decode(&mut buf).unwrap().unwrap() {
+            HandlerEvent::Message { rpc, .. } => assert_eq!(rpc.control_msgs, control_msgs),
+            _ => panic!("Must decode a message"),
+        }
+    }
+
+    #[test]
+    /// Test that IDONTWANT control messages of gossipsub v1.2 can be encoded and decoded.
+    fn encode_decode_idontwant() {
+        let control_msgs = vec![ControlAction::IDontWant {
+            message_ids: vec![MessageId::from("a"), MessageId::from("b")],
+        }];
+        let rpc = Rpc {
+            messages: vec![],
+            subscriptions: vec![],
+            control_msgs: control_msgs.clone(),
+        };
+
+        let mut codec = GossipsubCodec::new(u32::MAX as usize, ValidationMode::Strict);
+        let mut buf = BytesMut::new();
+        codec.encode(rpc.into_protobuf(), &mut buf).unwrap();
+        match codec.decode(&mut buf).unwrap().unwrap() {
+            HandlerEvent::Message { rpc, .. } => assert_eq!(rpc.control_msgs, control_msgs),
+            _ => panic!("Must decode a message"),
+        }
+    }
+
+    #[test]
+    /// Test that topics are replaced by aliases after their first use with compact topics.
+    fn encode_decode_compact_topics() {
+        let keypair = Keypair::generate_ed25519();
+        let mut gs: Behaviour = Behaviour::new(
+            crate::MessageAuthenticity::Signed(keypair),
+            Config::default(),
+        )
+        .unwrap();
+        let topic = Topic::new("/a/long/namespaced/topic/name").hash();
+        let messages = (0..3)
+            .map(|i| gs.build_raw_message(topic.clone(), vec![i]).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut encoder = GossipsubCodec::new(u32::MAX as usize, ValidationMode::Strict)
+            .with_compact_topics(true);
+        let mut decoder = GossipsubCodec::new(u32::MAX as usize, ValidationMode::Strict)
+            .with_compact_topics(true);
+        let mut sizes = Vec::new();
+        for message in &messages {
+            let rpc = Rpc {
+                messages: vec![message.clone()],
+                subscriptions: vec![],
+                control_msgs: vec![],
+            };
+            let mut buf = BytesMut::new();
+            encoder.encode(rpc.into_protobuf(), &mut buf).unwrap();
+            sizes.push(buf.len());
+            match decoder.decode(&mut buf).unwrap().unwrap() {
+                HandlerEvent::Message {
+                    mut rpc,
+                    invalid_messages,
+                } => {
+                    assert!(invalid_messages.is_empty());
+                    rpc.messages[0].validated = true;
+                    assert_eq!(vec![message.clone()], rpc.messages);
+                }
+                _ => panic!("Must decode a message"),
+            }
+        }
+        assert!(sizes[1] + topic.as_str().len() <= sizes[0] + 2);
+        assert_eq!(sizes[1], sizes[2]);
+
+        // A decoder without the aliases drops messages referring to them.
+        let rpc = Rpc {
+            messages: vec![messages[0].clone()],
+            subscriptions: vec![],
+            control_msgs: vec![],
+        };
+        let mut buf = BytesMut::new();
+        encoder.encode(rpc.into_protobuf(), &mut buf).unwrap();
+        let mut decoder = GossipsubCodec::new(u32::MAX as usize, ValidationMode::Strict)
+            .with_compact_topics(true);
+        match decoder.decode(&mut buf).unwrap().unwrap() {
+            HandlerEvent::Message { rpc, .. } => assert!(rpc.messages.is_empty()),
+            _ => panic!("Must decode a message"),
+        }
+    }
+
+    #[test]
+    fn compact_topics_protocols_are_preferred() {
+        let protocol_config = ConfigBuilder::default()
+            .compact_topics()
+            .support_floodsub()
+            .build()
+            .unwrap()
+            .protocol_config();
+        let protocol_ids = protocol_config.protocol_info();
+
+        assert_eq!(protocol_ids.len(), 7);
+        assert_eq!(protocol_ids[0].protocol, "/meshsub/1.2.0/compact-topics");
+        assert!(protocol_ids[0].compact_topics);
+        assert_eq!(protocol_ids[0].kind, PeerKind::Gossipsubv1_2);
+        assert_eq!(protocol_ids[2].protocol, "/meshsub/1.0.0/compact-topics");
+        assert_eq!(protocol_ids[3], GOSSIPSUB_1_2_0_PROTOCOL);
+        assert_eq!(protocol_ids[6], FLOODSUB_PROTOCOL);
+    }
+
     #[test]
     fn support_floodsub_with_custom_protocol() {
         let protocol_config = ConfigBuilder::default()
@@ -611,4 +919,23 @@ mod tests {
         assert_eq!(protocol_config.protocol_ids[0].protocol, "/foosub");
         assert_eq!(protocol_config.protocol_ids[1].protocol, "/floodsub/1.0.0");
     }
+
+    #[test]
+    fn px_drops_invalid_signed_peer_records() {
+        let key = Keypair::generate_ed25519();
+        let record = PeerRecord::new(&key, vec!["/ip4/1.2.3.4/tcp/1234".parse().unwrap()]).unwrap();
+        let bytes = record
+            .clone()
+            .into_signed_envelope()
+            .into_protobuf_encoding();
+
+        assert_eq!(verify_peer_record(&bytes, None), Some(record.clone()));
+        assert_eq!(
+            verify_peer_record(&bytes, Some(record.peer_id())),
+            Some(record)
+        );
+        // Signed by another peer than the proposed one.
+        assert_eq!(verify_peer_record(&bytes, Some(PeerId::random())), None);
+        assert_eq!(verify_peer_record(&bytes[1..], None), None);
+    }
 }
@@ -44,6 +44,7 @@ mod test {
             topic: topic1.clone().into_string(),
             signature: Some(rand::thread_rng().gen::<[u8; 32]>().to_vec()),
             key: Some(rand::thread_rng().gen::<[u8; 32]>().to_vec()),
+            topic_alias: None,
         };
         let old_message1 = compat::pb::Message {
             from: Some(PeerId::random().to_bytes()),
@@ -143,6 +143,20 @@ where
         }
     }
 
+    /// Removes the value of `key` from the cache.
+    pub(crate) fn remove(&mut self, key: &Key) -> Option<Value> {
+        let removed = self.map.remove(key)?;
+        // Drop the expiry of the removed value, lest it evicts the value of a later reinsert.
+        if let Some(i) = self
+            .list
+            .iter()
+            .position(|e| &e.element == key && e.expires == removed.expires)
+        {
+            self.list.remove(i);
+        }
+        Some(removed.element)
+    }
+
     /// Empties the entire cache.
     #[cfg(test)]
     pub(crate) fn clear(&mut self) {
@@ -153,6 +167,10 @@ where
     pub(crate) fn contains_key(&self, key: &Key) -> bool {
         self.map.contains_key(key)
     }
+
+    pub(crate) fn get(&self, key: &Key) -> Option<&Value> {
+        self.map.get(key).map(|element| &element.element)
+    }
 }
 
 pub(crate) struct DuplicateCache<Key>(TimeCache<Key, ()>);
@@ -216,4 +234,21 @@ mod test {
         // should be removed from the cache
         assert!(cache.insert("t"));
     }
+
+    #[test]
+    fn reinserted_entries_expire_after_their_own_ttl() {
+        let mut cache = TimeCache::new(Duration::from_millis(100));
+
+        *cache.entry("t").or_default() = 1;
+        assert_eq!(cache.remove(&"t"), Some(1));
+        assert!(cache.list.is_empty());
+
+        std::thread::sleep(Duration::from_millis(50));
+        *cache.entry("t").or_default() = 2;
+        // sleep until the expiry of the removed value
+        std::thread::sleep(Duration::from_millis(60));
+        cache.entry("s").or_default();
+
+        assert_eq!(cache.get(&"t"), Some(&2));
+    }
 }
@@ -19,13 +19,18 @@
 // DEALINGS IN THE SOFTWARE.
 
 //! A collection of types using the Gossipsub system.
-use crate::TopicHash;
+use crate::{PublishError, TopicHash};
+use futures::channel::oneshot;
+use futures::prelude::*;
+use libp2p_core::PeerRecord;
 use libp2p_identity::PeerId;
 use libp2p_swarm::ConnectionId;
 use prometheus_client::encoding::EncodeLabelValue;
 use quick_protobuf::MessageWrite;
 use std::fmt;
 use std::fmt::Debug;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 use crate::rpc_proto::proto;
 #[cfg(feature = "serde")]
@@ -82,6 +87,8 @@ pub(crate) struct PeerConnections {
 /// Describes the types of peers that can exist in the gossipsub context.
 #[derive(Debug, Clone, PartialEq, Hash, EncodeLabelValue, Eq)]
 pub enum PeerKind {
+    /// A gossipsub 1.2 peer.
+    Gossipsubv1_2,
     /// A gossipsub 1.1 peer.
     Gossipsubv1_1,
     /// A gossipsub 1.0 peer.
@@ -127,6 +134,7 @@ impl RawMessage {
             topic: TopicHash::into_string(self.topic.clone()),
             signature: self.signature.clone(),
             key: self.key.clone(),
+            topic_alias: None,
         };
         message.get_size()
     }
@@ -141,6 +149,7 @@ impl From<RawMessage> for proto::Message {
             topic: TopicHash::into_string(raw.topic),
             signature: raw.signature,
             key: raw.key,
+            topic_alias: None,
         }
     }
 }
@@ -194,12 +203,24 @@ pub enum SubscriptionAction {
     Unsubscribe,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A peer proposed in the peer exchange of a PRUNE control message.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PeerInfo {
     pub peer_id: Option<PeerId>,
-    //TODO add this when RFC: Signed Address Records got added to the spec (see pull request
-    // https://github.com/libp2p/specs/pull/217)
-    //pub signed_peer_record: ?,
+    /// The signed peer record of the peer, carrying the addresses to dial it at.
+    ///
+    /// Received records are verified to be signed by the peer.
+    pub signed_peer_record: Option<PeerRecord>,
+}
+
+impl std::hash::Hash for PeerInfo {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.peer_id.hash(state);
+        self.signed_peer_record
+            .as_ref()
+            .map(PeerRecord::seq)
+            .hash(state);
+    }
 }
 
 /// A Control message received by the gossipsub system.
@@ -231,6 +252,24 @@ pub enum ControlAction {
         /// The backoff time in seconds before we allow to reconnect
         backoff: Option<u64>,
     },
+    /// The node has received messages and asks its mesh peers not to send them - IDontWant
+    /// control message of gossipsub v1.2.
+    IDontWant {
+        /// A list of received message ids (peer_id + sequence _number) as a string.
+        message_ids: Vec<MessageId>,
+    },
+    /// The node asks a mesh peer to announce the messages of a topic via IHAVE instead of
+    /// sending them - Choke control message of the choking extension (episub).
+    Choke {
+        /// The mesh topic the peer is choked on.
+        topic_hash: TopicHash,
+    },
+    /// The node asks a choked mesh peer to send the messages of a topic again - Unchoke control
+    /// message of the choking extension (episub).
+    Unchoke {
+        /// The mesh topic the peer is unchoked on.
+        topic_hash: TopicHash,
+    },
 }
 
 /// A Gossipsub RPC message sent.
@@ -300,6 +339,9 @@ impl From<RpcOut> for proto::RPC {
                     iwant: vec![],
                     graft: vec![],
                     prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![],
                 }),
             },
             RpcOut::Control(ControlAction::IWant { message_ids }) => proto::RPC {
@@ -312,6 +354,9 @@ impl From<RpcOut> for proto::RPC {
                     }],
                     graft: vec![],
                     prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![],
                 }),
             },
             RpcOut::Control(ControlAction::Graft { topic_hash }) => proto::RPC {
@@ -324,35 +369,85 @@ impl From<RpcOut> for proto::RPC {
                         topic_id: Some(topic_hash.into_string()),
                     }],
                     prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![],
                 }),
             },
             RpcOut::Control(ControlAction::Prune {
                 topic_hash,
                 peers,
                 backoff,
-            }) => {
-                proto::RPC {
-                    publish: Vec::new(),
-                    subscriptions: vec![],
-                    control: Some(proto::ControlMessage {
-                        ihave: vec![],
-                        iwant: vec![],
-                        graft: vec![],
-                        prune: vec![proto::ControlPrune {
-                            topic_id: Some(topic_hash.into_string()),
-                            peers: peers
-                                .into_iter()
-                                .map(|info| proto::PeerInfo {
-                                    peer_id: info.peer_id.map(|id| id.to_bytes()),
-                                    // TODO, see https://github.com/libp2p/specs/pull/217
-                                    signed_peer_record: None,
-                                })
-                                .collect(),
-                            backoff,
-                        }],
-                    }),
-                }
-            }
+            }) => proto::RPC {
+                publish: Vec::new(),
+                subscriptions: vec![],
+                control: Some(proto::ControlMessage {
+                    ihave: vec![],
+                    iwant: vec![],
+                    graft: vec![],
+                    prune: vec![proto::ControlPrune {
+                        topic_id: Some(topic_hash.into_string()),
+                        peers: peers
+                            .into_iter()
+                            .map(|info| proto::PeerInfo {
+                                peer_id: info.peer_id.map(|id| id.to_bytes()),
+                                signed_peer_record: info
+                                    .signed_peer_record
+                                    .map(|r| r.into_signed_envelope().into_protobuf_encoding()),
+                            })
+                            .collect(),
+                        backoff,
+                    }],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![],
+                }),
+            },
+            RpcOut::Control(ControlAction::IDontWant { message_ids }) => proto::RPC {
+                publish: Vec::new(),
+                subscriptions: vec![],
+                control: Some(proto::ControlMessage {
+                    ihave: vec![],
+                    iwant: vec![],
+                    graft: vec![],
+                    prune: vec![],
+                    idontwant: vec![proto::ControlIDontWant {
+                        message_ids: message_ids.into_iter().map(|msg_id| msg_id.0).collect(),
+                    }],
+                    choke: vec![],
+                    unchoke: vec![],
+                }),
+            },
+            RpcOut::Control(ControlAction::Choke { topic_hash }) => proto::RPC {
+                publish: Vec::new(),
+                subscriptions: vec![],
+                control: Some(proto::ControlMessage {
+                    ihave: vec![],
+                    iwant: vec![],
+                    graft: vec![],
+                    prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![proto::ControlChoke {
+                        topic_id: Some(topic_hash.into_string()),
+                    }],
+                    unchoke: vec![],
+                }),
+            },
+            RpcOut::Control(ControlAction::Unchoke { topic_hash }) => proto::RPC {
+                publish: Vec::new(),
+                subscriptions: vec![],
+                control: Some(proto::ControlMessage {
+                    ihave: vec![],
+                    iwant: vec![],
+                    graft: vec![],
+                    prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![proto::ControlUnChoke {
+                        topic_id: Some(topic_hash.into_string()),
+                    }],
+                }),
+            },
         }
     }
 }
@@ -390,6 +485,7 @@ impl From<Rpc> for proto::RPC {
                 topic: TopicHash::into_string(message.topic),
                 signature: message.signature,
                 key: message.key,
+                topic_alias: None,
             };
 
             publish.push(message);
@@ -411,6 +507,9 @@ impl From<Rpc> for proto::RPC {
             iwant: Vec::new(),
             graft: Vec::new(),
             prune: Vec::new(),
+            idontwant: Vec::new(),
+            choke: Vec::new(),
+            unchoke: Vec::new(),
         };
 
         let empty_control_msg = rpc.control_msgs.is_empty();
@@ -451,14 +550,33 @@ impl From<Rpc> for proto::RPC {
                             .into_iter()
                             .map(|info| proto::PeerInfo {
                                 peer_id: info.peer_id.map(|id| id.to_bytes()),
-                                // TODO, see https://github.com/libp2p/specs/pull/217
-                                signed_peer_record: None,
+                                signed_peer_record: info
+                                    .signed_peer_record
+                                    .map(|r| r.into_signed_envelope().into_protobuf_encoding()),
                             })
                             .collect(),
                         backoff,
                     };
                     control.prune.push(rpc_prune);
                 }
+                ControlAction::IDontWant { message_ids } => {
+                    let rpc_idontwant = proto::ControlIDontWant {
+                        message_ids: message_ids.into_iter().map(|msg_id| msg_id.0).collect(),
+                    };
+                    control.idontwant.push(rpc_idontwant);
+                }
+                ControlAction::Choke { topic_hash } => {
+                    let rpc_choke = proto::ControlChoke {
+                        topic_id: Some(topic_hash.into_string()),
+                    };
+                    control.choke.push(rpc_choke);
+                }
+                ControlAction::Unchoke { topic_hash } => {
+                    let rpc_unchoke = proto::ControlUnChoke {
+                        topic_id: Some(topic_hash.into_string()),
+                    };
+                    control.unchoke.push(rpc_unchoke);
+                }
             }
         }
 
@@ -497,8 +615,22 @@ impl PeerKind {
             Self::Floodsub => "Floodsub",
             Self::Gossipsub => "Gossipsub v1.0",
             Self::Gossipsubv1_1 => "Gossipsub v1.1",
+            Self::Gossipsubv1_2 => "Gossipsub v1.2",
         }
     }
+
+    /// Returns whether the peer supports gossipsub, of any version.
+    pub(crate) fn is_gossipsub(&self) -> bool {
+        matches!(
+            self,
+            Self::Gossipsubv1_2 | Self::Gossipsubv1_1 | Self::Gossipsub
+        )
+    }
+
+    /// Returns whether the peer supports gossipsub v1.1, or a later version.
+    pub(crate) fn is_gossipsub_v1_1(&self) -> bool {
+        matches!(self, Self::Gossipsubv1_2 | Self::Gossipsubv1_1)
+    }
 }
 
 impl AsRef<str> for PeerKind {
@@ -512,3 +644,42 @@ impl fmt::Display for PeerKind {
         f.write_str(self.as_ref())
     }
 }
+
+/// Identifies a message published via [`crate::Behaviour::publish_awaitable`] whose delivery to
+/// the individual recipients is tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PublishId(pub(crate) u64);
+
+/// A handle to a message published via [`crate::Behaviour::publish_awaitable`].
+///
+/// Resolves to the number of peers the message was sent to once it was sent to at least the
+/// requested number of mesh, fanout, explicit or floodsub peers, or to
+/// [`PublishError::InsufficientRecipients`] once that is no longer possible.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct PublishHandle {
+    pub(crate) message_id: MessageId,
+    pub(crate) receiver: oneshot::Receiver<Result<usize, PublishError>>,
+}
+
+impl PublishHandle {
+    /// The id of the published message.
+    pub fn message_id(&self) -> &MessageId {
+        &self.message_id
+    }
+}
+
+impl Future for PublishHandle {
+    type Output = Result<usize, PublishError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.receiver.poll_unpin(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            // The behaviour was dropped before the delivery was confirmed.
+            Poll::Ready(Err(oneshot::Canceled)) => {
+                Poll::Ready(Err(PublishError::InsufficientRecipients { recipients: 0 }))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
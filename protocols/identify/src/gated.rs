@@ -0,0 +1,518 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::behaviour::{Behaviour, Event};
+use crate::handler::{self, Handler};
+use either::Either;
+use futures::{future, FutureExt};
+use futures_timer::Delay;
+use libp2p_core::upgrade::DeniedUpgrade;
+use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr};
+use libp2p_identity::PeerId;
+use libp2p_swarm::behaviour::{
+    AddressChange, BandwidthEstimated, ConnectionClosed, ConnectionEstablished, ConnectionStreams,
+    FromSwarm,
+};
+use libp2p_swarm::handler::{
+    ConnectionEvent, FullyNegotiatedInbound, ListenUpgradeError, SendWrapper,
+};
+use libp2p_swarm::{
+    BandwidthEstimate, ConnectionDenied, ConnectionHandler, ConnectionHandlerEvent,
+    ConnectionHandlerSelect, ConnectionId, NetworkBehaviour, NotifyHandler, StreamUsage,
+    SubstreamProtocol, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use std::collections::{HashMap, VecDeque};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The default of [`Gated::with_timeout`].
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Network behaviour that identifies every new connection before the inner behaviour
+/// gets to use it.
+///
+/// Identification is requested as the first protocol on every established connection.
+/// Until the remote has been identified, identification failed or [`Gated::with_timeout`]
+/// elapsed, the inner behaviour is not informed of the connection via
+/// [`FromSwarm::ConnectionEstablished`] and its [`ConnectionHandler`] is not polled, i.e.
+/// cannot open streams. Streams opened by the remote in the meantime are denied, except for
+/// identify itself. By then, the inner handler has been informed of the protocols
+/// supported by the remote, thus protocol-compatibility decisions can be made before any
+/// application traffic.
+///
+/// Events of the identify [`Behaviour`] are reported as [`Either::Left`], those of the inner
+/// behaviour as [`Either::Right`].
+pub struct Gated<B> {
+    identify: Behaviour,
+    inner: B,
+    timeout: Duration,
+    /// The connections not yet identified, withheld from the inner behaviour.
+    pending: HashMap<ConnectionId, PendingConnection>,
+    /// The connections whose handlers are to be opened.
+    to_open: VecDeque<(PeerId, ConnectionId)>,
+}
+
+/// An established connection withheld from the inner behaviour.
+struct PendingConnection {
+    peer_id: PeerId,
+    endpoint: ConnectedPoint,
+    failed_addresses: Vec<Multiaddr>,
+    other_established: usize,
+    /// Reported via [`FromSwarm::ConnectionStreams`] once the connection is opened.
+    streams: Option<StreamUsage>,
+    /// Reported via [`FromSwarm::BandwidthEstimated`] once the connection is opened.
+    bandwidth: Option<BandwidthEstimate>,
+}
+
+impl<B> Gated<B> {
+    /// Creates a new [`Gated`] behaviour identifying new connections via `identify` before
+    /// informing `inner` of them.
+    pub fn new(identify: Behaviour, inner: B) -> Self {
+        Self {
+            identify,
+            inner,
+            timeout: DEFAULT_TIMEOUT,
+            pending: HashMap::new(),
+            to_open: VecDeque::new(),
+        }
+    }
+
+    /// Configures how long the inner behaviour is withheld from a connection at most,
+    /// awaiting the identification of the remote.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns a reference to the identify [`Behaviour`].
+    pub fn identify(&self) -> &Behaviour {
+        &self.identify
+    }
+
+    /// Returns a mutable reference to the identify [`Behaviour`].
+    pub fn identify_mut(&mut self) -> &mut Behaviour {
+        &mut self.identify
+    }
+
+    /// Returns a reference to the inner behaviour.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner behaviour.
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+}
+
+impl<B> Gated<B>
+where
+    B: NetworkBehaviour,
+{
+    /// Informs the inner behaviour of a pending connection, returning `false` if the
+    /// connection is not pending (anymore).
+    fn open(&mut self, connection_id: ConnectionId) -> bool {
+        let Some(connection) = self.pending.remove(&connection_id) else {
+            return false;
+        };
+
+        self.inner
+            .on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                peer_id: connection.peer_id,
+                connection_id,
+                endpoint: &connection.endpoint,
+                failed_addresses: &connection.failed_addresses,
+                other_established: connection.other_established,
+            }));
+        if let Some(usage) = &connection.streams {
+            self.inner
+                .on_swarm_event(FromSwarm::ConnectionStreams(ConnectionStreams {
+                    peer_id: connection.peer_id,
+                    connection_id,
+                    usage,
+                }));
+        }
+        if let Some(estimate) = connection.bandwidth {
+            self.inner
+                .on_swarm_event(FromSwarm::BandwidthEstimated(BandwidthEstimated {
+                    peer_id: connection.peer_id,
+                    connection_id,
+                    estimate,
+                }));
+        }
+
+        true
+    }
+}
+
+impl<B> NetworkBehaviour for Gated<B>
+where
+    B: NetworkBehaviour,
+{
+    type ConnectionHandler = ConnectionHandlerSelect<Handler, GatedHandler<THandler<B>>>;
+    type ToSwarm = Either<Event, B::ToSwarm>;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.identify
+            .handle_pending_inbound_connection(connection_id, local_addr, remote_addr)?;
+        self.inner
+            .handle_pending_inbound_connection(connection_id, local_addr, remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        let identify = self.identify.handle_established_inbound_connection(
+            connection_id,
+            peer,
+            local_addr,
+            remote_addr,
+        )?;
+        let inner = self.inner.handle_established_inbound_connection(
+            connection_id,
+            peer,
+            local_addr,
+            remote_addr,
+        )?;
+
+        Ok(identify.select(GatedHandler::new(inner, self.timeout)))
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        let mut combined = self.identify.handle_pending_outbound_connection(
+            connection_id,
+            maybe_peer,
+            addresses,
+            effective_role,
+        )?;
+        combined.extend(self.inner.handle_pending_outbound_connection(
+            connection_id,
+            maybe_peer,
+            addresses,
+            effective_role,
+        )?);
+
+        Ok(combined)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        let identify = self.identify.handle_established_outbound_connection(
+            connection_id,
+            peer,
+            addr,
+            role_override,
+        )?;
+        let inner = self.inner.handle_established_outbound_connection(
+            connection_id,
+            peer,
+            addr,
+            role_override,
+        )?;
+
+        Ok(identify.select(GatedHandler::new(inner, self.timeout)))
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        self.identify.on_swarm_event(event);
+
+        match event {
+            FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                peer_id,
+                connection_id,
+                endpoint,
+                failed_addresses,
+                other_established,
+            }) => {
+                self.pending.insert(
+                    connection_id,
+                    PendingConnection {
+                        peer_id,
+                        endpoint: endpoint.clone(),
+                        failed_addresses: failed_addresses.to_vec(),
+                        other_established,
+                        streams: None,
+                        bandwidth: None,
+                    },
+                );
+            }
+            FromSwarm::ConnectionClosed(ConnectionClosed { connection_id, .. })
+                if self.pending.remove(&connection_id).is_some() => {}
+            FromSwarm::AddressChange(AddressChange {
+                connection_id, new, ..
+            }) if self.pending.contains_key(&connection_id) => {
+                if let Some(connection) = self.pending.get_mut(&connection_id) {
+                    connection.endpoint = new.clone();
+                }
+            }
+            FromSwarm::ConnectionStreams(streams)
+                if self.pending.contains_key(&streams.connection_id) =>
+            {
+                if let Some(connection) = self.pending.get_mut(&streams.connection_id) {
+                    connection.streams = Some(streams.usage.clone());
+                }
+            }
+            FromSwarm::BandwidthEstimated(estimated)
+                if self.pending.contains_key(&estimated.connection_id) =>
+            {
+                if let Some(connection) = self.pending.get_mut(&estimated.connection_id) {
+                    connection.bandwidth = Some(estimated.estimate);
+                }
+            }
+            event => self.inner.on_swarm_event(event),
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        match event {
+            Either::Left(event) => {
+                if matches!(
+                    event,
+                    handler::Event::Identified(_) | handler::Event::IdentificationError(_)
+                ) && self.open(connection_id)
+                {
+                    self.to_open.push_back((peer_id, connection_id));
+                }
+                self.identify
+                    .on_connection_handler_event(peer_id, connection_id, event);
+            }
+            Either::Right(GatedOutEvent::TimedOut) => {
+                if self.open(connection_id) {
+                    tracing::debug!(peer=%peer_id, "Identification timed out, opening connection");
+                }
+            }
+            Either::Right(GatedOutEvent::Inner(event)) => {
+                // Only emitted by a closing handler of a connection never opened.
+                if self.pending.contains_key(&connection_id) {
+                    return;
+                }
+                self.inner
+                    .on_connection_handler_event(peer_id, connection_id, event);
+            }
+        }
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        if let Some((peer_id, connection_id)) = self.to_open.pop_front() {
+            return Poll::Ready(ToSwarm::NotifyHandler {
+                peer_id,
+                handler: NotifyHandler::One(connection_id),
+                event: Either::Right(GatedInEvent::Open),
+            });
+        }
+
+        if let Poll::Ready(event) = self.identify.poll(cx) {
+            return Poll::Ready(event.map_out(Either::Left).map_in(Either::Left));
+        }
+
+        if let Poll::Ready(event) = self.inner.poll(cx) {
+            return Poll::Ready(
+                event
+                    .map_out(Either::Right)
+                    .map_in(|event| Either::Right(GatedInEvent::Inner(event))),
+            );
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Wraps the [`ConnectionHandler`] of the inner behaviour of [`Gated`], which is only
+/// polled once the connection has been opened.
+pub struct GatedHandler<H> {
+    inner: H,
+    open: bool,
+    timeout: Delay,
+}
+
+/// An event from [`Gated`] to a [`GatedHandler`].
+#[derive(Debug)]
+pub enum GatedInEvent<T> {
+    /// The remote has been identified, the inner handler may be polled.
+    Open,
+    Inner(T),
+}
+
+/// An event from a [`GatedHandler`] to [`Gated`].
+#[derive(Debug)]
+pub enum GatedOutEvent<T> {
+    /// The remote has not been identified in time, the inner handler is polled regardless.
+    TimedOut,
+    Inner(T),
+}
+
+impl<H> GatedHandler<H> {
+    fn new(inner: H, timeout: Duration) -> Self {
+        Self {
+            inner,
+            open: false,
+            timeout: Delay::new(timeout),
+        }
+    }
+}
+
+impl<H> ConnectionHandler for GatedHandler<H>
+where
+    H: ConnectionHandler,
+{
+    type FromBehaviour = GatedInEvent<H::FromBehaviour>;
+    type ToBehaviour = GatedOutEvent<H::ToBehaviour>;
+    type InboundProtocol = Either<SendWrapper<H::InboundProtocol>, DeniedUpgrade>;
+    type OutboundProtocol = H::OutboundProtocol;
+    type InboundOpenInfo = Either<H::InboundOpenInfo, ()>;
+    type OutboundOpenInfo = H::OutboundOpenInfo;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        // Deny the streams of the remote until the connection is opened.
+        if !self.open {
+            return SubstreamProtocol::new(Either::Right(DeniedUpgrade), Either::Right(()));
+        }
+
+        self.inner
+            .listen_protocol()
+            .map_upgrade(|upgrade| Either::Left(SendWrapper(upgrade)))
+            .map_info(Either::Left)
+    }
+
+    fn on_behaviour_event(&mut self, event: Self::FromBehaviour) {
+        match event {
+            GatedInEvent::Open => self.open = true,
+            GatedInEvent::Inner(event) => self.inner.on_behaviour_event(event),
+        }
+    }
+
+    fn connection_keep_alive(&self) -> bool {
+        !self.open || self.inner.connection_keep_alive()
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<
+        ConnectionHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::ToBehaviour>,
+    > {
+        if !self.open {
+            futures::ready!(self.timeout.poll_unpin(cx));
+            self.open = true;
+            return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                GatedOutEvent::TimedOut,
+            ));
+        }
+
+        self.inner
+            .poll(cx)
+            .map(|event| event.map_custom(GatedOutEvent::Inner))
+    }
+
+    fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<Option<Self::ToBehaviour>> {
+        self.inner
+            .poll_close(cx)
+            .map(|event| event.map(GatedOutEvent::Inner))
+    }
+
+    fn on_connection_event(
+        &mut self,
+        event: ConnectionEvent<
+            Self::InboundProtocol,
+            Self::OutboundProtocol,
+            Self::InboundOpenInfo,
+            Self::OutboundOpenInfo,
+        >,
+    ) {
+        match event {
+            // Streams are only accepted for the inner handler once opened, see `listen_protocol`.
+            ConnectionEvent::FullyNegotiatedInbound(FullyNegotiatedInbound { protocol, info }) => {
+                match (protocol, info) {
+                    (future::Either::Left(protocol), Either::Left(info)) => self
+                        .inner
+                        .on_connection_event(ConnectionEvent::FullyNegotiatedInbound(
+                            FullyNegotiatedInbound { protocol, info },
+                        )),
+                    (future::Either::Right(never), _) => void::unreachable(never),
+                    (future::Either::Left(_), Either::Right(())) => {
+                        unreachable!("the info of a stream matches its upgrade")
+                    }
+                }
+            }
+            ConnectionEvent::ListenUpgradeError(ListenUpgradeError { info, error }) => {
+                match (error, info) {
+                    (Either::Left(error), Either::Left(info)) => {
+                        self.inner
+                            .on_connection_event(ConnectionEvent::ListenUpgradeError(
+                                ListenUpgradeError { info, error },
+                            ))
+                    }
+                    (Either::Right(never), _) => void::unreachable(never),
+                    (Either::Left(_), Either::Right(())) => {
+                        unreachable!("the info of a stream matches its upgrade")
+                    }
+                }
+            }
+            ConnectionEvent::FullyNegotiatedOutbound(event) => self
+                .inner
+                .on_connection_event(ConnectionEvent::FullyNegotiatedOutbound(event)),
+            ConnectionEvent::DialUpgradeError(event) => self
+                .inner
+                .on_connection_event(ConnectionEvent::DialUpgradeError(event)),
+            // Forwarded regardless of the connection being opened, informing the inner
+            // handler of the protocols of the remote ahead of any application traffic.
+            ConnectionEvent::AddressChange(event) => self
+                .inner
+                .on_connection_event(ConnectionEvent::AddressChange(event)),
+            ConnectionEvent::LocalProtocolsChange(event) => self
+                .inner
+                .on_connection_event(ConnectionEvent::LocalProtocolsChange(event)),
+            ConnectionEvent::RemoteProtocolsChange(event) => self
+                .inner
+                .on_connection_event(ConnectionEvent::RemoteProtocolsChange(event)),
+            _ => {}
+        }
+    }
+}
@@ -38,13 +38,18 @@
 //! The [`Behaviour`] struct implements a [`NetworkBehaviour`](libp2p_swarm::NetworkBehaviour)
 //! that negotiates and executes the protocol on every established connection, emitting
 //! [`Event`]s.
+//!
+//! The [`Gated`] struct wraps another [`NetworkBehaviour`](libp2p_swarm::NetworkBehaviour),
+//! identifying every new connection before the wrapped behaviour gets to use it.
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
 pub use self::behaviour::{Behaviour, Config, Event};
+pub use self::gated::{Gated, GatedHandler, GatedInEvent, GatedOutEvent};
 pub use self::protocol::{Info, UpgradeError, PROTOCOL_NAME, PUSH_PROTOCOL_NAME};
 
 mod behaviour;
+mod gated;
 mod handler;
 mod protocol;
 
@@ -1,10 +1,18 @@
+use either::Either;
 use futures::StreamExt;
 use libp2p_core::multiaddr::Protocol;
+use libp2p_core::{Endpoint, Multiaddr};
 use libp2p_identify as identify;
-use libp2p_swarm::{Swarm, SwarmEvent};
+use libp2p_identity::PeerId;
+use libp2p_swarm::behaviour::FromSwarm;
+use libp2p_swarm::{
+    dummy, ConnectionDenied, ConnectionId, NetworkBehaviour, StreamProtocol, Swarm, SwarmEvent,
+    THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
 use libp2p_swarm_test::SwarmExt;
 use std::collections::HashSet;
 use std::iter;
+use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 use tracing_subscriber::EnvFilter;
 
@@ -372,3 +380,177 @@ async fn configured_interval_starts_after_first_identify() {
 
     assert!(time_to_first_identify < identify_interval)
 }
+
+#[async_std::test]
+async fn gated_behaviour_learns_of_connection_after_identification() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarm1 = Swarm::new_ephemeral(|identity| {
+        identify::Gated::new(
+            identify::Behaviour::new(identify::Config::new("a".to_string(), identity.public())),
+            ConnectionRecorder::default(),
+        )
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(identify::Config::new("a".to_string(), identity.public()))
+    });
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    let mut identified = false;
+    loop {
+        match swarm1.next_behaviour_event().await {
+            Either::Left(identify::Event::Received { .. }) => identified = true,
+            Either::Left(_) => {}
+            Either::Right(()) => break,
+        }
+    }
+    assert!(identified);
+}
+
+#[async_std::test]
+async fn gated_behaviour_learns_of_connection_if_identification_fails() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarm1 = Swarm::new_ephemeral(|identity| {
+        identify::Gated::new(
+            identify::Behaviour::new(identify::Config::new("a".to_string(), identity.public())),
+            ConnectionRecorder::default(),
+        )
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|_| dummy::Behaviour);
+
+    swarm2.listen().with_memory_addr_external().await;
+    swarm1.connect(&mut swarm2).await;
+
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    let mut failed = false;
+    loop {
+        match swarm1.next_behaviour_event().await {
+            Either::Left(identify::Event::Error { .. }) => failed = true,
+            Either::Left(_) => {}
+            Either::Right(()) => break,
+        }
+    }
+    assert!(failed);
+}
+
+#[async_std::test]
+async fn gated_behaviour_denies_streams_before_identification() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    const APP_PROTOCOL: StreamProtocol = StreamProtocol::new("/app/1.0.0");
+    let gate_timeout = Duration::from_millis(500);
+
+    let mut swarm1 = Swarm::new_ephemeral(|identity| {
+        identify::Gated::new(
+            identify::Behaviour::new(identify::Config::new("a".to_string(), identity.public())),
+            libp2p_stream::Behaviour::new(),
+        )
+        .with_timeout(gate_timeout)
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|_| libp2p_stream::Behaviour::new());
+    let swarm1_id = *swarm1.local_peer_id();
+
+    let mut app_streams = swarm1.behaviour().inner().new_control();
+    let mut incoming_app_streams = app_streams.accept(APP_PROTOCOL).unwrap();
+    let mut control = swarm2.behaviour().new_control();
+    // Accept identify requests without ever answering them, thus identification stalls.
+    let _stalled_identify = control.accept(identify::PROTOCOL_NAME).unwrap();
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    async_std::task::spawn(swarm1.loop_on_next());
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    let error = control
+        .open_stream(swarm1_id, APP_PROTOCOL)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        libp2p_stream::OpenStreamError::UnsupportedProtocol(_)
+    ));
+
+    // The inner behaviour gets to use the connection once the gate timed out.
+    async_std::task::sleep(gate_timeout).await;
+    control.open_stream(swarm1_id, APP_PROTOCOL).await.unwrap();
+    incoming_app_streams.next().await.unwrap();
+}
+
+/// Reports every established connection it is informed of, once informed of its streams.
+#[derive(Default)]
+struct ConnectionRecorder {
+    connections: HashSet<ConnectionId>,
+    established: usize,
+}
+
+impl NetworkBehaviour for ConnectionRecorder {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = ();
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(established) => {
+                self.connections.insert(established.connection_id);
+            }
+            FromSwarm::ConnectionStreams(streams) => {
+                assert!(self.connections.contains(&streams.connection_id));
+                self.established += 1;
+            }
+            FromSwarm::BandwidthEstimated(estimated) => {
+                assert!(self.connections.contains(&estimated.connection_id));
+            }
+            _ => {}
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        void::unreachable(event)
+    }
+
+    fn poll(&mut self, _: &mut Context<'_>) -> Poll<ToSwarm<(), THandlerInEvent<Self>>> {
+        if self.established > 0 {
+            self.established -= 1;
+            return Poll::Ready(ToSwarm::GenerateEvent(()));
+        }
+
+        Poll::Pending
+    }
+}
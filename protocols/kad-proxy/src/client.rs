@@ -0,0 +1,380 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The client side of delegated routing.
+
+use crate::protocol::{peers_from_wire, Request, Response, WireQuorum, WireRecord};
+use crate::Error;
+use libp2p_core::{Endpoint, Multiaddr};
+use libp2p_identity::PeerId;
+use libp2p_kad::{PeerRecord, Quorum, Record, RecordKey};
+use libp2p_request_response::{self as request_response, OutboundRequestId, ProtocolSupport};
+use libp2p_swarm::{
+    ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler, THandlerInEvent,
+    THandlerOutEvent, ToSwarm,
+};
+use std::collections::{HashMap, HashSet};
+use std::iter;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The configuration of a [`Behaviour`].
+#[derive(Debug, Clone)]
+pub struct Config {
+    request_timeout: Duration,
+}
+
+impl Config {
+    /// Sets the time to wait for the response of a server.
+    ///
+    /// As the server runs a full DHT query before it responds, the timeout should exceed the
+    /// query timeout of the server. Defaults to 2 minutes.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+/// The identifier of a query delegated to a server.
+pub type QueryId = OutboundRequestId;
+
+/// The kind of a pending query, used to tell the responses apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueryKind {
+    GetRecord,
+    GetProviders,
+    GetClosestPeers,
+    PutRecord,
+}
+
+/// A [`NetworkBehaviour`] that delegates DHT queries to trusted servers.
+pub struct Behaviour {
+    inner: request_response::cbor::Behaviour<Request, Response>,
+
+    /// The servers queries are delegated to, in the order they were added.
+    servers: Vec<PeerId>,
+    /// The index into `servers` of the server to use for the next query.
+    next_server: usize,
+
+    pending_queries: HashMap<QueryId, (PeerId, QueryKind)>,
+}
+
+impl Behaviour {
+    /// Creates a new delegated routing client without any servers.
+    ///
+    /// Servers are added via [`Behaviour::add_server`].
+    pub fn new(config: Config) -> Self {
+        Self {
+            inner: request_response::cbor::Behaviour::new(
+                iter::once((crate::PROTOCOL_IDENT, ProtocolSupport::Outbound)),
+                request_response::Config::default().with_request_timeout(config.request_timeout),
+            ),
+            servers: Vec::new(),
+            next_server: 0,
+            pending_queries: Default::default(),
+        }
+    }
+
+    /// Adds a server that queries may be delegated to.
+    ///
+    /// Queries are distributed among all servers in a round-robin fashion. The addresses of
+    /// the server have to be made known to the [`Swarm`](libp2p_swarm::Swarm), e.g. via
+    /// [`Swarm::add_peer_address`](libp2p_swarm::Swarm::add_peer_address).
+    pub fn add_server(&mut self, server: PeerId) {
+        if !self.servers.contains(&server) {
+            self.servers.push(server);
+        }
+    }
+
+    /// Removes a server, such that no further queries are delegated to it.
+    ///
+    /// Queries that are already in progress at the server are not affected.
+    pub fn remove_server(&mut self, server: &PeerId) {
+        self.servers.retain(|s| s != server);
+    }
+
+    /// Returns the servers queries are delegated to.
+    pub fn servers(&self) -> impl Iterator<Item = &PeerId> {
+        self.servers.iter()
+    }
+
+    /// Looks up the records of the given key.
+    ///
+    /// The result is reported via [`Event::QueryCompleted`] with a
+    /// [`QueryResult::GetRecord`]. A [`PeerRecord`] without a peer was retrieved from the
+    /// local storage of the server.
+    pub fn get_record(&mut self, key: RecordKey) -> Result<QueryId, NoServers> {
+        self.send(
+            Request::GetRecord { key: key.to_vec() },
+            QueryKind::GetRecord,
+        )
+    }
+
+    /// Looks up the providers of the given key.
+    ///
+    /// The result is reported via [`Event::QueryCompleted`] with a
+    /// [`QueryResult::GetProviders`].
+    pub fn get_providers(&mut self, key: RecordKey) -> Result<QueryId, NoServers> {
+        self.send(
+            Request::GetProviders { key: key.to_vec() },
+            QueryKind::GetProviders,
+        )
+    }
+
+    /// Looks up the peers closest to the given key.
+    ///
+    /// The result is reported via [`Event::QueryCompleted`] with a
+    /// [`QueryResult::GetClosestPeers`].
+    pub fn get_closest_peers<K>(&mut self, key: K) -> Result<QueryId, NoServers>
+    where
+        K: Into<Vec<u8>>,
+    {
+        self.send(
+            Request::GetClosestPeers { key: key.into() },
+            QueryKind::GetClosestPeers,
+        )
+    }
+
+    /// Stores the given record in the DHT.
+    ///
+    /// The record is published by the server and thus carries the server as its publisher.
+    /// The result is reported via [`Event::QueryCompleted`] with a
+    /// [`QueryResult::PutRecord`].
+    pub fn put_record(&mut self, record: Record, quorum: Quorum) -> Result<QueryId, NoServers> {
+        self.send(
+            Request::PutRecord {
+                record: WireRecord::from(&record),
+                quorum: WireQuorum::from(quorum),
+            },
+            QueryKind::PutRecord,
+        )
+    }
+
+    fn send(&mut self, request: Request, kind: QueryKind) -> Result<QueryId, NoServers> {
+        if self.servers.is_empty() {
+            return Err(NoServers());
+        }
+        let server = self.servers[self.next_server % self.servers.len()];
+        self.next_server = (self.next_server + 1) % self.servers.len();
+
+        let id = self.inner.send_request(&server, request);
+        self.pending_queries.insert(id, (server, kind));
+
+        Ok(id)
+    }
+
+    fn on_response(&mut self, id: QueryId, response: Response) -> Option<Event> {
+        let (server, kind) = self.pending_queries.remove(&id)?;
+
+        let result = match (kind, response) {
+            (QueryKind::GetRecord, Response::GetRecord(result)) => {
+                QueryResult::GetRecord(result.and_then(|records| {
+                    records
+                        .into_iter()
+                        .map(PeerRecord::try_from)
+                        .collect::<Result<_, _>>()
+                }))
+            }
+            (QueryKind::GetProviders, Response::GetProviders(result)) => {
+                QueryResult::GetProviders(result.and_then(peers_from_wire))
+            }
+            (QueryKind::GetClosestPeers, Response::GetClosestPeers(result)) => {
+                QueryResult::GetClosestPeers(result.and_then(peers_from_wire))
+            }
+            (QueryKind::PutRecord, Response::PutRecord(result)) => {
+                QueryResult::PutRecord(result.and_then(peers_from_wire))
+            }
+            (kind, response) => {
+                tracing::debug!(%server, ?kind, ?response, "Server responded with the wrong kind of response");
+                kind.error_result(Error::InvalidMessage)
+            }
+        };
+
+        Some(Event::QueryCompleted { id, server, result })
+    }
+
+    fn on_outbound_failure(&mut self, id: QueryId) -> Option<Event> {
+        let (server, kind) = self.pending_queries.remove(&id)?;
+
+        Some(Event::QueryCompleted {
+            id,
+            server,
+            result: kind.error_result(Error::Unavailable),
+        })
+    }
+}
+
+impl QueryKind {
+    fn error_result(self, error: Error) -> QueryResult {
+        match self {
+            QueryKind::GetRecord => QueryResult::GetRecord(Err(error)),
+            QueryKind::GetProviders => QueryResult::GetProviders(Err(error)),
+            QueryKind::GetClosestPeers => QueryResult::GetClosestPeers(Err(error)),
+            QueryKind::PutRecord => QueryResult::PutRecord(Err(error)),
+        }
+    }
+}
+
+/// The error returned when a query is started without any servers.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("No servers to delegate queries to")]
+pub struct NoServers();
+
+/// The result of a query delegated to a server.
+#[derive(Debug, Clone)]
+pub enum QueryResult {
+    /// The records found by [`Behaviour::get_record`].
+    GetRecord(Result<Vec<PeerRecord>, Error>),
+    /// The providers found by [`Behaviour::get_providers`].
+    GetProviders(Result<HashSet<PeerId>, Error>),
+    /// The closest peers found by [`Behaviour::get_closest_peers`].
+    GetClosestPeers(Result<Vec<PeerId>, Error>),
+    /// The peers that stored the record of [`Behaviour::put_record`].
+    PutRecord(Result<Vec<PeerId>, Error>),
+}
+
+#[derive(Debug)]
+pub enum Event {
+    /// A query delegated to a server completed.
+    QueryCompleted {
+        id: QueryId,
+        server: PeerId,
+        result: QueryResult,
+    },
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = <request_response::cbor::Behaviour<Request, Response> as NetworkBehaviour>::ConnectionHandler;
+
+    type ToSwarm = Event;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner.handle_established_inbound_connection(
+            connection_id,
+            peer,
+            local_addr,
+            remote_addr,
+        )
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        self.inner.handle_pending_outbound_connection(
+            connection_id,
+            maybe_peer,
+            addresses,
+            effective_role,
+        )
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner
+            .handle_established_outbound_connection(connection_id, peer, addr, role_override)
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        self.inner
+            .on_connection_handler_event(peer_id, connection_id, event);
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        self.inner.on_swarm_event(event);
+    }
+
+    #[tracing::instrument(level = "trace", name = "NetworkBehaviour::poll", skip(self, cx))]
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        loop {
+            match self.inner.poll(cx) {
+                Poll::Ready(ToSwarm::GenerateEvent(request_response::Event::Message {
+                    message:
+                        request_response::Message::Response {
+                            request_id,
+                            response,
+                        },
+                    ..
+                })) => {
+                    if let Some(event) = self.on_response(request_id, response) {
+                        return Poll::Ready(ToSwarm::GenerateEvent(event));
+                    }
+                }
+                Poll::Ready(ToSwarm::GenerateEvent(request_response::Event::OutboundFailure {
+                    request_id,
+                    ..
+                })) => {
+                    if let Some(event) = self.on_outbound_failure(request_id) {
+                        return Poll::Ready(ToSwarm::GenerateEvent(event));
+                    }
+                }
+                Poll::Ready(ToSwarm::GenerateEvent(request_response::Event::Message {
+                    peer,
+                    message: request_response::Message::Request { .. },
+                })) => {
+                    // Inbound requests are not supported, thus never reported.
+                    tracing::debug!(%peer, "Ignoring unexpected request");
+                }
+                Poll::Ready(ToSwarm::GenerateEvent(
+                    request_response::Event::InboundFailure { .. }
+                    | request_response::Event::ResponseSent { .. }
+                    | request_response::Event::InboundRequestTiming { .. }
+                    | request_response::Event::OutboundRequestTiming { .. },
+                )) => {}
+                Poll::Ready(other) => {
+                    return Poll::Ready(other.map_out(|_| {
+                        unreachable!("`GenerateEvent` is handled by the arms above")
+                    }));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Delegated routing for the Kademlia DHT.
+//!
+//! Constrained nodes, e.g. nodes running in a browser or on a mobile device, often cannot
+//! afford to take part in the DHT themselves: keeping a routing table up to date and running
+//! lookups requires many short-lived connections to other peers. With delegated routing,
+//! such a node instead asks a trusted server to run the lookups on its behalf.
+//!
+//! The [`client::Behaviour`] offers the familiar operations of [`libp2p_kad::Behaviour`],
+//! i.e. [`get_record`](client::Behaviour::get_record),
+//! [`get_providers`](client::Behaviour::get_providers),
+//! [`get_closest_peers`](client::Behaviour::get_closest_peers) and
+//! [`put_record`](client::Behaviour::put_record), and sends each of them as a single request
+//! to one of its servers. The [`server::Behaviour`] wraps a [`libp2p_kad::Behaviour`], runs a
+//! query for every request of a client and answers the request once the query finished.
+//! Which clients are served is decided by a [`server::ClientAuthorizer`]; by default, records
+//! put by clients are refused, as the server publishes them under its own peer ID.
+//!
+//! Requests and responses are exchanged over the `/libp2p/kad-proxy/1.0.0` protocol using
+//! [`libp2p_request_response`] with CBOR encoding.
+
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+use libp2p_swarm::StreamProtocol;
+
+mod protocol;
+
+pub use protocol::Error;
+
+pub(crate) const PROTOCOL_IDENT: StreamProtocol = StreamProtocol::new("/libp2p/kad-proxy/1.0.0");
+
+pub mod client;
+pub mod server;
@@ -0,0 +1,202 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use instant::Instant;
+use libp2p_identity::PeerId;
+use libp2p_kad::{PeerRecord, Quorum, Record, RecordKey};
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+/// The reason why a query of a client did not succeed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum Error {
+    #[error("the record was not found")]
+    NotFound,
+    #[error("the quorum of the query was not reached")]
+    QuorumFailed,
+    #[error("the query of the server timed out")]
+    Timeout,
+    #[error("the server refused to run the query")]
+    Refused,
+    #[error("the server could not be reached or did not respond in time")]
+    Unavailable,
+    #[error("the remote sent an invalid message")]
+    InvalidMessage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Request {
+    GetRecord {
+        key: Vec<u8>,
+    },
+    GetProviders {
+        key: Vec<u8>,
+    },
+    GetClosestPeers {
+        key: Vec<u8>,
+    },
+    PutRecord {
+        record: WireRecord,
+        quorum: WireQuorum,
+    },
+}
+
+impl Request {
+    /// The response to send if the request is not served.
+    pub(crate) fn error_response(&self, error: Error) -> Response {
+        match self {
+            Request::GetRecord { .. } => Response::GetRecord(Err(error)),
+            Request::GetProviders { .. } => Response::GetProviders(Err(error)),
+            Request::GetClosestPeers { .. } => Response::GetClosestPeers(Err(error)),
+            Request::PutRecord { .. } => Response::PutRecord(Err(error)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Response {
+    GetRecord(Result<Vec<WirePeerRecord>, Error>),
+    GetProviders(Result<Vec<Vec<u8>>, Error>),
+    GetClosestPeers(Result<Vec<Vec<u8>>, Error>),
+    PutRecord(Result<Vec<Vec<u8>>, Error>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireRecord {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    publisher: Option<Vec<u8>>,
+    /// The remaining time-to-live of the record in seconds.
+    ttl: Option<u64>,
+    sequence: Option<u64>,
+}
+
+impl From<&Record> for WireRecord {
+    fn from(record: &Record) -> Self {
+        WireRecord {
+            key: record.key.to_vec(),
+            value: record.value.clone(),
+            publisher: record.publisher.map(|p| p.to_bytes()),
+            ttl: record
+                .expires
+                .map(|t| t.saturating_duration_since(Instant::now()).as_secs()),
+            sequence: record.sequence,
+        }
+    }
+}
+
+impl TryFrom<WireRecord> for Record {
+    type Error = Error;
+
+    fn try_from(record: WireRecord) -> Result<Self, Self::Error> {
+        let publisher = record
+            .publisher
+            .map(|p| PeerId::from_bytes(&p))
+            .transpose()
+            .map_err(|_| Error::InvalidMessage)?;
+
+        Ok(Record {
+            key: RecordKey::from(record.key),
+            value: record.value,
+            publisher,
+            expires: record
+                .ttl
+                .map(|ttl| Instant::now() + Duration::from_secs(ttl)),
+            sequence: record.sequence,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WirePeerRecord {
+    peer: Option<Vec<u8>>,
+    record: WireRecord,
+}
+
+impl From<&PeerRecord> for WirePeerRecord {
+    fn from(record: &PeerRecord) -> Self {
+        WirePeerRecord {
+            peer: record.peer.map(|p| p.to_bytes()),
+            record: WireRecord::from(&record.record),
+        }
+    }
+}
+
+impl TryFrom<WirePeerRecord> for PeerRecord {
+    type Error = Error;
+
+    fn try_from(record: WirePeerRecord) -> Result<Self, Self::Error> {
+        let peer = record
+            .peer
+            .map(|p| PeerId::from_bytes(&p))
+            .transpose()
+            .map_err(|_| Error::InvalidMessage)?;
+
+        Ok(PeerRecord {
+            peer,
+            record: Record::try_from(record.record)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum WireQuorum {
+    One,
+    Majority,
+    All,
+    N(NonZeroUsize),
+}
+
+impl From<Quorum> for WireQuorum {
+    fn from(quorum: Quorum) -> Self {
+        match quorum {
+            Quorum::One => WireQuorum::One,
+            Quorum::Majority => WireQuorum::Majority,
+            Quorum::All => WireQuorum::All,
+            Quorum::N(n) => WireQuorum::N(n),
+        }
+    }
+}
+
+impl From<WireQuorum> for Quorum {
+    fn from(quorum: WireQuorum) -> Self {
+        match quorum {
+            WireQuorum::One => Quorum::One,
+            WireQuorum::Majority => Quorum::Majority,
+            WireQuorum::All => Quorum::All,
+            WireQuorum::N(n) => Quorum::N(n),
+        }
+    }
+}
+
+pub(crate) fn peers_to_wire<'a>(peers: impl IntoIterator<Item = &'a PeerId>) -> Vec<Vec<u8>> {
+    peers.into_iter().map(|p| p.to_bytes()).collect()
+}
+
+pub(crate) fn peers_from_wire<T>(peers: Vec<Vec<u8>>) -> Result<T, Error>
+where
+    T: FromIterator<PeerId>,
+{
+    peers
+        .iter()
+        .map(|p| PeerId::from_bytes(p).map_err(|_| Error::InvalidMessage))
+        .collect()
+}
@@ -0,0 +1,620 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The server side of delegated routing.
+
+use crate::protocol::{peers_to_wire, Request, Response, WirePeerRecord};
+use crate::Error;
+use instant::Instant;
+use libp2p_core::{Endpoint, Multiaddr};
+use libp2p_identity::PeerId;
+use libp2p_kad as kad;
+use libp2p_kad::store::AsyncRecordStore;
+use libp2p_request_response::{self as request_response, ProtocolSupport, ResponseChannel};
+use libp2p_swarm::{
+    ConnectionDenied, ConnectionHandlerSelect, ConnectionId, FromSwarm, NetworkBehaviour, THandler,
+    THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::iter;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Decides whether a request of a client is served.
+pub trait ClientAuthorizer: Send + Sync + 'static {
+    /// Returns whether the given request of the client is served.
+    fn authorize(&self, client: &PeerId, request: ClientRequest) -> bool;
+}
+
+impl<F> ClientAuthorizer for F
+where
+    F: Fn(&PeerId, ClientRequest) -> bool + Send + Sync + 'static,
+{
+    fn authorize(&self, client: &PeerId, request: ClientRequest) -> bool {
+        self(client, request)
+    }
+}
+
+/// A request of a client subject to a [`ClientAuthorizer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientRequest {
+    /// A request to look up a record.
+    GetRecord,
+    /// A request to look up the providers of a key.
+    GetProviders,
+    /// A request to look up the closest peers to a key.
+    GetClosestPeers,
+    /// A request to put a record into the DHT.
+    PutRecord,
+}
+
+impl From<&Request> for ClientRequest {
+    fn from(request: &Request) -> Self {
+        match request {
+            Request::GetRecord { .. } => ClientRequest::GetRecord,
+            Request::GetProviders { .. } => ClientRequest::GetProviders,
+            Request::GetClosestPeers { .. } => ClientRequest::GetClosestPeers,
+            Request::PutRecord { .. } => ClientRequest::PutRecord,
+        }
+    }
+}
+
+/// Serves all requests of the given clients only.
+struct Allowlist(HashSet<PeerId>);
+
+impl ClientAuthorizer for Allowlist {
+    fn authorize(&self, client: &PeerId, _: ClientRequest) -> bool {
+        self.0.contains(client)
+    }
+}
+
+/// The [`ClientAuthorizer`] set in the [`Config`], if any.
+#[derive(Clone, Default)]
+struct Authorizer {
+    authorizer: Option<Arc<dyn ClientAuthorizer>>,
+}
+
+impl Authorizer {
+    /// Returns whether the request is served. Without a [`ClientAuthorizer`], all requests
+    /// but [`ClientRequest::PutRecord`] are served.
+    fn authorize(&self, client: &PeerId, request: ClientRequest) -> bool {
+        match &self.authorizer {
+            Some(authorizer) => authorizer.authorize(client, request),
+            None => request != ClientRequest::PutRecord,
+        }
+    }
+}
+
+impl fmt::Debug for Authorizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authorizer")
+            .field("enabled", &self.authorizer.is_some())
+            .finish()
+    }
+}
+
+/// The configuration of a [`Behaviour`].
+#[derive(Debug, Clone)]
+pub struct Config {
+    request_timeout: Duration,
+    max_pending_queries: usize,
+    authorizer: Authorizer,
+    max_record_ttl: Duration,
+}
+
+impl Config {
+    /// Sets the time a client may wait for the response to a request.
+    ///
+    /// Requests whose queries take longer are dropped. Defaults to 2 minutes.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of queries run on behalf of clients at the same time.
+    ///
+    /// Further requests are refused with [`Error::Refused`]. Defaults to 256.
+    pub fn with_max_pending_queries(mut self, max: usize) -> Self {
+        self.max_pending_queries = max;
+        self
+    }
+
+    /// Sets the [`ClientAuthorizer`] deciding whether a request of a client is served.
+    /// Requests that are not are refused with [`Error::Refused`]. An authorizer or
+    /// allowlist set before is replaced.
+    ///
+    /// By default, the requests of all clients are served except for
+    /// [`ClientRequest::PutRecord`], as the server publishes the records of clients
+    /// under its own peer ID.
+    pub fn with_client_authorizer(mut self, authorizer: impl ClientAuthorizer) -> Self {
+        self.authorizer = Authorizer {
+            authorizer: Some(Arc::new(authorizer)),
+        };
+        self
+    }
+
+    /// Serves all requests of the given clients only, see [`Config::with_client_authorizer`].
+    pub fn with_allowed_clients(self, clients: impl IntoIterator<Item = PeerId>) -> Self {
+        self.with_client_authorizer(Allowlist(clients.into_iter().collect()))
+    }
+
+    /// Sets the maximum time-to-live of the records put by clients.
+    ///
+    /// The records are stored and replicated until they expire, but they are not
+    /// re-published by the server, thus clients have to put them again to keep them in
+    /// the DHT. Defaults to 1 hour.
+    pub fn with_max_record_ttl(mut self, ttl: Duration) -> Self {
+        self.max_record_ttl = ttl;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(120),
+            max_pending_queries: 256,
+            authorizer: Authorizer::default(),
+            max_record_ttl: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+#[derive(NetworkBehaviour)]
+#[behaviour(prelude = "libp2p_swarm::derive_prelude", to_swarm = "InnerEvent")]
+struct Inner<TStore>
+where
+    TStore: AsyncRecordStore + Send + 'static,
+{
+    proxy: request_response::cbor::Behaviour<Request, Response>,
+    kad: kad::Behaviour<TStore>,
+}
+
+enum InnerEvent {
+    Proxy(request_response::Event<Request, Response>),
+    Kad(kad::Event),
+}
+
+impl From<request_response::Event<Request, Response>> for InnerEvent {
+    fn from(event: request_response::Event<Request, Response>) -> Self {
+        InnerEvent::Proxy(event)
+    }
+}
+
+impl From<kad::Event> for InnerEvent {
+    fn from(event: kad::Event) -> Self {
+        InnerEvent::Kad(event)
+    }
+}
+
+/// A query run on behalf of a client.
+struct PendingQuery {
+    client: PeerId,
+    channel: ResponseChannel<Response>,
+    state: QueryState,
+}
+
+/// The results of a query collected so far.
+enum QueryState {
+    GetRecord(Vec<kad::PeerRecord>),
+    GetProviders(HashSet<PeerId>),
+    GetClosestPeers,
+    PutRecord,
+}
+
+impl QueryState {
+    /// The response to send if the query does not succeed.
+    fn error_response(&self, error: Error) -> Response {
+        match self {
+            QueryState::GetRecord(_) => Response::GetRecord(Err(error)),
+            QueryState::GetProviders(_) => Response::GetProviders(Err(error)),
+            QueryState::GetClosestPeers => Response::GetClosestPeers(Err(error)),
+            QueryState::PutRecord => Response::PutRecord(Err(error)),
+        }
+    }
+}
+
+/// A [`NetworkBehaviour`] that runs DHT queries on behalf of clients.
+///
+/// The wrapped [`kad::Behaviour`] takes part in the DHT as usual and can be accessed via
+/// [`Behaviour::kad_mut`]. Its events that do not belong to queries of clients are reported
+/// via [`Event::Kademlia`].
+pub struct Behaviour<TStore>
+where
+    TStore: AsyncRecordStore + Send + 'static,
+{
+    inner: Inner<TStore>,
+
+    max_pending_queries: usize,
+    authorizer: Authorizer,
+    max_record_ttl: Duration,
+    pending_queries: HashMap<kad::QueryId, PendingQuery>,
+}
+
+impl<TStore> Behaviour<TStore>
+where
+    TStore: AsyncRecordStore + Send + 'static,
+{
+    /// Creates a new delegated routing server that runs the queries of clients on the
+    /// given Kademlia behaviour.
+    pub fn new(kad: kad::Behaviour<TStore>, config: Config) -> Self {
+        Self {
+            inner: Inner {
+                proxy: request_response::cbor::Behaviour::new(
+                    iter::once((crate::PROTOCOL_IDENT, ProtocolSupport::Inbound)),
+                    request_response::Config::default()
+                        .with_request_timeout(config.request_timeout),
+                ),
+                kad,
+            },
+            max_pending_queries: config.max_pending_queries,
+            authorizer: config.authorizer,
+            max_record_ttl: config.max_record_ttl,
+            pending_queries: Default::default(),
+        }
+    }
+
+    /// Returns a reference to the wrapped Kademlia behaviour.
+    pub fn kad(&self) -> &kad::Behaviour<TStore> {
+        &self.inner.kad
+    }
+
+    /// Returns a mutable reference to the wrapped Kademlia behaviour.
+    pub fn kad_mut(&mut self) -> &mut kad::Behaviour<TStore> {
+        &mut self.inner.kad
+    }
+
+    /// Returns the number of queries currently run on behalf of clients.
+    pub fn num_pending_queries(&self) -> usize {
+        self.pending_queries.len()
+    }
+
+    fn on_request(
+        &mut self,
+        client: PeerId,
+        request: Request,
+        channel: ResponseChannel<Response>,
+    ) -> Option<Event> {
+        if !self
+            .authorizer
+            .authorize(&client, ClientRequest::from(&request))
+        {
+            tracing::debug!(%client, "Refusing request of unauthorized client");
+            let _ = self
+                .inner
+                .proxy
+                .send_response(channel, request.error_response(Error::Refused));
+            return Some(Event::RequestRefused { client });
+        }
+
+        if self.pending_queries.len() >= self.max_pending_queries {
+            tracing::debug!(%client, "Refusing request, too many pending queries");
+            let _ = self
+                .inner
+                .proxy
+                .send_response(channel, request.error_response(Error::Refused));
+            return Some(Event::RequestRefused { client });
+        }
+
+        let kad = &mut self.inner.kad;
+        let (id, state) = match request {
+            Request::GetRecord { key } => (
+                kad.get_record(kad::RecordKey::from(key)),
+                QueryState::GetRecord(Vec::new()),
+            ),
+            Request::GetProviders { key } => (
+                kad.get_providers(kad::RecordKey::from(key)),
+                QueryState::GetProviders(HashSet::new()),
+            ),
+            Request::GetClosestPeers { key } => {
+                (kad.get_closest_peers(key), QueryState::GetClosestPeers)
+            }
+            Request::PutRecord { record, quorum } => {
+                let max_expires = Instant::now() + self.max_record_ttl;
+                let result = kad::Record::try_from(record)
+                    .map_err(|_| Error::InvalidMessage)
+                    .and_then(|mut record| {
+                        record.expires = Some(
+                            record
+                                .expires
+                                .map_or(max_expires, |expires| expires.min(max_expires)),
+                        );
+                        kad.put_record(record, quorum.into())
+                            .map_err(|_| Error::Refused)
+                    });
+                match result {
+                    Ok(id) => (id, QueryState::PutRecord),
+                    Err(error) => {
+                        tracing::debug!(%client, "Failed to put record of client: {error}");
+                        let _ = self
+                            .inner
+                            .proxy
+                            .send_response(channel, Response::PutRecord(Err(error)));
+                        return Some(Event::RequestRefused { client });
+                    }
+                }
+            }
+        };
+
+        self.pending_queries.insert(
+            id,
+            PendingQuery {
+                client,
+                channel,
+                state,
+            },
+        );
+
+        None
+    }
+
+    fn on_kad_event(&mut self, event: kad::Event) -> Option<Event> {
+        let (id, result, last) = match event {
+            kad::Event::OutboundQueryProgressed {
+                id, result, step, ..
+            } if self.pending_queries.contains_key(&id) => (id, result, step.last),
+            other => return Some(Event::Kademlia(other)),
+        };
+        let pending = self
+            .pending_queries
+            .get_mut(&id)
+            .expect("query to be pending");
+
+        let response = match (&mut pending.state, result) {
+            (QueryState::GetRecord(records), kad::QueryResult::GetRecord(result)) => {
+                match result {
+                    Ok(kad::GetRecordOk::FoundRecord(record)) => {
+                        records.push(record);
+                        if !last {
+                            return None;
+                        }
+                    }
+                    Ok(
+                        kad::GetRecordOk::FinishedWithNoAdditionalRecord { .. }
+                        | kad::GetRecordOk::AgreementReached { .. },
+                    ) => {}
+                    Err(error) if records.is_empty() => {
+                        let error = match error {
+                            kad::GetRecordError::NotFound { .. } => Error::NotFound,
+                            kad::GetRecordError::QuorumFailed { .. } => Error::QuorumFailed,
+                            kad::GetRecordError::Timeout { .. } => Error::Timeout,
+                        };
+                        return self.respond(id, Response::GetRecord(Err(error)));
+                    }
+                    Err(_) => {}
+                }
+                Response::GetRecord(Ok(records.iter().map(WirePeerRecord::from).collect()))
+            }
+            (QueryState::GetProviders(providers), kad::QueryResult::GetProviders(result)) => {
+                match result {
+                    Ok(kad::GetProvidersOk::FoundProviders { providers: new, .. }) => {
+                        providers.extend(new);
+                        if !last {
+                            return None;
+                        }
+                    }
+                    Ok(kad::GetProvidersOk::FinishedWithNoAdditionalRecord { .. }) => {}
+                    Err(error) if providers.is_empty() => {
+                        let error = match error {
+                            kad::GetProvidersError::Timeout { .. } => Error::Timeout,
+                            kad::GetProvidersError::QuorumFailed { .. } => Error::QuorumFailed,
+                        };
+                        return self.respond(id, Response::GetProviders(Err(error)));
+                    }
+                    Err(_) => {}
+                }
+                Response::GetProviders(Ok(peers_to_wire(providers.iter())))
+            }
+            (QueryState::GetClosestPeers, kad::QueryResult::GetClosestPeers(result)) => {
+                Response::GetClosestPeers(match result {
+                    Ok(ok) => Ok(peers_to_wire(&ok.peers)),
+                    Err(kad::GetClosestPeersError::Timeout { .. }) => Err(Error::Timeout),
+                })
+            }
+            (QueryState::PutRecord, kad::QueryResult::PutRecord(result)) => {
+                Response::PutRecord(match result {
+                    Ok(ok) => Ok(peers_to_wire(&ok.success)),
+                    Err(kad::PutRecordError::QuorumFailed { .. }) => Err(Error::QuorumFailed),
+                    Err(kad::PutRecordError::Timeout { .. }) => Err(Error::Timeout),
+                })
+            }
+            (state, result) => {
+                tracing::warn!(
+                    query=?id,
+                    "Query of client produced an unexpected result: {result:?}"
+                );
+                let response = state.error_response(Error::Refused);
+                if let Some(mut query) = self.inner.kad.query_mut(&id) {
+                    query.finish();
+                }
+                return self.respond(id, response);
+            }
+        };
+
+        self.respond(id, response)
+    }
+
+    fn on_proxy_event(
+        &mut self,
+        event: request_response::Event<Request, Response>,
+    ) -> Option<Event> {
+        match event {
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Request {
+                        request, channel, ..
+                    },
+            } => self.on_request(peer, request, channel),
+            request_response::Event::InboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                tracing::debug!(
+                    %peer,
+                    request=%request_id,
+                    "Inbound request with client failed: {error}"
+                );
+                None
+            }
+            request_response::Event::Message {
+                peer,
+                message: request_response::Message::Response { .. },
+            } => {
+                tracing::debug!(%peer, "Ignoring unexpected response of client");
+                None
+            }
+            request_response::Event::OutboundFailure { .. }
+            | request_response::Event::ResponseSent { .. }
+            | request_response::Event::InboundRequestTiming { .. }
+            | request_response::Event::OutboundRequestTiming { .. } => None,
+        }
+    }
+
+    fn respond(&mut self, id: kad::QueryId, response: Response) -> Option<Event> {
+        let PendingQuery {
+            client, channel, ..
+        } = self.pending_queries.remove(&id)?;
+
+        if self.inner.proxy.send_response(channel, response).is_err() {
+            tracing::debug!(%client, query=?id, "Client is gone, dropping response");
+        }
+
+        Some(Event::QueryServed { client, id })
+    }
+}
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum Event {
+    /// A query was run on behalf of a client and its result sent to the client.
+    QueryServed { client: PeerId, id: kad::QueryId },
+    /// A request of a client was refused, e.g. because of too many pending queries.
+    RequestRefused { client: PeerId },
+    /// An event of the wrapped Kademlia behaviour.
+    Kademlia(kad::Event),
+}
+
+impl<TStore> NetworkBehaviour for Behaviour<TStore>
+where
+    TStore: AsyncRecordStore + Send + 'static,
+{
+    type ConnectionHandler = ConnectionHandlerSelect<
+        THandler<request_response::cbor::Behaviour<Request, Response>>,
+        THandler<kad::Behaviour<TStore>>,
+    >;
+
+    type ToSwarm = Event;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.inner
+            .handle_pending_inbound_connection(connection_id, local_addr, remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner.handle_established_inbound_connection(
+            connection_id,
+            peer,
+            local_addr,
+            remote_addr,
+        )
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        self.inner.handle_pending_outbound_connection(
+            connection_id,
+            maybe_peer,
+            addresses,
+            effective_role,
+        )
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner
+            .handle_established_outbound_connection(connection_id, peer, addr, role_override)
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        self.inner
+            .on_connection_handler_event(peer_id, connection_id, event);
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        self.inner.on_swarm_event(event);
+    }
+
+    #[tracing::instrument(level = "trace", name = "NetworkBehaviour::poll", skip(self, cx))]
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        loop {
+            let event = match self.inner.poll(cx) {
+                Poll::Ready(ToSwarm::GenerateEvent(InnerEvent::Kad(event))) => {
+                    self.on_kad_event(event)
+                }
+                Poll::Ready(ToSwarm::GenerateEvent(InnerEvent::Proxy(event))) => {
+                    self.on_proxy_event(event)
+                }
+                Poll::Ready(other) => {
+                    return Poll::Ready(other.map_out(|_| {
+                        unreachable!("`GenerateEvent` is handled by the arms above")
+                    }));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if let Some(event) = event {
+                return Poll::Ready(ToSwarm::GenerateEvent(event));
+            }
+        }
+    }
+}
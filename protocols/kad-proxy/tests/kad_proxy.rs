@@ -0,0 +1,258 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::FutureExt;
+use instant::Instant;
+use libp2p_identity::PeerId;
+use libp2p_kad::store::{MemoryStore, RecordStore};
+use libp2p_kad::{self as kad, ProviderRecord, Quorum, Record, RecordKey};
+use libp2p_kad_proxy::client::{self, QueryResult};
+use libp2p_kad_proxy::{server, Error};
+use libp2p_swarm::Swarm;
+use libp2p_swarm_test::SwarmExt;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+#[async_std::test]
+async fn get_record_returns_record_stored_at_server() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let (mut client, mut server) = new_client_and_server(server::Config::default()).await;
+
+    let record = Record::new(RecordKey::new(&"key"), b"value".to_vec());
+    server
+        .behaviour_mut()
+        .kad_mut()
+        .store_mut()
+        .put(record.clone())
+        .unwrap();
+    async_std::task::spawn(server.loop_on_next());
+
+    let id = client
+        .behaviour_mut()
+        .get_record(record.key.clone())
+        .unwrap();
+
+    match client.next_behaviour_event().await {
+        client::Event::QueryCompleted {
+            id: completed,
+            result: QueryResult::GetRecord(Ok(records)),
+            ..
+        } => {
+            assert_eq!(completed, id);
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].peer, None);
+            assert_eq!(records[0].record.key, record.key);
+            assert_eq!(records[0].record.value, record.value);
+        }
+        e => panic!("Unexpected event: {e:?}"),
+    }
+}
+
+#[async_std::test]
+async fn get_providers_returns_providers_known_to_server() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let (mut client, mut server) = new_client_and_server(server::Config::default()).await;
+
+    let key = RecordKey::new(&"key");
+    let provider = PeerId::random();
+    server
+        .behaviour_mut()
+        .kad_mut()
+        .store_mut()
+        .add_provider(ProviderRecord::new(key.clone(), provider, Vec::new()))
+        .unwrap();
+    async_std::task::spawn(server.loop_on_next());
+
+    client.behaviour_mut().get_providers(key).unwrap();
+
+    match client.next_behaviour_event().await {
+        client::Event::QueryCompleted {
+            result: QueryResult::GetProviders(Ok(providers)),
+            ..
+        } => {
+            assert_eq!(providers.into_iter().collect::<Vec<_>>(), vec![provider]);
+        }
+        e => panic!("Unexpected event: {e:?}"),
+    }
+}
+
+#[async_std::test]
+async fn put_record_stores_record_at_server() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let ttl = Duration::from_secs(60);
+    let (mut client, server) = new_client_and_server(
+        server::Config::default()
+            .with_client_authorizer(|_: &PeerId, _| true)
+            .with_max_record_ttl(ttl),
+    )
+    .await;
+    async_std::task::spawn(server.loop_on_next());
+
+    let record = Record::new(RecordKey::new(&"key"), b"value".to_vec());
+    client
+        .behaviour_mut()
+        .put_record(record.clone(), Quorum::One)
+        .unwrap();
+
+    // The server knows no other peers to replicate the record to.
+    match client.next_behaviour_event().await {
+        client::Event::QueryCompleted {
+            result: QueryResult::PutRecord(Err(Error::QuorumFailed)),
+            ..
+        } => {}
+        e => panic!("Unexpected event: {e:?}"),
+    }
+
+    client.behaviour_mut().get_record(record.key).unwrap();
+
+    match client.next_behaviour_event().await {
+        client::Event::QueryCompleted {
+            result: QueryResult::GetRecord(Ok(records)),
+            ..
+        } => {
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].record.value, record.value);
+            assert!(records[0]
+                .record
+                .expires
+                .is_some_and(|expires| expires <= Instant::now() + ttl));
+        }
+        e => panic!("Unexpected event: {e:?}"),
+    }
+}
+
+#[async_std::test]
+async fn put_record_of_unauthorized_client_is_refused() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let (mut client, server) =
+        new_client_and_server(server::Config::default().with_allowed_clients([PeerId::random()]))
+            .await;
+    async_std::task::spawn(server.loop_on_next());
+
+    client
+        .behaviour_mut()
+        .put_record(
+            Record::new(RecordKey::new(&"key"), b"value".to_vec()),
+            Quorum::One,
+        )
+        .unwrap();
+
+    match client.next_behaviour_event().await {
+        client::Event::QueryCompleted {
+            result: QueryResult::PutRecord(Err(Error::Refused)),
+            ..
+        } => {}
+        e => panic!("Unexpected event: {e:?}"),
+    }
+}
+
+#[async_std::test]
+async fn put_record_is_refused_by_default() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let (mut client, mut server) = new_client_and_server(server::Config::default()).await;
+
+    let key = RecordKey::new(&"key");
+    client
+        .behaviour_mut()
+        .put_record(Record::new(key.clone(), b"value".to_vec()), Quorum::One)
+        .unwrap();
+
+    loop {
+        futures::select! {
+            event = client.next_behaviour_event().fuse() => match event {
+                client::Event::QueryCompleted {
+                    result: QueryResult::PutRecord(Err(Error::Refused)),
+                    ..
+                } => break,
+                e => panic!("Unexpected event: {e:?}"),
+            },
+            _ = server.next_behaviour_event().fuse() => {}
+        }
+    }
+    assert!(server
+        .behaviour_mut()
+        .kad_mut()
+        .store_mut()
+        .get(&key)
+        .is_none());
+}
+
+#[async_std::test]
+async fn requests_beyond_max_pending_queries_are_refused() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let (mut client, server) =
+        new_client_and_server(server::Config::default().with_max_pending_queries(0)).await;
+    async_std::task::spawn(server.loop_on_next());
+
+    client
+        .behaviour_mut()
+        .get_record(RecordKey::new(&"key"))
+        .unwrap();
+
+    match client.next_behaviour_event().await {
+        client::Event::QueryCompleted {
+            result: QueryResult::GetRecord(Err(Error::Refused)),
+            ..
+        } => {}
+        e => panic!("Unexpected event: {e:?}"),
+    }
+}
+
+#[test]
+fn queries_without_servers_fail() {
+    let mut client = client::Behaviour::new(client::Config::default());
+
+    assert!(client.get_record(RecordKey::new(&"key")).is_err());
+}
+
+async fn new_client_and_server(
+    config: server::Config,
+) -> (
+    Swarm<client::Behaviour>,
+    Swarm<server::Behaviour<MemoryStore>>,
+) {
+    let mut server = Swarm::new_ephemeral(|identity| {
+        let peer_id = identity.public().to_peer_id();
+        server::Behaviour::new(
+            kad::Behaviour::new(peer_id, MemoryStore::new(peer_id)),
+            config,
+        )
+    });
+    server.listen().with_memory_addr_external().await;
+
+    let mut client = Swarm::new_ephemeral(|_| client::Behaviour::new(client::Config::default()));
+    client.connect(&mut server).await;
+    let server_id = *server.local_peer_id();
+    client.behaviour_mut().add_server(server_id);
+
+    (client, server)
+}
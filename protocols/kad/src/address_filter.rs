@@ -0,0 +1,95 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Filtering of the addresses learned by the local node.
+//!
+//! Applications set an [`AddressFilter`] via
+//! [`Config::set_address_filter`](crate::Config::set_address_filter), e.g. to keep
+//! private, relayed or otherwise undesirable addresses out of the routing table.
+
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use std::fmt;
+use std::sync::Arc;
+
+/// Decides whether an address of a peer learned by the local node is used.
+pub trait AddressFilter: Send + Sync + 'static {
+    /// Returns whether the given address of the peer is accepted.
+    ///
+    /// Rejected addresses do not enter the routing table. Rejected addresses reported
+    /// in a response to a query are also not dialed by the query.
+    fn accepts(&self, peer: &PeerId, address: &Multiaddr, source: AddressSource) -> bool;
+}
+
+impl<F> AddressFilter for F
+where
+    F: Fn(&PeerId, &Multiaddr, AddressSource) -> bool + Send + Sync + 'static,
+{
+    fn accepts(&self, peer: &PeerId, address: &Multiaddr, source: AddressSource) -> bool {
+        self(peer, address, source)
+    }
+}
+
+/// How an address subject to an [`AddressFilter`] was learned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSource {
+    /// The address was added via [`Behaviour::add_address`](crate::Behaviour::add_address),
+    /// e.g. as reported by `libp2p-identify`, including addresses of bootstrap peers and
+    /// addresses restored into the routing table.
+    Explicit,
+    /// The local node dialed the peer at the address.
+    Connection,
+    /// The address was reported by the given peer in a response to a query.
+    QueryResponse { source: PeerId },
+}
+
+/// The [`AddressFilter`] set in the [`Config`](crate::Config), if any.
+#[derive(Clone, Default)]
+pub(crate) struct AddressFiltering {
+    filter: Option<Arc<dyn AddressFilter>>,
+}
+
+impl AddressFiltering {
+    pub(crate) fn new(filter: impl AddressFilter) -> Self {
+        Self {
+            filter: Some(Arc::new(filter)),
+        }
+    }
+
+    /// Returns whether the address is accepted, i.e. `true` if there is no [`AddressFilter`].
+    pub(crate) fn accepts(
+        &self,
+        peer: &PeerId,
+        address: &Multiaddr,
+        source: AddressSource,
+    ) -> bool {
+        self.filter
+            .as_ref()
+            .map_or(true, |filter| filter.accepts(peer, address, source))
+    }
+}
+
+impl fmt::Debug for AddressFiltering {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AddressFiltering")
+            .field("enabled", &self.filter.is_some())
+            .finish()
+    }
+}
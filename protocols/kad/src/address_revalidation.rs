@@ -0,0 +1,178 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Background revalidation of the addresses of routing table peers.
+//!
+//! Peers that have not been needed for a query may sit in the routing table with
+//! addresses that no longer work, and would be handed out to other nodes in
+//! responses. Disconnected peers are therefore dialed periodically, one at a time,
+//! and evicted if they consistently fail to be reached.
+
+use futures::FutureExt;
+use futures_timer::Delay;
+use instant::Instant;
+use libp2p_identity::PeerId;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU32;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Schedules the revalidation dials and tracks their outcomes.
+pub(crate) struct AddressRevalidation {
+    /// The interval between two revalidation dials, if enabled.
+    interval: Option<Duration>,
+    /// The number of consecutive failed revalidations after which a peer is evicted.
+    max_failures: NonZeroU32,
+    /// The delay until the next revalidation dial.
+    delay: Option<Delay>,
+    /// When the peers were last revalidated.
+    revalidated_at: HashMap<PeerId, Instant>,
+    /// The consecutive failed revalidations per peer.
+    failures: HashMap<PeerId, u32>,
+    /// Peers whose revalidation dial is in progress.
+    pending: HashSet<PeerId>,
+}
+
+/// The outcome of a failed revalidation dial.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Failure {
+    /// The peer failed before, but is given another chance.
+    Retry,
+    /// The peer consistently failed and is to be evicted.
+    Evict,
+}
+
+impl AddressRevalidation {
+    pub(crate) fn new(interval: Option<Duration>, max_failures: NonZeroU32) -> Self {
+        Self {
+            interval,
+            max_failures,
+            delay: interval.map(Delay::new),
+            revalidated_at: HashMap::new(),
+            failures: HashMap::new(),
+            pending: HashSet::new(),
+        }
+    }
+
+    /// Resolves once the next peer is to be revalidated, see [`AddressRevalidation::next_peer`].
+    pub(crate) fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        match self.delay.as_mut() {
+            Some(delay) => delay.poll_unpin(cx),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Picks the peer revalidated the longest time ago among the given disconnected
+    /// routing table peers, considering it pending from now on, and schedules the
+    /// next revalidation.
+    pub(crate) fn next_peer(&mut self, disconnected: Vec<PeerId>) -> Option<PeerId> {
+        let interval = self.interval?;
+        if let Some(delay) = self.delay.as_mut() {
+            delay.reset(interval);
+        }
+
+        // Forget about peers that left the routing table or got connected.
+        let candidates = disconnected.into_iter().collect::<HashSet<_>>();
+        self.revalidated_at.retain(|p, _| candidates.contains(p));
+        self.failures.retain(|p, _| candidates.contains(p));
+
+        let peer = candidates
+            .into_iter()
+            .filter(|p| !self.pending.contains(p))
+            .min_by_key(|p| self.revalidated_at.get(p).copied())?;
+        self.revalidated_at.insert(peer, Instant::now());
+        self.pending.insert(peer);
+        Some(peer)
+    }
+
+    /// Records that a connection to the peer has been established.
+    pub(crate) fn on_success(&mut self, peer: &PeerId) {
+        self.pending.remove(peer);
+        self.failures.remove(peer);
+    }
+
+    /// Records that the revalidation dial was not attempted, e.g. as the peer is
+    /// being dialed already.
+    pub(crate) fn on_skipped(&mut self, peer: &PeerId) {
+        self.pending.remove(peer);
+    }
+
+    /// Records that the peer could not be reached, returning `None` if the dial
+    /// was not a revalidation.
+    pub(crate) fn on_failure(&mut self, peer: &PeerId) -> Option<Failure> {
+        if !self.pending.remove(peer) {
+            return None;
+        }
+        let failures = self.failures.entry(*peer).or_default();
+        *failures += 1;
+        if *failures >= self.max_failures.get() {
+            self.failures.remove(peer);
+            self.revalidated_at.remove(peer);
+            return Some(Failure::Evict);
+        }
+        Some(Failure::Retry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_least_recently_revalidated_peer() {
+        let mut revalidation =
+            AddressRevalidation::new(Some(Duration::from_secs(1)), NonZeroU32::new(2).unwrap());
+        let peers = [PeerId::random(), PeerId::random()];
+
+        let first = revalidation.next_peer(peers.to_vec()).unwrap();
+        let second = revalidation.next_peer(peers.to_vec()).unwrap();
+        assert_ne!(first, second);
+        // Both revalidations are still pending.
+        assert_eq!(revalidation.next_peer(peers.to_vec()), None);
+
+        revalidation.on_success(&first);
+        assert_eq!(revalidation.next_peer(peers.to_vec()), Some(first));
+    }
+
+    #[test]
+    fn evicts_after_consecutive_failures() {
+        let mut revalidation =
+            AddressRevalidation::new(Some(Duration::from_secs(1)), NonZeroU32::new(2).unwrap());
+        let peer = PeerId::random();
+
+        assert_eq!(revalidation.on_failure(&peer), None);
+
+        revalidation.next_peer(vec![peer]);
+        assert_eq!(revalidation.on_failure(&peer), Some(Failure::Retry));
+        revalidation.next_peer(vec![peer]);
+        revalidation.on_success(&peer);
+
+        revalidation.next_peer(vec![peer]);
+        assert_eq!(revalidation.on_failure(&peer), Some(Failure::Retry));
+        revalidation.next_peer(vec![peer]);
+        assert_eq!(revalidation.on_failure(&peer), Some(Failure::Evict));
+    }
+
+    #[test]
+    fn disabled_revalidation_picks_no_peers() {
+        let mut revalidation = AddressRevalidation::new(None, NonZeroU32::new(2).unwrap());
+        assert_eq!(revalidation.next_peer(vec![PeerId::random()]), None);
+    }
+}
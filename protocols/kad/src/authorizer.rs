@@ -0,0 +1,105 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Authorization of inbound requests in server mode.
+//!
+//! Applications set a [`RequestAuthorizer`] via
+//! [`Config::set_request_authorizer`](crate::Config::set_request_authorizer) or an
+//! allowlist of peers via [`Config::set_request_allowlist`](crate::Config::set_request_allowlist),
+//! e.g. to restrict a private DHT to its members where the protocol name alone is not
+//! sufficient isolation.
+
+use libp2p_identity::PeerId;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+/// Decides whether an inbound request of a peer is answered.
+///
+/// Only requests for records and providers are subject to authorization. `FIND_NODE`
+/// requests are always answered, as they are needed for the routing of all peers.
+pub trait RequestAuthorizer: Send + Sync + 'static {
+    /// Returns whether the given request of the peer is answered.
+    fn authorize(&self, peer: &PeerId, request: RestrictedRequest) -> bool;
+}
+
+impl<F> RequestAuthorizer for F
+where
+    F: Fn(&PeerId, RestrictedRequest) -> bool + Send + Sync + 'static,
+{
+    fn authorize(&self, peer: &PeerId, request: RestrictedRequest) -> bool {
+        self(peer, request)
+    }
+}
+
+/// An inbound request subject to a [`RequestAuthorizer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestrictedRequest {
+    /// A `GET_VALUE` request.
+    GetRecord,
+    /// A `GET_PROVIDERS` request.
+    GetProviders,
+    /// A `PUT_VALUE` request.
+    PutRecord,
+    /// An `ADD_PROVIDER` request.
+    AddProvider,
+}
+
+/// Answers the requests of the given peers only.
+struct Allowlist(HashSet<PeerId>);
+
+impl RequestAuthorizer for Allowlist {
+    fn authorize(&self, peer: &PeerId, _: RestrictedRequest) -> bool {
+        self.0.contains(peer)
+    }
+}
+
+/// The [`RequestAuthorizer`] set in the [`Config`](crate::Config), if any.
+#[derive(Clone, Default)]
+pub(crate) struct Authorizer {
+    authorizer: Option<Arc<dyn RequestAuthorizer>>,
+}
+
+impl Authorizer {
+    pub(crate) fn new(authorizer: impl RequestAuthorizer) -> Self {
+        Self {
+            authorizer: Some(Arc::new(authorizer)),
+        }
+    }
+
+    pub(crate) fn allowlist(peers: impl IntoIterator<Item = PeerId>) -> Self {
+        Self::new(Allowlist(peers.into_iter().collect()))
+    }
+
+    /// Returns whether the request is answered, i.e. `true` if there is no [`RequestAuthorizer`].
+    pub(crate) fn authorize(&self, peer: &PeerId, request: RestrictedRequest) -> bool {
+        self.authorizer
+            .as_ref()
+            .map_or(true, |authorizer| authorizer.authorize(peer, request))
+    }
+}
+
+impl fmt::Debug for Authorizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authorizer")
+            .field("enabled", &self.authorizer.is_some())
+            .finish()
+    }
+}
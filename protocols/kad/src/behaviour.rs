@@ -22,22 +22,52 @@
 
 mod test;
 
+use crate::address_filter::{AddressFilter, AddressFiltering, AddressSource};
+use crate::address_revalidation::{self, AddressRevalidation};
 use crate::addresses::Addresses;
+use crate::authorizer::{Authorizer, RequestAuthorizer, RestrictedRequest};
 use crate::bootstrap;
-use crate::handler::{Handler, HandlerEvent, HandlerIn, RequestId};
-use crate::kbucket::{self, Distance, KBucketsTable, NodeStatus};
+use crate::bootstrap_peers::{self, BootstrapPeers};
+use crate::bucket_refresh::BucketRefreshes;
+use crate::conflict::{ConflictResolver, ConflictResolvers};
+use crate::handler::{Handler, HandlerEvent, HandlerIn, HandlerQueryErr, RequestId};
+use crate::inbound_observer::{
+    InboundObserver, InboundRequestInfo, InboundRequestObserver, InboundRequestType,
+};
+use crate::ip_diversity::IpPrefix;
+use crate::kbucket::{
+    self, BucketSizes, Distance, KBucketsTable, KeyHasher, KeyHashing, NodeStatus,
+};
+use crate::namespace::{Namespace, Namespaces};
+use crate::peer_store::{PeerStore, PeerStoreSeeding};
 use crate::protocol::{ConnectionType, KadPeer, ProtocolConfig};
-use crate::query::{Query, QueryConfig, QueryId, QueryPool, QueryPoolState};
+use crate::provider_filter::{ProviderFilter, ProviderFiltering, ProvidersFor};
+use crate::provider_summary::{KeyspaceRange, ProviderSummary};
+use crate::query::{
+    AdaptiveParallelism, Query, QueryConfig, QueryId, QueryOpts, QueryPool, QueryPoolState,
+    QueryPriority, QueryRpc, RetryPolicy,
+};
+#[cfg(feature = "stream")]
+use crate::query_stream::{QueryStream, QueryStreams};
+use crate::rate_limiter::RateLimiter;
 use crate::record::{
     self,
-    store::{self, RecordStore},
+    store::{self, AsyncRecordStore, StoreFuture},
     ProviderRecord, Record,
 };
+use crate::routing_updates::{RoutingUpdates, RoutingUpdatesCoalescer};
+use crate::scorer::{Misbehaviour, PeerScorer, Scorer};
+use crate::signed_records::SignedPeerRecords;
+use crate::snapshot::{self, RoutingTableEntry, RoutingTableSnapshot};
+use crate::validator::RecordValidator;
 use crate::K_VALUE;
 use crate::{jobs::*, protocol};
 use fnv::{FnvHashMap, FnvHashSet};
+use futures::task::AtomicWaker;
+use futures::{channel::mpsc, task::noop_waker_ref, FutureExt};
+use futures_timer::Delay;
 use instant::Instant;
-use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr};
+use libp2p_core::{multiaddr::Protocol, ConnectedPoint, Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_swarm::behaviour::{
     AddressChange, ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm,
@@ -48,10 +78,14 @@ use libp2p_swarm::{
     ListenAddresses, NetworkBehaviour, NotifyHandler, StreamProtocol, THandler, THandlerInEvent,
     THandlerOutEvent, ToSwarm,
 };
+use rand::Rng;
 use smallvec::SmallVec;
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fmt;
-use std::num::NonZeroUsize;
+use std::io;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, Waker};
 use std::time::Duration;
 use std::vec;
@@ -69,6 +103,9 @@ pub struct Behaviour<TStore> {
     /// The k-bucket insertion strategy.
     kbucket_inserts: BucketInserts,
 
+    /// See [`Config::set_relayed_addresses`].
+    relayed_addresses: RelayedAddresses,
+
     /// Configuration of the wire protocol.
     protocol_config: ProtocolConfig,
 
@@ -83,20 +120,113 @@ pub struct Behaviour<TStore> {
     /// This is a superset of the connected peers currently in the routing table.
     connected_peers: FnvHashSet<PeerId>,
 
-    /// Periodic job for re-publication of provider records for keys
+    /// The protocol each connected peer negotiated.
+    peer_protocols: FnvHashMap<PeerId, StreamProtocol>,
+
+    /// See [`Config::set_routing_table_protocols`].
+    routing_table_protocols: Option<Vec<StreamProtocol>>,
+
+    /// The time, as a duration since the UNIX epoch, a disconnected peer in the
+    /// routing table was last seen connected.
+    last_seen: FnvHashMap<PeerId, Duration>,
+
+    /// Job for re-publication of provider records for keys
     /// provided by the local node.
-    add_provider_job: Option<AddProviderJob>,
+    add_provider_job: AddProviderJob,
 
     /// Periodic job for (re-)replication and (re-)publishing of
     /// regular (value-)records.
     put_record_job: Option<PutRecordJob>,
 
+    /// Job replicating stored records to peers joining the routing table,
+    /// see [`Config::set_replicate_on_join`].
+    replicate_on_join_job: Option<ReplicateOnJoinJob>,
+
+    /// The intervals of the `put_record_job`, see [`Behaviour::set_replication_interval`]
+    /// and [`Behaviour::set_publication_interval`].
+    record_replication_interval: Option<Duration>,
+    record_publication_interval: Option<Duration>,
+
+    /// Whether the background jobs are paused, see [`Behaviour::pause_background_jobs`].
+    background_jobs_paused: bool,
+
     /// The TTL of regular (value-)records.
     record_ttl: Option<Duration>,
 
     /// The TTL of provider records.
     provider_record_ttl: Option<Duration>,
 
+    /// The expirations of the provider records of the local node that
+    /// expire other than by the configured TTL, see [`Behaviour::start_providing_with_ttl`].
+    local_provider_expirations: HashMap<record::Key, Instant>,
+
+    /// Whether to answer requests for provider summaries.
+    provider_summaries: bool,
+
+    /// See [`Config::add_namespace`].
+    namespaces: Namespaces,
+
+    /// See [`Config::set_max_response_peers`].
+    max_response_peers: Option<NonZeroUsize>,
+
+    /// See [`Config::set_max_response_size`].
+    max_response_size: Option<usize>,
+
+    /// See [`Config::set_max_peers_per_ip_prefix`].
+    max_peers_per_ip_prefix: Option<NonZeroUsize>,
+
+    /// See [`Config::set_address_filter`].
+    address_filter: AddressFiltering,
+
+    /// See [`Config::set_peer_scorer`].
+    peer_scorer: Scorer,
+
+    /// See [`Config::set_request_authorizer`].
+    request_authorizer: Authorizer,
+
+    /// See [`Config::set_provider_filter`].
+    provider_filter: ProviderFiltering,
+
+    /// See [`Config::set_inbound_request_observer`].
+    inbound_observer: InboundObserver,
+
+    /// See [`Config::set_peer_store`].
+    peer_store: PeerStoreSeeding,
+
+    /// See [`Config::set_key_hasher`].
+    hasher: KeyHashing,
+
+    /// See [`Config::set_coalesced_routing_updates`].
+    routing_updates: RoutingUpdatesCoalescer,
+
+    /// See [`Config::set_inbound_write_rate_limit`].
+    inbound_write_limiter: Option<RateLimiter<PeerId>>,
+
+    /// See [`Config::set_global_inbound_write_rate_limit`].
+    global_inbound_write_limiter: Option<RateLimiter<()>>,
+
+    /// See [`Config::set_advertise_when_dialable`].
+    advertise_when_dialable: bool,
+
+    /// See [`Behaviour::add_signed_peer_record`] and [`Config::set_require_signed_peer_records`].
+    signed_records: SignedPeerRecords,
+
+    /// Queries advertising the local node or its records that wait for
+    /// the local node to become dialable, see [`Config::set_advertise_when_dialable`].
+    pending_advertisements: Vec<(QueryId, record::Key, QueryInner, QueryPriority)>,
+
+    /// See [`Config::set_query_retry_policy`].
+    query_retry_policy: Option<RetryPolicy>,
+
+    /// Queries that timed out and wait for the backoff to elapse before being retried.
+    pending_retries: Vec<(Delay, QueryId, QueryInner, QueryPriority)>,
+
+    /// Commands for queries sent via [`QueryHandle`]s.
+    query_commands: Arc<QueryCommands>,
+
+    /// See [`Config::set_max_user_queries`].
+    max_user_queries: Option<NonZeroUsize>,
+
     /// Queued events to return when the behaviour is being polled.
     queued_events: VecDeque<ToSwarm<Event, HandlerIn>>,
 
@@ -106,6 +236,9 @@ pub struct Behaviour<TStore> {
 
     connections: HashMap<ConnectionId, PeerId>,
 
+    /// The protocols supported by the remote on each connection, as far as known.
+    remote_protocols: HashMap<ConnectionId, Vec<StreamProtocol>>,
+
     /// See [`Config::caching`].
     caching: Caching,
 
@@ -115,11 +248,42 @@ pub struct Behaviour<TStore> {
     auto_mode: bool,
     no_events_waker: Option<Waker>,
 
+    /// The reachability of the node and its confidence, see [`Behaviour::set_reachability`].
+    reachability: (Reachability, usize),
+    /// See [`Config::set_reachability_confidence_threshold`].
+    reachability_confidence_threshold: usize,
+    /// See [`Config::set_mode_switch_interval`].
+    mode_switch_interval: Option<Duration>,
+    /// When the mode was last changed automatically.
+    last_mode_switch: Option<Instant>,
+    /// Fires once a deferred automatic mode change may take place.
+    deferred_mode_switch: Option<Delay>,
+
     /// The record storage.
     store: TStore,
 
+    /// Operations on the record store that did not complete immediately,
+    /// together with the inbound request they serve, if any.
+    pending_store_ops:
+        futures_bounded::FuturesTupleSet<StoreOutcome, Option<(PeerId, ConnectionId, RequestId)>>,
+
     /// Tracks the status of the current bootstrap.
     bootstrap_status: bootstrap::Status,
+
+    /// Tracks the lookups per bucket, see [`Config::set_bucket_staleness_threshold`].
+    bucket_refreshes: BucketRefreshes,
+
+    /// Schedules the dials revalidating the addresses of routing table peers,
+    /// see [`Config::set_address_revalidation_interval`].
+    address_revalidation: AddressRevalidation,
+
+    /// The bootstrap peers re-dialed while the node is isolated,
+    /// see [`Behaviour::add_bootstrap_peer`].
+    bootstrap_peers: BootstrapPeers,
+
+    /// The streams of the queries started via e.g. [`Behaviour::get_record_stream`].
+    #[cfg(feature = "stream")]
+    query_streams: QueryStreams,
 }
 
 /// The configurable strategies for the insertion of peers
@@ -146,6 +310,25 @@ pub enum BucketInserts {
     Manual,
 }
 
+/// The configurable policies for relayed, i.e. `/p2p-circuit`, addresses
+/// of peers in the Kademlia routing table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RelayedAddresses {
+    /// Relayed addresses are stored in the routing table and returned to
+    /// other nodes like any other address.
+    Include,
+    /// Relayed addresses are neither stored in the routing table nor returned
+    /// to other nodes. Peers only reachable via a relay are not added to the
+    /// routing table.
+    Exclude,
+    /// Relayed addresses are stored in the routing table, but a peer's relayed
+    /// addresses are only returned to other nodes if it has no direct address.
+    ///
+    /// This keeps DHT servers behind a NAT useful without handing out relayed
+    /// addresses where direct ones are known.
+    Fallback,
+}
+
 /// The configurable filtering strategies for the acceptance of
 /// incoming records.
 ///
@@ -175,6 +358,7 @@ pub enum StoreInserts {
 #[derive(Debug, Clone)]
 pub struct Config {
     kbucket_pending_timeout: Duration,
+    kbucket_sizes: BucketSizes,
     query_config: QueryConfig,
     protocol_config: ProtocolConfig,
     record_ttl: Option<Duration>,
@@ -184,9 +368,37 @@ pub struct Config {
     provider_record_ttl: Option<Duration>,
     provider_publication_interval: Option<Duration>,
     kbucket_inserts: BucketInserts,
+    relayed_addresses: RelayedAddresses,
     caching: Caching,
     periodic_bootstrap_interval: Option<Duration>,
     automatic_bootstrap_throttle: Option<Duration>,
+    bucket_staleness_threshold: Option<Duration>,
+    address_revalidation_interval: Option<Duration>,
+    address_revalidation_max_failures: NonZeroU32,
+    bootstrap_redial_interval: Duration,
+    provider_summaries: bool,
+    store_operation_timeout: Duration,
+    namespaces: Namespaces,
+    max_response_peers: Option<NonZeroUsize>,
+    max_response_size: Option<usize>,
+    max_peers_per_ip_prefix: Option<NonZeroUsize>,
+    address_filter: AddressFiltering,
+    peer_scorer: Scorer,
+    request_authorizer: Authorizer,
+    provider_filter: ProviderFiltering,
+    inbound_observer: InboundObserver,
+    peer_store: PeerStoreSeeding,
+    routing_updates_interval: Option<Duration>,
+    inbound_write_rate_limit: Option<(NonZeroU32, Duration)>,
+    global_inbound_write_rate_limit: Option<(NonZeroU32, Duration)>,
+    replicate_on_join: Option<(NonZeroU32, Duration)>,
+    advertise_when_dialable: bool,
+    require_signed_peer_records: bool,
+    max_user_queries: Option<NonZeroUsize>,
+    query_retry_policy: Option<RetryPolicy>,
+    reachability_confidence_threshold: usize,
+    mode_switch_interval: Option<Duration>,
+    routing_table_protocols: Option<Vec<StreamProtocol>>,
 }
 
 impl Default for Config {
@@ -211,6 +423,30 @@ pub enum Caching {
     /// The write-back operation must be performed explicitly, if
     /// desired and after choosing a record from the results, via [`Behaviour::put_record_to`].
     Enabled { max_peers: u16 },
+    /// Like [`Caching::Enabled`], but the first record found by a successful
+    /// lookup is additionally written back to the tracked peers, as in classic
+    /// Kademlia. Successful write-backs are reported via [`Event::RecordCached`].
+    ///
+    /// The cached copies expire after at most `max_ttl`. Each lookup only
+    /// performs the write-back with the given `probability` in `[0, 1]`,
+    /// which spreads the caching load for popular records.
+    Automatic {
+        max_peers: u16,
+        max_ttl: Duration,
+        probability: f64,
+    },
+}
+
+impl Caching {
+    /// The maximum number of cache candidates to track per lookup, if any.
+    fn max_peers(&self) -> Option<u16> {
+        match self {
+            Caching::Disabled => None,
+            Caching::Enabled { max_peers } | Caching::Automatic { max_peers, .. } => {
+                Some(*max_peers)
+            }
+        }
+    }
 }
 
 impl Config {
@@ -218,6 +454,7 @@ impl Config {
     pub fn new(protocol_name: StreamProtocol) -> Self {
         Config {
             kbucket_pending_timeout: Duration::from_secs(60),
+            kbucket_sizes: BucketSizes::default(),
             query_config: QueryConfig::default(),
             protocol_config: ProtocolConfig::new(protocol_name),
             record_ttl: Some(Duration::from_secs(48 * 60 * 60)),
@@ -227,9 +464,37 @@ impl Config {
             provider_publication_interval: Some(Duration::from_secs(12 * 60 * 60)),
             provider_record_ttl: Some(Duration::from_secs(48 * 60 * 60)),
             kbucket_inserts: BucketInserts::OnConnected,
+            relayed_addresses: RelayedAddresses::Include,
             caching: Caching::Enabled { max_peers: 1 },
             periodic_bootstrap_interval: Some(Duration::from_secs(5 * 60)),
             automatic_bootstrap_throttle: Some(bootstrap::DEFAULT_AUTOMATIC_THROTTLE),
+            bucket_staleness_threshold: None,
+            address_revalidation_interval: None,
+            address_revalidation_max_failures: NonZeroU32::new(3).expect("3 > 0"),
+            bootstrap_redial_interval: Duration::from_secs(30),
+            provider_summaries: false,
+            store_operation_timeout: Duration::from_secs(10),
+            namespaces: Default::default(),
+            max_response_peers: None,
+            max_response_size: None,
+            max_peers_per_ip_prefix: None,
+            address_filter: AddressFiltering::default(),
+            peer_scorer: Scorer::default(),
+            request_authorizer: Authorizer::default(),
+            provider_filter: ProviderFiltering::default(),
+            inbound_observer: InboundObserver::default(),
+            peer_store: PeerStoreSeeding::default(),
+            routing_updates_interval: None,
+            inbound_write_rate_limit: None,
+            global_inbound_write_rate_limit: None,
+            replicate_on_join: None,
+            advertise_when_dialable: false,
+            require_signed_peer_records: false,
+            max_user_queries: None,
+            query_retry_policy: None,
+            reachability_confidence_threshold: 0,
+            mode_switch_interval: None,
+            routing_table_protocols: None,
         }
     }
 
@@ -256,6 +521,34 @@ impl Config {
         self
     }
 
+    /// Adds a protocol name to speak besides the ones the configuration was created with,
+    /// e.g. to bridge forked networks.
+    ///
+    /// The configured names are proposed in the order they were added. The protocol
+    /// a peer negotiated is available via [`Behaviour::peer_protocol`], see also
+    /// [`Config::set_routing_table_protocols`].
+    pub fn add_protocol_name(&mut self, name: StreamProtocol) -> &mut Self {
+        self.protocol_config.add_protocol_name(name);
+        self
+    }
+
+    /// Restricts the routing table to peers that negotiated one of the given protocol names.
+    ///
+    /// Peers speaking other configured protocol names are still served and can be queried
+    /// directly, but are neither added to the routing table nor handed out to other nodes.
+    /// Running one [`Behaviour`] per protocol name, each restricted to its name, segregates
+    /// the routing tables of the networks. Peers added via [`Behaviour::add_address`] are
+    /// not subject to the restriction.
+    ///
+    /// `None` means that peers of all configured protocol names are added, which is the default.
+    pub fn set_routing_table_protocols(
+        &mut self,
+        protocols: Option<Vec<StreamProtocol>>,
+    ) -> &mut Self {
+        self.routing_table_protocols = protocols;
+        self
+    }
+
     /// Sets the timeout for a single query.
     ///
     /// > **Note**: A single query usually comprises at least as many requests
@@ -267,6 +560,18 @@ impl Config {
         self
     }
 
+    /// Sets the policy by which queries for the closest peers, for records and
+    /// for providers are retried when they time out, see [`RetryPolicy`].
+    ///
+    /// The final `Timeout` error of a query is only reported once it is not
+    /// retried anymore. The policy can be overridden per query via [`QueryOpts::retry`].
+    ///
+    /// `None` means that queries are not retried, which is the default.
+    pub fn set_query_retry_policy(&mut self, policy: Option<RetryPolicy>) -> &mut Self {
+        self.query_retry_policy = policy;
+        self
+    }
+
     /// Sets the replication factor to use.
     ///
     /// The replication factor determines to how many closest peers
@@ -293,6 +598,40 @@ impl Config {
         self
     }
 
+    /// Sets the bounds for adapting the parallelism of iterative queries to
+    /// the response times of the contacted peers.
+    ///
+    /// Queries wait for more peers in parallel when peers respond slowly, reducing
+    /// the tail latency of lookups on networks with heterogeneous peers, and for fewer
+    /// peers when they respond fast. See [`AdaptiveParallelism`] for details.
+    ///
+    /// `None` means that queries use the parallelism set via
+    /// [`Config::set_parallelism`] throughout, which is the default.
+    pub fn set_adaptive_parallelism(
+        &mut self,
+        adaptive_parallelism: Option<AdaptiveParallelism>,
+    ) -> &mut Self {
+        self.query_config.adaptive_parallelism = adaptive_parallelism;
+        self
+    }
+
+    /// Sets the weight of the round-trip time of a peer, relative to its distance
+    /// to the target, when choosing the next peer to contact in iterative queries.
+    ///
+    /// The round-trip times of peers are tracked from their responses to queries.
+    /// With a weight of `0.0`, peers are contacted by increasing distance to the
+    /// target. The higher the weight, the more a query prefers peers that respond
+    /// fast among the closest peers it has not contacted yet, which can shorten
+    /// lookups considerably. A weight of `1.0` ignores the distance of these peers.
+    ///
+    /// This does not apply to queries using [`Config::disjoint_query_paths`].
+    ///
+    /// The weight is clamped to the range `[0.0, 1.0]`. The default is `0.0`.
+    pub fn set_latency_weight(&mut self, weight: f64) -> &mut Self {
+        self.query_config.latency_weight = weight.clamp(0.0, 1.0);
+        self
+    }
+
     /// Require iterative queries to use disjoint paths for increased resiliency
     /// in the presence of potentially adversarial nodes.
     ///
@@ -306,6 +645,59 @@ impl Config {
         self
     }
 
+    /// Sets the maximum number of queries running at the same time.
+    ///
+    /// Queries started beyond the limit wait until running queries finish and are
+    /// started in the order of their [`QueryPriority`], such that queries of the user
+    /// are started before queries of background jobs, e.g. re-publishing records.
+    /// A waiting query is started regardless of its priority once a number of
+    /// other queries have been started ahead of it.
+    ///
+    /// The priority of a query can be changed via [`QueryMut::set_priority`].
+    ///
+    /// `None` means no limit, which is the default.
+    pub fn set_max_running_queries(&mut self, max: Option<NonZeroUsize>) -> &mut Self {
+        self.query_config.max_running_queries = max;
+        self
+    }
+
+    /// Sets the maximum number of queries of the user in progress at the same time.
+    ///
+    /// Queries of the user are those with a priority above [`QueryPriority::Background`],
+    /// running or waiting to be started. Once the limit is reached, the `try_` methods
+    /// like [`Behaviour::try_get_record`] return [`QueryLimitReached`] instead of
+    /// starting a query, allowing to apply backpressure until a query finishes.
+    /// Queries started via the other methods count towards the limit but are never
+    /// rejected.
+    ///
+    /// `None` means no limit, which is the default.
+    pub fn set_max_user_queries(&mut self, max: Option<NonZeroUsize>) -> &mut Self {
+        self.max_user_queries = max;
+        self
+    }
+
+    /// Sets the confidence a reachability reported via [`Behaviour::set_reachability`]
+    /// must have to determine the [`Mode`] of the node.
+    ///
+    /// Below the threshold, the mode is determined by the confirmed external addresses
+    /// as if no reachability was reported. The default is 0, i.e. any reported
+    /// reachability other than [`Reachability::Unknown`] determines the mode.
+    pub fn set_reachability_confidence_threshold(&mut self, threshold: usize) -> &mut Self {
+        self.reachability_confidence_threshold = threshold;
+        self
+    }
+
+    /// Sets the minimum interval between two automatic [`Mode`] changes.
+    ///
+    /// A mode change within the interval after the last one is deferred until the
+    /// interval elapsed, and dropped if the reason for it no longer holds by then.
+    /// This keeps the node from flapping between modes, e.g. on an unstable
+    /// reachability. The default is `None`, i.e. the mode changes immediately.
+    pub fn set_mode_switch_interval(&mut self, interval: Option<Duration>) -> &mut Self {
+        self.mode_switch_interval = interval;
+        self
+    }
+
     /// Sets the TTL for stored records.
     ///
     /// The TTL should be significantly longer than the (re-)publication
@@ -400,12 +792,74 @@ impl Config {
         self
     }
 
+    /// Sets the maximum number of entries of a k-bucket in the routing table.
+    ///
+    /// The default is [`K_VALUE`].
+    pub fn set_kbucket_size(&mut self, size: NonZeroUsize) -> &mut Self {
+        self.kbucket_sizes.size = size;
+        self
+    }
+
+    /// Sets a different maximum number of entries for the `num_buckets` k-buckets
+    /// closest to the local node, overriding [`Config::set_kbucket_size`] for these
+    /// buckets.
+    ///
+    /// The k-bucket with index `i` holds the peers at a distance in `[2^i, 2^(i+1))`
+    /// from the local key, hence this applies to all peers at a distance below
+    /// `2^num_buckets`. In a network of `n` nodes, only the buckets with an index
+    /// above about `256 - log2(n)` are populated, so `num_buckets` should be chosen
+    /// accordingly. Larger buckets close to the local key improve the accuracy of
+    /// the local node's view of its own neighbourhood in the keyspace.
+    ///
+    /// By default all k-buckets have the same size.
+    pub fn set_close_kbucket_size(&mut self, num_buckets: usize, size: NonZeroUsize) -> &mut Self {
+        self.kbucket_sizes.close = Some((num_buckets, size));
+        self
+    }
+
+    /// Sets the maximum number of peers pending insertion into a full k-bucket.
+    ///
+    /// A connected peer that does not fit into a full k-bucket is kept as pending until
+    /// a slot frees up or the least-recently connected peer of the bucket, if disconnected,
+    /// did not reconnect within the pending timeout. When that happens, the best pending
+    /// peer is inserted: connected peers are preferred over disconnected ones, then peers
+    /// with a lower observed round-trip time, then peers pending for longer. If all pending
+    /// slots are taken, a new peer only replaces a pending peer that disconnected meanwhile.
+    ///
+    /// The default is 1.
+    pub fn set_kbucket_pending_entries(&mut self, max: NonZeroUsize) -> &mut Self {
+        self.kbucket_sizes.pending = max;
+        self
+    }
+
     /// Sets the k-bucket insertion strategy for the Kademlia routing table.
     pub fn set_kbucket_inserts(&mut self, inserts: BucketInserts) -> &mut Self {
         self.kbucket_inserts = inserts;
         self
     }
 
+    /// Sets the [`RelayedAddresses`] policy for relayed addresses in the routing table
+    /// and in the responses to other nodes.
+    ///
+    /// The default is [`RelayedAddresses::Include`].
+    pub fn set_relayed_addresses(&mut self, policy: RelayedAddresses) -> &mut Self {
+        self.relayed_addresses = policy;
+        self
+    }
+
+    /// Sets the [`AddressFilter`] consulted whenever an address of a peer is learned, i.e.
+    /// added via [`Behaviour::add_address`], dialed or reported in a response to a query,
+    /// before it enters the routing table. A filter set before is replaced.
+    ///
+    /// Unlike [`BucketInserts::Manual`], which leaves all insertions to the application,
+    /// the filter allows rejecting individual addresses, e.g. private or relayed ones.
+    ///
+    /// By default, all addresses are accepted.
+    pub fn set_address_filter(&mut self, filter: impl AddressFilter) -> &mut Self {
+        self.address_filter = AddressFiltering::new(filter);
+        self
+    }
+
     /// Sets the [`Caching`] strategy to use for successful lookups.
     ///
     /// The default is [`Caching::Enabled`] with a `max_peers` of 1.
@@ -426,170 +880,708 @@ impl Config {
         self
     }
 
-    /// Sets the time to wait before calling [`Behaviour::bootstrap`] after a new peer is inserted in the routing table.
-    /// This prevent cascading bootstrap requests when multiple peers are inserted into the routing table "at the same time".
-    /// This also allows to wait a little bit for other potential peers to be inserted into the routing table before
-    /// triggering a bootstrap, giving more context to the future bootstrap request.  
+    /// Sets the time after which a non-empty bucket is considered stale and automatically
+    /// refreshed, unless a lookup for a key in its range was started within that time.
     ///
-    /// * Default to `500` ms.
-    /// * Set to `Some(Duration::ZERO)` to never wait before triggering a bootstrap request when a new peer
-    ///     is inserted in the routing table.
-    /// * Set to `None` to disable automatic bootstrap (no bootstrap request will be triggered when a new
-    ///     peer is inserted in the routing table).
-    #[cfg(test)]
-    pub(crate) fn set_automatic_bootstrap_throttle(
-        &mut self,
-        duration: Option<Duration>,
-    ) -> &mut Self {
-        self.automatic_bootstrap_throttle = duration;
+    /// Bucket refreshes by [`Behaviour::bootstrap`], [`Behaviour::refresh_bucket`] and
+    /// [`Behaviour::refresh_all`] count as such lookups. Automatic refreshes are reported
+    /// like the ones of [`Behaviour::refresh_all`].
+    ///
+    /// `None` means that buckets are only refreshed by bootstrapping or on demand,
+    /// which is the default.
+    pub fn set_bucket_staleness_threshold(&mut self, threshold: Option<Duration>) -> &mut Self {
+        self.bucket_staleness_threshold = threshold;
         self
     }
-}
 
-impl<TStore> Behaviour<TStore>
-where
-    TStore: RecordStore + Send + 'static,
-{
-    /// Creates a new `Kademlia` network behaviour with a default configuration.
-    pub fn new(id: PeerId, store: TStore) -> Self {
-        Self::with_config(id, store, Default::default())
+    /// Sets the interval at which a disconnected peer of the routing table is dialed to
+    /// verify that its addresses still work, starting with the peers revalidated the
+    /// longest time ago.
+    ///
+    /// This keeps peers that have not been needed for a query, but are handed out in
+    /// responses to other nodes, from silently going stale. Addresses failing to be dialed
+    /// are removed as usual and peers that fail to be reached repeatedly are evicted, see
+    /// [`Config::set_address_revalidation_max_failures`].
+    ///
+    /// `None` means that addresses are not revalidated, which is the default.
+    pub fn set_address_revalidation_interval(&mut self, interval: Option<Duration>) -> &mut Self {
+        self.address_revalidation_interval = interval;
+        self
     }
 
-    /// Get the protocol name of this kademlia instance.
-    pub fn protocol_names(&self) -> &[StreamProtocol] {
-        self.protocol_config.protocol_names()
+    /// Sets the number of consecutive failed revalidations after which a peer is evicted
+    /// from the routing table, see [`Config::set_address_revalidation_interval`].
+    ///
+    /// The default is 3.
+    pub fn set_address_revalidation_max_failures(&mut self, max: NonZeroU32) -> &mut Self {
+        self.address_revalidation_max_failures = max;
+        self
     }
 
-    /// Creates a new `Kademlia` network behaviour with the given configuration.
-    pub fn with_config(id: PeerId, store: TStore, config: Config) -> Self {
-        let local_key = kbucket::Key::from(id);
-
-        let put_record_job = config
-            .record_replication_interval
-            .or(config.record_publication_interval)
-            .map(|interval| {
-                PutRecordJob::new(
-                    id,
-                    interval,
-                    config.record_publication_interval,
-                    config.record_ttl,
-                )
-            });
-
-        let add_provider_job = config
-            .provider_publication_interval
-            .map(AddProviderJob::new);
+    /// Sets the interval at which the bootstrap peers are re-dialed while the node is
+    /// isolated, see [`Behaviour::add_bootstrap_peer`].
+    ///
+    /// The default is 30 seconds.
+    pub fn set_bootstrap_redial_interval(&mut self, interval: Duration) -> &mut Self {
+        self.bootstrap_redial_interval = interval;
+        self
+    }
 
-        Behaviour {
-            store,
-            caching: config.caching,
-            kbuckets: KBucketsTable::new(local_key, config.kbucket_pending_timeout),
-            kbucket_inserts: config.kbucket_inserts,
-            protocol_config: config.protocol_config,
-            record_filtering: config.record_filtering,
-            queued_events: VecDeque::with_capacity(config.query_config.replication_factor.get()),
-            listen_addresses: Default::default(),
-            queries: QueryPool::new(config.query_config),
-            connected_peers: Default::default(),
-            add_provider_job,
-            put_record_job,
-            record_ttl: config.record_ttl,
-            provider_record_ttl: config.provider_record_ttl,
-            external_addresses: Default::default(),
-            local_peer_id: id,
-            connections: Default::default(),
-            mode: Mode::Client,
-            auto_mode: true,
-            no_events_waker: None,
-            bootstrap_status: bootstrap::Status::new(
-                config.periodic_bootstrap_interval,
-                config.automatic_bootstrap_throttle,
-            ),
-        }
+    /// Sets whether the local node answers requests for a [`ProviderSummary`]
+    /// of the keys it stores provider records for.
+    ///
+    /// See [`Behaviour::get_provider_summary`]. Requests are reset if disabled.
+    ///
+    /// Disabled by default.
+    pub fn set_provider_summaries(&mut self, enabled: bool) -> &mut Self {
+        self.provider_summaries = enabled;
+        self
     }
 
-    /// Gets an iterator over immutable references to all running queries.
-    pub fn iter_queries(&self) -> impl Iterator<Item = QueryRef<'_>> {
-        self.queries.iter().filter_map(|query| {
-            if !query.is_finished() {
-                Some(QueryRef { query })
-            } else {
-                None
-            }
-        })
+    /// Sets the timeout for operations on an [`AsyncRecordStore`] that do
+    /// not complete immediately.
+    ///
+    /// Inbound requests served by an operation that times out are reset.
+    ///
+    /// Defaults to `10` seconds.
+    pub fn set_store_operation_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.store_operation_timeout = timeout;
+        self
     }
 
-    /// Gets an iterator over mutable references to all running queries.
-    pub fn iter_queries_mut(&mut self) -> impl Iterator<Item = QueryMut<'_>> {
-        self.queries.iter_mut().filter_map(|query| {
-            if !query.is_finished() {
-                Some(QueryMut { query })
-            } else {
-                None
-            }
-        })
+    /// Registers a [`RecordValidator`] for the records whose keys are in the given
+    /// namespace, i.e. keys of the form `/<namespace>/<path>`, e.g. `ipns`.
+    ///
+    /// The validator is consulted on records of inbound `PUT_VALUE` requests, which are
+    /// rejected if invalid, and on records found by [`Behaviour::get_record`], which are
+    /// ignored if invalid. A validator registered for the same namespace before is replaced.
+    ///
+    /// Records whose key is not in a namespace with a registered validator are accepted.
+    pub fn add_record_validator(
+        &mut self,
+        namespace: &str,
+        validator: impl RecordValidator,
+    ) -> &mut Self {
+        self.namespaces.validators.insert(namespace, validator);
+        self
     }
 
-    /// Gets an immutable reference to a running query, if it exists.
-    pub fn query(&self, id: &QueryId) -> Option<QueryRef<'_>> {
-        self.queries.get(id).and_then(|query| {
-            if !query.is_finished() {
-                Some(QueryRef { query })
-            } else {
-                None
-            }
-        })
+    /// Registers a [`ConflictResolver`] for the records whose keys are in the given
+    /// namespace, i.e. keys of the form `/<namespace>/<path>`, e.g. `ipns`.
+    ///
+    /// The resolver decides which of two records for the same key supersedes the other.
+    /// Records of inbound `PUT_VALUE` requests superseded by the record stored locally are
+    /// rejected, and records found by [`Behaviour::get_record`] superseded by a record
+    /// found before are not reported. A resolver registered for the same namespace before
+    /// is replaced.
+    ///
+    /// Records whose key is not in a namespace with a registered resolver are resolved
+    /// by [`HighestSequence`](crate::HighestSequence).
+    pub fn add_conflict_resolver(
+        &mut self,
+        namespace: &str,
+        resolver: impl ConflictResolver,
+    ) -> &mut Self {
+        self.namespaces.resolvers.insert(namespace, resolver);
+        self
     }
 
-    /// Gets a mutable reference to a running query, if it exists.
-    pub fn query_mut<'a>(&'a mut self, id: &QueryId) -> Option<QueryMut<'a>> {
-        self.queries.get_mut(id).and_then(|query| {
-            if !query.is_finished() {
-                Some(QueryMut { query })
-            } else {
-                None
-            }
-        })
+    /// Registers a [`Namespace`] for the records whose keys are in the namespace with
+    /// the given name, i.e. keys of the form `/<name>/<path>`, e.g. `ipns` or `pk`.
+    ///
+    /// The namespace combines the [`RecordValidator`] and [`ConflictResolver`] of its records,
+    /// see [`Config::add_record_validator`] and [`Config::add_conflict_resolver`], with a
+    /// storage policy overriding the [`Config`] for its records. Aspects of a namespace
+    /// registered before are replaced if set.
+    pub fn add_namespace(&mut self, name: &str, namespace: Namespace) -> &mut Self {
+        self.namespaces.insert(name, namespace);
+        self
     }
 
-    /// Adds a known listen address of a peer participating in the DHT to the
-    /// routing table.
+    /// Sets whether records whose keys are not in a registered namespace are rejected,
+    /// like records rejected by a [`RecordValidator`].
     ///
-    /// Explicitly adding addresses of peers serves two purposes:
+    /// A namespace is registered via [`Config::add_namespace`], [`Config::add_record_validator`]
+    /// or [`Config::add_conflict_resolver`]. Enabling this is required to interoperate with
+    /// nodes which only accept records of known namespaces, e.g. IPNS records.
     ///
-    ///   1. In order for a node to join the DHT, it must know about at least
-    ///      one other node of the DHT.
+    /// Defaults to `false`.
+    pub fn set_reject_unknown_namespaces(&mut self, reject: bool) -> &mut Self {
+        self.namespaces.reject_unknown = reject;
+        self
+    }
+
+    /// Sets the maximum number of closer peers, as well as the maximum number of
+    /// providers, returned in a response to a request of a remote node.
     ///
-    ///   2. When a remote peer initiates a connection and that peer is not
-    ///      yet in the routing table, the `Kademlia` behaviour must be
-    ///      informed of an address on which that peer is listening for
-    ///      connections before it can be added to the routing table
-    ///      from where it can subsequently be discovered by all peers
-    ///      in the DHT.
+    /// The closest peers to the requested key are returned. Values larger than the
+    /// replication factor have no effect.
+    ///
+    /// Defaults to the replication factor.
+    pub fn set_max_response_peers(&mut self, max: NonZeroUsize) -> &mut Self {
+        self.max_response_peers = Some(max);
+        self
+    }
+
+    /// Sets the maximum size, in bytes, of the peers and the record returned in a
+    /// response to a request of a remote node, e.g. to keep `FIND_NODE` responses
+    /// small on links with a small MTU or on relayed connections.
+    ///
+    /// Peers are dropped from a response that exceeds the size, closer peers before
+    /// providers and the peers farthest from the requested key first. The closest
+    /// peer is always returned, such that the remote can make progress.
+    ///
+    /// `None` means no limit beyond the maximum packet size, which is the default.
+    pub fn set_max_response_size(&mut self, size: Option<usize>) -> &mut Self {
+        self.max_response_size = size;
+        self
+    }
+
+    /// Sets the maximum number of peers in the routing table with an address in
+    /// the same `/24` (IPv4) or `/48` (IPv6) network, to make Sybil and eclipse
+    /// attacks more costly.
+    ///
+    /// The limit is checked whenever a peer or an address of a peer is added to the
+    /// routing table. Peers and addresses exceeding the limit are not added and
+    /// reported via [`Event::IpDiversityExceeded`]. Only globally reachable IP
+    /// addresses are subject to the limit, i.e. loopback, private and link-local
+    /// addresses as well as DNS addresses are not.
+    ///
+    /// `None` means no limit, which is the default.
+    pub fn set_max_peers_per_ip_prefix(&mut self, max: Option<NonZeroUsize>) -> &mut Self {
+        self.max_peers_per_ip_prefix = max;
+        self
+    }
+
+    /// Sets the [`PeerScorer`] consulted before a peer is inserted into the routing table
+    /// or promoted within it, see [`PeerScorer`] for details.
+    ///
+    /// The scorer is informed of the misbehaviour of peers, i.e. invalid records, timed out
+    /// requests and unexpected responses, as well as of misbehaviour reported via
+    /// [`Behaviour::report_misbehaviour`]. A scorer set before is replaced.
+    ///
+    /// By default, all peers are admitted.
+    pub fn set_peer_scorer(&mut self, scorer: impl PeerScorer) -> &mut Self {
+        self.peer_scorer = Scorer::new(scorer);
+        self
+    }
+
+    /// Sets the [`RequestAuthorizer`] deciding whether inbound `GET_VALUE`, `GET_PROVIDERS`,
+    /// `PUT_VALUE` and `ADD_PROVIDER` requests are answered, e.g. to restrict a private DHT
+    /// to its members where the protocol name alone is not sufficient isolation.
+    ///
+    /// Denied requests are reported via [`Event::InboundRequestDenied`]. `FIND_NODE` requests
+    /// are always answered. An authorizer or allowlist set before is replaced.
+    ///
+    /// By default, the requests of all peers are answered.
+    pub fn set_request_authorizer(&mut self, authorizer: impl RequestAuthorizer) -> &mut Self {
+        self.request_authorizer = Authorizer::new(authorizer);
+        self
+    }
+
+    /// Answers inbound `GET_VALUE`, `GET_PROVIDERS`, `PUT_VALUE` and `ADD_PROVIDER` requests
+    /// of the given peers only, see [`Config::set_request_authorizer`].
+    pub fn set_request_allowlist(&mut self, peers: impl IntoIterator<Item = PeerId>) -> &mut Self {
+        self.request_authorizer = Authorizer::allowlist(peers);
+        self
+    }
+
+    /// Sets the [`ProviderFilter`] deciding which providers are returned in response to inbound
+    /// `GET_PROVIDERS` requests and reported via [`GetProvidersOk::FoundProviders`], e.g. to
+    /// redact providers known to be stale, blocked or failing a custom policy.
+    ///
+    /// Providers filtered from a response do not count towards the quorum of a query.
+    ///
+    /// By default, all providers are kept.
+    pub fn set_provider_filter(&mut self, filter: impl ProviderFilter) -> &mut Self {
+        self.provider_filter = ProviderFiltering::new(filter);
+        self
+    }
+
+    /// Sets the [`InboundRequestObserver`] called for every inbound request answered by the
+    /// local node, attributing the request to the peer and key, e.g. to identify hot keys and
+    /// abusive peers.
+    ///
+    /// Requests that are denied or throttled are not observed, see
+    /// [`Event::InboundRequestDenied`] and [`Event::InboundRequestThrottled`].
+    pub fn set_inbound_request_observer(
+        &mut self,
+        observer: impl InboundRequestObserver,
+    ) -> &mut Self {
+        self.inbound_observer = InboundObserver::new(observer);
+        self
+    }
+
+    /// Sets the persistent [`PeerStore`] the routing table is seeded from when the
+    /// [`Behaviour`] is created, in addition to the bootnodes added by the application.
+    ///
+    /// Of the peers returned by [`PeerStore::kad_peers`], the at most `max_peers` most
+    /// recently seen ones that have been seen within `max_age` are added to the routing
+    /// table as by [`Behaviour::restore_routing_table`]. Peers of the routing table are
+    /// reported to the store via [`PeerStore::on_peer_seen`] when they disconnect.
+    ///
+    /// By default, there is no peer store.
+    pub fn set_peer_store(
+        &mut self,
+        store: impl PeerStore,
+        max_peers: usize,
+        max_age: Duration,
+    ) -> &mut Self {
+        self.peer_store = PeerStoreSeeding::new(store, max_peers, max_age);
+        self
+    }
+
+    /// Sets the [`KeyHasher`] mapping peer IDs and record keys into the keyspace, whose
+    /// distances determine the peers queried for and storing a record.
+    ///
+    /// All nodes of a network must use the same hashing, see [`KeyHasher`] for details.
+    ///
+    /// By default, keys are hashed via SHA-256.
+    pub fn set_key_hasher(&mut self, hasher: impl KeyHasher) -> &mut Self {
+        self.query_config.hasher = KeyHashing::new(hasher);
+        self
+    }
+
+    /// Sets the interval at which updates of the routing table are reported
+    /// in aggregate via [`Event::RoutingUpdatesCoalesced`], instead of individually
+    /// via [`Event::RoutingUpdated`], e.g. to avoid a flood of events while bootstrapping.
+    ///
+    /// The interval also applies to [`Behaviour::subscribe_routing_updates`].
+    ///
+    /// `None` means that every update is reported individually, which is the default.
+    pub fn set_coalesced_routing_updates(&mut self, interval: Option<Duration>) -> &mut Self {
+        self.routing_updates_interval = interval;
+        self
+    }
+
+    /// Sets the rate limit of inbound `PUT_VALUE` and `ADD_PROVIDER` requests of a single peer.
+    ///
+    /// A peer may send bursts of up to `limit` requests, refilled at a rate of
+    /// one request per `interval`. Requests exceeding the limit are dropped
+    /// and reported via [`Event::InboundRequestThrottled`].
+    ///
+    /// `None` means no limit, which is the default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    pub fn set_inbound_write_rate_limit(
+        &mut self,
+        limit: Option<(NonZeroU32, Duration)>,
+    ) -> &mut Self {
+        assert!(limit.map_or(true, |(_, interval)| !interval.is_zero()));
+        self.inbound_write_rate_limit = limit;
+        self
+    }
+
+    /// Sets the rate limit of inbound `PUT_VALUE` and `ADD_PROVIDER` requests of all peers combined.
+    ///
+    /// See [`Config::set_inbound_write_rate_limit`] for the semantics of the limit.
+    /// Requests are checked against the per-peer limit first, thus requests dropped
+    /// due to the per-peer limit do not count towards the global limit.
+    ///
+    /// `None` means no limit, which is the default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    pub fn set_global_inbound_write_rate_limit(
+        &mut self,
+        limit: Option<(NonZeroU32, Duration)>,
+    ) -> &mut Self {
+        assert!(limit.map_or(true, |(_, interval)| !interval.is_zero()));
+        self.global_inbound_write_rate_limit = limit;
+        self
+    }
+
+    /// Sets whether stored records are replicated to peers joining the routing table
+    /// that are among the `k` closest peers to the keys of the records, i.e. the
+    /// classic Kademlia "transfer on join", and the rate limit of such replications.
+    ///
+    /// Bursts of up to `limit` records are replicated, refilled at a rate of one
+    /// record per `interval`, regardless of the number of peers a record is
+    /// replicated to. Replications exceeding the limit are deferred.
+    ///
+    /// `None` means that records are only replicated by the periodic replication,
+    /// see [`Config::set_replication_interval`], which is the default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    pub fn set_replicate_on_join(&mut self, limit: Option<(NonZeroU32, Duration)>) -> &mut Self {
+        assert!(limit.map_or(true, |(_, interval)| !interval.is_zero()));
+        self.replicate_on_join = limit;
+        self
+    }
+
+    /// Sets whether the local node defers advertising itself as a provider
+    /// and publishing its records until it is dialable, i.e. has a confirmed
+    /// external address or listens on a relayed address through an active
+    /// relay reservation.
+    ///
+    /// This keeps nodes behind a NAT from announcing provider records that
+    /// other nodes cannot use to connect to them. Affected are
+    /// [`Behaviour::start_providing`], [`Behaviour::put_record`] and the
+    /// periodic re-publication of provider records and records. Their queries
+    /// only start once the local node is dialable.
+    ///
+    /// Disabled by default.
+    pub fn set_advertise_when_dialable(&mut self, enabled: bool) -> &mut Self {
+        self.advertise_when_dialable = enabled;
+        self
+    }
+
+    /// Sets whether peers in `FIND_NODE`, `GET_VALUE` and `GET_PROVIDERS` responses are
+    /// only accepted along with a signed peer record, authenticating their addresses.
+    ///
+    /// Regardless of this setting, the addresses of a signed peer record attached to a peer
+    /// take precedence over the unsigned addresses of the peer. Requiring records rejects
+    /// spoofed addresses in responses, but also all peers of remotes that do not know their
+    /// records. See [`Behaviour::add_signed_peer_record`].
+    ///
+    /// Disabled by default.
+    pub fn set_require_signed_peer_records(&mut self, require: bool) -> &mut Self {
+        self.require_signed_peer_records = require;
+        self
+    }
+
+    /// Sets the time to wait before calling [`Behaviour::bootstrap`] after a new peer is inserted in the routing table.
+    /// This prevent cascading bootstrap requests when multiple peers are inserted into the routing table "at the same time".
+    /// This also allows to wait a little bit for other potential peers to be inserted into the routing table before
+    /// triggering a bootstrap, giving more context to the future bootstrap request.  
+    ///
+    /// * Default to `500` ms.
+    /// * Set to `Some(Duration::ZERO)` to never wait before triggering a bootstrap request when a new peer
+    ///     is inserted in the routing table.
+    /// * Set to `None` to disable automatic bootstrap (no bootstrap request will be triggered when a new
+    ///     peer is inserted in the routing table).
+    #[cfg(test)]
+    pub(crate) fn set_automatic_bootstrap_throttle(
+        &mut self,
+        duration: Option<Duration>,
+    ) -> &mut Self {
+        self.automatic_bootstrap_throttle = duration;
+        self
+    }
+}
+
+impl<TStore> Behaviour<TStore>
+where
+    TStore: AsyncRecordStore + Send + 'static,
+{
+    /// Creates a new `Kademlia` network behaviour with a default configuration.
+    pub fn new(id: PeerId, store: TStore) -> Self {
+        Self::with_config(id, store, Default::default())
+    }
+
+    /// Get the protocol name of this kademlia instance.
+    pub fn protocol_names(&self) -> &[StreamProtocol] {
+        self.protocol_config.protocol_names()
+    }
+
+    /// Returns the protocol name the given connected peer negotiated, if confirmed.
+    pub fn peer_protocol(&self, peer: &PeerId) -> Option<&StreamProtocol> {
+        self.peer_protocols.get(peer)
+    }
+
+    /// Returns the number of connected peers per negotiated protocol name.
+    pub fn connected_peers_per_protocol(&self) -> HashMap<StreamProtocol, usize> {
+        let mut counts = HashMap::new();
+        for protocol in self.peer_protocols.values() {
+            *counts.entry(protocol.clone()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Creates a new `Kademlia` network behaviour with the given configuration.
+    pub fn with_config(id: PeerId, store: TStore, config: Config) -> Self {
+        let hasher = config.query_config.hasher.clone();
+        let local_key = hasher.peer(id);
+
+        let put_record_job = new_put_record_job(
+            id,
+            config.record_replication_interval,
+            config.record_publication_interval,
+            config.record_ttl,
+        );
+
+        let add_provider_job = AddProviderJob::new(config.provider_publication_interval);
+
+        let mut behaviour = Behaviour {
+            store,
+            pending_store_ops: futures_bounded::FuturesTupleSet::new(
+                config.store_operation_timeout,
+                MAX_PENDING_STORE_OPS,
+            ),
+            caching: config.caching,
+            kbuckets: KBucketsTable::new(
+                local_key,
+                config.kbucket_pending_timeout,
+                config.kbucket_sizes,
+            ),
+            kbucket_inserts: config.kbucket_inserts,
+            relayed_addresses: config.relayed_addresses,
+            protocol_config: config.protocol_config,
+            record_filtering: config.record_filtering,
+            queued_events: VecDeque::with_capacity(config.query_config.replication_factor.get()),
+            listen_addresses: Default::default(),
+            queries: QueryPool::new(config.query_config),
+            connected_peers: Default::default(),
+            peer_protocols: Default::default(),
+            routing_table_protocols: config.routing_table_protocols,
+            last_seen: Default::default(),
+            add_provider_job,
+            put_record_job,
+            replicate_on_join_job: config
+                .replicate_on_join
+                .map(|(limit, interval)| ReplicateOnJoinJob::new(limit, interval)),
+            record_replication_interval: config.record_replication_interval,
+            record_publication_interval: config.record_publication_interval,
+            background_jobs_paused: false,
+            record_ttl: config.record_ttl,
+            provider_record_ttl: config.provider_record_ttl,
+            local_provider_expirations: HashMap::new(),
+            provider_summaries: config.provider_summaries,
+            namespaces: config.namespaces,
+            max_response_peers: config.max_response_peers,
+            max_response_size: config.max_response_size,
+            max_peers_per_ip_prefix: config.max_peers_per_ip_prefix,
+            address_filter: config.address_filter,
+            peer_scorer: config.peer_scorer,
+            hasher,
+            request_authorizer: config.request_authorizer,
+            provider_filter: config.provider_filter,
+            inbound_observer: config.inbound_observer,
+            peer_store: config.peer_store,
+            routing_updates: RoutingUpdatesCoalescer::new(config.routing_updates_interval),
+            inbound_write_limiter: config
+                .inbound_write_rate_limit
+                .map(|(limit, interval)| RateLimiter::new(limit, interval)),
+            global_inbound_write_limiter: config
+                .global_inbound_write_rate_limit
+                .map(|(limit, interval)| RateLimiter::new(limit, interval)),
+            advertise_when_dialable: config.advertise_when_dialable,
+            signed_records: SignedPeerRecords::new(id, config.require_signed_peer_records),
+            pending_advertisements: Vec::new(),
+            query_commands: Default::default(),
+            max_user_queries: config.max_user_queries,
+            query_retry_policy: config.query_retry_policy,
+            pending_retries: Vec::new(),
+            reachability: (Reachability::Unknown, 0),
+            reachability_confidence_threshold: config.reachability_confidence_threshold,
+            mode_switch_interval: config.mode_switch_interval,
+            last_mode_switch: None,
+            deferred_mode_switch: None,
+            external_addresses: Default::default(),
+            local_peer_id: id,
+            connections: Default::default(),
+            remote_protocols: Default::default(),
+            mode: Mode::Client,
+            auto_mode: true,
+            no_events_waker: None,
+            bootstrap_status: bootstrap::Status::new(
+                config.periodic_bootstrap_interval,
+                config.automatic_bootstrap_throttle,
+            ),
+            bucket_refreshes: BucketRefreshes::new(config.bucket_staleness_threshold),
+            address_revalidation: AddressRevalidation::new(
+                config.address_revalidation_interval,
+                config.address_revalidation_max_failures,
+            ),
+            bootstrap_peers: BootstrapPeers::new(config.bootstrap_redial_interval),
+            #[cfg(feature = "stream")]
+            query_streams: QueryStreams::default(),
+        };
+        behaviour.seed_from_peer_store();
+        behaviour
+    }
+
+    /// Seeds the routing table with the recently seen peers of the [`PeerStore`], if any.
+    fn seed_from_peer_store(&mut self) {
+        let entries = self.peer_store.seeds(snapshot::unix_time_now());
+        if entries.is_empty() {
+            return;
+        }
+        let num_seeds = entries.len();
+        let restored = self.restore_routing_table(RoutingTableSnapshot { entries });
+        tracing::debug!("Seeded routing table with {restored} of {num_seeds} peers of peer store");
+    }
+
+    /// Gets an iterator over immutable references to all running queries.
+    pub fn iter_queries(&self) -> impl Iterator<Item = QueryRef<'_>> {
+        self.queries.iter().filter_map(|query| {
+            if !query.is_finished() {
+                Some(QueryRef { query })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Gets an iterator over mutable references to all running queries.
+    pub fn iter_queries_mut(&mut self) -> impl Iterator<Item = QueryMut<'_>> {
+        self.queries.iter_mut().filter_map(|query| {
+            if !query.is_finished() {
+                Some(QueryMut { query })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Gets an immutable reference to a running query, if it exists.
+    pub fn query(&self, id: &QueryId) -> Option<QueryRef<'_>> {
+        self.queries.get(id).and_then(|query| {
+            if !query.is_finished() {
+                Some(QueryRef { query })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Gets a mutable reference to a running query, if it exists.
+    pub fn query_mut<'a>(&'a mut self, id: &QueryId) -> Option<QueryMut<'a>> {
+        self.queries.get_mut(id).and_then(|query| {
+            if !query.is_finished() {
+                Some(QueryMut { query })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Gets a handle to the query with the given ID, to finish or cancel it from
+    /// outside of the event handling, e.g. from another task.
+    ///
+    /// The commands of a handle take effect the next time the behaviour is polled
+    /// and are ignored once the query is no longer running.
+    pub fn query_handle(&self, id: &QueryId) -> QueryHandle {
+        QueryHandle {
+            id: *id,
+            commands: self.query_commands.clone(),
+        }
+    }
+
+    /// Like [`Behaviour::get_record`] but also reports the progress of the query via the
+    /// returned [`QueryStream`].
+    #[cfg(feature = "stream")]
+    pub fn get_record_stream(&mut self, key: record::Key) -> QueryStream<GetRecordResult> {
+        let id = self.get_record(key);
+        self.query_streams.register(id, |result| match result {
+            QueryResult::GetRecord(r) => Some(r),
+            _ => None,
+        })
+    }
+
+    /// Like [`Behaviour::get_providers`] but also reports the progress of the query via the
+    /// returned [`QueryStream`].
+    #[cfg(feature = "stream")]
+    pub fn get_providers_stream(&mut self, key: record::Key) -> QueryStream<GetProvidersResult> {
+        let id = self.get_providers(key);
+        self.query_streams.register(id, |result| match result {
+            QueryResult::GetProviders(r) => Some(r),
+            _ => None,
+        })
+    }
+
+    /// Like [`Behaviour::get_closest_peers`] but also reports the progress of the query via
+    /// the returned [`QueryStream`].
+    #[cfg(feature = "stream")]
+    pub fn get_closest_peers_stream<K>(&mut self, key: K) -> QueryStream<GetClosestPeersResult>
+    where
+        K: Into<kbucket::Key<K>> + Into<Vec<u8>> + Clone,
+    {
+        let id = self.get_closest_peers(key);
+        self.query_streams.register(id, |result| match result {
+            QueryResult::GetClosestPeers(r) => Some(r),
+            _ => None,
+        })
+    }
+
+    /// Forwards the progress of a query to its [`QueryStream`], if any.
+    #[cfg(feature = "stream")]
+    fn route_to_query_stream(&mut self, event: &Event) {
+        if let Event::OutboundQueryProgressed {
+            id, result, step, ..
+        } = event
+        {
+            self.query_streams.on_progress(*id, result, step.last);
+        }
+    }
+
+    /// Ends the [`QueryStream`]s of cancelled queries and sends the progress
+    /// queued for the streams.
+    #[cfg(feature = "stream")]
+    fn poll_query_streams(&mut self, cx: &mut Context<'_>) {
+        let queries = &self.queries;
+        let pending_retries = &self.pending_retries;
+        self.query_streams.on_queries_removed(|id| {
+            queries.get(id).is_some() || pending_retries.iter().any(|(_, retry, ..)| retry == id)
+        });
+        self.query_streams.poll(cx);
+    }
+
+    /// Adds a known listen address of a peer participating in the DHT to the
+    /// routing table.
+    ///
+    /// Explicitly adding addresses of peers serves two purposes:
+    ///
+    ///   1. In order for a node to join the DHT, it must know about at least
+    ///      one other node of the DHT.
+    ///
+    ///   2. When a remote peer initiates a connection and that peer is not
+    ///      yet in the routing table, the `Kademlia` behaviour must be
+    ///      informed of an address on which that peer is listening for
+    ///      connections before it can be added to the routing table
+    ///      from where it can subsequently be discovered by all peers
+    ///      in the DHT.
     ///
     /// If the routing table has been updated as a result of this operation,
     /// a [`Event::RoutingUpdated`] event is emitted.
+    ///
+    /// Relayed addresses are rejected if [`RelayedAddresses::Exclude`] is configured, as are
+    /// addresses not accepted by the [`AddressFilter`], see [`Config::set_address_filter`].
     pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) -> RoutingUpdate {
         // ensuring address is a fully-qualified /p2p multiaddr
         let Ok(address) = address.with_p2p(*peer) else {
             return RoutingUpdate::Failed;
         };
-        let key = kbucket::Key::from(*peer);
+        if self.relayed_addresses == RelayedAddresses::Exclude && is_relayed(&address) {
+            tracing::debug!(%peer, %address, "Not adding relayed address to routing table");
+            return RoutingUpdate::Failed;
+        }
+        if !self
+            .address_filter
+            .accepts(peer, &address, AddressSource::Explicit)
+        {
+            tracing::debug!(%peer, %address, "Address rejected by address filter");
+            return RoutingUpdate::Failed;
+        }
+        if self.ip_diversity_exceeded(peer, &address) {
+            self.queued_events
+                .push_back(ToSwarm::GenerateEvent(Event::IpDiversityExceeded {
+                    peer: *peer,
+                    address,
+                }));
+            return RoutingUpdate::Failed;
+        }
+        let key = self.hasher.peer(*peer);
         match self.kbuckets.entry(&key) {
             Some(kbucket::Entry::Present(mut entry, _)) => {
                 if entry.value().insert(address) {
-                    self.queued_events
-                        .push_back(ToSwarm::GenerateEvent(Event::RoutingUpdated {
-                            peer: *peer,
-                            is_new_peer: false,
-                            addresses: entry.value().clone(),
-                            old_peer: None,
-                            bucket_range: self
-                                .kbuckets
-                                .bucket(&key)
-                                .map(|b| b.range())
-                                .expect("Not kbucket::Entry::SelfEntry."),
-                        }))
+                    let event = Event::RoutingUpdated {
+                        peer: *peer,
+                        is_new_peer: false,
+                        addresses: entry.value().clone(),
+                        old_peer: None,
+                        bucket_range: self
+                            .kbuckets
+                            .bucket(&key)
+                            .map(|b| b.range())
+                            .expect("Not kbucket::Entry::SelfEntry."),
+                    };
+                    self.queue_routing_updated(event);
                 }
                 RoutingUpdate::Success
             }
@@ -597,6 +1589,10 @@ where
                 entry.value().insert(address);
                 RoutingUpdate::Pending
             }
+            Some(kbucket::Entry::Absent(_)) if !self.peer_scorer.admits(peer) => {
+                tracing::debug!(%peer, "Peer not admitted to routing table by scorer");
+                RoutingUpdate::Failed
+            }
             Some(kbucket::Entry::Absent(entry)) => {
                 let addresses = Addresses::new(address);
                 let status = if self.connected_peers.contains(peer) {
@@ -604,22 +1600,23 @@ where
                 } else {
                     NodeStatus::Disconnected
                 };
-                match entry.insert(addresses.clone(), status) {
+                let rtt = self.queries.latency(peer);
+                match entry.insert(addresses.clone(), status, rtt) {
                     kbucket::InsertResult::Inserted => {
                         self.bootstrap_status.on_new_peer_in_routing_table();
-                        self.queued_events.push_back(ToSwarm::GenerateEvent(
-                            Event::RoutingUpdated {
-                                peer: *peer,
-                                is_new_peer: true,
-                                addresses,
-                                old_peer: None,
-                                bucket_range: self
-                                    .kbuckets
-                                    .bucket(&key)
-                                    .map(|b| b.range())
-                                    .expect("Not kbucket::Entry::SelfEntry."),
-                            },
-                        ));
+                        self.routing_table_updated(*peer, RoutingTableAction::Inserted);
+                        let event = Event::RoutingUpdated {
+                            peer: *peer,
+                            is_new_peer: true,
+                            addresses,
+                            old_peer: None,
+                            bucket_range: self
+                                .kbuckets
+                                .bucket(&key)
+                                .map(|b| b.range())
+                                .expect("Not kbucket::Entry::SelfEntry."),
+                        };
+                        self.queue_routing_updated(event);
                         RoutingUpdate::Success
                     }
                     kbucket::InsertResult::Full => {
@@ -638,6 +1635,46 @@ where
         }
     }
 
+    /// Adds a known listen address of a bootstrap peer, which is also added to the
+    /// routing table as per [`Behaviour::add_address`].
+    ///
+    /// Unlike other peers, bootstrap peers are never forgotten. Whenever the node is
+    /// isolated, i.e. it has no connections or its routing table is empty, the bootstrap
+    /// peers are added to the routing table again and dialed immediately and then every
+    /// [`Config::set_bootstrap_redial_interval`] until the node is connected again.
+    /// Losing and regaining connectivity is reported via
+    /// [`Event::BootstrapConnectivityLost`] and [`Event::BootstrapConnectivityRegained`].
+    pub fn add_bootstrap_peer(&mut self, peer: PeerId, address: Multiaddr) -> RoutingUpdate {
+        self.bootstrap_peers.insert(peer, address.clone());
+        if let Some(waker) = self.no_events_waker.take() {
+            waker.wake();
+        }
+        self.add_address(&peer, address)
+    }
+
+    /// Removes a bootstrap peer added via [`Behaviour::add_bootstrap_peer`], returning
+    /// its addresses. The peer is kept in the routing table.
+    pub fn remove_bootstrap_peer(&mut self, peer: &PeerId) -> Option<Vec<Multiaddr>> {
+        self.bootstrap_peers.remove(peer)
+    }
+
+    /// Returns the bootstrap peers and their addresses, see [`Behaviour::add_bootstrap_peer`].
+    pub fn bootstrap_peers(&self) -> impl Iterator<Item = (&PeerId, &[Multiaddr])> {
+        self.bootstrap_peers
+            .iter()
+            .map(|(peer, addresses)| (peer, addresses.as_slice()))
+    }
+
+    /// Adds a signed peer record, attached to the peer in responses to remotes.
+    ///
+    /// The record of the local node, signed by its key, is attached when the local node
+    /// advertises itself as a provider and is returned as one. Records of remote peers are
+    /// also learned from responses and provider announcements. Of the records of a peer,
+    /// the one with the highest sequence number is kept.
+    pub fn add_signed_peer_record(&mut self, record: libp2p_core::PeerRecord) {
+        self.signed_records.insert(record);
+    }
+
     /// Removes an address of a peer from the routing table.
     ///
     /// If the given address is the last address of the peer in the
@@ -654,24 +1691,36 @@ where
         address: &Multiaddr,
     ) -> Option<kbucket::EntryView<kbucket::Key<PeerId>, Addresses>> {
         let address = &address.to_owned().with_p2p(*peer).ok()?;
-        let key = kbucket::Key::from(*peer);
-        match self.kbuckets.entry(&key)? {
+        let key = self.hasher.peer(*peer);
+        let (removed, was_present) = match self.kbuckets.entry(&key)? {
             kbucket::Entry::Present(mut entry, _) => {
                 if entry.value().remove(address).is_err() {
-                    Some(entry.remove()) // it is the last address, thus remove the peer.
+                    (Some(entry.remove()), true) // it is the last address, thus remove the peer.
                 } else {
-                    None
+                    (None, false)
                 }
             }
             kbucket::Entry::Pending(mut entry, _) => {
                 if entry.value().remove(address).is_err() {
-                    Some(entry.remove()) // it is the last address, thus remove the peer.
+                    (Some(entry.remove()), false) // it is the last address, thus remove the peer.
                 } else {
-                    None
+                    (None, false)
                 }
             }
-            kbucket::Entry::Absent(..) => None,
+            kbucket::Entry::Absent(..) => (None, false),
+        };
+        if removed.is_some() {
+            self.last_seen.remove(peer);
+        }
+        if was_present {
+            self.routing_table_updated(
+                *peer,
+                RoutingTableAction::Evicted {
+                    reason: EvictionReason::Manual,
+                },
+            );
         }
+        removed
     }
 
     /// Removes a peer from the routing table.
@@ -682,12 +1731,120 @@ where
         &mut self,
         peer: &PeerId,
     ) -> Option<kbucket::EntryView<kbucket::Key<PeerId>, Addresses>> {
-        let key = kbucket::Key::from(*peer);
-        match self.kbuckets.entry(&key)? {
-            kbucket::Entry::Present(entry, _) => Some(entry.remove()),
-            kbucket::Entry::Pending(entry, _) => Some(entry.remove()),
-            kbucket::Entry::Absent(..) => None,
+        let key = self.hasher.peer(*peer);
+        let (removed, was_present) = match self.kbuckets.entry(&key)? {
+            kbucket::Entry::Present(entry, _) => (Some(entry.remove()), true),
+            kbucket::Entry::Pending(entry, _) => (Some(entry.remove()), false),
+            kbucket::Entry::Absent(..) => (None, false),
+        };
+        if removed.is_some() {
+            self.last_seen.remove(peer);
+        }
+        if was_present {
+            self.routing_table_updated(
+                *peer,
+                RoutingTableAction::Evicted {
+                    reason: EvictionReason::Manual,
+                },
+            );
         }
+        removed
+    }
+
+    /// Reports misbehaviour of a peer to the [`PeerScorer`], if any, see
+    /// [`Config::set_peer_scorer`].
+    ///
+    /// If the scorer no longer admits the peer, a pending entry of the peer in the routing
+    /// table is dropped and the peer is neither inserted into nor promoted within the routing
+    /// table thereafter. A peer already in the routing table is kept.
+    pub fn report_misbehaviour(&mut self, peer: &PeerId, misbehaviour: Misbehaviour) {
+        self.peer_scorer.on_misbehaviour(peer, misbehaviour);
+        if self.peer_scorer.admits(peer) {
+            return;
+        }
+        let key = self.hasher.peer(*peer);
+        if let Some(kbucket::Entry::Pending(entry, _)) = self.kbuckets.entry(&key) {
+            tracing::debug!(%peer, "Pending peer dropped from routing table by scorer");
+            entry.remove();
+        }
+    }
+
+    /// Removes a peer that repeatedly failed to be reached from the routing table.
+    fn evict_unreachable(&mut self, peer: PeerId) {
+        let key = self.hasher.peer(peer);
+        if let Some(kbucket::Entry::Present(entry, _)) = self.kbuckets.entry(&key) {
+            entry.remove();
+            self.last_seen.remove(&peer);
+            self.routing_table_updated(
+                peer,
+                RoutingTableAction::Evicted {
+                    reason: EvictionReason::Unreachable,
+                },
+            );
+        }
+    }
+
+    /// Subscribes to the updates of the routing table in aggregate, e.g. for
+    /// components like metrics that are not interested in the individual peers.
+    ///
+    /// The [`RoutingUpdates`] are sent at the interval configured via
+    /// [`Config::set_coalesced_routing_updates`] or, if not configured, for every
+    /// update. Updates are dropped if the receiver does not keep up. The subscription
+    /// ends when the receiver is dropped.
+    pub fn subscribe_routing_updates(&mut self) -> mpsc::Receiver<RoutingUpdates> {
+        self.routing_updates.subscribe()
+    }
+
+    /// Takes a snapshot of the peers in the routing table.
+    ///
+    /// The snapshot can be persisted and used to seed the routing table of a
+    /// new `Behaviour` via [`Behaviour::restore_routing_table`], e.g. after a
+    /// restart of the local node. Peers pending insertion are not included.
+    pub fn routing_table_snapshot(&mut self) -> RoutingTableSnapshot {
+        let now = snapshot::unix_time_now();
+        let mut entries = Vec::new();
+        for bucket in self.kbuckets.iter() {
+            for entry in bucket.iter() {
+                let peer_id = *entry.node.key.preimage();
+                let last_seen = match entry.status {
+                    NodeStatus::Connected => Some(now),
+                    NodeStatus::Disconnected => self.last_seen.get(&peer_id).copied(),
+                };
+                entries.push(RoutingTableEntry {
+                    peer_id,
+                    addresses: entry.node.value.iter().cloned().collect(),
+                    status: entry.status,
+                    last_seen,
+                });
+            }
+        }
+        RoutingTableSnapshot { entries }
+    }
+
+    /// Seeds the routing table with the peers of a snapshot previously taken via
+    /// [`Behaviour::routing_table_snapshot`].
+    ///
+    /// The peers are added as if by [`Behaviour::add_address`], i.e. they are
+    /// considered disconnected until a connection is established, and peers of
+    /// buckets that are already full are ignored. Returns the number of peers
+    /// that have been added to the routing table.
+    pub fn restore_routing_table(&mut self, snapshot: RoutingTableSnapshot) -> usize {
+        let mut restored = 0;
+        for entry in snapshot.entries {
+            let mut added = false;
+            for address in entry.addresses {
+                if let RoutingUpdate::Success = self.add_address(&entry.peer_id, address) {
+                    added = true;
+                }
+            }
+            if added {
+                restored += 1;
+                if let Some(last_seen) = entry.last_seen {
+                    self.last_seen.insert(entry.peer_id, last_seen);
+                }
+            }
+        }
+        restored
     }
 
     /// Returns an iterator over all non-empty buckets in the routing table.
@@ -718,15 +1875,140 @@ where
     where
         K: Into<kbucket::Key<K>> + Into<Vec<u8>> + Clone,
     {
-        let target: kbucket::Key<K> = key.clone().into();
-        let key: Vec<u8> = key.into();
-        let info = QueryInfo::GetClosestPeers {
-            key,
-            step: ProgressStep::first(),
+        self.get_closest_peers_with_opts(key, QueryOpts::default())
+    }
+
+    /// Like [`Behaviour::get_closest_peers`] but with options for this query only.
+    pub fn get_closest_peers_with_opts<K>(&mut self, key: K, opts: QueryOpts) -> QueryId
+    where
+        K: Into<kbucket::Key<K>> + Into<Vec<u8>> + Clone,
+    {
+        let key: Vec<u8> = key.into();
+        let target = self.hasher.key(key.clone());
+        let info = QueryInfo::GetClosestPeers {
+            key,
+            step: ProgressStep::first(),
+        };
+        let peer_keys: Vec<kbucket::Key<PeerId>> = self.kbuckets.closest_keys(&target).collect();
+        let inner = QueryInner::new(info);
+        let id = self
+            .queries
+            .add_iter_closest(target, peer_keys, inner, QueryPriority::Normal);
+        self.apply_query_opts(id, opts);
+
+        id
+    }
+
+    /// Like [`Behaviour::get_closest_peers`] but fails if the maximum number of
+    /// queries of the user is reached, see [`Config::set_max_user_queries`].
+    pub fn try_get_closest_peers<K>(&mut self, key: K) -> Result<QueryId, QueryLimitReached>
+    where
+        K: Into<kbucket::Key<K>> + Into<Vec<u8>> + Clone,
+    {
+        self.check_user_query_capacity()?;
+        Ok(self.get_closest_peers(key))
+    }
+
+    /// Returns the number of queries of the user that can be started before
+    /// reaching [`Config::set_max_user_queries`], or `None` if there is no limit.
+    pub fn user_query_capacity(&self) -> Option<usize> {
+        let max = self.max_user_queries?;
+        let running = self
+            .queries
+            .iter()
+            .map(|q| q.priority())
+            .chain(self.pending_advertisements.iter().map(|(.., p)| *p))
+            .chain(self.pending_retries.iter().map(|(.., p)| *p))
+            .filter(|p| *p > QueryPriority::Background)
+            .count();
+        Some(max.get().saturating_sub(running))
+    }
+
+    fn check_user_query_capacity(&self) -> Result<(), QueryLimitReached> {
+        match self.user_query_capacity() {
+            Some(0) => Err(QueryLimitReached()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Applies the options of a query that was just started.
+    fn apply_query_opts(&mut self, id: QueryId, opts: QueryOpts) {
+        if let Some(query) = self.queries.get_mut(&id) {
+            query.set_timeout(opts.timeout);
+            query.set_trace(opts.trace);
+            query.inner.retry = opts
+                .retry
+                .or(self.query_retry_policy)
+                .map(|policy| QueryRetry {
+                    policy,
+                    retries: 0,
+                    timeout: opts.timeout,
+                    trace: opts.trace,
+                });
+        }
+    }
+
+    /// Schedules the retry of a query that timed out as per its [`RetryPolicy`],
+    /// returning the query if it is not to be retried.
+    fn retry_query(&mut self, query: Query<QueryInner>) -> Option<Query<QueryInner>> {
+        let retryable = match &query.inner.info {
+            QueryInfo::GetClosestPeers { .. } => true,
+            QueryInfo::GetRecord { found_a_record, .. } => !found_a_record,
+            QueryInfo::GetProviders { providers, .. } => providers.is_empty(),
+            _ => false,
         };
-        let peer_keys: Vec<kbucket::Key<PeerId>> = self.kbuckets.closest_keys(&target).collect();
-        let inner = QueryInner::new(info);
-        self.queries.add_iter_closest(target, peer_keys, inner)
+        let backoff = query
+            .inner
+            .retry
+            .as_ref()
+            .filter(|_| retryable)
+            .and_then(|retry| retry.policy.backoff(retry.retries));
+        let Some(backoff) = backoff else {
+            return Some(query);
+        };
+
+        let id = query.id();
+        let priority = query.priority();
+        let mut inner = query.into_result().inner;
+        inner.pending_rpcs.clear();
+        if let Some(retry) = inner.retry.as_mut() {
+            retry.retries += 1;
+        }
+        tracing::debug!(query=?id, ?backoff, "Query timed out, retrying");
+        self.pending_retries
+            .push((Delay::new(backoff), id, inner, priority));
+        None
+    }
+
+    /// Restarts the queries whose retry backoff elapsed.
+    fn poll_pending_retries(&mut self, cx: &mut Context<'_>) {
+        let mut i = 0;
+        while i < self.pending_retries.len() {
+            if self.pending_retries[i].0.poll_unpin(cx).is_pending() {
+                i += 1;
+                continue;
+            }
+            let (_, id, inner, priority) = self.pending_retries.swap_remove(i);
+            let key = match &inner.info {
+                QueryInfo::GetClosestPeers { key, .. } => key.clone(),
+                QueryInfo::GetRecord { key, .. } | QueryInfo::GetProviders { key, .. } => {
+                    key.to_vec()
+                }
+                _ => unreachable!("Only lookups are retried."),
+            };
+            let (timeout, trace) = inner
+                .retry
+                .as_ref()
+                .map_or((None, false), |r| (r.timeout, r.trace));
+            let target = self.hasher.key(key);
+            let peers = self.kbuckets.closest_keys(&target);
+            self.queries
+                .continue_iter_closest(id, target.clone(), peers, inner, priority);
+            if let Some(query) = self.queries.get_mut(&id) {
+                query.set_timeout(timeout);
+                query.set_trace(trace);
+            }
+        }
     }
 
     /// Returns closest peers to the given key; takes peers from local routing table only.
@@ -741,55 +2023,48 @@ where
     ///
     /// The result of this operation is delivered in a
     /// [`Event::OutboundQueryProgressed{QueryResult::GetRecord}`].
+    ///
+    /// > **Note**: If the record is not looked up immediately by an [`AsyncRecordStore`],
+    /// > a locally stored record is only reported if the lookup completes before
+    /// > the query finishes.
     pub fn get_record(&mut self, key: record::Key) -> QueryId {
-        let record = if let Some(record) = self.store.get(&key) {
-            if record.is_expired(Instant::now()) {
-                self.store.remove(&key);
-                None
-            } else {
-                Some(PeerRecord {
-                    peer: None,
-                    record: record.into_owned(),
-                })
-            }
-        } else {
-            None
-        };
+        self.get_record_with_opts(key, QueryOpts::default())
+    }
 
-        let step = ProgressStep::first();
+    /// Like [`Behaviour::get_record`] but fails if the maximum number of
+    /// queries of the user is reached, see [`Config::set_max_user_queries`].
+    pub fn try_get_record(&mut self, key: record::Key) -> Result<QueryId, QueryLimitReached> {
+        self.check_user_query_capacity()?;
+        Ok(self.get_record(key))
+    }
 
-        let target = kbucket::Key::new(key.clone());
-        let info = if record.is_some() {
-            QueryInfo::GetRecord {
-                key,
-                step: step.next(),
-                found_a_record: true,
-                cache_candidates: BTreeMap::new(),
-            }
-        } else {
-            QueryInfo::GetRecord {
-                key,
-                step: step.clone(),
-                found_a_record: false,
-                cache_candidates: BTreeMap::new(),
-            }
+    /// Like [`Behaviour::get_record`] but with options for this query only.
+    pub fn get_record_with_opts(&mut self, key: record::Key, opts: QueryOpts) -> QueryId {
+        let target = self.hasher.key(key.clone());
+        let info = QueryInfo::GetRecord {
+            key: key.clone(),
+            step: ProgressStep::first(),
+            found_a_record: false,
+            best_record: None,
+            best_record_peers: Vec::new(),
+            cache_candidates: BTreeMap::new(),
+            agreement: opts.agreement,
+            agreeing_peers: HashMap::new(),
+            agreed_record: None,
         };
         let peers = self.kbuckets.closest_keys(&target);
         let inner = QueryInner::new(info);
-        let id = self.queries.add_iter_closest(target.clone(), peers, inner);
-
-        // No queries were actually done for the results yet.
-        let stats = QueryStats::empty();
-
-        if let Some(record) = record {
-            self.queued_events
-                .push_back(ToSwarm::GenerateEvent(Event::OutboundQueryProgressed {
-                    id,
-                    result: QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(record))),
-                    step,
-                    stats,
-                }));
-        }
+        let id = self
+            .queries
+            .add_iter_closest(target.clone(), peers, inner, QueryPriority::Normal);
+        self.apply_query_opts(id, opts);
+
+        // Lookup the record locally.
+        let op = self.store.get_record(&key);
+        self.run_store_op(op, None, move |record| StoreOutcome::GetRecord {
+            query_id: id,
+            record,
+        });
 
         id
     }
@@ -812,19 +2087,24 @@ where
     /// does not update the record's expiration in local storage, thus a given record
     /// with an explicit expiration will always expire at that instant and until then
     /// is subject to regular (re-)replication and (re-)publication.
+    ///
+    /// > **Note**: If the record is not stored immediately by an [`AsyncRecordStore`],
+    /// > the record is published regardless and a failure to store it locally
+    /// > is only logged.
     pub fn put_record(
         &mut self,
         mut record: Record,
         quorum: Quorum,
     ) -> Result<QueryId, store::Error> {
         record.publisher = Some(*self.kbuckets.local_key().preimage());
-        self.store.put(record.clone())?;
+        let op = self.store.put_record(record.clone());
+        self.run_local_store_op(op, record.key.clone())?;
+        let record_ttl = self.record_ttl(&record.key);
         record.expires = record
             .expires
-            .or_else(|| self.record_ttl.map(|ttl| Instant::now() + ttl));
+            .or_else(|| record_ttl.map(|ttl| Instant::now() + ttl));
         let quorum = quorum.eval(self.queries.config().replication_factor);
-        let target = kbucket::Key::new(record.key.clone());
-        let peers = self.kbuckets.closest_keys(&target);
+        let key = record.key.clone();
         let context = PutRecordContext::Publish;
         let info = QueryInfo::PutRecord {
             context,
@@ -833,7 +2113,7 @@ where
             phase: PutRecordPhase::GetClosestPeers,
         };
         let inner = QueryInner::new(info);
-        Ok(self.queries.add_iter_closest(target.clone(), peers, inner))
+        Ok(self.start_advertisement(key, inner, QueryPriority::Normal))
     }
 
     /// Stores a record at specific peers, without storing it locally.
@@ -865,9 +2145,10 @@ where
             // introducing a new kind of error.
             NonZeroUsize::new(1).expect("1 > 0")
         };
+        let record_ttl = self.record_ttl(&record.key);
         record.expires = record
             .expires
-            .or_else(|| self.record_ttl.map(|ttl| Instant::now() + ttl));
+            .or_else(|| record_ttl.map(|ttl| Instant::now() + ttl));
         let context = PutRecordContext::Custom;
         let info = QueryInfo::PutRecord {
             context,
@@ -875,11 +2156,48 @@ where
             quorum,
             phase: PutRecordPhase::PutRecord {
                 success: Vec::new(),
+                failed: Vec::new(),
+                get_closest_peers_stats: QueryStats::empty(),
+                step: ProgressStep::first(),
+            },
+        };
+        let inner = QueryInner::new(info);
+        self.queries.add_fixed(peers, inner, QueryPriority::Normal)
+    }
+
+    /// Writes a record found by a lookup back to the given peers,
+    /// according to the [`Caching::Automatic`] configuration.
+    fn cache_record(&mut self, mut record: Record, peers: Vec<PeerId>) {
+        let Caching::Automatic {
+            max_ttl,
+            probability,
+            ..
+        } = self.caching
+        else {
+            return;
+        };
+        let Some(quorum) = NonZeroUsize::new(peers.len()) else {
+            return;
+        };
+        if !rand::thread_rng().gen_bool(probability.clamp(0.0, 1.0)) {
+            return;
+        }
+        let max_expires = Instant::now() + max_ttl;
+        record.expires = Some(record.expires.map_or(max_expires, |e| e.min(max_expires)));
+        let info = QueryInfo::PutRecord {
+            context: PutRecordContext::Cache,
+            record,
+            quorum,
+            phase: PutRecordPhase::PutRecord {
+                success: Vec::new(),
+                failed: Vec::new(),
                 get_closest_peers_stats: QueryStats::empty(),
+                step: ProgressStep::first(),
             },
         };
         let inner = QueryInner::new(info);
-        self.queries.add_fixed(peers, inner)
+        self.queries
+            .add_fixed(peers, inner, QueryPriority::Background);
     }
 
     /// Removes the record with the given key from _local_ storage,
@@ -892,11 +2210,8 @@ where
     /// the record will no longer be periodically re-published, allowing the
     /// record to eventually expire throughout the DHT.
     pub fn remove_record(&mut self, key: &record::Key) {
-        if let Some(r) = self.store.get(key) {
-            if r.publisher.as_ref() == Some(self.kbuckets.local_key().preimage()) {
-                self.store.remove(key)
-            }
-        }
+        let op = self.store.get_record(key);
+        self.run_store_op(op, None, |record| StoreOutcome::RemoveRecord { record });
     }
 
     /// Gets a mutable reference to the record store.
@@ -943,8 +2258,146 @@ where
         } else {
             self.bootstrap_status.on_started();
             let inner = QueryInner::new(info);
-            Ok(self.queries.add_iter_closest(local_key, peers, inner))
+            Ok(self
+                .queries
+                .add_iter_closest(local_key, peers, inner, QueryPriority::Background))
+        }
+    }
+
+    /// Refreshes the bucket with the given index by performing a lookup for
+    /// a random key in its range.
+    ///
+    /// The bucket with index `i` holds the peers at a distance in the range
+    /// `[2^i, 2^(i+1))` from the local key. As with the bucket refreshes of
+    /// [`Behaviour::bootstrap`], finding a key in the range is a "best effort",
+    /// which is unlikely to succeed for the buckets closest to the local key.
+    ///
+    /// The result is reported via [`Event::OutboundQueryProgressed{QueryResult::Refresh}`].
+    ///
+    /// Returns `Err` if no peers are known to start the lookup from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is not smaller than 256.
+    pub fn refresh_bucket(&mut self, bucket: u32) -> Result<QueryId, NoKnownPeers> {
+        assert!(
+            (bucket as usize) < kbucket::NUM_BUCKETS,
+            "Invalid bucket index."
+        );
+        self.start_refresh(vec![bucket], QueryPriority::Normal)
+    }
+
+    /// Refreshes all buckets from the first non-empty one onwards, i.e. all
+    /// buckets that may hold peers, by performing a lookup for a random key in
+    /// the range of each, one after the other.
+    ///
+    /// Unlike [`Behaviour::bootstrap`], this does not look up the local key first,
+    /// allowing to refresh the routing table after connectivity changes without a full
+    /// bootstrap. The results are reported via
+    /// [`Event::OutboundQueryProgressed{QueryResult::Refresh}`], one per lookup.
+    ///
+    /// Returns `Err` if no peers are known to start the lookups from.
+    pub fn refresh_all(&mut self) -> Result<QueryId, NoKnownPeers> {
+        let first = self
+            .kbuckets
+            .iter()
+            .position(|b| !b.is_empty())
+            .ok_or(NoKnownPeers())?;
+        let buckets = (first as u32..kbucket::NUM_BUCKETS as u32).collect();
+        self.start_refresh(buckets, QueryPriority::Normal)
+    }
+
+    /// Starts a query refreshing the given non-empty list of buckets.
+    fn start_refresh(
+        &mut self,
+        buckets: Vec<u32>,
+        priority: QueryPriority,
+    ) -> Result<QueryId, NoKnownPeers> {
+        let mut remaining = buckets.into_iter();
+        let bucket = remaining.next().expect("at least one bucket");
+        let target = bucket_refresh_target(&self.hasher, self.kbuckets.local_key(), bucket);
+        let peers = self.kbuckets.closest_keys(&target).collect::<Vec<_>>();
+        if peers.is_empty() {
+            return Err(NoKnownPeers());
+        }
+        self.bucket_refreshes.on_lookup(bucket);
+        let info = QueryInfo::Refresh {
+            bucket,
+            target: *target.preimage(),
+            remaining,
+            step: ProgressStep::first(),
+        };
+        let inner = QueryInner::new(info);
+        Ok(self
+            .queries
+            .add_iter_closest(target, peers, inner, priority))
+    }
+
+    /// Continues a bucket refresh with the lookup for the next remaining bucket,
+    /// if any, returning the step of the event reporting the end of the current lookup.
+    fn continue_refresh(
+        &mut self,
+        query_id: QueryId,
+        priority: QueryPriority,
+        mut remaining: vec::IntoIter<u32>,
+        mut step: ProgressStep,
+    ) -> ProgressStep {
+        if let Some(bucket) = remaining.next() {
+            let target = bucket_refresh_target(&self.hasher, self.kbuckets.local_key(), bucket);
+            self.bucket_refreshes.on_lookup(bucket);
+            let info = QueryInfo::Refresh {
+                bucket,
+                target: *target.preimage(),
+                remaining,
+                step: step.next(),
+            };
+            let peers = self.kbuckets.closest_keys(&target);
+            let inner = QueryInner::new(info);
+            self.queries
+                .continue_iter_closest(query_id, target.clone(), peers, inner, priority);
+        } else {
+            step.last = true;
+        }
+        step
+    }
+
+    /// Crawls the DHT by performing lookups for the given number of random
+    /// targets, spread evenly across the keyspace.
+    ///
+    /// Every peer learned of during the crawl is reported once, via
+    /// [`CrawlOk::PeerDiscovered`] together with its addresses, and every peer
+    /// answering a lookup of the crawl is reported once, via [`CrawlOk::PeerCrawled`]
+    /// together with the protocols it supports. The more lookups, the larger the
+    /// share of the DHT that is covered, at the cost of more requests. This allows to
+    /// build network measurement tools on top of the regular iterative lookups.
+    ///
+    /// The agent version of a peer is not known to Kademlia. Since crawled peers are
+    /// connected to, it can be obtained by combining the crawl with `libp2p-identify`,
+    /// which is also the source of the reported protocols.
+    ///
+    /// The results are reported via [`Event::OutboundQueryProgressed{QueryResult::Crawl}`],
+    /// the last step of which is the [`CrawlOk::LookupFinished`] or [`CrawlError::Timeout`]
+    /// of the last lookup.
+    ///
+    /// Returns `Err` if no peers are known to start the crawl from.
+    pub fn crawl(&mut self, num_lookups: NonZeroUsize) -> Result<QueryId, NoKnownPeers> {
+        let mut remaining = crawl_targets(&self.hasher, num_lookups).into_iter();
+        let target = remaining.next().expect("`num_lookups` is non-zero");
+        let peers = self.kbuckets.closest_keys(&target).collect::<Vec<_>>();
+        if peers.is_empty() {
+            return Err(NoKnownPeers());
         }
+        let info = QueryInfo::Crawl {
+            target: *target.preimage(),
+            remaining,
+            discovered: HashSet::new(),
+            crawled: HashSet::new(),
+            step: ProgressStep::first(),
+        };
+        let inner = QueryInner::new(info);
+        Ok(self
+            .queries
+            .add_iter_closest(target, peers, inner, QueryPriority::Background))
     }
 
     /// Establishes the local node as a provider of a value for the given key.
@@ -969,90 +2422,243 @@ where
     ///
     /// The results of the (repeated) provider announcements sent by this node are
     /// reported via [`Event::OutboundQueryProgressed{QueryResult::StartProviding}`].
+    ///
+    /// > **Note**: If the provider record is not stored immediately by an
+    /// > [`AsyncRecordStore`], the local node is announced as a provider regardless
+    /// > and a failure to store the record locally is only logged.
     pub fn start_providing(&mut self, key: record::Key) -> Result<QueryId, store::Error> {
+        self.start_providing_until(key, None)
+    }
+
+    /// Establishes the local node as a provider of a value for the given key
+    /// until the given TTL elapses.
+    ///
+    /// The provider record stored locally expires after the TTL, after which it
+    /// is no longer re-published. Provider announcements carry the remaining TTL,
+    /// such that remote nodes store the provider record for the remaining TTL, up
+    /// to their [`Config::set_provider_record_ttl`]. This allows to publish
+    /// short-lived provider announcements without changing the global provider TTL.
+    ///
+    /// See [`Behaviour::start_providing`] for details.
+    pub fn start_providing_with_ttl(
+        &mut self,
+        key: record::Key,
+        ttl: Duration,
+    ) -> Result<QueryId, store::Error> {
+        self.start_providing_until(key, Some(Instant::now() + ttl))
+    }
+
+    fn start_providing_until(
+        &mut self,
+        key: record::Key,
+        expires: Option<Instant>,
+    ) -> Result<QueryId, store::Error> {
+        self.add_provider_job.unschedule(&key);
         // Note: We store our own provider records locally without local addresses
         // to avoid redundant storage and outdated addresses. Instead these are
         // acquired on demand when returning a `ProviderRecord` for the local node.
         let local_addrs = Vec::new();
-        let record = ProviderRecord::new(
+        let mut record = ProviderRecord::new(
             key.clone(),
             *self.kbuckets.local_key().preimage(),
             local_addrs,
         );
-        self.store.add_provider(record)?;
-        let target = kbucket::Key::new(key.clone());
-        let peers = self.kbuckets.closest_keys(&target);
+        record.expires = expires;
+        match expires {
+            Some(expires) => self.local_provider_expirations.insert(key.clone(), expires),
+            None => self.local_provider_expirations.remove(&key),
+        };
+        let op = self.store.add_provider_record(record);
+        self.run_local_store_op(op, key.clone())?;
         let context = AddProviderContext::Publish;
         let info = QueryInfo::AddProvider {
             context,
-            key,
+            key: key.clone(),
             phase: AddProviderPhase::GetClosestPeers,
         };
         let inner = QueryInner::new(info);
-        let id = self.queries.add_iter_closest(target.clone(), peers, inner);
+        Ok(self.start_advertisement(key, inner, QueryPriority::Normal))
+    }
+
+    /// Establishes the local node as a provider of a value for the given key,
+    /// re-publishing the provider record on the given interval.
+    ///
+    /// The interval takes the place of the configured
+    /// [`Config::set_provider_publication_interval`] for this key, such that
+    /// e.g. keys whose providers change frequently can be re-published more
+    /// often than others. It applies until [`Behaviour::stop_providing`] or
+    /// [`Behaviour::start_providing`] is called for the key.
+    ///
+    /// See [`Behaviour::start_providing`] for details.
+    pub fn start_providing_with_interval(
+        &mut self,
+        key: record::Key,
+        interval: Duration,
+    ) -> Result<QueryId, store::Error> {
+        let id = self.start_providing(key.clone())?;
+        self.add_provider_job
+            .schedule(key, interval, Instant::now());
         Ok(id)
     }
 
+    /// Announces the local node as a provider for the given key to the given peers.
+    ///
+    /// In contrast to [`Behaviour::start_providing`], the provider records are sent
+    /// to the given peers directly, without looking up the closest peers to the key.
+    ///
+    /// The result of this operation is reported via
+    /// [`Event::OutboundQueryProgressed{QueryResult::StartProviding}`].
+    ///
+    /// > **Note**: This is not a regular Kademlia DHT operation. It can be used
+    /// > to e.g. announce provider records to a known set of infrastructure nodes.
+    /// >
+    /// > In particular, the provider record is not stored locally and the
+    /// > announcement is not republished, which requires [`Behaviour::start_providing`].
+    pub fn start_providing_to<I>(&mut self, key: record::Key, peers: I) -> QueryId
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        let info = QueryInfo::AddProvider {
+            context: AddProviderContext::Publish,
+            phase: AddProviderPhase::AddProvider {
+                provider_id: self.local_peer_id,
+                external_addresses: self.external_addresses.iter().cloned().collect(),
+                expires: self.local_provider_expirations.get(&key).copied(),
+                get_closest_peers_stats: QueryStats::empty(),
+            },
+            key,
+        };
+        let inner = QueryInner::new(info);
+        self.queries.add_fixed(peers, inner, QueryPriority::Normal)
+    }
+
     /// Stops the local node from announcing that it is a provider for the given key.
     ///
     /// This is a local operation. The local node will still be considered as a
     /// provider for the key by other nodes until these provider records expire.
     pub fn stop_providing(&mut self, key: &record::Key) {
-        self.store
-            .remove_provider(key, self.kbuckets.local_key().preimage());
+        self.add_provider_job.unschedule(key);
+        self.local_provider_expirations.remove(key);
+        self.pending_advertisements.retain(|(_, k, inner, _)| {
+            k != key || !matches!(inner.info, QueryInfo::AddProvider { .. })
+        });
+        let op = self
+            .store
+            .remove_provider_record(key, self.kbuckets.local_key().preimage());
+        self.run_store_op(op, None, |()| StoreOutcome::Removed);
     }
 
     /// Performs a lookup for providers of a value to the given key.
     ///
     /// The result of this operation is delivered in a
     /// reported via [`Event::OutboundQueryProgressed{QueryResult::GetProviders}`].
+    ///
+    /// > **Note**: If the providers are not looked up immediately by an
+    /// > [`AsyncRecordStore`], locally stored providers are only reported if the
+    /// > lookup completes before the query finishes.
     pub fn get_providers(&mut self, key: record::Key) -> QueryId {
-        let providers: HashSet<_> = self
-            .store
-            .providers(&key)
-            .into_iter()
-            .filter(|p| !p.is_expired(Instant::now()))
-            .map(|p| p.provider)
-            .collect();
+        self.start_get_providers(key, None, NonZeroUsize::MIN, QueryOpts::default())
+    }
+
+    /// Like [`Behaviour::get_providers`] but fails if the maximum number of
+    /// queries of the user is reached, see [`Config::set_max_user_queries`].
+    pub fn try_get_providers(&mut self, key: record::Key) -> Result<QueryId, QueryLimitReached> {
+        self.check_user_query_capacity()?;
+        Ok(self.get_providers(key))
+    }
+
+    /// Like [`Behaviour::get_providers`] but with options for this query only.
+    pub fn get_providers_with_opts(&mut self, key: record::Key, opts: QueryOpts) -> QueryId {
+        self.start_get_providers(key, None, NonZeroUsize::MIN, opts)
+    }
+
+    /// Performs a lookup for providers of a value to the given key, finishing the
+    /// query as soon as `quorum` distinct providers have been found.
+    ///
+    /// Found providers are reported like for [`Behaviour::get_providers`]. If the
+    /// query finishes without reaching the quorum, the last result is a
+    /// [`GetProvidersError::QuorumFailed`] with all providers found.
+    pub fn get_providers_with_quorum(&mut self, key: record::Key, quorum: NonZeroUsize) -> QueryId {
+        self.start_get_providers(key, Some(quorum), NonZeroUsize::MIN, QueryOpts::default())
+    }
 
-        let step = ProgressStep::first();
+    /// Like [`Behaviour::get_providers_with_quorum`], but a provider only counts
+    /// towards the `quorum` once it has been returned by `confirmations` distinct
+    /// peers, guarding against peers returning junk provider lists.
+    ///
+    /// Providers stored locally count as confirmed by the local node. The number
+    /// of confirmations of the found providers is reported with every
+    /// [`GetProvidersOk::FoundProviders`] and with [`GetProvidersError::QuorumFailed`].
+    pub fn get_providers_with_confirmed_quorum(
+        &mut self,
+        key: record::Key,
+        quorum: NonZeroUsize,
+        confirmations: NonZeroUsize,
+    ) -> QueryId {
+        self.start_get_providers(key, Some(quorum), confirmations, QueryOpts::default())
+    }
 
+    fn start_get_providers(
+        &mut self,
+        key: record::Key,
+        quorum: Option<NonZeroUsize>,
+        confirmations: NonZeroUsize,
+        opts: QueryOpts,
+    ) -> QueryId {
         let info = QueryInfo::GetProviders {
             key: key.clone(),
-            providers_found: providers.len(),
-            step: if providers.is_empty() {
-                step.clone()
-            } else {
-                step.next()
-            },
+            providers: HashMap::new(),
+            quorum,
+            confirmations,
+            step: ProgressStep::first(),
         };
 
-        let target = kbucket::Key::new(key.clone());
+        let target = self.hasher.key(key.clone());
         let peers = self.kbuckets.closest_keys(&target);
         let inner = QueryInner::new(info);
-        let id = self.queries.add_iter_closest(target.clone(), peers, inner);
-
-        // No queries were actually done for the results yet.
-        let stats = QueryStats::empty();
+        let id = self
+            .queries
+            .add_iter_closest(target.clone(), peers, inner, QueryPriority::Normal);
+        self.apply_query_opts(id, opts);
+
+        // Lookup the providers locally.
+        let op = self.store.provider_records(&key);
+        self.run_store_op(op, None, move |providers| StoreOutcome::GetProviders {
+            query_id: id,
+            providers,
+        });
 
-        if !providers.is_empty() {
-            self.queued_events
-                .push_back(ToSwarm::GenerateEvent(Event::OutboundQueryProgressed {
-                    id,
-                    result: QueryResult::GetProviders(Ok(GetProvidersOk::FoundProviders {
-                        key,
-                        providers,
-                    })),
-                    step,
-                    stats,
-                }));
-        }
         id
     }
 
+    /// Requests a summary of the keys the given peer provides within a range of the keyspace.
+    ///
+    /// The returned [`ProviderSummary`] is a bloom filter that can be used to skip
+    /// `GET_PROVIDERS` requests for keys that the peer definitely does not provide,
+    /// e.g. when reconciling the provider records of many keys with a peer. Large
+    /// ranges can be split into smaller ones via [`KeyspaceRange::split`] to keep
+    /// the false positive rate low.
+    ///
+    /// > **Note**: This is an extension specific to rust-libp2p. The peer must have
+    /// > enabled [`Config::set_provider_summaries`].
+    ///
+    /// The result of this operation is delivered in a
+    /// [`Event::OutboundQueryProgressed{QueryResult::GetProviderSummary}`].
+    pub fn get_provider_summary(&mut self, peer: PeerId, range: KeyspaceRange) -> QueryId {
+        let info = QueryInfo::GetProviderSummary {
+            peer,
+            range,
+            summary: None,
+        };
+        let inner = QueryInner::new(info);
+        self.queries
+            .add_fixed(std::iter::once(peer), inner, QueryPriority::Normal)
+    }
+
     /// Set the [`Mode`] in which we should operate.
     ///
-    /// By default, we are in [`Mode::Client`] and will swap into [`Mode::Server`] as soon as we have a confirmed, external address via [`FromSwarm::ExternalAddrConfirmed`].
+    /// By default, we are in [`Mode::Client`] and will swap into [`Mode::Server`] as soon as we have a confirmed, external address via [`FromSwarm::ExternalAddrConfirmed`],
+    /// or as soon as we are known to be publicly reachable, see [`Behaviour::set_reachability`].
     ///
     /// Setting a mode via this function disables this automatic behaviour and unconditionally operates in the specified mode.
     /// To reactivate the automatic configuration, pass [`None`] instead.
@@ -1061,11 +2667,12 @@ where
             Some(mode) => {
                 self.mode = mode;
                 self.auto_mode = false;
+                self.deferred_mode_switch = None;
                 self.reconfigure_mode();
             }
             None => {
                 self.auto_mode = true;
-                self.determine_mode_from_external_addresses();
+                self.determine_mode();
             }
         }
 
@@ -1074,6 +2681,94 @@ where
         }
     }
 
+    /// Reports the reachability of the local node, e.g. as determined by AutoNAT, together
+    /// with the confidence in it.
+    ///
+    /// Unless the mode is set via [`Behaviour::set_mode`], a reachability with a confidence
+    /// of at least [`Config::set_reachability_confidence_threshold`] takes precedence over
+    /// the confirmed external addresses in determining the [`Mode`]: the node operates in
+    /// [`Mode::Server`] if it is publicly reachable and in [`Mode::Client`] otherwise.
+    /// Reporting [`Reachability::Unknown`] restores the mode based on external addresses.
+    pub fn set_reachability(&mut self, reachability: Reachability, confidence: usize) {
+        self.reachability = (reachability, confidence);
+
+        if self.auto_mode {
+            self.determine_mode();
+        }
+
+        if let Some(waker) = self.no_events_waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Pauses the background jobs re-publishing and re-replicating records and provider
+    /// records, e.g. to quiesce DHT traffic during a large data transfer.
+    ///
+    /// Runs of the jobs that become due while paused are deferred until the jobs
+    /// are resumed via [`Behaviour::resume_background_jobs`]. Queries started by the
+    /// jobs before are not affected.
+    pub fn pause_background_jobs(&mut self) {
+        self.background_jobs_paused = true;
+    }
+
+    /// Resumes the background jobs paused via [`Behaviour::pause_background_jobs`].
+    pub fn resume_background_jobs(&mut self) {
+        self.background_jobs_paused = false;
+
+        if let Some(waker) = self.no_events_waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Returns whether the background jobs are paused, see [`Behaviour::pause_background_jobs`].
+    pub fn background_jobs_paused(&self) -> bool {
+        self.background_jobs_paused
+    }
+
+    /// Sets the interval at which stored records are re-replicated,
+    /// see [`Config::set_replication_interval`].
+    ///
+    /// The next run of the job is due after the new interval, starting now.
+    pub fn set_replication_interval(&mut self, interval: Option<Duration>) {
+        self.record_replication_interval = interval;
+        self.reset_put_record_job();
+    }
+
+    /// Sets the interval at which stored records are re-published,
+    /// see [`Config::set_publication_interval`].
+    ///
+    /// The next run of the job is due after the new interval, starting now.
+    pub fn set_publication_interval(&mut self, interval: Option<Duration>) {
+        self.record_publication_interval = interval;
+        self.reset_put_record_job();
+    }
+
+    /// Sets the interval at which provider records of the local node are re-published,
+    /// see [`Config::set_provider_publication_interval`].
+    ///
+    /// Keys re-published on an interval of their own, see
+    /// [`Behaviour::start_providing_with_interval`], are not affected.
+    pub fn set_provider_publication_interval(&mut self, interval: Option<Duration>) {
+        self.add_provider_job.set_interval(interval);
+
+        if let Some(waker) = self.no_events_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn reset_put_record_job(&mut self) {
+        self.put_record_job = new_put_record_job(
+            self.local_peer_id,
+            self.record_replication_interval,
+            self.record_publication_interval,
+            self.record_ttl,
+        );
+
+        if let Some(waker) = self.no_events_waker.take() {
+            waker.wake();
+        }
+    }
+
     fn reconfigure_mode(&mut self) {
         if self.connections.is_empty() {
             return;
@@ -1101,50 +2796,73 @@ where
             );
     }
 
-    fn determine_mode_from_external_addresses(&mut self) {
-        let old_mode = self.mode;
+    /// Determines the [`Mode`] from the reachability of the node, if known with enough
+    /// confidence, and from the confirmed external addresses otherwise.
+    fn determine_mode(&mut self) {
+        let (new_mode, reason) = match self.reachability {
+            (Reachability::Public, confidence)
+                if confidence >= self.reachability_confidence_threshold =>
+            {
+                (Mode::Server, ModeChangeReason::Reachability)
+            }
+            (Reachability::Private, confidence)
+                if confidence >= self.reachability_confidence_threshold =>
+            {
+                (Mode::Client, ModeChangeReason::Reachability)
+            }
+            _ if self.external_addresses.as_slice().is_empty() => {
+                (Mode::Client, ModeChangeReason::ExternalAddresses)
+            }
+            _ => (Mode::Server, ModeChangeReason::ExternalAddresses),
+        };
 
-        self.mode = match (self.external_addresses.as_slice(), self.mode) {
-            ([], Mode::Server) => {
-                tracing::debug!("Switching to client-mode because we no longer have any confirmed external addresses");
+        if new_mode == self.mode {
+            self.deferred_mode_switch = None;
+            return;
+        }
 
-                Mode::Client
+        if let (Some(interval), Some(last)) = (self.mode_switch_interval, self.last_mode_switch) {
+            let elapsed = last.elapsed();
+            if elapsed < interval {
+                if self.deferred_mode_switch.is_none() {
+                    tracing::debug!(
+                        "Deferring switch to {new_mode}-mode by {:?} to avoid flapping",
+                        interval - elapsed
+                    );
+                    self.deferred_mode_switch = Some(Delay::new(interval - elapsed));
+                }
+                return;
             }
-            ([], Mode::Client) => {
-                // Previously client-mode, now also client-mode because no external addresses.
+        }
 
-                Mode::Client
+        match reason {
+            ModeChangeReason::Reachability => {
+                tracing::debug!(
+                    "Switching to {new_mode}-mode because of the reported reachability"
+                );
             }
-            (confirmed_external_addresses, Mode::Client) => {
+            ModeChangeReason::ExternalAddresses if new_mode == Mode::Server => {
                 if tracing::enabled!(Level::DEBUG) {
                     let confirmed_external_addresses =
-                        to_comma_separated_list(confirmed_external_addresses);
+                        to_comma_separated_list(self.external_addresses.as_slice());
 
                     tracing::debug!("Switching to server-mode assuming that one of [{confirmed_external_addresses}] is externally reachable");
                 }
-
-                Mode::Server
             }
-            (confirmed_external_addresses, Mode::Server) => {
-                debug_assert!(
-                    !confirmed_external_addresses.is_empty(),
-                    "Previous match arm handled empty list"
-                );
-
-                // Previously, server-mode, now also server-mode because > 1 external address. Don't log anything to avoid spam.
-
-                Mode::Server
+            ModeChangeReason::ExternalAddresses => {
+                tracing::debug!("Switching to client-mode because we no longer have any confirmed external addresses");
             }
-        };
+        }
 
+        self.mode = new_mode;
+        self.last_mode_switch = Some(Instant::now());
+        self.deferred_mode_switch = None;
         self.reconfigure_mode();
-
-        if old_mode != self.mode {
-            self.queued_events
-                .push_back(ToSwarm::GenerateEvent(Event::ModeChanged {
-                    new_mode: self.mode,
-                }));
-        }
+        self.queued_events
+            .push_back(ToSwarm::GenerateEvent(Event::ModeChanged {
+                new_mode,
+                reason,
+            }));
     }
 
     /// Processes discovered peers from a successful request in an iterative `Query`.
@@ -1154,6 +2872,7 @@ where
     {
         let local_id = self.kbuckets.local_key().preimage();
         let others_iter = peers.filter(|p| &p.node_id != local_id);
+        let address_filter = &self.address_filter;
         if let Some(query) = self.queries.get_mut(query_id) {
             tracing::trace!(peer=%source, query=?query_id, "Request to peer in query succeeded");
             for peer in others_iter.clone() {
@@ -1163,13 +2882,109 @@ where
                     query=?query_id,
                     "Peer reported by source in query"
                 );
-                let addrs = peer.multiaddrs.iter().cloned().collect();
+                let addrs = peer
+                    .multiaddrs
+                    .iter()
+                    .filter(|a| {
+                        address_filter.accepts(
+                            &peer.node_id,
+                            a,
+                            AddressSource::QueryResponse { source: *source },
+                        )
+                    })
+                    .cloned()
+                    .collect();
                 query.inner.addresses.insert(peer.node_id, addrs);
             }
             query.on_success(source, others_iter.cloned().map(|kp| kp.node_id))
         }
     }
 
+    /// Reports the peers newly discovered and crawled through a response
+    /// of `source` to a lookup, if the query is a crawl.
+    fn crawled(
+        &mut self,
+        query_id: &QueryId,
+        source: PeerId,
+        connection: ConnectionId,
+        closer_peers: &[KadPeer],
+    ) {
+        let local_id = *self.kbuckets.local_key().preimage();
+        let Some(query) = self.queries.get_mut(query_id) else {
+            return;
+        };
+        let stats = query.stats().clone();
+        let QueryInfo::Crawl {
+            discovered,
+            crawled,
+            step,
+            ..
+        } = &mut query.inner.info
+        else {
+            return;
+        };
+
+        let mut results = Vec::new();
+        if crawled.insert(source) {
+            results.push(CrawlOk::PeerCrawled {
+                peer: source,
+                protocols: self
+                    .remote_protocols
+                    .get(&connection)
+                    .cloned()
+                    .unwrap_or_default(),
+            });
+        }
+        for peer in closer_peers {
+            if peer.node_id != local_id && discovered.insert(peer.node_id) {
+                results.push(CrawlOk::PeerDiscovered {
+                    peer: peer.node_id,
+                    addresses: peer.multiaddrs.clone(),
+                });
+            }
+        }
+
+        for result in results {
+            self.queued_events
+                .push_back(ToSwarm::GenerateEvent(Event::OutboundQueryProgressed {
+                    id: *query_id,
+                    result: QueryResult::Crawl(Ok(result)),
+                    step: step.clone(),
+                    stats: stats.clone(),
+                }));
+            *step = step.next();
+        }
+    }
+
+    /// Continues a crawl with the lookup for the next remaining target, if any,
+    /// returning the step of the event reporting the end of the current lookup.
+    fn continue_crawl(
+        &mut self,
+        query_id: QueryId,
+        priority: QueryPriority,
+        mut remaining: vec::IntoIter<kbucket::Key<PeerId>>,
+        discovered: HashSet<PeerId>,
+        crawled: HashSet<PeerId>,
+        mut step: ProgressStep,
+    ) -> ProgressStep {
+        if let Some(target) = remaining.next() {
+            let info = QueryInfo::Crawl {
+                target: *target.preimage(),
+                remaining,
+                discovered,
+                crawled,
+                step: step.next(),
+            };
+            let peers = self.kbuckets.closest_keys(&target);
+            let inner = QueryInner::new(info);
+            self.queries
+                .continue_iter_closest(query_id, target.clone(), peers, inner, priority);
+        } else {
+            step.last = true;
+        }
+        step
+    }
+
     /// Finds the closest peers to a `target` in the context of a request by
     /// the `source` peer, such that the `source` peer is never included in the
     /// result.
@@ -1178,26 +2993,138 @@ where
         target: &kbucket::Key<T>,
         source: &PeerId,
     ) -> Vec<KadPeer> {
+        let num_peers = self.num_response_peers();
+        let relayed_addresses = self.relayed_addresses;
+        let signed_records = &self.signed_records;
         self.kbuckets
             .closest(target)
             .filter(|e| e.node.key.preimage() != source)
-            .take(self.queries.config().replication_factor.get())
+            .take(num_peers)
             .map(KadPeer::from)
+            .map(|mut peer| {
+                let has_direct = peer.multiaddrs.iter().any(|a| !is_relayed(a));
+                match relayed_addresses {
+                    RelayedAddresses::Include => {}
+                    RelayedAddresses::Fallback if !has_direct => {}
+                    RelayedAddresses::Exclude | RelayedAddresses::Fallback => {
+                        peer.multiaddrs.retain(|a| !is_relayed(a))
+                    }
+                }
+                signed_records.attach(&mut peer);
+                peer
+            })
             .collect()
     }
 
-    /// Collects all peers who are known to be providers of the value for a given `Multihash`.
-    fn provider_peers(&mut self, key: &record::Key, source: &PeerId) -> Vec<KadPeer> {
+    /// The maximum number of closer peers or providers in a response to a remote.
+    fn num_response_peers(&self) -> usize {
+        let replication_factor = self.queries.config().replication_factor;
+        self.max_response_peers
+            .map_or(replication_factor, |max| max.min(replication_factor))
+            .get()
+    }
+
+    /// Drops peers from a response to a remote until it fits the configured
+    /// maximum response size, see [`Config::set_max_response_size`].
+    ///
+    /// `reserved` is the size of the remainder of the response, e.g. a record.
+    fn truncate_response(
+        &self,
+        reserved: usize,
+        closer_peers: &mut Vec<KadPeer>,
+        provider_peers: &mut Vec<KadPeer>,
+    ) {
+        let Some(max_size) = self.max_response_size else {
+            return;
+        };
+
+        let mut size = reserved
+            + closer_peers
+                .iter()
+                .chain(provider_peers.iter())
+                .map(KadPeer::encoded_len)
+                .sum::<usize>();
+        while size > max_size && closer_peers.len() + provider_peers.len() > 1 {
+            let peer = if closer_peers.len() > 1 || provider_peers.is_empty() {
+                closer_peers.pop()
+            } else if provider_peers.len() > 1 {
+                provider_peers.pop()
+            } else {
+                closer_peers.pop()
+            };
+            size -= peer.as_ref().map_or(0, KadPeer::encoded_len);
+        }
+    }
+
+    /// Builds a page of the response to a paginated `GET_PROVIDERS` request of a remote,
+    /// starting at the provider with index `offset`.
+    ///
+    /// The closer peers are only part of the first page. Providers are added to the page
+    /// as long as it fits into the maximum response size, see [`Config::set_max_response_size`],
+    /// and the maximum packet size. Returns the closer peers, the providers and the offset
+    /// of the next page, if any providers remain.
+    fn provider_page(
+        &mut self,
+        key: record::Key,
+        providers: Vec<ProviderRecord>,
+        offset: u32,
+        source: &PeerId,
+    ) -> (Vec<KadPeer>, Vec<KadPeer>, Option<u32>) {
+        let mut closer_peers = if offset == 0 {
+            self.find_closest(&self.hasher.key(key), source)
+        } else {
+            Vec::new()
+        };
+        self.truncate_response(0, &mut closer_peers, &mut Vec::new());
+
+        let max_packet_size = self.protocol_config.max_packet_size();
+        let max_size = self
+            .max_response_size
+            .map_or(max_packet_size, |size| size.min(max_packet_size))
+            .saturating_sub(PROVIDER_PAGE_OVERHEAD);
+        let mut size = closer_peers.iter().map(KadPeer::encoded_len).sum::<usize>();
+
+        let mut provider_peers = Vec::new();
+        let mut next_offset = None;
+        for peer in self
+            .provider_peers(providers, source, usize::MAX)
+            .into_iter()
+            .skip(offset as usize)
+        {
+            let len = peer.encoded_len();
+            // A page always contains a provider, such that the remote makes progress.
+            if !provider_peers.is_empty() && size + len > max_size {
+                next_offset = Some(offset.saturating_add(provider_peers.len() as u32));
+                break;
+            }
+            size += len;
+            provider_peers.push(peer);
+        }
+
+        (closer_peers, provider_peers, next_offset)
+    }
+
+    /// Collects up to `num_peers` peers who are known to be providers of the value
+    /// for a given `Multihash` from the stored provider records.
+    fn provider_peers(
+        &mut self,
+        providers: Vec<ProviderRecord>,
+        source: &PeerId,
+        num_peers: usize,
+    ) -> Vec<KadPeer> {
         let kbuckets = &mut self.kbuckets;
         let connected = &mut self.connected_peers;
         let listen_addresses = &self.listen_addresses;
         let external_addresses = &self.external_addresses;
+        let hasher = &self.hasher;
+        let filter = &self.provider_filter;
+        let signed_records = &self.signed_records;
 
-        self.store
-            .providers(key)
+        providers
             .into_iter()
             .filter_map(move |p| {
                 if &p.provider != source {
+                    let key = p.key;
                     let node_id = p.provider;
                     let multiaddrs = p.addresses;
                     let connection_ty = if connected.contains(&node_id) {
@@ -1221,7 +3148,7 @@ where
                                     .collect::<Vec<_>>(),
                             )
                         } else {
-                            let key = kbucket::Key::from(node_id);
+                            let key = hasher.peer(node_id);
                             kbuckets
                                 .entry(&key)
                                 .as_mut()
@@ -1231,37 +3158,78 @@ where
                     } else {
                         Some(multiaddrs)
                     }
-                    .map(|multiaddrs| KadPeer {
-                        node_id,
-                        multiaddrs,
-                        connection_ty,
+                    .filter(|multiaddrs| {
+                        filter.retain(
+                            &key,
+                            &node_id,
+                            multiaddrs,
+                            ProvidersFor::Remote { requester: *source },
+                        )
+                    })
+                    .map(|multiaddrs| {
+                        let mut peer = KadPeer {
+                            node_id,
+                            multiaddrs,
+                            connection_ty,
+                            signed_record: None,
+                        };
+                        signed_records.attach(&mut peer);
+                        peer
                     })
                 } else {
                     None
                 }
             })
-            .take(self.queries.config().replication_factor.get())
+            .take(num_peers)
             .collect()
     }
 
     /// Starts an iterative `ADD_PROVIDER` query for the given key.
     fn start_add_provider(&mut self, key: record::Key, context: AddProviderContext) {
+        if let Some(expires) = self.local_provider_expirations.get(&key) {
+            if *expires <= Instant::now() {
+                tracing::debug!(?key, "Not re-publishing expired provider record");
+                self.add_provider_job.unschedule(&key);
+                self.local_provider_expirations.remove(&key);
+                return;
+            }
+        }
         let info = QueryInfo::AddProvider {
             context,
             key: key.clone(),
             phase: AddProviderPhase::GetClosestPeers,
         };
-        let target = kbucket::Key::new(key);
-        let peers = self.kbuckets.closest_keys(&target);
         let inner = QueryInner::new(info);
-        self.queries.add_iter_closest(target.clone(), peers, inner);
+        self.start_advertisement(key, inner, QueryPriority::Background);
+    }
+
+    /// Replicates a stored record to the given peers, e.g. peers that joined
+    /// the routing table, see [`Config::set_replicate_on_join`].
+    fn start_replicate_to(&mut self, record: Record, peers: Vec<PeerId>) {
+        let Some(quorum) = NonZeroUsize::new(peers.len()) else {
+            return;
+        };
+        tracing::debug!(record=?record.key, ?peers, "Replicating record to joined peers");
+        let info = QueryInfo::PutRecord {
+            context: PutRecordContext::Replicate,
+            record,
+            quorum,
+            phase: PutRecordPhase::PutRecord {
+                success: Vec::new(),
+                failed: Vec::new(),
+                get_closest_peers_stats: QueryStats::empty(),
+                step: ProgressStep::first(),
+            },
+        };
+        let inner = QueryInner::new(info);
+        self.queries
+            .add_fixed(peers, inner, QueryPriority::Background);
     }
 
     /// Starts an iterative `PUT_VALUE` query for the given record.
     fn start_put_record(&mut self, record: Record, quorum: Quorum, context: PutRecordContext) {
         let quorum = quorum.eval(self.queries.config().replication_factor);
-        let target = kbucket::Key::new(record.key.clone());
-        let peers = self.kbuckets.closest_keys(&target);
+        let key = record.key.clone();
         let info = QueryInfo::PutRecord {
             record,
             quorum,
@@ -1269,7 +3237,105 @@ where
             phase: PutRecordPhase::GetClosestPeers,
         };
         let inner = QueryInner::new(info);
-        self.queries.add_iter_closest(target.clone(), peers, inner);
+        if context == PutRecordContext::Replicate {
+            // Replicating records of other nodes does not advertise the local node.
+            let target = self.hasher.key(key);
+            let peers = self.kbuckets.closest_keys(&target);
+            self.queries
+                .add_iter_closest(target.clone(), peers, inner, QueryPriority::Background);
+        } else {
+            self.start_advertisement(key, inner, QueryPriority::Background);
+        }
+    }
+
+    /// Starts an iterative query that advertises the local node or its records
+    /// for the given key, deferring it until the local node is dialable if
+    /// configured, see [`Config::set_advertise_when_dialable`].
+    fn start_advertisement(
+        &mut self,
+        key: record::Key,
+        inner: QueryInner,
+        priority: QueryPriority,
+    ) -> QueryId {
+        if self.advertise_when_dialable && !self.is_dialable() {
+            let id = self.queries.reserve_id();
+            tracing::debug!(query=?id, ?key, "Deferring advertisement until the local node is dialable");
+            self.pending_advertisements.push((id, key, inner, priority));
+            return id;
+        }
+        let target = self.hasher.key(key);
+        let peers = self.kbuckets.closest_keys(&target);
+        self.queries
+            .add_iter_closest(target.clone(), peers, inner, priority)
+    }
+
+    /// Starts the advertisements deferred until the local node is dialable.
+    fn start_pending_advertisements(&mut self) {
+        for (id, key, inner, priority) in std::mem::take(&mut self.pending_advertisements) {
+            tracing::debug!(query=?id, ?key, "Starting deferred advertisement");
+            let target = self.hasher.key(key);
+            let peers = self.kbuckets.closest_keys(&target);
+            self.queries
+                .add_iter_closest_with_id(id, target.clone(), peers, inner, priority);
+        }
+    }
+
+    /// Whether other nodes can presumably dial the local node, i.e. it has a confirmed
+    /// external address or listens on a relayed address of an active relay reservation.
+    fn is_dialable(&self) -> bool {
+        !self.external_addresses.as_slice().is_empty()
+            || self
+                .listen_addresses
+                .iter()
+                .any(|addr| addr.iter().any(|p| p == Protocol::P2pCircuit))
+    }
+
+    /// Records an [`Event::RoutingUpdated`], returning it unless updates of
+    /// the routing table are coalesced, see [`Config::set_coalesced_routing_updates`].
+    fn routing_updated(&mut self, event: Event) -> Option<Event> {
+        if let Event::RoutingUpdated {
+            is_new_peer,
+            bucket_range: (min, _),
+            old_peer,
+            ..
+        } = &event
+        {
+            let bucket = min.ilog2().expect("Not the local key.");
+            self.routing_updates
+                .on_update(bucket, *is_new_peer, old_peer.is_some());
+        }
+        if self.routing_updates.is_coalescing() {
+            return None;
+        }
+        Some(event)
+    }
+
+    /// Queues an [`Event::RoutingUpdated`], see [`Behaviour::routing_updated`].
+    fn queue_routing_updated(&mut self, event: Event) {
+        if let Some(event) = self.routing_updated(event) {
+            self.queued_events.push_back(ToSwarm::GenerateEvent(event));
+        }
+    }
+
+    /// Queues an [`Event::RoutingTableUpdated`] for a peer entering or leaving the routing table.
+    fn routing_table_updated(&mut self, peer: PeerId, action: RoutingTableAction) {
+        if let (RoutingTableAction::Inserted, Some(job)) =
+            (&action, self.replicate_on_join_job.as_mut())
+        {
+            job.on_peer_joined(peer);
+        }
+        let bucket = self
+            .kbuckets
+            .local_key()
+            .distance(&self.hasher.peer(peer))
+            .ilog2()
+            .expect("Not the local key.");
+        self.queued_events
+            .push_back(ToSwarm::GenerateEvent(Event::RoutingTableUpdated {
+                peer,
+                bucket,
+                action,
+            }));
     }
 
     /// Updates the routing table with a new connection status and address of a peer.
@@ -1279,35 +3345,64 @@ where
         address: Option<Multiaddr>,
         new_status: NodeStatus,
     ) {
-        let key = kbucket::Key::from(peer);
+        let key = self.hasher.peer(peer);
+        let address = address
+            .filter(|a| self.relayed_addresses != RelayedAddresses::Exclude || !is_relayed(a))
+            .filter(|a| {
+                self.address_filter
+                    .accepts(&peer, a, AddressSource::Connection)
+            });
+        let diversity_exceeded = address
+            .as_ref()
+            .is_some_and(|a| self.ip_diversity_exceeded(&peer, a));
         match self.kbuckets.entry(&key) {
             Some(kbucket::Entry::Present(mut entry, old_status)) => {
                 if old_status != new_status {
-                    entry.update(new_status)
+                    // A peer not admitted stays prone to be replaced by pending entries.
+                    if new_status == NodeStatus::Connected && !self.peer_scorer.admits(&peer) {
+                        tracing::debug!(%peer, "Peer not promoted in routing table by scorer");
+                    } else {
+                        entry.update(new_status)
+                    }
                 }
                 if let Some(address) = address {
-                    if entry.value().insert(address) {
+                    if diversity_exceeded {
                         self.queued_events.push_back(ToSwarm::GenerateEvent(
-                            Event::RoutingUpdated {
-                                peer,
-                                is_new_peer: false,
-                                addresses: entry.value().clone(),
-                                old_peer: None,
-                                bucket_range: self
-                                    .kbuckets
-                                    .bucket(&key)
-                                    .map(|b| b.range())
-                                    .expect("Not kbucket::Entry::SelfEntry."),
-                            },
+                            Event::IpDiversityExceeded { peer, address },
                         ))
+                    } else if entry.value().insert(address) {
+                        let event = Event::RoutingUpdated {
+                            peer,
+                            is_new_peer: false,
+                            addresses: entry.value().clone(),
+                            old_peer: None,
+                            bucket_range: self
+                                .kbuckets
+                                .bucket(&key)
+                                .map(|b| b.range())
+                                .expect("Not kbucket::Entry::SelfEntry."),
+                        };
+                        self.queue_routing_updated(event);
                     }
                 }
             }
 
+            Some(kbucket::Entry::Pending(entry, _)) if !self.peer_scorer.admits(&peer) => {
+                tracing::debug!(%peer, "Pending peer dropped from routing table by scorer");
+                entry.remove();
+            }
+
             Some(kbucket::Entry::Pending(mut entry, old_status)) => {
                 if let Some(address) = address {
-                    entry.value().insert(address);
+                    if diversity_exceeded {
+                        self.queued_events.push_back(ToSwarm::GenerateEvent(
+                            Event::IpDiversityExceeded { peer, address },
+                        ))
+                    } else {
+                        entry.value().insert(address);
+                    }
                 }
+                entry.set_rtt(self.queries.latency(&peer));
                 if old_status != new_status {
                     entry.update(new_status);
                 }
@@ -1330,11 +3425,26 @@ where
                                 address: a,
                             }));
                     }
+                    (Some(_), BucketInserts::OnConnected) if !self.peer_scorer.admits(&peer) => {
+                        tracing::debug!(%peer, "Peer not admitted to routing table by scorer");
+                    }
+                    (Some(address), BucketInserts::OnConnected) if diversity_exceeded => {
+                        tracing::debug!(
+                            %peer,
+                            %address,
+                            "IP diversity limit exceeded. Peer not added to routing table"
+                        );
+                        self.queued_events.push_back(ToSwarm::GenerateEvent(
+                            Event::IpDiversityExceeded { peer, address },
+                        ));
+                    }
                     (Some(a), BucketInserts::OnConnected) => {
                         let addresses = Addresses::new(a);
-                        match entry.insert(addresses.clone(), new_status) {
+                        let rtt = self.queries.latency(&peer);
+                        match entry.insert(addresses.clone(), new_status, rtt) {
                             kbucket::InsertResult::Inserted => {
                                 self.bootstrap_status.on_new_peer_in_routing_table();
+                                self.routing_table_updated(peer, RoutingTableAction::Inserted);
                                 let event = Event::RoutingUpdated {
                                     peer,
                                     is_new_peer: true,
@@ -1346,7 +3456,7 @@ where
                                         .map(|b| b.range())
                                         .expect("Not kbucket::Entry::SelfEntry."),
                                 };
-                                self.queued_events.push_back(ToSwarm::GenerateEvent(event));
+                                self.queue_routing_updated(event);
                             }
                             kbucket::InsertResult::Full => {
                                 tracing::debug!(
@@ -1385,6 +3495,51 @@ where
         }
     }
 
+    /// Checks whether adding the given address of a peer to the routing table would
+    /// exceed the maximum number of peers with an address in the same network,
+    /// see [`Config::set_max_peers_per_ip_prefix`].
+    fn ip_diversity_exceeded(&mut self, peer: &PeerId, address: &Multiaddr) -> bool {
+        let (Some(max), Some(prefix)) = (self.max_peers_per_ip_prefix, IpPrefix::of(address))
+        else {
+            return false;
+        };
+        let mut num_peers = 0;
+        for bucket in self.kbuckets.iter() {
+            num_peers += bucket
+                .iter()
+                .filter(|e| {
+                    e.node.key.preimage() != peer
+                        && e.node.value.iter().any(|a| IpPrefix::of(a) == Some(prefix))
+                })
+                .count();
+        }
+        num_peers >= max.get()
+    }
+
+    /// Applies a command sent via a [`QueryHandle`].
+    fn on_query_command(&mut self, id: QueryId, command: QueryCommand) {
+        match command {
+            QueryCommand::Finish => {
+                if let Some(query) = self.queries.get_mut(&id) {
+                    query.finish();
+                }
+            }
+            QueryCommand::Cancel => {
+                self.pending_advertisements
+                    .retain(|(query_id, ..)| *query_id != id);
+                self.pending_retries
+                    .retain(|(_, query_id, ..)| *query_id != id);
+                let Some(query) = self.queries.remove(&id) else {
+                    return;
+                };
+                tracing::debug!(query=?id, "Query cancelled");
+                if let QueryInfo::Bootstrap { .. } = query.inner.info {
+                    self.bootstrap_status.on_finish();
+                }
+            }
+        }
+    }
+
     /// Handles a finished (i.e. successful) query.
     fn query_finished(&mut self, q: Query<QueryInner>) -> Option<Event> {
         let query_id = q.id();
@@ -1421,13 +3576,13 @@ where
                             // Pr(bucket-253) = 1 - (7/8)^16   ~= 0.88
                             // Pr(bucket-252) = 1 - (15/16)^16 ~= 0.64
                             // ...
-                            let mut target = kbucket::Key::from(PeerId::random());
+                            let mut target = self.hasher.peer(PeerId::random());
                             for _ in 0..16 {
                                 let d = local_key.distance(&target);
                                 if b.contains(&d) {
                                     break;
                                 }
-                                target = kbucket::Key::from(PeerId::random());
+                                target = self.hasher.peer(PeerId::random());
                             }
                             target
                         })
@@ -1438,6 +3593,9 @@ where
                 let num_remaining = remaining.len() as u32;
 
                 if let Some(target) = remaining.next() {
+                    if let Some(bucket) = local_key.distance(&target).ilog2() {
+                        self.bucket_refreshes.on_lookup(bucket);
+                    }
                     let info = QueryInfo::Bootstrap {
                         peer: *target.preimage(),
                         remaining: Some(remaining),
@@ -1445,8 +3603,13 @@ where
                     };
                     let peers = self.kbuckets.closest_keys(&target);
                     let inner = QueryInner::new(info);
-                    self.queries
-                        .continue_iter_closest(query_id, target.clone(), peers, inner);
+                    self.queries.continue_iter_closest(
+                        query_id,
+                        target.clone(),
+                        peers,
+                        inner,
+                        result.priority,
+                    );
                 } else {
                     step.last = true;
                     self.bootstrap_status.on_finish();
@@ -1463,6 +3626,54 @@ where
                 })
             }
 
+            QueryInfo::Crawl {
+                target,
+                remaining,
+                discovered,
+                crawled,
+                step,
+            } => {
+                let num_remaining = remaining.len() as u32;
+                let step = self.continue_crawl(
+                    query_id,
+                    result.priority,
+                    remaining,
+                    discovered,
+                    crawled,
+                    step,
+                );
+
+                Some(Event::OutboundQueryProgressed {
+                    id: query_id,
+                    stats: result.stats,
+                    result: QueryResult::Crawl(Ok(CrawlOk::LookupFinished {
+                        target,
+                        num_remaining,
+                    })),
+                    step,
+                })
+            }
+
+            QueryInfo::Refresh {
+                bucket,
+                remaining,
+                step,
+                ..
+            } => {
+                let num_remaining = remaining.len() as u32;
+                let step = self.continue_refresh(query_id, result.priority, remaining, step);
+
+                Some(Event::OutboundQueryProgressed {
+                    id: query_id,
+                    stats: result.stats,
+                    result: QueryResult::Refresh(Ok(RefreshOk {
+                        bucket,
+                        num_remaining,
+                    })),
+                    step,
+                })
+            }
+
             QueryInfo::GetClosestPeers { key, mut step } => {
                 step.last = true;
 
@@ -1477,17 +3688,34 @@ where
                 })
             }
 
-            QueryInfo::GetProviders { mut step, .. } => {
+            QueryInfo::GetProviders {
+                key,
+                providers,
+                quorum,
+                confirmations,
+                mut step,
+            } => {
                 step.last = true;
 
+                let providers_result = match quorum {
+                    Some(quorum)
+                        if !providers_quorum_reached(&providers, quorum, confirmations) =>
+                    {
+                        Err(GetProvidersError::QuorumFailed {
+                            key,
+                            providers: providers.keys().copied().collect(),
+                            confirmations: providers,
+                            quorum,
+                        })
+                    }
+                    _ => Ok(GetProvidersOk::FinishedWithNoAdditionalRecord {
+                        closest_peers: result.peers.collect(),
+                    }),
+                };
                 Some(Event::OutboundQueryProgressed {
                     id: query_id,
                     stats: result.stats,
-                    result: QueryResult::GetProviders(Ok(
-                        GetProvidersOk::FinishedWithNoAdditionalRecord {
-                            closest_peers: result.peers.collect(),
-                        },
-                    )),
+                    result: QueryResult::GetProviders(providers_result),
                     step,
                 })
             }
@@ -1499,16 +3727,19 @@ where
             } => {
                 let provider_id = self.local_peer_id;
                 let external_addresses = self.external_addresses.iter().cloned().collect();
+                let expires = self.local_provider_expirations.get(&key).copied();
                 let inner = QueryInner::new(QueryInfo::AddProvider {
                     context,
                     key,
                     phase: AddProviderPhase::AddProvider {
                         provider_id,
                         external_addresses,
+                        expires,
                         get_closest_peers_stats: result.stats,
                     },
                 });
-                self.queries.continue_fixed(query_id, result.peers, inner);
+                self.queries
+                    .continue_fixed(query_id, result.peers, inner, result.priority);
                 None
             }
 
@@ -1540,10 +3771,20 @@ where
                 mut step,
                 found_a_record,
                 cache_candidates,
+                mut agreeing_peers,
+                agreed_record,
+                ..
             } => {
                 step.last = true;
 
-                let results = if found_a_record {
+                if let Some(record) = result.inner.cache_record {
+                    self.cache_record(record, cache_candidates.values().copied().collect());
+                }
+
+                let results = if let Some(record) = agreed_record {
+                    let peers = agreeing_peers.remove(&record.value).unwrap_or_default();
+                    Ok(GetRecordOk::AgreementReached { record, peers })
+                } else if found_a_record {
                     Ok(GetRecordOk::FinishedWithNoAdditionalRecord { cache_candidates })
                 } else {
                     Err(GetRecordError::NotFound {
@@ -1571,11 +3812,14 @@ where
                     quorum,
                     phase: PutRecordPhase::PutRecord {
                         success: vec![],
+                        failed: vec![],
                         get_closest_peers_stats: result.stats,
+                        step: ProgressStep::first(),
                     },
                 };
                 let inner = QueryInner::new(info);
-                self.queries.continue_fixed(query_id, result.peers, inner);
+                self.queries
+                    .continue_fixed(query_id, result.peers, inner, result.priority);
                 None
             }
 
@@ -1586,17 +3830,25 @@ where
                 phase:
                     PutRecordPhase::PutRecord {
                         success,
+                        failed,
                         get_closest_peers_stats,
+                        mut step,
                     },
             } => {
+                step.last = true;
                 let mk_result = |key: record::Key| {
                     if success.len() >= quorum.get() {
-                        Ok(PutRecordOk { key })
+                        Ok(PutRecordOk {
+                            key,
+                            success,
+                            failed,
+                        })
                     } else {
                         Err(PutRecordError::QuorumFailed {
                             key,
                             quorum,
                             success,
+                            failed,
                         })
                     }
                 };
@@ -1606,21 +3858,57 @@ where
                             id: query_id,
                             stats: get_closest_peers_stats.merge(result.stats),
                             result: QueryResult::PutRecord(mk_result(record.key)),
-                            step: ProgressStep::first_and_last(),
+                            step,
                         })
                     }
                     PutRecordContext::Republish => Some(Event::OutboundQueryProgressed {
                         id: query_id,
                         stats: get_closest_peers_stats.merge(result.stats),
                         result: QueryResult::RepublishRecord(mk_result(record.key)),
-                        step: ProgressStep::first_and_last(),
+                        step,
                     }),
                     PutRecordContext::Replicate => {
                         tracing::debug!(record=?record.key, "Record replicated");
                         None
                     }
+                    PutRecordContext::Cache => match mk_result(record.key) {
+                        Ok(PutRecordOk { key, success, .. }) => Some(Event::RecordCached {
+                            key,
+                            peers: success,
+                        }),
+                        Err(PutRecordError::QuorumFailed { key, success, .. })
+                            if !success.is_empty() =>
+                        {
+                            Some(Event::RecordCached {
+                                key,
+                                peers: success,
+                            })
+                        }
+                        err => {
+                            tracing::debug!("Caching record failed: {:?}", err);
+                            None
+                        }
+                    },
                 }
             }
+
+            QueryInfo::GetProviderSummary {
+                peer,
+                range,
+                summary,
+            } => {
+                let summary_result = match summary {
+                    Some(summary) => Ok(GetProviderSummaryOk { peer, summary }),
+                    None => Err(GetProviderSummaryError::Unavailable { peer, range }),
+                };
+
+                Some(Event::OutboundQueryProgressed {
+                    id: query_id,
+                    stats: result.stats,
+                    result: QueryResult::GetProviderSummary(summary_result),
+                    step: ProgressStep::first_and_last(),
+                })
+            }
         }
     }
 
@@ -1628,6 +3916,7 @@ where
     fn query_timeout(&mut self, query: Query<QueryInner>) -> Option<Event> {
         let query_id = query.id();
         tracing::trace!(query=?query_id, "Query timed out");
+        let timed_out = query.pending_peers().copied().collect::<Vec<_>>();
         let result = query.into_result();
         match result.inner.info {
             QueryInfo::Bootstrap {
@@ -1641,6 +3930,9 @@ where
                 if let Some((target, remaining)) =
                     remaining.take().and_then(|mut r| Some((r.next()?, r)))
                 {
+                    if let Some(bucket) = self.kbuckets.local_key().distance(&target).ilog2() {
+                        self.bucket_refreshes.on_lookup(bucket);
+                    }
                     let info = QueryInfo::Bootstrap {
                         peer: target.clone().into_preimage(),
                         remaining: Some(remaining),
@@ -1648,8 +3940,13 @@ where
                     };
                     let peers = self.kbuckets.closest_keys(&target);
                     let inner = QueryInner::new(info);
-                    self.queries
-                        .continue_iter_closest(query_id, target.clone(), peers, inner);
+                    self.queries.continue_iter_closest(
+                        query_id,
+                        target.clone(),
+                        peers,
+                        inner,
+                        result.priority,
+                    );
                 } else {
                     step.last = true;
                     self.bootstrap_status.on_finish();
@@ -1681,6 +3978,54 @@ where
                 },
             }),
 
+            QueryInfo::Crawl {
+                target,
+                remaining,
+                discovered,
+                crawled,
+                step,
+            } => {
+                let num_remaining = remaining.len() as u32;
+                let step = self.continue_crawl(
+                    query_id,
+                    result.priority,
+                    remaining,
+                    discovered,
+                    crawled,
+                    step,
+                );
+
+                Some(Event::OutboundQueryProgressed {
+                    id: query_id,
+                    stats: result.stats,
+                    result: QueryResult::Crawl(Err(CrawlError::Timeout {
+                        target,
+                        num_remaining,
+                    })),
+                    step,
+                })
+            }
+
+            QueryInfo::Refresh {
+                bucket,
+                remaining,
+                step,
+                ..
+            } => {
+                let num_remaining = remaining.len() as u32;
+                let step = self.continue_refresh(query_id, result.priority, remaining, step);
+
+                Some(Event::OutboundQueryProgressed {
+                    id: query_id,
+                    stats: result.stats,
+                    result: QueryResult::Refresh(Err(RefreshError::Timeout {
+                        bucket,
+                        num_remaining,
+                    })),
+                    step,
+                })
+            }
+
             QueryInfo::GetClosestPeers { key, mut step } => {
                 step.last = true;
 
@@ -1701,13 +4046,33 @@ where
                 context,
                 phase,
             } => {
+                let (success, failed, step) = match phase {
+                    PutRecordPhase::GetClosestPeers => {
+                        (vec![], vec![], ProgressStep::first_and_last())
+                    }
+                    PutRecordPhase::PutRecord {
+                        ref success,
+                        ref failed,
+                        ref step,
+                        ..
+                    } => {
+                        // Peers that did not answer before the query timed out.
+                        let mut failed = failed.clone();
+                        failed.extend(
+                            timed_out
+                                .iter()
+                                .map(|peer| (*peer, PutRecordPeerError::Timeout)),
+                        );
+                        let mut step = step.clone();
+                        step.last = true;
+                        (success.clone(), failed, step)
+                    }
+                };
                 let err = Err(PutRecordError::Timeout {
                     key: record.key,
                     quorum,
-                    success: match phase {
-                        PutRecordPhase::GetClosestPeers => vec![],
-                        PutRecordPhase::PutRecord { ref success, .. } => success.clone(),
-                    },
+                    success,
+                    failed,
                 });
                 match context {
                     PutRecordContext::Publish | PutRecordContext::Custom => {
@@ -1715,14 +4080,14 @@ where
                             id: query_id,
                             stats: result.stats,
                             result: QueryResult::PutRecord(err),
-                            step: ProgressStep::first_and_last(),
+                            step,
                         })
                     }
                     PutRecordContext::Republish => Some(Event::OutboundQueryProgressed {
                         id: query_id,
                         stats: result.stats,
                         result: QueryResult::RepublishRecord(err),
-                        step: ProgressStep::first_and_last(),
+                        step,
                     }),
                     PutRecordContext::Replicate => match phase {
                         PutRecordPhase::GetClosestPeers => {
@@ -1737,6 +4102,20 @@ where
                             None
                         }
                     },
+                    PutRecordContext::Cache => match err {
+                        Err(PutRecordError::Timeout { key, success, .. })
+                            if !success.is_empty() =>
+                        {
+                            Some(Event::RecordCached {
+                                key,
+                                peers: success,
+                            })
+                        }
+                        err => {
+                            tracing::debug!("Caching record failed: {:?}", err);
+                            None
+                        }
+                    },
                 }
             }
 
@@ -1764,9 +4143,25 @@ where
                     step,
                 })
             }
+
+            QueryInfo::GetProviderSummary { peer, range, .. } => {
+                Some(Event::OutboundQueryProgressed {
+                    id: query_id,
+                    stats: result.stats,
+                    result: QueryResult::GetProviderSummary(Err(
+                        GetProviderSummaryError::Timeout { peer, range },
+                    )),
+                    step: ProgressStep::first_and_last(),
+                })
+            }
         }
     }
 
+    /// Returns the TTL of the records with the given key, as per its namespace.
+    fn record_ttl(&self, key: &record::Key) -> Option<Duration> {
+        self.namespaces.record_ttl(key).unwrap_or(self.record_ttl)
+    }
+
     /// Processes a record received from a peer.
     fn record_received(
         &mut self,
@@ -1775,6 +4170,17 @@ where
         request_id: RequestId,
         mut record: Record,
     ) {
+        if let Err(e) = self.namespaces.validate(&record) {
+            tracing::debug!(peer=%source, record=?record.key, "Rejecting record: {e}");
+            self.report_misbehaviour(&source, Misbehaviour::InvalidRecord);
+            self.queued_events.push_back(ToSwarm::NotifyHandler {
+                peer_id: source,
+                handler: NotifyHandler::One(connection),
+                event: HandlerIn::Reset(request_id),
+            });
+            return;
+        }
+
         if record.publisher.as_ref() == Some(self.kbuckets.local_key().preimage()) {
             // If the (alleged) publisher is the local node, do nothing. The record of
             // the original publisher should never change as a result of replication
@@ -1797,12 +4203,12 @@ where
         // number of nodes between the local node and the closest node to the key
         // (beyond the replication factor). This ensures avoiding over-caching
         // outside of the k closest nodes to a key.
-        let target = kbucket::Key::new(record.key.clone());
+        let target = self.hasher.key(record.key.clone());
         let num_between = self.kbuckets.count_nodes_between(&target);
         let k = self.queries.config().replication_factor.get();
         let num_beyond_k = (usize::max(k, num_between) - k) as u32;
         let expiration = self
-            .record_ttl
+            .record_ttl(&record.key)
             .map(|ttl| now + exp_decrease(ttl, num_beyond_k));
         // The smaller TTL prevails. Only if neither TTL is set is the record
         // stored "forever".
@@ -1817,48 +4223,37 @@ where
             job.skip(record.key.clone())
         }
 
-        // While records received from a publisher, as well as records that do
-        // not exist locally should always (attempted to) be stored, there is a
-        // choice here w.r.t. the handling of replicated records whose keys refer
-        // to records that exist locally: The value and / or the publisher may
-        // either be overridden or left unchanged. At the moment and in the
-        // absence of a decisive argument for another option, both are always
-        // overridden as it avoids having to load the existing record in the
-        // first place.
+        // While records that do not exist locally should always (attempted to)
+        // be stored, records whose keys refer to records that exist locally are
+        // only stored if not superseded by the existing record, as per the
+        // conflict resolver of the key's namespace. Otherwise the value and the
+        // publisher of the existing record are overridden.
 
         if !record.is_expired(now) {
             // The record is cloned because of the weird libp2p protocol
             // requirement to send back the value in the response, although this
             // is a waste of resources.
-            match self.record_filtering {
-                StoreInserts::Unfiltered => match self.store.put(record.clone()) {
-                    Ok(()) => {
-                        tracing::debug!(
-                            record=?record.key,
-                            "Record stored: {} bytes",
-                            record.value.len()
-                        );
-                        self.queued_events.push_back(ToSwarm::GenerateEvent(
-                            Event::InboundRequest {
-                                request: InboundRequest::PutRecord {
-                                    source,
-                                    connection,
-                                    record: None,
-                                },
-                            },
-                        ));
-                    }
-                    Err(e) => {
-                        tracing::info!("Record not stored: {:?}", e);
-                        self.queued_events.push_back(ToSwarm::NotifyHandler {
-                            peer_id: source,
-                            handler: NotifyHandler::One(connection),
-                            event: HandlerIn::Reset(request_id),
-                        });
+            let filtering = self
+                .namespaces
+                .record_filtering(&record.key)
+                .unwrap_or(self.record_filtering);
+            match filtering {
+                StoreInserts::Unfiltered => {
+                    let op = self.store.get_record(&record.key);
+                    self.run_store_op(
+                        op,
+                        Some((source, connection, request_id)),
+                        move |existing| StoreOutcome::InboundPutRecordExisting {
+                            source,
+                            connection,
+                            request_id,
+                            record,
+                            existing,
+                        },
+                    );
 
-                        return;
-                    }
-                },
+                    return;
+                }
                 StoreInserts::FilterBoth => {
                     self.queued_events
                         .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
@@ -1879,6 +4274,78 @@ where
         // closest nodes to the target. In addition returning
         // [`HandlerIn::PutRecordRes`] does not reveal any internal
         // information to a possibly malicious remote node.
+        self.put_record_res(source, connection, request_id, record)
+    }
+
+    /// Checks an inbound write request of `source` against the rate limits,
+    /// reporting it via [`Event::InboundRequestThrottled`] if it exceeds one.
+    fn inbound_write_throttled(&mut self, source: PeerId, request: ThrottledRequest) -> bool {
+        let now = Instant::now();
+        let limit = if self
+            .inbound_write_limiter
+            .as_mut()
+            .is_some_and(|l| !l.try_next(source, now))
+        {
+            RateLimit::PerPeer
+        } else if self
+            .global_inbound_write_limiter
+            .as_mut()
+            .is_some_and(|l| !l.try_next((), now))
+        {
+            RateLimit::Global
+        } else {
+            return false;
+        };
+
+        tracing::debug!(peer=%source, ?request, ?limit, "Throttling inbound request");
+        self.queued_events
+            .push_back(ToSwarm::GenerateEvent(Event::InboundRequestThrottled {
+                peer: source,
+                request,
+                limit,
+            }));
+
+        true
+    }
+
+    /// Checks an inbound request of `source` against the [`RequestAuthorizer`], if any,
+    /// resetting and reporting it via [`Event::InboundRequestDenied`] if it is denied.
+    fn inbound_request_denied(
+        &mut self,
+        source: PeerId,
+        connection: ConnectionId,
+        request_id: Option<RequestId>,
+        request: RestrictedRequest,
+    ) -> bool {
+        if self.request_authorizer.authorize(&source, request) {
+            return false;
+        }
+
+        tracing::debug!(peer=%source, ?request, "Denying inbound request");
+        if let Some(request_id) = request_id {
+            self.queued_events.push_back(ToSwarm::NotifyHandler {
+                peer_id: source,
+                handler: NotifyHandler::One(connection),
+                event: HandlerIn::Reset(request_id),
+            });
+        }
+        self.queued_events
+            .push_back(ToSwarm::GenerateEvent(Event::InboundRequestDenied {
+                peer: source,
+                request,
+            }));
+
+        true
+    }
+
+    /// Answers an inbound [`HandlerEvent::PutRecord`] request.
+    fn put_record_res(
+        &mut self,
+        source: PeerId,
+        connection: ConnectionId,
+        request_id: RequestId,
+        record: Record,
+    ) {
         self.queued_events.push_back(ToSwarm::NotifyHandler {
             peer_id: source,
             handler: NotifyHandler::One(connection),
@@ -1891,40 +4358,397 @@ where
     }
 
     /// Processes a provider record received from a peer.
-    fn provider_received(&mut self, key: record::Key, provider: KadPeer) {
+    fn provider_received(&mut self, key: record::Key, provider: KadPeer, expires: Option<Instant>) {
         if &provider.node_id != self.kbuckets.local_key().preimage() {
+            // The expiration announced by the provider is capped by the configured TTL.
+            let max_expires = self.provider_record_ttl.map(|ttl| Instant::now() + ttl);
+            let expires = match (expires, max_expires) {
+                (Some(e), Some(max)) => Some(e.min(max)),
+                (e, max) => e.or(max),
+            };
             let record = ProviderRecord {
                 key,
                 provider: provider.node_id,
-                expires: self.provider_record_ttl.map(|ttl| Instant::now() + ttl),
+                expires,
                 addresses: provider.multiaddrs,
             };
             match self.record_filtering {
                 StoreInserts::Unfiltered => {
-                    if let Err(e) = self.store.add_provider(record) {
-                        tracing::info!("Provider record not stored: {:?}", e);
+                    let op = self.store.add_provider_record(record);
+                    self.run_store_op(op, None, |result| StoreOutcome::InboundAddProvider {
+                        result,
+                    });
+                }
+                StoreInserts::FilterBoth => {
+                    self.queued_events
+                        .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
+                            request: InboundRequest::AddProvider {
+                                record: Some(record),
+                            },
+                        }));
+                }
+            }
+        }
+    }
+
+    /// Runs an operation on the record store, continuing with its outcome right
+    /// away if the operation completes immediately, as it always does for a
+    /// [`RecordStore`](store::RecordStore), or once it completes otherwise.
+    ///
+    /// The inbound request served by the operation, if any, is reset if the
+    /// operation cannot complete.
+    fn run_store_op<T>(
+        &mut self,
+        mut op: StoreFuture<T>,
+        request: Option<(PeerId, ConnectionId, RequestId)>,
+        outcome: impl FnOnce(T) -> StoreOutcome + Send + 'static,
+    ) where
+        T: 'static,
+    {
+        match poll_store_op(&mut op) {
+            Poll::Ready(value) => self.on_store_outcome(outcome(value)),
+            Poll::Pending => {
+                if self
+                    .pending_store_ops
+                    .try_push(op.map(outcome), request)
+                    .is_err()
+                {
+                    tracing::warn!("Dropping record store operation: too many pending operations");
+                    if let Some((peer_id, connection, request_id)) = request {
+                        self.queued_events.push_back(ToSwarm::NotifyHandler {
+                            peer_id,
+                            handler: NotifyHandler::One(connection),
+                            event: HandlerIn::Reset(request_id),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs an operation storing a record of the local node, returning the
+    /// error of the operation if it completes immediately.
+    fn run_local_store_op(
+        &mut self,
+        op: StoreFuture<store::Result<()>>,
+        key: record::Key,
+    ) -> Result<(), store::Error> {
+        let mut op = op;
+        match poll_store_op(&mut op) {
+            Poll::Ready(result) => result,
+            Poll::Pending => {
+                self.run_store_op(op, None, move |result| StoreOutcome::Stored { key, result });
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the given record, if it is not expired. An expired record is
+    /// removed from the store.
+    fn unexpired_record(&mut self, record: Option<Record>) -> Option<Record> {
+        let record = record?;
+        if record.is_expired(Instant::now()) {
+            let op = self.store.remove_record(&record.key);
+            self.run_store_op(op, None, |()| StoreOutcome::Removed);
+            return None;
+        }
+        Some(record)
+    }
+
+    /// Continues with the outcome of a completed operation on the record store.
+    fn on_store_outcome(&mut self, outcome: StoreOutcome) {
+        match outcome {
+            StoreOutcome::GetRecord { query_id, record } => {
+                let Some(record) = self.unexpired_record(record) else {
+                    return;
+                };
+                if let Some(query) = self.queries.get_mut(&query_id) {
+                    if !query.inner.on_record_found(
+                        &record,
+                        None,
+                        &self.namespaces.resolvers,
+                        &self.caching,
+                        &self.hasher,
+                    ) {
                         return;
                     }
+                    if let QueryInfo::GetRecord { ref mut step, .. } = query.inner.info {
+                        let record = PeerRecord { peer: None, record };
+
+                        // No queries were actually done for the results yet.
+                        self.queued_events.push_back(ToSwarm::GenerateEvent(
+                            Event::OutboundQueryProgressed {
+                                id: query_id,
+                                result: QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(
+                                    record,
+                                ))),
+                                step: step.clone(),
+                                stats: QueryStats::empty(),
+                            },
+                        ));
+                        *step = step.next();
+                    }
+                }
+            }
+            StoreOutcome::GetProviders {
+                query_id,
+                providers,
+            } => {
+                let now = Instant::now();
+                let providers: HashSet<_> = providers
+                    .into_iter()
+                    .filter(|p| !p.is_expired(now))
+                    .filter(|p| {
+                        self.provider_filter.retain(
+                            &p.key,
+                            &p.provider,
+                            &p.addresses,
+                            ProvidersFor::Local { source: None },
+                        )
+                    })
+                    .map(|p| p.provider)
+                    .collect();
+                if providers.is_empty() {
+                    return;
+                }
+                if let Some(query) = self.queries.get_mut(&query_id) {
+                    let mut quorum_reached = false;
+                    if let QueryInfo::GetProviders {
+                        ref key,
+                        providers: ref mut found,
+                        quorum,
+                        confirmations,
+                        ref mut step,
+                    } = query.inner.info
+                    {
+                        let counts = confirm_providers(found, &providers);
+                        quorum_reached = quorum
+                            .is_some_and(|q| providers_quorum_reached(found, q, confirmations));
+
+                        // No queries were actually done for the results yet.
+                        self.queued_events.push_back(ToSwarm::GenerateEvent(
+                            Event::OutboundQueryProgressed {
+                                id: query_id,
+                                result: QueryResult::GetProviders(Ok(
+                                    GetProvidersOk::FoundProviders {
+                                        key: key.clone(),
+                                        providers,
+                                        confirmations: counts,
+                                    },
+                                )),
+                                step: step.clone(),
+                                stats: QueryStats::empty(),
+                            },
+                        ));
+                        *step = step.next();
+                    }
+                    if quorum_reached {
+                        query.finish();
+                    }
+                }
+            }
+            StoreOutcome::RemoveRecord { record } => {
+                if let Some(r) = record {
+                    if r.publisher.as_ref() == Some(self.kbuckets.local_key().preimage()) {
+                        let op = self.store.remove_record(&r.key);
+                        self.run_store_op(op, None, |()| StoreOutcome::Removed);
+                    }
+                }
+            }
+            StoreOutcome::Stored { key, result } => {
+                if let Err(e) = result {
+                    tracing::warn!(record=?key, "Failed to store record locally: {e}");
+                }
+            }
+            StoreOutcome::InboundPutRecordExisting {
+                source,
+                connection,
+                request_id,
+                record,
+                existing,
+            } => {
+                let superseded = self.unexpired_record(existing).is_some_and(|existing| {
+                    self.namespaces.resolvers.compare(&record, &existing) == Ordering::Less
+                });
+                if superseded {
+                    tracing::debug!(peer=%source, record=?record.key, "Rejecting superseded record");
+                    self.queued_events.push_back(ToSwarm::NotifyHandler {
+                        peer_id: source,
+                        handler: NotifyHandler::One(connection),
+                        event: HandlerIn::Reset(request_id),
+                    });
+                    return;
+                }
+                let op = self.store.put_record(record.clone());
+                self.run_store_op(op, Some((source, connection, request_id)), move |result| {
+                    StoreOutcome::InboundPutRecord {
+                        source,
+                        connection,
+                        request_id,
+                        record,
+                        result,
+                    }
+                });
+            }
+            StoreOutcome::InboundPutRecord {
+                source,
+                connection,
+                request_id,
+                record,
+                result,
+            } => match result {
+                Ok(()) => {
+                    tracing::debug!(
+                        record=?record.key,
+                        "Record stored: {} bytes",
+                        record.value.len()
+                    );
+                    self.queued_events
+                        .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
+                            request: InboundRequest::PutRecord {
+                                source,
+                                connection,
+                                record: None,
+                            },
+                        }));
+                    self.put_record_res(source, connection, request_id, record);
+                }
+                Err(e) => {
+                    tracing::info!("Record not stored: {:?}", e);
+                    self.queued_events.push_back(ToSwarm::NotifyHandler {
+                        peer_id: source,
+                        handler: NotifyHandler::One(connection),
+                        event: HandlerIn::Reset(request_id),
+                    });
+                }
+            },
+            StoreOutcome::InboundAddProvider { result } => {
+                if let Err(e) = result {
+                    tracing::info!("Provider record not stored: {:?}", e);
+                    return;
+                }
+
+                self.queued_events
+                    .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
+                        request: InboundRequest::AddProvider { record: None },
+                    }));
+            }
+            StoreOutcome::InboundGetRecord {
+                source,
+                connection,
+                request_id,
+                key,
+                record,
+            } => {
+                let record = self.unexpired_record(record);
+                let mut closer_peers = self.find_closest(&self.hasher.key(key.clone()), &source);
+                let reserved = record.as_ref().map_or(0, protocol::record_encoded_len);
+                self.truncate_response(reserved, &mut closer_peers, &mut Vec::new());
+
+                self.inbound_observer.observe(|| InboundRequestInfo {
+                    peer: source,
+                    request: InboundRequestType::GetRecord,
+                    key: key.to_vec(),
+                    served_from_store: record.is_some(),
+                    num_closer_peers: closer_peers.len(),
+                });
+
+                self.queued_events
+                    .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
+                        request: InboundRequest::GetRecord {
+                            num_closer_peers: closer_peers.len(),
+                            present_locally: record.is_some(),
+                        },
+                    }));
+
+                self.queued_events.push_back(ToSwarm::NotifyHandler {
+                    peer_id: source,
+                    handler: NotifyHandler::One(connection),
+                    event: HandlerIn::GetRecordRes {
+                        record,
+                        closer_peers,
+                        request_id,
+                    },
+                });
+            }
+            StoreOutcome::InboundGetProviders {
+                source,
+                connection,
+                request_id,
+                key,
+                provider_offset,
+                providers,
+            } => {
+                let observed_key = key.to_vec();
+                let (closer_peers, provider_peers, next_provider_offset) = match provider_offset {
+                    Some(offset) => self.provider_page(key, providers, offset, &source),
+                    None => {
+                        let num_peers = self.num_response_peers();
+                        let mut provider_peers = self.provider_peers(providers, &source, num_peers);
+                        let mut closer_peers = self.find_closest(&self.hasher.key(key), &source);
+                        self.truncate_response(0, &mut closer_peers, &mut provider_peers);
+                        (closer_peers, provider_peers, None)
+                    }
+                };
+
+                self.inbound_observer.observe(|| InboundRequestInfo {
+                    peer: source,
+                    request: InboundRequestType::GetProviders,
+                    key: observed_key,
+                    served_from_store: !provider_peers.is_empty(),
+                    num_closer_peers: closer_peers.len(),
+                });
+
+                self.queued_events
+                    .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
+                        request: InboundRequest::GetProvider {
+                            num_closer_peers: closer_peers.len(),
+                            num_provider_peers: provider_peers.len(),
+                        },
+                    }));
+
+                self.queued_events.push_back(ToSwarm::NotifyHandler {
+                    peer_id: source,
+                    handler: NotifyHandler::One(connection),
+                    event: HandlerIn::GetProvidersRes {
+                        closer_peers,
+                        provider_peers,
+                        next_provider_offset,
+                        request_id,
+                    },
+                });
+            }
+            StoreOutcome::InboundProviderSummary {
+                source,
+                connection,
+                request_id,
+                range,
+                keys,
+            } => {
+                let summary = ProviderSummary::new(range, &keys);
+
+                self.queued_events
+                    .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
+                        request: InboundRequest::GetProviderSummary {
+                            range: summary.range().clone(),
+                        },
+                    }));
 
-                    self.queued_events
-                        .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
-                            request: InboundRequest::AddProvider { record: None },
-                        }));
-                }
-                StoreInserts::FilterBoth => {
-                    self.queued_events
-                        .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
-                            request: InboundRequest::AddProvider {
-                                record: Some(record),
-                            },
-                        }));
-                }
+                self.queued_events.push_back(ToSwarm::NotifyHandler {
+                    peer_id: source,
+                    handler: NotifyHandler::One(connection),
+                    event: HandlerIn::GetProviderSummaryRes {
+                        summary,
+                        request_id,
+                    },
+                });
             }
+            StoreOutcome::Removed => {}
         }
     }
 
     fn address_failed(&mut self, peer_id: PeerId, address: &Multiaddr) {
-        let key = kbucket::Key::from(peer_id);
+        let key = self.hasher.peer(peer_id);
 
         if let Some(addrs) = self.kbuckets.entry(&key).as_mut().and_then(|e| e.value()) {
             // TODO: Ideally, the address should only be removed if the error can
@@ -1972,6 +4796,8 @@ where
             ..
         }: ConnectionEstablished,
     ) {
+        self.address_revalidation.on_success(&peer_id);
+
         for addr in failed_addresses {
             self.address_failed(peer_id, addr);
         }
@@ -1996,7 +4822,7 @@ where
         // Update routing table.
         if let Some(addrs) = self
             .kbuckets
-            .entry(&kbucket::Key::from(peer))
+            .entry(&self.hasher.peer(peer))
             .as_mut()
             .and_then(|e| e.value())
         {
@@ -2066,8 +4892,21 @@ where
                     }
                 }
 
+                if let Some(address_revalidation::Failure::Evict) =
+                    self.address_revalidation.on_failure(&peer_id)
+                {
+                    tracing::debug!(peer=%peer_id, "Evicting peer failing address revalidation");
+                    self.evict_unreachable(peer_id);
+                }
+
                 for query in self.queries.iter_mut() {
-                    query.on_failure(&peer_id);
+                    if query.on_failure(&peer_id) {
+                        if let Some(event) =
+                            on_put_record_failure(query, peer_id, PutRecordPeerError::Unreachable)
+                        {
+                            self.queued_events.push_back(ToSwarm::GenerateEvent(event));
+                        }
+                    }
                 }
             }
             DialError::DialPeerConditionFalse(
@@ -2077,6 +4916,7 @@ where
             ) => {
                 // We might (still) be connected, or about to be connected, thus do not report the
                 // failure to the queries.
+                self.address_revalidation.on_skipped(&peer_id);
             }
             DialError::DialPeerConditionFalse(dial_opts::PeerCondition::Always) => {
                 unreachable!("DialPeerCondition::Always can not trigger DialPeerConditionFalse.");
@@ -2094,13 +4934,38 @@ where
         }: ConnectionClosed,
     ) {
         self.connections.remove(&connection_id);
+        self.remote_protocols.remove(&connection_id);
 
         if remaining_established == 0 {
             for query in self.queries.iter_mut() {
-                query.on_failure(&peer_id);
+                if query.on_failure(&peer_id) {
+                    if let Some(event) =
+                        on_put_record_failure(query, peer_id, PutRecordPeerError::Unreachable)
+                    {
+                        self.queued_events.push_back(ToSwarm::GenerateEvent(event));
+                    }
+                }
             }
             self.connection_updated(peer_id, None, NodeStatus::Disconnected);
             self.connected_peers.remove(&peer_id);
+            self.peer_protocols.remove(&peer_id);
+            let key = self.hasher.peer(peer_id);
+            let now = snapshot::unix_time_now();
+            match self.kbuckets.entry(&key) {
+                Some(kbucket::Entry::Present(mut entry, _)) => {
+                    self.last_seen.insert(peer_id, now);
+                    self.peer_store.on_peer_seen(|| RoutingTableEntry {
+                        peer_id,
+                        addresses: entry.value().iter().cloned().collect(),
+                        status: NodeStatus::Disconnected,
+                        last_seen: Some(now),
+                    });
+                }
+                Some(kbucket::Entry::Pending(..)) => {
+                    self.last_seen.insert(peer_id, now);
+                }
+                _ => {}
+            }
         }
     }
 
@@ -2114,26 +4979,133 @@ where
         self.connections.insert(connection_id, peer);
         // Queue events for sending pending RPCs to the connected peer.
         // There can be only one pending RPC for a particular peer and query per definition.
-        for (_peer_id, event) in self.queries.iter_mut().filter_map(|q| {
-            q.inner
+        for query in self.queries.iter_mut() {
+            let Some(pos) = query
+                .inner
                 .pending_rpcs
                 .iter()
                 .position(|(p, _)| p == &peer)
-                .map(|p| q.inner.pending_rpcs.remove(p))
-        }) {
+            else {
+                continue;
+            };
+            let (_peer_id, event) = query.inner.pending_rpcs.remove(pos);
+            // `AddProvider` requests yield no response, see `poll`.
+            if let HandlerIn::AddProvider { .. } = event {
+                query.on_success(&peer, vec![])
+            }
             handler.on_behaviour_event(event)
         }
     }
 }
 
+/// Creates the job re-replicating and re-publishing records on the given
+/// intervals, if any.
+fn new_put_record_job(
+    local_id: PeerId,
+    replication_interval: Option<Duration>,
+    publication_interval: Option<Duration>,
+    record_ttl: Option<Duration>,
+) -> Option<PutRecordJob> {
+    replication_interval
+        .or(publication_interval)
+        .map(|interval| PutRecordJob::new(local_id, interval, publication_interval, record_ttl))
+}
+
 /// Exponentially decrease the given duration (base 2).
 fn exp_decrease(ttl: Duration, exp: u32) -> Duration {
     Duration::from_secs(ttl.as_secs().checked_shr(exp).unwrap_or(0))
 }
 
+/// Counts a confirmation for each of the given providers returned by a peer,
+/// returning the number of confirmations of these providers so far.
+fn confirm_providers(
+    found: &mut HashMap<PeerId, usize>,
+    providers: &HashSet<PeerId>,
+) -> HashMap<PeerId, usize> {
+    providers
+        .iter()
+        .map(|provider| {
+            let count = found.entry(*provider).or_default();
+            *count += 1;
+            (*provider, *count)
+        })
+        .collect()
+}
+
+/// Checks whether `quorum` providers have each been confirmed by
+/// at least `confirmations` peers.
+fn providers_quorum_reached(
+    found: &HashMap<PeerId, usize>,
+    quorum: NonZeroUsize,
+    confirmations: NonZeroUsize,
+) -> bool {
+    found
+        .values()
+        .filter(|count| **count >= confirmations.get())
+        .count()
+        >= quorum.get()
+}
+
+/// Generates a random lookup target in the range of the bucket with the given index.
+///
+/// As with the bucket refreshes of [`Behaviour::bootstrap`], this is a "best effort"
+/// of finding a key hashing into the bucket with at most 16 trials.
+/// Returns whether the address is a relayed, i.e. `/p2p-circuit`, address.
+fn is_relayed(address: &Multiaddr) -> bool {
+    address.iter().any(|p| p == Protocol::P2pCircuit)
+}
+
+fn bucket_refresh_target(
+    hasher: &KeyHashing,
+    local_key: &kbucket::Key<PeerId>,
+    bucket: u32,
+) -> kbucket::Key<PeerId> {
+    let mut target = hasher.peer(PeerId::random());
+    for _ in 0..16 {
+        if local_key.distance(&target).ilog2() == Some(bucket) {
+            break;
+        }
+        target = hasher.peer(PeerId::random());
+    }
+    target
+}
+
+/// Generates random lookup targets for a crawl, one in each of `num` equally
+/// sized regions of the keyspace.
+///
+/// As with the bucket refreshes of [`Behaviour::bootstrap`], the wire protocol
+/// requires the preimages of the keys, hence this is a "best effort" of finding
+/// a key hashing into each region within a bounded number of trials. Regions for
+/// which no key is found get a random target instead.
+fn crawl_targets(hasher: &KeyHashing, num: NonZeroUsize) -> Vec<kbucket::Key<PeerId>> {
+    let num = num.get();
+    let mut targets = vec![None; num];
+    let mut missing = num;
+    for _ in 0..num.saturating_mul(16) {
+        let target = hasher.peer(PeerId::random());
+        let prefix = u64::from_be_bytes(
+            target.hashed_bytes()[..8]
+                .try_into()
+                .expect("a key has 32 bytes"),
+        );
+        let region = ((prefix as u128 * num as u128) >> 64) as usize;
+        if targets[region].is_none() {
+            targets[region] = Some(target);
+            missing -= 1;
+            if missing == 0 {
+                break;
+            }
+        }
+    }
+    targets
+        .into_iter()
+        .map(|t| t.unwrap_or_else(|| hasher.peer(PeerId::random())))
+        .collect()
+}
+
 impl<TStore> NetworkBehaviour for Behaviour<TStore>
 where
-    TStore: RecordStore + Send + 'static,
+    TStore: AsyncRecordStore + Send + 'static,
 {
     type ConnectionHandler = Handler;
     type ToSwarm = Event;
@@ -2198,7 +5170,7 @@ where
 
         // We should order addresses from decreasing likelihood of connectivity, so start with
         // the addresses of that peer in the k-buckets.
-        let key = kbucket::Key::from(peer_id);
+        let key = self.hasher.peer(peer_id);
         let mut peer_addrs =
             if let Some(kbucket::Entry::Present(mut entry, _)) = self.kbuckets.entry(&key) {
                 let addrs = entry.value().iter().cloned().collect::<Vec<_>>();
@@ -2225,8 +5197,22 @@ where
         event: THandlerOutEvent<Self>,
     ) {
         match event {
-            HandlerEvent::ProtocolConfirmed { endpoint } => {
+            HandlerEvent::ProtocolConfirmed { endpoint, protocol } => {
                 debug_assert!(self.connected_peers.contains(&source));
+                self.peer_protocols.insert(source, protocol.clone());
+                if self
+                    .routing_table_protocols
+                    .as_ref()
+                    .is_some_and(|protocols| !protocols.contains(&protocol))
+                {
+                    tracing::debug!(
+                        peer=%source,
+                        %protocol,
+                        "Not adding peer to the routing table due to its protocol"
+                    );
+                    return;
+                }
+
                 // The remote's address can only be put into the routing table,
                 // and thus shared with other nodes, if the local node is the dialer,
                 // since the remote address on an inbound connection may be specific
@@ -2247,8 +5233,21 @@ where
                 self.connection_updated(source, address, NodeStatus::Disconnected);
             }
 
+            HandlerEvent::RemoteProtocolsChanged { protocols } => {
+                self.remote_protocols.insert(connection, protocols);
+            }
+
             HandlerEvent::FindNodeReq { key, request_id } => {
-                let closer_peers = self.find_closest(&kbucket::Key::new(key), &source);
+                let mut closer_peers = self.find_closest(&self.hasher.key(key.clone()), &source);
+                self.truncate_response(0, &mut closer_peers, &mut Vec::new());
+
+                self.inbound_observer.observe(|| InboundRequestInfo {
+                    peer: source,
+                    request: InboundRequestType::FindNode,
+                    key,
+                    served_from_store: false,
+                    num_closer_peers: closer_peers.len(),
+                });
 
                 self.queued_events
                     .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
@@ -2271,30 +5270,38 @@ where
                 closer_peers,
                 query_id,
             } => {
+                let closer_peers = self.signed_records.verify(closer_peers);
                 self.discovered(&query_id, &source, closer_peers.iter());
+                self.crawled(&query_id, source, connection, &closer_peers);
             }
 
-            HandlerEvent::GetProvidersReq { key, request_id } => {
-                let provider_peers = self.provider_peers(&key, &source);
-                let closer_peers = self.find_closest(&kbucket::Key::new(key), &source);
-
-                self.queued_events
-                    .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
-                        request: InboundRequest::GetProvider {
-                            num_closer_peers: closer_peers.len(),
-                            num_provider_peers: provider_peers.len(),
-                        },
-                    }));
+            HandlerEvent::GetProvidersReq {
+                key,
+                provider_offset,
+                request_id,
+            } => {
+                if self.inbound_request_denied(
+                    source,
+                    connection,
+                    Some(request_id),
+                    RestrictedRequest::GetProviders,
+                ) {
+                    return;
+                }
 
-                self.queued_events.push_back(ToSwarm::NotifyHandler {
-                    peer_id: source,
-                    handler: NotifyHandler::One(connection),
-                    event: HandlerIn::GetProvidersRes {
-                        closer_peers,
-                        provider_peers,
+                let op = self.store.provider_records(&key);
+                self.run_store_op(
+                    op,
+                    Some((source, connection, request_id)),
+                    move |providers| StoreOutcome::InboundGetProviders {
+                        source,
+                        connection,
                         request_id,
+                        key,
+                        provider_offset,
+                        providers,
                     },
-                });
+                );
             }
 
             HandlerEvent::GetProvidersRes {
@@ -2302,19 +5309,38 @@ where
                 provider_peers,
                 query_id,
             } => {
+                let closer_peers = self.signed_records.verify(closer_peers);
+                let provider_peers = self.signed_records.verify(provider_peers);
                 let peers = closer_peers.iter().chain(provider_peers.iter());
                 self.discovered(&query_id, &source, peers);
                 if let Some(query) = self.queries.get_mut(&query_id) {
                     let stats = query.stats().clone();
+                    let mut quorum_reached = false;
                     if let QueryInfo::GetProviders {
                         ref key,
-                        ref mut providers_found,
+                        providers: ref mut found,
+                        quorum,
+                        confirmations,
                         ref mut step,
-                        ..
                     } = query.inner.info
                     {
-                        *providers_found += provider_peers.len();
-                        let providers = provider_peers.iter().map(|p| p.node_id).collect();
+                        let providers: HashSet<_> = provider_peers
+                            .iter()
+                            .filter(|p| {
+                                self.provider_filter.retain(
+                                    key,
+                                    &p.node_id,
+                                    &p.multiaddrs,
+                                    ProvidersFor::Local {
+                                        source: Some(source),
+                                    },
+                                )
+                            })
+                            .map(|p| p.node_id)
+                            .collect();
+                        let counts = confirm_providers(found, &providers);
+                        quorum_reached = quorum
+                            .is_some_and(|q| providers_quorum_reached(found, q, confirmations));
 
                         self.queued_events.push_back(ToSwarm::GenerateEvent(
                             Event::OutboundQueryProgressed {
@@ -2323,6 +5349,7 @@ where
                                     GetProvidersOk::FoundProviders {
                                         key: key.clone(),
                                         providers,
+                                        confirmations: counts,
                                     },
                                 )),
                                 step: step.clone(),
@@ -2331,6 +5358,9 @@ where
                         ));
                         *step = step.next();
                     }
+                    if quorum_reached {
+                        query.finish();
+                    }
                 }
             }
             HandlerEvent::QueryError { query_id, error } => {
@@ -2340,54 +5370,89 @@ where
                     "Request to peer in query failed with {:?}",
                     error
                 );
+                match &error {
+                    HandlerQueryErr::UnexpectedMessage => {
+                        self.report_misbehaviour(&source, Misbehaviour::UnexpectedMessage)
+                    }
+                    HandlerQueryErr::Io(e) if e.kind() == io::ErrorKind::TimedOut => {
+                        self.report_misbehaviour(&source, Misbehaviour::Timeout)
+                    }
+                    HandlerQueryErr::Io(_) => {}
+                }
                 // If the query to which the error relates is still active,
                 // signal the failure w.r.t. `source`.
                 if let Some(query) = self.queries.get_mut(&query_id) {
-                    query.on_failure(&source)
+                    if query.on_failure(&source) {
+                        let error = match error {
+                            HandlerQueryErr::UnexpectedMessage => {
+                                PutRecordPeerError::UnexpectedMessage
+                            }
+                            HandlerQueryErr::Io(e) if e.kind() == io::ErrorKind::TimedOut => {
+                                PutRecordPeerError::Timeout
+                            }
+                            HandlerQueryErr::Io(e) => PutRecordPeerError::Io(e.kind()),
+                        };
+                        if let Some(event) = on_put_record_failure(query, source, error) {
+                            self.queued_events.push_back(ToSwarm::GenerateEvent(event));
+                        }
+                    }
                 }
             }
 
-            HandlerEvent::AddProvider { key, provider } => {
+            HandlerEvent::AddProvider {
+                key,
+                provider,
+                expires,
+            } => {
                 // Only accept a provider record from a legitimate peer.
                 if provider.node_id != source {
                     return;
                 }
+                if self.inbound_request_denied(
+                    source,
+                    connection,
+                    None,
+                    RestrictedRequest::AddProvider,
+                ) {
+                    return;
+                }
+                if self.inbound_write_throttled(source, ThrottledRequest::AddProvider) {
+                    return;
+                }
+                if let Some(record) = &provider.signed_record {
+                    self.signed_records.insert((**record).clone());
+                }
 
-                self.provider_received(key, provider);
+                self.inbound_observer.observe(|| InboundRequestInfo {
+                    peer: source,
+                    request: InboundRequestType::AddProvider,
+                    key: key.to_vec(),
+                    served_from_store: false,
+                    num_closer_peers: 0,
+                });
+                self.provider_received(key, provider, expires);
             }
 
             HandlerEvent::GetRecord { key, request_id } => {
-                // Lookup the record locally.
-                let record = match self.store.get(&key) {
-                    Some(record) => {
-                        if record.is_expired(Instant::now()) {
-                            self.store.remove(&key);
-                            None
-                        } else {
-                            Some(record.into_owned())
-                        }
-                    }
-                    None => None,
-                };
-
-                let closer_peers = self.find_closest(&kbucket::Key::new(key), &source);
-
-                self.queued_events
-                    .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
-                        request: InboundRequest::GetRecord {
-                            num_closer_peers: closer_peers.len(),
-                            present_locally: record.is_some(),
-                        },
-                    }));
+                if self.inbound_request_denied(
+                    source,
+                    connection,
+                    Some(request_id),
+                    RestrictedRequest::GetRecord,
+                ) {
+                    return;
+                }
 
-                self.queued_events.push_back(ToSwarm::NotifyHandler {
-                    peer_id: source,
-                    handler: NotifyHandler::One(connection),
-                    event: HandlerIn::GetRecordRes {
-                        record,
-                        closer_peers,
+                // Lookup the record locally.
+                let op = self.store.get_record(&key);
+                self.run_store_op(op, Some((source, connection, request_id)), move |record| {
+                    StoreOutcome::InboundGetRecord {
+                        source,
+                        connection,
                         request_id,
-                    },
+                        key,
+                        record,
+                    }
                 });
             }
 
@@ -2396,62 +5461,187 @@ where
                 closer_peers,
                 query_id,
             } => {
+                let has_record = record.is_some();
+                // An invalid record is treated as if the record was not found at `source`.
+                let record = record.filter(|record| match self.namespaces.validate(record) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::debug!(peer=%source, record=?record.key, "Ignoring record: {e}");
+                        false
+                    }
+                });
+                if record.is_none() && has_record {
+                    self.report_misbehaviour(&source, Misbehaviour::InvalidRecord);
+                }
+
                 if let Some(query) = self.queries.get_mut(&query_id) {
                     let stats = query.stats().clone();
+                    let report = record.as_ref().is_some_and(|record| {
+                        query.inner.on_record_found(
+                            record,
+                            Some(source),
+                            &self.namespaces.resolvers,
+                            &self.caching,
+                            &self.hasher,
+                        )
+                    });
                     if let QueryInfo::GetRecord {
                         key,
                         ref mut step,
-                        ref mut found_a_record,
                         cache_candidates,
+                        agreement,
+                        agreeing_peers,
+                        agreed_record,
+                        ..
                     } = &mut query.inner.info
                     {
-                        if let Some(record) = record {
-                            *found_a_record = true;
-                            let record = PeerRecord {
-                                peer: Some(source),
-                                record,
-                            };
-
-                            self.queued_events.push_back(ToSwarm::GenerateEvent(
-                                Event::OutboundQueryProgressed {
-                                    id: query_id,
-                                    result: QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(
-                                        record,
-                                    ))),
-                                    step: step.clone(),
-                                    stats,
-                                },
-                            ));
-
-                            *step = step.next();
-                        } else {
-                            tracing::trace!(record=?key, %source, "Record not found at source");
-                            if let Caching::Enabled { max_peers } = self.caching {
-                                let source_key = kbucket::Key::from(source);
-                                let target_key = kbucket::Key::from(key.clone());
-                                let distance = source_key.distance(&target_key);
-                                cache_candidates.insert(distance, source);
-                                if cache_candidates.len() > max_peers as usize {
-                                    // TODO: `pop_last()` would be nice once stabilised.
-                                    // See https://github.com/rust-lang/rust/issues/62924.
-                                    let last =
-                                        *cache_candidates.keys().next_back().expect("len > 0");
-                                    cache_candidates.remove(&last);
-                                }
+                        if let (Some(agreement), Some(record)) = (agreement, &record) {
+                            let peers = agreeing_peers.entry(record.value.clone()).or_default();
+                            peers.push(source);
+                            if peers.len() >= agreement.get() && agreed_record.is_none() {
+                                tracing::debug!(record=?key, "Peers agree on the value of the record, finishing query");
+                                *agreed_record = Some(record.clone());
+                            }
+                        }
+
+                        match record {
+                            Some(record) if report => {
+                                let record = PeerRecord {
+                                    peer: Some(source),
+                                    record,
+                                };
+
+                                self.queued_events.push_back(ToSwarm::GenerateEvent(
+                                    Event::OutboundQueryProgressed {
+                                        id: query_id,
+                                        result: QueryResult::GetRecord(Ok(
+                                            GetRecordOk::FoundRecord(record),
+                                        )),
+                                        step: step.clone(),
+                                        stats,
+                                    },
+                                ));
+
+                                *step = step.next();
+                            }
+                            Some(_) => {}
+                            None => {
+                                tracing::trace!(record=?key, %source, "Record not found at source");
+                                insert_cache_candidate(
+                                    cache_candidates,
+                                    &self.caching,
+                                    &self.hasher,
+                                    key,
+                                    source,
+                                );
                             }
                         }
+
+                        if agreed_record.is_some() {
+                            query.finish();
+                        }
                     }
                 }
 
+                let closer_peers = self.signed_records.verify(closer_peers);
                 self.discovered(&query_id, &source, closer_peers.iter());
             }
 
             HandlerEvent::PutRecord { record, request_id } => {
+                if self.inbound_request_denied(
+                    source,
+                    connection,
+                    Some(request_id),
+                    RestrictedRequest::PutRecord,
+                ) {
+                    return;
+                }
+                if self.inbound_write_throttled(source, ThrottledRequest::PutRecord) {
+                    self.queued_events.push_back(ToSwarm::NotifyHandler {
+                        peer_id: source,
+                        handler: NotifyHandler::One(connection),
+                        event: HandlerIn::Reset(request_id),
+                    });
+                    return;
+                }
+
+                self.inbound_observer.observe(|| InboundRequestInfo {
+                    peer: source,
+                    request: InboundRequestType::PutRecord,
+                    key: record.key.to_vec(),
+                    served_from_store: false,
+                    num_closer_peers: 0,
+                });
                 self.record_received(source, connection, request_id, record);
             }
 
-            HandlerEvent::PutRecordRes { query_id, .. } => {
+            HandlerEvent::GetProviderSummaryReq { range, request_id } => {
+                if !self.provider_summaries {
+                    tracing::debug!(peer=%source, "Provider summaries disabled, resetting request");
+                    self.queued_events.push_back(ToSwarm::NotifyHandler {
+                        peer_id: source,
+                        handler: NotifyHandler::One(connection),
+                        event: HandlerIn::Reset(request_id),
+                    });
+                    return;
+                }
+
+                let op = self.store.provider_record_keys();
+                self.run_store_op(op, Some((source, connection, request_id)), move |keys| {
+                    StoreOutcome::InboundProviderSummary {
+                        source,
+                        connection,
+                        request_id,
+                        range,
+                        keys,
+                    }
+                });
+            }
+
+            HandlerEvent::GetProviderSummaryRes { summary, query_id } => {
+                if let Some(query) = self.queries.get_mut(&query_id) {
+                    if let QueryInfo::GetProviderSummary {
+                        range,
+                        summary: ref mut result,
+                        ..
+                    } = &mut query.inner.info
+                    {
+                        if summary.range() == range {
+                            *result = Some(summary);
+                        } else {
+                            tracing::debug!(peer=%source, "Provider summary for unexpected range");
+                        }
+                    }
+                    query.on_success(&source, vec![]);
+                }
+            }
+
+            HandlerEvent::PutRecordRes {
+                query_id,
+                key,
+                value,
+            } => {
                 if let Some(query) = self.queries.get_mut(&query_id) {
+                    // The remote acknowledges the storage of the record by
+                    // echoing it, any other record was not stored.
+                    let acknowledged = match &query.inner.info {
+                        QueryInfo::PutRecord { record, .. } => {
+                            key == record.key && value == record.value
+                        }
+                        _ => true,
+                    };
+                    if !acknowledged {
+                        if query.on_failure(&source) {
+                            if let Some(event) = on_put_record_failure(
+                                query,
+                                source,
+                                PutRecordPeerError::ValueMismatch,
+                            ) {
+                                self.queued_events.push_back(ToSwarm::GenerateEvent(event));
+                            }
+                        }
+                        return;
+                    }
                     query.on_success(&source, vec![]);
                     if let QueryInfo::PutRecord {
                         phase: PutRecordPhase::PutRecord { success, .. },
@@ -2489,21 +5679,64 @@ where
     ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
         let now = Instant::now();
 
+        // Apply the commands sent via query handles in the meantime.
+        self.query_commands.waker.register(cx.waker());
+        let commands = std::mem::take(
+            &mut *self
+                .query_commands
+                .commands
+                .lock()
+                .expect("lock not to be poisoned"),
+        );
+        for (id, command) in commands {
+            self.on_query_command(id, command);
+        }
+
+        self.poll_pending_retries(cx);
+
+        // Continue with the store operations that completed in the meantime.
+        while let Poll::Ready((result, request)) = self.pending_store_ops.poll_unpin(cx) {
+            match result {
+                Ok(outcome) => self.on_store_outcome(outcome),
+                Err(e) => {
+                    tracing::warn!("Record store operation failed: {e}");
+                    if let Some((peer_id, connection, request_id)) = request {
+                        self.queued_events.push_back(ToSwarm::NotifyHandler {
+                            peer_id,
+                            handler: NotifyHandler::One(connection),
+                            event: HandlerIn::Reset(request_id),
+                        });
+                    }
+                }
+            }
+        }
+
+        // Apply a mode change deferred to avoid flapping, if it still holds.
+        if let Some(delay) = self.deferred_mode_switch.as_mut() {
+            if delay.poll_unpin(cx).is_ready() {
+                self.deferred_mode_switch = None;
+                if self.auto_mode {
+                    self.determine_mode();
+                }
+            }
+        }
+
         // Calculate the available capacity for queries triggered by background jobs.
         let mut jobs_query_capacity = JOBS_MAX_QUERIES.saturating_sub(self.queries.size());
 
+        if self.background_jobs_paused {
+            jobs_query_capacity = 0;
+        }
+
         // Run the periodic provider announcement job.
-        if let Some(mut job) = self.add_provider_job.take() {
-            let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
-            for i in 0..num {
-                if let Poll::Ready(r) = job.poll(cx, &mut self.store, now) {
-                    self.start_add_provider(r.key, AddProviderContext::Republish)
-                } else {
-                    jobs_query_capacity -= i;
-                    break;
-                }
+        let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
+        for i in 0..num {
+            if let Poll::Ready(key) = self.add_provider_job.poll(cx, &mut self.store, now) {
+                self.start_add_provider(key, AddProviderContext::Republish)
+            } else {
+                jobs_query_capacity -= i;
+                break;
             }
-            self.add_provider_job = Some(job);
         }
 
         // Run the periodic record replication / publication job.
@@ -2525,6 +5758,27 @@ where
             self.put_record_job = Some(job);
         }
 
+        // Run the replication of records to peers that joined the routing table.
+        if let Some(mut job) = self.replicate_on_join_job.take() {
+            let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
+            for _ in 0..num {
+                let kbuckets = &mut self.kbuckets;
+                let k = self.queries.config().replication_factor.get();
+                let is_close = |key: &record::Key, peer: &PeerId| {
+                    kbuckets
+                        .closest_keys(&self.hasher.key(key.clone()))
+                        .take(k)
+                        .any(|p| p.preimage() == peer)
+                };
+                if let Poll::Ready((r, peers)) = job.poll(cx, &mut self.store, now, is_close) {
+                    self.start_replicate_to(r, peers)
+                } else {
+                    break;
+                }
+            }
+            self.replicate_on_join_job = Some(job);
+        }
+
         // Poll bootstrap periodically and automatically.
         if let Poll::Ready(()) = self.bootstrap_status.poll_next_bootstrap(cx) {
             if let Err(e) = self.bootstrap() {
@@ -2532,15 +5786,108 @@ where
             }
         }
 
+        // Refresh stale buckets, if enabled.
+        if let Poll::Ready(()) = self.bucket_refreshes.poll_check(cx) {
+            let non_empty = self
+                .kbuckets
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| !b.is_empty())
+                .map(|(i, _)| i as u32)
+                .collect::<Vec<_>>();
+            let stale = self.bucket_refreshes.stale_buckets(non_empty.into_iter());
+            if !stale.is_empty() {
+                tracing::debug!(buckets=?stale, "Refreshing stale buckets");
+                if let Err(e) = self.start_refresh(stale, QueryPriority::Background) {
+                    tracing::warn!("Failed to refresh stale buckets: {e}");
+                }
+            }
+            // Register the rescheduled check.
+            let _ = self.bucket_refreshes.poll_check(cx);
+        }
+
+        // Revalidate the addresses of a disconnected peer, if enabled.
+        if let Poll::Ready(()) = self.address_revalidation.poll_next(cx) {
+            let disconnected = self
+                .kbuckets
+                .iter()
+                .flat_map(|bucket| {
+                    bucket
+                        .iter()
+                        .filter(|e| e.status == NodeStatus::Disconnected)
+                        .map(|e| *e.node.key.preimage())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            if let Some(peer_id) = self.address_revalidation.next_peer(disconnected) {
+                tracing::debug!(peer=%peer_id, "Revalidating addresses of peer");
+                self.queued_events.push_back(ToSwarm::Dial {
+                    opts: DialOpts::peer_id(peer_id).build(),
+                });
+            }
+            // Register the rescheduled revalidation.
+            let _ = self.address_revalidation.poll_next(cx);
+        }
+
+        // Re-dial the bootstrap peers while the node is isolated.
+        if !self.bootstrap_peers.is_empty() {
+            let isolated =
+                self.connected_peers.is_empty() || self.kbuckets.iter().all(|b| b.is_empty());
+            match self.bootstrap_peers.update(isolated) {
+                Some(bootstrap_peers::Change::Lost) => {
+                    tracing::debug!("Lost connectivity, re-dialing bootstrap peers");
+                    self.queued_events
+                        .push_back(ToSwarm::GenerateEvent(Event::BootstrapConnectivityLost));
+                }
+                Some(bootstrap_peers::Change::Regained) => {
+                    self.queued_events
+                        .push_back(ToSwarm::GenerateEvent(Event::BootstrapConnectivityRegained));
+                }
+                None => {}
+            }
+            if let Poll::Ready(()) = self.bootstrap_peers.poll_redial(cx) {
+                let peers = self
+                    .bootstrap_peers
+                    .iter()
+                    .map(|(peer, addresses)| (*peer, addresses.clone()))
+                    .collect::<Vec<_>>();
+                for (peer, addresses) in peers {
+                    for address in &addresses {
+                        self.add_address(&peer, address.clone());
+                    }
+                    tracing::debug!(%peer, "Re-dialing bootstrap peer");
+                    self.queued_events.push_back(ToSwarm::Dial {
+                        opts: DialOpts::peer_id(peer).addresses(addresses).build(),
+                    });
+                }
+                // Register the rescheduled re-dial.
+                let _ = self.bootstrap_peers.poll_redial(cx);
+            }
+        }
+
         loop {
             // Drain queued events first.
             if let Some(event) = self.queued_events.pop_front() {
+                #[cfg(feature = "stream")]
+                if let ToSwarm::GenerateEvent(event) = &event {
+                    self.route_to_query_stream(event);
+                }
                 return Poll::Ready(event);
             }
 
             // Drain applied pending entries from the routing table.
-            if let Some(entry) = self.kbuckets.take_applied_pending() {
+            while let Some(entry) = self.kbuckets.take_applied_pending() {
                 let kbucket::Node { key, value } = entry.inserted;
+                if let Some(evicted) = &entry.evicted {
+                    self.last_seen.remove(evicted.key.preimage());
+                    self.routing_table_updated(
+                        *evicted.key.preimage(),
+                        RoutingTableAction::Replaced {
+                            by: *key.preimage(),
+                        },
+                    );
+                }
+                self.routing_table_updated(*key.preimage(), RoutingTableAction::Inserted);
                 let event = Event::RoutingUpdated {
                     bucket_range: self
                         .kbuckets
@@ -2552,7 +5899,16 @@ where
                     addresses: value,
                     old_peer: entry.evicted.map(|n| n.key.into_preimage()),
                 };
-                return Poll::Ready(ToSwarm::GenerateEvent(event));
+                if let Some(event) = self.routing_updated(event) {
+                    self.queued_events.push_back(ToSwarm::GenerateEvent(event));
+                }
+            }
+
+            // Report the coalesced updates of the routing table.
+            if let Poll::Ready(updates) = self.routing_updates.poll(cx) {
+                return Poll::Ready(ToSwarm::GenerateEvent(Event::RoutingUpdatesCoalesced {
+                    updates,
+                }));
             }
 
             // Look for a finished query.
@@ -2560,30 +5916,49 @@ where
                 match self.queries.poll(now) {
                     QueryPoolState::Finished(q) => {
                         if let Some(event) = self.query_finished(q) {
+                            #[cfg(feature = "stream")]
+                            self.route_to_query_stream(&event);
                             return Poll::Ready(ToSwarm::GenerateEvent(event));
                         }
                     }
                     QueryPoolState::Timeout(q) => {
+                        let Some(q) = self.retry_query(q) else {
+                            // Register the backoff of the retry on the next poll.
+                            cx.waker().wake_by_ref();
+                            continue;
+                        };
                         if let Some(event) = self.query_timeout(q) {
+                            #[cfg(feature = "stream")]
+                            self.route_to_query_stream(&event);
                             return Poll::Ready(ToSwarm::GenerateEvent(event));
                         }
                     }
                     QueryPoolState::Waiting(Some((query, peer_id))) => {
-                        let event = query.inner.info.to_request(query.id());
+                        let mut event = query.inner.info.to_request(query.id());
+                        if let HandlerIn::AddProvider { provider, .. } = &mut event {
+                            self.signed_records.attach(provider);
+                        }
+                        query.on_request(peer_id, query.inner.info.rpc());
                         // TODO: AddProvider requests yield no response, so the query completes
                         // as soon as all requests have been sent. However, the handler should
                         // better emit an event when the request has been sent (and report
                         // an error if sending fails), instead of immediately reporting
                         // "success" somewhat prematurely here.
-                        if let QueryInfo::AddProvider {
-                            phase: AddProviderPhase::AddProvider { .. },
-                            ..
-                        } = &query.inner.info
-                        {
-                            query.on_success(&peer_id, vec![])
-                        }
+                        //
+                        // Requests to peers that are not connected are only considered sent
+                        // once the connection is established, see `preload_new_handler`.
+                        let is_add_provider = matches!(
+                            &query.inner.info,
+                            QueryInfo::AddProvider {
+                                phase: AddProviderPhase::AddProvider { .. },
+                                ..
+                            }
+                        );
 
                         if self.connected_peers.contains(&peer_id) {
+                            if is_add_provider {
+                                query.on_success(&peer_id, vec![])
+                            }
                             self.queued_events.push_back(ToSwarm::NotifyHandler {
                                 peer_id,
                                 event,
@@ -2594,6 +5969,8 @@ where
                             self.queued_events.push_back(ToSwarm::Dial {
                                 opts: DialOpts::peer_id(peer_id).build(),
                             });
+                        } else if is_add_provider {
+                            query.on_success(&peer_id, vec![])
                         }
                     }
                     QueryPoolState::Waiting(None) | QueryPoolState::Idle => break,
@@ -2605,6 +5982,8 @@ where
             // be polled again later.
             if self.queued_events.is_empty() {
                 self.no_events_waker = Some(cx.waker().clone());
+                #[cfg(feature = "stream")]
+                self.poll_query_streams(cx);
 
                 return Poll::Pending;
             }
@@ -2616,7 +5995,11 @@ where
         let external_addresses_changed = self.external_addresses.on_swarm_event(&event);
 
         if self.auto_mode && external_addresses_changed {
-            self.determine_mode_from_external_addresses();
+            self.determine_mode();
+        }
+
+        if !self.pending_advertisements.is_empty() && self.is_dialable() {
+            self.start_pending_advertisements();
         }
 
         match event {
@@ -2696,6 +6079,8 @@ pub enum Event {
 
     /// The routing table has been updated with a new peer and / or
     /// address, thereby possibly evicting another peer.
+    ///
+    /// Not emitted if updates are coalesced, see [`Config::set_coalesced_routing_updates`].
     RoutingUpdated {
         /// The ID of the peer that was added or updated.
         peer: PeerId,
@@ -2712,6 +6097,24 @@ pub enum Event {
         old_peer: Option<PeerId>,
     },
 
+    /// The routing table has been updated since the last interval,
+    /// see [`Config::set_coalesced_routing_updates`].
+    RoutingUpdatesCoalesced { updates: RoutingUpdates },
+
+    /// A peer has entered or left the routing table.
+    ///
+    /// In contrast to [`Event::RoutingUpdated`], this is emitted for every change
+    /// of the peers in the routing table, including removals, regardless of
+    /// [`Config::set_coalesced_routing_updates`]. This allows to mirror the peers
+    /// of the routing table externally.
+    RoutingTableUpdated {
+        peer: PeerId,
+        /// The index of the bucket of the peer. The bucket with index `i` holds
+        /// the peers at a distance in the range `[2^i, 2^(i+1))` from the local key.
+        bucket: u32,
+        action: RoutingTableAction,
+    },
+
     /// A peer has connected for whom no listen address is known.
     ///
     /// If the peer is to be added to the routing table, a known
@@ -2744,15 +6147,54 @@ pub enum Event {
     /// the k-bucket of `peer`.
     PendingRoutablePeer { peer: PeerId, address: Multiaddr },
 
+    /// A peer or an address of a peer has not been added to the routing table
+    /// because the routing table already holds the maximum number of peers with
+    /// an address in the same network, see [`Config::set_max_peers_per_ip_prefix`].
+    IpDiversityExceeded { peer: PeerId, address: Multiaddr },
+
+    /// An inbound request has been dropped because it exceeded a rate limit,
+    /// see [`Config::set_inbound_write_rate_limit`].
+    InboundRequestThrottled {
+        peer: PeerId,
+        request: ThrottledRequest,
+        limit: RateLimit,
+    },
+
+    /// An inbound request has been denied by the [`RequestAuthorizer`],
+    /// see [`Config::set_request_authorizer`].
+    InboundRequestDenied {
+        peer: PeerId,
+        request: RestrictedRequest,
+    },
+
+    /// A record found by a lookup has been written back to the peers
+    /// closest to its key, as configured by [`Caching::Automatic`].
+    RecordCached {
+        key: record::Key,
+        /// The peers that successfully stored the record.
+        peers: Vec<PeerId>,
+    },
+
     /// This peer's mode has been updated automatically.
     ///
-    /// This happens in response to an external
-    /// address being added or removed.
-    ModeChanged { new_mode: Mode },
+    /// This happens in response to an external address being added or removed,
+    /// or to a change of the reachability reported via [`Behaviour::set_reachability`].
+    ModeChanged {
+        new_mode: Mode,
+        /// Why the mode changed.
+        reason: ModeChangeReason,
+    },
+
+    /// The node has become isolated, i.e. it has no connections or its routing
+    /// table is empty, and re-dials the peers added via [`Behaviour::add_bootstrap_peer`].
+    BootstrapConnectivityLost,
+
+    /// The node is no longer isolated after [`Event::BootstrapConnectivityLost`].
+    BootstrapConnectivityRegained,
 }
 
 /// Information about progress events.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ProgressStep {
     /// The index into the event
     pub count: NonZeroUsize,
@@ -2782,6 +6224,24 @@ impl ProgressStep {
     }
 }
 
+/// An inbound request subject to rate limiting, see [`Event::InboundRequestThrottled`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottledRequest {
+    /// A `PUT_VALUE` request.
+    PutRecord,
+    /// An `ADD_PROVIDER` request.
+    AddProvider,
+}
+
+/// The rate limit exceeded by an inbound request, see [`Event::InboundRequestThrottled`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimit {
+    /// See [`Config::set_inbound_write_rate_limit`].
+    PerPeer,
+    /// See [`Config::set_global_inbound_write_rate_limit`].
+    Global,
+}
+
 /// Information about a received and handled inbound request.
 #[derive(Debug, Clone)]
 pub enum InboundRequest {
@@ -2813,6 +6273,10 @@ pub enum InboundRequest {
         connection: ConnectionId,
         record: Option<Record>,
     },
+    /// Request for a summary of the provided keys within a range of the keyspace.
+    ///
+    /// Only emitted if [`Config::set_provider_summaries`] is enabled.
+    GetProviderSummary { range: KeyspaceRange },
 }
 
 /// The results of Kademlia queries.
@@ -2839,8 +6303,22 @@ pub enum QueryResult {
     /// The result of [`Behaviour::put_record`].
     PutRecord(PutRecordResult),
 
+    /// Intermediate progress of [`Behaviour::put_record`] and [`Behaviour::put_record_to`],
+    /// reported for every peer that failed to store the record.
+    PutRecordPeerFailed(PutRecordPeerFailure),
+
     /// The result of a (automatic) republishing of a (value-)record.
     RepublishRecord(PutRecordResult),
+
+    /// The result of [`Behaviour::get_provider_summary`].
+    GetProviderSummary(GetProviderSummaryResult),
+
+    /// The result of [`Behaviour::crawl`].
+    Crawl(CrawlResult),
+
+    /// The result of [`Behaviour::refresh_bucket`], [`Behaviour::refresh_all`]
+    /// or an automatic refresh of stale buckets.
+    Refresh(RefreshResult),
 }
 
 /// The result of [`Behaviour::get_record`].
@@ -2862,6 +6340,14 @@ pub enum GetRecordOk {
         /// after selecting one of the returned records.
         cache_candidates: BTreeMap<kbucket::Distance, PeerId>,
     },
+    /// The query finished early as the configured number of peers returned
+    /// the same value, see [`QueryOpts::finish_on_agreement`].
+    AgreementReached {
+        /// The record whose value the peers agree on.
+        record: Record,
+        /// The peers that returned the value.
+        peers: Vec<PeerId>,
+    },
 }
 
 /// The error result of [`Behaviour::get_record`].
@@ -2910,6 +6396,10 @@ pub type PutRecordResult = Result<PutRecordOk, PutRecordError>;
 #[derive(Debug, Clone)]
 pub struct PutRecordOk {
     pub key: record::Key,
+    /// [`PeerId`]s of the peers that acknowledged storing the record.
+    pub success: Vec<PeerId>,
+    /// The peers that failed to store the record before the quorum was reached.
+    pub failed: Vec<(PeerId, PutRecordPeerError)>,
 }
 
 /// The error result of [`Behaviour::put_record`].
@@ -2920,6 +6410,8 @@ pub enum PutRecordError {
         key: record::Key,
         /// [`PeerId`]s of the peers the record was successfully stored on.
         success: Vec<PeerId>,
+        /// The peers that failed to store the record.
+        failed: Vec<(PeerId, PutRecordPeerError)>,
         quorum: NonZeroUsize,
     },
     #[error("the request timed out")]
@@ -2927,6 +6419,8 @@ pub enum PutRecordError {
         key: record::Key,
         /// [`PeerId`]s of the peers the record was successfully stored on.
         success: Vec<PeerId>,
+        /// The peers that failed to store the record.
+        failed: Vec<(PeerId, PutRecordPeerError)>,
         quorum: NonZeroUsize,
     },
 }
@@ -2950,6 +6444,38 @@ impl PutRecordError {
     }
 }
 
+/// The reason for which a peer did not store the record of a [`Behaviour::put_record`] query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum PutRecordPeerError {
+    /// The peer acknowledged a record other than the one sent, i.e. refused to store it.
+    #[error("the peer acknowledged a different record")]
+    ValueMismatch,
+    /// The peer answered with a message other than a `PUT_VALUE` acknowledgement.
+    #[error("the peer answered with an unexpected message")]
+    UnexpectedMessage,
+    /// The request failed with an I/O error, e.g. because the peer reset the
+    /// stream instead of acknowledging the record.
+    #[error("the request failed with an I/O error: {0:?}")]
+    Io(io::ErrorKind),
+    /// The peer could not be dialed or disconnected before answering.
+    #[error("the peer could not be reached")]
+    Unreachable,
+    /// The peer did not answer in time, or had not answered yet when the
+    /// query timed out.
+    #[error("the peer did not answer in time")]
+    Timeout,
+}
+
+/// A peer that failed to store the record of a [`Behaviour::put_record`] query,
+/// see [`QueryResult::PutRecordPeerFailed`].
+#[derive(Debug, Clone)]
+pub struct PutRecordPeerFailure {
+    pub key: record::Key,
+    /// The peer that failed to store the record.
+    pub peer: PeerId,
+    pub error: PutRecordPeerError,
+}
+
 /// The result of [`Behaviour::bootstrap`].
 pub type BootstrapResult = Result<BootstrapOk, BootstrapError>;
 
@@ -2970,6 +6496,86 @@ pub enum BootstrapError {
     },
 }
 
+/// The result of a bucket refresh, see [`Behaviour::refresh_all`].
+pub type RefreshResult = Result<RefreshOk, RefreshError>;
+
+/// The successful result of a bucket refresh, see [`Behaviour::refresh_all`].
+#[derive(Debug, Clone)]
+pub struct RefreshOk {
+    /// The index of the refreshed bucket.
+    pub bucket: u32,
+    /// The number of buckets remaining to be refreshed by the query.
+    pub num_remaining: u32,
+}
+
+/// The error result of a bucket refresh, see [`Behaviour::refresh_all`].
+#[derive(Debug, Clone, Error)]
+pub enum RefreshError {
+    /// The lookup refreshing a bucket timed out. The query continues with
+    /// the remaining buckets, if any.
+    #[error("the request timed out")]
+    Timeout { bucket: u32, num_remaining: u32 },
+}
+
+/// A change of the peers in the routing table, see [`Event::RoutingTableUpdated`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingTableAction {
+    /// The peer has been inserted into the routing table.
+    Inserted,
+    /// The peer has been replaced by the given peer that was pending insertion,
+    /// because the peer did not reconnect in time, i.e. was unresponsive.
+    Replaced { by: PeerId },
+    /// The peer has been removed from the routing table.
+    Evicted { reason: EvictionReason },
+}
+
+/// The reason for the removal of a peer from the routing table,
+/// see [`RoutingTableAction::Evicted`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EvictionReason {
+    /// The peer has been removed via [`Behaviour::remove_peer`] or with its
+    /// last address via [`Behaviour::remove_address`].
+    Manual,
+    /// The peer repeatedly failed to be reached when revalidating its addresses,
+    /// see [`Config::set_address_revalidation_interval`].
+    Unreachable,
+}
+
+/// The result of [`Behaviour::crawl`].
+pub type CrawlResult = Result<CrawlOk, CrawlError>;
+
+/// The successful result of [`Behaviour::crawl`].
+#[derive(Debug, Clone)]
+pub enum CrawlOk {
+    /// A peer has been learned of for the first time during the crawl,
+    /// i.e. it was returned by another peer in response to a lookup.
+    PeerDiscovered {
+        peer: PeerId,
+        /// The addresses of the peer, as reported by the other peer.
+        addresses: Vec<Multiaddr>,
+    },
+    /// A peer has answered a lookup of the crawl for the first time,
+    /// i.e. it is a reachable DHT server.
+    PeerCrawled {
+        peer: PeerId,
+        /// The protocols supported by the peer on the connection it answered on,
+        /// as far as known, e.g. through `libp2p-identify`. Empty if unknown.
+        protocols: Vec<StreamProtocol>,
+    },
+    /// A lookup of the crawl has finished.
+    LookupFinished { target: PeerId, num_remaining: u32 },
+}
+
+/// The error result of [`Behaviour::crawl`].
+#[derive(Debug, Clone, Error)]
+pub enum CrawlError {
+    /// A lookup of the crawl timed out. The crawl continues with
+    /// the remaining lookups, if any.
+    #[error("the request timed out")]
+    Timeout { target: PeerId, num_remaining: u32 },
+}
+
 /// The result of [`Behaviour::get_closest_peers`].
 pub type GetClosestPeersResult = Result<GetClosestPeersOk, GetClosestPeersError>;
 
@@ -3014,6 +6620,10 @@ pub enum GetProvidersOk {
         key: record::Key,
         /// The new set of providers discovered.
         providers: HashSet<PeerId>,
+        /// The number of distinct peers that returned each of the `providers`
+        /// so far during the query, including the local node for providers
+        /// stored locally.
+        confirmations: HashMap<PeerId, usize>,
     },
     FinishedWithNoAdditionalRecord {
         closest_peers: Vec<PeerId>,
@@ -3028,6 +6638,15 @@ pub enum GetProvidersError {
         key: record::Key,
         closest_peers: Vec<PeerId>,
     },
+    #[error("the quorum failed; needed {quorum} confirmed providers")]
+    QuorumFailed {
+        key: record::Key,
+        /// The distinct providers found.
+        providers: HashSet<PeerId>,
+        /// The number of distinct peers that returned each of the `providers`.
+        confirmations: HashMap<PeerId, usize>,
+        quorum: NonZeroUsize,
+    },
 }
 
 impl GetProvidersError {
@@ -3035,6 +6654,7 @@ impl GetProvidersError {
     pub fn key(&self) -> &record::Key {
         match self {
             GetProvidersError::Timeout { key, .. } => key,
+            GetProvidersError::QuorumFailed { key, .. } => key,
         }
     }
 
@@ -3043,6 +6663,38 @@ impl GetProvidersError {
     pub fn into_key(self) -> record::Key {
         match self {
             GetProvidersError::Timeout { key, .. } => key,
+            GetProvidersError::QuorumFailed { key, .. } => key,
+        }
+    }
+}
+
+/// The result of [`Behaviour::get_provider_summary`].
+pub type GetProviderSummaryResult = Result<GetProviderSummaryOk, GetProviderSummaryError>;
+
+/// The successful result of [`Behaviour::get_provider_summary`].
+#[derive(Debug, Clone)]
+pub struct GetProviderSummaryOk {
+    /// The peer that sent the summary.
+    pub peer: PeerId,
+    pub summary: ProviderSummary,
+}
+
+/// The error result of [`Behaviour::get_provider_summary`].
+#[derive(Debug, Clone, Error)]
+pub enum GetProviderSummaryError {
+    /// The peer could not be reached or does not serve provider summaries.
+    #[error("the peer did not provide a summary")]
+    Unavailable { peer: PeerId, range: KeyspaceRange },
+    #[error("the request timed out")]
+    Timeout { peer: PeerId, range: KeyspaceRange },
+}
+
+impl GetProviderSummaryError {
+    /// Gets the peer for which the operation failed.
+    pub fn peer(&self) -> &PeerId {
+        match self {
+            GetProviderSummaryError::Unavailable { peer, .. } => peer,
+            GetProviderSummaryError::Timeout { peer, .. } => peer,
         }
     }
 }
@@ -3084,6 +6736,7 @@ impl From<kbucket::EntryView<kbucket::Key<PeerId>, Addresses>> for KadPeer {
         KadPeer {
             node_id: e.node.key.into_preimage(),
             multiaddrs: e.node.value.into_vec(),
+            signed_record: None,
             connection_ty: match e.status {
                 NodeStatus::Connected => ConnectionType::Connected,
                 NodeStatus::Disconnected => ConnectionType::NotConnected,
@@ -3092,6 +6745,88 @@ impl From<kbucket::EntryView<kbucket::Key<PeerId>, Addresses>> for KadPeer {
     }
 }
 
+//////////////////////////////////////////////////////////////////////////////
+// Internal record store state
+
+/// The maximum number of pending operations on an [`AsyncRecordStore`].
+const MAX_PENDING_STORE_OPS: usize = 1024;
+
+/// The size reserved for the fields of a page of a `GET_PROVIDERS` response besides the peers.
+const PROVIDER_PAGE_OVERHEAD: usize = 64;
+
+/// Polls an operation on the record store once, without registering for wake-ups.
+fn poll_store_op<T>(op: &mut StoreFuture<T>) -> Poll<T> {
+    op.poll_unpin(&mut Context::from_waker(noop_waker_ref()))
+}
+
+/// The outcome of an operation on the record store, together with the
+/// state needed to continue with it.
+enum StoreOutcome {
+    /// A record was looked up locally for a [`QueryInfo::GetRecord`] query.
+    GetRecord {
+        query_id: QueryId,
+        record: Option<Record>,
+    },
+    /// Providers were looked up locally for a [`QueryInfo::GetProviders`] query.
+    GetProviders {
+        query_id: QueryId,
+        providers: Vec<ProviderRecord>,
+    },
+    /// A record was looked up locally to remove it if published by the local node.
+    RemoveRecord { record: Option<Record> },
+    /// A record or provider record of the local node was stored.
+    Stored {
+        key: record::Key,
+        result: store::Result<()>,
+    },
+    /// The record stored locally for the key of a record received from a remote
+    /// was looked up before storing the received record.
+    InboundPutRecordExisting {
+        source: PeerId,
+        connection: ConnectionId,
+        request_id: RequestId,
+        record: Record,
+        existing: Option<Record>,
+    },
+    /// A record received from a remote was stored.
+    InboundPutRecord {
+        source: PeerId,
+        connection: ConnectionId,
+        request_id: RequestId,
+        record: Record,
+        result: store::Result<()>,
+    },
+    /// A provider record received from a remote was stored.
+    InboundAddProvider { result: store::Result<()> },
+    /// A record requested by a remote was looked up locally.
+    InboundGetRecord {
+        source: PeerId,
+        connection: ConnectionId,
+        request_id: RequestId,
+        key: record::Key,
+        record: Option<Record>,
+    },
+    /// Providers requested by a remote were looked up locally.
+    InboundGetProviders {
+        source: PeerId,
+        connection: ConnectionId,
+        request_id: RequestId,
+        key: record::Key,
+        provider_offset: Option<u32>,
+        providers: Vec<ProviderRecord>,
+    },
+    /// The provider keys to summarise for a remote were looked up locally.
+    InboundProviderSummary {
+        source: PeerId,
+        connection: ConnectionId,
+        request_id: RequestId,
+        range: KeyspaceRange,
+        keys: Vec<record::Key>,
+    },
+    /// A record or provider record was removed.
+    Removed,
+}
+
 //////////////////////////////////////////////////////////////////////////////
 // Internal query state
 
@@ -3105,6 +6840,21 @@ struct QueryInner {
     /// A request is pending if the targeted peer is not currently connected
     /// and these requests are sent as soon as a connection to the peer is established.
     pending_rpcs: SmallVec<[(PeerId, HandlerIn); K_VALUE.get()]>,
+    /// The record to write back to the cache candidates once a
+    /// [`QueryInfo::GetRecord`] query finishes, if [`Caching::Automatic`].
+    cache_record: Option<Record>,
+    /// The retries of the query if it times out, see [`RetryPolicy`].
+    retry: Option<QueryRetry>,
+}
+
+/// The retries of a query that times out.
+struct QueryRetry {
+    policy: RetryPolicy,
+    /// The number of retries so far.
+    retries: u32,
+    /// The options of the query applied to every retry.
+    timeout: Option<Duration>,
+    trace: bool,
 }
 
 impl QueryInner {
@@ -3113,10 +6863,93 @@ impl QueryInner {
             info,
             addresses: Default::default(),
             pending_rpcs: SmallVec::default(),
+            cache_record: None,
+            retry: None,
+        }
+    }
+
+    /// Compares a record found by a [`QueryInfo::GetRecord`] query at the given peer,
+    /// or locally if `None`, with the best record found so far, returning whether the
+    /// record is to be reported, i.e. is not superseded by the best record.
+    ///
+    /// The peers that returned a superseded record become cache candidates and, with
+    /// automatic caching, a record superseding another one is cached at them.
+    fn on_record_found(
+        &mut self,
+        record: &Record,
+        peer: Option<PeerId>,
+        resolvers: &ConflictResolvers,
+        caching: &Caching,
+        hasher: &KeyHashing,
+    ) -> bool {
+        let QueryInfo::GetRecord {
+            key,
+            found_a_record,
+            best_record,
+            best_record_peers,
+            cache_candidates,
+            ..
+        } = &mut self.info
+        else {
+            return false;
+        };
+        *found_a_record = true;
+
+        let ordering = best_record
+            .as_ref()
+            .map_or(Ordering::Greater, |best| resolvers.compare(record, best));
+        match ordering {
+            Ordering::Less => {
+                tracing::debug!(record=?key, ?peer, "Ignoring superseded record");
+                if let Some(peer) = peer {
+                    insert_cache_candidate(cache_candidates, caching, hasher, key, peer);
+                }
+                false
+            }
+            Ordering::Equal => {
+                best_record_peers.extend(peer);
+                true
+            }
+            Ordering::Greater => {
+                let supersedes = best_record.replace(record.clone()).is_some();
+                for outdated in std::mem::take(best_record_peers) {
+                    insert_cache_candidate(cache_candidates, caching, hasher, key, outdated);
+                }
+                best_record_peers.extend(peer);
+                if matches!(caching, Caching::Automatic { .. })
+                    && (supersedes || (peer.is_some() && self.cache_record.is_none()))
+                {
+                    self.cache_record = Some(record.clone());
+                }
+                true
+            }
         }
     }
 }
 
+/// Tracks the given peer as a candidate for caching the record with the given key,
+/// keeping only the candidates closest to the key, if caching is enabled.
+fn insert_cache_candidate(
+    cache_candidates: &mut BTreeMap<kbucket::Distance, PeerId>,
+    caching: &Caching,
+    hasher: &KeyHashing,
+    key: &record::Key,
+    peer: PeerId,
+) {
+    let Some(max_peers) = caching.max_peers() else {
+        return;
+    };
+    let peer_key = hasher.peer(peer);
+    let target_key = hasher.key(key.clone());
+    cache_candidates.insert(peer_key.distance(&target_key), peer);
+    if cache_candidates.len() > max_peers as usize {
+        // TODO: `pop_last()` would be nice once stabilised.
+        // See https://github.com/rust-lang/rust/issues/62924.
+        let last = *cache_candidates.keys().next_back().expect("len > 0");
+        cache_candidates.remove(&last);
+    }
+}
+
 /// The context of a [`QueryInfo::AddProvider`] query.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AddProviderContext {
@@ -3141,6 +6974,9 @@ pub enum PutRecordContext {
     /// The context is a custom store operation targeting specific
     /// peers initiated by [`Behaviour::put_record_to`].
     Custom,
+    /// The context is the write-back of a record found by a lookup,
+    /// as configured by [`Caching::Automatic`].
+    Cache,
 }
 
 /// Information about a running query.
@@ -3160,6 +6996,31 @@ pub enum QueryInfo {
         step: ProgressStep,
     },
 
+    /// A query initiated by [`Behaviour::crawl`].
+    Crawl {
+        /// The target of the current lookup.
+        target: PeerId,
+        /// The targets of the remaining lookups.
+        remaining: vec::IntoIter<kbucket::Key<PeerId>>,
+        /// The peers reported so far via [`CrawlOk::PeerDiscovered`].
+        discovered: HashSet<PeerId>,
+        /// The peers reported so far via [`CrawlOk::PeerCrawled`].
+        crawled: HashSet<PeerId>,
+        step: ProgressStep,
+    },
+
+    /// A query initiated by [`Behaviour::refresh_bucket`], [`Behaviour::refresh_all`]
+    /// or an automatic refresh of stale buckets.
+    Refresh {
+        /// The bucket refreshed by the current lookup.
+        bucket: u32,
+        /// The target of the current lookup.
+        target: PeerId,
+        /// The remaining buckets to refresh.
+        remaining: vec::IntoIter<u32>,
+        step: ProgressStep,
+    },
+
     /// A (repeated) query initiated by [`Behaviour::get_closest_peers`].
     GetClosestPeers {
         /// The key being queried (the preimage).
@@ -3172,8 +7033,14 @@ pub enum QueryInfo {
     GetProviders {
         /// The key for which to search for providers.
         key: record::Key,
-        /// The number of providers found so far.
-        providers_found: usize,
+        /// The distinct providers found so far, with the number of
+        /// distinct peers that returned each provider.
+        providers: HashMap<PeerId, usize>,
+        /// The number of distinct confirmed providers after which to finish the query, if any.
+        quorum: Option<NonZeroUsize>,
+        /// The number of distinct peers that need to return a provider for
+        /// it to count towards the `quorum`.
+        confirmations: NonZeroUsize,
         /// Current index of events.
         step: ProgressStep,
     },
@@ -3207,21 +7074,157 @@ pub enum QueryInfo {
         step: ProgressStep,
         /// Did we find at least one record?
         found_a_record: bool,
+        /// The record found so far that is not superseded by any other record found,
+        /// as per the [`ConflictResolver`] of the namespace of the `key`.
+        best_record: Option<Record>,
+        /// The peers that returned the `best_record`.
+        best_record_peers: Vec<PeerId>,
         /// The peers closest to the `key` that were queried but did not return a record,
-        /// i.e. the peers that are candidates for caching the record.
+        /// or returned a superseded one, i.e. the peers that are candidates for caching
+        /// the record.
         cache_candidates: BTreeMap<kbucket::Distance, PeerId>,
+        /// The number of peers that have to return identical values to finish the
+        /// query early, see [`QueryOpts::finish_on_agreement`].
+        agreement: Option<NonZeroUsize>,
+        /// The peers that returned each distinct value, if `agreement` is set.
+        agreeing_peers: HashMap<Vec<u8>, Vec<PeerId>>,
+        /// The record whose value reached the `agreement`.
+        agreed_record: Option<Record>,
+    },
+
+    /// A query initiated by [`Behaviour::get_provider_summary`].
+    GetProviderSummary {
+        /// The peer asked for the summary.
+        peer: PeerId,
+        /// The range of the keyspace to summarise.
+        range: KeyspaceRange,
+        /// The summary, once received.
+        summary: Option<ProviderSummary>,
     },
 }
 
+/// Records that `peer` failed to store the record of a [`QueryInfo::PutRecord`]
+/// query, if this is such a query replicating its record.
+///
+/// Returns the event reporting the failure as progress of the query, if the
+/// query was started by [`Behaviour::put_record`] or [`Behaviour::put_record_to`].
+fn on_put_record_failure(
+    query: &mut Query<QueryInner>,
+    peer: PeerId,
+    error: PutRecordPeerError,
+) -> Option<Event> {
+    let id = query.id();
+    let QueryInfo::PutRecord {
+        context,
+        record,
+        phase: PutRecordPhase::PutRecord { failed, step, .. },
+        ..
+    } = &mut query.inner.info
+    else {
+        return None;
+    };
+    failed.push((peer, error));
+    if !matches!(
+        context,
+        PutRecordContext::Publish | PutRecordContext::Custom
+    ) {
+        return None;
+    }
+    let event = Event::OutboundQueryProgressed {
+        id,
+        result: QueryResult::PutRecordPeerFailed(PutRecordPeerFailure {
+            key: record.key.clone(),
+            peer,
+            error,
+        }),
+        step: step.clone(),
+        stats: query.stats().clone(),
+    };
+    if let QueryInfo::PutRecord {
+        phase: PutRecordPhase::PutRecord { step, .. },
+        ..
+    } = &mut query.inner.info
+    {
+        *step = step.next();
+    }
+    Some(event)
+}
+
 impl QueryInfo {
+    /// Gets the preimage of the key targeted by the query, i.e. the peer ID of a lookup of
+    /// the closest peers to a peer, or the record key.
+    ///
+    /// Returns `None` for a [`QueryInfo::GetProviderSummary`] query, which targets a range
+    /// of the keyspace.
+    pub fn target(&self) -> Option<Vec<u8>> {
+        match self {
+            QueryInfo::Bootstrap { peer, .. } => Some(peer.to_bytes()),
+            QueryInfo::Crawl { target, .. } | QueryInfo::Refresh { target, .. } => {
+                Some(target.to_bytes())
+            }
+            QueryInfo::GetClosestPeers { key, .. } => Some(key.clone()),
+            QueryInfo::GetProviders { key, .. }
+            | QueryInfo::AddProvider { key, .. }
+            | QueryInfo::GetRecord { key, .. } => Some(key.to_vec()),
+            QueryInfo::PutRecord { record, .. } => Some(record.key.to_vec()),
+            QueryInfo::GetProviderSummary { .. } => None,
+        }
+    }
+
+    /// Gets the step of the progress reported next for the query, if it reports any.
+    pub fn step(&self) -> Option<&ProgressStep> {
+        match self {
+            QueryInfo::Bootstrap { step, .. }
+            | QueryInfo::Crawl { step, .. }
+            | QueryInfo::Refresh { step, .. }
+            | QueryInfo::GetClosestPeers { step, .. }
+            | QueryInfo::GetProviders { step, .. }
+            | QueryInfo::GetRecord { step, .. }
+            | QueryInfo::PutRecord {
+                phase: PutRecordPhase::PutRecord { step, .. },
+                ..
+            } => Some(step),
+            QueryInfo::PutRecord { .. }
+            | QueryInfo::AddProvider { .. }
+            | QueryInfo::GetProviderSummary { .. } => None,
+        }
+    }
+
     /// Creates an event for a handler to issue an outgoing request in the
     /// context of a query.
+    /// The type of the requests of [`QueryInfo::to_request`].
+    fn rpc(&self) -> QueryRpc {
+        match &self {
+            QueryInfo::Bootstrap { .. }
+            | QueryInfo::Crawl { .. }
+            | QueryInfo::Refresh { .. }
+            | QueryInfo::GetClosestPeers { .. } => QueryRpc::FindNode,
+            QueryInfo::GetProviders { .. } => QueryRpc::GetProviders,
+            QueryInfo::AddProvider { phase, .. } => match phase {
+                AddProviderPhase::GetClosestPeers => QueryRpc::FindNode,
+                AddProviderPhase::AddProvider { .. } => QueryRpc::AddProvider,
+            },
+            QueryInfo::GetRecord { .. } => QueryRpc::GetRecord,
+            QueryInfo::PutRecord { phase, .. } => match phase {
+                PutRecordPhase::GetClosestPeers => QueryRpc::FindNode,
+                PutRecordPhase::PutRecord { .. } => QueryRpc::PutRecord,
+            },
+            QueryInfo::GetProviderSummary { .. } => QueryRpc::GetProviderSummary,
+        }
+    }
+
     fn to_request(&self, query_id: QueryId) -> HandlerIn {
         match &self {
             QueryInfo::Bootstrap { peer, .. } => HandlerIn::FindNodeReq {
                 key: peer.to_bytes(),
                 query_id,
             },
+            QueryInfo::Crawl { target, .. } | QueryInfo::Refresh { target, .. } => {
+                HandlerIn::FindNodeReq {
+                    key: target.to_bytes(),
+                    query_id,
+                }
+            }
             QueryInfo::GetClosestPeers { key, .. } => HandlerIn::FindNodeReq {
                 key: key.clone(),
                 query_id,
@@ -3238,6 +7241,7 @@ impl QueryInfo {
                 AddProviderPhase::AddProvider {
                     provider_id,
                     external_addresses,
+                    expires,
                     ..
                 } => HandlerIn::AddProvider {
                     key: key.clone(),
@@ -3245,7 +7249,9 @@ impl QueryInfo {
                         node_id: *provider_id,
                         multiaddrs: external_addresses.clone(),
                         connection_ty: crate::protocol::ConnectionType::Connected,
+                        signed_record: None,
                     },
+                    expires: *expires,
                     query_id,
                 },
             },
@@ -3263,6 +7269,10 @@ impl QueryInfo {
                     query_id,
                 },
             },
+            QueryInfo::GetProviderSummary { range, .. } => HandlerIn::GetProviderSummaryReq {
+                range: range.clone(),
+                query_id,
+            },
         }
     }
 }
@@ -3280,6 +7290,9 @@ pub enum AddProviderPhase {
         provider_id: PeerId,
         /// The external addresses of the provider being advertised.
         external_addresses: Vec<Multiaddr>,
+        /// The expiration of the provider record being advertised, if any,
+        /// see [`Behaviour::start_providing_with_ttl`].
+        expires: Option<Instant>,
         /// Query statistics from the finished `GetClosestPeers` phase.
         get_closest_peers_stats: QueryStats,
     },
@@ -3295,8 +7308,12 @@ pub enum PutRecordPhase {
     PutRecord {
         /// A list of peers the given record has been successfully replicated to.
         success: Vec<PeerId>,
+        /// A list of peers that failed to store the given record.
+        failed: Vec<(PeerId, PutRecordPeerError)>,
         /// Query statistics from the finished `GetClosestPeers` phase.
         get_closest_peers_stats: QueryStats,
+        /// The next step of the progress reported for the query.
+        step: ProgressStep,
     },
 }
 
@@ -3323,6 +7340,16 @@ impl<'a> QueryMut<'a> {
         self.query.stats()
     }
 
+    /// Gets the priority of the query.
+    pub fn priority(&self) -> QueryPriority {
+        self.query.priority()
+    }
+
+    /// Sets the priority of the query, see [`Config::set_max_running_queries`].
+    pub fn set_priority(&mut self, priority: QueryPriority) {
+        self.query.set_priority(priority)
+    }
+
     /// Finishes the query asap, without waiting for the
     /// regular termination conditions.
     pub fn finish(&mut self) {
@@ -3330,6 +7357,59 @@ impl<'a> QueryMut<'a> {
     }
 }
 
+/// A handle to a query, see [`Behaviour::query_handle`].
+///
+/// Unlike [`QueryMut`], a handle can be sent to and used from other tasks.
+#[derive(Debug, Clone)]
+pub struct QueryHandle {
+    id: QueryId,
+    commands: Arc<QueryCommands>,
+}
+
+impl QueryHandle {
+    pub fn id(&self) -> QueryId {
+        self.id
+    }
+
+    /// Finishes the query asap, without waiting for the regular termination
+    /// conditions, reporting the results obtained so far.
+    pub fn finish(&self) {
+        self.commands.send(self.id, QueryCommand::Finish);
+    }
+
+    /// Cancels the query, discarding its state without reporting any further progress.
+    pub fn cancel(&self) {
+        self.commands.send(self.id, QueryCommand::Cancel);
+    }
+}
+
+/// A command sent via a [`QueryHandle`].
+///
+/// Ordered by precedence, i.e. cancelling a query overrides finishing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum QueryCommand {
+    Finish,
+    Cancel,
+}
+
+/// The commands sent via [`QueryHandle`]s since the behaviour was last polled,
+/// at most one per query.
+#[derive(Debug, Default)]
+struct QueryCommands {
+    commands: Mutex<FnvHashMap<QueryId, QueryCommand>>,
+    waker: AtomicWaker,
+}
+
+impl QueryCommands {
+    fn send(&self, id: QueryId, command: QueryCommand) {
+        let mut commands = self.commands.lock().expect("lock not to be poisoned");
+        let pending = commands.entry(id).or_insert(command);
+        *pending = (*pending).max(command);
+        drop(commands);
+        self.waker.wake();
+    }
+}
+
 /// An immutable reference to a running query.
 pub struct QueryRef<'a> {
     query: &'a Query<QueryInner>,
@@ -3352,6 +7432,38 @@ impl<'a> QueryRef<'a> {
     pub fn stats(&self) -> &QueryStats {
         self.query.stats()
     }
+
+    /// Gets the priority of the query.
+    pub fn priority(&self) -> QueryPriority {
+        self.query.priority()
+    }
+
+    /// Gets the preimage of the key targeted by the query, if any, see [`QueryInfo::target`].
+    pub fn target(&self) -> Option<Vec<u8>> {
+        self.info().target()
+    }
+
+    /// Gets the instant the query started, if it did yet.
+    pub fn started_at(&self) -> Option<Instant> {
+        self.stats().start()
+    }
+
+    /// Gets the step of the progress reported next for the query, if it reports any.
+    pub fn step(&self) -> Option<&ProgressStep> {
+        self.info().step()
+    }
+
+    /// Gets the peers the query sent a request to, in the order they were contacted.
+    ///
+    /// For a multi-phase query such as `put_record`, these are the peers of the current phase.
+    pub fn contacted_peers(&self) -> &[PeerId] {
+        self.query.contacted_peers()
+    }
+
+    /// Gets the peers the query sent a request to and is still waiting on.
+    pub fn pending_peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.query.pending_peers()
+    }
 }
 
 /// An operation failed to due no known peers in the routing table.
@@ -3366,6 +7478,19 @@ impl fmt::Display for NoKnownPeers {
 
 impl std::error::Error for NoKnownPeers {}
 
+/// A query was not started because the maximum number of queries of the user
+/// is reached, see [`Config::set_max_user_queries`].
+#[derive(Debug, Clone)]
+pub struct QueryLimitReached();
+
+impl fmt::Display for QueryLimitReached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Maximum number of queries reached.")
+    }
+}
+
+impl std::error::Error for QueryLimitReached {}
+
 /// The possible outcomes of [`Behaviour::add_address`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RoutingUpdate {
@@ -3392,6 +7517,27 @@ pub enum Mode {
     Server,
 }
 
+/// The reachability of the local node from the public internet, see
+/// [`Behaviour::set_reachability`].
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Reachability {
+    /// The node is publicly reachable, e.g. as per AutoNAT.
+    Public,
+    /// The node is not publicly reachable, e.g. as it is behind a NAT.
+    Private,
+    /// The reachability of the node is not known.
+    Unknown,
+}
+
+/// The reason for an automatic change of the [`Mode`], see [`Event::ModeChanged`].
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum ModeChangeReason {
+    /// The confirmed external addresses of the node changed.
+    ExternalAddresses,
+    /// The reachability reported via [`Behaviour::set_reachability`] changed.
+    Reachability,
+}
+
 impl fmt::Display for Mode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
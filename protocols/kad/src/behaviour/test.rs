@@ -22,22 +22,28 @@
 
 use super::*;
 
-use crate::record::{store::MemoryStore, Key};
-use crate::{PROTOCOL_NAME, SHA_256_MH};
+use crate::record::{
+    store::{MemoryStore, MemoryStoreConfig, RecordStore},
+    Key,
+};
+use crate::{InvalidRecord, QueryHopResult, PROTOCOL_NAME, SHA_256_MH};
 use futures::{executor::block_on, future::poll_fn, prelude::*};
 use futures_timer::Delay;
 use libp2p_core::{
     multiaddr::{multiaddr, Protocol},
     multihash::Multihash,
-    transport::MemoryTransport,
+    transport::{ListenerId, MemoryTransport},
     upgrade, Transport,
 };
 use libp2p_identity as identity;
 use libp2p_noise as noise;
+use libp2p_swarm::behaviour::{ExternalAddrConfirmed, NewListenAddr};
 use libp2p_swarm::{self as swarm, Swarm, SwarmEvent};
 use libp2p_yamux as yamux;
 use quickcheck::*;
 use rand::{random, rngs::StdRng, thread_rng, Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
 
 type TestSwarm = Swarm<Behaviour<MemoryStore>>;
 
@@ -46,6 +52,16 @@ fn build_node() -> (Multiaddr, TestSwarm) {
 }
 
 fn build_node_with_config(cfg: Config) -> (Multiaddr, TestSwarm) {
+    build_node_with_store(cfg, MemoryStore::new)
+}
+
+fn build_node_with_store<TStore>(
+    cfg: Config,
+    store: impl FnOnce(PeerId) -> TStore,
+) -> (Multiaddr, Swarm<Behaviour<TStore>>)
+where
+    TStore: AsyncRecordStore + Send + 'static,
+{
     let local_key = identity::Keypair::generate_ed25519();
     let local_public_key = local_key.public();
     let transport = MemoryTransport::default()
@@ -55,7 +71,7 @@ fn build_node_with_config(cfg: Config) -> (Multiaddr, TestSwarm) {
         .boxed();
 
     let local_id = local_public_key.to_peer_id();
-    let store = MemoryStore::new(local_id);
+    let store = store(local_id);
     let behaviour = Behaviour::with_config(local_id, store, cfg);
 
     let mut swarm = Swarm::new(
@@ -233,6 +249,170 @@ fn bootstrap() {
     QuickCheck::new().tests(10).quickcheck(prop as fn(_) -> _)
 }
 
+#[test]
+fn crawl() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_periodic_bootstrap_interval(None);
+    cfg.set_automatic_bootstrap_throttle(None);
+    let mut swarms = build_connected_nodes_with_config(8, 2, cfg)
+        .into_iter()
+        .map(|(_a, s)| s)
+        .collect::<Vec<_>>();
+    let expected = swarms
+        .iter()
+        .skip(1)
+        .map(|s| *s.local_peer_id())
+        .collect::<HashSet<_>>();
+
+    let qid = swarms[0]
+        .behaviour_mut()
+        .crawl(NonZeroUsize::new(4).unwrap())
+        .unwrap();
+
+    let mut discovered = HashSet::new();
+    let mut crawled = HashSet::new();
+    let mut num_lookups = 0;
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in swarms.iter_mut() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::Crawl(result),
+                        step,
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        match result {
+                            Ok(CrawlOk::PeerDiscovered { peer, addresses }) => {
+                                assert!(!addresses.is_empty());
+                                assert!(discovered.insert(peer), "Peer discovered twice.");
+                            }
+                            Ok(CrawlOk::PeerCrawled { peer, .. }) => {
+                                assert!(crawled.insert(peer), "Peer crawled twice.");
+                            }
+                            Ok(CrawlOk::LookupFinished { num_remaining, .. })
+                            | Err(CrawlError::Timeout { num_remaining, .. }) => {
+                                num_lookups += 1;
+                                assert_eq!(num_remaining, 4 - num_lookups);
+                                if step.last {
+                                    assert_eq!(num_remaining, 0);
+                                    assert_eq!(crawled, expected);
+                                    assert!(discovered.is_subset(&expected));
+                                    return Poll::Ready(());
+                                }
+                            }
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn refresh_all() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_periodic_bootstrap_interval(None);
+    cfg.set_automatic_bootstrap_throttle(None);
+    let mut swarms = build_connected_nodes_with_config(4, 2, cfg)
+        .into_iter()
+        .map(|(_a, s)| s)
+        .collect::<Vec<_>>();
+
+    let first = swarms[0]
+        .behaviour_mut()
+        .kbuckets
+        .iter()
+        .position(|b| !b.is_empty())
+        .unwrap() as u32;
+    let qid = swarms[0].behaviour_mut().refresh_all().unwrap();
+
+    let mut expected_bucket = first;
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in swarms.iter_mut() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::Refresh(result),
+                        step,
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        let (bucket, num_remaining) = match result {
+                            Ok(RefreshOk {
+                                bucket,
+                                num_remaining,
+                            })
+                            | Err(RefreshError::Timeout {
+                                bucket,
+                                num_remaining,
+                            }) => (bucket, num_remaining),
+                        };
+                        assert_eq!(bucket, expected_bucket);
+                        assert_eq!(num_remaining, 255 - bucket);
+                        expected_bucket += 1;
+                        if step.last {
+                            assert_eq!(bucket, 255);
+                            return Poll::Ready(());
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn refreshes_stale_buckets() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_periodic_bootstrap_interval(None);
+    cfg.set_automatic_bootstrap_throttle(None);
+    cfg.set_bucket_staleness_threshold(Some(Duration::from_millis(100)));
+    let mut swarms = build_connected_nodes_with_config(2, 1, cfg)
+        .into_iter()
+        .map(|(_a, s)| s)
+        .collect::<Vec<_>>();
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in swarms.iter_mut() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        result: QueryResult::Refresh(_),
+                        ..
+                    }))) => return Poll::Ready(()),
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn refresh_without_known_peers() {
+    let mut swarm = build_node().1;
+    assert!(swarm.behaviour_mut().refresh_all().is_err());
+    assert!(swarm.behaviour_mut().refresh_bucket(255).is_err());
+}
+
 #[test]
 fn query_iter() {
     fn distances<K>(key: &kbucket::Key<K>, peers: Vec<PeerId>) -> Vec<Distance> {
@@ -603,6 +783,8 @@ fn put_record() {
                                 Err(e) => panic!("{e:?}"),
                                 Ok(ok) => {
                                     assert!(records.contains_key(&ok.key));
+                                    assert!(ok.success.len() >= replication_factor.get());
+                                    assert!(ok.failed.is_empty());
                                     let record = swarm.behaviour_mut().store.get(&ok.key).unwrap();
                                     results.push(record.into_owned());
                                 }
@@ -800,49 +982,48 @@ fn get_record() {
     }))
 }
 
+/// Returns a config with a validator rejecting records with an empty value
+/// in the `test` namespace.
+fn config_with_record_validator() -> Config {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.add_record_validator("test", |record: &Record| {
+        if record.value.is_empty() {
+            Err(InvalidRecord::new("empty value"))
+        } else {
+            Ok(())
+        }
+    });
+    cfg
+}
+
 #[test]
-fn get_record_many() {
-    // TODO: Randomise
-    let num_nodes = 12;
-    let mut swarms = build_connected_nodes(num_nodes, 3)
+fn get_record_ignores_invalid_record() {
+    let mut swarms = build_nodes_with_config(2, config_with_record_validator());
+    let (peer_id, address) = (*swarms[1].1.local_peer_id(), swarms[1].0.clone());
+    swarms[0].1.behaviour_mut().add_address(&peer_id, address);
+
+    // Drop the swarm addresses.
+    let mut swarms = swarms
         .into_iter()
         .map(|(_addr, swarm)| swarm)
         .collect::<Vec<_>>();
-    let num_results = 10;
-
-    let record = Record::new(random_multihash(), vec![4, 5, 6]);
-
-    for swarm in swarms.iter_mut().take(num_nodes) {
-        swarm.behaviour_mut().store.put(record.clone()).unwrap();
-    }
 
-    let quorum = Quorum::N(NonZeroUsize::new(num_results).unwrap());
+    let record = Record::new(Key::new(&"/test/key"), Vec::new());
+    swarms[1].behaviour_mut().store.put(record.clone()).unwrap();
     let qid = swarms[0].behaviour_mut().get_record(record.key.clone());
 
     block_on(poll_fn(move |ctx| {
-        for (i, swarm) in swarms.iter_mut().enumerate() {
-            let mut records = Vec::new();
-            let quorum = quorum.eval(swarm.behaviour().queries.config().replication_factor);
+        for swarm in &mut swarms {
             loop {
-                if i == 0 && records.len() >= quorum.get() {
-                    swarm.behaviour_mut().query_mut(&qid).unwrap().finish();
-                }
                 match swarm.poll_next_unpin(ctx) {
                     Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
                         id,
-                        result: QueryResult::GetRecord(Ok(r)),
-                        step: ProgressStep { count: _, last },
+                        result: QueryResult::GetRecord(r),
                         ..
                     }))) => {
                         assert_eq!(id, qid);
-                        if let GetRecordOk::FoundRecord(r) = r {
-                            assert_eq!(r.record, record);
-                            records.push(r);
-                        }
-
-                        if last {
-                            return Poll::Ready(());
-                        }
+                        assert!(matches!(r, Err(GetRecordError::NotFound { .. })));
+                        return Poll::Ready(());
                     }
                     // Ignore any other event.
                     Poll::Ready(Some(_)) => (),
@@ -851,76 +1032,367 @@ fn get_record_many() {
                 }
             }
         }
+
         Poll::Pending
     }))
 }
 
-/// A node joining a fully connected network via three (ALPHA_VALUE) bootnodes
-/// should be able to add itself as a provider to the X closest nodes of the
-/// network where X is equal to the configured replication factor.
 #[test]
-fn add_provider() {
-    fn prop(keys: Vec<record::Key>, seed: Seed) {
-        let mut rng = StdRng::from_seed(seed.0);
-        let replication_factor =
-            NonZeroUsize::new(rng.gen_range(1..(K_VALUE.get() / 2) + 1)).unwrap();
-        // At least 4 nodes, 1 under test + 3 bootnodes.
-        let num_total = usize::max(4, replication_factor.get() * 2);
+fn put_record_rejects_invalid_record() {
+    let mut swarms = build_nodes_with_config(2, config_with_record_validator());
+    let (peer_id, address) = (*swarms[1].1.local_peer_id(), swarms[1].0.clone());
+    swarms[0].1.behaviour_mut().add_address(&peer_id, address);
 
-        let mut config = Config::new(PROTOCOL_NAME);
-        config.set_replication_factor(replication_factor);
-        // Disabling periodic bootstrap and automatic bootstrap to prevent the bootstrap from triggering automatically.
-        config.set_periodic_bootstrap_interval(None);
-        config.set_automatic_bootstrap_throttle(None);
-        if rng.gen() {
-            config.disjoint_query_paths(true);
-        }
+    // Drop the swarm addresses.
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
 
-        let mut swarms = {
-            let mut fully_connected_swarms =
-                build_fully_connected_nodes_with_config(num_total - 1, config.clone());
+    let record = Record::new(Key::new(&"/test/key"), Vec::new());
+    let qid = swarms[0]
+        .behaviour_mut()
+        .put_record(record.clone(), Quorum::One)
+        .unwrap();
+    let mut reported_failure = false;
 
-            let mut single_swarm = build_node_with_config(config);
-            // Connect `single_swarm` to three bootnodes.
-            for swarm in fully_connected_swarms.iter().take(3) {
-                single_swarm
-                    .1
-                    .behaviour_mut()
-                    .add_address(swarm.1.local_peer_id(), swarm.0.clone());
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::PutRecordPeerFailed(failure),
+                        step,
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        assert_eq!(failure.key, record.key);
+                        assert_eq!(failure.peer, peer_id);
+                        assert_eq!(step.count.get(), 1);
+                        assert!(!step.last);
+                        reported_failure = true;
+                    }
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::PutRecord(r),
+                        step,
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        assert!(reported_failure);
+                        assert_eq!(step.count.get(), 2);
+                        assert!(step.last);
+                        match r {
+                            Err(PutRecordError::QuorumFailed {
+                                success, failed, ..
+                            }) => {
+                                assert!(success.is_empty());
+                                assert_eq!(
+                                    failed.iter().map(|(p, _)| *p).collect::<Vec<_>>(),
+                                    vec![peer_id]
+                                );
+                            }
+                            r => panic!("Unexpected result: {r:?}"),
+                        }
+                        return Poll::Ready(());
+                    }
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::InboundRequest {
+                        request: InboundRequest::PutRecord { .. },
+                    }))) => panic!("Invalid record must not be accepted"),
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
             }
+        }
 
-            let mut swarms = vec![single_swarm];
-            swarms.append(&mut fully_connected_swarms);
+        Poll::Pending
+    }))
+}
 
-            // Drop addresses before returning.
-            swarms
-                .into_iter()
-                .map(|(_addr, swarm)| swarm)
-                .collect::<Vec<_>>()
-        };
+#[test]
+fn get_record_ignores_superseded_records() {
+    let swarms = build_fully_connected_nodes_with_config(3, Default::default());
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
 
-        #[allow(clippy::mutable_key_type)] // False positive, we never modify `Bytes`.
-        let keys: HashSet<_> = keys.into_iter().take(num_total).collect();
+    let key = Key::from(random_multihash());
+    let mut old = Record::new(key.clone(), vec![1]);
+    old.sequence = Some(1);
+    let mut new = Record::new(key.clone(), vec![2]);
+    new.sequence = Some(2);
+    let outdated_peer = *swarms[1].local_peer_id();
+    swarms[1].behaviour_mut().store.put(old).unwrap();
+    swarms[2].behaviour_mut().store.put(new.clone()).unwrap();
 
-        // Each test run publishes all records twice.
-        let mut published = false;
-        let mut republished = false;
-        // The accumulated results for one round of publishing.
-        let mut results = Vec::new();
+    let qid = swarms[0].behaviour_mut().get_record(key);
+    let mut found = Vec::new();
 
-        // Initiate the first round of publishing.
-        let mut qids = HashSet::new();
-        for k in &keys {
-            let qid = swarms[0]
-                .behaviour_mut()
-                .start_providing(k.clone())
-                .unwrap();
-            qids.insert(qid);
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetRecord(Ok(r)),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        match r {
+                            GetRecordOk::FoundRecord(r) => found.push(r.record),
+                            GetRecordOk::FinishedWithNoAdditionalRecord { cache_candidates } => {
+                                // The newest record is reported last, the superseded
+                                // one only if it was found before.
+                                assert_eq!(found.last(), Some(&new));
+                                assert!(found.len() <= 2);
+                                assert!(cache_candidates.values().any(|p| *p == outdated_peer));
+                                return Poll::Ready(());
+                            }
+                            r => panic!("Unexpected result: {r:?}"),
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
         }
 
-        block_on(poll_fn(move |ctx| loop {
-            // Poll all swarms until they are "Pending".
-            for swarm in &mut swarms {
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn get_record_finishes_on_agreement() {
+    let swarms = build_fully_connected_nodes_with_config(4, Default::default());
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let key = Key::from(random_multihash());
+    let record = Record::new(key.clone(), vec![1]);
+    for swarm in &mut swarms[1..] {
+        swarm.behaviour_mut().store.put(record.clone()).unwrap();
+    }
+
+    let qid = swarms[0].behaviour_mut().get_record_with_opts(
+        key,
+        QueryOpts::new().finish_on_agreement(NonZeroUsize::new(2).unwrap()),
+    );
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetRecord(Ok(r)),
+                        step,
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        match r {
+                            GetRecordOk::FoundRecord(_) => assert!(!step.last),
+                            GetRecordOk::AgreementReached { record: r, peers } => {
+                                assert!(step.last);
+                                assert_eq!(r.value, record.value);
+                                assert_eq!(peers.len(), 2);
+                                return Poll::Ready(());
+                            }
+                            r => panic!("Unexpected result: {r:?}"),
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn put_record_rejects_superseded_record() {
+    let mut swarms = build_nodes(2);
+    let (peer_id, address) = (*swarms[1].1.local_peer_id(), swarms[1].0.clone());
+    swarms[0].1.behaviour_mut().add_address(&peer_id, address);
+
+    // Drop the swarm addresses.
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let key = Key::from(random_multihash());
+    let mut stored = Record::new(key.clone(), vec![2]);
+    stored.sequence = Some(2);
+    swarms[1].behaviour_mut().store.put(stored.clone()).unwrap();
+
+    let mut record = Record::new(key.clone(), vec![1]);
+    record.sequence = Some(1);
+    let qid = swarms[0]
+        .behaviour_mut()
+        .put_record(record, Quorum::One)
+        .unwrap();
+
+    block_on(poll_fn(|ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::PutRecord(r),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        assert!(matches!(r, Err(PutRecordError::QuorumFailed { .. })));
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }));
+
+    let kad = swarms[1].behaviour_mut();
+    assert_eq!(kad.store.get(&key).map(|r| r.into_owned()), Some(stored));
+}
+
+#[test]
+fn get_record_many() {
+    // TODO: Randomise
+    let num_nodes = 12;
+    let mut swarms = build_connected_nodes(num_nodes, 3)
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+    let num_results = 10;
+
+    let record = Record::new(random_multihash(), vec![4, 5, 6]);
+
+    for swarm in swarms.iter_mut().take(num_nodes) {
+        swarm.behaviour_mut().store.put(record.clone()).unwrap();
+    }
+
+    let quorum = Quorum::N(NonZeroUsize::new(num_results).unwrap());
+    let qid = swarms[0].behaviour_mut().get_record(record.key.clone());
+
+    block_on(poll_fn(move |ctx| {
+        for (i, swarm) in swarms.iter_mut().enumerate() {
+            let mut records = Vec::new();
+            let quorum = quorum.eval(swarm.behaviour().queries.config().replication_factor);
+            loop {
+                if i == 0 && records.len() >= quorum.get() {
+                    swarm.behaviour_mut().query_mut(&qid).unwrap().finish();
+                }
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetRecord(Ok(r)),
+                        step: ProgressStep { count: _, last },
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        if let GetRecordOk::FoundRecord(r) = r {
+                            assert_eq!(r.record, record);
+                            records.push(r);
+                        }
+
+                        if last {
+                            return Poll::Ready(());
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Poll::Pending
+    }))
+}
+
+/// A node joining a fully connected network via three (ALPHA_VALUE) bootnodes
+/// should be able to add itself as a provider to the X closest nodes of the
+/// network where X is equal to the configured replication factor.
+#[test]
+fn add_provider() {
+    fn prop(keys: Vec<record::Key>, seed: Seed) {
+        let mut rng = StdRng::from_seed(seed.0);
+        let replication_factor =
+            NonZeroUsize::new(rng.gen_range(1..(K_VALUE.get() / 2) + 1)).unwrap();
+        // At least 4 nodes, 1 under test + 3 bootnodes.
+        let num_total = usize::max(4, replication_factor.get() * 2);
+
+        let mut config = Config::new(PROTOCOL_NAME);
+        config.set_replication_factor(replication_factor);
+        // Disabling periodic bootstrap and automatic bootstrap to prevent the bootstrap from triggering automatically.
+        config.set_periodic_bootstrap_interval(None);
+        config.set_automatic_bootstrap_throttle(None);
+        if rng.gen() {
+            config.disjoint_query_paths(true);
+        }
+
+        let mut swarms = {
+            let mut fully_connected_swarms =
+                build_fully_connected_nodes_with_config(num_total - 1, config.clone());
+
+            let mut single_swarm = build_node_with_config(config);
+            // Connect `single_swarm` to three bootnodes.
+            for swarm in fully_connected_swarms.iter().take(3) {
+                single_swarm
+                    .1
+                    .behaviour_mut()
+                    .add_address(swarm.1.local_peer_id(), swarm.0.clone());
+            }
+
+            let mut swarms = vec![single_swarm];
+            swarms.append(&mut fully_connected_swarms);
+
+            // Drop addresses before returning.
+            swarms
+                .into_iter()
+                .map(|(_addr, swarm)| swarm)
+                .collect::<Vec<_>>()
+        };
+
+        #[allow(clippy::mutable_key_type)] // False positive, we never modify `Bytes`.
+        let keys: HashSet<_> = keys.into_iter().take(num_total).collect();
+
+        // Each test run publishes all records twice.
+        let mut published = false;
+        let mut republished = false;
+        // The accumulated results for one round of publishing.
+        let mut results = Vec::new();
+
+        // Initiate the first round of publishing.
+        let mut qids = HashSet::new();
+        for k in &keys {
+            let qid = swarms[0]
+                .behaviour_mut()
+                .start_providing(k.clone())
+                .unwrap();
+            qids.insert(qid);
+        }
+
+        block_on(poll_fn(move |ctx| loop {
+            // Poll all swarms until they are "Pending".
+            for swarm in &mut swarms {
                 loop {
                     match swarm.poll_next_unpin(ctx) {
                         Poll::Ready(Some(SwarmEvent::Behaviour(
@@ -1029,12 +1501,7 @@ fn add_provider() {
 
             // Initiate the second round of publishing by telling the
             // periodic provider job to run asap.
-            swarms[0]
-                .behaviour_mut()
-                .add_provider_job
-                .as_mut()
-                .unwrap()
-                .asap();
+            swarms[0].behaviour_mut().add_provider_job.asap();
             published = false;
             republished = true;
         }))
@@ -1179,13 +1646,12 @@ fn disjoint_query_does_not_finish_before_all_paths_did() {
     }));
 
     // Make sure `alice` has exactly one query with `trudy`'s record only.
-    assert_eq!(1, alice.behaviour().queries.iter().count());
+    assert_eq!(1, alice.behaviour().iter_queries().count());
 
     alice
         .behaviour()
-        .queries
-        .iter()
-        .for_each(|q| match &q.inner.info {
+        .iter_queries()
+        .for_each(|q| match q.info() {
             QueryInfo::GetRecord { step, .. } => {
                 assert_eq!(usize::from(step.count), 2);
             }
@@ -1337,7 +1803,10 @@ fn network_behaviour_on_address_change() {
     kademlia.on_connection_handler_event(
         remote_peer_id,
         connection_id,
-        HandlerEvent::ProtocolConfirmed { endpoint },
+        HandlerEvent::ProtocolConfirmed {
+            endpoint,
+            protocol: PROTOCOL_NAME,
+        },
     );
 
     assert_eq!(
@@ -1388,14 +1857,16 @@ fn get_providers_single() {
             .expect("could not provide");
 
         block_on(async {
-            match single_swarm.next().await.unwrap() {
-                SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
-                    result: QueryResult::StartProviding(Ok(_)),
-                    ..
-                }) => {}
-                SwarmEvent::Behaviour(Event::ModeChanged { .. }) => {}
-                SwarmEvent::Behaviour(e) => panic!("Unexpected event: {e:?}"),
-                _ => {}
+            loop {
+                match single_swarm.next().await.unwrap() {
+                    SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        result: QueryResult::StartProviding(Ok(_)),
+                        ..
+                    }) => break,
+                    SwarmEvent::Behaviour(Event::ModeChanged { .. }) => {}
+                    SwarmEvent::Behaviour(e) => panic!("Unexpected event: {e:?}"),
+                    _ => {}
+                }
             }
         });
 
@@ -1489,6 +1960,7 @@ fn get_providers_limit<const N: usize>() {
                                 if let GetProvidersOk::FoundProviders {
                                     key: found_key,
                                     providers,
+                                    ..
                                 } = ok
                                 {
                                     // There are a total of 2 providers.
@@ -1533,3 +2005,1847 @@ fn get_providers_limit_n_2() {
 fn get_providers_limit_n_5() {
     get_providers_limit::<5>();
 }
+
+#[test]
+fn get_providers_with_quorum() {
+    let (_, mut swarm) = build_node();
+    let key = record::Key::from(random_multihash());
+    swarm
+        .behaviour_mut()
+        .start_providing(key.clone())
+        .expect("could not provide");
+
+    #[allow(clippy::result_large_err)]
+    let run = |swarm: &mut TestSwarm, quorum: usize, confirmations: Option<usize>| {
+        let quorum = NonZeroUsize::new(quorum).unwrap();
+        let query_id = match confirmations {
+            Some(c) => swarm.behaviour_mut().get_providers_with_confirmed_quorum(
+                key.clone(),
+                quorum,
+                NonZeroUsize::new(c).unwrap(),
+            ),
+            None => swarm
+                .behaviour_mut()
+                .get_providers_with_quorum(key.clone(), quorum),
+        };
+        block_on(async {
+            loop {
+                if let SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::GetProviders(result),
+                    step,
+                    ..
+                }) = swarm.next().await.unwrap()
+                {
+                    if id == query_id && step.last {
+                        return result as GetProvidersResult;
+                    }
+                }
+            }
+        })
+    };
+
+    // The local provider record satisfies a quorum of one.
+    assert!(matches!(
+        run(&mut swarm, 1, None),
+        Ok(GetProvidersOk::FinishedWithNoAdditionalRecord { .. })
+    ));
+    assert!(matches!(
+        run(&mut swarm, 1, Some(1)),
+        Ok(GetProvidersOk::FinishedWithNoAdditionalRecord { .. })
+    ));
+
+    match run(&mut swarm, 2, None) {
+        Err(GetProvidersError::QuorumFailed {
+            providers, quorum, ..
+        }) => {
+            assert_eq!(quorum.get(), 2);
+            assert_eq!(providers, HashSet::from([*swarm.local_peer_id()]));
+        }
+        r => panic!("Unexpected result: {r:?}"),
+    }
+
+    // The local provider record is a single confirmation.
+    match run(&mut swarm, 1, Some(2)) {
+        Err(GetProvidersError::QuorumFailed {
+            providers,
+            confirmations,
+            ..
+        }) => {
+            assert_eq!(providers, HashSet::from([*swarm.local_peer_id()]));
+            assert_eq!(confirmations, HashMap::from([(*swarm.local_peer_id(), 1)]));
+        }
+        r => panic!("Unexpected result: {r:?}"),
+    }
+}
+
+#[test]
+fn get_providers_counts_confirmations() {
+    let mut swarms = build_fully_connected_nodes_with_config(3, Default::default())
+        .into_iter()
+        .map(|(_a, s)| s)
+        .collect::<Vec<_>>();
+    let key = record::Key::from(random_multihash());
+    let provider = *swarms[2].local_peer_id();
+    let record = ProviderRecord::new(key.clone(), provider, Vec::new());
+    for swarm in &mut swarms[1..] {
+        swarm
+            .behaviour_mut()
+            .store_mut()
+            .add_provider(record.clone())
+            .unwrap();
+    }
+
+    let query_id = swarms[0]
+        .behaviour_mut()
+        .get_providers_with_confirmed_quorum(
+            key,
+            NonZeroUsize::new(1).unwrap(),
+            NonZeroUsize::new(2).unwrap(),
+        );
+
+    let mut max_confirmations = 0;
+    block_on(poll_fn(move |ctx| {
+        for swarm in swarms.iter_mut() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetProviders(result),
+                        step,
+                        ..
+                    }))) if id == query_id => {
+                        match result {
+                            Ok(GetProvidersOk::FoundProviders {
+                                providers,
+                                confirmations,
+                                ..
+                            }) => {
+                                assert!(providers.is_subset(&HashSet::from([provider])));
+                                if let Some(count) = confirmations.get(&provider) {
+                                    max_confirmations = max_confirmations.max(*count);
+                                }
+                            }
+                            Ok(GetProvidersOk::FinishedWithNoAdditionalRecord { .. }) => {}
+                            Err(e) => panic!("Unexpected error: {e:?}"),
+                        }
+                        if step.last {
+                            // Both peers storing the provider record confirmed it.
+                            assert_eq!(max_confirmations, 2);
+                            return Poll::Ready(());
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn get_provider_summary() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_provider_summaries(true);
+    let mut swarms = build_nodes_with_config(2, cfg);
+    let (addr, peer) = (swarms[1].0.clone(), *swarms[1].1.local_peer_id());
+
+    let provided = (0..10)
+        .map(|_| Key::from(random_multihash()))
+        .collect::<Vec<_>>();
+    for key in &provided {
+        let record = ProviderRecord::new(key.clone(), PeerId::random(), Vec::new());
+        swarms[1]
+            .1
+            .behaviour_mut()
+            .store
+            .add_provider(record)
+            .unwrap();
+    }
+
+    // Drop the swarm addresses.
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    swarms[0].behaviour_mut().add_address(&peer, addr);
+    let qid = swarms[0]
+        .behaviour_mut()
+        .get_provider_summary(peer, KeyspaceRange::full());
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetProviderSummary(r),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        let summary = r.expect("summary").summary;
+                        assert!(provided.iter().all(|k| summary.may_provide(k)));
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn get_providers_accumulates_pages() {
+    const NUM_PROVIDERS: usize = 100;
+
+    let key = Key::from(random_multihash());
+    let (_, mut requester) = build_node();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_max_response_size(Some(1024));
+    let (addr, mut responder) = build_node_with_store(cfg, |local_id| {
+        let config = MemoryStoreConfig {
+            max_providers_per_key: NUM_PROVIDERS,
+            ..Default::default()
+        };
+        MemoryStore::with_config(local_id, config)
+    });
+
+    let mut provided = HashSet::new();
+    for _ in 0..NUM_PROVIDERS {
+        let provider = PeerId::random();
+        let address = Protocol::Memory(random::<u64>()).into();
+        let record = ProviderRecord::new(key.clone(), provider, vec![address]);
+        responder
+            .behaviour_mut()
+            .store
+            .add_provider(record)
+            .unwrap();
+        provided.insert(provider);
+    }
+
+    requester
+        .behaviour_mut()
+        .add_address(responder.local_peer_id(), addr);
+    let qid = requester.behaviour_mut().get_providers(key);
+
+    let mut found = HashSet::new();
+    block_on(poll_fn(move |ctx| {
+        while let Poll::Ready(Some(_)) = responder.poll_next_unpin(ctx) {}
+        loop {
+            match requester.poll_next_unpin(ctx) {
+                Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::GetProviders(Ok(ok)),
+                    step,
+                    ..
+                }))) if id == qid => {
+                    if let GetProvidersOk::FoundProviders { providers, .. } = ok {
+                        found.extend(providers);
+                    }
+                    if step.last {
+                        // The providers don't fit into a single response, yet all are found.
+                        assert_eq!(found, provided);
+                        return Poll::Ready(());
+                    }
+                }
+                // Ignore any other event.
+                Poll::Ready(Some(_)) => (),
+                e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                Poll::Pending => break,
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn start_providing_to() {
+    let swarms = build_nodes(2);
+    let (addr, peer) = (swarms[1].0.clone(), *swarms[1].1.local_peer_id());
+    let local_peer = *swarms[0].1.local_peer_id();
+    let key = Key::from(random_multihash());
+
+    // Drop the swarm addresses.
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    swarms[0].behaviour_mut().add_address(&peer, addr);
+    let qid = swarms[0]
+        .behaviour_mut()
+        .start_providing_to(key.clone(), std::iter::once(peer));
+
+    let mut started = false;
+    let mut stored = false;
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::StartProviding(r),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        assert_eq!(r.expect("providing to succeed").key, key);
+                        // The provider record is not stored locally.
+                        assert!(swarm.behaviour_mut().store.providers(&key).is_empty());
+                        started = true;
+                    }
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::InboundRequest {
+                        request: InboundRequest::AddProvider { .. },
+                    }))) => {
+                        let providers = swarm.behaviour_mut().store.providers(&key);
+                        assert_eq!(providers.len(), 1);
+                        assert_eq!(providers[0].provider, local_peer);
+                        stored = true;
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        if started && stored {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }));
+}
+
+/// A record store whose operations only complete after a delay.
+struct DelayedStore(MemoryStore);
+
+impl DelayedStore {
+    fn delayed<T: Send + 'static>(op: StoreFuture<T>) -> StoreFuture<T> {
+        Delay::new(Duration::from_millis(10)).then(|()| op).boxed()
+    }
+}
+
+impl AsyncRecordStore for DelayedStore {
+    fn get_record(&mut self, k: &Key) -> StoreFuture<Option<Record>> {
+        Self::delayed(self.0.get_record(k))
+    }
+
+    fn put_record(&mut self, r: Record) -> StoreFuture<store::Result<()>> {
+        Self::delayed(self.0.put_record(r))
+    }
+
+    fn remove_record(&mut self, k: &Key) -> StoreFuture<()> {
+        Self::delayed(self.0.remove_record(k))
+    }
+
+    fn all_records(&mut self) -> StoreFuture<Vec<Record>> {
+        Self::delayed(self.0.all_records())
+    }
+
+    fn add_provider_record(&mut self, record: ProviderRecord) -> StoreFuture<store::Result<()>> {
+        Self::delayed(self.0.add_provider_record(record))
+    }
+
+    fn provider_records(&mut self, key: &Key) -> StoreFuture<Vec<ProviderRecord>> {
+        Self::delayed(self.0.provider_records(key))
+    }
+
+    fn provided_records(&mut self) -> StoreFuture<Vec<ProviderRecord>> {
+        Self::delayed(self.0.provided_records())
+    }
+
+    fn remove_provider_record(&mut self, k: &Key, p: &PeerId) -> StoreFuture<()> {
+        Self::delayed(self.0.remove_provider_record(k, p))
+    }
+
+    fn provider_record_keys(&mut self) -> StoreFuture<Vec<Key>> {
+        Self::delayed(self.0.provider_record_keys())
+    }
+}
+
+#[test]
+fn get_record_from_async_store() {
+    let (_, mut swarm) = build_node();
+    let (addr, mut async_swarm) =
+        build_node_with_store(Default::default(), |id| DelayedStore(MemoryStore::new(id)));
+    let async_peer = *async_swarm.local_peer_id();
+
+    let record = Record::new(random_multihash(), vec![4, 5, 6]);
+    async_swarm
+        .behaviour_mut()
+        .put_record(record.clone(), Quorum::One)
+        .expect("record is stored in the background");
+
+    swarm.behaviour_mut().add_address(&async_peer, addr);
+    let qid = swarm.behaviour_mut().get_record(record.key.clone());
+
+    block_on(poll_fn(move |ctx| {
+        while let Poll::Ready(Some(_)) = async_swarm.poll_next_unpin(ctx) {}
+        loop {
+            match swarm.poll_next_unpin(ctx) {
+                Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::GetRecord(r),
+                    ..
+                }))) => {
+                    assert_eq!(id, qid);
+                    match r {
+                        Ok(GetRecordOk::FoundRecord(r)) => {
+                            assert_eq!(r.peer, Some(async_peer));
+                            assert_eq!(r.record.key, record.key);
+                            assert_eq!(r.record.value, record.value);
+                        }
+                        r => panic!("Unexpected result: {r:?}"),
+                    }
+                    return Poll::Ready(());
+                }
+                // Ignore any other event.
+                Poll::Ready(Some(_)) => (),
+                e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }))
+}
+
+#[test]
+fn truncate_response_keeps_closest_peers() {
+    fn peer() -> KadPeer {
+        KadPeer {
+            node_id: PeerId::random(),
+            multiaddrs: vec![Protocol::Memory(random::<u64>()).into()],
+            connection_ty: ConnectionType::Connected,
+            signed_record: None,
+        }
+    }
+
+    let local_id = PeerId::random();
+    let peer_len = peer().encoded_len();
+
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_max_response_peers(NonZeroUsize::new(5).unwrap());
+    cfg.set_max_response_size(Some(3 * peer_len));
+    let kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    assert_eq!(kad.num_response_peers(), 5);
+
+    let closer = (0..5).map(|_| peer()).collect::<Vec<_>>();
+    let providers = (0..2).map(|_| peer()).collect::<Vec<_>>();
+
+    // Closer peers are dropped first, farthest first.
+    let (mut closer_peers, mut provider_peers) = (closer.clone(), providers.clone());
+    kad.truncate_response(0, &mut closer_peers, &mut provider_peers);
+    assert_eq!(closer_peers, closer[..1]);
+    assert_eq!(provider_peers, providers);
+
+    // The closest peer is always kept.
+    let mut closer_peers = closer.clone();
+    kad.truncate_response(10 * peer_len, &mut closer_peers, &mut Vec::new());
+    assert_eq!(closer_peers, closer[..1]);
+}
+
+#[test]
+fn restore_routing_table_from_snapshot() {
+    let local_id = PeerId::random();
+    let new_behaviour = || {
+        Behaviour::with_config(
+            local_id,
+            MemoryStore::new(local_id),
+            Config::new(PROTOCOL_NAME),
+        )
+    };
+    let mut kad = new_behaviour();
+    let peers = (0..10).map(|_| PeerId::random()).collect::<Vec<_>>();
+    for peer in &peers {
+        let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+        kad.add_address(peer, addr);
+    }
+
+    let snapshot = kad.routing_table_snapshot();
+    assert_eq!(snapshot.entries.len(), peers.len());
+    assert!(snapshot
+        .entries
+        .iter()
+        .all(|e| e.status == NodeStatus::Disconnected && e.last_seen.is_none()));
+
+    let mut restored = new_behaviour();
+    assert_eq!(
+        restored.restore_routing_table(snapshot.clone()),
+        peers.len()
+    );
+    assert_eq!(restored.routing_table_snapshot(), snapshot);
+}
+
+#[test]
+fn seed_routing_table_from_peer_store() {
+    let now = crate::snapshot::unix_time_now();
+    let entry = |age: Option<u64>| RoutingTableEntry {
+        peer_id: PeerId::random(),
+        addresses: vec![Protocol::Memory(random::<u64>()).into()],
+        status: NodeStatus::Disconnected,
+        last_seen: age.map(|age| now - Duration::from_secs(age)),
+    };
+    // The most recently seen peers first, then a stale one and one never seen.
+    let fresh = (0..3).map(|age| entry(Some(age))).collect::<Vec<_>>();
+    let stale = entry(Some(2 * 60 * 60));
+    let unseen = entry(None);
+    let store = Arc::new(Mutex::new(
+        [fresh.clone(), vec![stale.clone(), unseen.clone()]].concat(),
+    ));
+
+    struct Store(Arc<Mutex<Vec<RoutingTableEntry>>>);
+
+    impl PeerStore for Store {
+        fn kad_peers(&self) -> Vec<RoutingTableEntry> {
+            self.0.lock().unwrap().clone()
+        }
+
+        fn on_peer_seen(&self, entry: RoutingTableEntry) {
+            self.0.lock().unwrap().push(entry);
+        }
+    }
+
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_peer_store(Store(store.clone()), 2, Duration::from_secs(60 * 60));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+
+    let seeded = kad
+        .routing_table_snapshot()
+        .entries
+        .into_iter()
+        .map(|e| e.peer_id)
+        .collect::<HashSet<_>>();
+    assert_eq!(seeded, HashSet::from([fresh[0].peer_id, fresh[1].peer_id]));
+
+    // Peers of the routing table are reported to the store when they disconnect.
+    let peer = fresh[0].peer_id;
+    let endpoint = ConnectedPoint::Dialer {
+        address: fresh[0].addresses[0].clone(),
+        role_override: Endpoint::Dialer,
+    };
+    kad.on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
+        peer_id: peer,
+        connection_id: ConnectionId::new_unchecked(0),
+        endpoint: &endpoint,
+        remaining_established: 0,
+    }));
+    let reported = store.lock().unwrap().last().cloned().unwrap();
+    assert_eq!(reported.peer_id, peer);
+    assert_eq!(reported.addresses.len(), 1);
+    assert!(reported.last_seen >= Some(now));
+}
+
+#[test]
+fn ip_diversity_limits_peers_per_prefix() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_max_peers_per_ip_prefix(NonZeroUsize::new(2));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+
+    for i in 1..=2 {
+        let addr: Multiaddr = format!("/ip4/8.8.8.{i}/tcp/4001").parse().unwrap();
+        assert!(matches!(
+            kad.add_address(&PeerId::random(), addr),
+            RoutingUpdate::Success
+        ));
+    }
+
+    // A third peer in the same /24 network is rejected.
+    let peer = PeerId::random();
+    let addr: Multiaddr = "/ip4/8.8.8.3/tcp/4001".parse().unwrap();
+    assert!(matches!(
+        kad.add_address(&peer, addr.clone()),
+        RoutingUpdate::Failed
+    ));
+    assert!(kad.queued_events.iter().any(|e| matches!(
+        e,
+        ToSwarm::GenerateEvent(Event::IpDiversityExceeded { peer: p, address })
+            if *p == peer && *address == addr.clone().with_p2p(peer).unwrap()
+    )));
+
+    // Peers in other networks and with non-public addresses are not limited.
+    let addr: Multiaddr = "/ip4/8.8.9.1/tcp/4001".parse().unwrap();
+    assert!(matches!(
+        kad.add_address(&peer, addr),
+        RoutingUpdate::Success
+    ));
+    for _ in 0..3 {
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+        assert!(matches!(
+            kad.add_address(&PeerId::random(), addr),
+            RoutingUpdate::Success
+        ));
+    }
+}
+
+#[test]
+fn peer_scorer_decides_routing_table_admission() {
+    #[derive(Clone, Default)]
+    struct Strikes(std::sync::Arc<std::sync::Mutex<Vec<(PeerId, Misbehaviour)>>>);
+
+    impl PeerScorer for Strikes {
+        fn on_misbehaviour(&self, peer: &PeerId, misbehaviour: Misbehaviour) {
+            self.0.lock().unwrap().push((*peer, misbehaviour));
+        }
+
+        fn admits(&self, peer: &PeerId) -> bool {
+            !self.0.lock().unwrap().iter().any(|(p, _)| p == peer)
+        }
+    }
+
+    let local_id = PeerId::random();
+    let strikes = Strikes::default();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_peer_scorer(strikes.clone());
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+
+    let (good, bad) = (PeerId::random(), PeerId::random());
+    kad.report_misbehaviour(&bad, Misbehaviour::Application);
+    assert_eq!(
+        *strikes.0.lock().unwrap(),
+        vec![(bad, Misbehaviour::Application)]
+    );
+
+    let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+    assert!(matches!(
+        kad.add_address(&bad, addr.clone()),
+        RoutingUpdate::Failed
+    ));
+    assert!(matches!(
+        kad.add_address(&good, addr),
+        RoutingUpdate::Success
+    ));
+    assert!(kad
+        .kbucket(bad)
+        .unwrap()
+        .iter()
+        .all(|e| e.node.key.preimage() != &bad));
+
+    // Peers already in the routing table are kept.
+    kad.report_misbehaviour(&good, Misbehaviour::Timeout);
+    assert!(kad
+        .kbucket(good)
+        .unwrap()
+        .iter()
+        .any(|e| e.node.key.preimage() == &good));
+}
+
+#[test]
+fn coalesced_routing_updates() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_coalesced_routing_updates(Some(Duration::from_millis(10)));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    let mut subscription = kad.subscribe_routing_updates();
+
+    let peers = (0..10).map(|_| PeerId::random()).collect::<Vec<_>>();
+    for peer in &peers {
+        let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+        kad.add_address(peer, addr);
+    }
+    assert!(!kad
+        .queued_events
+        .iter()
+        .any(|e| matches!(e, ToSwarm::GenerateEvent(Event::RoutingUpdated { .. }))));
+
+    let updates = block_on(poll_fn(|cx| loop {
+        match kad.poll(cx) {
+            Poll::Ready(ToSwarm::GenerateEvent(Event::RoutingUpdatesCoalesced { updates })) => {
+                return Poll::Ready(updates)
+            }
+            Poll::Ready(ToSwarm::GenerateEvent(Event::RoutingUpdated { .. })) => {
+                panic!("Unexpected individual routing update")
+            }
+            Poll::Ready(_) => {}
+            Poll::Pending => return Poll::Pending,
+        }
+    }));
+    assert_eq!(updates.new_peers, peers.len());
+    assert_eq!(updates.updated_peers, 0);
+    let mut buckets = peers
+        .iter()
+        .map(|p| {
+            kbucket::Key::from(local_id)
+                .distance(&kbucket::Key::from(*p))
+                .ilog2()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    buckets.sort_unstable();
+    buckets.dedup();
+    assert_eq!(updates.buckets, buckets);
+    assert_eq!(block_on(subscription.next()), Some(updates));
+}
+
+#[test]
+fn routing_table_updates() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.kbucket_pending_timeout = Duration::ZERO;
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    let local_key = kbucket::Key::from(local_id);
+    let bucket_of = |peer: &PeerId| {
+        local_key
+            .distance(&kbucket::Key::from(*peer))
+            .ilog2()
+            .unwrap()
+    };
+
+    // Fill the farthest bucket, with one more peer pending insertion.
+    let mut peers = Vec::new();
+    while peers.len() <= K_VALUE.get() {
+        let peer = PeerId::random();
+        if bucket_of(&peer) == 255 {
+            peers.push(peer);
+        }
+    }
+    let pending = peers[K_VALUE.get()];
+    // Only connected peers replace disconnected peers in a full bucket.
+    kad.connected_peers.insert(pending);
+    for peer in &peers {
+        kad.add_address(peer, Protocol::Memory(random::<u64>()).into());
+    }
+
+    let mut cx = Context::from_waker(noop_waker_ref());
+    let mut updates = Vec::new();
+    let mut poll_updates = |kad: &mut Behaviour<MemoryStore>| {
+        while let Poll::Ready(event) = kad.poll(&mut cx) {
+            if let ToSwarm::GenerateEvent(Event::RoutingTableUpdated {
+                peer,
+                bucket,
+                action,
+            }) = event
+            {
+                assert_eq!(bucket, 255);
+                updates.push((peer, action));
+            }
+        }
+    };
+    poll_updates(&mut kad);
+    // Pending entries are applied lazily, upon access of the routing table.
+    kad.kbuckets.iter().for_each(drop);
+    poll_updates(&mut kad);
+    kad.remove_peer(&peers[1]);
+    poll_updates(&mut kad);
+
+    let mut expected = peers[..K_VALUE.get()]
+        .iter()
+        .map(|p| (*p, RoutingTableAction::Inserted))
+        .collect::<Vec<_>>();
+    expected.extend([
+        (peers[0], RoutingTableAction::Replaced { by: pending }),
+        (pending, RoutingTableAction::Inserted),
+        (
+            peers[1],
+            RoutingTableAction::Evicted {
+                reason: EvictionReason::Manual,
+            },
+        ),
+    ]);
+    assert_eq!(updates, expected);
+}
+
+/// Polls the behaviour until it has no more events to emit.
+fn drain_events(kad: &mut Behaviour<MemoryStore>) {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    while kad.poll(&mut cx).is_ready() {}
+}
+
+fn is_running(kad: &Behaviour<MemoryStore>, id: QueryId) -> bool {
+    kad.query(&id).unwrap().stats().duration().is_some()
+}
+
+#[test]
+fn running_queries_can_be_inspected() {
+    let local_id = PeerId::random();
+    let mut kad = Behaviour::new(local_id, MemoryStore::new(local_id));
+    let peer = PeerId::random();
+    kad.add_address(&peer, Protocol::Memory(random::<u64>()).into());
+    drain_events(&mut kad);
+    let ids = kad.iter_queries().map(|q| q.id()).collect::<Vec<_>>();
+    for id in ids {
+        kad.query_mut(&id).unwrap().finish();
+    }
+    drain_events(&mut kad);
+
+    let key = record::Key::new(&"key");
+    let id = kad.get_record(key.clone());
+    let query = kad.query(&id).unwrap();
+    assert_eq!(query.target(), Some(key.to_vec()));
+    assert!(query.started_at().is_none());
+    assert!(query.contacted_peers().is_empty());
+
+    drain_events(&mut kad);
+
+    let query = kad.query(&id).unwrap();
+    assert!(query.started_at().is_some());
+    assert_eq!(usize::from(query.step().unwrap().count), 1);
+    assert_eq!(query.contacted_peers(), &[peer]);
+    assert_eq!(query.pending_peers().collect::<Vec<_>>(), vec![&peer]);
+    assert_eq!(
+        kad.iter_queries().map(|q| q.id()).collect::<Vec<_>>(),
+        vec![id]
+    );
+}
+
+#[test]
+fn max_running_queries_starts_queries_by_priority() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_max_running_queries(NonZeroUsize::new(1));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+    kad.add_address(&PeerId::random(), addr);
+
+    let normal = kad.get_closest_peers(PeerId::random());
+    let high = kad.get_record(Key::from(random_multihash()));
+    kad.query_mut(&high)
+        .unwrap()
+        .set_priority(QueryPriority::High);
+    drain_events(&mut kad);
+
+    assert!(is_running(&kad, high));
+    assert!(!is_running(&kad, normal));
+
+    kad.query_mut(&high).unwrap().finish();
+    drain_events(&mut kad);
+    assert!(kad.query(&high).is_none());
+    assert!(is_running(&kad, normal));
+}
+
+#[test]
+fn max_running_queries_does_not_starve_background_queries() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_max_running_queries(NonZeroUsize::new(1));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+    kad.add_address(&PeerId::random(), addr);
+    // Finish the bootstrap triggered by adding the address.
+    drain_events(&mut kad);
+    let ids = kad.iter_queries().map(|q| q.id()).collect::<Vec<_>>();
+    for id in ids {
+        kad.query_mut(&id).unwrap().finish();
+    }
+    drain_events(&mut kad);
+
+    let mut running = kad.get_closest_peers(PeerId::random());
+    let background = kad.get_closest_peers(PeerId::random());
+    kad.query_mut(&background)
+        .unwrap()
+        .set_priority(QueryPriority::Background);
+
+    // Keep starting queries of a higher priority while the background query waits.
+    let mut started = 0;
+    loop {
+        let next = kad.get_closest_peers(PeerId::random());
+        drain_events(&mut kad);
+        if is_running(&kad, background) {
+            break;
+        }
+        assert!(is_running(&kad, running));
+        kad.query_mut(&running).unwrap().finish();
+        running = next;
+        started += 1;
+        assert!(started <= 20, "Background query starved");
+    }
+    assert!(started > 1);
+}
+
+#[test]
+fn query_handles_finish_and_cancel_queries() {
+    let local_id = PeerId::random();
+    let mut kad = Behaviour::new(local_id, MemoryStore::new(local_id));
+    let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+    kad.add_address(&PeerId::random(), addr);
+    drain_events(&mut kad);
+
+    let finished = kad.get_closest_peers(PeerId::random());
+    let cancelled = kad.get_record(Key::from(random_multihash()));
+    let finish = kad.query_handle(&finished);
+    let cancel = kad.query_handle(&cancelled);
+    drain_events(&mut kad);
+    assert!(is_running(&kad, finished));
+    assert!(is_running(&kad, cancelled));
+
+    // Use the handles from another thread.
+    std::thread::spawn(move || {
+        finish.finish();
+        cancel.cancel();
+    })
+    .join()
+    .unwrap();
+
+    let mut cx = Context::from_waker(noop_waker_ref());
+    let mut progressed = Vec::new();
+    while let Poll::Ready(event) = kad.poll(&mut cx) {
+        if let ToSwarm::GenerateEvent(Event::OutboundQueryProgressed { id, step, .. }) = event {
+            assert!(step.last);
+            progressed.push(id);
+        }
+    }
+    assert_eq!(progressed, [finished]);
+    assert!(kad.query(&finished).is_none());
+    assert!(kad.query(&cancelled).is_none());
+
+    // Commands for queries no longer running are ignored.
+    kad.query_handle(&cancelled).finish();
+    drain_events(&mut kad);
+}
+
+#[test]
+fn paused_background_jobs_do_not_start_queries() {
+    let local_id = PeerId::random();
+    let mut kad = Behaviour::new(local_id, MemoryStore::new(local_id));
+    let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+    kad.add_address(&PeerId::random(), addr);
+    let record = Record::new(random_multihash(), vec![1]);
+    kad.store.put(record.clone()).unwrap();
+
+    let replicates = |kad: &Behaviour<MemoryStore>| {
+        kad.iter_queries().any(|q| {
+            matches!(
+                q.info(),
+                QueryInfo::PutRecord { record: r, .. } if r.key == record.key
+            )
+        })
+    };
+
+    kad.pause_background_jobs();
+    kad.put_record_job.as_mut().unwrap().asap(false);
+    drain_events(&mut kad);
+    assert!(!replicates(&kad));
+
+    kad.resume_background_jobs();
+    drain_events(&mut kad);
+    assert!(replicates(&kad));
+}
+
+#[test]
+fn max_user_queries_rejects_try_queries() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_max_user_queries(NonZeroUsize::new(2));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+    kad.add_address(&PeerId::random(), addr);
+    assert_eq!(kad.user_query_capacity(), Some(2));
+
+    let first = kad.try_get_record(Key::new(&"a")).unwrap();
+    kad.get_closest_peers(PeerId::random());
+    assert_eq!(kad.user_query_capacity(), Some(0));
+    assert!(kad.try_get_providers(Key::new(&"b")).is_err());
+    assert!(kad.try_get_closest_peers(PeerId::random()).is_err());
+
+    // Background queries do not count towards the limit.
+    kad.query_mut(&first)
+        .unwrap()
+        .set_priority(QueryPriority::Background);
+    assert_eq!(kad.user_query_capacity(), Some(1));
+    assert!(kad.try_get_providers(Key::new(&"b")).is_ok());
+}
+
+#[test]
+fn query_timeout_can_be_overridden_per_query() {
+    let local_id = PeerId::random();
+    let mut kad = Behaviour::new(local_id, MemoryStore::new(local_id));
+    let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+    kad.add_address(&PeerId::random(), addr);
+
+    let timeout = Duration::from_millis(50);
+    let key = Key::from(random_multihash());
+    let id = kad.get_record_with_opts(key.clone(), QueryOpts::new().timeout(timeout));
+    let other = kad.get_record(key.clone());
+    drain_events(&mut kad);
+
+    std::thread::sleep(timeout);
+
+    let result = block_on(poll_fn(|cx| loop {
+        match kad.poll(cx) {
+            Poll::Ready(ToSwarm::GenerateEvent(Event::OutboundQueryProgressed {
+                id: event_id,
+                result: QueryResult::GetRecord(result),
+                ..
+            })) if event_id == id => return Poll::Ready(result),
+            Poll::Ready(_) => {}
+            Poll::Pending => return Poll::Pending,
+        }
+    }));
+    assert!(matches!(result, Err(GetRecordError::Timeout { key: k }) if k == key));
+    // Other queries still use the configured timeout.
+    assert!(kad.query(&other).is_some());
+}
+
+#[test]
+fn query_retried_after_timeout() {
+    let local_id = PeerId::random();
+    let mut kad = Behaviour::new(local_id, MemoryStore::new(local_id));
+    let addr: Multiaddr = Protocol::Memory(random::<u64>()).into();
+    kad.add_address(&PeerId::random(), addr);
+
+    let timeout = Duration::from_millis(50);
+    let backoff = Duration::from_millis(10);
+    let policy = RetryPolicy::new(NonZeroU32::new(2).unwrap(), backoff);
+    let key = Key::from(random_multihash());
+    let opts = QueryOpts::new().timeout(timeout).retry(policy);
+    let id = kad.get_record_with_opts(key.clone(), opts);
+    drain_events(&mut kad);
+
+    let poll_result = |kad: &mut Behaviour<MemoryStore>| {
+        block_on(poll_fn(|cx| loop {
+            match kad.poll(cx) {
+                Poll::Ready(ToSwarm::GenerateEvent(Event::OutboundQueryProgressed {
+                    id: event_id,
+                    result: QueryResult::GetRecord(result),
+                    ..
+                })) if event_id == id => return Poll::Ready(Some(result)),
+                Poll::Ready(_) => {}
+                Poll::Pending => return Poll::Ready(None),
+            }
+        }))
+    };
+
+    // The first timeout is not reported but the query is retried after the backoff.
+    std::thread::sleep(timeout);
+    assert!(poll_result(&mut kad).is_none());
+    assert!(kad.query(&id).is_none());
+    std::thread::sleep(backoff);
+    assert!(poll_result(&mut kad).is_none());
+    assert!(kad.query(&id).is_some());
+
+    // The timeout of the last attempt is reported.
+    std::thread::sleep(timeout);
+    let result = poll_result(&mut kad).expect("query to time out");
+    assert!(matches!(result, Err(GetRecordError::Timeout { key: k }) if k == key));
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn query_stream_reports_progress_until_finished() {
+    let local_id = PeerId::random();
+    let timeout = Duration::from_millis(50);
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_query_timeout(timeout);
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    kad.add_address(&PeerId::random(), Protocol::Memory(random::<u64>()).into());
+
+    let key = Key::from(random_multihash());
+    let timed_out = kad.get_record_stream(key.clone());
+    let cancelled = kad.get_record_stream(Key::from(random_multihash()));
+    kad.query_handle(&cancelled.id()).cancel();
+    drain_events(&mut kad);
+    std::thread::sleep(timeout);
+    drain_events(&mut kad);
+
+    let result = block_on(timed_out.outcome()).expect("query to time out");
+    assert!(matches!(result, Err(GetRecordError::Timeout { key: k }) if k == key));
+    assert!(block_on(cancelled.outcome()).is_none());
+}
+
+#[test]
+fn query_trace_records_hops() {
+    let swarms = build_connected_nodes(3, 1);
+    let peers: Vec<_> = swarms.iter().map(|(_, s)| *s.local_peer_id()).collect();
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let untraced = swarms[0]
+        .behaviour_mut()
+        .get_closest_peers(PeerId::random());
+    let traced = swarms[0]
+        .behaviour_mut()
+        .get_closest_peers_with_opts(PeerId::random(), QueryOpts::new().trace(true));
+    let mut finished = HashMap::new();
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetClosestPeers(_),
+                        stats,
+                        ..
+                    }))) => {
+                        finished.insert(id, stats);
+                        if finished.len() == 2 {
+                            assert!(finished[&untraced].trace().is_none());
+                            let trace = finished[&traced].trace().unwrap();
+                            assert_eq!(trace.len(), 2);
+                            assert!(trace.iter().all(|hop| hop.rpc == QueryRpc::FindNode));
+                            assert!(trace.iter().all(|hop| hop.latency.is_some()));
+                            // The second node returns the third node, which is contacted next.
+                            assert_eq!(trace[0].peer, peers[1]);
+                            assert!(matches!(
+                                &trace[0].result,
+                                QueryHopResult::Success { peers: returned } if returned.contains(&peers[2])
+                            ));
+                            assert_eq!(trace[1].peer, peers[2]);
+                            return Poll::Ready(());
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn inbound_write_requests_are_rate_limited() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_inbound_write_rate_limit(Some((
+        NonZeroU32::new(2).unwrap(),
+        Duration::from_secs(3600),
+    )));
+    cfg.set_global_inbound_write_rate_limit(Some((
+        NonZeroU32::new(3).unwrap(),
+        Duration::from_secs(3600),
+    )));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+
+    let add_provider = |kad: &mut Behaviour<MemoryStore>, peer: PeerId| {
+        kad.on_connection_handler_event(
+            peer,
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::AddProvider {
+                key: Key::from(random_multihash()),
+                provider: KadPeer {
+                    node_id: peer,
+                    multiaddrs: Vec::new(),
+                    connection_ty: ConnectionType::Connected,
+                    signed_record: None,
+                },
+                expires: None,
+            },
+        );
+        kad.queued_events.drain(..).find_map(|e| match e {
+            ToSwarm::GenerateEvent(Event::InboundRequestThrottled {
+                peer: p,
+                request: ThrottledRequest::AddProvider,
+                limit,
+            }) if p == peer => Some(limit),
+            _ => None,
+        })
+    };
+
+    let first = PeerId::random();
+    assert_eq!(add_provider(&mut kad, first), None);
+    assert_eq!(add_provider(&mut kad, first), None);
+    assert_eq!(add_provider(&mut kad, first), Some(RateLimit::PerPeer));
+
+    let second = PeerId::random();
+    assert_eq!(add_provider(&mut kad, second), None);
+    assert_eq!(add_provider(&mut kad, second), Some(RateLimit::Global));
+}
+
+#[test]
+fn inbound_requests_of_peers_not_allowlisted_are_denied() {
+    let local_id = PeerId::random();
+    let member = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_request_allowlist([member]);
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+
+    let add_provider = |kad: &mut Behaviour<MemoryStore>, peer: PeerId| {
+        let key = Key::from(random_multihash());
+        kad.on_connection_handler_event(
+            peer,
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::AddProvider {
+                key: key.clone(),
+                provider: KadPeer {
+                    node_id: peer,
+                    multiaddrs: Vec::new(),
+                    connection_ty: ConnectionType::Connected,
+                    signed_record: None,
+                },
+                expires: None,
+            },
+        );
+        let denied = kad.queued_events.drain(..).any(|e| {
+            matches!(
+                e,
+                ToSwarm::GenerateEvent(Event::InboundRequestDenied {
+                    peer: p,
+                    request: RestrictedRequest::AddProvider,
+                }) if p == peer
+            )
+        });
+        assert_eq!(kad.store.providers(&key).is_empty(), denied);
+        denied
+    };
+
+    assert!(!add_provider(&mut kad, member));
+    assert!(add_provider(&mut kad, PeerId::random()));
+}
+
+#[test]
+fn provider_filter_redacts_providers() {
+    let key = Key::from(random_multihash());
+    let [kept, remote_redacted, local_redacted, stored_redacted] =
+        std::array::from_fn(|_| PeerId::random());
+
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_provider_filter(
+        move |_: &Key, provider: &PeerId, _: &[Multiaddr], target| match target {
+            ProvidersFor::Remote { .. } => provider != &remote_redacted,
+            ProvidersFor::Local { source: Some(_) } => provider != &local_redacted,
+            ProvidersFor::Local { source: None } => provider != &stored_redacted,
+        },
+    );
+    let mut swarms = build_nodes_with_config(2, cfg);
+    let (peer_id, address) = (*swarms[1].1.local_peer_id(), swarms[1].0.clone());
+    swarms[0].1.behaviour_mut().add_address(&peer_id, address);
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let provider = |peer| {
+        ProviderRecord::new(
+            key.clone(),
+            peer,
+            vec!["/ip4/127.0.0.1/tcp/4001".parse().unwrap()],
+        )
+    };
+    for peer in [kept, remote_redacted, local_redacted] {
+        swarms[1]
+            .behaviour_mut()
+            .store
+            .add_provider(provider(peer))
+            .unwrap();
+    }
+    swarms[0]
+        .behaviour_mut()
+        .store
+        .add_provider(provider(stored_redacted))
+        .unwrap();
+
+    let query_id = swarms[0].behaviour_mut().get_providers(key.clone());
+    let mut found = HashSet::new();
+    block_on(poll_fn(|ctx| {
+        for (i, swarm) in swarms.iter_mut().enumerate() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetProviders(Ok(ok)),
+                        step,
+                        ..
+                    }))) if i == 0 && id == query_id => {
+                        if let GetProvidersOk::FoundProviders { providers, .. } = ok {
+                            found.extend(providers);
+                        }
+                        if step.last {
+                            return Poll::Ready(());
+                        }
+                    }
+                    Poll::Ready(..) => {}
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Poll::Pending
+    }));
+
+    assert_eq!(found, HashSet::from([kept]));
+}
+
+#[test]
+fn inbound_requests_are_observed() {
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_inbound_request_observer({
+        let observed = observed.clone();
+        move |info: &InboundRequestInfo| observed.lock().unwrap().push(info.clone())
+    });
+    let mut swarms = build_nodes_with_config(2, cfg);
+    let (peer_id, address) = (*swarms[1].1.local_peer_id(), swarms[1].0.clone());
+    swarms[0].1.behaviour_mut().add_address(&peer_id, address);
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let record = Record::new(random_multihash(), vec![1]);
+    swarms[1].behaviour_mut().store.put(record.clone()).unwrap();
+
+    let query_id = swarms[0].behaviour_mut().get_record(record.key.clone());
+    block_on(poll_fn(|ctx| {
+        for (i, swarm) in swarms.iter_mut().enumerate() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        step,
+                        ..
+                    }))) if i == 0 && id == query_id && step.last => return Poll::Ready(()),
+                    Poll::Ready(..) => {}
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Poll::Pending
+    }));
+
+    let observed = observed.lock().unwrap();
+    let info = observed
+        .iter()
+        .find(|info| info.request == InboundRequestType::GetRecord)
+        .expect("the get record request to be observed");
+    assert_eq!(&info.peer, swarms[0].local_peer_id());
+    assert_eq!(info.key, record.key.to_vec());
+    assert!(info.served_from_store);
+    assert_eq!(info.key_prefix(2), &record.key.to_vec()[..2]);
+}
+
+#[test]
+fn get_record_caches_record_automatically() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_caching(Caching::Automatic {
+        max_peers: 1,
+        max_ttl: Duration::from_secs(60),
+        probability: 1.0,
+    });
+    let mut swarms = build_nodes_with_config(3, cfg);
+
+    // Let first peer know of second peer and second peer know of third peer.
+    for i in 0..2 {
+        let (peer_id, address) = (
+            *Swarm::local_peer_id(&swarms[i + 1].1),
+            swarms[i + 1].0.clone(),
+        );
+        swarms[i].1.behaviour_mut().add_address(&peer_id, address);
+    }
+
+    // Drop the swarm addresses.
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let record = Record::new(random_multihash(), vec![4, 5, 6]);
+    swarms[2].behaviour_mut().store.put(record.clone()).unwrap();
+    swarms[0].behaviour_mut().get_record(record.key.clone());
+    let cache_peer = *swarms[1].local_peer_id();
+
+    let peers = block_on(poll_fn(|ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::RecordCached {
+                        key,
+                        peers,
+                    }))) => {
+                        assert_eq!(key, record.key);
+                        return Poll::Ready(peers);
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }));
+
+    assert_eq!(peers, vec![cache_peer]);
+    let cached = swarms[1]
+        .behaviour_mut()
+        .store
+        .get(&record.key)
+        .expect("record to be cached")
+        .into_owned();
+    assert_eq!(cached.value, record.value);
+    assert!(cached.expires.unwrap() <= Instant::now() + Duration::from_secs(60));
+}
+
+#[test]
+fn advertisements_are_deferred_until_dialable() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_advertise_when_dialable(true);
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    kad.add_address(&PeerId::random(), "/ip4/1.2.3.4/tcp/4001".parse().unwrap());
+
+    let key = Key::from(random_multihash());
+    let id = kad.start_providing(key.clone()).unwrap();
+    assert!(kad.query(&id).is_none());
+    assert!(kad
+        .store
+        .providers(&key)
+        .iter()
+        .any(|p| p.provider == local_id));
+
+    let relayed: Multiaddr = format!("/ip4/5.6.7.8/tcp/4001/p2p/{}/p2p-circuit", PeerId::random())
+        .parse()
+        .unwrap();
+    kad.on_swarm_event(FromSwarm::NewListenAddr(NewListenAddr {
+        listener_id: ListenerId::next(),
+        addr: &relayed,
+    }));
+    assert!(kad.query(&id).is_some());
+
+    // Advertisements of stopped providers are dropped.
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_advertise_when_dialable(true);
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    let id = kad.start_providing(key.clone()).unwrap();
+    kad.stop_providing(&key);
+    kad.on_swarm_event(FromSwarm::ExternalAddrConfirmed(ExternalAddrConfirmed {
+        addr: &"/ip4/1.2.3.4/tcp/4001".parse().unwrap(),
+    }));
+    assert!(kad.query(&id).is_none());
+    assert_eq!(kad.iter_queries().count(), 0);
+}
+
+#[test]
+fn provider_records_carry_expirations() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_provider_record_ttl(Some(Duration::from_secs(60)));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+
+    let key = Key::from(random_multihash());
+    kad.start_providing_with_ttl(key.clone(), Duration::from_secs(10))
+        .unwrap();
+    let local = kad.store.provider(&key, &local_id).unwrap();
+    assert!(local.expires.unwrap() <= Instant::now() + Duration::from_secs(10));
+
+    let mut add_provider = |peer: PeerId, expires: Option<Instant>| {
+        kad.on_connection_handler_event(
+            peer,
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::AddProvider {
+                key: key.clone(),
+                provider: KadPeer {
+                    node_id: peer,
+                    multiaddrs: Vec::new(),
+                    connection_ty: ConnectionType::Connected,
+                    signed_record: None,
+                },
+                expires,
+            },
+        );
+        kad.store.provider(&key, &peer).unwrap().expires.unwrap()
+    };
+
+    let now = Instant::now();
+    let short = add_provider(PeerId::random(), Some(now + Duration::from_secs(5)));
+    assert!(short <= now + Duration::from_secs(5));
+    // Announced expirations are capped by the configured TTL.
+    let capped = add_provider(PeerId::random(), Some(now + Duration::from_secs(3600)));
+    assert!(capped <= Instant::now() + Duration::from_secs(60));
+    let default = add_provider(PeerId::random(), None);
+    assert!(default > now + Duration::from_secs(30));
+}
+
+/// Polls the behaviour until it has no more events to emit, returning the mode changes.
+fn drain_mode_changes(kad: &mut Behaviour<MemoryStore>) -> Vec<(Mode, ModeChangeReason)> {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    let mut changes = Vec::new();
+    while let Poll::Ready(event) = kad.poll(&mut cx) {
+        if let ToSwarm::GenerateEvent(Event::ModeChanged { new_mode, reason }) = event {
+            changes.push((new_mode, reason));
+        }
+    }
+    changes
+}
+
+#[test]
+fn reachability_determines_mode() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_reachability_confidence_threshold(2);
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+
+    kad.on_swarm_event(FromSwarm::ExternalAddrConfirmed(ExternalAddrConfirmed {
+        addr: &"/ip4/1.2.3.4/tcp/4001".parse().unwrap(),
+    }));
+    assert_eq!(
+        drain_mode_changes(&mut kad),
+        [(Mode::Server, ModeChangeReason::ExternalAddresses)]
+    );
+
+    // Not confident enough to override the external addresses.
+    kad.set_reachability(Reachability::Private, 1);
+    assert!(drain_mode_changes(&mut kad).is_empty());
+
+    kad.set_reachability(Reachability::Private, 2);
+    assert_eq!(
+        drain_mode_changes(&mut kad),
+        [(Mode::Client, ModeChangeReason::Reachability)]
+    );
+
+    kad.set_reachability(Reachability::Unknown, 0);
+    assert_eq!(
+        drain_mode_changes(&mut kad),
+        [(Mode::Server, ModeChangeReason::ExternalAddresses)]
+    );
+
+    // An explicitly set mode is not overridden.
+    kad.set_mode(Some(Mode::Server));
+    kad.set_reachability(Reachability::Private, 5);
+    assert!(drain_mode_changes(&mut kad).is_empty());
+    assert_eq!(kad.mode, Mode::Server);
+}
+
+#[test]
+fn mode_switch_interval_prevents_flapping() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_mode_switch_interval(Some(Duration::from_millis(200)));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+
+    kad.set_reachability(Reachability::Public, 1);
+    assert_eq!(
+        drain_mode_changes(&mut kad),
+        [(Mode::Server, ModeChangeReason::Reachability)]
+    );
+
+    // Flapping back and forth within the interval is suppressed.
+    kad.set_reachability(Reachability::Private, 1);
+    kad.set_reachability(Reachability::Public, 1);
+    kad.set_reachability(Reachability::Private, 1);
+    assert!(drain_mode_changes(&mut kad).is_empty());
+    assert_eq!(kad.mode, Mode::Server);
+
+    // The last reachability takes effect once the interval elapsed.
+    block_on(Delay::new(Duration::from_millis(250)));
+    assert_eq!(
+        drain_mode_changes(&mut kad),
+        [(Mode::Client, ModeChangeReason::Reachability)]
+    );
+}
+
+#[test]
+fn unreachable_peers_are_evicted_by_address_revalidation() {
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_address_revalidation_interval(Some(Duration::from_millis(10)));
+    cfg.set_address_revalidation_max_failures(NonZeroU32::new(2).unwrap());
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    let peer = PeerId::random();
+    kad.add_address(&peer, Protocol::Memory(random::<u64>()).into());
+
+    let revalidate = |kad: &mut Behaviour<MemoryStore>| {
+        block_on(poll_fn(|cx| loop {
+            match kad.poll(cx) {
+                Poll::Ready(ToSwarm::Dial { opts }) => {
+                    assert_eq!(opts.get_peer_id(), Some(peer));
+                    return Poll::Ready(());
+                }
+                Poll::Ready(_) => continue,
+                Poll::Pending => return Poll::Pending,
+            }
+        }));
+        kad.on_swarm_event(FromSwarm::DialFailure(swarm::behaviour::DialFailure {
+            peer_id: Some(peer),
+            error: &swarm::DialError::NoAddresses,
+            connection_id: ConnectionId::new_unchecked(0),
+        }));
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut evicted = false;
+        while let Poll::Ready(event) = kad.poll(&mut cx) {
+            evicted |= matches!(
+                event,
+                ToSwarm::GenerateEvent(Event::RoutingTableUpdated {
+                    action: RoutingTableAction::Evicted {
+                        reason: EvictionReason::Unreachable
+                    },
+                    ..
+                })
+            );
+        }
+        evicted
+    };
+
+    assert!(!revalidate(&mut kad));
+    assert!(kad
+        .kbucket(peer)
+        .unwrap()
+        .iter()
+        .any(|e| e.node.key.preimage() == &peer));
+    assert!(revalidate(&mut kad));
+    assert!(kad.kbucket(peer).unwrap().is_empty());
+}
+
+#[test]
+fn routing_table_uses_configured_key_hasher() {
+    fn double_sha256(preimage: &[u8]) -> [u8; 32] {
+        Sha256::digest(Sha256::digest(preimage)).into()
+    }
+
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_key_hasher(double_sha256);
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    for _ in 0..20 {
+        kad.add_address(&PeerId::random(), Protocol::Udp(10u16).into());
+    }
+
+    let local_key = kad.kbuckets.local_key().clone();
+    assert_eq!(
+        local_key.hashed_bytes(),
+        double_sha256(&local_id.to_bytes())
+    );
+    let mut num_entries = 0;
+    for bucket in kad.kbuckets() {
+        for entry in bucket.iter() {
+            let key = entry.node.key;
+            assert_eq!(
+                key.hashed_bytes(),
+                double_sha256(&key.preimage().to_bytes())
+            );
+            assert!(bucket.contains(&local_key.distance(key)));
+            num_entries += 1;
+        }
+    }
+    assert_eq!(num_entries, 20);
+}
+
+#[test]
+fn routing_table_is_restricted_to_configured_protocols() {
+    const FORK_PROTOCOL: StreamProtocol = StreamProtocol::new("/fork/kad/1.0.0");
+
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.add_protocol_name(FORK_PROTOCOL);
+    cfg.set_routing_table_protocols(Some(vec![PROTOCOL_NAME]));
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+    assert_eq!(kad.protocol_names(), [PROTOCOL_NAME, FORK_PROTOCOL]);
+
+    let connect = |kad: &mut Behaviour<MemoryStore>, protocol: StreamProtocol| {
+        let peer = PeerId::random();
+        let endpoint = ConnectedPoint::Dialer {
+            address: Protocol::Memory(random::<u64>()).into(),
+            role_override: Endpoint::Dialer,
+        };
+        kad.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+            peer_id: peer,
+            connection_id: ConnectionId::new_unchecked(0),
+            endpoint: &endpoint,
+            failed_addresses: &[],
+            other_established: 0,
+        }));
+        kad.on_connection_handler_event(
+            peer,
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::ProtocolConfirmed { endpoint, protocol },
+        );
+        peer
+    };
+
+    let peer = connect(&mut kad, PROTOCOL_NAME);
+    let fork_peer = connect(&mut kad, FORK_PROTOCOL);
+
+    assert_eq!(kad.peer_protocol(&peer), Some(&PROTOCOL_NAME));
+    assert_eq!(kad.peer_protocol(&fork_peer), Some(&FORK_PROTOCOL));
+    assert_eq!(
+        kad.connected_peers_per_protocol(),
+        HashMap::from([(PROTOCOL_NAME, 1), (FORK_PROTOCOL, 1)])
+    );
+
+    let in_routing_table = |kad: &mut Behaviour<MemoryStore>, peer: PeerId| {
+        kad.kbucket(peer)
+            .is_some_and(|b| b.iter().any(|e| e.node.key.preimage() == &peer))
+    };
+    assert!(in_routing_table(&mut kad, peer));
+    assert!(!in_routing_table(&mut kad, fork_peer));
+}
+
+#[test]
+fn relayed_addresses_policy() {
+    let relayed = |relay: &str| -> Multiaddr {
+        format!("/ip4/{relay}/tcp/4001/p2p/{}/p2p-circuit", PeerId::random())
+            .parse()
+            .unwrap()
+    };
+    let direct: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+    let behind_nat = PeerId::random();
+    let dual_stack = PeerId::random();
+
+    let responses = |policy: RelayedAddresses| {
+        let local_id = PeerId::random();
+        let mut cfg = Config::new(PROTOCOL_NAME);
+        cfg.set_relayed_addresses(policy);
+        let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+        let updates = [
+            kad.add_address(&behind_nat, relayed("5.6.7.8")),
+            kad.add_address(&dual_stack, direct.clone()),
+            kad.add_address(&dual_stack, relayed("9.10.11.12")),
+        ];
+        let peers = kad.find_closest(&kbucket::Key::from(PeerId::random()), &local_id);
+        let num_addresses = |peer: PeerId| {
+            peers
+                .iter()
+                .find(|p| p.node_id == peer)
+                .map(|p| p.multiaddrs.len())
+        };
+        (
+            updates.map(|u| matches!(u, RoutingUpdate::Success)),
+            num_addresses(behind_nat),
+            num_addresses(dual_stack),
+        )
+    };
+
+    assert_eq!(
+        responses(RelayedAddresses::Include),
+        ([true, true, true], Some(1), Some(2))
+    );
+    assert_eq!(
+        responses(RelayedAddresses::Exclude),
+        ([false, true, false], None, Some(1))
+    );
+    assert_eq!(
+        responses(RelayedAddresses::Fallback),
+        ([true, true, true], Some(1), Some(1))
+    );
+}
+
+#[test]
+fn bootstrap_peers_are_redialed_when_isolated() {
+    let local_id = PeerId::random();
+    let mut kad = Behaviour::with_config(
+        local_id,
+        MemoryStore::new(local_id),
+        Config::new(PROTOCOL_NAME),
+    );
+    let peer = PeerId::random();
+    let address: Multiaddr = Protocol::Memory(random::<u64>()).into();
+    kad.add_bootstrap_peer(peer, address.clone());
+
+    let drain = |kad: &mut Behaviour<MemoryStore>| {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let (mut dialed, mut events) = (false, Vec::new());
+        while let Poll::Ready(event) = kad.poll(&mut cx) {
+            match event {
+                ToSwarm::Dial { opts } => dialed |= opts.get_peer_id() == Some(peer),
+                ToSwarm::GenerateEvent(
+                    e @ (Event::BootstrapConnectivityLost | Event::BootstrapConnectivityRegained),
+                ) => events.push(e),
+                _ => {}
+            }
+        }
+        (dialed, events)
+    };
+
+    // The node starts out isolated and dials its bootstrap peer right away.
+    let (dialed, events) = drain(&mut kad);
+    assert!(dialed);
+    assert!(events.is_empty());
+
+    let endpoint = ConnectedPoint::Dialer {
+        address,
+        role_override: Endpoint::Dialer,
+    };
+    kad.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+        peer_id: peer,
+        connection_id: ConnectionId::new_unchecked(0),
+        endpoint: &endpoint,
+        failed_addresses: &[],
+        other_established: 0,
+    }));
+    let (dialed, events) = drain(&mut kad);
+    assert!(!dialed);
+    assert!(events.is_empty());
+
+    kad.on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
+        peer_id: peer,
+        connection_id: ConnectionId::new_unchecked(0),
+        endpoint: &endpoint,
+        remaining_established: 0,
+    }));
+    let (dialed, events) = drain(&mut kad);
+    assert!(dialed);
+    assert!(matches!(events[..], [Event::BootstrapConnectivityLost]));
+
+    kad.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+        peer_id: peer,
+        connection_id: ConnectionId::new_unchecked(1),
+        endpoint: &endpoint,
+        failed_addresses: &[],
+        other_established: 0,
+    }));
+    let (_, events) = drain(&mut kad);
+    assert!(matches!(events[..], [Event::BootstrapConnectivityRegained]));
+}
+
+#[test]
+fn address_filter_rejects_addresses() {
+    let sources = Arc::new(Mutex::new(Vec::new()));
+    let local_id = PeerId::random();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_address_filter({
+        let sources = sources.clone();
+        move |_: &PeerId, address: &Multiaddr, source: AddressSource| {
+            sources.lock().unwrap().push(source);
+            !matches!(address.iter().next(), Some(Protocol::Ip4(ip)) if ip.is_private())
+        }
+    });
+    let mut kad = Behaviour::with_config(local_id, MemoryStore::new(local_id), cfg);
+
+    let private: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+    let public: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+    assert!(matches!(
+        kad.add_address(&PeerId::random(), private.clone()),
+        RoutingUpdate::Failed
+    ));
+    assert!(matches!(
+        kad.add_address(&PeerId::random(), public),
+        RoutingUpdate::Success
+    ));
+
+    // A peer dialed at a rejected address is not added to the routing table either.
+    let peer = PeerId::random();
+    let endpoint = ConnectedPoint::Dialer {
+        address: private,
+        role_override: Endpoint::Dialer,
+    };
+    kad.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+        peer_id: peer,
+        connection_id: ConnectionId::new_unchecked(0),
+        endpoint: &endpoint,
+        failed_addresses: &[],
+        other_established: 0,
+    }));
+    kad.on_connection_handler_event(
+        peer,
+        ConnectionId::new_unchecked(0),
+        HandlerEvent::ProtocolConfirmed {
+            endpoint,
+            protocol: PROTOCOL_NAME,
+        },
+    );
+
+    assert_eq!(kad.kbuckets().map(|b| b.num_entries()).sum::<usize>(), 1);
+    assert_eq!(
+        *sources.lock().unwrap(),
+        [
+            AddressSource::Explicit,
+            AddressSource::Explicit,
+            AddressSource::Connection
+        ]
+    );
+}
@@ -0,0 +1,167 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The managed set of bootstrap peers.
+//!
+//! A node that loses all its connections or all peers of its routing table has no
+//! way of rejoining the DHT on its own. The bootstrap peers registered by the
+//! application are therefore re-dialed periodically while the node is isolated.
+
+use futures::FutureExt;
+use futures_timer::Delay;
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use std::collections::HashMap;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Tracks the connectivity of the node and schedules the re-dials of the bootstrap peers.
+pub(crate) struct BootstrapPeers {
+    /// The addresses of the bootstrap peers.
+    peers: HashMap<PeerId, Vec<Multiaddr>>,
+    /// The interval between two re-dials while the node is isolated.
+    redial_interval: Duration,
+    /// Whether the node is connected to the DHT.
+    connectivity: Connectivity,
+    /// The delay until the next re-dial, set while the node is isolated.
+    redial: Option<Delay>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Connectivity {
+    /// The node has not been connected to the DHT yet.
+    Pending,
+    Connected,
+    Lost,
+}
+
+/// A change of the connectivity of the node to the DHT.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Change {
+    Lost,
+    Regained,
+}
+
+impl BootstrapPeers {
+    pub(crate) fn new(redial_interval: Duration) -> Self {
+        Self {
+            peers: HashMap::new(),
+            redial_interval,
+            connectivity: Connectivity::Pending,
+            redial: None,
+        }
+    }
+
+    /// Adds an address of a bootstrap peer, returning `false` if it is known already.
+    pub(crate) fn insert(&mut self, peer: PeerId, address: Multiaddr) -> bool {
+        let addresses = self.peers.entry(peer).or_default();
+        if addresses.contains(&address) {
+            return false;
+        }
+        addresses.push(address);
+        true
+    }
+
+    /// Removes a bootstrap peer, returning its addresses.
+    pub(crate) fn remove(&mut self, peer: &PeerId) -> Option<Vec<Multiaddr>> {
+        self.peers.remove(peer)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&PeerId, &Vec<Multiaddr>)> {
+        self.peers.iter()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Updates whether the node is isolated, i.e. has no connections or an empty routing
+    /// table, scheduling an immediate re-dial once it becomes isolated.
+    ///
+    /// Returns the change of connectivity, if any. Connectivity is only reported as
+    /// regained after it has been lost, not when the node connects for the first time.
+    pub(crate) fn update(&mut self, isolated: bool) -> Option<Change> {
+        let previous = self.connectivity;
+        match (previous, isolated) {
+            (Connectivity::Pending | Connectivity::Lost, false) => {
+                self.connectivity = Connectivity::Connected;
+                self.redial = None;
+                (previous == Connectivity::Lost).then_some(Change::Regained)
+            }
+            (Connectivity::Connected, true) => {
+                self.connectivity = Connectivity::Lost;
+                self.redial = Some(Delay::new(Duration::ZERO));
+                Some(Change::Lost)
+            }
+            (Connectivity::Pending | Connectivity::Lost, true) => {
+                if self.redial.is_none() {
+                    self.redial = Some(Delay::new(Duration::ZERO));
+                }
+                None
+            }
+            (Connectivity::Connected, false) => None,
+        }
+    }
+
+    /// Resolves once the bootstrap peers are to be re-dialed, scheduling the next re-dial.
+    pub(crate) fn poll_redial(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(delay) = self.redial.as_mut() else {
+            return Poll::Pending;
+        };
+        if self.peers.is_empty() {
+            return Poll::Pending;
+        }
+        futures::ready!(delay.poll_unpin(cx));
+        delay.reset(self.redial_interval);
+        Poll::Ready(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_lost_and_regained_connectivity() {
+        let mut peers = BootstrapPeers::new(Duration::from_secs(1));
+        assert!(peers.insert(PeerId::random(), Multiaddr::empty()));
+
+        // Connecting for the first time is not reported.
+        assert_eq!(peers.update(true), None);
+        assert!(peers.redial.is_some());
+        assert_eq!(peers.update(false), None);
+        assert!(peers.redial.is_none());
+
+        assert_eq!(peers.update(true), Some(Change::Lost));
+        assert_eq!(peers.update(true), None);
+        assert_eq!(peers.update(false), Some(Change::Regained));
+        assert_eq!(peers.update(false), None);
+    }
+
+    #[test]
+    fn ignores_duplicate_addresses() {
+        let mut peers = BootstrapPeers::new(Duration::from_secs(1));
+        let peer = PeerId::random();
+        assert!(peers.insert(peer, Multiaddr::empty()));
+        assert!(!peers.insert(peer, Multiaddr::empty()));
+        assert_eq!(peers.remove(&peer), Some(vec![Multiaddr::empty()]));
+        assert!(peers.is_empty());
+    }
+}
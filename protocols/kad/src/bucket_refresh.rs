@@ -0,0 +1,123 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Tracking of stale k-buckets.
+//!
+//! A bucket is refreshed by a lookup for a key in its range. A bucket without such
+//! a lookup within the configured staleness threshold is stale and, if not empty,
+//! automatically refreshed.
+
+use crate::kbucket::NUM_BUCKETS;
+use futures::FutureExt;
+use futures_timer::Delay;
+use instant::Instant;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Tracks the lookups per bucket to determine the stale buckets.
+pub(crate) struct BucketRefreshes {
+    /// The time after which a bucket without lookups is stale,
+    /// if stale buckets are refreshed automatically.
+    staleness_threshold: Option<Duration>,
+    /// The time of the last lookup for a key in each bucket.
+    last_lookups: Vec<Instant>,
+    /// The delay until the next check for stale buckets.
+    delay: Option<Delay>,
+}
+
+impl BucketRefreshes {
+    pub(crate) fn new(staleness_threshold: Option<Duration>) -> Self {
+        Self {
+            staleness_threshold,
+            last_lookups: vec![Instant::now(); NUM_BUCKETS],
+            delay: staleness_threshold.map(Delay::new),
+        }
+    }
+
+    /// Records a lookup for a key in the given bucket.
+    pub(crate) fn on_lookup(&mut self, bucket: u32) {
+        if let Some(last_lookup) = self.last_lookups.get_mut(bucket as usize) {
+            *last_lookup = Instant::now();
+        }
+    }
+
+    /// Resolves once the buckets are to be checked for staleness,
+    /// see [`BucketRefreshes::stale_buckets`].
+    pub(crate) fn poll_check(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        match self.delay.as_mut() {
+            Some(delay) => delay.poll_unpin(cx),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Returns the stale buckets among the given non-empty buckets, which are
+    /// considered refreshed from now on, and schedules the next check for the
+    /// time the first of the remaining buckets turns stale.
+    pub(crate) fn stale_buckets(&mut self, non_empty: impl Iterator<Item = u32>) -> Vec<u32> {
+        let Some(threshold) = self.staleness_threshold else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+        let mut next_check = threshold;
+        let mut stale = Vec::new();
+        for bucket in non_empty {
+            let Some(last_lookup) = self.last_lookups.get(bucket as usize) else {
+                continue;
+            };
+            match (*last_lookup + threshold).checked_duration_since(now) {
+                Some(remaining) if !remaining.is_zero() => {
+                    next_check = next_check.min(remaining);
+                }
+                _ => stale.push(bucket),
+            }
+        }
+        for bucket in &stale {
+            self.on_lookup(*bucket);
+        }
+        if let Some(delay) = self.delay.as_mut() {
+            delay.reset(next_check);
+        }
+        stale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_turn_stale_after_threshold() {
+        let mut refreshes = BucketRefreshes::new(Some(Duration::from_millis(50)));
+        assert!(refreshes.stale_buckets([250, 255].into_iter()).is_empty());
+
+        std::thread::sleep(Duration::from_millis(60));
+        refreshes.on_lookup(250);
+
+        assert_eq!(refreshes.stale_buckets([250, 255].into_iter()), vec![255]);
+        // Stale buckets are considered refreshed once reported.
+        assert!(refreshes.stale_buckets([250, 255].into_iter()).is_empty());
+    }
+
+    #[test]
+    fn no_stale_buckets_without_threshold() {
+        let mut refreshes = BucketRefreshes::new(None);
+        assert!(refreshes.stale_buckets([255].into_iter()).is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Resolution of conflicts between divergent records for the same key.
+//!
+//! Applications register a [`ConflictResolver`] per key namespace via
+//! [`Config::add_conflict_resolver`](crate::Config::add_conflict_resolver).
+//! Records whose key is not in a namespace with a registered resolver are
+//! resolved by [`HighestSequence`].
+
+use crate::record::Record;
+use crate::validator::namespace;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Decides which of two records for the same key of a namespace supersedes the other.
+///
+/// The resolver is consulted on records of inbound `PUT_VALUE` requests, which are
+/// rejected if superseded by the record stored locally, and on the records found by
+/// [`Behaviour::get_record`](crate::Behaviour::get_record), of which only those not
+/// superseded by a record found before are reported.
+pub trait ConflictResolver: Send + Sync + 'static {
+    /// Compares two records for the same key, returning [`Ordering::Greater`]
+    /// if `record` supersedes `other`, [`Ordering::Less`] if `other` supersedes
+    /// `record` and [`Ordering::Equal`] if neither supersedes the other.
+    fn compare(&self, record: &Record, other: &Record) -> Ordering;
+}
+
+impl<F> ConflictResolver for F
+where
+    F: Fn(&Record, &Record) -> Ordering + Send + Sync + 'static,
+{
+    fn compare(&self, record: &Record, other: &Record) -> Ordering {
+        self(record, other)
+    }
+}
+
+/// The default [`ConflictResolver`], under which a record with a higher
+/// [`Record::sequence`] supersedes one with a lower or without a sequence number.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HighestSequence;
+
+impl ConflictResolver for HighestSequence {
+    fn compare(&self, record: &Record, other: &Record) -> Ordering {
+        record.sequence.cmp(&other.sequence)
+    }
+}
+
+/// The [`ConflictResolver`]s registered per key namespace.
+#[derive(Clone, Default)]
+pub(crate) struct ConflictResolvers {
+    resolvers: HashMap<Vec<u8>, Arc<dyn ConflictResolver>>,
+}
+
+impl ConflictResolvers {
+    pub(crate) fn insert(&mut self, namespace: &str, resolver: impl ConflictResolver) {
+        self.insert_shared(namespace, Arc::new(resolver));
+    }
+
+    pub(crate) fn insert_shared(&mut self, namespace: &str, resolver: Arc<dyn ConflictResolver>) {
+        self.resolvers
+            .insert(namespace.as_bytes().to_vec(), resolver);
+    }
+
+    pub(crate) fn contains(&self, namespace: &[u8]) -> bool {
+        self.resolvers.contains_key(namespace)
+    }
+
+    /// Compares two records for the same key with the resolver of its key
+    /// namespace, if any, or [`HighestSequence`] otherwise.
+    pub(crate) fn compare(&self, record: &Record, other: &Record) -> Ordering {
+        match namespace(&record.key).and_then(|ns| self.resolvers.get(ns)) {
+            Some(resolver) => resolver.compare(record, other),
+            None => HighestSequence.compare(record, other),
+        }
+    }
+}
+
+impl fmt::Debug for ConflictResolvers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(
+                self.resolvers
+                    .keys()
+                    .map(|ns| String::from_utf8_lossy(ns).into_owned()),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record;
+
+    fn record(key: &str, value: u8, sequence: Option<u64>) -> Record {
+        let mut record = Record::new(record::Key::new(&key), vec![value]);
+        record.sequence = sequence;
+        record
+    }
+
+    #[test]
+    fn highest_sequence_supersedes() {
+        let resolvers = ConflictResolvers::default();
+
+        let old = record("/ipns/abc", 1, Some(1));
+        let new = record("/ipns/abc", 2, Some(2));
+        let unversioned = record("/ipns/abc", 3, None);
+
+        assert_eq!(resolvers.compare(&new, &old), Ordering::Greater);
+        assert_eq!(resolvers.compare(&old, &new), Ordering::Less);
+        assert_eq!(resolvers.compare(&old, &unversioned), Ordering::Greater);
+        assert_eq!(
+            resolvers.compare(&unversioned, &unversioned),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn resolves_by_namespace() {
+        let mut resolvers = ConflictResolvers::default();
+        // Prefers the larger value regardless of the sequence number.
+        resolvers.insert("pk", |record: &Record, other: &Record| {
+            record.value.cmp(&other.value)
+        });
+
+        let a = record("/pk/abc", 1, Some(2));
+        let b = record("/pk/abc", 2, Some(1));
+        assert_eq!(resolvers.compare(&b, &a), Ordering::Greater);
+
+        let a = record("/ipns/abc", 1, Some(2));
+        let b = record("/ipns/abc", 2, Some(1));
+        assert_eq!(resolvers.compare(&b, &a), Ordering::Less);
+    }
+}
@@ -21,6 +21,7 @@ pub struct Record {
     pub timeReceived: String,
     pub publisher: Vec<u8>,
     pub ttl: u32,
+    pub sequence: Option<u64>,
 }
 
 impl<'a> MessageRead<'a> for Record {
@@ -33,6 +34,7 @@ impl<'a> MessageRead<'a> for Record {
                 Ok(42) => msg.timeReceived = r.read_string(bytes)?.to_owned(),
                 Ok(5330) => msg.publisher = r.read_bytes(bytes)?.to_owned(),
                 Ok(6216) => msg.ttl = r.read_uint32(bytes)?,
+                Ok(7104) => msg.sequence = Some(r.read_uint64(bytes)?),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -49,6 +51,7 @@ impl MessageWrite for Record {
         + if self.timeReceived == String::default() { 0 } else { 1 + sizeof_len((&self.timeReceived).len()) }
         + if self.publisher.is_empty() { 0 } else { 2 + sizeof_len((&self.publisher).len()) }
         + if self.ttl == 0u32 { 0 } else { 2 + sizeof_varint(*(&self.ttl) as u64) }
+        + self.sequence.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
@@ -57,6 +60,51 @@ impl MessageWrite for Record {
         if self.timeReceived != String::default() { w.write_with_tag(42, |w| w.write_string(&**&self.timeReceived))?; }
         if !self.publisher.is_empty() { w.write_with_tag(5330, |w| w.write_bytes(&**&self.publisher))?; }
         if self.ttl != 0u32 { w.write_with_tag(6216, |w| w.write_uint32(*&self.ttl))?; }
+        if let Some(ref s) = self.sequence { w.write_with_tag(7104, |w| w.write_uint64(*s))?; }
+        Ok(())
+    }
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ProviderSummary {
+    pub prefix: Vec<u8>,
+    pub prefixBits: u32,
+    pub filter: Vec<u8>,
+    pub numHashes: u32,
+}
+
+impl<'a> MessageRead<'a> for ProviderSummary {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.prefix = r.read_bytes(bytes)?.to_owned(),
+                Ok(16) => msg.prefixBits = r.read_uint32(bytes)?,
+                Ok(26) => msg.filter = r.read_bytes(bytes)?.to_owned(),
+                Ok(32) => msg.numHashes = r.read_uint32(bytes)?,
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ProviderSummary {
+    fn get_size(&self) -> usize {
+        0
+        + if self.prefix.is_empty() { 0 } else { 1 + sizeof_len((&self.prefix).len()) }
+        + if self.prefixBits == 0u32 { 0 } else { 1 + sizeof_varint(*(&self.prefixBits) as u64) }
+        + if self.filter.is_empty() { 0 } else { 1 + sizeof_len((&self.filter).len()) }
+        + if self.numHashes == 0u32 { 0 } else { 1 + sizeof_varint(*(&self.numHashes) as u64) }
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if !self.prefix.is_empty() { w.write_with_tag(10, |w| w.write_bytes(&**&self.prefix))?; }
+        if self.prefixBits != 0u32 { w.write_with_tag(16, |w| w.write_uint32(*&self.prefixBits))?; }
+        if !self.filter.is_empty() { w.write_with_tag(26, |w| w.write_bytes(&**&self.filter))?; }
+        if self.numHashes != 0u32 { w.write_with_tag(32, |w| w.write_uint32(*&self.numHashes))?; }
         Ok(())
     }
 }
@@ -70,6 +118,9 @@ pub struct Message {
     pub record: Option<dht::pb::Record>,
     pub closerPeers: Vec<dht::pb::mod_Message::Peer>,
     pub providerPeers: Vec<dht::pb::mod_Message::Peer>,
+    pub providerSummary: Option<dht::pb::ProviderSummary>,
+    pub providerTtl: u32,
+    pub providerOffset: Option<u32>,
 }
 
 impl<'a> MessageRead<'a> for Message {
@@ -83,6 +134,9 @@ impl<'a> MessageRead<'a> for Message {
                 Ok(26) => msg.record = Some(r.read_message::<dht::pb::Record>(bytes)?),
                 Ok(66) => msg.closerPeers.push(r.read_message::<dht::pb::mod_Message::Peer>(bytes)?),
                 Ok(74) => msg.providerPeers.push(r.read_message::<dht::pb::mod_Message::Peer>(bytes)?),
+                Ok(7106) => msg.providerSummary = Some(r.read_message::<dht::pb::ProviderSummary>(bytes)?),
+                Ok(7992) => msg.providerTtl = r.read_uint32(bytes)?,
+                Ok(8000) => msg.providerOffset = Some(r.read_uint32(bytes)?),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -100,6 +154,9 @@ impl MessageWrite for Message {
         + self.record.as_ref().map_or(0, |m| 1 + sizeof_len((m).get_size()))
         + self.closerPeers.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
         + self.providerPeers.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.providerSummary.as_ref().map_or(0, |m| 2 + sizeof_len((m).get_size()))
+        + if self.providerTtl == 0u32 { 0 } else { 2 + sizeof_varint(*(&self.providerTtl) as u64) }
+        + self.providerOffset.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
@@ -109,6 +166,9 @@ impl MessageWrite for Message {
         if let Some(ref s) = self.record { w.write_with_tag(26, |w| w.write_message(s))?; }
         for s in &self.closerPeers { w.write_with_tag(66, |w| w.write_message(s))?; }
         for s in &self.providerPeers { w.write_with_tag(74, |w| w.write_message(s))?; }
+        if let Some(ref s) = self.providerSummary { w.write_with_tag(7106, |w| w.write_message(s))?; }
+        if self.providerTtl != 0u32 { w.write_with_tag(7992, |w| w.write_uint32(*&self.providerTtl))?; }
+        if let Some(ref s) = self.providerOffset { w.write_with_tag(8000, |w| w.write_uint32(*s))?; }
         Ok(())
     }
 }
@@ -123,6 +183,7 @@ pub struct Peer {
     pub id: Vec<u8>,
     pub addrs: Vec<Vec<u8>>,
     pub connection: dht::pb::mod_Message::ConnectionType,
+    pub signedRecord: Vec<u8>,
 }
 
 impl<'a> MessageRead<'a> for Peer {
@@ -133,6 +194,7 @@ impl<'a> MessageRead<'a> for Peer {
                 Ok(10) => msg.id = r.read_bytes(bytes)?.to_owned(),
                 Ok(18) => msg.addrs.push(r.read_bytes(bytes)?.to_owned()),
                 Ok(24) => msg.connection = r.read_enum(bytes)?,
+                Ok(8010) => msg.signedRecord = r.read_bytes(bytes)?.to_owned(),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -147,12 +209,14 @@ impl MessageWrite for Peer {
         + if self.id.is_empty() { 0 } else { 1 + sizeof_len((&self.id).len()) }
         + self.addrs.iter().map(|s| 1 + sizeof_len((s).len())).sum::<usize>()
         + if self.connection == dht::pb::mod_Message::ConnectionType::NOT_CONNECTED { 0 } else { 1 + sizeof_varint(*(&self.connection) as u64) }
+        + if self.signedRecord.is_empty() { 0 } else { 2 + sizeof_len((&self.signedRecord).len()) }
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
         if !self.id.is_empty() { w.write_with_tag(10, |w| w.write_bytes(&**&self.id))?; }
         for s in &self.addrs { w.write_with_tag(18, |w| w.write_bytes(&**s))?; }
         if self.connection != dht::pb::mod_Message::ConnectionType::NOT_CONNECTED { w.write_with_tag(24, |w| w.write_enum(*&self.connection as i32))?; }
+        if !self.signedRecord.is_empty() { w.write_with_tag(8010, |w| w.write_bytes(&**&self.signedRecord))?; }
         Ok(())
     }
 }
@@ -241,3 +305,89 @@ impl<'a> From<&'a str> for ConnectionType {
 
 }
 
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RoutingTableSnapshot {
+    pub peers: Vec<dht::pb::mod_RoutingTableSnapshot::Peer>,
+    pub timestampNs: u64,
+}
+
+impl<'a> MessageRead<'a> for RoutingTableSnapshot {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.peers.push(r.read_message::<dht::pb::mod_RoutingTableSnapshot::Peer>(bytes)?),
+                Ok(16) => msg.timestampNs = r.read_uint64(bytes)?,
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for RoutingTableSnapshot {
+    fn get_size(&self) -> usize {
+        0
+        + self.peers.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+        + if self.timestampNs == 0u64 { 0 } else { 1 + sizeof_varint(*(&self.timestampNs) as u64) }
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        for s in &self.peers { w.write_with_tag(10, |w| w.write_message(s))?; }
+        if self.timestampNs != 0u64 { w.write_with_tag(16, |w| w.write_uint64(*&self.timestampNs))?; }
+        Ok(())
+    }
+}
+
+pub mod mod_RoutingTableSnapshot {
+
+use super::*;
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Peer {
+    pub id: Vec<u8>,
+    pub addrs: Vec<Vec<u8>>,
+    pub lastSeenNs: u64,
+    pub connected: bool,
+}
+
+impl<'a> MessageRead<'a> for Peer {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.id = r.read_bytes(bytes)?.to_owned(),
+                Ok(18) => msg.addrs.push(r.read_bytes(bytes)?.to_owned()),
+                Ok(24) => msg.lastSeenNs = r.read_uint64(bytes)?,
+                Ok(32) => msg.connected = r.read_bool(bytes)?,
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for Peer {
+    fn get_size(&self) -> usize {
+        0
+        + if self.id.is_empty() { 0 } else { 1 + sizeof_len((&self.id).len()) }
+        + self.addrs.iter().map(|s| 1 + sizeof_len((s).len())).sum::<usize>()
+        + if self.lastSeenNs == 0u64 { 0 } else { 1 + sizeof_varint(*(&self.lastSeenNs) as u64) }
+        + if self.connected == false { 0 } else { 1 + sizeof_varint(*(&self.connected) as u64) }
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if !self.id.is_empty() { w.write_with_tag(10, |w| w.write_bytes(&**&self.id))?; }
+        for s in &self.addrs { w.write_with_tag(18, |w| w.write_bytes(&**s))?; }
+        if self.lastSeenNs != 0u64 { w.write_with_tag(24, |w| w.write_uint64(*&self.lastSeenNs))?; }
+        if self.connected != false { w.write_with_tag(32, |w| w.write_bool(*&self.connected))?; }
+        Ok(())
+    }
+}
+
+}
@@ -22,26 +22,31 @@ use crate::behaviour::Mode;
 use crate::protocol::{
     KadInStreamSink, KadOutStreamSink, KadPeer, KadRequestMsg, KadResponseMsg, ProtocolConfig,
 };
+use crate::provider_summary::{KeyspaceRange, ProviderSummary};
 use crate::record::{self, Record};
 use crate::QueryId;
 use either::Either;
 use futures::channel::oneshot;
 use futures::prelude::*;
 use futures::stream::SelectAll;
+use instant::Instant;
 use libp2p_core::{upgrade, ConnectedPoint};
 use libp2p_identity::PeerId;
 use libp2p_swarm::handler::{ConnectionEvent, FullyNegotiatedInbound, FullyNegotiatedOutbound};
 use libp2p_swarm::{
-    ConnectionHandler, ConnectionHandlerEvent, Stream, StreamUpgradeError, SubstreamProtocol,
-    SupportedProtocols,
+    ConnectionHandler, ConnectionHandlerEvent, Stream, StreamProtocol, StreamUpgradeError,
+    SubstreamProtocol, SupportedProtocols,
 };
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::task::Waker;
 use std::time::Duration;
 use std::{error, fmt, io, marker::PhantomData, pin::Pin, task::Context, task::Poll};
 
 const MAX_NUM_STREAMS: usize = 32;
 
+/// The maximum number of pages of providers requested from a remote for a single query.
+const MAX_PROVIDER_PAGES: usize = 64;
+
 /// Protocol handler that manages substreams for the Kademlia protocol
 /// on a single connection with a peer.
 ///
@@ -84,7 +89,26 @@ pub struct Handler {
     /// The current state of protocol confirmation.
     protocol_status: Option<ProtocolStatus>,
 
+    /// The kademlia protocol spoken with the remote, once known.
+    protocol: Option<StreamProtocol>,
+
     remote_supported_protocols: SupportedProtocols,
+
+    /// Whether a change of `remote_supported_protocols` is yet to be reported to the behaviour.
+    remote_protocols_changed: bool,
+
+    /// The pages of providers received so far for the outbound `GetProviders` requests.
+    provider_pages: HashMap<QueryId, ProviderPages>,
+}
+
+/// The providers accumulated from the pages of a `GetProviders` response.
+struct ProviderPages {
+    key: record::Key,
+    closer_peers: Vec<KadPeer>,
+    provider_peers: Vec<KadPeer>,
+    /// The offset of the last requested page.
+    offset: u32,
+    num_pages: usize,
 }
 
 /// The states of protocol confirmation that a connection
@@ -180,10 +204,18 @@ impl InboundSubstreamState {
 pub enum HandlerEvent {
     /// The configured protocol name has been confirmed by the peer through
     /// a successfully negotiated substream or by learning the supported protocols of the remote.
-    ProtocolConfirmed { endpoint: ConnectedPoint },
+    ProtocolConfirmed {
+        endpoint: ConnectedPoint,
+        /// The confirmed protocol, i.e. the negotiated one or, if learned from the supported
+        /// protocols of the remote, the first of the configured protocol names it supports.
+        protocol: StreamProtocol,
+    },
     /// The configured protocol name(s) are not or no longer supported by the peer on the provided
     /// connection and it should be removed from the routing table.
     ProtocolNotSupported { endpoint: ConnectedPoint },
+    /// The protocols supported by the peer on this connection have changed,
+    /// e.g. as learned through `libp2p-identify`.
+    RemoteProtocolsChanged { protocols: Vec<StreamProtocol> },
 
     /// Request for the list of nodes whose IDs are the closest to `key`. The number of nodes
     /// returned is not specified, but should be around 20.
@@ -207,6 +239,9 @@ pub enum HandlerEvent {
     GetProvidersReq {
         /// The key for which providers are requested.
         key: record::Key,
+        /// The index of the first provider to return, if the remote accepts the
+        /// providers in pages.
+        provider_offset: Option<u32>,
         /// Identifier of the request. Needs to be passed back when answering.
         request_id: RequestId,
     },
@@ -235,6 +270,8 @@ pub enum HandlerEvent {
         key: record::Key,
         /// The peer that is the provider of the value for `key`.
         provider: KadPeer,
+        /// The expiration of the provider record announced by the peer, if any.
+        expires: Option<Instant>,
     },
 
     /// Request to get a value from the dht records
@@ -271,6 +308,21 @@ pub enum HandlerEvent {
         /// The user data passed to the `PutValue`.
         query_id: QueryId,
     },
+    /// Request for a summary of the keys provided within a range of the keyspace.
+    GetProviderSummaryReq {
+        /// The range of the keyspace to summarise.
+        range: KeyspaceRange,
+        /// Identifier of the request. Needs to be passed back when answering.
+        request_id: RequestId,
+    },
+
+    /// Response to a `HandlerIn::GetProviderSummaryReq`.
+    GetProviderSummaryRes {
+        /// The summary of the provided keys.
+        summary: ProviderSummary,
+        /// The user data passed to the `GetProviderSummaryReq`.
+        query_id: QueryId,
+    },
 }
 
 /// Error that can happen when requesting an RPC query.
@@ -356,6 +408,9 @@ pub enum HandlerIn {
         closer_peers: Vec<KadPeer>,
         /// Known providers for this key.
         provider_peers: Vec<KadPeer>,
+        /// The index of the first provider of the next page, if the providers
+        /// don't fit into this response.
+        next_provider_offset: Option<u32>,
         /// Identifier of the request that was made by the remote.
         ///
         /// It is a logic error to use an id of the handler of a different node.
@@ -371,6 +426,8 @@ pub enum HandlerIn {
         key: record::Key,
         /// Known provider for this key.
         provider: KadPeer,
+        /// The expiration of the provider record to announce, if any.
+        expires: Option<Instant>,
         /// ID of the query that generated this request.
         query_id: QueryId,
     },
@@ -409,6 +466,21 @@ pub enum HandlerIn {
         /// Identifier of the request that was made by the remote.
         request_id: RequestId,
     },
+    /// Request for a summary of the keys provided within a range of the keyspace.
+    GetProviderSummaryReq {
+        /// The range of the keyspace to summarise.
+        range: KeyspaceRange,
+        /// ID of the query that generated this request.
+        query_id: QueryId,
+    },
+
+    /// Response to a `GetProviderSummaryReq`.
+    GetProviderSummaryRes {
+        /// The summary of the provided keys.
+        summary: ProviderSummary,
+        /// Identifier of the request that was made by the remote.
+        request_id: RequestId,
+    },
 }
 
 /// Unique identifier for a request. Must be passed back in order to answer a request from
@@ -461,14 +533,17 @@ impl Handler {
             pending_streams: Default::default(),
             pending_messages: Default::default(),
             protocol_status: None,
+            protocol: None,
             remote_supported_protocols: Default::default(),
+            remote_protocols_changed: false,
+            provider_pages: HashMap::new(),
         }
     }
 
     fn on_fully_negotiated_outbound(
         &mut self,
         FullyNegotiatedOutbound {
-            protocol: stream,
+            protocol: (stream, protocol),
             info: (),
         }: FullyNegotiatedOutbound<
             <Self as ConnectionHandler>::OutboundProtocol,
@@ -483,6 +558,7 @@ impl Handler {
             // Upon the first successfully negotiated substream, we know that the
             // remote is configured with the same protocol name and we want
             // the behaviour to add this peer to the routing table, if possible.
+            self.protocol = Some(protocol);
             self.protocol_status = Some(ProtocolStatus {
                 supported: true,
                 reported: false,
@@ -499,7 +575,7 @@ impl Handler {
     ) {
         // If `self.allow_listening` is false, then we produced a `DeniedUpgrade` and `protocol`
         // is a `Void`.
-        let protocol = match protocol {
+        let (protocol, name) = match protocol {
             future::Either::Left(p) => p,
             future::Either::Right(p) => void::unreachable(p),
         };
@@ -508,6 +584,7 @@ impl Handler {
             // Upon the first successfully negotiated substream, we know that the
             // remote is configured with the same protocol name and we want
             // the behaviour to add this peer to the routing table, if possible.
+            self.protocol = Some(name);
             self.protocol_status = Some(ProtocolStatus {
                 supported: true,
                 reported: false,
@@ -631,26 +708,46 @@ impl ConnectionHandler for Handler {
                 request_id,
             } => self.answer_pending_request(request_id, KadResponseMsg::FindNode { closer_peers }),
             HandlerIn::GetProvidersReq { key, query_id } => {
-                let msg = KadRequestMsg::GetProviders { key };
+                self.provider_pages.insert(
+                    query_id,
+                    ProviderPages {
+                        key: key.clone(),
+                        closer_peers: Vec::new(),
+                        provider_peers: Vec::new(),
+                        offset: 0,
+                        num_pages: 0,
+                    },
+                );
+                let msg = KadRequestMsg::GetProviders {
+                    key,
+                    provider_offset: Some(0),
+                };
                 self.pending_messages.push_back((msg, query_id));
             }
             HandlerIn::GetProvidersRes {
                 closer_peers,
                 provider_peers,
+                next_provider_offset,
                 request_id,
             } => self.answer_pending_request(
                 request_id,
                 KadResponseMsg::GetProviders {
                     closer_peers,
                     provider_peers,
+                    next_provider_offset,
                 },
             ),
             HandlerIn::AddProvider {
                 key,
                 provider,
+                expires,
                 query_id,
             } => {
-                let msg = KadRequestMsg::AddProvider { key, provider };
+                let msg = KadRequestMsg::AddProvider {
+                    key,
+                    provider,
+                    expires,
+                };
                 self.pending_messages.push_back((msg, query_id));
             }
             HandlerIn::GetRecord { key, query_id } => {
@@ -681,6 +778,19 @@ impl ConnectionHandler for Handler {
             } => {
                 self.answer_pending_request(request_id, KadResponseMsg::PutValue { key, value });
             }
+            HandlerIn::GetProviderSummaryReq { range, query_id } => {
+                let msg = KadRequestMsg::GetProviderSummary { range };
+                self.pending_messages.push_back((msg, query_id));
+            }
+            HandlerIn::GetProviderSummaryRes {
+                summary,
+                request_id,
+            } => {
+                self.answer_pending_request(
+                    request_id,
+                    KadResponseMsg::GetProviderSummary { summary },
+                );
+            }
             HandlerIn::ReconfigureMode { new_mode } => {
                 let peer = self.remote_peer_id;
 
@@ -717,9 +827,11 @@ impl ConnectionHandler for Handler {
             match &mut self.protocol_status {
                 Some(status) if !status.reported => {
                     status.reported = true;
-                    let event = if status.supported {
+                    let protocol = self.protocol.clone().filter(|_| status.supported);
+                    let event = if let Some(protocol) = protocol {
                         HandlerEvent::ProtocolConfirmed {
                             endpoint: self.endpoint.clone(),
+                            protocol,
                         }
                     } else {
                         HandlerEvent::ProtocolNotSupported {
@@ -732,29 +844,38 @@ impl ConnectionHandler for Handler {
                 _ => {}
             }
 
+            if self.remote_protocols_changed {
+                self.remote_protocols_changed = false;
+                return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                    HandlerEvent::RemoteProtocolsChanged {
+                        protocols: self.remote_supported_protocols.iter().cloned().collect(),
+                    },
+                ));
+            }
+
             match self.outbound_substreams.poll_unpin(cx) {
                 Poll::Ready((Ok(Ok(Some(response))), query_id)) => {
+                    let Some(response) = self.on_provider_page(response, query_id) else {
+                        continue;
+                    };
                     return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
                         process_kad_response(response, query_id),
-                    ))
+                    ));
                 }
                 Poll::Ready((Ok(Ok(None)), _)) => {
                     continue;
                 }
                 Poll::Ready((Ok(Err(e)), query_id)) => {
                     return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
-                        HandlerEvent::QueryError {
-                            error: HandlerQueryErr::Io(e),
-                            query_id,
-                        },
+                        self.on_query_error(HandlerQueryErr::Io(e), query_id),
                     ))
                 }
                 Poll::Ready((Err(_timeout), query_id)) => {
                     return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
-                        HandlerEvent::QueryError {
-                            error: HandlerQueryErr::Io(io::ErrorKind::TimedOut.into()),
+                        self.on_query_error(
+                            HandlerQueryErr::Io(io::ErrorKind::TimedOut.into()),
                             query_id,
-                        },
+                        ),
                     ))
                 }
                 Poll::Pending => {}
@@ -802,15 +923,22 @@ impl ConnectionHandler for Handler {
                 let dirty = self.remote_supported_protocols.on_protocols_change(change);
 
                 if dirty {
-                    let remote_supports_our_kademlia_protocols = self
-                        .remote_supported_protocols
+                    self.remote_protocols_changed = true;
+                    // Like multistream-select, prefer the protocol names in the configured order.
+                    let supported_protocol = self
+                        .protocol_config
+                        .protocol_names()
                         .iter()
-                        .any(|p| self.protocol_config.protocol_names().contains(p));
+                        .find(|p| self.remote_supported_protocols.iter().any(|r| r == *p))
+                        .cloned();
 
                     self.protocol_status = Some(compute_new_protocol_status(
-                        remote_supports_our_kademlia_protocols,
+                        supported_protocol.is_some(),
                         self.protocol_status,
-                    ))
+                    ));
+                    if supported_protocol.is_some() {
+                        self.protocol = self.protocol.take().or(supported_protocol);
+                    }
                 }
             }
             _ => {}
@@ -852,6 +980,83 @@ fn compute_new_protocol_status(
 }
 
 impl Handler {
+    /// Accumulates a page of a `GetProviders` response, requesting the next page if any.
+    ///
+    /// Returns the response to report once all pages have been received.
+    fn on_provider_page(
+        &mut self,
+        response: KadResponseMsg,
+        query_id: QueryId,
+    ) -> Option<KadResponseMsg> {
+        let (closer_peers, provider_peers, next_provider_offset) = match response {
+            KadResponseMsg::GetProviders {
+                closer_peers,
+                provider_peers,
+                next_provider_offset,
+            } => (closer_peers, provider_peers, next_provider_offset),
+            response => {
+                self.provider_pages.remove(&query_id);
+                return Some(response);
+            }
+        };
+
+        let Some(mut pages) = self.provider_pages.remove(&query_id) else {
+            return Some(KadResponseMsg::GetProviders {
+                closer_peers,
+                provider_peers,
+                next_provider_offset: None,
+            });
+        };
+
+        // The closer peers do not depend on the offset, thus keep those of the first page only.
+        if pages.num_pages == 0 {
+            pages.closer_peers = closer_peers;
+        }
+        pages.provider_peers.extend(provider_peers);
+        pages.num_pages += 1;
+
+        match next_provider_offset {
+            // Only follow offsets that make progress, lest a remote keeps us busy forever.
+            Some(offset) if offset > pages.offset && pages.num_pages < MAX_PROVIDER_PAGES => {
+                pages.offset = offset;
+                let msg = KadRequestMsg::GetProviders {
+                    key: pages.key.clone(),
+                    provider_offset: Some(offset),
+                };
+                self.pending_messages.push_back((msg, query_id));
+                self.provider_pages.insert(query_id, pages);
+                None
+            }
+            _ => Some(KadResponseMsg::GetProviders {
+                closer_peers: pages.closer_peers,
+                provider_peers: pages.provider_peers,
+                next_provider_offset: None,
+            }),
+        }
+    }
+
+    /// Reports a failed outbound request.
+    ///
+    /// The providers received so far are reported if a later page of a `GetProviders`
+    /// response fails.
+    fn on_query_error(&mut self, error: HandlerQueryErr, query_id: QueryId) -> HandlerEvent {
+        match self.provider_pages.remove(&query_id) {
+            Some(pages) if pages.num_pages > 0 => {
+                tracing::debug!(
+                    peer=%self.remote_peer_id,
+                    num_pages=%pages.num_pages,
+                    "Failed to receive page of providers: {error}"
+                );
+                HandlerEvent::GetProvidersRes {
+                    closer_peers: pages.closer_peers,
+                    provider_peers: pages.provider_peers,
+                    query_id,
+                }
+            }
+            _ => HandlerEvent::QueryError { error, query_id },
+        }
+    }
+
     fn answer_pending_request(&mut self, request_id: RequestId, mut msg: KadResponseMsg) {
         for state in self.inbound_substreams.iter_mut() {
             match state.try_answer_with(request_id, msg) {
@@ -901,26 +1106,38 @@ impl futures::Stream for InboundSubstreamState {
                             },
                         )));
                     }
-                    Poll::Ready(Some(Ok(KadRequestMsg::GetProviders { key }))) => {
+                    Poll::Ready(Some(Ok(KadRequestMsg::GetProviders {
+                        key,
+                        provider_offset,
+                    }))) => {
                         *this =
                             InboundSubstreamState::WaitingBehaviour(connection_id, substream, None);
                         return Poll::Ready(Some(ConnectionHandlerEvent::NotifyBehaviour(
                             HandlerEvent::GetProvidersReq {
                                 key,
+                                provider_offset,
                                 request_id: RequestId {
                                     connec_unique_id: connection_id,
                                 },
                             },
                         )));
                     }
-                    Poll::Ready(Some(Ok(KadRequestMsg::AddProvider { key, provider }))) => {
+                    Poll::Ready(Some(Ok(KadRequestMsg::AddProvider {
+                        key,
+                        provider,
+                        expires,
+                    }))) => {
                         *this = InboundSubstreamState::WaitingMessage {
                             first: false,
                             connection_id,
                             substream,
                         };
                         return Poll::Ready(Some(ConnectionHandlerEvent::NotifyBehaviour(
-                            HandlerEvent::AddProvider { key, provider },
+                            HandlerEvent::AddProvider {
+                                key,
+                                provider,
+                                expires,
+                            },
                         )));
                     }
                     Poll::Ready(Some(Ok(KadRequestMsg::GetValue { key }))) => {
@@ -947,6 +1164,18 @@ impl futures::Stream for InboundSubstreamState {
                             },
                         )));
                     }
+                    Poll::Ready(Some(Ok(KadRequestMsg::GetProviderSummary { range }))) => {
+                        *this =
+                            InboundSubstreamState::WaitingBehaviour(connection_id, substream, None);
+                        return Poll::Ready(Some(ConnectionHandlerEvent::NotifyBehaviour(
+                            HandlerEvent::GetProviderSummaryReq {
+                                range,
+                                request_id: RequestId {
+                                    connec_unique_id: connection_id,
+                                },
+                            },
+                        )));
+                    }
                     Poll::Pending => {
                         *this = InboundSubstreamState::WaitingMessage {
                             first,
@@ -1035,6 +1264,7 @@ fn process_kad_response(event: KadResponseMsg, query_id: QueryId) -> HandlerEven
         KadResponseMsg::GetProviders {
             closer_peers,
             provider_peers,
+            ..
         } => HandlerEvent::GetProvidersRes {
             closer_peers,
             provider_peers,
@@ -1053,6 +1283,9 @@ fn process_kad_response(event: KadResponseMsg, query_id: QueryId) -> HandlerEven
             value,
             query_id,
         },
+        KadResponseMsg::GetProviderSummary { summary } => {
+            HandlerEvent::GetProviderSummaryRes { summary, query_id }
+        }
     }
 }
 
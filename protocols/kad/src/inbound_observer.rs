@@ -0,0 +1,113 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Observation of the inbound requests answered in server mode.
+//!
+//! Applications set an [`InboundRequestObserver`] via
+//! [`Config::set_inbound_request_observer`](crate::Config::set_inbound_request_observer)
+//! to attribute the requests to peers and keys, e.g. to identify hot keys and abusive peers.
+
+use libp2p_identity::PeerId;
+use std::fmt;
+use std::sync::Arc;
+
+/// Observes the inbound requests answered by the local node.
+pub trait InboundRequestObserver: Send + Sync + 'static {
+    /// Called for every answered `FIND_NODE`, `GET_VALUE` and `GET_PROVIDERS` request
+    /// and every accepted `PUT_VALUE` and `ADD_PROVIDER` request.
+    fn on_inbound_request(&self, info: &InboundRequestInfo);
+}
+
+impl<F> InboundRequestObserver for F
+where
+    F: Fn(&InboundRequestInfo) + Send + Sync + 'static,
+{
+    fn on_inbound_request(&self, info: &InboundRequestInfo) {
+        self(info)
+    }
+}
+
+/// The type of an inbound request reported to an [`InboundRequestObserver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InboundRequestType {
+    /// A `FIND_NODE` request.
+    FindNode,
+    /// A `GET_VALUE` request.
+    GetRecord,
+    /// A `GET_PROVIDERS` request.
+    GetProviders,
+    /// A `PUT_VALUE` request.
+    PutRecord,
+    /// An `ADD_PROVIDER` request.
+    AddProvider,
+}
+
+/// An inbound request reported to an [`InboundRequestObserver`].
+#[derive(Debug, Clone)]
+pub struct InboundRequestInfo {
+    /// The peer that sent the request.
+    pub peer: PeerId,
+    /// The type of the request.
+    pub request: InboundRequestType,
+    /// The key of the request, i.e. the key of a record or provider record or the
+    /// target of a `FIND_NODE` request.
+    pub key: Vec<u8>,
+    /// Whether the response carried a record or provider records of the local store,
+    /// as opposed to closer peers only. Always `false` for `PUT_VALUE` and `ADD_PROVIDER`.
+    pub served_from_store: bool,
+    /// The number of closer peers in the response.
+    pub num_closer_peers: usize,
+}
+
+impl InboundRequestInfo {
+    /// Returns the first `len` bytes of the key, or the whole key if it is shorter.
+    pub fn key_prefix(&self, len: usize) -> &[u8] {
+        &self.key[..len.min(self.key.len())]
+    }
+}
+
+/// The [`InboundRequestObserver`] set in the [`Config`](crate::Config), if any.
+#[derive(Clone, Default)]
+pub(crate) struct InboundObserver {
+    observer: Option<Arc<dyn InboundRequestObserver>>,
+}
+
+impl InboundObserver {
+    pub(crate) fn new(observer: impl InboundRequestObserver) -> Self {
+        Self {
+            observer: Some(Arc::new(observer)),
+        }
+    }
+
+    /// Reports the request built by `info` to the [`InboundRequestObserver`], if any.
+    pub(crate) fn observe(&self, info: impl FnOnce() -> InboundRequestInfo) {
+        if let Some(observer) = &self.observer {
+            observer.on_inbound_request(&info());
+        }
+    }
+}
+
+impl fmt::Debug for InboundObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InboundObserver")
+            .field("enabled", &self.observer.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! IP prefixes for limiting the number of routing table entries in the same network.
+//!
+//! Peers whose addresses share a `/24` (IPv4) or `/48` (IPv6) prefix are likely to be
+//! operated by the same entity. Limiting the number of such peers in the routing table
+//! makes Sybil and eclipse attacks more costly.
+
+use libp2p_core::multiaddr::{Multiaddr, Protocol};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The network prefix of a public IP address.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum IpPrefix {
+    /// The `/24` prefix of an IPv4 address.
+    V4([u8; 3]),
+    /// The `/48` prefix of an IPv6 address.
+    V6([u16; 3]),
+}
+
+impl IpPrefix {
+    /// Returns the prefix of the IP address an address starts with.
+    ///
+    /// Returns `None` for addresses that do not start with an IP address, e.g. DNS
+    /// addresses, and for IP addresses that are not globally reachable, i.e.
+    /// loopback, private and link-local addresses.
+    pub(crate) fn of(address: &Multiaddr) -> Option<Self> {
+        match address.iter().next()? {
+            Protocol::Ip4(ip) if is_public_v4(&ip) => {
+                let [a, b, c, _] = ip.octets();
+                Some(IpPrefix::V4([a, b, c]))
+            }
+            Protocol::Ip6(ip) if is_public_v6(&ip) => {
+                let [a, b, c, ..] = ip.segments();
+                Some(IpPrefix::V6([a, b, c]))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn is_public_v4(ip: &Ipv4Addr) -> bool {
+    !(ip.is_unspecified() || ip.is_loopback() || ip.is_private() || ip.is_link_local())
+}
+
+fn is_public_v6(ip: &Ipv6Addr) -> bool {
+    let unique_local = ip.segments()[0] & 0xfe00 == 0xfc00;
+    let link_local = ip.segments()[0] & 0xffc0 == 0xfe80;
+    !(ip.is_unspecified() || ip.is_loopback() || unique_local || link_local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_of_public_addresses() {
+        let a: Multiaddr = "/ip4/8.8.8.8/tcp/4001".parse().unwrap();
+        let b: Multiaddr = "/ip4/8.8.8.200/udp/4001/quic-v1".parse().unwrap();
+        let c: Multiaddr = "/ip4/8.8.9.8/tcp/4001".parse().unwrap();
+        assert_eq!(IpPrefix::of(&a), Some(IpPrefix::V4([8, 8, 8])));
+        assert_eq!(IpPrefix::of(&a), IpPrefix::of(&b));
+        assert_ne!(IpPrefix::of(&a), IpPrefix::of(&c));
+
+        let d: Multiaddr = "/ip6/2001:db8:1:2::1/tcp/4001".parse().unwrap();
+        let e: Multiaddr = "/ip6/2001:db8:1:3::1/tcp/4001".parse().unwrap();
+        assert_eq!(IpPrefix::of(&d), Some(IpPrefix::V6([0x2001, 0xdb8, 1])));
+        assert_eq!(IpPrefix::of(&d), IpPrefix::of(&e));
+    }
+
+    #[test]
+    fn no_prefix_of_non_public_addresses() {
+        for address in [
+            "/ip4/127.0.0.1/tcp/4001",
+            "/ip4/192.168.1.1/tcp/4001",
+            "/ip4/10.0.0.1/tcp/4001",
+            "/ip6/::1/tcp/4001",
+            "/ip6/fd00::1/tcp/4001",
+            "/ip6/fe80::1/tcp/4001",
+            "/dns4/example.com/tcp/4001",
+            "/memory/1234",
+        ] {
+            assert_eq!(IpPrefix::of(&address.parse().unwrap()), None, "{address}");
+        }
+    }
+}
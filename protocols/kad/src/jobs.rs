@@ -46,6 +46,13 @@
 //!
 //!   * [`AddProviderJob`]: For (re-)publication of provider records.
 //!     Provider records currently have no separate replication mechanism.
+//!     Keys may be given a publication interval of their own, in which case
+//!     they are re-published on that interval instead of with every run.
+//!
+//! In addition, the opt-in [`ReplicateOnJoinJob`] replicates stored records
+//! to peers joining the routing table that are among the `k` closest nodes
+//! to the keys of the records, i.e. the classic Kademlia "transfer on join".
+//! It runs whenever peers joined since its last run and is rate limited.
 //!
 //! A periodic job is driven like a `Future` or `Stream` by `poll`ing it.
 //! Once a job starts running it emits records to send to the `k` closest
@@ -60,13 +67,25 @@
 //! > for the worst case, it temporarily requires additional memory proportional
 //! > to the size of all stored records. As a job runs, the records are moved
 //! > out of the job to the consumer, where they can be dropped after being sent.
-
-use crate::record::{self, store::RecordStore, ProviderRecord, Record};
+//!
+//! With an [`AsyncRecordStore`] that does not complete its operations
+//! immediately, a job first waits for the snapshot of the records to load
+//! and removes expired records in the background while it runs.
+
+use crate::rate_limiter::RateLimiter;
+use crate::record::{
+    self,
+    store::{AsyncRecordStore, StoreFuture},
+    ProviderRecord, Record,
+};
 use futures::prelude::*;
+use futures::stream::FuturesUnordered;
 use futures_timer::Delay;
 use instant::Instant;
 use libp2p_identity::PeerId;
-use std::collections::HashSet;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::num::NonZeroU32;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
@@ -80,17 +99,27 @@ pub(crate) const JOBS_MAX_QUERIES: usize = 100;
 /// per invocation of `Behaviour::poll`.
 pub(crate) const JOBS_MAX_NEW_QUERIES: usize = 10;
 /// A background job run periodically.
-#[derive(Debug)]
 struct PeriodicJob<T> {
     interval: Duration,
     state: PeriodicJobState<T>,
+    /// Pending removals of expired records from the store.
+    removals: FuturesUnordered<StoreFuture<()>>,
 }
 
 impl<T> PeriodicJob<T> {
+    fn new(interval: Duration) -> Self {
+        let deadline = Instant::now() + interval;
+        Self {
+            interval,
+            state: PeriodicJobState::Waiting(Delay::new(interval), deadline),
+            removals: FuturesUnordered::new(),
+        }
+    }
+
     #[cfg(test)]
     fn is_running(&self) -> bool {
         match self.state {
-            PeriodicJobState::Running(..) => true,
+            PeriodicJobState::Loading(..) | PeriodicJobState::Running(..) => true,
             PeriodicJobState::Waiting(..) => false,
         }
     }
@@ -116,11 +145,36 @@ impl<T> PeriodicJob<T> {
         }
         false
     }
+
+    /// Polls the snapshot of the records to run the job with, if it is
+    /// still loading. Returns `true` if the job is running afterwards.
+    fn poll_loading(&mut self, cx: &mut Context<'_>) -> bool {
+        if let PeriodicJobState::Loading(records) = &mut self.state {
+            match records.poll_unpin(cx) {
+                Poll::Ready(records) => self.state = PeriodicJobState::Running(records),
+                Poll::Pending => return false,
+            }
+        }
+        matches!(self.state, PeriodicJobState::Running(..))
+    }
+
+    /// Drives the pending removals of expired records.
+    fn poll_removals(&mut self, cx: &mut Context<'_>) {
+        while let Poll::Ready(Some(())) = self.removals.poll_next_unpin(cx) {}
+    }
+
+    /// Puts the job back into the waiting state for the next run.
+    fn wait(&mut self, cx: &mut Context<'_>, now: Instant) {
+        let deadline = now + self.interval;
+        let delay = Delay::new(self.interval);
+        self.state = PeriodicJobState::Waiting(delay, deadline);
+        assert!(!self.check_ready(cx, now));
+    }
 }
 
 /// The state of a background job run periodically.
-#[derive(Debug)]
 enum PeriodicJobState<T> {
+    Loading(StoreFuture<T>),
     Running(T),
     Waiting(Delay, Instant),
 }
@@ -147,20 +201,14 @@ impl PutRecordJob {
         publish_interval: Option<Duration>,
         record_ttl: Option<Duration>,
     ) -> Self {
-        let now = Instant::now();
-        let deadline = now + replicate_interval;
-        let delay = Delay::new(replicate_interval);
-        let next_publish = publish_interval.map(|i| now + i);
+        let next_publish = publish_interval.map(|i| Instant::now() + i);
         Self {
             local_id,
             next_publish,
             publish_interval,
             record_ttl,
             skipped: HashSet::new(),
-            inner: PeriodicJob {
-                interval: replicate_interval,
-                state: PeriodicJobState::Waiting(delay, deadline),
-            },
+            inner: PeriodicJob::new(replicate_interval),
         }
     }
 
@@ -200,55 +248,60 @@ impl PutRecordJob {
         now: Instant,
     ) -> Poll<Record>
     where
-        T: RecordStore,
+        T: AsyncRecordStore,
     {
         if self.inner.check_ready(cx, now) {
             let publish = self.next_publish.map_or(false, |t_pub| now >= t_pub);
+            let local_id = self.local_id;
+            let record_ttl = self.record_ttl;
+            let skipped = std::mem::take(&mut self.skipped);
             let records = store
-                .records()
-                .filter_map(|r| {
-                    let is_publisher = r.publisher.as_ref() == Some(&self.local_id);
-                    if self.skipped.contains(&r.key) || (!publish && is_publisher) {
-                        None
-                    } else {
-                        let mut record = r.into_owned();
-                        if publish && is_publisher {
-                            record.expires = record
-                                .expires
-                                .or_else(|| self.record_ttl.map(|ttl| now + ttl));
-                        }
-                        Some(record)
-                    }
+                .all_records()
+                .map(move |records| {
+                    records
+                        .into_iter()
+                        .filter_map(|mut record| {
+                            let is_publisher = record.publisher.as_ref() == Some(&local_id);
+                            if skipped.contains(&record.key) || (!publish && is_publisher) {
+                                None
+                            } else {
+                                if publish && is_publisher {
+                                    record.expires =
+                                        record.expires.or_else(|| record_ttl.map(|ttl| now + ttl));
+                                }
+                                Some(record)
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .into_iter()
                 })
-                .collect::<Vec<_>>()
-                .into_iter();
+                .boxed();
 
             // Schedule the next publishing run.
             if publish {
                 self.next_publish = self.publish_interval.map(|i| now + i);
             }
 
-            self.skipped.clear();
-
-            self.inner.state = PeriodicJobState::Running(records);
+            self.inner.state = PeriodicJobState::Loading(records);
         }
 
-        if let PeriodicJobState::Running(records) = &mut self.inner.state {
-            for r in records {
-                if r.is_expired(now) {
-                    store.remove(&r.key)
-                } else {
-                    return Poll::Ready(r);
+        if self.inner.poll_loading(cx) {
+            if let PeriodicJobState::Running(records) = &mut self.inner.state {
+                for r in records {
+                    if r.is_expired(now) {
+                        self.inner.removals.push(store.remove_record(&r.key))
+                    } else {
+                        return Poll::Ready(r);
+                    }
                 }
             }
 
             // Wait for the next run.
-            let deadline = now + self.inner.interval;
-            let delay = Delay::new(self.inner.interval);
-            self.inner.state = PeriodicJobState::Waiting(delay, deadline);
-            assert!(!self.inner.check_ready(cx, now));
+            self.inner.wait(cx, now);
         }
 
+        self.inner.poll_removals(cx);
+
         Poll::Pending
     }
 }
@@ -258,28 +311,59 @@ impl PutRecordJob {
 
 /// Periodic job for replicating provider records.
 pub(crate) struct AddProviderJob {
-    inner: PeriodicJob<vec::IntoIter<ProviderRecord>>,
+    inner: Option<PeriodicJob<vec::IntoIter<ProviderRecord>>>,
+    /// The keys re-published on an interval of their own, with
+    /// the interval and the time of the next re-publication.
+    intervals: HashMap<record::Key, (Duration, Instant)>,
+    /// The keys re-published on an interval of their own, ordered
+    /// by the time of their next re-publication.
+    ///
+    /// Entries are removed lazily, i.e. an entry whose time does not match
+    /// the one in `intervals` is outdated and skipped.
+    schedule: BinaryHeap<Reverse<ScheduledKey>>,
+    /// The delay until the next scheduled re-publication.
+    delay: Option<Delay>,
 }
 
 impl AddProviderJob {
-    /// Creates a new periodic job for provider announcements.
-    pub(crate) fn new(interval: Duration) -> Self {
-        let now = Instant::now();
+    /// Creates a new job for provider announcements, running
+    /// periodically if an interval is given.
+    pub(crate) fn new(interval: Option<Duration>) -> Self {
         Self {
-            inner: PeriodicJob {
-                interval,
-                state: {
-                    let deadline = now + interval;
-                    PeriodicJobState::Waiting(Delay::new(interval), deadline)
-                },
-            },
+            inner: interval.map(PeriodicJob::new),
+            intervals: HashMap::new(),
+            schedule: BinaryHeap::new(),
+            delay: None,
         }
     }
 
+    /// Sets the interval of the periodic runs of the job, disabling them
+    /// if `None`. The next run is due after the new interval, starting now.
+    pub(crate) fn set_interval(&mut self, interval: Option<Duration>) {
+        self.inner = interval.map(PeriodicJob::new);
+    }
+
+    /// Re-publishes the provider record for the given key on the given
+    /// interval, instead of with every periodic run of the job.
+    pub(crate) fn schedule(&mut self, key: record::Key, interval: Duration, now: Instant) {
+        let next = now + interval;
+        self.intervals.insert(key.clone(), (interval, next));
+        self.schedule.push(Reverse(ScheduledKey { next, key }));
+        // The next re-publication may now be due sooner.
+        self.delay = None;
+    }
+
+    /// Re-publishes the provider record for the given key with every
+    /// periodic run of the job again, if it was scheduled on an
+    /// interval of its own.
+    pub(crate) fn unschedule(&mut self, key: &record::Key) {
+        self.intervals.remove(key);
+    }
+
     /// Checks whether the job is currently running.
     #[cfg(test)]
     pub(crate) fn is_running(&self) -> bool {
-        self.inner.is_running()
+        self.inner.as_ref().is_some_and(|job| job.is_running())
     }
 
     /// Cuts short the remaining delay, if the job is currently waiting
@@ -288,10 +372,12 @@ impl AddProviderJob {
     /// The job is guaranteed to run on the next invocation of `poll`.
     #[cfg(test)]
     pub(crate) fn asap(&mut self) {
-        self.inner.asap()
+        if let Some(job) = self.inner.as_mut() {
+            job.asap()
+        }
     }
 
-    /// Polls the job for provider records to replicate.
+    /// Polls the job for the keys of provider records to re-publish.
     ///
     /// Must be called in the context of a task. When `NotReady` is returned,
     /// the current task is registered to be notified when the job is ready
@@ -301,42 +387,268 @@ impl AddProviderJob {
         cx: &mut Context<'_>,
         store: &mut T,
         now: Instant,
-    ) -> Poll<ProviderRecord>
+    ) -> Poll<record::Key>
     where
-        T: RecordStore,
+        T: AsyncRecordStore,
     {
-        if self.inner.check_ready(cx, now) {
+        if let Some(key) = self.poll_scheduled(cx, now) {
+            return Poll::Ready(key);
+        }
+
+        let Some(inner) = self.inner.as_mut() else {
+            return Poll::Pending;
+        };
+
+        if inner.check_ready(cx, now) {
             let records = store
-                .provided()
-                .map(|r| r.into_owned())
-                .collect::<Vec<_>>()
-                .into_iter();
-            self.inner.state = PeriodicJobState::Running(records);
+                .provided_records()
+                .map(|records| records.into_iter())
+                .boxed();
+            inner.state = PeriodicJobState::Loading(records);
         }
 
-        if let PeriodicJobState::Running(keys) = &mut self.inner.state {
-            for r in keys {
-                if r.is_expired(now) {
-                    store.remove_provider(&r.key, &r.provider)
-                } else {
-                    return Poll::Ready(r);
+        if inner.poll_loading(cx) {
+            if let PeriodicJobState::Running(keys) = &mut inner.state {
+                for r in keys {
+                    if r.is_expired(now) {
+                        inner
+                            .removals
+                            .push(store.remove_provider_record(&r.key, &r.provider))
+                    } else if !self.intervals.contains_key(&r.key) {
+                        return Poll::Ready(r.key);
+                    }
+                }
+            }
+
+            inner.wait(cx, now);
+        }
+
+        inner.poll_removals(cx);
+
+        Poll::Pending
+    }
+
+    /// Returns the next key scheduled on an interval of its own that is
+    /// due for re-publication, if any.
+    fn poll_scheduled(&mut self, cx: &mut Context<'_>, now: Instant) -> Option<record::Key> {
+        while let Some(Reverse(scheduled)) = self.schedule.peek() {
+            match self.intervals.get_mut(&scheduled.key) {
+                Some((interval, next)) if *next == scheduled.next => {
+                    if scheduled.next > now {
+                        break;
+                    }
+                    *next = now + *interval;
+                    let next = *next;
+                    let Reverse(ScheduledKey { key, .. }) = self.schedule.pop().expect("s.a.");
+                    self.schedule.push(Reverse(ScheduledKey {
+                        next,
+                        key: key.clone(),
+                    }));
+                    self.delay = None;
+                    return Some(key);
+                }
+                // The key has been unscheduled or rescheduled.
+                _ => {
+                    self.schedule.pop();
+                }
+            }
+        }
+
+        if let Some(Reverse(scheduled)) = self.schedule.peek() {
+            let delay = self
+                .delay
+                .get_or_insert_with(|| Delay::new(scheduled.next - now));
+            if delay.poll_unpin(cx).is_ready() {
+                self.delay = None;
+                cx.waker().wake_by_ref();
+            }
+        }
+
+        None
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+// ReplicateOnJoinJob
+
+/// Job for replicating stored records to peers that joined the routing table.
+pub(crate) struct ReplicateOnJoinJob {
+    /// The peers that joined the routing table since the last run.
+    joined: Vec<PeerId>,
+    state: ReplicateOnJoinState,
+    /// Limits the number of records replicated, combined for all peers.
+    limiter: RateLimiter<()>,
+    interval: Duration,
+    /// A record to replicate that exceeded the rate limit, with the peers
+    /// to replicate it to and the delay until the limit is checked again.
+    throttled: Option<(Record, Vec<PeerId>, Delay)>,
+    /// Pending removals of expired records from the store.
+    removals: FuturesUnordered<StoreFuture<()>>,
+}
+
+/// The state of a [`ReplicateOnJoinJob`], with the peers that joined
+/// the routing table before the current run.
+enum ReplicateOnJoinState {
+    Idle,
+    Loading(StoreFuture<Vec<Record>>, Vec<PeerId>),
+    Running(vec::IntoIter<Record>, Vec<PeerId>),
+}
+
+impl ReplicateOnJoinJob {
+    /// Creates a new job replicating bursts of up to `limit` records,
+    /// refilled at a rate of one record per `interval`.
+    pub(crate) fn new(limit: NonZeroU32, interval: Duration) -> Self {
+        Self {
+            joined: Vec::new(),
+            state: ReplicateOnJoinState::Idle,
+            limiter: RateLimiter::new(limit, interval),
+            interval,
+            throttled: None,
+            removals: FuturesUnordered::new(),
+        }
+    }
+
+    /// Notes a peer that joined the routing table, to replicate records to
+    /// on the next run of the job.
+    pub(crate) fn on_peer_joined(&mut self, peer: PeerId) {
+        if !self.joined.contains(&peer) {
+            self.joined.push(peer);
+        }
+    }
+
+    /// Polls the job for records to replicate, with the joined peers to
+    /// replicate them to.
+    ///
+    /// A record is replicated to the joined peers for which `is_close` returns
+    /// `true`, i.e. that are among the closest peers to the key of the record.
+    ///
+    /// Must be called in the context of a task. When `NotReady` is returned,
+    /// the current task is registered to be notified when the job is ready
+    /// to be run.
+    pub(crate) fn poll<T, F>(
+        &mut self,
+        cx: &mut Context<'_>,
+        store: &mut T,
+        now: Instant,
+        mut is_close: F,
+    ) -> Poll<(Record, Vec<PeerId>)>
+    where
+        T: AsyncRecordStore,
+        F: FnMut(&record::Key, &PeerId) -> bool,
+    {
+        self.poll_removals(cx);
+
+        if let Some((record, peers, mut delay)) = self.throttled.take() {
+            if delay.poll_unpin(cx).is_pending() {
+                self.throttled = Some((record, peers, delay));
+                return Poll::Pending;
+            }
+            if let Some(ready) = self.check_limit(cx, record, peers, now) {
+                return Poll::Ready(ready);
+            }
+            return Poll::Pending;
+        }
+
+        if matches!(self.state, ReplicateOnJoinState::Idle) && !self.joined.is_empty() {
+            let joined = std::mem::take(&mut self.joined);
+            self.state = ReplicateOnJoinState::Loading(store.all_records(), joined);
+        }
+
+        if let ReplicateOnJoinState::Loading(records, joined) = &mut self.state {
+            match records.poll_unpin(cx) {
+                Poll::Ready(records) => {
+                    let joined = std::mem::take(joined);
+                    self.state = ReplicateOnJoinState::Running(records.into_iter(), joined);
                 }
+                Poll::Pending => return Poll::Pending,
             }
+        }
 
-            let deadline = now + self.inner.interval;
-            let delay = Delay::new(self.inner.interval);
-            self.inner.state = PeriodicJobState::Waiting(delay, deadline);
-            assert!(!self.inner.check_ready(cx, now));
+        if let ReplicateOnJoinState::Running(records, joined) = &mut self.state {
+            for r in records.by_ref() {
+                if r.is_expired(now) {
+                    self.removals.push(store.remove_record(&r.key));
+                    continue;
+                }
+                let peers = joined
+                    .iter()
+                    .filter(|p| is_close(&r.key, p))
+                    .copied()
+                    .collect::<Vec<_>>();
+                if peers.is_empty() {
+                    continue;
+                }
+                if let Some(ready) = self.check_limit(cx, r, peers, now) {
+                    return Poll::Ready(ready);
+                }
+                return Poll::Pending;
+            }
+            self.state = ReplicateOnJoinState::Idle;
+            if !self.joined.is_empty() {
+                // Peers joined during the run.
+                cx.waker().wake_by_ref();
+            }
         }
 
         Poll::Pending
     }
+
+    /// Returns the record if it may be replicated as per the rate limit,
+    /// otherwise holds it back until the limit is checked again.
+    fn check_limit(
+        &mut self,
+        cx: &mut Context<'_>,
+        record: Record,
+        peers: Vec<PeerId>,
+        now: Instant,
+    ) -> Option<(Record, Vec<PeerId>)> {
+        if self.limiter.try_next((), now) {
+            return Some((record, peers));
+        }
+        tracing::debug!(record=?record.key, "Replication to joined peers throttled");
+        let mut delay = Delay::new(self.interval);
+        let _ = delay.poll_unpin(cx);
+        self.throttled = Some((record, peers, delay));
+        None
+    }
+
+    /// Drives the pending removals of expired records.
+    fn poll_removals(&mut self, cx: &mut Context<'_>) {
+        while let Poll::Ready(Some(())) = self.removals.poll_next_unpin(cx) {}
+    }
+}
+
+/// A key re-published on an interval of its own, ordered
+/// by the time of its next re-publication.
+struct ScheduledKey {
+    next: Instant,
+    key: record::Key,
+}
+
+impl PartialEq for ScheduledKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.next == other.next
+    }
+}
+
+impl Eq for ScheduledKey {}
+
+impl PartialOrd for ScheduledKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScheduledKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.next.cmp(&other.next)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::record::store::MemoryStore;
+    use crate::record::store::{MemoryStore, RecordStore};
     use futures::{executor::block_on, future::poll_fn};
     use quickcheck::*;
     use rand::Rng;
@@ -353,7 +665,7 @@ mod tests {
     fn rand_add_provider_job() -> AddProviderJob {
         let mut rng = rand::thread_rng();
         let interval = Duration::from_secs(rng.gen_range(1..60));
-        AddProviderJob::new(interval)
+        AddProviderJob::new(Some(interval))
     }
 
     #[test]
@@ -405,11 +717,11 @@ mod tests {
             }
 
             block_on(poll_fn(|ctx| {
-                let now = Instant::now() + job.inner.interval;
+                let now = Instant::now() + job.inner.as_ref().unwrap().interval;
                 // All (non-expired) records in the store must be yielded by the job.
                 for r in store.provided().map(|r| r.into_owned()).collect::<Vec<_>>() {
                     if !r.is_expired(now) {
-                        assert_eq!(job.poll(ctx, &mut store, now), Poll::Ready(r));
+                        assert_eq!(job.poll(ctx, &mut store, now), Poll::Ready(r.key));
                         assert!(job.is_running());
                     }
                 }
@@ -421,4 +733,105 @@ mod tests {
 
         quickcheck(prop as fn(_))
     }
+    #[test]
+    fn run_scheduled_add_provider_job() {
+        let id = PeerId::random();
+        let mut store = MemoryStore::new(id);
+        let mut job = rand_add_provider_job();
+        let interval = job.inner.as_ref().unwrap().interval;
+        let scheduled = record::Key::new(&"scheduled");
+        let periodic = record::Key::new(&"periodic");
+        for key in [&scheduled, &periodic] {
+            let record = ProviderRecord::new(key.clone(), id, Vec::new());
+            store.add_provider(record).unwrap();
+        }
+
+        let start = Instant::now();
+        job.schedule(scheduled.clone(), interval / 2, start);
+
+        block_on(poll_fn(|ctx| {
+            // A scheduled key is yielded once per interval of its own,
+            // but not by a periodic run.
+            let now = start + interval;
+            assert_eq!(
+                job.poll(ctx, &mut store, now),
+                Poll::Ready(scheduled.clone())
+            );
+            assert_eq!(
+                job.poll(ctx, &mut store, now),
+                Poll::Ready(periodic.clone())
+            );
+            assert_eq!(job.poll(ctx, &mut store, now), Poll::Pending);
+            let now = start + interval + interval / 2;
+            assert_eq!(
+                job.poll(ctx, &mut store, now),
+                Poll::Ready(scheduled.clone())
+            );
+            assert_eq!(job.poll(ctx, &mut store, now), Poll::Pending);
+
+            // An unscheduled key is yielded by a periodic run again.
+            job.unschedule(&scheduled);
+            let now = start + interval * 2;
+            let mut keys = vec![];
+            while let Poll::Ready(key) = job.poll(ctx, &mut store, now) {
+                keys.push(key);
+            }
+            keys.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+            assert_eq!(keys, vec![periodic.clone(), scheduled.clone()]);
+            Poll::Ready(())
+        }));
+    }
+
+    #[test]
+    fn run_replicate_on_join_job() {
+        let id = PeerId::random();
+        let mut store = MemoryStore::new(id);
+        let mut job = ReplicateOnJoinJob::new(NonZeroU32::new(2).unwrap(), Duration::from_secs(60));
+        let (near, far) = (PeerId::random(), PeerId::random());
+        let mut keys = (0..3u8)
+            .map(|i| {
+                let record = Record::new(vec![i], vec![i]);
+                store.put(record.clone()).unwrap();
+                record.key
+            })
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+
+        block_on(poll_fn(|ctx| {
+            let now = Instant::now();
+            let is_close = |_: &record::Key, p: &PeerId| *p == near;
+
+            // Nothing to replicate until peers join.
+            assert!(job.poll(ctx, &mut store, now, is_close).is_pending());
+
+            job.on_peer_joined(near);
+            job.on_peer_joined(far);
+            job.on_peer_joined(near);
+            let mut replicated = Vec::new();
+            while let Poll::Ready((record, peers)) = job.poll(ctx, &mut store, now, is_close) {
+                assert_eq!(peers, vec![near]);
+                replicated.push(record.key);
+            }
+            // The third record exceeds the rate limit.
+            assert_eq!(replicated.len(), 2);
+            assert!(job.throttled.is_some());
+
+            // The deferred record is replicated once the limit is refilled.
+            job.throttled.as_mut().unwrap().2 = Delay::new(Duration::ZERO);
+            let later = now + Duration::from_secs(60);
+            loop {
+                match job.poll(ctx, &mut store, later, is_close) {
+                    Poll::Ready((record, _)) => {
+                        replicated.push(record.key);
+                        break;
+                    }
+                    Poll::Pending => std::thread::sleep(Duration::from_millis(1)),
+                }
+            }
+            assert!(job.poll(ctx, &mut store, later, is_close).is_pending());
+            replicated.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+            assert_eq!(replicated, keys);
+            Poll::Ready(())
+        }));
+    }
 }
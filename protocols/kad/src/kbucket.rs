@@ -75,13 +75,46 @@ mod key;
 pub use bucket::NodeStatus;
 pub use entry::*;
 
-use arrayvec::ArrayVec;
 use bucket::KBucket;
 use std::collections::VecDeque;
+use std::num::NonZeroUsize;
 use std::time::{Duration, Instant};
 
 /// Maximum number of k-buckets.
-const NUM_BUCKETS: usize = 256;
+pub(crate) const NUM_BUCKETS: usize = 256;
+
+/// The sizes of the buckets of a `KBucketsTable`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct BucketSizes {
+    /// The maximum number of entries of a bucket.
+    pub(crate) size: NonZeroUsize,
+    /// The number of buckets closest to the local key, i.e. with the lowest
+    /// indices, and the maximum number of entries of each of these buckets,
+    /// overriding `size`.
+    pub(crate) close: Option<(usize, NonZeroUsize)>,
+    /// The maximum number of entries pending insertion into a full bucket.
+    pub(crate) pending: NonZeroUsize,
+}
+
+impl BucketSizes {
+    /// Returns the maximum number of entries of the bucket with the given index.
+    fn get(&self, index: BucketIndex) -> NonZeroUsize {
+        match self.close {
+            Some((num_buckets, size)) if index.get() < num_buckets => size,
+            _ => self.size,
+        }
+    }
+}
+
+impl Default for BucketSizes {
+    fn default() -> Self {
+        BucketSizes {
+            size: K_VALUE,
+            close: None,
+            pending: NonZeroUsize::MIN,
+        }
+    }
+}
 
 /// A `KBucketsTable` represents a Kademlia routing table.
 #[derive(Debug, Clone)]
@@ -155,11 +188,13 @@ where
     /// The given `pending_timeout` specifies the duration after creation of
     /// a [`PendingEntry`] after which it becomes eligible for insertion into
     /// a full bucket, replacing the least-recently (dis)connected node.
-    pub(crate) fn new(local_key: TKey, pending_timeout: Duration) -> Self {
+    ///
+    /// The maximum number of entries of each bucket is given by `sizes`.
+    pub(crate) fn new(local_key: TKey, pending_timeout: Duration, sizes: BucketSizes) -> Self {
         KBucketsTable {
             local_key,
             buckets: (0..NUM_BUCKETS)
-                .map(|_| KBucket::new(pending_timeout))
+                .map(|i| KBucket::new(sizes.get(BucketIndex(i)), sizes.pending, pending_timeout))
                 .collect(),
             applied_pending: VecDeque::new(),
         }
@@ -251,7 +286,7 @@ where
             iter: None,
             table: self,
             buckets_iter: ClosestBucketsIter::new(distance),
-            fmap: |b: &KBucket<TKey, _>| -> ArrayVec<_, { K_VALUE.get() }> {
+            fmap: |b: &KBucket<TKey, _>| -> Vec<_> {
                 b.iter().map(|(n, _)| n.key.clone()).collect()
             },
         }
@@ -273,7 +308,7 @@ where
             iter: None,
             table: self,
             buckets_iter: ClosestBucketsIter::new(distance),
-            fmap: |b: &KBucket<_, TVal>| -> ArrayVec<_, { K_VALUE.get() }> {
+            fmap: |b: &KBucket<_, TVal>| -> Vec<_> {
                 b.iter()
                     .map(|(n, status)| EntryView {
                         node: n.clone(),
@@ -323,7 +358,7 @@ struct ClosestIter<'a, TTarget, TKey, TVal, TMap, TOut> {
     /// distance of the local key to the target.
     buckets_iter: ClosestBucketsIter,
     /// The iterator over the entries in the currently traversed bucket.
-    iter: Option<arrayvec::IntoIter<TOut, { K_VALUE.get() }>>,
+    iter: Option<std::vec::IntoIter<TOut>>,
     /// The projection function / mapping applied on each bucket as
     /// it is encountered, producing the next `iter`ator.
     fmap: TMap,
@@ -428,7 +463,7 @@ where
     TTarget: AsRef<KeyBytes>,
     TKey: Clone + AsRef<KeyBytes>,
     TVal: Clone,
-    TMap: Fn(&KBucket<TKey, TVal>) -> ArrayVec<TOut, { K_VALUE.get() }>,
+    TMap: Fn(&KBucket<TKey, TVal>) -> Vec<TOut>,
     TOut: AsRef<KeyBytes>,
 {
     type Item = TOut;
@@ -490,9 +525,14 @@ where
         self.bucket.num_entries()
     }
 
+    /// Returns the maximum number of entries in the bucket.
+    pub fn capacity(&self) -> usize {
+        self.bucket.capacity()
+    }
+
     /// Returns true if the bucket has a pending node.
     pub fn has_pending(&self) -> bool {
-        self.bucket.pending().map_or(false, |n| !n.is_ready())
+        self.bucket.pending().any(|n| !n.is_ready())
     }
 
     /// Tests whether the given distance falls into this bucket.
@@ -534,7 +574,8 @@ mod tests {
         fn arbitrary(g: &mut Gen) -> TestTable {
             let local_key = Key::from(PeerId::random());
             let timeout = Duration::from_secs(g.gen_range(1..360));
-            let mut table = TestTable::new(local_key.clone().into(), timeout);
+            let mut table =
+                TestTable::new(local_key.clone().into(), timeout, BucketSizes::default());
             let mut num_total = g.gen_range(0..100);
             for (i, b) in &mut table.buckets.iter_mut().enumerate().rev() {
                 let ix = BucketIndex(i);
@@ -562,7 +603,8 @@ mod tests {
     fn buckets_are_non_overlapping_and_exhaustive() {
         let local_key = Key::from(PeerId::random());
         let timeout = Duration::from_secs(0);
-        let mut table = KBucketsTable::<KeyBytes, ()>::new(local_key.into(), timeout);
+        let mut table =
+            KBucketsTable::<KeyBytes, ()>::new(local_key.into(), timeout, BucketSizes::default());
 
         let mut prev_max = U256::from(0);
 
@@ -579,7 +621,8 @@ mod tests {
     fn bucket_contains_range() {
         fn prop(ix: u8) {
             let index = BucketIndex(ix as usize);
-            let mut bucket = KBucket::<Key<PeerId>, ()>::new(Duration::from_secs(0));
+            let mut bucket =
+                KBucket::<Key<PeerId>, ()>::new(K_VALUE, NonZeroUsize::MIN, Duration::from_secs(0));
             let bucket_ref = KBucketRef {
                 index,
                 bucket: &mut bucket,
@@ -625,9 +668,10 @@ mod tests {
         let local_key = Key::from(PeerId::random());
         let other_id = Key::from(PeerId::random());
 
-        let mut table = KBucketsTable::<_, ()>::new(local_key, Duration::from_secs(5));
+        let mut table =
+            KBucketsTable::<_, ()>::new(local_key, Duration::from_secs(5), BucketSizes::default());
         if let Some(Entry::Absent(entry)) = table.entry(&other_id) {
-            match entry.insert((), NodeStatus::Connected) {
+            match entry.insert((), NodeStatus::Connected, None) {
                 InsertResult::Inserted => (),
                 _ => panic!(),
             }
@@ -643,15 +687,43 @@ mod tests {
     #[test]
     fn entry_self() {
         let local_key = Key::from(PeerId::random());
-        let mut table = KBucketsTable::<_, ()>::new(local_key.clone(), Duration::from_secs(5));
+        let mut table = KBucketsTable::<_, ()>::new(
+            local_key.clone(),
+            Duration::from_secs(5),
+            BucketSizes::default(),
+        );
 
         assert!(table.entry(&local_key).is_none())
     }
 
+    #[test]
+    fn close_buckets_hold_more_entries() {
+        let local_key = Key::from(PeerId::random());
+        let sizes = BucketSizes {
+            size: NonZeroUsize::new(2).unwrap(),
+            close: Some((255, NonZeroUsize::new(4).unwrap())),
+            ..Default::default()
+        };
+        let mut table = KBucketsTable::<_, ()>::new(local_key, Duration::from_secs(5), sizes);
+        for _ in 0..200 {
+            let key = Key::from(PeerId::random());
+            if let Some(Entry::Absent(e)) = table.entry(&key) {
+                let _ = e.insert((), NodeStatus::Disconnected, None);
+            }
+        }
+
+        let buckets = table.iter().collect::<Vec<_>>();
+        assert_eq!(buckets[255].capacity(), 2);
+        assert_eq!(buckets[255].num_entries(), 2);
+        assert_eq!(buckets[254].capacity(), 4);
+        assert_eq!(buckets[254].num_entries(), 4);
+    }
+
     #[test]
     fn closest() {
         let local_key = Key::from(PeerId::random());
-        let mut table = KBucketsTable::<_, ()>::new(local_key, Duration::from_secs(5));
+        let mut table =
+            KBucketsTable::<_, ()>::new(local_key, Duration::from_secs(5), BucketSizes::default());
         let mut count = 0;
         loop {
             if count == 100 {
@@ -659,7 +731,7 @@ mod tests {
             }
             let key = Key::from(PeerId::random());
             if let Some(Entry::Absent(e)) = table.entry(&key) {
-                match e.insert((), NodeStatus::Connected) {
+                match e.insert((), NodeStatus::Connected, None) {
                     InsertResult::Inserted => count += 1,
                     _ => continue,
                 }
@@ -686,16 +758,20 @@ mod tests {
     #[test]
     fn applied_pending() {
         let local_key = Key::from(PeerId::random());
-        let mut table = KBucketsTable::<_, ()>::new(local_key.clone(), Duration::from_millis(1));
+        let mut table = KBucketsTable::<_, ()>::new(
+            local_key.clone(),
+            Duration::from_millis(1),
+            BucketSizes::default(),
+        );
         let expected_applied;
         let full_bucket_index;
         loop {
             let key = Key::from(PeerId::random());
             if let Some(Entry::Absent(e)) = table.entry(&key) {
-                match e.insert((), NodeStatus::Disconnected) {
+                match e.insert((), NodeStatus::Disconnected, None) {
                     InsertResult::Full => {
                         if let Some(Entry::Absent(e)) = table.entry(&key) {
-                            match e.insert((), NodeStatus::Connected) {
+                            match e.insert((), NodeStatus::Connected, None) {
                                 InsertResult::Pending { disconnected } => {
                                     expected_applied = AppliedPending {
                                         inserted: Node {
@@ -726,7 +802,10 @@ mod tests {
         // Expire the timeout for the pending entry on the full bucket.`
         let full_bucket = &mut table.buckets[full_bucket_index.unwrap().get()];
         let elapsed = Instant::now().checked_sub(Duration::from_secs(1)).unwrap();
-        full_bucket.pending_mut().unwrap().set_ready_at(elapsed);
+        full_bucket
+            .pending_mut(&expected_applied.inserted.key)
+            .unwrap()
+            .set_ready_at(elapsed);
 
         match table.entry(&expected_applied.inserted.key) {
             Some(Entry::Present(_, NodeStatus::Connected)) => {}
@@ -38,6 +38,9 @@ pub(crate) struct PendingNode<TKey, TVal> {
 
     /// The instant at which the pending node is eligible for insertion into a bucket.
     replace: Instant,
+
+    /// The round-trip time observed for the peer, if any.
+    rtt: Option<Duration>,
 }
 
 /// The status of a node in a bucket.
@@ -45,6 +48,7 @@ pub(crate) struct PendingNode<TKey, TVal> {
 /// The status of a node in a bucket together with the time of the
 /// last status change determines the position of the node in a
 /// bucket.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum NodeStatus {
     /// The node is considered connected.
@@ -71,9 +75,26 @@ impl<TKey, TVal> PendingNode<TKey, TVal> {
         self.replace = t;
     }
 
+    pub(crate) fn set_rtt(&mut self, rtt: Option<Duration>) {
+        self.rtt = rtt;
+    }
+
     pub(crate) fn into_node(self) -> Node<TKey, TVal> {
         self.node
     }
+
+    /// The ordering of pending nodes by their observed quality, best first:
+    /// connected nodes before disconnected ones, then nodes with a lower
+    /// round-trip time, with nodes without an observed round-trip time last,
+    /// and finally the nodes that are pending for longer.
+    fn quality_key(&self) -> (bool, bool, Option<Duration>, Instant) {
+        (
+            self.status != NodeStatus::Connected,
+            self.rtt.is_none(),
+            self.rtt,
+            self.replace,
+        )
+    }
 }
 
 /// A `Node` in a bucket, representing a peer participating
@@ -88,15 +109,18 @@ pub struct Node<TKey, TVal> {
 }
 
 /// The position of a node in a `KBucket`, i.e. a non-negative integer
-/// in the range `[0, K_VALUE)`.
+/// in the range `[0, capacity)`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) struct Position(usize);
-/// A `KBucket` is a list of up to `K_VALUE` keys and associated values,
+/// A `KBucket` is a list of up to `capacity` keys and associated values,
 /// ordered from least-recently connected to most-recently connected.
 #[derive(Debug, Clone)]
 pub(crate) struct KBucket<TKey, TVal> {
     /// The nodes contained in the bucket.
-    nodes: ArrayVec<Node<TKey, TVal>, { K_VALUE.get() }>,
+    nodes: Vec<Node<TKey, TVal>>,
+
+    /// The maximum number of nodes in the bucket.
+    capacity: NonZeroUsize,
 
     /// The position (index) in `nodes` that marks the first connected node.
     ///
@@ -104,7 +128,7 @@ pub(crate) struct KBucket<TKey, TVal> {
     /// most-recently connected, all entries above this index are also considered
     /// connected, i.e. the range `[0, first_connected_pos)` marks the sub-list of entries
     /// that are considered disconnected and the range
-    /// `[first_connected_pos, K_VALUE)` marks sub-list of entries that are
+    /// `[first_connected_pos, capacity)` marks sub-list of entries that are
     /// considered connected.
     ///
     /// `None` indicates that there are no connected entries in the bucket, i.e.
@@ -112,10 +136,13 @@ pub(crate) struct KBucket<TKey, TVal> {
     /// considered disconnected.
     first_connected_pos: Option<usize>,
 
-    /// A node that is pending to be inserted into a full bucket, should the
+    /// The nodes that are pending to be inserted into a full bucket, should the
     /// least-recently connected (and currently disconnected) node not be
-    /// marked as connected within `unresponsive_timeout`.
-    pending: Option<PendingNode<TKey, TVal>>,
+    /// marked as connected within `pending_timeout`.
+    pending: Vec<PendingNode<TKey, TVal>>,
+
+    /// The maximum number of pending nodes.
+    max_pending: NonZeroUsize,
 
     /// The timeout window before a new pending node is eligible for insertion,
     /// if the least-recently connected node is not updated as being connected
@@ -141,7 +168,8 @@ pub(crate) enum InsertResult<TKey> {
         /// [`NodeStatus::Connected`].
         disconnected: TKey,
     },
-    /// The entry was not inserted because the relevant bucket is full.
+    /// The entry was not inserted because the relevant bucket is full
+    /// and so are its pending entries.
     Full,
 }
 
@@ -161,31 +189,41 @@ where
     TKey: Clone + AsRef<KeyBytes>,
     TVal: Clone,
 {
-    /// Creates a new `KBucket` with the given timeout for pending entries.
-    pub(crate) fn new(pending_timeout: Duration) -> Self {
+    /// Creates a new `KBucket` holding up to `capacity` nodes and up to
+    /// `max_pending` pending nodes, with the given timeout for pending entries.
+    pub(crate) fn new(
+        capacity: NonZeroUsize,
+        max_pending: NonZeroUsize,
+        pending_timeout: Duration,
+    ) -> Self {
         KBucket {
-            nodes: ArrayVec::new(),
+            nodes: Vec::with_capacity(capacity.get()),
+            capacity,
             first_connected_pos: None,
-            pending: None,
+            pending: Vec::new(),
+            max_pending,
             pending_timeout,
         }
     }
 
-    /// Returns a reference to the pending node of the bucket, if there is any.
-    pub(crate) fn pending(&self) -> Option<&PendingNode<TKey, TVal>> {
-        self.pending.as_ref()
+    /// Returns an iterator over the pending nodes of the bucket.
+    pub(crate) fn pending(&self) -> impl Iterator<Item = &PendingNode<TKey, TVal>> {
+        self.pending.iter()
     }
 
-    /// Returns a mutable reference to the pending node of the bucket, if there is any.
-    pub(crate) fn pending_mut(&mut self) -> Option<&mut PendingNode<TKey, TVal>> {
-        self.pending.as_mut()
+    /// Returns a mutable reference to the pending node with a matching key, if any.
+    pub(crate) fn pending_mut(&mut self, key: &TKey) -> Option<&mut PendingNode<TKey, TVal>> {
+        self.pending
+            .iter_mut()
+            .find(|p| p.node.key.as_ref() == key.as_ref())
     }
 
     /// Returns a reference to the pending node of the bucket, if there is any
     /// with a matching key.
     pub(crate) fn as_pending(&self, key: &TKey) -> Option<&PendingNode<TKey, TVal>> {
-        self.pending()
-            .filter(|p| p.node.key.as_ref() == key.as_ref())
+        self.pending
+            .iter()
+            .find(|p| p.node.key.as_ref() == key.as_ref())
     }
 
     /// Returns an iterator over the nodes in the bucket, together with their status.
@@ -196,78 +234,80 @@ where
             .map(move |(p, n)| (n, self.status(Position(p))))
     }
 
-    /// Inserts the pending node into the bucket, if its timeout has elapsed,
-    /// replacing the least-recently connected node.
+    /// Inserts the best pending node into the bucket, if the bucket has a free
+    /// slot, or if its timeout has elapsed, replacing the least-recently connected node.
     ///
     /// If a pending node has been inserted, its key is returned together with
     /// the node that was replaced. `None` indicates that the nodes in the
     /// bucket remained unchanged.
     pub(crate) fn apply_pending(&mut self) -> Option<AppliedPending<TKey, TVal>> {
-        if let Some(pending) = self.pending.take() {
-            if pending.replace <= Instant::now() {
-                if self.nodes.is_full() {
-                    if self.status(Position(0)) == NodeStatus::Connected {
-                        // The bucket is full with connected nodes. Drop the pending node.
-                        return None;
-                    }
-                    debug_assert!(self.first_connected_pos.map_or(true, |p| p > 0)); // (*)
-                                                                                     // The pending node will be inserted.
-                    let inserted = pending.node.clone();
-                    // A connected pending node goes at the end of the list for
-                    // the connected peers, removing the least-recently connected.
-                    if pending.status == NodeStatus::Connected {
-                        let evicted = Some(self.nodes.remove(0));
-                        self.first_connected_pos = self
-                            .first_connected_pos
-                            .map_or_else(|| Some(self.nodes.len()), |p| p.checked_sub(1));
-                        self.nodes.push(pending.node);
-                        return Some(AppliedPending { inserted, evicted });
-                    }
-                    // A disconnected pending node goes at the end of the list
-                    // for the disconnected peers.
-                    else if let Some(p) = self.first_connected_pos {
-                        let insert_pos = p.checked_sub(1).expect("by (*)");
-                        let evicted = Some(self.nodes.remove(0));
-                        self.nodes.insert(insert_pos, pending.node);
-                        return Some(AppliedPending { inserted, evicted });
-                    } else {
-                        // All nodes are disconnected. Insert the new node as the most
-                        // recently disconnected, removing the least-recently disconnected.
-                        let evicted = Some(self.nodes.remove(0));
-                        self.nodes.push(pending.node);
-                        return Some(AppliedPending { inserted, evicted });
-                    }
-                } else {
-                    // There is room in the bucket, so just insert the pending node.
-                    let inserted = pending.node.clone();
-                    match self.insert(pending.node, pending.status) {
-                        InsertResult::Inserted => {
-                            return Some(AppliedPending {
-                                inserted,
-                                evicted: None,
-                            })
-                        }
-                        _ => unreachable!("Bucket is not full."),
-                    }
-                }
+        let now = Instant::now();
+        let is_full = self.is_full();
+        let best = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| !is_full || p.replace <= now)
+            .min_by_key(|(_, p)| p.quality_key())
+            .map(|(i, _)| i);
+        let i = best?;
+        let pending = self.pending.remove(i);
+        if self.is_full() {
+            if self.status(Position(0)) == NodeStatus::Connected {
+                // The bucket is full with connected nodes. Drop the pending nodes.
+                self.pending.clear();
+                return None;
+            }
+            debug_assert!(self.first_connected_pos.map_or(true, |p| p > 0)); // (*)
+                                                                             // The pending node will be inserted.
+            let inserted = pending.node.clone();
+            let evicted = Some(self.nodes.remove(0));
+            // A connected pending node goes at the end of the list for
+            // the connected peers, removing the least-recently connected.
+            if pending.status == NodeStatus::Connected {
+                self.first_connected_pos = self
+                    .first_connected_pos
+                    .map_or_else(|| Some(self.nodes.len()), |p| p.checked_sub(1));
+                self.nodes.push(pending.node);
+            }
+            // A disconnected pending node goes at the end of the list
+            // for the disconnected peers.
+            else if let Some(p) = self.first_connected_pos {
+                let insert_pos = p.checked_sub(1).expect("by (*)");
+                self.nodes.insert(insert_pos, pending.node);
             } else {
-                self.pending = Some(pending);
+                // All nodes are disconnected. Insert the new node as the most
+                // recently disconnected, removing the least-recently disconnected.
+                self.nodes.push(pending.node);
+            }
+            Some(AppliedPending { inserted, evicted })
+        } else {
+            // There is room in the bucket, so just insert the pending node.
+            let inserted = pending.node.clone();
+            match self.insert(pending.node, pending.status) {
+                InsertResult::Inserted => Some(AppliedPending {
+                    inserted,
+                    evicted: None,
+                }),
+                _ => unreachable!("Bucket is not full."),
             }
         }
-
-        None
     }
 
-    /// Updates the status of the pending node, if any.
-    pub(crate) fn update_pending(&mut self, status: NodeStatus) {
-        if let Some(pending) = &mut self.pending {
+    /// Updates the status of the pending node with a matching key, if any.
+    pub(crate) fn update_pending(&mut self, key: &TKey, status: NodeStatus) {
+        if let Some(pending) = self.pending_mut(key) {
             pending.status = status
         }
     }
 
-    /// Removes the pending node from the bucket, if any.
-    pub(crate) fn remove_pending(&mut self) -> Option<PendingNode<TKey, TVal>> {
-        self.pending.take()
+    /// Removes the pending node with a matching key from the bucket, if any.
+    pub(crate) fn remove_pending(&mut self, key: &TKey) -> Option<PendingNode<TKey, TVal>> {
+        let i = self
+            .pending
+            .iter()
+            .position(|p| p.node.key.as_ref() == key.as_ref())?;
+        Some(self.pending.remove(i))
     }
 
     /// Updates the status of the node referred to by the given key, if it is
@@ -280,9 +320,18 @@ where
         // respectively).
         if let Some((node, _status, pos)) = self.remove(key) {
             // If the least-recently connected node re-establishes its
-            // connected status, drop the pending node.
+            // connected status, drop the pending node that has been waiting
+            // for it the longest.
             if pos == Position(0) && status == NodeStatus::Connected {
-                self.pending = None
+                if let Some(i) = self
+                    .pending
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, p)| p.replace)
+                    .map(|(i, _)| i)
+                {
+                    self.pending.remove(i);
+                }
             }
             // Reinsert the node with the desired status.
             match self.insert(node, status) {
@@ -297,9 +346,11 @@ where
     /// The status of the node to insert determines the result as follows:
     ///
     ///   * `NodeStatus::Connected`: If the bucket is full and either all nodes are connected
-    ///     or there is already a pending node, insertion fails with `InsertResult::Full`.
-    ///     If the bucket is full but at least one node is disconnected and there is no pending
-    ///     node, the new node is inserted as pending, yielding `InsertResult::Pending`.
+    ///     or the maximum number of pending nodes is reached, insertion fails with
+    ///     `InsertResult::Full`, unless a disconnected pending node can be replaced.
+    ///     If the bucket is full but at least one node is disconnected and there is room
+    ///     for another pending node, the new node is inserted as pending, yielding
+    ///     `InsertResult::Pending`.
     ///     Otherwise the bucket has free slots and the new node is added to the end of the
     ///     bucket as the most-recently connected node.
     ///
@@ -313,22 +364,50 @@ where
         &mut self,
         node: Node<TKey, TVal>,
         status: NodeStatus,
+    ) -> InsertResult<TKey> {
+        self.insert_with_rtt(node, status, None)
+    }
+
+    /// Inserts a new node into the bucket with the given status, like [`KBucket::insert`].
+    ///
+    /// The round-trip time observed for the node, if any, ranks it among the
+    /// other pending nodes in case it is inserted as pending.
+    pub(crate) fn insert_with_rtt(
+        &mut self,
+        node: Node<TKey, TVal>,
+        status: NodeStatus,
+        rtt: Option<Duration>,
     ) -> InsertResult<TKey> {
         match status {
             NodeStatus::Connected => {
-                if self.nodes.is_full() {
-                    if self.first_connected_pos == Some(0) || self.pending.is_some() {
+                if self.is_full() {
+                    if self.first_connected_pos == Some(0) {
                         return InsertResult::Full;
-                    } else {
-                        self.pending = Some(PendingNode {
-                            node,
-                            status: NodeStatus::Connected,
-                            replace: Instant::now() + self.pending_timeout,
-                        });
-                        return InsertResult::Pending {
-                            disconnected: self.nodes[0].key.clone(),
+                    }
+                    if self.pending.len() >= self.max_pending.get() {
+                        // Make room by dropping the worst pending node that is
+                        // no longer connected, if any.
+                        let Some(i) = self
+                            .pending
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, p)| p.status == NodeStatus::Disconnected)
+                            .max_by_key(|(_, p)| p.quality_key())
+                            .map(|(i, _)| i)
+                        else {
+                            return InsertResult::Full;
                         };
+                        self.pending.remove(i);
                     }
+                    self.pending.push(PendingNode {
+                        node,
+                        status: NodeStatus::Connected,
+                        replace: Instant::now() + self.pending_timeout,
+                        rtt,
+                    });
+                    return InsertResult::Pending {
+                        disconnected: self.nodes[0].key.clone(),
+                    };
                 }
                 let pos = self.nodes.len();
                 self.first_connected_pos = self.first_connected_pos.or(Some(pos));
@@ -336,7 +415,7 @@ where
                 InsertResult::Inserted
             }
             NodeStatus::Disconnected => {
-                if self.nodes.is_full() {
+                if self.is_full() {
                     return InsertResult::Full;
                 }
                 if let Some(ref mut p) = self.first_connected_pos {
@@ -395,6 +474,16 @@ where
         self.nodes.len()
     }
 
+    /// Gets the maximum number of entries in the bucket.
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
+
+    /// Checks whether the bucket holds the maximum number of entries.
+    fn is_full(&self) -> bool {
+        self.nodes.len() >= self.capacity.get()
+    }
+
     /// Gets the number of entries in the bucket that are considered connected.
     #[cfg(test)]
     pub(crate) fn num_connected(&self) -> usize {
@@ -435,7 +524,7 @@ mod tests {
     impl Arbitrary for KBucket<Key<PeerId>, ()> {
         fn arbitrary(g: &mut Gen) -> KBucket<Key<PeerId>, ()> {
             let timeout = Duration::from_secs(g.gen_range(1..g.size()) as u64);
-            let mut bucket = KBucket::<Key<PeerId>, ()>::new(timeout);
+            let mut bucket = KBucket::<Key<PeerId>, ()>::new(K_VALUE, NonZeroUsize::MIN, timeout);
             let num_nodes = g.gen_range(1..K_VALUE.get() + 1);
             for _ in 0..num_nodes {
                 let key = Key::from(PeerId::random());
@@ -483,7 +572,8 @@ mod tests {
     #[test]
     fn ordering() {
         fn prop(status: Vec<NodeStatus>) -> bool {
-            let mut bucket = KBucket::<Key<PeerId>, ()>::new(Duration::from_secs(1));
+            let mut bucket =
+                KBucket::<Key<PeerId>, ()>::new(K_VALUE, NonZeroUsize::MIN, Duration::from_secs(1));
 
             // The expected lists of connected and disconnected nodes.
             let mut connected = VecDeque::new();
@@ -531,7 +621,8 @@ mod tests {
 
     #[test]
     fn full_bucket() {
-        let mut bucket = KBucket::<Key<PeerId>, ()>::new(Duration::from_secs(1));
+        let mut bucket =
+            KBucket::<Key<PeerId>, ()>::new(K_VALUE, NonZeroUsize::MIN, Duration::from_secs(1));
 
         // Fill the bucket with disconnected nodes.
         fill_bucket(&mut bucket, NodeStatus::Disconnected);
@@ -570,10 +661,10 @@ mod tests {
                 x => panic!("{x:?}"),
             }
 
-            assert!(bucket.pending().is_some());
+            assert!(bucket.pending().next().is_some());
 
             // Apply the pending node.
-            let pending = bucket.pending_mut().expect("No pending node.");
+            let pending = bucket.pending_mut(&key).expect("No pending node.");
             pending.set_ready_at(Instant::now().checked_sub(Duration::from_secs(1)).unwrap());
             let result = bucket.apply_pending();
             assert_eq!(
@@ -584,11 +675,11 @@ mod tests {
                 })
             );
             assert_eq!(Some((&node, NodeStatus::Connected)), bucket.iter().last());
-            assert!(bucket.pending().is_none());
+            assert!(bucket.pending().next().is_none());
             assert_eq!(Some(K_VALUE.get() - (i + 1)), bucket.first_connected_pos);
         }
 
-        assert!(bucket.pending().is_none());
+        assert!(bucket.pending().next().is_none());
         assert_eq!(K_VALUE.get(), bucket.num_entries());
 
         // Trying to insert another connected node fails.
@@ -602,7 +693,8 @@ mod tests {
 
     #[test]
     fn full_bucket_discard_pending() {
-        let mut bucket = KBucket::<Key<PeerId>, ()>::new(Duration::from_secs(1));
+        let mut bucket =
+            KBucket::<Key<PeerId>, ()>::new(K_VALUE, NonZeroUsize::MIN, Duration::from_secs(1));
         fill_bucket(&mut bucket, NodeStatus::Disconnected);
         let (first, _) = bucket.iter().next().unwrap();
         let first_disconnected = first.clone();
@@ -618,13 +710,13 @@ mod tests {
         } else {
             panic!()
         }
-        assert!(bucket.pending().is_some());
+        assert!(bucket.pending().next().is_some());
 
         // Update the status of the first disconnected node to be connected.
         bucket.update(&first_disconnected.key, NodeStatus::Connected);
 
         // The pending node has been discarded.
-        assert!(bucket.pending().is_none());
+        assert!(bucket.pending().next().is_none());
         assert!(bucket.iter().all(|(n, _)| n.key != key));
 
         // The initially disconnected node is now the most-recently connected.
@@ -640,6 +732,81 @@ mod tests {
         assert_eq!(K_VALUE.get() - 1, bucket.num_disconnected());
     }
 
+    #[test]
+    fn multiple_pending_nodes_ordered_by_quality() {
+        let max_pending = NonZeroUsize::new(3).unwrap();
+        let mut bucket = KBucket::<Key<PeerId>, ()>::new(K_VALUE, max_pending, Duration::ZERO);
+        fill_bucket(&mut bucket, NodeStatus::Disconnected);
+
+        fn insert(
+            bucket: &mut KBucket<Key<PeerId>, ()>,
+            rtt: Option<Duration>,
+        ) -> (Node<Key<PeerId>, ()>, InsertResult<Key<PeerId>>) {
+            let node = Node {
+                key: Key::from(PeerId::random()),
+                value: (),
+            };
+            let result = bucket.insert_with_rtt(node.clone(), NodeStatus::Connected, rtt);
+            (node, result)
+        }
+        let (slow, result) = insert(&mut bucket, Some(Duration::from_millis(200)));
+        assert!(matches!(result, InsertResult::Pending { .. }));
+        let (unknown, result) = insert(&mut bucket, None);
+        assert!(matches!(result, InsertResult::Pending { .. }));
+        let (fast, result) = insert(&mut bucket, Some(Duration::from_millis(20)));
+        assert!(matches!(result, InsertResult::Pending { .. }));
+        assert_eq!(bucket.pending().count(), 3);
+
+        // All pending slots are taken by connected nodes.
+        let (_, result) = insert(&mut bucket, None);
+        assert_eq!(result, InsertResult::Full);
+
+        // A pending node that disconnected makes room for a new one.
+        bucket.update_pending(&slow.key, NodeStatus::Disconnected);
+        let (other, result) = insert(&mut bucket, None);
+        assert!(matches!(result, InsertResult::Pending { .. }));
+        assert!(bucket.as_pending(&slow.key).is_none());
+
+        // Pending nodes are applied best first.
+        let applied = std::iter::from_fn(|| bucket.apply_pending())
+            .map(|a| a.inserted.key)
+            .collect::<Vec<_>>();
+        assert_eq!(applied, vec![fast.key, unknown.key, other.key]);
+        assert_eq!(bucket.pending().count(), 0);
+    }
+
+    #[test]
+    fn pending_node_applied_when_slot_frees_up() {
+        let mut bucket = KBucket::<Key<PeerId>, ()>::new(
+            K_VALUE,
+            NonZeroUsize::new(2).unwrap(),
+            Duration::from_secs(60),
+        );
+        fill_bucket(&mut bucket, NodeStatus::Disconnected);
+        let node = Node {
+            key: Key::from(PeerId::random()),
+            value: (),
+        };
+        assert!(matches!(
+            bucket.insert(node.clone(), NodeStatus::Connected),
+            InsertResult::Pending { .. }
+        ));
+
+        // The timeout did not elapse yet.
+        assert_eq!(bucket.apply_pending(), None);
+
+        let (first, _) = bucket.iter().next().unwrap();
+        let first = first.key.clone();
+        bucket.remove(&first);
+        assert_eq!(
+            bucket.apply_pending(),
+            Some(AppliedPending {
+                inserted: node,
+                evicted: None
+            })
+        );
+    }
+
     #[test]
     fn bucket_update() {
         fn prop(mut bucket: KBucket<Key<PeerId>, ()>, pos: Position, status: NodeStatus) -> bool {
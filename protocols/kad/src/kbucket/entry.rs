@@ -196,24 +196,33 @@ where
         PendingEntry(EntryRef { bucket, key })
     }
 
-    /// Returns the value associated with the key.
-    pub(crate) fn value(&mut self) -> &mut TVal {
+    fn pending(&mut self) -> &mut bucket::PendingNode<TKey, TVal> {
         self.0
             .bucket
-            .pending_mut()
-            .expect("We can only build a ConnectedPendingEntry if the entry is pending; QED")
-            .value_mut()
+            .pending_mut(self.0.key)
+            .expect("We can only build a PendingEntry if the entry is pending; QED")
+    }
+
+    /// Returns the value associated with the key.
+    pub(crate) fn value(&mut self) -> &mut TVal {
+        self.pending().value_mut()
+    }
+
+    /// Sets the round-trip time observed for the peer, which ranks the entry
+    /// among the other pending entries of the bucket.
+    pub(crate) fn set_rtt(&mut self, rtt: Option<Duration>) {
+        self.pending().set_rtt(rtt);
     }
 
     /// Updates the status of the pending entry.
     pub(crate) fn update(self, status: NodeStatus) -> PendingEntry<'a, TKey, TVal> {
-        self.0.bucket.update_pending(status);
+        self.0.bucket.update_pending(self.0.key, status);
         PendingEntry::new(self.0.bucket, self.0.key)
     }
 
     /// Removes the pending entry from the bucket.
     pub(crate) fn remove(self) -> EntryView<TKey, TVal> {
-        let pending = self.0.bucket.remove_pending().expect(
+        let pending = self.0.bucket.remove_pending(self.0.key).expect(
             "We can only build a PendingEntry if the entry is pending insertion
                     into the bucket; QED",
         );
@@ -236,13 +245,22 @@ where
     }
 
     /// Attempts to insert the entry into a bucket.
-    pub(crate) fn insert(self, value: TVal, status: NodeStatus) -> InsertResult<TKey> {
-        self.0.bucket.insert(
+    ///
+    /// The round-trip time observed for the peer, if any, ranks the entry among
+    /// the other pending entries of the bucket in case it is inserted as pending.
+    pub(crate) fn insert(
+        self,
+        value: TVal,
+        status: NodeStatus,
+        rtt: Option<Duration>,
+    ) -> InsertResult<TKey> {
+        self.0.bucket.insert_with_rtt(
             Node {
                 key: self.0.key.clone(),
                 value,
             },
             status,
+            rtt,
         )
     }
 }
@@ -24,7 +24,9 @@ use libp2p_identity::PeerId;
 use sha2::digest::generic_array::{typenum::U32, GenericArray};
 use sha2::{Digest, Sha256};
 use std::borrow::Borrow;
+use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use uint::*;
 
 construct_uint! {
@@ -185,6 +187,84 @@ impl AsRef<KeyBytes> for KeyBytes {
     }
 }
 
+/// Maps the preimages of keys, i.e. [`PeerId`]s and record keys, into the DHT keyspace.
+///
+/// By default, the keyspace is spanned by the SHA-256 digests of the preimages. Deployments
+/// may plug a different scheme via
+/// [`Config::set_key_hasher`](crate::Config::set_key_hasher), e.g. a double hash or a
+/// keyed hash for private networks. All nodes of a network must use the same scheme, as the
+/// distances of keys determine which peers are queried for and store a record.
+///
+/// The [`MemoryStore`](crate::store::MemoryStore) is to be given the same hasher via
+/// [`MemoryStore::with_key_hasher`](crate::store::MemoryStore::with_key_hasher). Provider
+/// summaries, see [`ProviderSummary`](crate::ProviderSummary), and keys converted via
+/// `From`, e.g. those passed to [`Behaviour::kbucket`](crate::Behaviour::kbucket), always
+/// use SHA-256.
+pub trait KeyHasher: Send + Sync + 'static {
+    /// Returns the position of the preimage in the DHT keyspace.
+    fn hash(&self, preimage: &[u8]) -> [u8; 32];
+}
+
+impl<F> KeyHasher for F
+where
+    F: Fn(&[u8]) -> [u8; 32] + Send + Sync + 'static,
+{
+    fn hash(&self, preimage: &[u8]) -> [u8; 32] {
+        self(preimage)
+    }
+}
+
+/// The [`KeyHasher`] set in the [`Config`](crate::Config), if any, hashing via SHA-256
+/// otherwise.
+#[derive(Clone, Default)]
+pub(crate) struct KeyHashing {
+    hasher: Option<Arc<dyn KeyHasher>>,
+}
+
+impl KeyHashing {
+    pub(crate) fn new(hasher: impl KeyHasher) -> Self {
+        Self {
+            hasher: Some(Arc::new(hasher)),
+        }
+    }
+
+    /// Returns the key of a record or of another preimage of bytes.
+    pub(crate) fn key<T>(&self, preimage: T) -> Key<T>
+    where
+        T: Borrow<[u8]>,
+    {
+        match &self.hasher {
+            None => Key::new(preimage),
+            Some(hasher) => {
+                let bytes = KeyBytes(GenericArray::from(hasher.hash(preimage.borrow())));
+                Key { preimage, bytes }
+            }
+        }
+    }
+
+    /// Returns the key of a peer.
+    pub(crate) fn peer(&self, peer: PeerId) -> Key<PeerId> {
+        match &self.hasher {
+            None => Key::from(peer),
+            Some(hasher) => {
+                let bytes = KeyBytes(GenericArray::from(hasher.hash(&peer.to_bytes())));
+                Key {
+                    preimage: peer,
+                    bytes,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for KeyHashing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyHashing")
+            .field("custom", &self.hasher.is_some())
+            .finish()
+    }
+}
+
 /// A distance between two keys in the DHT keyspace.
 #[derive(Copy, Clone, PartialEq, Eq, Default, PartialOrd, Ord, Debug)]
 pub struct Distance(pub(super) U256);
@@ -35,43 +35,86 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+mod address_filter;
+mod address_revalidation;
 mod addresses;
+mod authorizer;
 mod behaviour;
 mod bootstrap;
+mod bootstrap_peers;
+mod bucket_refresh;
+mod conflict;
 mod handler;
+mod inbound_observer;
+mod ip_diversity;
 mod jobs;
 mod kbucket;
+mod namespace;
+mod peer_store;
 mod protocol;
+mod provider_filter;
+mod provider_summary;
 mod query;
+#[cfg(feature = "stream")]
+mod query_stream;
+mod rate_limiter;
 mod record;
+mod routing_updates;
+mod scorer;
+mod signed_records;
+mod snapshot;
+mod validator;
 
 mod proto {
     #![allow(unreachable_pub)]
     include!("generated/mod.rs");
     pub use self::dht::pb::{
         mod_Message::{ConnectionType, MessageType, Peer},
-        Message, Record,
+        mod_RoutingTableSnapshot::Peer as SnapshotPeer,
+        Message, ProviderSummary, Record, RoutingTableSnapshot,
     };
 }
 
+pub use address_filter::{AddressFilter, AddressSource};
 pub use addresses::Addresses;
+pub use authorizer::{RequestAuthorizer, RestrictedRequest};
 pub use behaviour::{
     AddProviderContext, AddProviderError, AddProviderOk, AddProviderPhase, AddProviderResult,
-    BootstrapError, BootstrapOk, BootstrapResult, GetClosestPeersError, GetClosestPeersOk,
-    GetClosestPeersResult, GetProvidersError, GetProvidersOk, GetProvidersResult, GetRecordError,
-    GetRecordOk, GetRecordResult, InboundRequest, Mode, NoKnownPeers, PeerRecord, PutRecordContext,
-    PutRecordError, PutRecordOk, PutRecordPhase, PutRecordResult, QueryInfo, QueryMut, QueryRef,
-    QueryResult, QueryStats, RoutingUpdate,
+    BootstrapError, BootstrapOk, BootstrapResult, CrawlError, CrawlOk, CrawlResult, EvictionReason,
+    GetClosestPeersError, GetClosestPeersOk, GetClosestPeersResult, GetProviderSummaryError,
+    GetProviderSummaryOk, GetProviderSummaryResult, GetProvidersError, GetProvidersOk,
+    GetProvidersResult, GetRecordError, GetRecordOk, GetRecordResult, InboundRequest, Mode,
+    ModeChangeReason, NoKnownPeers, PeerRecord, PutRecordContext, PutRecordError, PutRecordOk,
+    PutRecordPeerError, PutRecordPeerFailure, PutRecordPhase, PutRecordResult, QueryHandle,
+    QueryInfo, QueryLimitReached, QueryMut, QueryRef, QueryResult, QueryStats, RateLimit,
+    Reachability, RefreshError, RefreshOk, RefreshResult, RoutingTableAction, RoutingUpdate,
+    ThrottledRequest,
 };
 pub use behaviour::{
-    Behaviour, BucketInserts, Caching, Config, Event, ProgressStep, Quorum, StoreInserts,
+    Behaviour, BucketInserts, Caching, Config, Event, ProgressStep, Quorum, RelayedAddresses,
+    StoreInserts,
 };
+pub use conflict::{ConflictResolver, HighestSequence};
+pub use inbound_observer::{InboundRequestInfo, InboundRequestObserver, InboundRequestType};
 pub use kbucket::{
-    Distance as KBucketDistance, EntryView, KBucketRef, Key as KBucketKey, NodeStatus,
+    Distance as KBucketDistance, EntryView, KBucketRef, Key as KBucketKey, KeyHasher, NodeStatus,
 };
+pub use namespace::Namespace;
+pub use peer_store::PeerStore;
 pub use protocol::ConnectionType;
-pub use query::QueryId;
+pub use provider_filter::{ProviderFilter, ProvidersFor};
+pub use provider_summary::{KeyspaceRange, ProviderSummary};
+pub use query::{
+    AdaptiveParallelism, QueryHop, QueryHopResult, QueryId, QueryOpts, QueryPriority, QueryRpc,
+    RetryPolicy,
+};
+#[cfg(feature = "stream")]
+pub use query_stream::{QueryOutcome, QueryStream};
 pub use record::{store, Key as RecordKey, ProviderRecord, Record};
+pub use routing_updates::RoutingUpdates;
+pub use scorer::{Misbehaviour, PeerScorer};
+pub use snapshot::{DecodeSnapshotError, RoutingTableEntry, RoutingTableSnapshot};
+pub use validator::{InvalidRecord, RecordValidator};
 
 use libp2p_swarm::StreamProtocol;
 use std::num::NonZeroUsize;
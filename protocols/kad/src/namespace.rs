@@ -0,0 +1,214 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Registry of key namespaces and their handling of records.
+//!
+//! Applications register a [`Namespace`] via
+//! [`Config::add_namespace`](crate::Config::add_namespace), combining the validation,
+//! the resolution of conflicts and the storage policy of the records whose keys are in
+//! the namespace, like the validator namespaces of other libp2p implementations, e.g.
+//! `ipns` and `pk`.
+
+use crate::behaviour::StoreInserts;
+use crate::conflict::{ConflictResolver, ConflictResolvers};
+use crate::record::{self, Record};
+use crate::validator::{namespace, InvalidRecord, RecordValidator, RecordValidators};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The handling of the records of a key namespace, see
+/// [`Config::add_namespace`](crate::Config::add_namespace).
+///
+/// Aspects not set fall back to the defaults of the [`Config`](crate::Config).
+#[derive(Clone, Default)]
+pub struct Namespace {
+    validator: Option<Arc<dyn RecordValidator>>,
+    resolver: Option<Arc<dyn ConflictResolver>>,
+    policy: StoragePolicy,
+}
+
+impl Namespace {
+    /// Creates a namespace falling back to the defaults of the [`Config`](crate::Config).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the [`RecordValidator`] of the namespace, see
+    /// [`Config::add_record_validator`](crate::Config::add_record_validator).
+    pub fn with_validator(mut self, validator: impl RecordValidator) -> Self {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Sets the [`ConflictResolver`] of the namespace, see
+    /// [`Config::add_conflict_resolver`](crate::Config::add_conflict_resolver).
+    pub fn with_conflict_resolver(mut self, resolver: impl ConflictResolver) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Sets the TTL of the records of the namespace stored locally, overriding
+    /// [`Config::set_record_ttl`](crate::Config::set_record_ttl).
+    pub fn with_record_ttl(mut self, record_ttl: Option<Duration>) -> Self {
+        self.policy.record_ttl = Some(record_ttl);
+        self
+    }
+
+    /// Sets how inbound records of the namespace are stored, overriding
+    /// [`Config::set_record_filtering`](crate::Config::set_record_filtering).
+    pub fn with_record_filtering(mut self, filtering: StoreInserts) -> Self {
+        self.policy.record_filtering = Some(filtering);
+        self
+    }
+}
+
+impl std::fmt::Debug for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Namespace")
+            .field("validator", &self.validator.is_some())
+            .field("resolver", &self.resolver.is_some())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+/// The storage policy of a namespace, overriding the [`Config`](crate::Config) where set.
+#[derive(Debug, Clone, Copy, Default)]
+struct StoragePolicy {
+    record_ttl: Option<Option<Duration>>,
+    record_filtering: Option<StoreInserts>,
+}
+
+/// The registered namespaces.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Namespaces {
+    pub(crate) validators: RecordValidators,
+    pub(crate) resolvers: ConflictResolvers,
+    policies: HashMap<Vec<u8>, StoragePolicy>,
+    /// See [`Config::set_reject_unknown_namespaces`](crate::Config::set_reject_unknown_namespaces).
+    pub(crate) reject_unknown: bool,
+}
+
+impl Namespaces {
+    pub(crate) fn insert(&mut self, name: &str, namespace: Namespace) {
+        if let Some(validator) = namespace.validator {
+            self.validators.insert_shared(name, validator);
+        }
+        if let Some(resolver) = namespace.resolver {
+            self.resolvers.insert_shared(name, resolver);
+        }
+        self.policies
+            .insert(name.as_bytes().to_vec(), namespace.policy);
+    }
+
+    /// Validates a record received from a remote node with the validator of its key
+    /// namespace, rejecting records of unknown namespaces if configured.
+    pub(crate) fn validate(&self, record: &Record) -> Result<(), InvalidRecord> {
+        if self.reject_unknown && !self.is_known(&record.key) {
+            return Err(InvalidRecord::new("unknown key namespace"));
+        }
+        self.validators.validate(record)
+    }
+
+    /// Returns the record TTL of the namespace of the key, if overridden.
+    pub(crate) fn record_ttl(&self, key: &record::Key) -> Option<Option<Duration>> {
+        self.policy(key).and_then(|p| p.record_ttl)
+    }
+
+    /// Returns the record filtering of the namespace of the key, if overridden.
+    pub(crate) fn record_filtering(&self, key: &record::Key) -> Option<StoreInserts> {
+        self.policy(key).and_then(|p| p.record_filtering)
+    }
+
+    fn policy(&self, key: &record::Key) -> Option<&StoragePolicy> {
+        namespace(key).and_then(|ns| self.policies.get(ns))
+    }
+
+    /// Returns whether the namespace of the key is registered, either via
+    /// [`Namespaces::insert`] or with a validator or resolver only.
+    fn is_known(&self, key: &record::Key) -> bool {
+        namespace(key).is_some_and(|ns| {
+            self.policies.contains_key(ns)
+                || self.validators.contains(ns)
+                || self.resolvers.contains(ns)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    fn record(key: &str, value: u8) -> Record {
+        Record::new(record::Key::new(&key), vec![value])
+    }
+
+    #[test]
+    fn routes_by_namespace() {
+        let mut namespaces = Namespaces::default();
+        namespaces.insert(
+            "pk",
+            Namespace::new()
+                .with_validator(|record: &Record| match record.value[..] {
+                    [0] => Err(InvalidRecord::new("zero")),
+                    _ => Ok(()),
+                })
+                .with_conflict_resolver(|_: &Record, _: &Record| Ordering::Greater)
+                .with_record_ttl(None)
+                .with_record_filtering(StoreInserts::FilterBoth),
+        );
+
+        assert!(namespaces.validate(&record("/pk/a", 0)).is_err());
+        assert!(namespaces.validate(&record("/pk/a", 1)).is_ok());
+        assert!(namespaces.validate(&record("/ipns/a", 0)).is_ok());
+        assert_eq!(
+            namespaces
+                .resolvers
+                .compare(&record("/pk/a", 1), &record("/pk/a", 1)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            namespaces.record_ttl(&record::Key::new(&"/pk/a")),
+            Some(None)
+        );
+        assert_eq!(namespaces.record_ttl(&record::Key::new(&"/ipns/a")), None);
+        assert_eq!(
+            namespaces.record_filtering(&record::Key::new(&"/pk/a")),
+            Some(StoreInserts::FilterBoth)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_namespaces() {
+        let mut namespaces = Namespaces {
+            reject_unknown: true,
+            ..Default::default()
+        };
+        namespaces.insert("pk", Namespace::new());
+        namespaces.validators.insert("ipns", |_: &Record| Ok(()));
+
+        assert!(namespaces.validate(&record("/pk/a", 0)).is_ok());
+        assert!(namespaces.validate(&record("/ipns/a", 0)).is_ok());
+        assert!(namespaces.validate(&record("/app/a", 0)).is_err());
+        assert!(namespaces.validate(&record("no-namespace", 0)).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Seeding the routing table from a persistent peer store on startup.
+//!
+//! Applications set a [`PeerStore`] via [`Config::set_peer_store`](crate::Config::set_peer_store)
+//! such that a restarted node starts out with the peers it has recently seen, instead of
+//! only its bootnodes.
+
+use crate::snapshot::RoutingTableEntry;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A persistent store of the peers seen by the local node.
+pub trait PeerStore: Send + Sync + 'static {
+    /// Returns the peers known to support the Kademlia protocol of the local node, with
+    /// their addresses and the time they were last seen.
+    ///
+    /// Called once, when the [`Behaviour`](crate::Behaviour) is created.
+    fn kad_peers(&self) -> Vec<RoutingTableEntry>;
+
+    /// Called when a peer of the routing table disconnects, with the addresses of the
+    /// peer in the routing table and the current time as `last_seen`.
+    ///
+    /// Does nothing by default, for stores fed by other means.
+    fn on_peer_seen(&self, _entry: RoutingTableEntry) {}
+}
+
+impl<F> PeerStore for F
+where
+    F: Fn() -> Vec<RoutingTableEntry> + Send + Sync + 'static,
+{
+    fn kad_peers(&self) -> Vec<RoutingTableEntry> {
+        self()
+    }
+}
+
+/// The [`PeerStore`] set in the [`Config`](crate::Config), if any, and which of its
+/// peers seed the routing table.
+#[derive(Clone, Default)]
+pub(crate) struct PeerStoreSeeding {
+    store: Option<Arc<dyn PeerStore>>,
+    max_peers: usize,
+    max_age: Duration,
+}
+
+impl PeerStoreSeeding {
+    pub(crate) fn new(store: impl PeerStore, max_peers: usize, max_age: Duration) -> Self {
+        Self {
+            store: Some(Arc::new(store)),
+            max_peers,
+            max_age,
+        }
+    }
+
+    /// Returns the at most `max_peers` most recently seen peers of the store that have
+    /// been seen within `max_age` of `now`, a duration since the UNIX epoch.
+    pub(crate) fn seeds(&self, now: Duration) -> Vec<RoutingTableEntry> {
+        let Some(store) = &self.store else {
+            return Vec::new();
+        };
+        let mut peers = store
+            .kad_peers()
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .last_seen
+                    .is_some_and(|last_seen| now.saturating_sub(last_seen) <= self.max_age)
+            })
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|entry| std::cmp::Reverse(entry.last_seen));
+        peers.truncate(self.max_peers);
+        peers
+    }
+
+    pub(crate) fn on_peer_seen(&self, entry: impl FnOnce() -> RoutingTableEntry) {
+        if let Some(store) = &self.store {
+            store.on_peer_seen(entry());
+        }
+    }
+}
+
+impl fmt::Debug for PeerStoreSeeding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerStoreSeeding")
+            .field("enabled", &self.store.is_some())
+            .field("max_peers", &self.max_peers)
+            .field("max_age", &self.max_age)
+            .finish()
+    }
+}
@@ -27,15 +27,17 @@
 //! is used to send messages to remote peers.
 
 use crate::proto;
+use crate::provider_summary::{KeyspaceRange, ProviderSummary};
 use crate::record::{self, Record};
 use asynchronous_codec::{Decoder, Encoder, Framed};
 use bytes::BytesMut;
 use futures::prelude::*;
 use instant::Instant;
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
-use libp2p_core::Multiaddr;
+use libp2p_core::{Multiaddr, PeerRecord, SignedEnvelope};
 use libp2p_identity::PeerId;
 use libp2p_swarm::StreamProtocol;
+use quick_protobuf::{sizeofs::sizeof_len, MessageWrite};
 use std::marker::PhantomData;
 use std::time::Duration;
 use std::{io, iter};
@@ -91,6 +93,16 @@ pub struct KadPeer {
     pub multiaddrs: Vec<Multiaddr>,
     /// How the sender is connected to that remote.
     pub connection_ty: ConnectionType,
+    /// The signed peer record of the peer, if known to the sender, authenticating
+    /// the addresses of the peer.
+    pub signed_record: Option<Box<PeerRecord>>,
+}
+
+impl KadPeer {
+    /// The size of the peer when encoded as part of a message, in bytes.
+    pub(crate) fn encoded_len(&self) -> usize {
+        1 + sizeof_len(proto::Peer::from(self.clone()).get_size())
+    }
 }
 
 // Builds a `KadPeer` from a corresponding protobuf message.
@@ -113,10 +125,34 @@ impl TryFrom<proto::Peer> for KadPeer {
             };
         }
 
+        let signed_record = if peer.signedRecord.is_empty() {
+            None
+        } else {
+            match SignedEnvelope::from_protobuf_encoding(&peer.signedRecord)
+                .map_err(|e| e.to_string())
+                .and_then(|envelope| {
+                    PeerRecord::from_signed_envelope(envelope).map_err(|e| e.to_string())
+                }) {
+                Ok(record) if record.peer_id() == node_id => Some(Box::new(record)),
+                Ok(record) => {
+                    debug!(
+                        "Ignoring signed peer record of {} for {node_id}",
+                        record.peer_id()
+                    );
+                    None
+                }
+                Err(e) => {
+                    debug!("Ignoring invalid signed peer record of {node_id}: {e}");
+                    None
+                }
+            }
+        };
+
         Ok(KadPeer {
             node_id,
             multiaddrs: addrs,
             connection_ty: peer.connection.into(),
+            signed_record,
         })
     }
 }
@@ -127,6 +163,10 @@ impl From<KadPeer> for proto::Peer {
             id: peer.node_id.to_bytes(),
             addrs: peer.multiaddrs.into_iter().map(|a| a.to_vec()).collect(),
             connection: peer.connection_ty.into(),
+            signedRecord: peer
+                .signed_record
+                .map(|record| (*record).into_signed_envelope().into_protobuf_encoding())
+                .unwrap_or_default(),
         }
     }
 }
@@ -171,10 +211,22 @@ impl ProtocolConfig {
         self.protocol_names = names;
     }
 
+    /// Adds a protocol name, proposed after the already configured ones.
+    pub(crate) fn add_protocol_name(&mut self, name: StreamProtocol) {
+        if !self.protocol_names.contains(&name) {
+            self.protocol_names.push(name);
+        }
+    }
+
     /// Modifies the maximum allowed size of a single Kademlia packet.
     pub fn set_max_packet_size(&mut self, size: usize) {
         self.max_packet_size = size;
     }
+
+    /// Returns the maximum allowed size of a single Kademlia packet.
+    pub(crate) fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
 }
 
 impl Default for ProtocolConfig {
@@ -238,14 +290,14 @@ impl<C> InboundUpgrade<C> for ProtocolConfig
 where
     C: AsyncRead + AsyncWrite + Unpin,
 {
-    type Output = KadInStreamSink<C>;
+    type Output = (KadInStreamSink<C>, StreamProtocol);
     type Future = future::Ready<Result<Self::Output, io::Error>>;
     type Error = io::Error;
 
-    fn upgrade_inbound(self, incoming: C, _: Self::Info) -> Self::Future {
+    fn upgrade_inbound(self, incoming: C, protocol: Self::Info) -> Self::Future {
         let codec = Codec::new(self.max_packet_size);
 
-        future::ok(Framed::new(incoming, codec))
+        future::ok((Framed::new(incoming, codec), protocol))
     }
 }
 
@@ -253,14 +305,14 @@ impl<C> OutboundUpgrade<C> for ProtocolConfig
 where
     C: AsyncRead + AsyncWrite + Unpin,
 {
-    type Output = KadOutStreamSink<C>;
+    type Output = (KadOutStreamSink<C>, StreamProtocol);
     type Future = future::Ready<Result<Self::Output, io::Error>>;
     type Error = io::Error;
 
-    fn upgrade_outbound(self, incoming: C, _: Self::Info) -> Self::Future {
+    fn upgrade_outbound(self, incoming: C, protocol: Self::Info) -> Self::Future {
         let codec = Codec::new(self.max_packet_size);
 
-        future::ok(Framed::new(incoming, codec))
+        future::ok((Framed::new(incoming, codec), protocol))
     }
 }
 
@@ -282,6 +334,9 @@ pub enum KadRequestMsg {
     GetProviders {
         /// Identifier being searched.
         key: record::Key,
+        /// The index of the first provider to return, if the sender
+        /// accepts the providers in pages.
+        provider_offset: Option<u32>,
     },
 
     /// Indicates that this list of providers is known for this key.
@@ -290,6 +345,8 @@ pub enum KadRequestMsg {
         key: record::Key,
         /// Known provider for this key.
         provider: KadPeer,
+        /// The expiration of the provider record, if announced by the provider.
+        expires: Option<Instant>,
     },
 
     /// Request to get a value from the dht records.
@@ -300,6 +357,15 @@ pub enum KadRequestMsg {
 
     /// Request to put a value into the dht records.
     PutValue { record: Record },
+
+    /// Request for a summary of the keys provided within a range of the keyspace.
+    ///
+    /// Encoded as a `GET_PROVIDERS` request, such that nodes not supporting
+    /// summaries answer with a regular `GET_PROVIDERS` response.
+    GetProviderSummary {
+        /// The range of the keyspace to summarise.
+        range: KeyspaceRange,
+    },
 }
 
 /// Response that we can send to a peer or that we received from a peer.
@@ -320,6 +386,8 @@ pub enum KadResponseMsg {
         closer_peers: Vec<KadPeer>,
         /// Known providers for this key.
         provider_peers: Vec<KadPeer>,
+        /// The index of the first provider of the next page, if any.
+        next_provider_offset: Option<u32>,
     },
 
     /// Response to a `GetValue`.
@@ -337,6 +405,12 @@ pub enum KadResponseMsg {
         /// Value of the record.
         value: Vec<u8>,
     },
+
+    /// Response to a `GetProviderSummary`.
+    GetProviderSummary {
+        /// The summary of the provided keys.
+        summary: ProviderSummary,
+    },
 }
 
 impl From<KadRequestMsg> for proto::Message {
@@ -377,17 +451,26 @@ fn req_msg_to_proto(kad_msg: KadRequestMsg) -> proto::Message {
             clusterLevelRaw: 10,
             ..proto::Message::default()
         },
-        KadRequestMsg::GetProviders { key } => proto::Message {
+        KadRequestMsg::GetProviders {
+            key,
+            provider_offset,
+        } => proto::Message {
             type_pb: proto::MessageType::GET_PROVIDERS,
             key: key.to_vec(),
             clusterLevelRaw: 10,
+            providerOffset: provider_offset,
             ..proto::Message::default()
         },
-        KadRequestMsg::AddProvider { key, provider } => proto::Message {
+        KadRequestMsg::AddProvider {
+            key,
+            provider,
+            expires,
+        } => proto::Message {
             type_pb: proto::MessageType::ADD_PROVIDER,
             clusterLevelRaw: 10,
             key: key.to_vec(),
             providerPeers: vec![provider.into()],
+            providerTtl: expires.map(ttl_to_proto).unwrap_or(0),
             ..proto::Message::default()
         },
         KadRequestMsg::GetValue { key } => proto::Message {
@@ -402,6 +485,12 @@ fn req_msg_to_proto(kad_msg: KadRequestMsg) -> proto::Message {
             record: Some(record_to_proto(record)),
             ..proto::Message::default()
         },
+        KadRequestMsg::GetProviderSummary { range } => proto::Message {
+            type_pb: proto::MessageType::GET_PROVIDERS,
+            clusterLevelRaw: 10,
+            providerSummary: Some(range.to_proto()),
+            ..proto::Message::default()
+        },
     }
 }
 
@@ -421,11 +510,13 @@ fn resp_msg_to_proto(kad_msg: KadResponseMsg) -> proto::Message {
         KadResponseMsg::GetProviders {
             closer_peers,
             provider_peers,
+            next_provider_offset,
         } => proto::Message {
             type_pb: proto::MessageType::GET_PROVIDERS,
             clusterLevelRaw: 9,
             closerPeers: closer_peers.into_iter().map(KadPeer::into).collect(),
             providerPeers: provider_peers.into_iter().map(KadPeer::into).collect(),
+            providerOffset: next_provider_offset,
             ..proto::Message::default()
         },
         KadResponseMsg::GetValue {
@@ -448,6 +539,12 @@ fn resp_msg_to_proto(kad_msg: KadResponseMsg) -> proto::Message {
             }),
             ..proto::Message::default()
         },
+        KadResponseMsg::GetProviderSummary { summary } => proto::Message {
+            type_pb: proto::MessageType::GET_PROVIDERS,
+            clusterLevelRaw: 9,
+            providerSummary: Some(summary.to_proto()),
+            ..proto::Message::default()
+        },
     }
 }
 
@@ -465,9 +562,15 @@ fn proto_to_req_msg(message: proto::Message) -> Result<KadRequestMsg, io::Error>
             key: record::Key::from(message.key),
         }),
         proto::MessageType::FIND_NODE => Ok(KadRequestMsg::FindNode { key: message.key }),
-        proto::MessageType::GET_PROVIDERS => Ok(KadRequestMsg::GetProviders {
-            key: record::Key::from(message.key),
-        }),
+        proto::MessageType::GET_PROVIDERS => match message.providerSummary {
+            Some(summary) => Ok(KadRequestMsg::GetProviderSummary {
+                range: KeyspaceRange::from_proto(&summary)?,
+            }),
+            None => Ok(KadRequestMsg::GetProviders {
+                key: record::Key::from(message.key),
+                provider_offset: message.providerOffset,
+            }),
+        },
         proto::MessageType::ADD_PROVIDER => {
             // TODO: for now we don't parse the peer properly, so it is possible that we get
             //       parsing errors for peers even when they are valid; we ignore these
@@ -479,7 +582,12 @@ fn proto_to_req_msg(message: proto::Message) -> Result<KadRequestMsg, io::Error>
 
             if let Some(provider) = provider {
                 let key = record::Key::from(message.key);
-                Ok(KadRequestMsg::AddProvider { key, provider })
+                let expires = ttl_from_proto(message.providerTtl);
+                Ok(KadRequestMsg::AddProvider {
+                    key,
+                    provider,
+                    expires,
+                })
             } else {
                 Err(invalid_data("AddProvider message with no valid peer."))
             }
@@ -523,6 +631,12 @@ fn proto_to_resp_msg(message: proto::Message) -> Result<KadResponseMsg, io::Erro
         }
 
         proto::MessageType::GET_PROVIDERS => {
+            if let Some(summary) = message.providerSummary {
+                return Ok(KadResponseMsg::GetProviderSummary {
+                    summary: ProviderSummary::from_proto(summary)?,
+                });
+            }
+
             let closer_peers = message
                 .closerPeers
                 .into_iter()
@@ -538,6 +652,7 @@ fn proto_to_resp_msg(message: proto::Message) -> Result<KadResponseMsg, io::Erro
             Ok(KadResponseMsg::GetProviders {
                 closer_peers,
                 provider_peers,
+                next_provider_offset: message.providerOffset,
             })
         }
 
@@ -571,37 +686,49 @@ fn record_from_proto(record: proto::Record) -> Result<Record, io::Error> {
         None
     };
 
-    let expires = if record.ttl > 0 {
-        Some(Instant::now() + Duration::from_secs(record.ttl as u64))
-    } else {
-        None
-    };
+    let expires = ttl_from_proto(record.ttl);
 
     Ok(Record {
         key,
         value,
         publisher,
         expires,
+        sequence: record.sequence,
     })
 }
 
+/// The size of a record when encoded as part of a message, in bytes.
+pub(crate) fn record_encoded_len(record: &Record) -> usize {
+    1 + sizeof_len(record_to_proto(record.clone()).get_size())
+}
+
 fn record_to_proto(record: Record) -> proto::Record {
     proto::Record {
         key: record.key.to_vec(),
         value: record.value,
         publisher: record.publisher.map(|id| id.to_bytes()).unwrap_or_default(),
-        ttl: record
-            .expires
-            .map(|t| {
-                let now = Instant::now();
-                if t > now {
-                    (t - now).as_secs() as u32
-                } else {
-                    1 // because 0 means "does not expire"
-                }
-            })
-            .unwrap_or(0),
+        ttl: record.expires.map(ttl_to_proto).unwrap_or(0),
         timeReceived: String::new(),
+        sequence: record.sequence,
+    }
+}
+
+/// Converts an expiration to a TTL in seconds, where 0 means "does not expire".
+fn ttl_to_proto(expires: Instant) -> u32 {
+    let now = Instant::now();
+    if expires > now {
+        (expires - now).as_secs() as u32
+    } else {
+        1 // because 0 means "does not expire"
+    }
+}
+
+/// Converts a TTL in seconds, where 0 means "does not expire", to an expiration.
+fn ttl_from_proto(ttl: u32) -> Option<Instant> {
+    if ttl > 0 {
+        Some(Instant::now() + Duration::from_secs(ttl as u64))
+    } else {
+        None
     }
 }
 
@@ -626,6 +753,7 @@ mod tests {
             id: peer_id.to_bytes(),
             addrs: vec![multiaddr.to_vec()],
             connection: proto::ConnectionType::CAN_CONNECT,
+            signedRecord: Vec::new(),
         };
 
         let peer = KadPeer::try_from(payload).unwrap();
@@ -660,6 +788,7 @@ mod tests {
                 invalid_multiaddr,
             ],
             connection: proto::ConnectionType::CAN_CONNECT,
+            signedRecord: Vec::new(),
         };
 
         let peer = KadPeer::try_from(payload).unwrap();
@@ -667,6 +796,78 @@ mod tests {
         assert_eq!(peer.multiaddrs, vec![valid_multiaddr])
     }
 
+    #[test]
+    fn signed_record_of_peer_is_carried() {
+        let key = libp2p_identity::Keypair::generate_ed25519();
+        let record = PeerRecord::new(&key, vec!["/ip4/1.2.3.4/tcp/4001".parse().unwrap()]).unwrap();
+        let peer = |node_id| KadPeer {
+            node_id,
+            multiaddrs: Vec::new(),
+            connection_ty: ConnectionType::Connected,
+            signed_record: Some(Box::new(record.clone())),
+        };
+
+        let signed = peer(key.public().to_peer_id());
+        assert_eq!(
+            KadPeer::try_from(proto::Peer::from(signed.clone())).unwrap(),
+            signed
+        );
+
+        // A record of another peer is ignored.
+        let other = KadPeer::try_from(proto::Peer::from(peer(PeerId::random()))).unwrap();
+        assert!(other.signed_record.is_none());
+    }
+
+    #[test]
+    fn add_provider_carries_expiration() {
+        let request = KadRequestMsg::AddProvider {
+            key: record::Key::new(&vec![1, 2, 3]),
+            provider: KadPeer {
+                node_id: PeerId::random(),
+                multiaddrs: Vec::new(),
+                connection_ty: ConnectionType::Connected,
+                signed_record: None,
+            },
+            expires: Some(Instant::now() + Duration::from_secs(60)),
+        };
+        let message = req_msg_to_proto(request);
+        assert!(message.providerTtl > 0 && message.providerTtl <= 60);
+
+        let KadRequestMsg::AddProvider { expires, .. } = proto_to_req_msg(message).unwrap() else {
+            panic!("Unexpected request");
+        };
+        assert!(expires.unwrap() <= Instant::now() + Duration::from_secs(60));
+    }
+
+    #[test]
+    fn get_providers_carries_provider_offset() {
+        let request = KadRequestMsg::GetProviders {
+            key: record::Key::new(&vec![1, 2, 3]),
+            provider_offset: Some(20),
+        };
+        let KadRequestMsg::GetProviders {
+            provider_offset, ..
+        } = proto_to_req_msg(req_msg_to_proto(request)).unwrap()
+        else {
+            panic!("Unexpected request");
+        };
+        assert_eq!(provider_offset, Some(20));
+
+        let response = KadResponseMsg::GetProviders {
+            closer_peers: Vec::new(),
+            provider_peers: Vec::new(),
+            next_provider_offset: Some(40),
+        };
+        let KadResponseMsg::GetProviders {
+            next_provider_offset,
+            ..
+        } = proto_to_resp_msg(resp_msg_to_proto(response)).unwrap()
+        else {
+            panic!("Unexpected response");
+        };
+        assert_eq!(next_provider_offset, Some(40));
+    }
+
     /*// TODO: restore
     use self::libp2p_tcp::TcpTransport;
     use self::tokio::runtime::current_thread::Runtime;
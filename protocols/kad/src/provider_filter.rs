@@ -0,0 +1,103 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Filtering of the providers disclosed to remote peers and reported locally.
+//!
+//! Applications set a [`ProviderFilter`] via
+//! [`Config::set_provider_filter`](crate::Config::set_provider_filter), e.g. to redact
+//! providers known to be stale, blocked or failing a custom policy.
+
+use crate::record;
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use std::fmt;
+use std::sync::Arc;
+
+/// Decides whether a provider of a key is returned to a remote peer or reported locally.
+pub trait ProviderFilter: Send + Sync + 'static {
+    /// Returns whether the provider with the given addresses is kept.
+    fn retain(
+        &self,
+        key: &record::Key,
+        provider: &PeerId,
+        addresses: &[Multiaddr],
+        target: ProvidersFor,
+    ) -> bool;
+}
+
+impl<F> ProviderFilter for F
+where
+    F: Fn(&record::Key, &PeerId, &[Multiaddr], ProvidersFor) -> bool + Send + Sync + 'static,
+{
+    fn retain(
+        &self,
+        key: &record::Key,
+        provider: &PeerId,
+        addresses: &[Multiaddr],
+        target: ProvidersFor,
+    ) -> bool {
+        self(key, provider, addresses, target)
+    }
+}
+
+/// Where the providers subject to a [`ProviderFilter`] are about to go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProvidersFor {
+    /// The response to a `GET_PROVIDERS` request of the given peer.
+    Remote { requester: PeerId },
+    /// A [`GetProvidersOk::FoundProviders`](crate::GetProvidersOk::FoundProviders) result,
+    /// received from the given peer or from the local store if `None`.
+    Local { source: Option<PeerId> },
+}
+
+/// The [`ProviderFilter`] set in the [`Config`](crate::Config), if any.
+#[derive(Clone, Default)]
+pub(crate) struct ProviderFiltering {
+    filter: Option<Arc<dyn ProviderFilter>>,
+}
+
+impl ProviderFiltering {
+    pub(crate) fn new(filter: impl ProviderFilter) -> Self {
+        Self {
+            filter: Some(Arc::new(filter)),
+        }
+    }
+
+    /// Returns whether the provider is kept, i.e. `true` if there is no [`ProviderFilter`].
+    pub(crate) fn retain(
+        &self,
+        key: &record::Key,
+        provider: &PeerId,
+        addresses: &[Multiaddr],
+        target: ProvidersFor,
+    ) -> bool {
+        self.filter.as_ref().map_or(true, |filter| {
+            filter.retain(key, provider, addresses, target)
+        })
+    }
+}
+
+impl fmt::Debug for ProviderFiltering {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProviderFiltering")
+            .field("enabled", &self.filter.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Bloom filter summaries of the keys provided by a node.
+//!
+//! A [`ProviderSummary`] allows a peer to test which keys of a [`KeyspaceRange`] a remote
+//! node stores provider records for, without issuing a `GET_PROVIDERS` request per key.
+//! The summary may yield false positives, but never false negatives.
+
+use crate::kbucket;
+use crate::proto;
+use crate::record;
+use std::io;
+
+/// Number of bits in the Kademlia keyspace.
+const KEYSPACE_BITS: usize = 256;
+
+/// Number of filter bits allotted per key, yielding a false positive rate of about 1%.
+const BITS_PER_KEY: usize = 10;
+
+/// Maximum number of hash functions accepted from a remote.
+const MAX_NUM_HASHES: u32 = 32;
+
+/// Maximum size of a filter, in bytes.
+///
+/// Keeps a summary response well below the default maximum packet size.
+pub(crate) const MAX_FILTER_BYTES: usize = 8 * 1024;
+
+/// A range of the Kademlia keyspace, i.e. all keys whose SHA-256 hash starts with a
+/// common prefix.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyspaceRange {
+    /// The prefix, with all bits beyond `prefix_len` set to zero.
+    prefix: [u8; 32],
+    prefix_len: usize,
+}
+
+impl KeyspaceRange {
+    /// The range covering the entire keyspace.
+    pub fn full() -> Self {
+        KeyspaceRange {
+            prefix: [0; 32],
+            prefix_len: 0,
+        }
+    }
+
+    /// The range of all keys sharing the first `prefix_len` bits with `key`.
+    ///
+    /// `prefix_len` is capped at 256.
+    pub fn new<T>(key: &kbucket::Key<T>, prefix_len: usize) -> Self {
+        let mut prefix = [0; 32];
+        prefix.copy_from_slice(key.hashed_bytes());
+        Self::from_prefix(prefix, prefix_len)
+    }
+
+    fn from_prefix(mut prefix: [u8; 32], prefix_len: usize) -> Self {
+        let prefix_len = prefix_len.min(KEYSPACE_BITS);
+        for (i, byte) in prefix.iter_mut().enumerate() {
+            let bits = prefix_len.saturating_sub(i * 8).min(8);
+            *byte &= !(0xffu8.checked_shr(bits as u32).unwrap_or(0));
+        }
+        KeyspaceRange { prefix, prefix_len }
+    }
+
+    /// The number of leading bits shared by all keys in the range.
+    pub fn prefix_len(&self) -> usize {
+        self.prefix_len
+    }
+
+    /// Checks whether the given key falls into the range.
+    pub fn contains<T>(&self, key: &kbucket::Key<T>) -> bool {
+        let hash = key.hashed_bytes();
+        (0..self.prefix_len).all(|i| bit(hash, i) == bit(&self.prefix, i))
+    }
+
+    /// Splits the range into its two halves.
+    ///
+    /// Returns `None` if the range consists of a single key hash.
+    pub fn split(&self) -> Option<(Self, Self)> {
+        if self.prefix_len == KEYSPACE_BITS {
+            return None;
+        }
+        let lower = KeyspaceRange {
+            prefix: self.prefix,
+            prefix_len: self.prefix_len + 1,
+        };
+        let mut upper = lower.clone();
+        upper.prefix[self.prefix_len / 8] |= 0x80 >> (self.prefix_len % 8);
+
+        Some((lower, upper))
+    }
+
+    pub(crate) fn to_proto(&self) -> proto::ProviderSummary {
+        proto::ProviderSummary {
+            prefix: self.prefix[..self.prefix_len.div_ceil(8)].to_vec(),
+            prefixBits: self.prefix_len as u32,
+            ..proto::ProviderSummary::default()
+        }
+    }
+
+    pub(crate) fn from_proto(summary: &proto::ProviderSummary) -> Result<Self, io::Error> {
+        let prefix_len = summary.prefixBits as usize;
+        if prefix_len > KEYSPACE_BITS || summary.prefix.len() < prefix_len.div_ceil(8) {
+            return Err(invalid_data("invalid keyspace range"));
+        }
+        let mut prefix = [0; 32];
+        let n = summary.prefix.len().min(32);
+        prefix[..n].copy_from_slice(&summary.prefix[..n]);
+
+        Ok(Self::from_prefix(prefix, prefix_len))
+    }
+}
+
+/// A bloom filter over the keys a node provides within a [`KeyspaceRange`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderSummary {
+    range: KeyspaceRange,
+    filter: Vec<u8>,
+    num_hashes: u32,
+}
+
+impl ProviderSummary {
+    /// Builds a summary of the given keys that fall into `range`.
+    pub(crate) fn new<'a, I>(range: KeyspaceRange, keys: I) -> Self
+    where
+        I: IntoIterator<Item = &'a record::Key>,
+    {
+        let hashes = keys
+            .into_iter()
+            .map(|k| kbucket::Key::new(k.clone()))
+            .filter(|k| range.contains(k))
+            .map(|k| hash_pair(k.hashed_bytes()))
+            .collect::<Vec<_>>();
+
+        let num_bytes = (hashes.len() * BITS_PER_KEY)
+            .div_ceil(8)
+            .clamp(8, MAX_FILTER_BYTES);
+        let num_bits = num_bytes as u64 * 8;
+        // The optimal number of hash functions is `m / n * ln 2`.
+        let num_hashes = if hashes.is_empty() {
+            1
+        } else {
+            ((num_bits as f64 / hashes.len() as f64) * std::f64::consts::LN_2).round() as u32
+        }
+        .clamp(1, MAX_NUM_HASHES);
+
+        let mut filter = vec![0; num_bytes];
+        for (h1, h2) in hashes {
+            for i in 0..num_hashes {
+                let pos = h1.wrapping_add(u64::from(i).wrapping_mul(h2)) % num_bits;
+                filter[(pos / 8) as usize] |= 1 << (pos % 8);
+            }
+        }
+
+        ProviderSummary {
+            range,
+            filter,
+            num_hashes,
+        }
+    }
+
+    /// The range of the keyspace covered by the summary.
+    pub fn range(&self) -> &KeyspaceRange {
+        &self.range
+    }
+
+    /// Checks whether the summarised node may provide the given key.
+    ///
+    /// Returns `false` if the node definitely does not store a provider record
+    /// for the key or if the key is outside of [`ProviderSummary::range`].
+    pub fn may_provide(&self, key: &record::Key) -> bool {
+        let key = kbucket::Key::new(key.clone());
+        if !self.range.contains(&key) {
+            return false;
+        }
+        let (h1, h2) = hash_pair(key.hashed_bytes());
+        let num_bits = self.filter.len() as u64 * 8;
+
+        (0..self.num_hashes).all(|i| {
+            let pos = h1.wrapping_add(u64::from(i).wrapping_mul(h2)) % num_bits;
+            self.filter[(pos / 8) as usize] & (1 << (pos % 8)) != 0
+        })
+    }
+
+    /// The size of the filter in bytes.
+    pub fn filter_len(&self) -> usize {
+        self.filter.len()
+    }
+
+    pub(crate) fn to_proto(&self) -> proto::ProviderSummary {
+        proto::ProviderSummary {
+            filter: self.filter.clone(),
+            numHashes: self.num_hashes,
+            ..self.range.to_proto()
+        }
+    }
+
+    pub(crate) fn from_proto(summary: proto::ProviderSummary) -> Result<Self, io::Error> {
+        let range = KeyspaceRange::from_proto(&summary)?;
+        if summary.filter.is_empty() || summary.filter.len() > MAX_FILTER_BYTES {
+            return Err(invalid_data("invalid provider summary filter"));
+        }
+        if summary.numHashes == 0 || summary.numHashes > MAX_NUM_HASHES {
+            return Err(invalid_data("invalid number of provider summary hashes"));
+        }
+
+        Ok(ProviderSummary {
+            range,
+            filter: summary.filter,
+            num_hashes: summary.numHashes,
+        })
+    }
+}
+
+/// Returns the `i`-th most significant bit of `bytes`.
+fn bit(bytes: &[u8], i: usize) -> bool {
+    bytes[i / 8] & (0x80 >> (i % 8)) != 0
+}
+
+/// Derives the two hashes for double hashing from a key hash.
+///
+/// Keys within a range share a prefix, hence the hashes are taken from the end.
+fn hash_pair(hash: &[u8]) -> (u64, u64) {
+    let h1 = u64::from_be_bytes(hash[16..24].try_into().expect("32 byte hash"));
+    let h2 = u64::from_be_bytes(hash[24..32].try_into().expect("32 byte hash"));
+    (h1, h2 | 1)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::*;
+
+    fn random_keys(n: usize) -> Vec<record::Key> {
+        (0..n)
+            .map(|_| record::Key::new(&rand::random::<[u8; 32]>()))
+            .collect()
+    }
+
+    #[test]
+    fn range_contains_own_key() {
+        fn prop(key: record::Key, prefix_len: u16) -> bool {
+            let key = kbucket::Key::new(key);
+            KeyspaceRange::new(&key, prefix_len as usize).contains(&key)
+        }
+        quickcheck(prop as fn(_, _) -> _)
+    }
+
+    #[test]
+    fn split_partitions_range() {
+        fn prop(key: record::Key, prefix_len: u8) {
+            let key = kbucket::Key::new(key);
+            let range = KeyspaceRange::new(&key, prefix_len as usize);
+            let (lower, upper) = range.split().unwrap();
+
+            assert_eq!(lower.prefix_len(), range.prefix_len() + 1);
+            assert!(lower.contains(&key) ^ upper.contains(&key));
+        }
+        quickcheck(prop as fn(_, _))
+    }
+
+    #[test]
+    fn no_false_negatives() {
+        let keys = random_keys(1000);
+        let summary = ProviderSummary::new(KeyspaceRange::full(), &keys);
+
+        assert!(keys.iter().all(|k| summary.may_provide(k)));
+    }
+
+    #[test]
+    fn few_false_positives() {
+        let keys = random_keys(1000);
+        let summary = ProviderSummary::new(KeyspaceRange::full(), &keys);
+
+        let false_positives = random_keys(10_000)
+            .iter()
+            .filter(|k| summary.may_provide(k))
+            .count();
+        assert!(false_positives < 500, "{false_positives} false positives");
+    }
+
+    #[test]
+    fn keys_outside_of_range_are_excluded() {
+        let keys = random_keys(100);
+        let range = KeyspaceRange::new(&kbucket::Key::new(keys[0].clone()), 1);
+        let summary = ProviderSummary::new(range.clone(), &keys);
+
+        for key in &keys {
+            let in_range = range.contains(&kbucket::Key::new(key.clone()));
+            assert_eq!(summary.may_provide(key), in_range);
+        }
+    }
+
+    #[test]
+    fn proto_roundtrip() {
+        let keys = random_keys(50);
+        let range = KeyspaceRange::new(&kbucket::Key::new(keys[0].clone()), 3);
+        let summary = ProviderSummary::new(range.clone(), &keys);
+
+        assert_eq!(KeyspaceRange::from_proto(&range.to_proto()).unwrap(), range);
+        assert_eq!(
+            ProviderSummary::from_proto(summary.to_proto()).unwrap(),
+            summary
+        );
+    }
+}
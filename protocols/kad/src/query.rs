@@ -18,21 +18,32 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+mod latency;
 mod peers;
 
+pub(crate) use latency::PeerLatencies;
+
 use peers::closest::{
     disjoint::ClosestDisjointPeersIter, ClosestPeersIter, ClosestPeersIterConfig,
 };
 use peers::fixed::FixedPeersIter;
 use peers::PeersIterState;
 
-use crate::kbucket::{Key, KeyBytes};
+use crate::kbucket::{Key, KeyBytes, KeyHashing};
 use crate::{ALPHA_VALUE, K_VALUE};
 use either::Either;
 use fnv::FnvHashMap;
 use instant::Instant;
 use libp2p_identity::PeerId;
-use std::{num::NonZeroUsize, time::Duration};
+use std::{
+    cmp::Reverse,
+    num::{NonZeroU32, NonZeroUsize},
+    time::Duration,
+};
+
+/// The number of queries admitted ahead of a waiting query, after which
+/// the waiting query is admitted regardless of its priority.
+const MAX_ADMISSIONS_AHEAD: u64 = 16;
 
 /// A `QueryPool` provides an aggregate state machine for driving `Query`s to completion.
 ///
@@ -43,6 +54,10 @@ pub(crate) struct QueryPool<TInner> {
     next_id: usize,
     config: QueryConfig,
     queries: FnvHashMap<QueryId, Query<TInner>>,
+    /// The total number of queries admitted to run so far.
+    admissions: u64,
+    /// The round-trip times of the peers contacted by the queries.
+    latencies: PeerLatencies,
 }
 
 /// The observable states emitted by [`QueryPool::poll`].
@@ -65,6 +80,8 @@ impl<TInner> QueryPool<TInner> {
             next_id: 0,
             config,
             queries: Default::default(),
+            admissions: 0,
+            latencies: Default::default(),
         }
     }
 
@@ -73,6 +90,11 @@ impl<TInner> QueryPool<TInner> {
         &self.config
     }
 
+    /// Returns the round-trip time observed for the given peer, if any.
+    pub(crate) fn latency(&self, peer: &PeerId) -> Option<Duration> {
+        self.latencies.get(peer)
+    }
+
     /// Returns an iterator over the queries in the pool.
     pub(crate) fn iter(&self) -> impl Iterator<Item = &Query<TInner>> {
         self.queries.values()
@@ -89,70 +111,170 @@ impl<TInner> QueryPool<TInner> {
     }
 
     /// Adds a query to the pool that contacts a fixed set of peers.
-    pub(crate) fn add_fixed<I>(&mut self, peers: I, inner: TInner) -> QueryId
+    pub(crate) fn add_fixed<I>(
+        &mut self,
+        peers: I,
+        inner: TInner,
+        priority: QueryPriority,
+    ) -> QueryId
     where
         I: IntoIterator<Item = PeerId>,
     {
-        let id = self.next_query_id();
-        self.continue_fixed(id, peers, inner);
+        let id = self.reserve_id();
+        let peer_iter = self.fixed_iter(peers);
+        self.insert(Query::new(id, peer_iter, inner, priority), false);
         id
     }
 
     /// Continues an earlier query with a fixed set of peers, reusing
     /// the given query ID, which must be from a query that finished
     /// earlier.
-    pub(crate) fn continue_fixed<I>(&mut self, id: QueryId, peers: I, inner: TInner)
-    where
+    ///
+    /// The continued query runs right away, as the query it continues did.
+    pub(crate) fn continue_fixed<I>(
+        &mut self,
+        id: QueryId,
+        peers: I,
+        inner: TInner,
+        priority: QueryPriority,
+    ) where
         I: IntoIterator<Item = PeerId>,
     {
-        assert!(!self.queries.contains_key(&id));
-        let parallelism = self.config.replication_factor;
-        let peer_iter = QueryPeerIter::Fixed(FixedPeersIter::new(peers, parallelism));
-        let query = Query::new(id, peer_iter, inner);
-        self.queries.insert(id, query);
+        let peer_iter = self.fixed_iter(peers);
+        self.insert(Query::new(id, peer_iter, inner, priority), true);
     }
 
     /// Adds a query to the pool that iterates towards the closest peers to the target.
-    pub(crate) fn add_iter_closest<T, I>(&mut self, target: T, peers: I, inner: TInner) -> QueryId
+    pub(crate) fn add_iter_closest<T, I>(
+        &mut self,
+        target: T,
+        peers: I,
+        inner: TInner,
+        priority: QueryPriority,
+    ) -> QueryId
     where
         T: Into<KeyBytes> + Clone,
         I: IntoIterator<Item = Key<PeerId>>,
     {
-        let id = self.next_query_id();
-        self.continue_iter_closest(id, target, peers, inner);
+        let id = self.reserve_id();
+        self.add_iter_closest_with_id(id, target, peers, inner, priority);
         id
     }
 
-    /// Adds a query to the pool that iterates towards the closest peers to the target.
+    /// Adds a query to the pool that iterates towards the closest peers to the target,
+    /// using the given query ID, which must have been obtained from [`QueryPool::reserve_id`].
+    pub(crate) fn add_iter_closest_with_id<T, I>(
+        &mut self,
+        id: QueryId,
+        target: T,
+        peers: I,
+        inner: TInner,
+        priority: QueryPriority,
+    ) where
+        T: Into<KeyBytes> + Clone,
+        I: IntoIterator<Item = Key<PeerId>>,
+    {
+        let peer_iter = self.closest_iter(target, peers);
+        self.insert(Query::new(id, peer_iter, inner, priority), false);
+    }
+
+    /// Continues an earlier query that iterates towards the closest peers to
+    /// the target, reusing the given query ID, which must be from a query that
+    /// finished earlier.
+    ///
+    /// The continued query runs right away, as the query it continues did.
     pub(crate) fn continue_iter_closest<T, I>(
         &mut self,
         id: QueryId,
         target: T,
         peers: I,
         inner: TInner,
+        priority: QueryPriority,
     ) where
         T: Into<KeyBytes> + Clone,
         I: IntoIterator<Item = Key<PeerId>>,
+    {
+        let peer_iter = self.closest_iter(target, peers);
+        self.insert(Query::new(id, peer_iter, inner, priority), true);
+    }
+
+    fn fixed_iter<I>(&self, peers: I) -> QueryPeerIter
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        let parallelism = self.config.replication_factor;
+        QueryPeerIter::Fixed(FixedPeersIter::new(peers, parallelism))
+    }
+
+    fn closest_iter<T, I>(&self, target: T, peers: I) -> QueryPeerIter
+    where
+        T: Into<KeyBytes> + Clone,
+        I: IntoIterator<Item = Key<PeerId>>,
     {
         let cfg = ClosestPeersIterConfig {
             num_results: self.config.replication_factor,
             parallelism: self.config.parallelism,
+            adaptive_parallelism: self.config.adaptive_parallelism,
+            latency_weight: self.config.latency_weight,
+            hasher: self.config.hasher.clone(),
             ..ClosestPeersIterConfig::default()
         };
 
-        let peer_iter = if self.config.disjoint_query_paths {
+        if self.config.disjoint_query_paths {
             QueryPeerIter::ClosestDisjoint(ClosestDisjointPeersIter::with_config(
                 cfg, target, peers,
             ))
         } else {
             QueryPeerIter::Closest(ClosestPeersIter::with_config(cfg, target, peers))
-        };
+        }
+    }
 
-        let query = Query::new(id, peer_iter, inner);
-        self.queries.insert(id, query);
+    /// Inserts a query into the pool, either admitting it to run right away
+    /// or letting it wait for admission, see [`QueryPool::admit`].
+    fn insert(&mut self, mut query: Query<TInner>, admit: bool) {
+        assert!(!self.queries.contains_key(&query.id));
+        if admit {
+            query.admitted = true;
+            self.admissions += 1;
+        }
+        query.admissions_on_insert = self.admissions;
+        self.queries.insert(query.id, query);
     }
 
-    fn next_query_id(&mut self) -> QueryId {
+    /// Admits waiting queries to run, as long as the configured maximum
+    /// number of running queries is not reached.
+    ///
+    /// Queries of a higher priority are admitted first. To avoid starvation,
+    /// a query is admitted regardless of its priority once a number of queries
+    /// have been admitted ahead of it.
+    fn admit(&mut self) {
+        let mut running = self.queries.values().filter(|q| q.admitted).count();
+        loop {
+            if let Some(max) = self.config.max_running_queries {
+                if running >= max.get() {
+                    return;
+                }
+            }
+            let admissions = self.admissions;
+            let Some(query) = self
+                .queries
+                .values_mut()
+                .filter(|q| !q.admitted)
+                .max_by_key(|q| {
+                    let starved = admissions - q.admissions_on_insert >= MAX_ADMISSIONS_AHEAD;
+                    (starved, q.priority, Reverse(q.id.0))
+                })
+            else {
+                return;
+            };
+            query.admitted = true;
+            self.admissions += 1;
+            running += 1;
+        }
+    }
+
+    /// Reserves the ID of a query that is only added to the pool later.
+    pub(crate) fn reserve_id(&mut self) -> QueryId {
         let id = QueryId(self.next_id);
         self.next_id = self.next_id.wrapping_add(1);
         id
@@ -168,15 +290,40 @@ impl<TInner> QueryPool<TInner> {
         self.queries.get_mut(id)
     }
 
+    /// Removes a query with the given ID from the pool, if it is in the pool.
+    pub(crate) fn remove(&mut self, id: &QueryId) -> Option<Query<TInner>> {
+        self.queries.remove(id)
+    }
+
     /// Polls the pool to advance the queries.
+    ///
+    /// Running queries are advanced in the order of their priority.
     pub(crate) fn poll(&mut self, now: Instant) -> QueryPoolState<'_, TInner> {
+        self.admit();
+
+        for query in self.queries.values_mut() {
+            for (peer, rtt) in query.rtt_samples.drain(..) {
+                self.latencies.record(peer, rtt);
+            }
+        }
+
+        let mut running = self
+            .queries
+            .values()
+            .filter(|q| q.admitted)
+            .map(|q| (Reverse(q.priority), q.id.0))
+            .collect::<Vec<_>>();
+        running.sort_unstable();
+
         let mut finished = None;
         let mut timeout = None;
         let mut waiting = None;
 
-        for (&query_id, query) in self.queries.iter_mut() {
+        for (_, id) in running {
+            let query_id = QueryId(id);
+            let query = self.queries.get_mut(&query_id).expect("s.a.");
             query.stats.start = query.stats.start.or(Some(now));
-            match query.next(now) {
+            match query.next(now, &self.latencies) {
                 PeersIterState::Finished => {
                     finished = Some(query_id);
                     break;
@@ -188,7 +335,7 @@ impl<TInner> QueryPool<TInner> {
                 }
                 PeersIterState::Waiting(None) | PeersIterState::WaitingAtCapacity => {
                     let elapsed = now - query.stats.start.unwrap_or(now);
-                    if elapsed >= self.config.timeout {
+                    if elapsed >= query.timeout.unwrap_or(self.config.timeout) {
                         timeout = Some(query_id);
                         break;
                     }
@@ -246,10 +393,26 @@ pub(crate) struct QueryConfig {
     ///
     /// See [`crate::behaviour::Config::set_parallelism`] for details.
     pub(crate) parallelism: NonZeroUsize,
+    /// Bounds for adapting the parallelism of iterative queries, if any.
+    ///
+    /// See [`crate::behaviour::Config::set_adaptive_parallelism`] for details.
+    pub(crate) adaptive_parallelism: Option<AdaptiveParallelism>,
+    /// The weight of the round-trip time of peers when choosing the next peer to contact.
+    ///
+    /// See [`crate::behaviour::Config::set_latency_weight`] for details.
+    pub(crate) latency_weight: f64,
     /// Whether to use disjoint paths on iterative lookups.
     ///
     /// See [`crate::behaviour::Config::disjoint_query_paths`] for details.
     pub(crate) disjoint_query_paths: bool,
+    /// The maximum number of queries running at the same time.
+    ///
+    /// See [`crate::behaviour::Config::set_max_running_queries`] for details.
+    pub(crate) max_running_queries: Option<NonZeroUsize>,
+    /// The hashing of keys into the keyspace.
+    ///
+    /// See [`crate::behaviour::Config::set_key_hasher`] for details.
+    pub(crate) hasher: KeyHashing,
 }
 
 impl Default for QueryConfig {
@@ -258,19 +421,175 @@ impl Default for QueryConfig {
             timeout: Duration::from_secs(60),
             replication_factor: NonZeroUsize::new(K_VALUE.get()).expect("K_VALUE > 0"),
             parallelism: ALPHA_VALUE,
+            adaptive_parallelism: None,
+            latency_weight: 0.0,
             disjoint_query_paths: false,
+            max_running_queries: None,
+            hasher: KeyHashing::default(),
         }
     }
 }
 
+/// The priority of a query.
+///
+/// Queries of a higher priority are started before queries of a lower priority
+/// if the number of running queries is limited, see
+/// [`Config::set_max_running_queries`](crate::Config::set_max_running_queries),
+/// and get to contact peers first.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QueryPriority {
+    /// The priority of queries started by background jobs, i.e. bootstrapping
+    /// and the re-publication and replication of records.
+    Background,
+    /// The default priority of queries started by the user.
+    #[default]
+    Normal,
+    /// A priority above the default.
+    High,
+}
+
+/// Bounds for adapting the parallelism of iterative queries to the observed response times,
+/// see [`Config::set_adaptive_parallelism`](crate::Config::set_adaptive_parallelism).
+///
+/// A query starts with the configured parallelism, clamped to the bounds. Whenever the
+/// smoothed response time of the contacted peers exceeds `slow_response`, including when
+/// a peer does not respond in time, the query waits for one more peer in parallel. Whenever
+/// it falls below half of `slow_response`, the query waits for one peer less.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveParallelism {
+    /// The lowest parallelism of a query.
+    pub min: NonZeroUsize,
+    /// The highest parallelism of a query.
+    pub max: NonZeroUsize,
+    /// The response time above which the parallelism of a query is increased.
+    pub slow_response: Duration,
+}
+
+/// Options of a single query, overriding the [`Config`](crate::Config) for that query only.
+#[derive(Debug, Clone, Default)]
+pub struct QueryOpts {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) trace: bool,
+    pub(crate) agreement: Option<NonZeroUsize>,
+    pub(crate) retry: Option<RetryPolicy>,
+}
+
+impl QueryOpts {
+    /// Creates options that use the [`Config`](crate::Config) for everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the timeout of the query, overriding
+    /// [`Config::set_query_timeout`](crate::Config::set_query_timeout).
+    ///
+    /// A query timing out yields the same `Timeout` errors as with the global timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Enables the trace of the query, recording every request sent to a peer
+    /// as a [`QueryHop`] in the [`QueryStats`] of the query, see [`QueryStats::trace`].
+    ///
+    /// Disabled by default.
+    pub fn trace(mut self, enabled: bool) -> Self {
+        self.trace = enabled;
+        self
+    }
+
+    /// Finishes a query for a record as soon as the given number of peers
+    /// returned byte-identical values, reporting the value with
+    /// [`GetRecordOk::AgreementReached`](crate::GetRecordOk::AgreementReached).
+    ///
+    /// Only applies to [`Behaviour::get_record_with_opts`](crate::Behaviour::get_record_with_opts).
+    /// A record stored locally does not count towards the agreement.
+    ///
+    /// Disabled by default, i.e. the query runs until it either finishes or is
+    /// finished via [`QueryMut::finish`](crate::QueryMut::finish).
+    pub fn finish_on_agreement(mut self, peers: NonZeroUsize) -> Self {
+        self.agreement = Some(peers);
+        self
+    }
+
+    /// Sets the policy by which the query is retried when it times out, overriding
+    /// [`Config::set_query_retry_policy`](crate::Config::set_query_retry_policy).
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+}
+
+/// The policy by which a query that timed out is retried, with an exponential backoff.
+///
+/// Applies to queries for the closest peers, for records and for providers. A query
+/// for a record or providers is only retried if it did not find any yet. A retried
+/// query keeps its [`QueryId`] and the [`QueryStats`] reported cover the last attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: NonZeroU32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a policy running a query at most `max_attempts` times, waiting
+    /// `initial_backoff` before the first retry and doubling the backoff for
+    /// every further retry.
+    pub fn new(max_attempts: NonZeroU32, initial_backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff,
+            max_backoff: Duration::MAX,
+        }
+    }
+
+    /// Sets the maximum backoff between two attempts.
+    ///
+    /// Unbounded by default.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// The backoff before the given retry, starting at zero for the first retry,
+    /// or `None` if the query is not to be retried again.
+    pub(crate) fn backoff(&self, retry: u32) -> Option<Duration> {
+        if retry.saturating_add(1) >= self.max_attempts.get() {
+            return None;
+        }
+        let factor = 2u32.checked_pow(retry).unwrap_or(u32::MAX);
+        Some(
+            self.initial_backoff
+                .checked_mul(factor)
+                .unwrap_or(Duration::MAX)
+                .min(self.max_backoff),
+        )
+    }
+}
+
 /// A query in a `QueryPool`.
 pub(crate) struct Query<TInner> {
     /// The unique ID of the query.
     id: QueryId,
+    /// The priority of the query.
+    priority: QueryPriority,
+    /// Whether the query has been admitted to run.
+    admitted: bool,
+    /// The total number of queries admitted in the pool when the query was inserted.
+    admissions_on_insert: u64,
+    /// The timeout of the query, overriding [`QueryConfig::timeout`].
+    timeout: Option<Duration>,
     /// The peer iterator that drives the query state.
     peer_iter: QueryPeerIter,
     /// Execution statistics of the query.
     stats: QueryStats,
+    /// When the pending requests of the query were sent, per peer.
+    requests_sent: FnvHashMap<PeerId, Instant>,
+    /// The peers the query sent a request to, in the order they were contacted.
+    contacted: Vec<PeerId>,
+    /// The round-trip times of responses not yet recorded by the [`QueryPool`].
+    rtt_samples: Vec<(PeerId, Duration)>,
     /// The opaque inner query state.
     pub(crate) inner: TInner,
 }
@@ -284,12 +603,19 @@ enum QueryPeerIter {
 
 impl<TInner> Query<TInner> {
     /// Creates a new query without starting it.
-    fn new(id: QueryId, peer_iter: QueryPeerIter, inner: TInner) -> Self {
+    fn new(id: QueryId, peer_iter: QueryPeerIter, inner: TInner, priority: QueryPriority) -> Self {
         Query {
             id,
+            priority,
+            admitted: false,
+            admissions_on_insert: 0,
+            timeout: None,
             inner,
             peer_iter,
             stats: QueryStats::empty(),
+            requests_sent: Default::default(),
+            contacted: Vec::new(),
+            rtt_samples: Vec::new(),
         }
     }
 
@@ -303,16 +629,83 @@ impl<TInner> Query<TInner> {
         &self.stats
     }
 
-    /// Informs the query that the attempt to contact `peer` failed.
-    pub(crate) fn on_failure(&mut self, peer: &PeerId) {
+    /// Gets the priority of the query.
+    pub(crate) fn priority(&self) -> QueryPriority {
+        self.priority
+    }
+
+    /// Sets the priority of the query.
+    pub(crate) fn set_priority(&mut self, priority: QueryPriority) {
+        self.priority = priority
+    }
+
+    /// Sets the timeout of the query, overriding the timeout of the [`QueryPool`].
+    pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout
+    }
+
+    /// Enables or disables the trace of the query, see [`QueryStats::trace`].
+    pub(crate) fn set_trace(&mut self, enabled: bool) {
+        self.stats.trace = enabled.then(Vec::new);
+    }
+
+    /// Records a request of the given type sent to `peer` in the trace of the query, if enabled.
+    pub(crate) fn on_request(&mut self, peer: PeerId, rpc: QueryRpc) {
+        self.requests_sent.insert(peer, Instant::now());
+        if !self.contacted.contains(&peer) {
+            self.contacted.push(peer);
+        }
+        if let Some(trace) = self.stats.trace.as_mut() {
+            trace.push(QueryHop {
+                peer,
+                rpc,
+                sent: Instant::now(),
+                latency: None,
+                result: QueryHopResult::Pending,
+            });
+        }
+    }
+
+    /// Records the response of `peer` to the last request sent to it in the trace
+    /// of the query, if enabled.
+    fn on_response(&mut self, peer: &PeerId, result: QueryHopResult) {
+        let Some(trace) = self.stats.trace.as_mut() else {
+            return;
+        };
+        if let Some(hop) = trace
+            .iter_mut()
+            .rev()
+            .find(|hop| hop.peer == *peer && hop.result == QueryHopResult::Pending)
+        {
+            hop.latency = Some(hop.sent.elapsed());
+            hop.result = result;
+        }
+    }
+
+    /// The peers the query sent a request to and is still waiting on.
+    pub(crate) fn pending_peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.requests_sent.keys()
+    }
+
+    /// The peers the query sent a request to, in the order they were contacted.
+    pub(crate) fn contacted_peers(&self) -> &[PeerId] {
+        &self.contacted
+    }
+
+    /// Informs the query that the attempt to contact `peer` failed,
+    /// returning whether the query was waiting on `peer`.
+    pub(crate) fn on_failure(&mut self, peer: &PeerId) -> bool {
         let updated = match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.on_failure(peer),
             QueryPeerIter::ClosestDisjoint(iter) => iter.on_failure(peer),
             QueryPeerIter::Fixed(iter) => iter.on_failure(peer),
         };
         if updated {
+            self.requests_sent.remove(peer);
             self.stats.failure += 1;
+            self.on_response(peer, QueryHopResult::Failure);
         }
+        updated
     }
 
     /// Informs the query that the attempt to contact `peer` succeeded,
@@ -322,20 +715,27 @@ impl<TInner> Query<TInner> {
     where
         I: IntoIterator<Item = PeerId>,
     {
+        let new_peers: Vec<PeerId> = new_peers.into_iter().collect();
         let updated = match &mut self.peer_iter {
-            QueryPeerIter::Closest(iter) => iter.on_success(peer, new_peers),
-            QueryPeerIter::ClosestDisjoint(iter) => iter.on_success(peer, new_peers),
+            QueryPeerIter::Closest(iter) => iter.on_success(peer, new_peers.iter().copied()),
+            QueryPeerIter::ClosestDisjoint(iter) => {
+                iter.on_success(peer, new_peers.iter().copied())
+            }
             QueryPeerIter::Fixed(iter) => iter.on_success(peer),
         };
         if updated {
+            if let Some(sent) = self.requests_sent.remove(peer) {
+                self.rtt_samples.push((*peer, sent.elapsed()));
+            }
             self.stats.success += 1;
+            self.on_response(peer, QueryHopResult::Success { peers: new_peers });
         }
     }
 
     /// Advances the state of the underlying peer iterator.
-    fn next(&mut self, now: Instant) -> PeersIterState<'_> {
+    fn next(&mut self, now: Instant, latencies: &PeerLatencies) -> PeersIterState<'_> {
         let state = match &mut self.peer_iter {
-            QueryPeerIter::Closest(iter) => iter.next(now),
+            QueryPeerIter::Closest(iter) => iter.next_with_latencies(now, latencies),
             QueryPeerIter::ClosestDisjoint(iter) => iter.next(now),
             QueryPeerIter::Fixed(iter) => iter.next(),
         };
@@ -416,6 +816,7 @@ impl<TInner> Query<TInner> {
             peers,
             inner: self.inner,
             stats: self.stats,
+            priority: self.priority,
         }
     }
 }
@@ -428,6 +829,8 @@ pub(crate) struct QueryResult<TInner, TPeers> {
     pub(crate) peers: TPeers,
     /// The collected query statistics.
     pub(crate) stats: QueryStats,
+    /// The priority of the query.
+    pub(crate) priority: QueryPriority,
 }
 
 /// Execution statistics of a query.
@@ -438,6 +841,7 @@ pub struct QueryStats {
     failure: u32,
     start: Option<Instant>,
     end: Option<Instant>,
+    trace: Option<Vec<QueryHop>>,
 }
 
 impl QueryStats {
@@ -448,6 +852,7 @@ impl QueryStats {
             failure: 0,
             start: None,
             end: None,
+            trace: None,
         }
     }
 
@@ -474,6 +879,12 @@ impl QueryStats {
         self.requests - (self.success + self.failure)
     }
 
+    /// Gets the instant the query started, i.e. yielded the first peer to contact,
+    /// if it did yet.
+    pub fn start(&self) -> Option<Instant> {
+        self.start
+    }
+
     /// Gets the duration of the query.
     ///
     /// If the query has not yet finished, the duration is measured from the
@@ -493,12 +904,18 @@ impl QueryStats {
         }
     }
 
+    /// Gets the requests sent by the query so far, in the order they were sent,
+    /// if the trace of the query is enabled via [`QueryOpts::trace`].
+    pub fn trace(&self) -> Option<&[QueryHop]> {
+        self.trace.as_deref()
+    }
+
     /// Merges these stats with the given stats of another query,
     /// e.g. to accumulate statistics from a multi-phase query.
     ///
     /// Counters are merged cumulatively while the instants for
     /// start and end of the queries are taken as the minimum and
-    /// maximum, respectively.
+    /// maximum, respectively. Traces are concatenated.
     pub fn merge(self, other: QueryStats) -> Self {
         QueryStats {
             requests: self.requests + other.requests,
@@ -509,6 +926,53 @@ impl QueryStats {
                 (a, b) => a.or(b),
             },
             end: std::cmp::max(self.end, other.end),
+            trace: match (self.trace, other.trace) {
+                (Some(mut a), Some(b)) => {
+                    a.extend(b);
+                    Some(a)
+                }
+                (a, b) => a.or(b),
+            },
         }
     }
 }
+
+/// A request sent to a peer by a query, recorded in the trace of the query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryHop {
+    /// The peer the request was sent to.
+    pub peer: PeerId,
+    /// The type of the request.
+    pub rpc: QueryRpc,
+    /// The instant the request was sent, or the peer dialed if not connected.
+    pub sent: Instant,
+    /// The time from sending the request until receiving the response or
+    /// the request failing, `None` while the request is pending.
+    pub latency: Option<Duration>,
+    /// The result of the request.
+    pub result: QueryHopResult,
+}
+
+/// The type of a request sent by a query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QueryRpc {
+    FindNode,
+    GetProviders,
+    AddProvider,
+    GetRecord,
+    PutRecord,
+    GetProviderSummary,
+}
+
+/// The result of a request sent by a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryHopResult {
+    /// No response has been received yet.
+    Pending,
+    /// The peer responded, returning the given peers closer to the target
+    /// of the query, if any.
+    Success { peers: Vec<PeerId> },
+    /// The request failed, e.g. because the peer could not be dialed,
+    /// did not respond in time or returned an invalid response.
+    Failure,
+}
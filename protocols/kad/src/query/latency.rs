@@ -0,0 +1,101 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use fnv::FnvHashMap;
+use libp2p_identity::PeerId;
+use std::time::Duration;
+
+/// The maximum number of peers whose round-trip time is tracked.
+const MAX_TRACKED_PEERS: usize = 1024;
+
+/// The smoothed round-trip times of the peers contacted by queries,
+/// see [`Config::set_latency_weight`](crate::Config::set_latency_weight).
+#[derive(Debug, Default)]
+pub(crate) struct PeerLatencies {
+    /// The smoothed round-trip time per peer and the number of the sample
+    /// that last updated it.
+    latencies: FnvHashMap<PeerId, (Duration, u64)>,
+    /// The number of samples recorded so far.
+    samples: u64,
+}
+
+impl PeerLatencies {
+    /// Records the round-trip time of a request to a peer.
+    pub(crate) fn record(&mut self, peer: PeerId, rtt: Duration) {
+        if !self.latencies.contains_key(&peer) && self.latencies.len() >= MAX_TRACKED_PEERS {
+            // Forget the peer whose round-trip time was updated the longest time ago.
+            if let Some(oldest) = self
+                .latencies
+                .iter()
+                .min_by_key(|(_, (_, updated))| *updated)
+                .map(|(peer, _)| *peer)
+            {
+                self.latencies.remove(&oldest);
+            }
+        }
+        self.samples += 1;
+        let sample = self.samples;
+        self.latencies
+            .entry(peer)
+            .and_modify(|(smoothed, updated)| {
+                // Weigh a new sample by 1/8, as for the smoothed round-trip time of TCP.
+                *smoothed = (*smoothed * 7 + rtt) / 8;
+                *updated = sample;
+            })
+            .or_insert((rtt, sample));
+    }
+
+    /// Returns the smoothed round-trip time of a peer, if known.
+    pub(crate) fn get(&self, peer: &PeerId) -> Option<Duration> {
+        self.latencies.get(peer).map(|(rtt, _)| *rtt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooths_round_trip_times() {
+        let mut latencies = PeerLatencies::default();
+        let peer = PeerId::random();
+        assert_eq!(latencies.get(&peer), None);
+
+        latencies.record(peer, Duration::from_millis(80));
+        assert_eq!(latencies.get(&peer), Some(Duration::from_millis(80)));
+        latencies.record(peer, Duration::from_millis(160));
+        assert_eq!(latencies.get(&peer), Some(Duration::from_millis(90)));
+    }
+
+    #[test]
+    fn forgets_least_recently_updated_peers() {
+        let mut latencies = PeerLatencies::default();
+        let first = PeerId::random();
+        latencies.record(first, Duration::from_millis(1));
+        for _ in 1..MAX_TRACKED_PEERS {
+            latencies.record(PeerId::random(), Duration::from_millis(1));
+        }
+        assert!(latencies.get(&first).is_some());
+
+        latencies.record(PeerId::random(), Duration::from_millis(1));
+        assert_eq!(latencies.latencies.len(), MAX_TRACKED_PEERS);
+        assert_eq!(latencies.get(&first), None);
+    }
+}
@@ -20,7 +20,8 @@
 
 use super::*;
 
-use crate::kbucket::{Distance, Key, KeyBytes};
+use crate::kbucket::{Distance, Key, KeyBytes, KeyHashing};
+use crate::query::{AdaptiveParallelism, PeerLatencies};
 use crate::{ALPHA_VALUE, K_VALUE};
 use instant::Instant;
 use std::collections::btree_map::{BTreeMap, Entry};
@@ -45,6 +46,9 @@ pub struct ClosestPeersIter {
 
     /// The number of peers for which the iterator is currently waiting for results.
     num_waiting: usize,
+
+    /// The parallelism adapted to the response times of peers, if enabled.
+    adaptive: Option<AdaptiveState>,
 }
 
 /// Configuration for a `ClosestPeersIter`.
@@ -70,6 +74,22 @@ pub struct ClosestPeersIterConfig {
     /// the peer when evaluating the termination conditions, until and unless a
     /// result is delivered. Defaults to `10` seconds.
     pub peer_timeout: Duration,
+
+    /// Bounds for adapting the parallelism to the response times of peers.
+    ///
+    /// If set, `parallelism` is only the initial level of parallelism.
+    /// Defaults to `None`.
+    pub adaptive_parallelism: Option<AdaptiveParallelism>,
+
+    /// The weight of the round-trip time of a peer, relative to its distance to the
+    /// target, when choosing the next peer to contact, between `0.0` and `1.0`.
+    ///
+    /// Only applies to [`ClosestPeersIter::next_with_latencies`]. Defaults to `0.0`,
+    /// i.e. peers are contacted by increasing distance.
+    pub latency_weight: f64,
+
+    /// The hashing of the peers reported by other peers into the keyspace.
+    pub(crate) hasher: KeyHashing,
 }
 
 impl Default for ClosestPeersIterConfig {
@@ -78,6 +98,9 @@ impl Default for ClosestPeersIterConfig {
             parallelism: ALPHA_VALUE,
             num_results: K_VALUE,
             peer_timeout: Duration::from_secs(10),
+            adaptive_parallelism: None,
+            latency_weight: 0.0,
+            hasher: KeyHashing::default(),
         }
     }
 }
@@ -122,12 +145,17 @@ impl ClosestPeersIter {
         // The iterator initially makes progress by iterating towards the target.
         let state = State::Iterating { no_progress: 0 };
 
+        let adaptive = config
+            .adaptive_parallelism
+            .map(|bounds| AdaptiveState::new(bounds, config.parallelism));
+
         ClosestPeersIter {
             config,
             target,
             state,
             closest_peers,
             num_waiting: 0,
+            adaptive,
         }
     }
 
@@ -155,17 +183,21 @@ impl ClosestPeersIter {
             return false;
         }
 
-        let key = Key::from(*peer);
+        let key = self.config.hasher.peer(*peer);
         let distance = key.distance(&self.target);
 
         // Mark the peer as succeeded.
         match self.closest_peers.entry(distance) {
             Entry::Vacant(..) => return false,
             Entry::Occupied(mut e) => match e.get().state {
-                PeerState::Waiting(..) => {
+                PeerState::Waiting(timeout) => {
                     debug_assert!(self.num_waiting > 0);
                     self.num_waiting -= 1;
                     e.get_mut().state = PeerState::Succeeded;
+                    if let Some(adaptive) = &mut self.adaptive {
+                        let remaining = timeout.saturating_duration_since(Instant::now());
+                        adaptive.on_response(self.config.peer_timeout.saturating_sub(remaining));
+                    }
                 }
                 PeerState::Unresponsive => {
                     e.get_mut().state = PeerState::Succeeded;
@@ -197,7 +229,7 @@ impl ClosestPeersIter {
         //        (i.e. is the first entry after being incorporated)
         let mut progress = self.closest_peers.len() < self.config.num_results.get();
         for peer in closer_peers {
-            let key = peer.into();
+            let key = self.config.hasher.peer(peer);
             let distance = self.target.distance(&key);
             let peer = Peer {
                 key,
@@ -219,7 +251,7 @@ impl ClosestPeersIter {
         self.state = match self.state {
             State::Iterating { no_progress } => {
                 let no_progress = if progress { 0 } else { no_progress + 1 };
-                if no_progress >= self.config.parallelism.get() {
+                if no_progress >= self.parallelism() {
                     State::Stalled
                 } else {
                     State::Iterating { no_progress }
@@ -253,7 +285,7 @@ impl ClosestPeersIter {
             return false;
         }
 
-        let key = Key::from(*peer);
+        let key = self.config.hasher.peer(*peer);
         let distance = key.distance(&self.target);
 
         match self.closest_peers.entry(distance) {
@@ -296,6 +328,21 @@ impl ClosestPeersIter {
 
     /// Advances the state of the iterator, potentially getting a new peer to contact.
     pub fn next(&mut self, now: Instant) -> PeersIterState<'_> {
+        self.advance(now, None)
+    }
+
+    /// Advances the state of the iterator like [`ClosestPeersIter::next`], preferring
+    /// peers with a low round-trip time over closer peers as per
+    /// [`ClosestPeersIterConfig::latency_weight`].
+    pub(crate) fn next_with_latencies(
+        &mut self,
+        now: Instant,
+        latencies: &PeerLatencies,
+    ) -> PeersIterState<'_> {
+        self.advance(now, Some(latencies))
+    }
+
+    fn advance(&mut self, now: Instant, latencies: Option<&PeerLatencies>) -> PeersIterState<'_> {
         if let State::Finished = self.state {
             return PeersIterState::Finished;
         }
@@ -310,7 +357,10 @@ impl ClosestPeersIter {
         // Check if the iterator is at capacity w.r.t. the allowed parallelism.
         let at_capacity = self.at_capacity();
 
-        for peer in self.closest_peers.values_mut() {
+        // The closest peer not contacted yet, if the iterator is to contact another peer.
+        let mut next_peer = None;
+
+        for (distance, peer) in self.closest_peers.iter_mut() {
             match peer.state {
                 PeerState::Waiting(timeout) => {
                     if now >= timeout {
@@ -319,7 +369,10 @@ impl ClosestPeersIter {
                         // their results can still be delivered to the iterator.
                         debug_assert!(self.num_waiting > 0);
                         self.num_waiting -= 1;
-                        peer.state = PeerState::Unresponsive
+                        peer.state = PeerState::Unresponsive;
+                        if let Some(adaptive) = &mut self.adaptive {
+                            adaptive.on_response(self.config.peer_timeout);
+                        }
                     } else if at_capacity {
                         // The iterator is still waiting for a result from a peer and is
                         // at capacity w.r.t. the maximum number of peers being waited on.
@@ -347,10 +400,8 @@ impl ClosestPeersIter {
 
                 PeerState::NotContacted => {
                     if !at_capacity {
-                        let timeout = now + self.config.peer_timeout;
-                        peer.state = PeerState::Waiting(timeout);
-                        self.num_waiting += 1;
-                        return PeersIterState::Waiting(Some(Cow::Borrowed(peer.key.preimage())));
+                        next_peer = Some(*distance);
+                        break;
                     } else {
                         return PeersIterState::WaitingAtCapacity;
                     }
@@ -362,6 +413,16 @@ impl ClosestPeersIter {
             }
         }
 
+        if let Some(mut distance) = next_peer {
+            if let Some(latencies) = latencies.filter(|_| self.config.latency_weight > 0.0) {
+                distance = self.select_by_latency(distance, latencies);
+            }
+            let peer = self.closest_peers.get_mut(&distance).expect("s.a.");
+            peer.state = PeerState::Waiting(now + self.config.peer_timeout);
+            self.num_waiting += 1;
+            return PeersIterState::Waiting(Some(Cow::Borrowed(peer.key.preimage())));
+        }
+
         if self.num_waiting > 0 {
             // The iterator is still waiting for results and not at capacity w.r.t.
             // the allowed parallelism, but there are no new peers to contact
@@ -387,11 +448,16 @@ impl ClosestPeersIter {
 
     /// Consumes the iterator, returning the closest peers.
     pub fn into_result(self) -> impl Iterator<Item = PeerId> {
+        self.into_result_keys().map(Key::into_preimage)
+    }
+
+    /// Consumes the iterator, returning the keys of the peers of [`ClosestPeersIter::into_result`].
+    fn into_result_keys(self) -> impl Iterator<Item = Key<PeerId>> {
         self.closest_peers
             .into_iter()
             .filter_map(|(_, peer)| {
                 if let PeerState::Succeeded = peer.state {
-                    Some(peer.key.into_preimage())
+                    Some(peer.key)
                 } else {
                     None
                 }
@@ -399,6 +465,53 @@ impl ClosestPeersIter {
             .take(self.config.num_results.get())
     }
 
+    /// Chooses the peer to contact next among the `num_results` closest peers not
+    /// contacted yet, starting with the closest one at `closest`, by weighing the rank
+    /// of their distance to the target against their round-trip time.
+    ///
+    /// Peers with an unknown round-trip time are assumed to have the average round-trip
+    /// time of the candidates, so that peers are neither favoured nor penalised for
+    /// not having been contacted before.
+    fn select_by_latency(&self, closest: Distance, latencies: &PeerLatencies) -> Distance {
+        let candidates = self
+            .closest_peers
+            .range(closest..)
+            .filter(|(_, peer)| matches!(peer.state, PeerState::NotContacted))
+            .take(self.config.num_results.get())
+            .map(|(distance, peer)| (*distance, latencies.get(peer.key.preimage())))
+            .collect::<Vec<_>>();
+
+        let known = candidates.iter().filter_map(|(_, rtt)| *rtt);
+        let num_known = known.clone().count();
+        let Some(max_rtt) = known.clone().max().filter(|max| !max.is_zero()) else {
+            return closest;
+        };
+        let average_rtt = known.sum::<Duration>() / num_known as u32;
+
+        let weight = self.config.latency_weight.clamp(0.0, 1.0);
+        let score = |rank: usize, rtt: Option<Duration>| {
+            let distance_score = rank as f64 / candidates.len() as f64;
+            let latency_score = rtt.unwrap_or(average_rtt).as_secs_f64() / max_rtt.as_secs_f64();
+            (1.0 - weight) * distance_score + weight * latency_score
+        };
+        candidates
+            .iter()
+            .enumerate()
+            .map(|(rank, (distance, rtt))| (*distance, score(rank, *rtt)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(closest, |(distance, _)| distance)
+    }
+
+    /// Returns the current level of parallelism, i.e. the configured parallelism
+    /// unless it is adapted to the response times of peers.
+    pub fn parallelism(&self) -> usize {
+        self.adaptive
+            .as_ref()
+            .map_or(self.config.parallelism.get(), |adaptive| {
+                adaptive.parallelism
+            })
+    }
+
     /// Checks if the iterator is at capacity w.r.t. the permitted parallelism.
     ///
     /// While the iterator is stalled, up to `num_results` parallel requests
@@ -408,10 +521,9 @@ impl ClosestPeersIter {
     fn at_capacity(&self) -> bool {
         match self.state {
             State::Stalled => {
-                self.num_waiting
-                    >= usize::max(self.config.num_results.get(), self.config.parallelism.get())
+                self.num_waiting >= usize::max(self.config.num_results.get(), self.parallelism())
             }
-            State::Iterating { .. } => self.num_waiting >= self.config.parallelism.get(),
+            State::Iterating { .. } => self.num_waiting >= self.parallelism(),
             State::Finished => true,
         }
     }
@@ -457,6 +569,44 @@ enum State {
     Finished,
 }
 
+/// The parallelism of an iterator adapted to the response times of peers.
+#[derive(Debug, Clone)]
+struct AdaptiveState {
+    bounds: AdaptiveParallelism,
+    /// The current level of parallelism, within the bounds.
+    parallelism: usize,
+    /// The exponentially weighted moving average of the response times.
+    smoothed_response: Option<Duration>,
+}
+
+impl AdaptiveState {
+    fn new(bounds: AdaptiveParallelism, parallelism: NonZeroUsize) -> Self {
+        AdaptiveState {
+            bounds,
+            parallelism: parallelism
+                .get()
+                .clamp(bounds.min.get(), bounds.max.get().max(bounds.min.get())),
+            smoothed_response: None,
+        }
+    }
+
+    /// Adapts the parallelism to the response time of a peer.
+    fn on_response(&mut self, response: Duration) {
+        // Weigh a new sample by 1/8, as for the smoothed round-trip time of TCP.
+        let smoothed = match self.smoothed_response {
+            Some(smoothed) => (smoothed * 7 + response) / 8,
+            None => response,
+        };
+        self.smoothed_response = Some(smoothed);
+
+        if smoothed > self.bounds.slow_response {
+            self.parallelism = usize::min(self.parallelism + 1, self.bounds.max.get());
+        } else if smoothed < self.bounds.slow_response / 2 {
+            self.parallelism = usize::max(self.parallelism - 1, self.bounds.min.get());
+        }
+    }
+}
+
 /// Representation of a peer in the context of a iterator.
 #[derive(Debug, Clone)]
 struct Peer {
@@ -538,6 +688,9 @@ mod tests {
                 parallelism: NonZeroUsize::new(g.gen_range(1..10)).unwrap(),
                 num_results: NonZeroUsize::new(g.gen_range(1..25)).unwrap(),
                 peer_timeout: Duration::from_secs(g.gen_range(10..30)),
+                adaptive_parallelism: None,
+                latency_weight: 0.0,
+                hasher: KeyHashing::default(),
             };
             ClosestPeersIter::with_config(config, target, known_closest_peers)
         }
@@ -833,4 +986,83 @@ mod tests {
 
         QuickCheck::new().tests(10).quickcheck(prop as fn(_))
     }
+
+    #[test]
+    fn adaptive_parallelism_follows_response_times() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let target = Key::from(random_peers(1, &mut rng)[0]);
+        let peers = random_peers(K_VALUE.get(), &mut rng)
+            .into_iter()
+            .map(Key::from);
+        let config = ClosestPeersIterConfig {
+            parallelism: NonZeroUsize::new(3).unwrap(),
+            adaptive_parallelism: Some(AdaptiveParallelism {
+                min: NonZeroUsize::new(2).unwrap(),
+                max: NonZeroUsize::new(5).unwrap(),
+                slow_response: Duration::from_secs(1),
+            }),
+            peer_timeout: Duration::from_secs(2),
+            ..ClosestPeersIterConfig::default()
+        };
+        let mut iter = ClosestPeersIter::with_config(config.clone(), target, peers);
+        assert_eq!(iter.parallelism(), 3);
+
+        // Peers not responding within the timeout widen the parallelism up to the maximum.
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(matches!(iter.next(now), PeersIterState::Waiting(Some(_))));
+        }
+        assert_eq!(iter.next(now), PeersIterState::WaitingAtCapacity);
+        iter.next(now + config.peer_timeout);
+        assert_eq!(iter.parallelism(), 5);
+        assert_eq!(iter.num_waiting(), 0);
+
+        // Fast responses narrow the parallelism down to the minimum.
+        loop {
+            let now = Instant::now();
+            match iter.next(now) {
+                PeersIterState::Waiting(Some(peer)) => {
+                    let peer = peer.into_owned();
+                    iter.on_success(&peer, iter::empty());
+                }
+                _ => break,
+            }
+        }
+        assert_eq!(iter.parallelism(), 2);
+    }
+
+    #[test]
+    fn latency_weight_prefers_fast_peers() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let target = Key::from(random_peers(1, &mut rng)[0]);
+        let mut peers = random_peers(5, &mut rng)
+            .into_iter()
+            .map(Key::from)
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|p| p.distance(&target));
+
+        // The farthest peer responds much faster than all others.
+        let mut latencies = PeerLatencies::default();
+        for peer in &peers {
+            latencies.record(*peer.preimage(), Duration::from_millis(500));
+        }
+        let fastest = *peers[4].preimage();
+        latencies.record(fastest, Duration::from_millis(10));
+        latencies.record(fastest, Duration::from_millis(10));
+
+        let first_contacted = |latency_weight: f64| {
+            let config = ClosestPeersIterConfig {
+                latency_weight,
+                ..ClosestPeersIterConfig::default()
+            };
+            let mut iter = ClosestPeersIter::with_config(config, target.clone(), peers.clone());
+            match iter.next_with_latencies(Instant::now(), &latencies) {
+                PeersIterState::Waiting(Some(peer)) => peer.into_owned(),
+                state => panic!("Unexpected state: {state:?}"),
+            }
+        };
+
+        assert_eq!(first_contacted(0.0), *peers[0].preimage());
+        assert_eq!(first_contacted(0.9), fastest);
+    }
 }
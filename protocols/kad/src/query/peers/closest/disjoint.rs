@@ -319,7 +319,7 @@ impl ClosestDisjointPeersIter {
         let result_per_path = self
             .iters
             .into_iter()
-            .map(|iter| iter.into_result().map(Key::from));
+            .map(ClosestPeersIter::into_result_keys);
 
         ResultIter::new(self.target, result_per_path).map(Key::into_preimage)
     }
@@ -595,6 +595,9 @@ mod tests {
                 parallelism: Parallelism::arbitrary(g).0,
                 num_results: NumResults::arbitrary(g).0,
                 peer_timeout: Duration::from_secs(1),
+                adaptive_parallelism: None,
+                latency_weight: 0.0,
+                hasher: KeyHashing::default(),
             }
         }
     }
@@ -0,0 +1,268 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Consuming the progress of queries as [`Stream`]s instead of events.
+//!
+//! The progress of a query started via e.g. [`Behaviour::get_record_stream`](crate::Behaviour::get_record_stream)
+//! is forwarded to its [`QueryStream`] in addition to being reported via
+//! [`Event::OutboundQueryProgressed`](crate::Event::OutboundQueryProgressed), sparing
+//! applications from routing the events to the tasks that started the queries themselves.
+
+use crate::behaviour::QueryResult;
+use crate::query::QueryId;
+use fnv::FnvHashMap;
+use futures::channel::mpsc;
+use futures::{Stream, StreamExt};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The number of results buffered per [`QueryStream`] beyond which the results are
+/// queued in the behaviour until consumed.
+const STREAM_BUFFER_SIZE: usize = 16;
+
+/// The progress of a query, ending after the last step of the query.
+///
+/// The stream also ends if the query is cancelled or the behaviour dropped.
+/// Results are queued in the behaviour until consumed, thus a stream that is not
+/// interested in the progress of a query should be turned into a [`QueryOutcome`] via
+/// [`QueryStream::outcome`] or dropped.
+#[must_use = "streams do nothing unless polled"]
+pub struct QueryStream<T> {
+    id: QueryId,
+    receiver: mpsc::Receiver<QueryResult>,
+    extract: fn(QueryResult) -> Option<T>,
+}
+
+impl<T> QueryStream<T> {
+    pub fn id(&self) -> QueryId {
+        self.id
+    }
+
+    /// Turns the stream into a future resolving to the result of the last step reported for
+    /// the query, or `None` if the query was cancelled before reporting any.
+    pub fn outcome(self) -> QueryOutcome<T> {
+        QueryOutcome {
+            stream: self,
+            last: None,
+        }
+    }
+}
+
+impl<T> Unpin for QueryStream<T> {}
+
+impl<T> Stream for QueryStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match futures::ready!(self.receiver.poll_next_unpin(cx)) {
+                Some(result) => {
+                    if let Some(item) = (self.extract)(result) {
+                        return Poll::Ready(Some(item));
+                    }
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// The result of the last step of a query, see [`QueryStream::outcome`].
+#[must_use = "futures do nothing unless polled"]
+pub struct QueryOutcome<T> {
+    stream: QueryStream<T>,
+    last: Option<T>,
+}
+
+impl<T> QueryOutcome<T> {
+    pub fn id(&self) -> QueryId {
+        self.stream.id
+    }
+}
+
+impl<T> Unpin for QueryOutcome<T> {}
+
+impl<T> Future for QueryOutcome<T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        while let Some(item) = futures::ready!(self.stream.poll_next_unpin(cx)) {
+            self.last = Some(item);
+        }
+
+        Poll::Ready(self.last.take())
+    }
+}
+
+/// The sender of the [`QueryStream`] of a query.
+struct StreamSender {
+    sender: mpsc::Sender<QueryResult>,
+    /// The progress not yet sent due to the stream not keeping up.
+    backlog: VecDeque<QueryResult>,
+    /// Whether the stream ends once the backlog is sent.
+    finished: bool,
+}
+
+/// The senders of the [`QueryStream`]s of running queries.
+#[derive(Default)]
+pub(crate) struct QueryStreams {
+    senders: FnvHashMap<QueryId, StreamSender>,
+}
+
+impl QueryStreams {
+    pub(crate) fn register<T>(
+        &mut self,
+        id: QueryId,
+        extract: fn(QueryResult) -> Option<T>,
+    ) -> QueryStream<T> {
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER_SIZE);
+        self.senders.insert(
+            id,
+            StreamSender {
+                sender,
+                backlog: VecDeque::new(),
+                finished: false,
+            },
+        );
+
+        QueryStream {
+            id,
+            receiver,
+            extract,
+        }
+    }
+
+    /// Queues the progress of a query for its stream, if any, ending the stream
+    /// after the last step.
+    pub(crate) fn on_progress(&mut self, id: QueryId, result: &QueryResult, last: bool) {
+        if let Some(stream) = self.senders.get_mut(&id) {
+            stream.backlog.push_back(result.clone());
+            stream.finished |= last;
+        }
+    }
+
+    /// Ends the streams of the queries that are no longer running without reporting
+    /// their last step, i.e. were cancelled.
+    pub(crate) fn on_queries_removed(&mut self, mut is_running: impl FnMut(&QueryId) -> bool) {
+        for (id, stream) in self.senders.iter_mut() {
+            stream.finished |= !is_running(id);
+        }
+    }
+
+    /// Sends the queued progress to the streams as far as they have capacity.
+    pub(crate) fn poll(&mut self, cx: &mut Context<'_>) {
+        self.senders.retain(|_, stream| loop {
+            if stream.backlog.is_empty() {
+                return !stream.finished;
+            }
+            match stream.sender.poll_ready(cx) {
+                Poll::Ready(Ok(())) => {
+                    let result = stream.backlog.pop_front().expect("backlog not to be empty");
+                    if stream.sender.start_send(result).is_err() {
+                        return false;
+                    }
+                }
+                // The stream was dropped.
+                Poll::Ready(Err(_)) => return false,
+                Poll::Pending => return true,
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::{GetClosestPeersError, GetClosestPeersOk};
+    use crate::query::{QueryConfig, QueryPool};
+    use futures::executor::block_on;
+    use futures::task::noop_waker_ref;
+    use libp2p_identity::PeerId;
+
+    fn closest_peers(
+        result: QueryResult,
+    ) -> Option<Result<GetClosestPeersOk, GetClosestPeersError>> {
+        match result {
+            QueryResult::GetClosestPeers(r) => Some(r),
+            _ => None,
+        }
+    }
+
+    fn progress(peer: PeerId) -> QueryResult {
+        QueryResult::GetClosestPeers(Ok(GetClosestPeersOk {
+            key: Vec::new(),
+            peers: vec![peer],
+        }))
+    }
+
+    fn query_ids() -> QueryPool<()> {
+        QueryPool::new(QueryConfig::default())
+    }
+
+    #[test]
+    fn stream_ends_after_last_step() {
+        let mut streams = QueryStreams::default();
+        let id = query_ids().reserve_id();
+        let (first, second) = (PeerId::random(), PeerId::random());
+        let mut stream = streams.register(id, closest_peers);
+
+        // More progress than fits into the buffer of the stream.
+        for _ in 0..STREAM_BUFFER_SIZE * 2 {
+            streams.on_progress(id, &progress(first), false);
+        }
+        streams.on_progress(id, &progress(second), true);
+        streams.poll(&mut Context::from_waker(noop_waker_ref()));
+        assert_eq!(streams.senders.len(), 1);
+
+        let mut items = Vec::new();
+        while items.len() < STREAM_BUFFER_SIZE {
+            items.push(block_on(stream.next()).unwrap());
+        }
+        streams.poll(&mut Context::from_waker(noop_waker_ref()));
+        assert!(streams.senders.is_empty());
+        streams.on_progress(id, &progress(first), false);
+
+        items.extend(block_on(stream.collect::<Vec<_>>()));
+        assert_eq!(items.len(), STREAM_BUFFER_SIZE * 2 + 1);
+        assert_eq!(items.last().unwrap().as_ref().unwrap().peers, vec![second]);
+    }
+
+    #[test]
+    fn outcome_is_none_for_cancelled_query() {
+        let mut streams = QueryStreams::default();
+        let mut ids = query_ids();
+        let id = ids.reserve_id();
+        let outcome = streams.register(id, closest_peers).outcome();
+
+        streams.on_progress(id, &progress(PeerId::random()), false);
+        streams.on_queries_removed(|_| false);
+        streams.poll(&mut Context::from_waker(noop_waker_ref()));
+
+        // The progress reported before the cancellation is still the last step.
+        assert!(block_on(outcome).is_some());
+
+        let outcome = streams.register(ids.reserve_id(), closest_peers).outcome();
+        streams.on_queries_removed(|_| false);
+        streams.poll(&mut Context::from_waker(noop_waker_ref()));
+        assert!(block_on(outcome).is_none());
+    }
+}
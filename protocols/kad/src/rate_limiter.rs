@@ -0,0 +1,152 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use instant::Instant;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// Rate limiter using the [Token Bucket] algorithm, with a bucket per `Id`.
+///
+/// [Token Bucket]: https://en.wikipedia.org/wiki/Token_bucket
+#[derive(Debug)]
+pub(crate) struct RateLimiter<Id> {
+    limit: u32,
+    interval: Duration,
+
+    refill_schedule: VecDeque<(Instant, Id)>,
+    buckets: HashMap<Id, u32>,
+}
+
+impl<Id: Eq + Hash + Clone> RateLimiter<Id> {
+    /// Creates a limiter allowing bursts of `limit` requests, refilled
+    /// by one request per `interval`.
+    pub(crate) fn new(limit: NonZeroU32, interval: Duration) -> Self {
+        assert!(!interval.is_zero());
+
+        Self {
+            limit: limit.into(),
+            interval,
+            refill_schedule: Default::default(),
+            buckets: Default::default(),
+        }
+    }
+
+    /// Takes a token from the bucket of `id`, returning `false` if it is empty.
+    pub(crate) fn try_next(&mut self, id: Id, now: Instant) -> bool {
+        self.refill(now);
+
+        match self.buckets.get_mut(&id) {
+            Some(balance) => match balance.checked_sub(1) {
+                Some(a) => {
+                    *balance = a;
+                    true
+                }
+                None => false,
+            },
+            // A missing bucket is equivalent to a full bucket.
+            None => {
+                self.buckets.insert(id.clone(), self.limit - 1);
+                self.refill_schedule.push_back((now, id));
+                true
+            }
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        loop {
+            // Items in `refill_schedule` are sorted, thus, if the first
+            // is not due, none of them are.
+            match self.refill_schedule.front() {
+                Some((last_refill, _)) if now.duration_since(*last_refill) >= self.interval => {}
+                _ => return,
+            };
+
+            let (last_refill, id) = self
+                .refill_schedule
+                .pop_front()
+                .expect("Queue not to be empty.");
+            let balance = self
+                .buckets
+                .get(&id)
+                .expect("Entry can only be removed via refill.");
+
+            let new_tokens = now
+                .duration_since(last_refill)
+                .as_micros()
+                .checked_div(self.interval.as_micros())
+                .and_then(|i| i.try_into().ok())
+                .unwrap_or(u32::MAX);
+            let new_balance = balance.checked_add(new_tokens).unwrap_or(u32::MAX);
+
+            if new_balance < self.limit {
+                self.buckets.insert(id.clone(), new_balance);
+                self.refill_schedule.push_back((now, id));
+            } else {
+                self.buckets.remove(&id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(limit: u32, interval: Duration) -> RateLimiter<u32> {
+        RateLimiter::new(NonZeroU32::new(limit).unwrap(), interval)
+    }
+
+    #[test]
+    fn limits_and_refills() {
+        let now = Instant::now();
+        let mut l = limiter(10, Duration::from_secs(1));
+
+        for _ in 0..10 {
+            assert!(l.try_next(1, now));
+        }
+        assert!(!l.try_next(1, now));
+        assert!(l.try_next(2, now));
+
+        let now = now + Duration::from_secs(1);
+        assert!(l.try_next(1, now));
+        assert!(!l.try_next(1, now));
+
+        let now = now + Duration::from_secs(10);
+        for _ in 0..10 {
+            assert!(l.try_next(1, now));
+        }
+    }
+
+    #[test]
+    fn garbage_collects() {
+        let now = Instant::now();
+        let mut l = limiter(1, Duration::from_secs(1));
+
+        assert!(l.try_next(1, now));
+
+        let now = now + Duration::from_secs(1);
+        assert!(l.try_next(2, now));
+
+        assert_eq!(l.buckets.len(), 1);
+        assert_eq!(l.refill_schedule.len(), 1);
+    }
+}
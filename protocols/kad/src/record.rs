@@ -83,6 +83,12 @@ pub struct Record {
     pub publisher: Option<PeerId>,
     /// The expiration time as measured by a local, monotonic clock.
     pub expires: Option<Instant>,
+    /// The sequence number of the record, if it is a mutable record.
+    ///
+    /// Of two records for the same key, the one with the higher sequence number
+    /// supersedes the other, unless a [`ConflictResolver`](crate::ConflictResolver)
+    /// is registered for the namespace of the key.
+    pub sequence: Option<u64>,
 }
 
 impl Record {
@@ -96,6 +102,7 @@ impl Record {
             value,
             publisher: None,
             expires: None,
+            sequence: None,
         }
     }
 
@@ -187,6 +194,7 @@ mod tests {
                 } else {
                     None
                 },
+                sequence: Option::arbitrary(g),
             }
         }
     }
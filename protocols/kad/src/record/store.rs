@@ -19,17 +19,26 @@
 // DEALINGS IN THE SOFTWARE.
 
 mod memory;
+mod tiered;
 
-pub use memory::{MemoryStore, MemoryStoreConfig};
+pub use memory::{
+    EvictedProvider, MemoryEvictionPolicy, MemoryStore, MemoryStoreConfig, ProviderEvictionPolicy,
+    ProviderEvictionReason,
+};
 use thiserror::Error;
+pub use tiered::{TieredStore, TieredStoreConfig};
 
 use super::*;
 use crate::K_VALUE;
+use futures::future::{self, BoxFuture, FutureExt};
 use std::borrow::Cow;
 
 /// The result of an operation on a `RecordStore`.
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The future returned by an operation on an [`AsyncRecordStore`].
+pub type StoreFuture<T> = BoxFuture<'static, T>;
+
 /// The possible errors of a `RecordStore` operation.
 #[derive(Error, Debug, Clone)]
 pub enum Error {
@@ -44,6 +53,11 @@ pub enum Error {
     /// The store cannot store this value because it is too large.
     #[error("the value is too large to be stored")]
     ValueTooLarge,
+
+    /// The store is at capacity w.r.t. its memory budget and cannot evict
+    /// enough records to make room.
+    #[error("the store exceeds its memory budget")]
+    MemoryBudget,
 }
 
 /// Trait for types implementing a record store.
@@ -100,4 +114,135 @@ pub trait RecordStore {
 
     /// Removes a provider record from the store.
     fn remove_provider(&mut self, k: &Key, p: &PeerId);
+
+    /// Gets the keys for which the store holds at least one provider record.
+    ///
+    /// These are the keys summarised in a [`ProviderSummary`](crate::ProviderSummary)
+    /// sent to other peers. The default implementation only returns the keys
+    /// provided by the local node, see [`RecordStore::provided`].
+    fn provider_keys(&self) -> Vec<Key> {
+        self.provided().map(|r| r.key.clone()).collect()
+    }
+
+    /// Gets the provider record of the given provider for the given key, e.g.
+    /// to query its expiration.
+    fn provider(&self, k: &Key, p: &PeerId) -> Option<ProviderRecord> {
+        self.providers(k).into_iter().find(|r| &r.provider == p)
+    }
+
+    /// Sets the expiration of the record with the given key, e.g. to extend it,
+    /// returning whether the record is stored.
+    fn set_record_expiration(&mut self, k: &Key, expires: Option<Instant>) -> Result<bool> {
+        let Some(mut record) = self.get(k).map(Cow::into_owned) else {
+            return Ok(false);
+        };
+        record.expires = expires;
+        self.put(record)?;
+        Ok(true)
+    }
+
+    /// Sets the expiration of the provider record of the given provider for the
+    /// given key, e.g. to extend it, returning whether the provider record is stored.
+    fn set_provider_expiration(
+        &mut self,
+        k: &Key,
+        p: &PeerId,
+        expires: Option<Instant>,
+    ) -> Result<bool> {
+        let Some(mut record) = self.provider(k, p) else {
+            return Ok(false);
+        };
+        record.expires = expires;
+        self.add_provider(record)?;
+        Ok(true)
+    }
+}
+
+/// Trait for types implementing a record store whose operations may complete
+/// asynchronously, e.g. a store backed by a database or by network storage.
+///
+/// The records managed by an `AsyncRecordStore` are the same as those managed
+/// by a [`RecordStore`]. Instead of returning results directly, every operation
+/// returns a [`StoreFuture`] that is driven by the Kademlia `Behaviour` as part
+/// of its `poll` loop, thus never blocking it. The returned futures must not
+/// borrow from the store.
+///
+/// Every [`RecordStore`] is also an `AsyncRecordStore` whose operations complete
+/// immediately, so that synchronous stores like the [`MemoryStore`] can be used
+/// as before.
+pub trait AsyncRecordStore {
+    /// Gets a record from the store, given its key.
+    fn get_record(&mut self, k: &Key) -> StoreFuture<Option<Record>>;
+
+    /// Puts a record into the store.
+    fn put_record(&mut self, r: Record) -> StoreFuture<Result<()>>;
+
+    /// Removes the record with the given key from the store.
+    fn remove_record(&mut self, k: &Key) -> StoreFuture<()>;
+
+    /// Gets all (value-) records currently stored.
+    fn all_records(&mut self) -> StoreFuture<Vec<Record>>;
+
+    /// Adds a provider record to the store.
+    ///
+    /// See [`RecordStore::add_provider`].
+    fn add_provider_record(&mut self, record: ProviderRecord) -> StoreFuture<Result<()>>;
+
+    /// Gets the stored provider records for the given key.
+    fn provider_records(&mut self, key: &Key) -> StoreFuture<Vec<ProviderRecord>>;
+
+    /// Gets all stored provider records for which the node owning the store
+    /// is itself the provider.
+    fn provided_records(&mut self) -> StoreFuture<Vec<ProviderRecord>>;
+
+    /// Removes a provider record from the store.
+    fn remove_provider_record(&mut self, k: &Key, p: &PeerId) -> StoreFuture<()>;
+
+    /// Gets the keys for which the store holds at least one provider record.
+    ///
+    /// See [`RecordStore::provider_keys`].
+    fn provider_record_keys(&mut self) -> StoreFuture<Vec<Key>>;
+}
+
+impl<T> AsyncRecordStore for T
+where
+    T: RecordStore,
+{
+    fn get_record(&mut self, k: &Key) -> StoreFuture<Option<Record>> {
+        future::ready(self.get(k).map(Cow::into_owned)).boxed()
+    }
+
+    fn put_record(&mut self, r: Record) -> StoreFuture<Result<()>> {
+        future::ready(self.put(r)).boxed()
+    }
+
+    fn remove_record(&mut self, k: &Key) -> StoreFuture<()> {
+        self.remove(k);
+        future::ready(()).boxed()
+    }
+
+    fn all_records(&mut self) -> StoreFuture<Vec<Record>> {
+        future::ready(self.records().map(Cow::into_owned).collect()).boxed()
+    }
+
+    fn add_provider_record(&mut self, record: ProviderRecord) -> StoreFuture<Result<()>> {
+        future::ready(self.add_provider(record)).boxed()
+    }
+
+    fn provider_records(&mut self, key: &Key) -> StoreFuture<Vec<ProviderRecord>> {
+        future::ready(self.providers(key)).boxed()
+    }
+
+    fn provided_records(&mut self) -> StoreFuture<Vec<ProviderRecord>> {
+        future::ready(self.provided().map(Cow::into_owned).collect()).boxed()
+    }
+
+    fn remove_provider_record(&mut self, k: &Key, p: &PeerId) -> StoreFuture<()> {
+        self.remove_provider(k, p);
+        future::ready(()).boxed()
+    }
+
+    fn provider_record_keys(&mut self) -> StoreFuture<Vec<Key>> {
+        future::ready(self.provider_keys()).boxed()
+    }
 }
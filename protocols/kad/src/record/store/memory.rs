@@ -20,10 +20,15 @@
 
 use super::*;
 
-use crate::kbucket;
+use crate::kbucket::{self, KeyHasher, KeyHashing};
 use smallvec::SmallVec;
-use std::collections::{hash_map, hash_set, HashMap, HashSet};
-use std::iter;
+use std::collections::{hash_map, hash_set, BTreeMap, HashMap, HashSet, VecDeque};
+use std::{iter, mem};
+
+/// The maximum number of [`EvictedProvider`]s and evicted records buffered by a
+/// [`MemoryStore`] until they are taken via [`MemoryStore::take_evicted_providers`]
+/// and [`MemoryStore::take_evicted_records`], respectively.
+const MAX_BUFFERED_EVICTIONS: usize = 1024;
 
 /// In-memory implementation of a `RecordStore`.
 pub struct MemoryStore {
@@ -31,14 +36,31 @@ pub struct MemoryStore {
     local_key: kbucket::Key<PeerId>,
     /// The configuration of the store.
     config: MemoryStoreConfig,
+    /// The hashing of keys by which providers are ordered, see [`MemoryStore::with_key_hasher`].
+    hasher: KeyHashing,
     /// The stored (regular) records.
     records: HashMap<Key, Record>,
     /// The stored provider records.
     providers: HashMap<Key, SmallVec<[ProviderRecord; K_VALUE.get()]>>,
+    /// The total number of provider records in `providers`.
+    num_provider_records: usize,
     /// The set of all provider records for the node identified by `local_key`.
     ///
     /// Must be kept in sync with `providers`.
     provided: HashSet<ProviderRecord>,
+    /// The recency of the provider records of remote providers, if the
+    /// [`ProviderEvictionPolicy::LeastRecentlyUsed`] is configured.
+    recency: Recency,
+    /// The evicted provider records not yet taken.
+    evicted: VecDeque<EvictedProvider>,
+    /// The total number of evicted provider records.
+    num_evicted: u64,
+    /// The records evicted due to the memory budget not yet taken.
+    evicted_records: VecDeque<Record>,
+    /// The total number of records evicted due to the memory budget.
+    num_evicted_records: u64,
+    /// The estimated memory used by the stored records and provider records, in bytes.
+    memory_used: usize,
 }
 
 /// Configuration for a `MemoryStore`.
@@ -55,6 +77,20 @@ pub struct MemoryStoreConfig {
     /// The maximum number of provider records for which the
     /// local node is the provider.
     pub max_provided_keys: usize,
+    /// The maximum number of provider records stored for all keys.
+    pub max_provider_records: usize,
+    /// The policy by which provider records are evicted once
+    /// `max_providers_per_key` or `max_provider_records` is reached.
+    pub provider_eviction: ProviderEvictionPolicy,
+    /// The maximum estimated memory used by all records and provider records, in bytes.
+    ///
+    /// Once reached, records and provider records are evicted as per `memory_eviction`
+    /// to make room for new ones. Records published by and provider records of the
+    /// local node are never evicted. `None` means that there is no memory budget.
+    pub max_memory_bytes: Option<usize>,
+    /// The order in which records and provider records are evicted once
+    /// `max_memory_bytes` is reached.
+    pub memory_eviction: MemoryEvictionPolicy,
 }
 
 impl Default for MemoryStoreConfig {
@@ -64,10 +100,97 @@ impl Default for MemoryStoreConfig {
             max_value_bytes: 65 * 1024,
             max_provided_keys: 1024,
             max_providers_per_key: K_VALUE.get(),
+            max_provider_records: 1024 * K_VALUE.get(),
+            provider_eviction: ProviderEvictionPolicy::default(),
+            max_memory_bytes: None,
+            memory_eviction: MemoryEvictionPolicy::default(),
         }
     }
 }
 
+/// The policy by which a [`MemoryStore`] makes room for new provider records.
+///
+/// Provider records of the local node are never evicted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProviderEvictionPolicy {
+    /// Keeps the providers closest to their keys, evicting the provider record
+    /// whose provider is farthest from its key if the new provider is closer.
+    #[default]
+    NearestFirst,
+    /// Evicts the provider record that was least recently added or updated.
+    LeastRecentlyUsed,
+}
+
+/// The order in which a [`MemoryStore`] evicts records and provider records
+/// once [`MemoryStoreConfig::max_memory_bytes`] is reached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemoryEvictionPolicy {
+    /// Evicts the records and provider records expiring first, those that never
+    /// expire last.
+    #[default]
+    Expiration,
+    /// Evicts the largest records and provider records first.
+    Size,
+    /// Evicts provider records before records, each expiring first.
+    ProvidersBeforeRecords,
+}
+
+/// The reason for which a [`MemoryStore`] evicted a provider record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderEvictionReason {
+    /// The key already had [`MemoryStoreConfig::max_providers_per_key`] providers.
+    MaxProvidersPerKey,
+    /// The store already held [`MemoryStoreConfig::max_provider_records`] provider records.
+    MaxProviderRecords,
+    /// The store reached [`MemoryStoreConfig::max_memory_bytes`].
+    MemoryBudget,
+}
+
+/// A provider record evicted from a [`MemoryStore`] to make room for another.
+#[derive(Debug, Clone)]
+pub struct EvictedProvider {
+    /// The evicted provider record.
+    pub record: ProviderRecord,
+    /// Why the provider record was evicted.
+    pub reason: ProviderEvictionReason,
+}
+
+/// Tracks the order in which provider records were last added or updated.
+#[derive(Default)]
+struct Recency {
+    /// The next tick to assign.
+    next: u64,
+    /// The tick of every tracked provider record.
+    ticks: HashMap<(Key, PeerId), u64>,
+    /// The tracked provider records, least recently used first.
+    order: BTreeMap<u64, (Key, PeerId)>,
+}
+
+impl Recency {
+    fn touch(&mut self, key: &Key, provider: PeerId) {
+        let tick = self.next;
+        self.next += 1;
+        if let Some(old) = self.ticks.insert((key.clone(), provider), tick) {
+            self.order.remove(&old);
+        }
+        self.order.insert(tick, (key.clone(), provider));
+    }
+
+    fn remove(&mut self, key: &Key, provider: PeerId) {
+        if let Some(tick) = self.ticks.remove(&(key.clone(), provider)) {
+            self.order.remove(&tick);
+        }
+    }
+
+    fn tick(&self, key: &Key, provider: PeerId) -> Option<u64> {
+        self.ticks.get(&(key.clone(), provider)).copied()
+    }
+
+    fn least_recent(&self) -> Option<&(Key, PeerId)> {
+        self.order.values().next()
+    }
+}
+
 impl MemoryStore {
     /// Creates a new `MemoryRecordStore` with a default configuration.
     pub fn new(local_id: PeerId) -> Self {
@@ -79,19 +202,272 @@ impl MemoryStore {
         MemoryStore {
             local_key: kbucket::Key::from(local_id),
             config,
+            hasher: KeyHashing::default(),
             records: HashMap::default(),
             provided: HashSet::default(),
             providers: HashMap::default(),
+            num_provider_records: 0,
+            recency: Recency::default(),
+            evicted: VecDeque::default(),
+            num_evicted: 0,
+            evicted_records: VecDeque::default(),
+            num_evicted_records: 0,
+            memory_used: 0,
         }
     }
 
+    /// Orders the providers of a key by their distance as per the given [`KeyHasher`],
+    /// which should be the one set via [`Config::set_key_hasher`](crate::Config::set_key_hasher).
+    pub fn with_key_hasher(mut self, hasher: impl KeyHasher) -> Self {
+        self.hasher = KeyHashing::new(hasher);
+        self
+    }
+
     /// Retains the records satisfying a predicate.
-    pub fn retain<F>(&mut self, f: F)
+    pub fn retain<F>(&mut self, mut f: F)
     where
         F: FnMut(&Key, &mut Record) -> bool,
     {
-        self.records.retain(f);
+        let memory_used = &mut self.memory_used;
+        self.records.retain(|k, r| {
+            *memory_used -= record_size(r);
+            let retain = f(k, r);
+            if retain {
+                // The record may have been modified.
+                *memory_used += record_size(r);
+            }
+            retain
+        });
+    }
+
+    /// Returns the estimated memory used by the stored records and provider
+    /// records, in bytes, see [`MemoryStoreConfig::max_memory_bytes`].
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+
+    /// Takes the records evicted due to [`MemoryStoreConfig::max_memory_bytes`]
+    /// since the last call.
+    ///
+    /// At most the most recent 1024 evictions are buffered, see
+    /// [`MemoryStore::num_evicted_records`] for the total number.
+    pub fn take_evicted_records(&mut self) -> Vec<Record> {
+        self.evicted_records.drain(..).collect()
+    }
+
+    /// Returns the total number of records evicted from the store due to
+    /// [`MemoryStoreConfig::max_memory_bytes`].
+    pub fn num_evicted_records(&self) -> u64 {
+        self.num_evicted_records
+    }
+
+    /// Takes the provider records evicted since the last call.
+    ///
+    /// At most the most recent 1024 evictions are buffered, see
+    /// [`MemoryStore::num_evicted_providers`] for the total number.
+    pub fn take_evicted_providers(&mut self) -> Vec<EvictedProvider> {
+        self.evicted.drain(..).collect()
+    }
+
+    /// Returns the total number of provider records evicted from the store.
+    pub fn num_evicted_providers(&self) -> u64 {
+        self.num_evicted
+    }
+
+    /// Returns the total number of stored provider records.
+    pub fn num_provider_records(&self) -> usize {
+        self.num_provider_records
+    }
+
+    fn is_local(&self, provider: &PeerId) -> bool {
+        self.local_key.preimage() == provider
+    }
+
+    fn touch(&mut self, record: &ProviderRecord) {
+        if self.config.provider_eviction == ProviderEvictionPolicy::LeastRecentlyUsed
+            && !self.is_local(&record.provider)
+        {
+            self.recency.touch(&record.key, record.provider);
+        }
+    }
+
+    /// Selects the provider of the given key to evict in favour of a new
+    /// provider at the given distance to the key, if any.
+    fn key_eviction_candidate(&self, key: &Key, distance: kbucket::Distance) -> Option<PeerId> {
+        let providers = self.providers.get(key)?;
+        let target = self.hasher.key(key.clone());
+        let remote = providers.iter().filter(|p| !self.is_local(&p.provider));
+        match self.config.provider_eviction {
+            ProviderEvictionPolicy::NearestFirst => remote
+                .last()
+                .filter(|p| self.hasher.peer(p.provider).distance(&target) > distance)
+                .map(|p| p.provider),
+            ProviderEvictionPolicy::LeastRecentlyUsed => remote
+                .min_by_key(|p| self.recency.tick(key, p.provider))
+                .map(|p| p.provider),
+        }
+    }
+
+    /// Selects the provider record of any key to evict in favour of a new
+    /// provider at the given distance to its key, if any.
+    fn store_eviction_candidate(&self, distance: kbucket::Distance) -> Option<(Key, PeerId)> {
+        match self.config.provider_eviction {
+            ProviderEvictionPolicy::NearestFirst => self
+                .providers
+                .iter()
+                .filter_map(|(key, providers)| {
+                    let target = self.hasher.key(key.clone());
+                    providers
+                        .iter()
+                        .rev()
+                        .find(|p| !self.is_local(&p.provider))
+                        .map(|p| (self.hasher.peer(p.provider).distance(&target), key, p))
+                })
+                .filter(|(d, _, _)| *d > distance)
+                .max_by_key(|(d, _, _)| *d)
+                .map(|(_, key, p)| (key.clone(), p.provider)),
+            ProviderEvictionPolicy::LeastRecentlyUsed => self.recency.least_recent().cloned(),
+        }
+    }
+
+    fn evict(&mut self, key: &Key, provider: &PeerId, reason: ProviderEvictionReason) {
+        if let Some(record) = self.take_provider(key, provider) {
+            self.num_evicted += 1;
+            if self.evicted.len() == MAX_BUFFERED_EVICTIONS {
+                self.evicted.pop_front();
+            }
+            self.evicted.push_back(EvictedProvider { record, reason });
+        }
+    }
+
+    /// Evicts records and provider records until `incoming` more bytes fit into
+    /// the memory budget, if any, given that `replaced` of the used bytes are
+    /// about to be replaced.
+    ///
+    /// The record with key `keep_record` and the provider record `keep_provider`
+    /// are not evicted. Nothing is evicted if not enough memory can be freed.
+    fn make_room(
+        &mut self,
+        incoming: usize,
+        replaced: usize,
+        keep_record: Option<&Key>,
+        keep_provider: Option<(&Key, &PeerId)>,
+    ) -> Result<()> {
+        let Some(budget) = self.config.max_memory_bytes else {
+            return Ok(());
+        };
+        let used = self.memory_used.saturating_sub(replaced);
+        let Some(needed) = (used + incoming).checked_sub(budget).filter(|n| *n > 0) else {
+            return Ok(());
+        };
+
+        enum Candidate {
+            Record(Key),
+            Provider(Key, PeerId),
+        }
+        let local = *self.local_key.preimage();
+        let records = self
+            .records
+            .values()
+            .filter(|r| r.publisher != Some(local) && Some(&r.key) != keep_record)
+            .map(|r| {
+                let c = Candidate::Record(r.key.clone());
+                (c, record_size(r), r.expires)
+            });
+        let providers = self
+            .providers
+            .values()
+            .flatten()
+            .filter(|p| p.provider != local && Some((&p.key, &p.provider)) != keep_provider)
+            .map(|p| {
+                let c = Candidate::Provider(p.key.clone(), p.provider);
+                (c, provider_size(p), p.expires)
+            });
+        let mut candidates = records.chain(providers).collect::<Vec<_>>();
+        match self.config.memory_eviction {
+            MemoryEvictionPolicy::Expiration => {
+                candidates.sort_by_key(|(_, _, expires)| (expires.is_none(), *expires))
+            }
+            MemoryEvictionPolicy::Size => {
+                candidates.sort_by_key(|(_, size, _)| std::cmp::Reverse(*size))
+            }
+            MemoryEvictionPolicy::ProvidersBeforeRecords => {
+                candidates.sort_by_key(|(c, _, expires)| {
+                    (
+                        matches!(c, Candidate::Record(_)),
+                        expires.is_none(),
+                        *expires,
+                    )
+                })
+            }
+        }
+
+        let mut freed = 0;
+        let n = candidates
+            .iter()
+            .take_while(|(_, size, _)| {
+                let enough = freed >= needed;
+                freed += size;
+                !enough
+            })
+            .count();
+        if freed < needed {
+            return Err(Error::MemoryBudget);
+        }
+
+        tracing::warn!(
+            budget,
+            evictions = n,
+            "Memory budget of record store reached, evicting records"
+        );
+        for (candidate, _, _) in candidates.into_iter().take(n) {
+            match candidate {
+                Candidate::Record(key) => {
+                    if let Some(record) = self.records.remove(&key) {
+                        self.memory_used -= record_size(&record);
+                        self.num_evicted_records += 1;
+                        if self.evicted_records.len() == MAX_BUFFERED_EVICTIONS {
+                            self.evicted_records.pop_front();
+                        }
+                        self.evicted_records.push_back(record);
+                    }
+                }
+                Candidate::Provider(key, provider) => {
+                    self.evict(&key, &provider, ProviderEvictionReason::MemoryBudget)
+                }
+            }
+        }
+        Ok(())
     }
+
+    fn take_provider(&mut self, key: &Key, provider: &PeerId) -> Option<ProviderRecord> {
+        let hash_map::Entry::Occupied(mut e) = self.providers.entry(key.clone()) else {
+            return None;
+        };
+        let providers = e.get_mut();
+        let i = providers.iter().position(|p| &p.provider == provider)?;
+        let p = providers.remove(i);
+        if providers.is_empty() {
+            e.remove();
+        }
+        self.num_provider_records -= 1;
+        self.memory_used -= provider_size(&p);
+        self.provided.remove(&p);
+        self.recency.remove(key, *provider);
+        Some(p)
+    }
+}
+
+/// The estimated memory used by a record, in bytes.
+fn record_size(r: &Record) -> usize {
+    mem::size_of::<Record>() + r.key.as_ref().len() + r.value.len()
+}
+
+/// The estimated memory used by a provider record, in bytes.
+fn provider_size(r: &ProviderRecord) -> usize {
+    mem::size_of::<ProviderRecord>()
+        + r.key.as_ref().len()
+        + r.addresses.iter().map(|a| a.len()).sum::<usize>()
 }
 
 impl RecordStore for MemoryStore {
@@ -113,24 +489,25 @@ impl RecordStore for MemoryStore {
         }
 
         let num_records = self.records.len();
+        let replaced = self.records.get(&r.key).map(record_size);
+        if replaced.is_none() && num_records >= self.config.max_records {
+            return Err(Error::MaxRecords);
+        }
+        let size = record_size(&r);
+        self.make_room(size, replaced.unwrap_or(0), Some(&r.key), None)?;
 
-        match self.records.entry(r.key.clone()) {
-            hash_map::Entry::Occupied(mut e) => {
-                e.insert(r);
-            }
-            hash_map::Entry::Vacant(e) => {
-                if num_records >= self.config.max_records {
-                    return Err(Error::MaxRecords);
-                }
-                e.insert(r);
-            }
+        self.memory_used += size;
+        if let Some(old) = self.records.insert(r.key.clone(), r) {
+            self.memory_used -= record_size(&old);
         }
 
         Ok(())
     }
 
     fn remove(&mut self, k: &Key) {
-        self.records.remove(k);
+        if let Some(r) = self.records.remove(k) {
+            self.memory_used -= record_size(&r);
+        }
     }
 
     fn records(&self) -> Self::RecordsIter<'_> {
@@ -138,52 +515,62 @@ impl RecordStore for MemoryStore {
     }
 
     fn add_provider(&mut self, record: ProviderRecord) -> Result<()> {
-        let num_keys = self.providers.len();
-
-        // Obtain the entry
-        let providers = match self.providers.entry(record.key.clone()) {
-            e @ hash_map::Entry::Occupied(_) => e,
-            e @ hash_map::Entry::Vacant(_) => {
-                if self.config.max_provided_keys == num_keys {
-                    return Err(Error::MaxProvidedKeys);
-                }
-                e
-            }
+        let existing = self.providers.get(&record.key);
+
+        if existing.is_none() && self.config.max_provided_keys == self.providers.len() {
+            return Err(Error::MaxProvidedKeys);
         }
-        .or_insert_with(Default::default);
 
-        if let Some(i) = providers.iter().position(|p| p.provider == record.provider) {
+        if let Some(i) =
+            existing.and_then(|ps| ps.iter().position(|p| p.provider == record.provider))
+        {
             // In-place update of an existing provider record.
-            providers.as_mut()[i] = record;
-        } else {
-            // It is a new provider record for that key.
-            let local_key = self.local_key.clone();
-            let key = kbucket::Key::new(record.key.clone());
-            let provider = kbucket::Key::from(record.provider);
-            if let Some(i) = providers.iter().position(|p| {
-                let pk = kbucket::Key::from(p.provider);
-                provider.distance(&key) < pk.distance(&key)
-            }) {
-                // Insert the new provider.
-                if local_key.preimage() == &record.provider {
-                    self.provided.insert(record.clone());
-                }
-                providers.insert(i, record);
-                // Remove the excess provider, if any.
-                if providers.len() > self.config.max_providers_per_key {
-                    if let Some(p) = providers.pop() {
-                        self.provided.remove(&p);
-                    }
-                }
-            } else if providers.len() < self.config.max_providers_per_key {
-                // The distance of the new provider to the key is larger than
-                // the distance of any existing provider, but there is still room.
-                if local_key.preimage() == &record.provider {
-                    self.provided.insert(record.clone());
-                }
-                providers.push(record);
+            let replaced = existing.map_or(0, |ps| provider_size(&ps[i]));
+            let size = provider_size(&record);
+            self.make_room(size, replaced, None, Some((&record.key, &record.provider)))?;
+            if self.is_local(&record.provider) {
+                self.provided.replace(record.clone());
             }
+            self.touch(&record);
+            if let Some(providers) = self.providers.get_mut(&record.key) {
+                self.memory_used = self.memory_used + size - provider_size(&providers[i]);
+                providers[i] = record;
+            }
+            return Ok(());
         }
+
+        // It is a new provider record for that key, make room for it if necessary.
+        let key = self.hasher.key(record.key.clone());
+        let distance = self.hasher.peer(record.provider).distance(&key);
+        if existing.map_or(0, |ps| ps.len()) >= self.config.max_providers_per_key {
+            match self.key_eviction_candidate(&record.key, distance) {
+                Some(p) => self.evict(&record.key, &p, ProviderEvictionReason::MaxProvidersPerKey),
+                // The provider is not to be stored in favour of the existing ones.
+                None => return Ok(()),
+            }
+        } else if self.num_provider_records >= self.config.max_provider_records {
+            match self.store_eviction_candidate(distance) {
+                Some((k, p)) => self.evict(&k, &p, ProviderEvictionReason::MaxProviderRecords),
+                None => return Err(Error::MaxProvidedKeys),
+            }
+        }
+
+        let size = provider_size(&record);
+        self.make_room(size, 0, None, None)?;
+
+        // Insert the new provider, keeping the providers ordered by distance to the key.
+        if self.is_local(&record.provider) {
+            self.provided.insert(record.clone());
+        }
+        self.touch(&record);
+        self.num_provider_records += 1;
+        self.memory_used += size;
+        let providers = self.providers.entry(record.key.clone()).or_default();
+        let i = providers
+            .iter()
+            .position(|p| distance < self.hasher.peer(p.provider).distance(&key))
+            .unwrap_or(providers.len());
+        providers.insert(i, record);
         Ok(())
     }
 
@@ -197,17 +584,12 @@ impl RecordStore for MemoryStore {
         self.provided.iter().map(Cow::Borrowed)
     }
 
+    fn provider_keys(&self) -> Vec<Key> {
+        self.providers.keys().cloned().collect()
+    }
+
     fn remove_provider(&mut self, key: &Key, provider: &PeerId) {
-        if let hash_map::Entry::Occupied(mut e) = self.providers.entry(key.clone()) {
-            let providers = e.get_mut();
-            if let Some(i) = providers.iter().position(|p| &p.provider == provider) {
-                let p = providers.remove(i);
-                self.provided.remove(&p);
-            }
-            if providers.is_empty() {
-                e.remove();
-            }
-        }
+        self.take_provider(key, provider);
     }
 }
 
@@ -217,6 +599,7 @@ mod tests {
     use crate::SHA_256_MH;
     use quickcheck::*;
     use rand::Rng;
+    use std::time::Duration;
 
     fn random_multihash() -> Multihash<64> {
         Multihash::wrap(SHA_256_MH, &rand::thread_rng().gen::<[u8; 32]>()).unwrap()
@@ -302,6 +685,30 @@ mod tests {
         assert_eq!(vec![rec.clone()], store.providers(&rec.key).to_vec());
     }
 
+    #[test]
+    fn set_expirations() {
+        let id = PeerId::random();
+        let mut store = MemoryStore::new(id);
+        let expires = Some(Instant::now());
+
+        let rec = ProviderRecord::new(random_multihash(), id, Vec::new());
+        assert!(store.add_provider(rec.clone()).is_ok());
+        assert!(store
+            .set_provider_expiration(&rec.key, &id, expires)
+            .unwrap());
+        assert_eq!(store.provider(&rec.key, &id).unwrap().expires, expires);
+        assert_eq!(store.provided().next().unwrap().expires, expires);
+        assert!(!store
+            .set_provider_expiration(&rec.key, &PeerId::random(), expires)
+            .unwrap());
+
+        let r = Record::new(random_multihash(), vec![1, 2, 3]);
+        assert!(!store.set_record_expiration(&r.key, expires).unwrap());
+        store.put(r.clone()).unwrap();
+        assert!(store.set_record_expiration(&r.key, expires).unwrap());
+        assert_eq!(store.get(&r.key).unwrap().expires, expires);
+    }
+
     #[test]
     fn max_provided_keys() {
         let mut store = MemoryStore::new(PeerId::random());
@@ -319,4 +726,159 @@ mod tests {
             _ => panic!("Unexpected result"),
         }
     }
+
+    #[test]
+    fn max_provider_records_evicts_farthest() {
+        let config = MemoryStoreConfig {
+            max_provider_records: 10,
+            ..Default::default()
+        };
+        let mut store = MemoryStore::with_config(PeerId::random(), config);
+        let mut records = (0..11)
+            .map(|_| ProviderRecord::new(random_multihash(), PeerId::random(), Vec::new()))
+            .collect::<Vec<_>>();
+        records.sort_by_key(distance);
+        let (farthest, rest) = records.split_last().unwrap();
+
+        store.add_provider(farthest.clone()).unwrap();
+        for r in rest {
+            store.add_provider(r.clone()).unwrap();
+        }
+
+        assert_eq!(store.num_provider_records(), 10);
+        assert!(store.providers(&farthest.key).is_empty());
+        let evicted = store.take_evicted_providers();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].record, *farthest);
+        assert_eq!(
+            evicted[0].reason,
+            ProviderEvictionReason::MaxProviderRecords
+        );
+        assert_eq!(store.num_evicted_providers(), 1);
+
+        // A provider farther than all stored ones is rejected.
+        match store.add_provider(farthest.clone()) {
+            Err(Error::MaxProvidedKeys) => {}
+            _ => panic!("Unexpected result"),
+        }
+    }
+
+    #[test]
+    fn least_recently_used_provider_evicted() {
+        let id = PeerId::random();
+        let config = MemoryStoreConfig {
+            max_provider_records: 3,
+            provider_eviction: ProviderEvictionPolicy::LeastRecentlyUsed,
+            ..Default::default()
+        };
+        let mut store = MemoryStore::with_config(id, config);
+        let local = ProviderRecord::new(random_multihash(), id, Vec::new());
+        let a = ProviderRecord::new(random_multihash(), PeerId::random(), Vec::new());
+        let b = ProviderRecord::new(random_multihash(), PeerId::random(), Vec::new());
+        let c = ProviderRecord::new(random_multihash(), PeerId::random(), Vec::new());
+
+        store.add_provider(local.clone()).unwrap();
+        store.add_provider(a.clone()).unwrap();
+        store.add_provider(b.clone()).unwrap();
+        // Refreshing `a` makes `b` the least recently used provider record.
+        store.add_provider(a.clone()).unwrap();
+        store.add_provider(c.clone()).unwrap();
+
+        assert!(store.providers(&b.key).is_empty());
+        assert_eq!(store.providers(&a.key), vec![a]);
+        assert_eq!(store.providers(&c.key), vec![c]);
+        assert_eq!(store.provided().count(), 1);
+        let evicted = store.take_evicted_providers();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].record, b);
+    }
+
+    #[test]
+    fn memory_budget_evicts_expiring_first() {
+        let id = PeerId::random();
+        let record = |expires| {
+            let mut r = Record::new(random_multihash(), vec![0; 100]);
+            r.publisher = Some(PeerId::random());
+            r.expires = expires;
+            r
+        };
+        let now = Instant::now();
+        let a = record(None);
+        let b = record(Some(now + Duration::from_secs(10)));
+        let c = record(Some(now + Duration::from_secs(20)));
+        let config = MemoryStoreConfig {
+            max_memory_bytes: Some(2 * record_size(&a)),
+            ..Default::default()
+        };
+        let mut store = MemoryStore::with_config(id, config);
+
+        store.put(a.clone()).unwrap();
+        store.put(b.clone()).unwrap();
+        assert_eq!(store.memory_used(), 2 * record_size(&a));
+        store.put(c.clone()).unwrap();
+
+        assert!(store.get(&a.key).is_some());
+        assert!(store.get(&b.key).is_none());
+        assert!(store.get(&c.key).is_some());
+        assert_eq!(store.take_evicted_records(), vec![b]);
+        assert_eq!(store.num_evicted_records(), 1);
+        assert_eq!(store.memory_used(), 2 * record_size(&a));
+
+        store.remove(&a.key);
+        store.remove(&c.key);
+        assert_eq!(store.memory_used(), 0);
+    }
+
+    #[test]
+    fn memory_budget_keeps_local_records() {
+        let id = PeerId::random();
+        let mut local = Record::new(random_multihash(), vec![0; 100]);
+        local.publisher = Some(id);
+        let provider = ProviderRecord::new(random_multihash(), id, Vec::new());
+        let config = MemoryStoreConfig {
+            max_memory_bytes: Some(record_size(&local) + provider_size(&provider)),
+            memory_eviction: MemoryEvictionPolicy::Size,
+            ..Default::default()
+        };
+        let mut store = MemoryStore::with_config(id, config);
+
+        store.put(local.clone()).unwrap();
+        store.add_provider(provider).unwrap();
+
+        let remote = ProviderRecord::new(random_multihash(), PeerId::random(), Vec::new());
+        assert!(matches!(
+            store.add_provider(remote),
+            Err(Error::MemoryBudget)
+        ));
+        assert!(store.get(&local.key).is_some());
+        assert_eq!(store.provided().count(), 1);
+        assert!(store.take_evicted_providers().is_empty());
+    }
+
+    #[test]
+    fn memory_budget_evicts_providers_first() {
+        let id = PeerId::random();
+        let mut record = Record::new(random_multihash(), vec![0; 100]);
+        record.expires = Some(Instant::now());
+        let provider = ProviderRecord::new(random_multihash(), PeerId::random(), Vec::new());
+        let config = MemoryStoreConfig {
+            max_memory_bytes: Some(record_size(&record) + provider_size(&provider)),
+            memory_eviction: MemoryEvictionPolicy::ProvidersBeforeRecords,
+            ..Default::default()
+        };
+        let mut store = MemoryStore::with_config(id, config);
+
+        store.put(record.clone()).unwrap();
+        store.add_provider(provider.clone()).unwrap();
+        let other = ProviderRecord::new(random_multihash(), PeerId::random(), Vec::new());
+        store.add_provider(other.clone()).unwrap();
+
+        assert!(store.get(&record.key).is_some());
+        assert!(store.providers(&provider.key).is_empty());
+        assert_eq!(store.providers(&other.key), vec![other]);
+        let evicted = store.take_evicted_providers();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].record, provider);
+        assert_eq!(evicted[0].reason, ProviderEvictionReason::MemoryBudget);
+    }
 }
@@ -0,0 +1,349 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use super::*;
+
+use std::collections::{hash_map, BTreeMap, HashMap};
+use std::iter;
+use std::sync::Mutex;
+
+/// A `RecordStore` keeping a bounded set of recently used records in memory, the
+/// hot tier, and all other records in another `RecordStore`, the cold tier.
+///
+/// Records are put into the hot tier, demoting the least recently used record to the
+/// cold tier once [`TieredStoreConfig::max_hot_records`] is reached. Records read from
+/// the cold tier are promoted into the hot tier on the next write to the store, as
+/// reads only borrow the store. Every record is held by exactly one of the tiers.
+///
+/// Provider records are kept in the cold tier.
+pub struct TieredStore<C> {
+    /// The configuration of the store.
+    config: TieredStoreConfig,
+    /// The records of the hot tier.
+    hot: HashMap<Key, Record>,
+    /// The next tick to assign.
+    next_tick: u64,
+    /// The tick of every record of the hot tier.
+    ticks: HashMap<Key, u64>,
+    /// The records of the hot tier, least recently used first.
+    order: BTreeMap<u64, Key>,
+    /// The keys read since the last write, applied to the tiers on the next write.
+    accessed: Mutex<Vec<Key>>,
+    /// The cold tier.
+    cold: C,
+}
+
+/// Configuration for a `TieredStore`.
+#[derive(Debug, Clone)]
+pub struct TieredStoreConfig {
+    /// The maximum number of records in the hot tier.
+    pub max_hot_records: usize,
+    /// The maximum size of record values in the hot tier, in bytes.
+    ///
+    /// Larger records are put into the cold tier directly.
+    pub max_value_bytes: usize,
+}
+
+impl Default for TieredStoreConfig {
+    fn default() -> Self {
+        Self {
+            max_hot_records: 1024,
+            max_value_bytes: 65 * 1024,
+        }
+    }
+}
+
+impl<C> TieredStore<C>
+where
+    C: RecordStore,
+{
+    /// Creates a new `TieredStore` with the given cold tier and a default configuration.
+    pub fn new(cold: C) -> Self {
+        Self::with_config(cold, Default::default())
+    }
+
+    /// Creates a new `TieredStore` with the given cold tier and configuration.
+    pub fn with_config(cold: C, config: TieredStoreConfig) -> Self {
+        TieredStore {
+            config,
+            hot: HashMap::default(),
+            next_tick: 0,
+            ticks: HashMap::default(),
+            order: BTreeMap::default(),
+            accessed: Mutex::default(),
+            cold,
+        }
+    }
+
+    /// Returns the number of records in the hot tier.
+    pub fn num_hot_records(&self) -> usize {
+        self.hot.len()
+    }
+
+    /// Returns whether the record with the given key is in the hot tier.
+    pub fn is_hot(&self, k: &Key) -> bool {
+        self.hot.contains_key(k)
+    }
+
+    /// Returns the cold tier.
+    pub fn cold(&self) -> &C {
+        &self.cold
+    }
+
+    /// Returns the cold tier mutably, e.g. to remove expired records from it.
+    ///
+    /// Records put into the cold tier directly must not be in the hot tier.
+    pub fn cold_mut(&mut self) -> &mut C {
+        &mut self.cold
+    }
+
+    /// Consumes the store, returning the cold tier with all records of the hot tier
+    /// demoted to it.
+    pub fn into_cold(mut self) -> Result<C> {
+        for (_, record) in self.hot.drain() {
+            self.cold.put(record)?;
+        }
+        Ok(self.cold)
+    }
+
+    fn touch(&mut self, k: &Key) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        if let Some(old) = self.ticks.insert(k.clone(), tick) {
+            self.order.remove(&old);
+        }
+        self.order.insert(tick, k.clone());
+    }
+
+    fn take_hot(&mut self, k: &Key) -> Option<Record> {
+        let record = self.hot.remove(k)?;
+        if let Some(tick) = self.ticks.remove(k) {
+            self.order.remove(&tick);
+        }
+        Some(record)
+    }
+
+    /// Promotes the records read since the last write and refreshes the recency of
+    /// those in the hot tier.
+    fn apply_accesses(&mut self) {
+        let accessed = std::mem::take(&mut *self.accessed.lock().expect("lock not to be poisoned"));
+        for k in accessed {
+            if self.hot.contains_key(&k) {
+                self.touch(&k);
+                continue;
+            }
+            let Some(record) = self.cold.get(&k).map(Cow::into_owned) else {
+                continue;
+            };
+            if self.insert_hot(record).is_none() {
+                self.cold.remove(&k);
+            }
+        }
+    }
+
+    /// Inserts a record that is not in the hot tier, demoting the least recently used
+    /// record if the hot tier is full.
+    ///
+    /// Returns the record if the hot tier has no room for it.
+    fn insert_hot(&mut self, r: Record) -> Option<Record> {
+        if r.value.len() >= self.config.max_value_bytes || self.config.max_hot_records == 0 {
+            return Some(r);
+        }
+        if self.hot.len() >= self.config.max_hot_records {
+            let lru = self
+                .order
+                .values()
+                .next()
+                .cloned()
+                .expect("a full hot tier to have a least recently used record");
+            let demoted = self.take_hot(&lru).expect("ordered record to be hot");
+            if let Err(e) = self.cold.put(demoted.clone()) {
+                tracing::debug!(key=?lru, "Failed to demote record to the cold tier: {e}");
+                self.hot.insert(lru.clone(), demoted);
+                self.touch(&lru);
+                return Some(r);
+            }
+        }
+        self.touch(&r.key);
+        self.hot.insert(r.key.clone(), r);
+        None
+    }
+}
+
+impl<C> RecordStore for TieredStore<C>
+where
+    C: RecordStore,
+{
+    type RecordsIter<'a>
+        = iter::Chain<
+        iter::Map<hash_map::Values<'a, Key, Record>, fn(&'a Record) -> Cow<'a, Record>>,
+        C::RecordsIter<'a>,
+    >
+    where
+        Self: 'a;
+
+    type ProvidedIter<'a>
+        = C::ProvidedIter<'a>
+    where
+        Self: 'a;
+
+    fn get(&self, k: &Key) -> Option<Cow<'_, Record>> {
+        let record = self
+            .hot
+            .get(k)
+            .map(Cow::Borrowed)
+            .or_else(|| self.cold.get(k))?;
+
+        let mut accessed = self.accessed.lock().expect("lock not to be poisoned");
+        if accessed.len() < self.config.max_hot_records {
+            accessed.push(k.clone());
+        }
+
+        Some(record)
+    }
+
+    fn put(&mut self, r: Record) -> Result<()> {
+        self.apply_accesses();
+
+        if let Some(existing) = self.hot.get_mut(&r.key) {
+            *existing = r.clone();
+            self.touch(&r.key);
+            return Ok(());
+        }
+
+        let key = r.key.clone();
+        match self.insert_hot(r) {
+            None => {
+                self.cold.remove(&key);
+                Ok(())
+            }
+            Some(r) => self.cold.put(r),
+        }
+    }
+
+    fn remove(&mut self, k: &Key) {
+        self.apply_accesses();
+
+        if self.take_hot(k).is_none() {
+            self.cold.remove(k);
+        }
+    }
+
+    fn records<'a>(&'a self) -> Self::RecordsIter<'a> {
+        let hot: fn(&'a Record) -> Cow<'a, Record> = Cow::Borrowed;
+        self.hot.values().map(hot).chain(self.cold.records())
+    }
+
+    fn add_provider(&mut self, record: ProviderRecord) -> Result<()> {
+        self.apply_accesses();
+        self.cold.add_provider(record)
+    }
+
+    fn providers(&self, key: &Key) -> Vec<ProviderRecord> {
+        self.cold.providers(key)
+    }
+
+    fn provided(&self) -> Self::ProvidedIter<'_> {
+        self.cold.provided()
+    }
+
+    fn remove_provider(&mut self, k: &Key, p: &PeerId) {
+        self.apply_accesses();
+        self.cold.remove_provider(k, p)
+    }
+
+    fn provider_keys(&self) -> Vec<Key> {
+        self.cold.provider_keys()
+    }
+
+    fn provider(&self, k: &Key, p: &PeerId) -> Option<ProviderRecord> {
+        self.cold.provider(k, p)
+    }
+
+    fn set_provider_expiration(
+        &mut self,
+        k: &Key,
+        p: &PeerId,
+        expires: Option<Instant>,
+    ) -> Result<bool> {
+        self.apply_accesses();
+        self.cold.set_provider_expiration(k, p, expires)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::store::MemoryStore;
+    use crate::SHA_256_MH;
+    use rand::Rng;
+
+    fn random_record() -> Record {
+        let key = Multihash::<64>::wrap(SHA_256_MH, &rand::thread_rng().gen::<[u8; 32]>()).unwrap();
+        Record::new(Key::from(key), vec![1])
+    }
+
+    fn tiered(max_hot_records: usize) -> TieredStore<MemoryStore> {
+        TieredStore::with_config(
+            MemoryStore::new(PeerId::random()),
+            TieredStoreConfig {
+                max_hot_records,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn least_recently_used_record_is_demoted() {
+        let mut store = tiered(2);
+        let [a, b, c] = [random_record(), random_record(), random_record()];
+
+        store.put(a.clone()).unwrap();
+        store.put(b.clone()).unwrap();
+        // Reading `a` makes `b` the least recently used record.
+        assert!(store.get(&a.key).is_some());
+        store.put(c.clone()).unwrap();
+
+        assert!(store.is_hot(&a.key) && store.is_hot(&c.key));
+        assert_eq!(store.cold().get(&b.key).as_deref(), Some(&b));
+        assert_eq!(store.get(&b.key).as_deref(), Some(&b));
+        assert_eq!(store.records().count(), 3);
+    }
+
+    #[test]
+    fn cold_records_are_promoted_on_access() {
+        let mut store = tiered(1);
+        let [a, b] = [random_record(), random_record()];
+
+        store.put(a.clone()).unwrap();
+        store.put(b.clone()).unwrap();
+        assert!(store.is_hot(&b.key) && !store.is_hot(&a.key));
+
+        assert!(store.get(&a.key).is_some());
+        store.remove(&Key::new(&[0u8]));
+        assert!(store.is_hot(&a.key) && !store.is_hot(&b.key));
+        assert!(store.cold().get(&a.key).is_none());
+        assert_eq!(store.num_hot_records(), 1);
+
+        store.remove(&b.key);
+        assert!(store.get(&b.key).is_none());
+        assert_eq!(store.into_cold().unwrap().records().count(), 1);
+    }
+}
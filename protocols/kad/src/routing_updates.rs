@@ -0,0 +1,205 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Coalescing of routing table updates.
+//!
+//! Instead of reporting every change to the routing table individually, changes can
+//! be aggregated into [`RoutingUpdates`] reported at most once per interval, e.g. to
+//! avoid a flood of events while bootstrapping. Components only interested in the
+//! aggregate changes can subscribe to the [`RoutingUpdates`] via a channel.
+
+use futures::channel::mpsc;
+use futures::FutureExt;
+use futures_timer::Delay;
+use std::collections::BTreeSet;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The number of [`RoutingUpdates`] buffered per subscriber.
+const SUBSCRIBER_BUFFER_SIZE: usize = 16;
+
+/// Changes to the routing table, aggregated over a period of time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoutingUpdates {
+    /// The number of peers that were added to the routing table.
+    pub new_peers: usize,
+    /// The number of peers in the routing table whose addresses changed.
+    pub updated_peers: usize,
+    /// The number of peers that were evicted from the routing table
+    /// to make room for new peers.
+    pub evicted_peers: usize,
+    /// The indices of the buckets that changed, in ascending order.
+    ///
+    /// The bucket with index `i` holds the peers at a distance
+    /// in the range `[2^i, 2^(i+1))` from the local key.
+    pub buckets: Vec<u32>,
+}
+
+/// Aggregates changes to the routing table and reports them
+/// to subscribers and, if coalescing, to the behaviour.
+pub(crate) struct RoutingUpdatesCoalescer {
+    /// The interval at which changes are reported, if coalescing.
+    interval: Option<Duration>,
+    /// The delay until the pending changes are reported.
+    delay: Option<Delay>,
+    new_peers: usize,
+    updated_peers: usize,
+    evicted_peers: usize,
+    buckets: BTreeSet<u32>,
+    subscribers: Vec<mpsc::Sender<RoutingUpdates>>,
+}
+
+impl RoutingUpdatesCoalescer {
+    pub(crate) fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            delay: None,
+            new_peers: 0,
+            updated_peers: 0,
+            evicted_peers: 0,
+            buckets: BTreeSet::new(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Whether changes are reported at most once per interval instead of individually.
+    pub(crate) fn is_coalescing(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    /// Adds a new subscriber to the aggregated changes.
+    pub(crate) fn subscribe(&mut self) -> mpsc::Receiver<RoutingUpdates> {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER_SIZE);
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Records a change to the bucket with the given index.
+    ///
+    /// If not coalescing, the change is reported to the subscribers right away.
+    pub(crate) fn on_update(&mut self, bucket: u32, is_new_peer: bool, evicted_peer: bool) {
+        if !self.is_coalescing() && self.subscribers.is_empty() {
+            return;
+        }
+        if is_new_peer {
+            self.new_peers += 1;
+        } else {
+            self.updated_peers += 1;
+        }
+        if evicted_peer {
+            self.evicted_peers += 1;
+        }
+        self.buckets.insert(bucket);
+
+        match self.interval {
+            Some(interval) => {
+                self.delay.get_or_insert_with(|| Delay::new(interval));
+            }
+            None => {
+                let updates = self.take();
+                self.notify(&updates);
+            }
+        }
+    }
+
+    /// Polls for the changes aggregated over the last interval, if coalescing.
+    ///
+    /// The changes are reported to the subscribers as well.
+    pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<RoutingUpdates> {
+        let Some(delay) = self.delay.as_mut() else {
+            return Poll::Pending;
+        };
+        if delay.poll_unpin(cx).is_pending() {
+            return Poll::Pending;
+        }
+        self.delay = None;
+        let updates = self.take();
+        self.notify(&updates);
+        Poll::Ready(updates)
+    }
+
+    fn take(&mut self) -> RoutingUpdates {
+        RoutingUpdates {
+            new_peers: std::mem::take(&mut self.new_peers),
+            updated_peers: std::mem::take(&mut self.updated_peers),
+            evicted_peers: std::mem::take(&mut self.evicted_peers),
+            buckets: std::mem::take(&mut self.buckets).into_iter().collect(),
+        }
+    }
+
+    /// Sends the changes to the subscribers, dropping them for subscribers
+    /// that do not keep up and removing closed subscriptions.
+    fn notify(&mut self, updates: &RoutingUpdates) {
+        self.subscribers
+            .retain_mut(|tx| match tx.try_send(updates.clone()) {
+                Ok(()) => true,
+                Err(e) => !e.is_disconnected(),
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future::poll_fn, StreamExt};
+
+    #[test]
+    fn coalesces_updates() {
+        let mut coalescer = RoutingUpdatesCoalescer::new(Some(Duration::from_millis(10)));
+        let mut rx = coalescer.subscribe();
+        coalescer.on_update(255, true, false);
+        coalescer.on_update(254, true, true);
+        coalescer.on_update(255, false, false);
+
+        let updates = block_on(poll_fn(|cx| coalescer.poll(cx)));
+        let expected = RoutingUpdates {
+            new_peers: 2,
+            updated_peers: 1,
+            evicted_peers: 1,
+            buckets: vec![254, 255],
+        };
+        assert_eq!(updates, expected);
+        assert_eq!(block_on(rx.next()), Some(expected));
+
+        // Nothing is reported without further changes.
+        block_on(poll_fn(|cx| {
+            assert!(coalescer.poll(cx).is_pending());
+            Poll::Ready(())
+        }));
+    }
+
+    #[test]
+    fn notifies_subscribers_of_every_update_if_not_coalescing() {
+        let mut coalescer = RoutingUpdatesCoalescer::new(None);
+        let mut rx = coalescer.subscribe();
+        coalescer.on_update(255, true, false);
+        coalescer.on_update(200, false, false);
+
+        let first = block_on(rx.next()).unwrap();
+        assert_eq!((first.new_peers, first.buckets), (1, vec![255]));
+        let second = block_on(rx.next()).unwrap();
+        assert_eq!((second.updated_peers, second.buckets), (1, vec![200]));
+
+        // Closed subscriptions are dropped.
+        drop(rx);
+        coalescer.on_update(255, true, false);
+        assert!(coalescer.subscribers.is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Scoring of peers for the admission to the routing table.
+//!
+//! Applications set a [`PeerScorer`] via
+//! [`Config::set_peer_scorer`](crate::Config::set_peer_scorer), which is informed
+//! of the misbehaviour of peers observed by the [`Behaviour`](crate::Behaviour) or
+//! reported via [`Behaviour::report_misbehaviour`](crate::Behaviour::report_misbehaviour).
+
+use libp2p_identity::PeerId;
+use std::fmt;
+use std::sync::Arc;
+
+/// Scores peers based on their misbehaviour and decides whether they are admitted to
+/// the routing table.
+///
+/// The scorer is consulted before a peer is inserted into a k-bucket, including as
+/// a pending entry, and before a peer in a k-bucket is promoted to be connected, which
+/// protects it from being replaced by pending entries. Peers not admitted are neither
+/// inserted nor promoted.
+pub trait PeerScorer: Send + Sync + 'static {
+    /// Records misbehaviour of the given peer, e.g. to lower its score.
+    fn on_misbehaviour(&self, peer: &PeerId, misbehaviour: Misbehaviour);
+
+    /// Returns whether the given peer may be inserted into or promoted within
+    /// the routing table.
+    fn admits(&self, peer: &PeerId) -> bool;
+}
+
+/// Misbehaviour of a peer reported to a [`PeerScorer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misbehaviour {
+    /// The peer sent a record rejected by a [`RecordValidator`](crate::RecordValidator).
+    InvalidRecord,
+    /// A request to the peer timed out.
+    Timeout,
+    /// The peer responded to a request with an unexpected message.
+    UnexpectedMessage,
+    /// The application observed misbehaviour of the peer, e.g. because it provided
+    /// content it does not have.
+    Application,
+}
+
+/// The [`PeerScorer`] set in the [`Config`](crate::Config), if any.
+#[derive(Clone, Default)]
+pub(crate) struct Scorer {
+    scorer: Option<Arc<dyn PeerScorer>>,
+}
+
+impl Scorer {
+    pub(crate) fn new(scorer: impl PeerScorer) -> Self {
+        Self {
+            scorer: Some(Arc::new(scorer)),
+        }
+    }
+
+    pub(crate) fn on_misbehaviour(&self, peer: &PeerId, misbehaviour: Misbehaviour) {
+        if let Some(scorer) = &self.scorer {
+            tracing::debug!(%peer, ?misbehaviour, "Peer misbehaved");
+            scorer.on_misbehaviour(peer, misbehaviour);
+        }
+    }
+
+    /// Returns whether the peer is admitted, i.e. `true` if there is no [`PeerScorer`].
+    pub(crate) fn admits(&self, peer: &PeerId) -> bool {
+        self.scorer
+            .as_ref()
+            .map_or(true, |scorer| scorer.admits(peer))
+    }
+}
+
+impl fmt::Debug for Scorer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scorer")
+            .field("enabled", &self.scorer.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Signed peer records authenticating the addresses of peers in responses, see
+//! [`Behaviour::add_signed_peer_record`](crate::Behaviour::add_signed_peer_record) and
+//! [`Config::set_require_signed_peer_records`](crate::Config::set_require_signed_peer_records).
+
+use crate::protocol::KadPeer;
+use libp2p_core::PeerRecord;
+use libp2p_identity::PeerId;
+use std::collections::{HashMap, VecDeque};
+
+/// The maximum number of signed peer records of remote peers kept, beyond which the
+/// records added first are dropped.
+const MAX_RECORDS: usize = 1024;
+
+#[derive(Debug)]
+pub(crate) struct SignedPeerRecords {
+    local_peer_id: PeerId,
+    /// Whether peers without a valid signed peer record are dropped from responses.
+    require: bool,
+    local: Option<PeerRecord>,
+    records: HashMap<PeerId, PeerRecord>,
+    /// The remote peers of `records` in the order their records were first added.
+    order: VecDeque<PeerId>,
+}
+
+impl SignedPeerRecords {
+    pub(crate) fn new(local_peer_id: PeerId, require: bool) -> Self {
+        Self {
+            local_peer_id,
+            require,
+            local: None,
+            records: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Adds a record, unless a record of the peer with a higher sequence number is known.
+    pub(crate) fn insert(&mut self, record: PeerRecord) {
+        let peer = record.peer_id();
+        if peer == self.local_peer_id {
+            if self
+                .local
+                .as_ref()
+                .map_or(true, |r| r.seq() <= record.seq())
+            {
+                self.local = Some(record);
+            }
+            return;
+        }
+
+        match self.records.get_mut(&peer) {
+            Some(known) if known.seq() > record.seq() => {}
+            Some(known) => *known = record,
+            None => {
+                if self.order.len() == MAX_RECORDS {
+                    if let Some(oldest) = self.order.pop_front() {
+                        self.records.remove(&oldest);
+                    }
+                }
+                self.records.insert(peer, record);
+                self.order.push_back(peer);
+            }
+        }
+    }
+
+    fn get(&self, peer: &PeerId) -> Option<&PeerRecord> {
+        if peer == &self.local_peer_id {
+            return self.local.as_ref();
+        }
+        self.records.get(peer)
+    }
+
+    /// Attaches the known record of the peer, if any, to a peer sent to a remote.
+    pub(crate) fn attach(&self, peer: &mut KadPeer) {
+        if peer.signed_record.is_none() {
+            peer.signed_record = self.get(&peer.node_id).cloned().map(Box::new);
+        }
+    }
+
+    /// Verifies the peers received in a response, replacing their addresses by the ones of
+    /// their signed peer records, if any.
+    ///
+    /// Peers without a signed peer record are dropped if records are required.
+    pub(crate) fn verify(&mut self, peers: Vec<KadPeer>) -> Vec<KadPeer> {
+        peers
+            .into_iter()
+            .filter_map(|mut peer| {
+                match &peer.signed_record {
+                    Some(record) => {
+                        let node_id = peer.node_id;
+                        peer.multiaddrs = record
+                            .addresses()
+                            .iter()
+                            .filter_map(|a| a.clone().with_p2p(node_id).ok())
+                            .collect();
+                        self.insert((**record).clone());
+                    }
+                    None if self.require => {
+                        tracing::debug!(peer=%peer.node_id, "Dropping peer without signed peer record");
+                        return None;
+                    }
+                    None => {}
+                }
+                Some(peer)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ConnectionType;
+    use libp2p_core::Multiaddr;
+    use libp2p_identity::Keypair;
+
+    fn peer(record: Option<PeerRecord>, node_id: PeerId) -> KadPeer {
+        KadPeer {
+            node_id,
+            multiaddrs: vec!["/ip4/6.6.6.6/tcp/4001".parse().unwrap()],
+            connection_ty: ConnectionType::NotConnected,
+            signed_record: record.map(Box::new),
+        }
+    }
+
+    #[test]
+    fn addresses_of_signed_records_take_precedence() {
+        let key = Keypair::generate_ed25519();
+        let address = "/ip4/1.2.3.4/tcp/4001".parse::<Multiaddr>().unwrap();
+        let record = PeerRecord::new(&key, vec![address.clone()]).unwrap();
+        let (signed, unsigned) = (key.public().to_peer_id(), PeerId::random());
+
+        let mut records = SignedPeerRecords::new(PeerId::random(), false);
+        let peers = records.verify(vec![peer(Some(record), signed), peer(None, unsigned)]);
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].multiaddrs, vec![address.with_p2p(signed).unwrap()]);
+
+        let mut attached = peer(None, signed);
+        records.attach(&mut attached);
+        assert!(attached.signed_record.is_some());
+
+        let mut records = SignedPeerRecords::new(PeerId::random(), true);
+        let peers = records.verify(vec![peer(None, unsigned)]);
+        assert!(peers.is_empty());
+    }
+}
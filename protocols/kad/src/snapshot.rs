@@ -0,0 +1,193 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Snapshots of the routing table.
+//!
+//! A [`RoutingTableSnapshot`] captures the peers of the routing table such that a node
+//! which restarts can seed its routing table with the peers it knew before, instead of
+//! bootstrapping from scratch.
+//!
+//! Besides via `serde`, snapshots can be encoded in the `RoutingTableSnapshot` protobuf
+//! format documented in `dht.proto`, which does not depend on rust-libp2p and can be
+//! read and written by other implementations sharing warm routing tables.
+
+use crate::kbucket::NodeStatus;
+use crate::proto;
+use instant::SystemTime;
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use quick_protobuf::{BytesReader, MessageRead, MessageWrite, Writer};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+
+/// A snapshot of the peers in the routing table of a [`Behaviour`](crate::Behaviour).
+///
+/// Obtained via [`Behaviour::routing_table_snapshot`](crate::Behaviour::routing_table_snapshot)
+/// and restored via [`Behaviour::restore_routing_table`](crate::Behaviour::restore_routing_table).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoutingTableSnapshot {
+    /// The peers of the routing table, ordered by bucket.
+    pub entries: Vec<RoutingTableEntry>,
+}
+
+impl RoutingTableSnapshot {
+    /// Encodes the snapshot in the `RoutingTableSnapshot` protobuf format.
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        let snapshot = proto::RoutingTableSnapshot {
+            peers: self
+                .entries
+                .iter()
+                .map(|entry| proto::SnapshotPeer {
+                    id: entry.peer_id.to_bytes(),
+                    addrs: entry.addresses.iter().map(|a| a.to_vec()).collect(),
+                    lastSeenNs: entry.last_seen.map_or(0, |t| t.as_nanos() as u64),
+                    connected: entry.status == NodeStatus::Connected,
+                })
+                .collect(),
+            timestampNs: unix_time_now().as_nanos() as u64,
+        };
+
+        let mut buf = Vec::with_capacity(snapshot.get_size());
+        snapshot
+            .write_message(&mut Writer::new(&mut buf))
+            .expect("Encoding to a `Vec` to succeed");
+        buf
+    }
+
+    /// Decodes a snapshot in the `RoutingTableSnapshot` protobuf format.
+    ///
+    /// Addresses that cannot be parsed, e.g. as they contain protocols unknown to
+    /// this implementation, are skipped.
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Self, DecodeSnapshotError> {
+        let mut reader = BytesReader::from_bytes(bytes);
+        let snapshot = proto::RoutingTableSnapshot::from_reader(&mut reader, bytes)
+            .map_err(|e| DecodeSnapshotError(e.to_string()))?;
+
+        let entries = snapshot
+            .peers
+            .into_iter()
+            .map(|peer| {
+                let peer_id = PeerId::from_bytes(&peer.id)
+                    .map_err(|_| DecodeSnapshotError("invalid peer id".to_owned()))?;
+                let addresses = peer
+                    .addrs
+                    .into_iter()
+                    .filter_map(|addr| match Multiaddr::try_from(addr) {
+                        Ok(addr) => Some(addr),
+                        Err(e) => {
+                            tracing::debug!(peer=%peer_id, "Unable to parse multiaddr: {e}");
+                            None
+                        }
+                    })
+                    .collect();
+                Ok(RoutingTableEntry {
+                    peer_id,
+                    addresses,
+                    status: if peer.connected {
+                        NodeStatus::Connected
+                    } else {
+                        NodeStatus::Disconnected
+                    },
+                    last_seen: (peer.lastSeenNs != 0)
+                        .then(|| Duration::from_nanos(peer.lastSeenNs)),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(RoutingTableSnapshot { entries })
+    }
+}
+
+/// The error returned by [`RoutingTableSnapshot::from_protobuf`].
+#[derive(Debug, Clone, Error)]
+#[error("Invalid routing table snapshot: {0}")]
+pub struct DecodeSnapshotError(String);
+
+/// A peer in a [`RoutingTableSnapshot`].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoutingTableEntry {
+    /// The ID of the peer.
+    pub peer_id: PeerId,
+    /// The known addresses of the peer.
+    pub addresses: Vec<Multiaddr>,
+    /// The status of the peer at the time of the snapshot.
+    pub status: NodeStatus,
+    /// The time the peer was last seen connected, as a duration since the UNIX epoch.
+    ///
+    /// `None` if the peer has not been connected since it was added to the routing table.
+    pub last_seen: Option<Duration>,
+}
+
+/// Returns the current time as a duration since the UNIX epoch.
+pub(crate) fn unix_time_now() -> Duration {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protobuf_roundtrip() {
+        let snapshot = RoutingTableSnapshot {
+            entries: vec![
+                RoutingTableEntry {
+                    peer_id: PeerId::random(),
+                    addresses: vec!["/ip4/1.2.3.4/tcp/4001".parse().unwrap()],
+                    status: NodeStatus::Connected,
+                    last_seen: Some(Duration::from_secs(1_700_000_000)),
+                },
+                RoutingTableEntry {
+                    peer_id: PeerId::random(),
+                    addresses: vec![
+                        "/ip6/::1/udp/4001/quic-v1".parse().unwrap(),
+                        "/dns4/example.com/tcp/443/wss".parse().unwrap(),
+                    ],
+                    status: NodeStatus::Disconnected,
+                    last_seen: None,
+                },
+            ],
+        };
+
+        let decoded = RoutingTableSnapshot::from_protobuf(&snapshot.to_protobuf()).unwrap();
+        assert_eq!(decoded, snapshot);
+    }
+
+    #[test]
+    fn protobuf_with_invalid_peer_id_is_rejected() {
+        let snapshot = proto::RoutingTableSnapshot {
+            peers: vec![proto::SnapshotPeer {
+                id: vec![1, 2, 3],
+                ..Default::default()
+            }],
+            timestampNs: 0,
+        };
+        let mut buf = Vec::new();
+        snapshot.write_message(&mut Writer::new(&mut buf)).unwrap();
+
+        assert!(RoutingTableSnapshot::from_protobuf(&buf).is_err());
+    }
+}
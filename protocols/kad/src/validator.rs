@@ -0,0 +1,154 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Validation of records received from remote nodes.
+//!
+//! Applications register a [`RecordValidator`] per key namespace via
+//! [`Config::add_record_validator`](crate::Config::add_record_validator). The namespace
+//! of a key is its first path segment, e.g. `ipns` for the key `/ipns/<peer-id>`.
+//! Records whose key is not in a namespace with a registered validator are accepted.
+
+use crate::record::{self, Record};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Validates records of a key namespace received from remote nodes, i.e. records
+/// of inbound `PUT_VALUE` requests and records found by [`Behaviour::get_record`](crate::Behaviour::get_record).
+///
+/// Invalid records are neither stored nor reported.
+pub trait RecordValidator: Send + Sync + 'static {
+    /// Validates the given record, returning an error if it is malformed
+    /// or otherwise not acceptable, e.g. because of a missing signature.
+    fn validate(&self, record: &Record) -> Result<(), InvalidRecord>;
+}
+
+impl<F> RecordValidator for F
+where
+    F: Fn(&Record) -> Result<(), InvalidRecord> + Send + Sync + 'static,
+{
+    fn validate(&self, record: &Record) -> Result<(), InvalidRecord> {
+        self(record)
+    }
+}
+
+/// The error of a [`RecordValidator`] rejecting a record.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("invalid record: {reason}")]
+pub struct InvalidRecord {
+    reason: String,
+}
+
+impl InvalidRecord {
+    /// Creates a new error with the reason for rejecting a record.
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// The [`RecordValidator`]s registered per key namespace.
+#[derive(Clone, Default)]
+pub(crate) struct RecordValidators {
+    validators: HashMap<Vec<u8>, Arc<dyn RecordValidator>>,
+}
+
+impl RecordValidators {
+    pub(crate) fn insert(&mut self, namespace: &str, validator: impl RecordValidator) {
+        self.insert_shared(namespace, Arc::new(validator));
+    }
+
+    pub(crate) fn insert_shared(&mut self, namespace: &str, validator: Arc<dyn RecordValidator>) {
+        self.validators
+            .insert(namespace.as_bytes().to_vec(), validator);
+    }
+
+    pub(crate) fn contains(&self, namespace: &[u8]) -> bool {
+        self.validators.contains_key(namespace)
+    }
+
+    /// Validates the given record with the validator of its key namespace, if any.
+    pub(crate) fn validate(&self, record: &Record) -> Result<(), InvalidRecord> {
+        match namespace(&record.key).and_then(|ns| self.validators.get(ns)) {
+            Some(validator) => validator.validate(record),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for RecordValidators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(
+                self.validators
+                    .keys()
+                    .map(|ns| String::from_utf8_lossy(ns).into_owned()),
+            )
+            .finish()
+    }
+}
+
+/// Returns the namespace of a key of the form `/<namespace>/<path>`.
+pub(crate) fn namespace(key: &record::Key) -> Option<&[u8]> {
+    let rest = key.as_ref().strip_prefix(b"/")?;
+    let end = rest.iter().position(|b| *b == b'/')?;
+    Some(&rest[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(s: &str) -> record::Key {
+        record::Key::new(&s)
+    }
+
+    #[test]
+    fn namespace_of_key() {
+        assert_eq!(namespace(&key("/ipns/abc")), Some(&b"ipns"[..]));
+        assert_eq!(namespace(&key("/pk/abc/def")), Some(&b"pk"[..]));
+        assert_eq!(namespace(&key("//abc")), Some(&b""[..]));
+        assert_eq!(namespace(&key("/ipns")), None);
+        assert_eq!(namespace(&key("ipns/abc")), None);
+    }
+
+    #[test]
+    fn validates_by_namespace() {
+        let mut validators = RecordValidators::default();
+        validators.insert("pk", |record: &Record| {
+            if record.value.is_empty() {
+                Err(InvalidRecord::new("empty value"))
+            } else {
+                Ok(())
+            }
+        });
+
+        assert!(validators
+            .validate(&Record::new(key("/pk/abc"), Vec::new()))
+            .is_err());
+        assert!(validators
+            .validate(&Record::new(key("/pk/abc"), vec![1]))
+            .is_ok());
+        assert!(validators
+            .validate(&Record::new(key("/ipns/abc"), Vec::new()))
+            .is_ok());
+    }
+}
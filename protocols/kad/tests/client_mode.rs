@@ -50,8 +50,8 @@ async fn two_servers_add_each_other_to_routing_table() {
 
     match libp2p_swarm_test::drive(&mut server1, &mut server2).await {
         (
-            [Identify(_), Identify(_), Kad(RoutingUpdated { peer: peer1, .. })]
-            | [Identify(_), Kad(RoutingUpdated { peer: peer1, .. }), Identify(_)],
+            [Identify(_), Identify(_), Kad(RoutingTableUpdated { .. }), Kad(RoutingUpdated { peer: peer1, .. })]
+            | [Identify(_), Kad(RoutingTableUpdated { .. }), Kad(RoutingUpdated { peer: peer1, .. }), Identify(_)],
             [Identify(_), Identify(_)],
         ) => {
             assert_eq!(peer1, server2_peer_id);
@@ -100,8 +100,8 @@ async fn adding_an_external_addresses_activates_server_mode_on_existing_connecti
     // The server reconfigured its connection to the client to be in server mode, pushes that information to client which as a result updates its routing table and triggers a mode change to Mode::Server.
     match libp2p_swarm_test::drive(&mut client, &mut server).await {
         (
-            [Identify(identify::Event::Received { .. }), Kad(RoutingUpdated { peer: peer1, .. })],
-            [Kad(ModeChanged { new_mode }), Identify(identify::Event::Pushed { .. })],
+            [Identify(identify::Event::Received { .. }), Kad(RoutingTableUpdated { .. }), Kad(RoutingUpdated { peer: peer1, .. })],
+            [Kad(ModeChanged { new_mode, .. }), Identify(identify::Event::Pushed { .. })],
         ) => {
             assert_eq!(new_mode, Mode::Server);
             assert_eq!(peer1, server_peer_id);
@@ -0,0 +1,58 @@
+use libp2p_kad::store::{MemoryStore, RecordStore};
+use libp2p_kad::{Behaviour, Event, GetRecordOk, QueryResult, Record, RecordKey};
+use libp2p_swarm::SwarmEvent;
+use libp2p_swarm_test::scenario::{Scenario, Topology};
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+#[async_std::test]
+async fn get_record_along_chain() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let record = Record::new(RecordKey::new(&"key"), b"value".to_vec());
+    let key = record.key.clone();
+
+    let outcome = Scenario::new(3, |_, keypair| {
+        let peer_id = keypair.public().to_peer_id();
+        Behaviour::new(peer_id, MemoryStore::new(peer_id))
+    })
+    .topology(Topology::Chain)
+    .at(Duration::ZERO, 0, |swarm, nodes| {
+        swarm
+            .behaviour_mut()
+            .add_address(&nodes[1].peer_id, nodes[1].addr.clone());
+    })
+    .at(Duration::ZERO, 1, |swarm, nodes| {
+        swarm
+            .behaviour_mut()
+            .add_address(&nodes[2].peer_id, nodes[2].addr.clone());
+    })
+    .at(Duration::ZERO, 2, move |swarm, _| {
+        swarm.behaviour_mut().store_mut().put(record).unwrap();
+    })
+    .at(Duration::from_millis(100), 0, move |swarm, _| {
+        swarm.behaviour_mut().get_record(key);
+    })
+    .expect(0, "record found at the last node", |event| {
+        matches!(
+            event,
+            SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                result: QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(found))),
+                ..
+            }) if found.record.value == b"value"
+        )
+    })
+    .run()
+    .await;
+
+    let peer = outcome.behaviour_events(0).find_map(|event| match event {
+        Event::OutboundQueryProgressed {
+            result: QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(found))),
+            ..
+        } => found.peer,
+        _ => None,
+    });
+    assert_eq!(peer, Some(outcome.nodes()[2].peer_id));
+}
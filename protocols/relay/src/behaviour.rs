@@ -20,8 +20,10 @@
 
 //! [`NetworkBehaviour`] to act as a circuit relay v2 **relay**.
 
+pub(crate) mod admission;
 pub(crate) mod handler;
 pub(crate) mod rate_limiter;
+use crate::behaviour::admission::{Admission, AdmissionControl, AdmissionRequest, Usage};
 use crate::behaviour::handler::Handler;
 use crate::multiaddr_ext::MultiaddrExt;
 use crate::proto;
@@ -58,6 +60,9 @@ pub struct Config {
     pub max_circuit_duration: Duration,
     pub max_circuit_bytes: u64,
     pub circuit_src_rate_limiters: Vec<Box<dyn rate_limiter::RateLimiter>>,
+
+    pub admission_control: Option<Box<dyn AdmissionControl>>,
+    pub admission_timeout: Duration,
 }
 
 impl Config {
@@ -92,6 +97,15 @@ impl Config {
             ));
         self
     }
+
+    /// Consults the given [`AdmissionControl`] for every reservation and circuit request
+    /// within the limits of this [`Config`].
+    ///
+    /// Requests whose decision takes longer than [`Config::admission_timeout`] are denied.
+    pub fn admission_control(mut self, control: impl AdmissionControl + 'static) -> Self {
+        self.admission_control = Some(Box::new(control));
+        self
+    }
 }
 
 impl std::fmt::Debug for Config {
@@ -112,6 +126,14 @@ impl std::fmt::Debug for Config {
                 "circuit_src_rate_limiters",
                 &format!("[{} rate limiters]", self.circuit_src_rate_limiters.len()),
             )
+            .field(
+                "admission_control",
+                &self
+                    .admission_control
+                    .as_ref()
+                    .map(|_| "<admission control>"),
+            )
+            .field("admission_timeout", &self.admission_timeout)
             .finish()
     }
 }
@@ -155,6 +177,9 @@ impl Default for Config {
             max_circuit_duration: Duration::from_secs(2 * 60),
             max_circuit_bytes: 1 << 17, // 128 kibibyte
             circuit_src_rate_limiters,
+
+            admission_control: None,
+            admission_timeout: Duration::from_secs(10),
         }
     }
 }
@@ -247,10 +272,22 @@ pub struct Behaviour {
     queued_actions: VecDeque<ToSwarm<Event, THandlerInEvent<Self>>>,
 
     external_addresses: ExternalAddresses,
+
+    /// Requests awaiting the decision of the [`AdmissionControl`].
+    pending_admissions: futures_bounded::FuturesTupleSet<Admission, PendingAdmission>,
+    /// The number of requests awaiting a decision per connection.
+    ///
+    /// Removed once the connection closes, thus the decisions on its requests are dropped.
+    admitting_connections: HashMap<ConnectionId, usize>,
 }
 
 impl Behaviour {
     pub fn new(local_peer_id: PeerId, config: Config) -> Self {
+        let pending_admissions = futures_bounded::FuturesTupleSet::new(
+            config.admission_timeout,
+            config.max_reservations + config.max_circuits,
+        );
+
         Self {
             config,
             local_peer_id,
@@ -258,9 +295,257 @@ impl Behaviour {
             circuits: Default::default(),
             queued_actions: Default::default(),
             external_addresses: Default::default(),
+            pending_admissions,
+            admitting_connections: Default::default(),
+        }
+    }
+
+    /// Returns the resources in use by all peers and by the given peer.
+    fn usage(&self, peer_id: PeerId) -> Usage {
+        Usage {
+            num_reservations: self.reservations.values().map(|cs| cs.len()).sum(),
+            num_reservations_of_peer: self.reservations.get(&peer_id).map_or(0, |cs| cs.len()),
+            num_circuits: self.circuits.len(),
+            num_circuits_of_peer: self.circuits.num_circuits_of_peer(peer_id),
+        }
+    }
+
+    fn reservation_limits_exceeded(&self, peer_id: PeerId, renewed: bool) -> bool {
+        let usage = self.usage(peer_id);
+
+        // Deny if it is a new reservation and exceeds `max_reservations_per_peer`.
+        (!renewed && usage.num_reservations_of_peer > self.config.max_reservations_per_peer)
+            // Deny if it exceeds `max_reservations`.
+            || usage.num_reservations >= self.config.max_reservations
+    }
+
+    fn circuit_limits_exceeded(&self, peer_id: PeerId) -> bool {
+        self.circuits.num_circuits_of_peer(peer_id) > self.config.max_circuits_per_peer
+            || self.circuits.len() >= self.config.max_circuits
+    }
+
+    fn accept_reservation(
+        &mut self,
+        peer_id: PeerId,
+        connection: ConnectionId,
+        inbound_reservation_req: inbound_hop::ReservationReq,
+    ) -> ToSwarm<Event, THandlerInEvent<Self>> {
+        self.reservations
+            .entry(peer_id)
+            .or_default()
+            .insert(connection);
+
+        ToSwarm::NotifyHandler {
+            handler: NotifyHandler::One(connection),
+            peer_id,
+            event: Either::Left(handler::In::AcceptReservationReq {
+                inbound_reservation_req,
+                addrs: self
+                    .external_addresses
+                    .iter()
+                    .cloned()
+                    // Add local peer ID in case it isn't present yet.
+                    .filter_map(|a| match a.iter().last()? {
+                        Protocol::P2p(_) => Some(a),
+                        _ => Some(a.with(Protocol::P2p(self.local_peer_id))),
+                    })
+                    .collect(),
+            }),
         }
     }
 
+    fn deny_reservation(
+        peer_id: PeerId,
+        connection: ConnectionId,
+        inbound_reservation_req: inbound_hop::ReservationReq,
+        status: proto::Status,
+    ) -> ToSwarm<Event, THandlerInEvent<Self>> {
+        ToSwarm::NotifyHandler {
+            handler: NotifyHandler::One(connection),
+            peer_id,
+            event: Either::Left(handler::In::DenyReservationReq {
+                inbound_reservation_req,
+                status,
+            }),
+        }
+    }
+
+    /// Connects the source of a circuit request to the destination, if the destination
+    /// has a reservation.
+    fn connect_circuit(
+        &mut self,
+        peer_id: PeerId,
+        connection: ConnectionId,
+        inbound_circuit_req: inbound_hop::CircuitReq,
+    ) -> ToSwarm<Event, THandlerInEvent<Self>> {
+        if let Some(dst_conn) = self
+            .reservations
+            .get(&inbound_circuit_req.dst())
+            .and_then(|cs| cs.iter().next())
+        {
+            // Accept circuit request if reservation present.
+            let circuit_id = self.circuits.insert(Circuit {
+                status: CircuitStatus::Accepting,
+                src_peer_id: peer_id,
+                src_connection_id: connection,
+                dst_peer_id: inbound_circuit_req.dst(),
+                dst_connection_id: *dst_conn,
+            });
+
+            ToSwarm::NotifyHandler {
+                handler: NotifyHandler::One(*dst_conn),
+                peer_id,
+                event: Either::Left(handler::In::NegotiateOutboundConnect {
+                    circuit_id,
+                    inbound_circuit_req,
+                    src_peer_id: peer_id,
+                    src_connection_id: connection,
+                }),
+            }
+        } else {
+            // Deny circuit request if no reservation present.
+            Self::deny_circuit(
+                peer_id,
+                connection,
+                inbound_circuit_req,
+                proto::Status::NO_RESERVATION,
+            )
+        }
+    }
+
+    fn deny_circuit(
+        peer_id: PeerId,
+        connection: ConnectionId,
+        inbound_circuit_req: inbound_hop::CircuitReq,
+        status: proto::Status,
+    ) -> ToSwarm<Event, THandlerInEvent<Self>> {
+        ToSwarm::NotifyHandler {
+            handler: NotifyHandler::One(connection),
+            peer_id,
+            event: Either::Left(handler::In::DenyCircuitReq {
+                circuit_id: None,
+                inbound_circuit_req,
+                status,
+            }),
+        }
+    }
+
+    /// Consults the [`AdmissionControl`] on a request within the limits.
+    ///
+    /// Returns the action denying the request if too many requests await a decision.
+    fn admit(
+        &mut self,
+        request: AdmissionRequest,
+        pending: PendingAdmission,
+    ) -> Option<ToSwarm<Event, THandlerInEvent<Self>>> {
+        let admission = self
+            .config
+            .admission_control
+            .as_mut()
+            .expect("to be called with admission control")
+            .admit(request);
+        let connection = pending.connection;
+        let Err((_, pending)) = self.pending_admissions.try_push(admission, pending) else {
+            *self.admitting_connections.entry(connection).or_default() += 1;
+            return None;
+        };
+
+        tracing::debug!(peer=%pending.peer_id, "Too many requests awaiting admission");
+        let action = match pending.request {
+            PendingRequest::Reservation {
+                inbound_reservation_req,
+                ..
+            } => Self::deny_reservation(
+                pending.peer_id,
+                pending.connection,
+                inbound_reservation_req,
+                proto::Status::RESOURCE_LIMIT_EXCEEDED,
+            ),
+            PendingRequest::Circuit {
+                inbound_circuit_req,
+            } => Self::deny_circuit(
+                pending.peer_id,
+                pending.connection,
+                inbound_circuit_req,
+                proto::Status::RESOURCE_LIMIT_EXCEEDED,
+            ),
+        };
+        Some(action)
+    }
+
+    /// Answers a request once the [`AdmissionControl`] decided on it.
+    ///
+    /// Returns `None` if the connection of the request closed in the meantime.
+    fn on_admission(
+        &mut self,
+        admission: Result<Admission, futures_bounded::Timeout>,
+        PendingAdmission {
+            peer_id,
+            connection,
+            request,
+        }: PendingAdmission,
+    ) -> Option<ToSwarm<Event, THandlerInEvent<Self>>> {
+        let hash_map::Entry::Occupied(mut admitting) = self.admitting_connections.entry(connection)
+        else {
+            tracing::debug!(peer=%peer_id, "Connection closed while awaiting admission");
+            return None;
+        };
+        *admitting.get_mut() -= 1;
+        if *admitting.get() == 0 {
+            admitting.remove();
+        }
+
+        let admission = admission.unwrap_or_else(|_| {
+            tracing::debug!(peer=%peer_id, "Admission decision timed out");
+            Admission::Deny
+        });
+
+        // The limits are checked again, as other requests may have been accepted
+        // in the meantime.
+        let action = match request {
+            PendingRequest::Reservation {
+                inbound_reservation_req,
+                renewed,
+            } => match admission {
+                Admission::Deny => Self::deny_reservation(
+                    peer_id,
+                    connection,
+                    inbound_reservation_req,
+                    proto::Status::RESERVATION_REFUSED,
+                ),
+                Admission::Accept if self.reservation_limits_exceeded(peer_id, renewed) => {
+                    Self::deny_reservation(
+                        peer_id,
+                        connection,
+                        inbound_reservation_req,
+                        proto::Status::RESOURCE_LIMIT_EXCEEDED,
+                    )
+                }
+                Admission::Accept => {
+                    self.accept_reservation(peer_id, connection, inbound_reservation_req)
+                }
+            },
+            PendingRequest::Circuit {
+                inbound_circuit_req,
+            } => match admission {
+                Admission::Deny => Self::deny_circuit(
+                    peer_id,
+                    connection,
+                    inbound_circuit_req,
+                    proto::Status::PERMISSION_DENIED,
+                ),
+                Admission::Accept if self.circuit_limits_exceeded(peer_id) => Self::deny_circuit(
+                    peer_id,
+                    connection,
+                    inbound_circuit_req,
+                    proto::Status::RESOURCE_LIMIT_EXCEEDED,
+                ),
+                Admission::Accept => self.connect_circuit(peer_id, connection, inbound_circuit_req),
+            },
+        };
+        Some(action)
+    }
+
     fn on_connection_closed(
         &mut self,
         ConnectionClosed {
@@ -269,6 +554,8 @@ impl Behaviour {
             ..
         }: ConnectionClosed,
     ) {
+        self.admitting_connections.remove(&connection_id);
+
         if let hash_map::Entry::Occupied(mut peer) = self.reservations.entry(peer_id) {
             peer.get_mut().remove(&connection_id);
             if peer.get().is_empty() {
@@ -380,22 +667,7 @@ impl NetworkBehaviour for Behaviour {
                      denies all inbound substreams."
                 );
 
-                let action = if
-                // Deny if it is a new reservation and exceeds `max_reservations_per_peer`.
-                (!renewed
-                    && self
-                        .reservations
-                        .get(&event_source)
-                        .map(|cs| cs.len())
-                        .unwrap_or(0)
-                        > self.config.max_reservations_per_peer)
-                    // Deny if it exceeds `max_reservations`.
-                    || self
-                        .reservations
-                        .values()
-                        .map(|cs| cs.len())
-                        .sum::<usize>()
-                        >= self.config.max_reservations
+                let action = if self.reservation_limits_exceeded(event_source, renewed)
                     // Deny if it exceeds the allowed rate of reservations.
                     || !self
                         .config
@@ -404,38 +676,34 @@ impl NetworkBehaviour for Behaviour {
                         .all(|limiter| {
                             limiter.try_next(event_source, endpoint.get_remote_address(), now)
                         }) {
-                    ToSwarm::NotifyHandler {
-                        handler: NotifyHandler::One(connection),
+                    Self::deny_reservation(
+                        event_source,
+                        connection,
+                        inbound_reservation_req,
+                        proto::Status::RESOURCE_LIMIT_EXCEEDED,
+                    )
+                } else if self.config.admission_control.is_some() {
+                    let request = AdmissionRequest::Reservation {
+                        src_peer_id: event_source,
+                        src_addr: endpoint.get_remote_address().clone(),
+                        renewed,
+                        usage: self.usage(event_source),
+                    };
+                    let pending = PendingAdmission {
                         peer_id: event_source,
-                        event: Either::Left(handler::In::DenyReservationReq {
+                        connection,
+                        request: PendingRequest::Reservation {
                             inbound_reservation_req,
-                            status: proto::Status::RESOURCE_LIMIT_EXCEEDED,
-                        }),
+                            renewed,
+                        },
+                    };
+                    match self.admit(request, pending) {
+                        Some(action) => action,
+                        None => return,
                     }
                 } else {
                     // Accept reservation.
-                    self.reservations
-                        .entry(event_source)
-                        .or_default()
-                        .insert(connection);
-
-                    ToSwarm::NotifyHandler {
-                        handler: NotifyHandler::One(connection),
-                        peer_id: event_source,
-                        event: Either::Left(handler::In::AcceptReservationReq {
-                            inbound_reservation_req,
-                            addrs: self
-                                .external_addresses
-                                .iter()
-                                .cloned()
-                                // Add local peer ID in case it isn't present yet.
-                                .filter_map(|a| match a.iter().last()? {
-                                    Protocol::P2p(_) => Some(a),
-                                    _ => Some(a.with(Protocol::P2p(self.local_peer_id))),
-                                })
-                                .collect(),
-                        }),
-                    }
+                    self.accept_reservation(event_source, connection, inbound_reservation_req)
                 };
 
                 self.queued_actions.push_back(action);
@@ -514,9 +782,7 @@ impl NetworkBehaviour for Behaviour {
                      denies all inbound substreams."
                 );
 
-                let action = if self.circuits.num_circuits_of_peer(event_source)
-                    > self.config.max_circuits_per_peer
-                    || self.circuits.len() >= self.config.max_circuits
+                let action = if self.circuit_limits_exceeded(event_source)
                     || !self
                         .config
                         .circuit_src_rate_limiters
@@ -525,50 +791,32 @@ impl NetworkBehaviour for Behaviour {
                             limiter.try_next(event_source, endpoint.get_remote_address(), now)
                         }) {
                     // Deny circuit exceeding limits.
-                    ToSwarm::NotifyHandler {
-                        handler: NotifyHandler::One(connection),
-                        peer_id: event_source,
-                        event: Either::Left(handler::In::DenyCircuitReq {
-                            circuit_id: None,
-                            inbound_circuit_req,
-                            status: proto::Status::RESOURCE_LIMIT_EXCEEDED,
-                        }),
-                    }
-                } else if let Some(dst_conn) = self
-                    .reservations
-                    .get(&inbound_circuit_req.dst())
-                    .and_then(|cs| cs.iter().next())
-                {
-                    // Accept circuit request if reservation present.
-                    let circuit_id = self.circuits.insert(Circuit {
-                        status: CircuitStatus::Accepting,
+                    Self::deny_circuit(
+                        event_source,
+                        connection,
+                        inbound_circuit_req,
+                        proto::Status::RESOURCE_LIMIT_EXCEEDED,
+                    )
+                } else if self.config.admission_control.is_some() {
+                    let request = AdmissionRequest::Circuit {
                         src_peer_id: event_source,
-                        src_connection_id: connection,
+                        src_addr: endpoint.get_remote_address().clone(),
                         dst_peer_id: inbound_circuit_req.dst(),
-                        dst_connection_id: *dst_conn,
-                    });
-
-                    ToSwarm::NotifyHandler {
-                        handler: NotifyHandler::One(*dst_conn),
+                        usage: self.usage(event_source),
+                    };
+                    let pending = PendingAdmission {
                         peer_id: event_source,
-                        event: Either::Left(handler::In::NegotiateOutboundConnect {
-                            circuit_id,
+                        connection,
+                        request: PendingRequest::Circuit {
                             inbound_circuit_req,
-                            src_peer_id: event_source,
-                            src_connection_id: connection,
-                        }),
+                        },
+                    };
+                    match self.admit(request, pending) {
+                        Some(action) => action,
+                        None => return,
                     }
                 } else {
-                    // Deny circuit request if no reservation present.
-                    ToSwarm::NotifyHandler {
-                        handler: NotifyHandler::One(connection),
-                        peer_id: event_source,
-                        event: Either::Left(handler::In::DenyCircuitReq {
-                            circuit_id: None,
-                            inbound_circuit_req,
-                            status: proto::Status::NO_RESERVATION,
-                        }),
-                    }
+                    self.connect_circuit(event_source, connection, inbound_circuit_req)
                 };
                 self.queued_actions.push_back(action);
             }
@@ -693,16 +941,42 @@ impl NetworkBehaviour for Behaviour {
         }
     }
 
-    #[tracing::instrument(level = "trace", name = "NetworkBehaviour::poll", skip(self))]
-    fn poll(&mut self, _: &mut Context<'_>) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+    #[tracing::instrument(level = "trace", name = "NetworkBehaviour::poll", skip(self, cx))]
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
         if let Some(to_swarm) = self.queued_actions.pop_front() {
             return Poll::Ready(to_swarm);
         }
 
+        while let Poll::Ready((admission, pending)) = self.pending_admissions.poll_unpin(cx) {
+            if let Some(action) = self.on_admission(admission, pending) {
+                return Poll::Ready(action);
+            }
+        }
+
         Poll::Pending
     }
 }
 
+/// A request awaiting the decision of the [`AdmissionControl`].
+struct PendingAdmission {
+    peer_id: PeerId,
+    connection: ConnectionId,
+    request: PendingRequest,
+}
+
+enum PendingRequest {
+    Reservation {
+        inbound_reservation_req: inbound_hop::ReservationReq,
+        renewed: bool,
+    },
+    Circuit {
+        inbound_circuit_req: inbound_hop::CircuitReq,
+    },
+}
+
 #[derive(Default)]
 struct CircuitsTracker {
     next_id: CircuitId,
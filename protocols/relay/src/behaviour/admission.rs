@@ -0,0 +1,97 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::future::{BoxFuture, Future, FutureExt};
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+
+/// Decides whether the relay admits a reservation or circuit request.
+///
+/// Consulted for every request within the limits of the [`Config`](crate::Config), e.g. to
+/// implement allowlists, payment checks or abuse heuristics. The decision may be made
+/// asynchronously, e.g. by querying a database. Requests whose decision takes longer than
+/// [`Config::admission_timeout`](crate::Config::admission_timeout) are denied.
+///
+/// Implemented for closures `FnMut(AdmissionRequest) -> impl Future<Output = Admission>`.
+pub trait AdmissionControl: Send {
+    fn admit(&mut self, request: AdmissionRequest) -> BoxFuture<'static, Admission>;
+}
+
+impl<F, Fut> AdmissionControl for F
+where
+    F: FnMut(AdmissionRequest) -> Fut + Send,
+    Fut: Future<Output = Admission> + Send + 'static,
+{
+    fn admit(&mut self, request: AdmissionRequest) -> BoxFuture<'static, Admission> {
+        self(request).boxed()
+    }
+}
+
+/// A request to be admitted by an [`AdmissionControl`].
+#[derive(Debug, Clone)]
+pub enum AdmissionRequest {
+    /// A peer requests a reservation.
+    Reservation {
+        src_peer_id: PeerId,
+        /// The address of the connection the request was received on.
+        src_addr: Multiaddr,
+        /// Indicates whether the request renews an existing reservation.
+        renewed: bool,
+        usage: Usage,
+    },
+    /// A peer requests a circuit to a peer with a reservation.
+    Circuit {
+        src_peer_id: PeerId,
+        /// The address of the connection the request was received on.
+        src_addr: Multiaddr,
+        dst_peer_id: PeerId,
+        usage: Usage,
+    },
+}
+
+impl AdmissionRequest {
+    /// The peer making the request.
+    pub fn src_peer_id(&self) -> PeerId {
+        match self {
+            AdmissionRequest::Reservation { src_peer_id, .. }
+            | AdmissionRequest::Circuit { src_peer_id, .. } => *src_peer_id,
+        }
+    }
+}
+
+/// The resources of the relay in use at the time of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    /// The number of reservations across all peers.
+    pub num_reservations: usize,
+    /// The number of reservations of the requesting peer.
+    pub num_reservations_of_peer: usize,
+    /// The number of circuits across all peers.
+    pub num_circuits: usize,
+    /// The number of circuits from or to the requesting peer.
+    pub num_circuits_of_peer: usize,
+}
+
+/// The decision of an [`AdmissionControl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Accept,
+    Deny,
+}
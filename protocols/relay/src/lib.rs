@@ -39,7 +39,11 @@ mod proto {
     };
 }
 
-pub use behaviour::{rate_limiter::RateLimiter, Behaviour, CircuitId, Config, Event};
+pub use behaviour::{
+    admission::{Admission, AdmissionControl, AdmissionRequest, Usage},
+    rate_limiter::RateLimiter,
+    Behaviour, CircuitId, Config, Event,
+};
 pub use protocol::{HOP_PROTOCOL_NAME, STOP_PROTOCOL_NAME};
 
 /// Types related to the relay protocol inbound.
@@ -64,7 +68,12 @@ pub mod outbound {
 
 /// Everything related to the relay protocol from a client's perspective.
 pub mod client {
-    pub use crate::priv_client::{new, transport::Transport, Behaviour, Connection, Event};
+    pub use crate::priv_client::{
+        new,
+        reservation::{ReservationHandle, ReservationOptions, ReservationState},
+        transport::Transport,
+        Behaviour, Connection, Event,
+    };
 
     pub mod transport {
         pub use crate::priv_client::transport::Error;
@@ -21,10 +21,14 @@
 //! [`NetworkBehaviour`] to act as a circuit relay v2 **client**.
 
 pub(crate) mod handler;
+pub(crate) mod reservation;
 pub(crate) mod transport;
 
 use crate::multiaddr_ext::MultiaddrExt;
 use crate::priv_client::handler::Handler;
+use crate::priv_client::reservation::{
+    Reservation, ReservationHandle, ReservationOptions, ReservationState,
+};
 use crate::protocol::{self, inbound_stop};
 use bytes::Bytes;
 use either::Either;
@@ -33,18 +37,24 @@ use futures::future::{BoxFuture, FutureExt};
 use futures::io::{AsyncRead, AsyncWrite};
 use futures::ready;
 use futures::stream::StreamExt;
+use futures::task::AtomicWaker;
 use libp2p_core::multiaddr::Protocol;
+use libp2p_core::transport::ListenerId;
 use libp2p_core::{Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
-use libp2p_swarm::behaviour::{ConnectionClosed, ConnectionEstablished, FromSwarm};
+use libp2p_swarm::behaviour::{
+    ConnectionClosed, ConnectionEstablished, FromSwarm, ListenerClosed, ListenerError,
+    NewListenAddr,
+};
 use libp2p_swarm::dial_opts::DialOpts;
 use libp2p_swarm::{
-    dummy, ConnectionDenied, ConnectionHandler, ConnectionId, DialFailure, NetworkBehaviour,
-    NotifyHandler, Stream, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+    dummy, ConnectionDenied, ConnectionHandler, ConnectionId, DialFailure, ListenOpts,
+    NetworkBehaviour, NotifyHandler, Stream, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
 use std::collections::{hash_map, HashMap, VecDeque};
 use std::io::{Error, ErrorKind, IoSlice};
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use transport::Transport;
 use void::Void;
@@ -95,6 +105,12 @@ pub struct Behaviour {
     queued_actions: VecDeque<ToSwarm<Event, Either<handler::In, Void>>>,
 
     pending_handler_commands: HashMap<ConnectionId, handler::In>,
+
+    /// The reservations made via [`Behaviour::reserve`], indexed by the [`ListenerId`] of
+    /// the listener of the reservation.
+    reservations: HashMap<ListenerId, Reservation>,
+    /// Woken by [`ReservationHandle::cancel`].
+    waker: Arc<AtomicWaker>,
 }
 
 /// Create a new client relay [`Behaviour`] with it's corresponding [`Transport`].
@@ -107,11 +123,76 @@ pub fn new(local_peer_id: PeerId) -> (Transport, Behaviour) {
         reservation_addresses: Default::default(),
         queued_actions: Default::default(),
         pending_handler_commands: Default::default(),
+        reservations: Default::default(),
+        waker: Default::default(),
     };
     (transport, behaviour)
 }
 
 impl Behaviour {
+    /// Makes a reservation on the given relay, listening for inbound circuits via the relay
+    /// like [`Swarm::listen_on`](libp2p_swarm::Swarm::listen_on) with the `/p2p-circuit`
+    /// address of the relay does.
+    ///
+    /// Returns a [`ReservationHandle`] to query the state of the reservation and to cancel it.
+    pub fn reserve(
+        &mut self,
+        relay_peer_id: PeerId,
+        options: ReservationOptions,
+    ) -> ReservationHandle {
+        let opts = ListenOpts::new(options.listen_addr(relay_peer_id));
+        let listener_id = opts.listener_id();
+        let (reservation, handle) =
+            Reservation::new(relay_peer_id, listener_id, &options, self.waker.clone());
+        self.reservations.insert(listener_id, reservation);
+        self.queued_actions.push_back(ToSwarm::ListenOn { opts });
+
+        handle
+    }
+
+    /// Closes the listener of a reservation that was cancelled or whose duration elapsed,
+    /// dropping the reservation on the connection to the relay.
+    fn close_reservation(&mut self, listener_id: ListenerId) {
+        let Some(reservation) = self.reservations.get(&listener_id) else {
+            return;
+        };
+        if let Some(connection_id) = reservation.connection {
+            if let Some(handler::In::Reserve { .. }) =
+                self.pending_handler_commands.get(&connection_id)
+            {
+                self.pending_handler_commands.remove(&connection_id);
+            } else if let Some(peer_id) = self
+                .directly_connected_peers
+                .iter()
+                .find_map(|(peer, cs)| cs.contains(&connection_id).then_some(*peer))
+            {
+                self.queued_actions.push_back(ToSwarm::NotifyHandler {
+                    peer_id,
+                    handler: NotifyHandler::One(connection_id),
+                    event: Either::Left(handler::In::CancelReservation),
+                });
+            }
+        }
+        self.queued_actions
+            .push_back(ToSwarm::RemoveListener { id: listener_id });
+    }
+
+    fn on_listener_closed(&mut self, listener_id: ListenerId) {
+        let Some(reservation) = self.reservations.remove(&listener_id) else {
+            return;
+        };
+        reservation.on_listener_closed();
+
+        if let Some(connection_id) = reservation.connection {
+            if let Some((addr, ReservationStatus::Confirmed)) =
+                self.reservation_addresses.remove(&connection_id)
+            {
+                self.queued_actions
+                    .push_back(ToSwarm::ExternalAddrExpired(addr));
+            }
+        }
+    }
+
     fn on_connection_closed(
         &mut self,
         ConnectionClosed {
@@ -222,6 +303,15 @@ impl NetworkBehaviour for Behaviour {
                 self.reservation_addresses.remove(&connection_id);
                 self.pending_handler_commands.remove(&connection_id);
             }
+            FromSwarm::NewListenAddr(NewListenAddr { listener_id, .. }) => {
+                if let Some(reservation) = self.reservations.get(&listener_id) {
+                    reservation.transition(ReservationState::Accepted);
+                }
+            }
+            FromSwarm::ListenerError(ListenerError { listener_id, .. })
+            | FromSwarm::ListenerClosed(ListenerClosed { listener_id, .. }) => {
+                self.on_listener_closed(listener_id)
+            }
             _ => {}
         }
     }
@@ -275,22 +365,48 @@ impl NetworkBehaviour for Behaviour {
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        self.waker.register(cx.waker());
+        let closed = self
+            .reservations
+            .iter_mut()
+            .filter_map(|(listener_id, reservation)| {
+                reservation
+                    .poll_close(cx)
+                    .is_ready()
+                    .then_some(*listener_id)
+            })
+            .collect::<Vec<_>>();
+        for listener_id in closed {
+            self.close_reservation(listener_id);
+        }
+
         if let Some(action) = self.queued_actions.pop_front() {
             return Poll::Ready(action);
         }
 
         let action = match ready!(self.from_transport.poll_next_unpin(cx)) {
             Some(transport::TransportToBehaviourMsg::ListenReq {
+                listener_id,
                 relay_peer_id,
                 relay_addr,
                 to_listener,
             }) => {
+                let reservation = self.reservations.get_mut(&listener_id);
+                let auto_renew = reservation.as_ref().map_or(true, |r| r.auto_renew);
+                let reserve = handler::In::Reserve {
+                    to_listener,
+                    auto_renew,
+                };
+
                 match self
                     .directly_connected_peers
                     .get(&relay_peer_id)
                     .and_then(|cs| cs.first())
                 {
                     Some(connection_id) => {
+                        if let Some(reservation) = reservation {
+                            reservation.connection = Some(*connection_id);
+                        }
                         self.reservation_addresses.insert(
                             *connection_id,
                             (
@@ -305,7 +421,7 @@ impl NetworkBehaviour for Behaviour {
                         ToSwarm::NotifyHandler {
                             peer_id: relay_peer_id,
                             handler: NotifyHandler::One(*connection_id),
-                            event: Either::Left(handler::In::Reserve { to_listener }),
+                            event: Either::Left(reserve),
                         }
                     }
                     None => {
@@ -314,6 +430,9 @@ impl NetworkBehaviour for Behaviour {
                             .extend_addresses_through_behaviour()
                             .build();
                         let relayed_connection_id = opts.connection_id();
+                        if let Some(reservation) = reservation {
+                            reservation.connection = Some(relayed_connection_id);
+                        }
 
                         self.reservation_addresses.insert(
                             relayed_connection_id,
@@ -327,7 +446,7 @@ impl NetworkBehaviour for Behaviour {
                         );
 
                         self.pending_handler_commands
-                            .insert(relayed_connection_id, handler::In::Reserve { to_listener });
+                            .insert(relayed_connection_id, reserve);
                         ToSwarm::Dial { opts }
                     }
                 }
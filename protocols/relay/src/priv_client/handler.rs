@@ -54,7 +54,11 @@ const STREAM_TIMEOUT: Duration = Duration::from_secs(60);
 pub enum In {
     Reserve {
         to_listener: mpsc::Sender<transport::ToListenerMsg>,
+        /// Whether to renew the reservation before it expires.
+        auto_renew: bool,
     },
+    /// Drops the reservation, denying inbound circuits from now on.
+    CancelReservation,
     EstablishCircuit {
         dst_peer_id: PeerId,
         to_dial: oneshot::Sender<Result<priv_client::Connection, outbound_hop::ConnectError>>,
@@ -64,7 +68,14 @@ pub enum In {
 impl fmt::Debug for In {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            In::Reserve { to_listener: _ } => f.debug_struct("In::Reserve").finish(),
+            In::Reserve {
+                to_listener: _,
+                auto_renew,
+            } => f
+                .debug_struct("In::Reserve")
+                .field("auto_renew", auto_renew)
+                .finish(),
+            In::CancelReservation => f.debug_struct("In::CancelReservation").finish(),
             In::EstablishCircuit {
                 dst_peer_id,
                 to_dial: _,
@@ -110,7 +121,7 @@ pub struct Handler {
 
     inflight_reserve_requests: futures_bounded::FuturesTupleSet<
         Result<outbound_hop::Reservation, outbound_hop::ReserveError>,
-        mpsc::Sender<transport::ToListenerMsg>,
+        (mpsc::Sender<transport::ToListenerMsg>, bool),
     >,
 
     inflight_outbound_connect_requests: futures_bounded::FuturesTupleSet<
@@ -170,7 +181,7 @@ impl Handler {
         }
     }
 
-    fn make_new_reservation(&mut self, to_listener: Sender<ToListenerMsg>) {
+    fn make_new_reservation(&mut self, to_listener: Sender<ToListenerMsg>, auto_renew: bool) {
         let (sender, receiver) = oneshot::channel();
 
         self.pending_streams.push_back(sender);
@@ -189,7 +200,7 @@ impl Handler {
 
                 Ok(reservation)
             },
-            to_listener,
+            (to_listener, auto_renew),
         );
 
         if result.is_err() {
@@ -241,8 +252,14 @@ impl ConnectionHandler for Handler {
 
     fn on_behaviour_event(&mut self, event: Self::FromBehaviour) {
         match event {
-            In::Reserve { to_listener } => {
-                self.make_new_reservation(to_listener);
+            In::Reserve {
+                to_listener,
+                auto_renew,
+            } => {
+                self.make_new_reservation(to_listener, auto_renew);
+            }
+            In::CancelReservation => {
+                self.reservation = Reservation::None;
             }
             In::EstablishCircuit {
                 to_dial,
@@ -273,11 +290,12 @@ impl ConnectionHandler for Handler {
                         addrs,
                         limit,
                     })),
-                    to_listener,
+                    (to_listener, auto_renew),
                 )) => {
                     return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
                         self.reservation.accepted(
                             renewal_timeout,
+                            auto_renew,
                             addrs,
                             to_listener,
                             self.local_peer_id,
@@ -285,7 +303,7 @@ impl ConnectionHandler for Handler {
                         ),
                     ));
                 }
-                Poll::Ready((Ok(Err(error)), mut to_listener)) => {
+                Poll::Ready((Ok(Err(error)), (mut to_listener, _))) => {
                     if let Err(e) =
                         to_listener.try_send(transport::ToListenerMsg::Reservation(Err(error)))
                     {
@@ -294,7 +312,7 @@ impl ConnectionHandler for Handler {
                     self.reservation.failed();
                     continue;
                 }
-                Poll::Ready((Err(futures_bounded::Timeout { .. }), mut to_listener)) => {
+                Poll::Ready((Err(futures_bounded::Timeout { .. }), (mut to_listener, _))) => {
                     if let Err(e) =
                         to_listener.try_send(transport::ToListenerMsg::Reservation(Err(
                             outbound_hop::ReserveError::Io(io::ErrorKind::TimedOut.into()),
@@ -396,7 +414,7 @@ impl ConnectionHandler for Handler {
             }
 
             if let Poll::Ready(Some(to_listener)) = self.reservation.poll(cx) {
-                self.make_new_reservation(to_listener);
+                self.make_new_reservation(to_listener, true);
                 continue;
             }
 
@@ -460,6 +478,9 @@ enum Reservation {
     /// The Reservation is accepted by the relay.
     Accepted {
         renewal_timeout: Delay,
+        /// Whether to renew the reservation once `renewal_timeout` elapses, instead of
+        /// dropping it.
+        auto_renew: bool,
         /// Buffer of messages to be send to the transport listener.
         pending_msgs: VecDeque<transport::ToListenerMsg>,
         to_listener: mpsc::Sender<transport::ToListenerMsg>,
@@ -476,6 +497,7 @@ impl Reservation {
     fn accepted(
         &mut self,
         renewal_timeout: Delay,
+        auto_renew: bool,
         addrs: Vec<Multiaddr>,
         to_listener: mpsc::Sender<transport::ToListenerMsg>,
         local_peer_id: PeerId,
@@ -501,6 +523,7 @@ impl Reservation {
 
         *self = Reservation::Accepted {
             renewal_timeout,
+            auto_renew,
             pending_msgs,
             to_listener,
         };
@@ -554,9 +577,11 @@ impl Reservation {
         let (next_reservation, poll_val) = match std::mem::replace(self, Reservation::None) {
             Reservation::Accepted {
                 mut renewal_timeout,
+                auto_renew,
                 pending_msgs,
                 to_listener,
             } => match renewal_timeout.poll_unpin(cx) {
+                Poll::Ready(()) if !auto_renew => (Reservation::None, Poll::Pending),
                 Poll::Ready(()) => (
                     Reservation::Renewing { pending_msgs },
                     Poll::Ready(Some(to_listener)),
@@ -564,6 +589,7 @@ impl Reservation {
                 Poll::Pending => (
                     Reservation::Accepted {
                         renewal_timeout,
+                        auto_renew,
                         pending_msgs,
                         to_listener,
                     },
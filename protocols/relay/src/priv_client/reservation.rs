@@ -0,0 +1,227 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Explicit reservations made via [`Behaviour::reserve`](super::Behaviour::reserve).
+//!
+//! An explicit reservation listens on the `/p2p-circuit` address of the relay like
+//! [`Swarm::listen_on`](libp2p_swarm::Swarm::listen_on) does, such that inbound circuits
+//! are reported as connections of the listener. Its state is derived from the events of
+//! that listener.
+
+use futures::task::AtomicWaker;
+use futures::FutureExt;
+use futures_timer::Delay;
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::transport::ListenerId;
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use libp2p_swarm::ConnectionId;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Options for a reservation made via [`Behaviour::reserve`](super::Behaviour::reserve).
+#[derive(Debug, Clone)]
+pub struct ReservationOptions {
+    relay_addr: Multiaddr,
+    duration: Option<Duration>,
+    auto_renew: bool,
+}
+
+impl ReservationOptions {
+    /// Creates options for a reservation on the relay reachable at the given address,
+    /// renewed until it is cancelled.
+    pub fn new(relay_addr: Multiaddr) -> Self {
+        Self {
+            relay_addr,
+            duration: None,
+            auto_renew: true,
+        }
+    }
+
+    /// Sets the duration after which the reservation is closed instead of being renewed
+    /// any further.
+    ///
+    /// Unset by default, i.e. the reservation lasts until it is cancelled.
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Sets whether the reservation is renewed before it expires on the relay.
+    ///
+    /// Without renewals, the reservation is closed once it would be renewed.
+    /// `true` by default.
+    pub fn with_auto_renew(mut self, auto_renew: bool) -> Self {
+        self.auto_renew = auto_renew;
+        self
+    }
+
+    /// Returns the `/p2p-circuit` address to listen on for the reservation.
+    pub(crate) fn listen_addr(&self, relay_peer_id: PeerId) -> Multiaddr {
+        let mut addr = self.relay_addr.clone();
+        if !matches!(addr.iter().last(), Some(Protocol::P2p(_))) {
+            addr.push(Protocol::P2p(relay_peer_id));
+        }
+        addr.with(Protocol::P2pCircuit)
+    }
+}
+
+/// The state of a reservation made via [`Behaviour::reserve`](super::Behaviour::reserve).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationState {
+    /// The reservation was requested but not accepted by the relay yet.
+    Pending,
+    /// The reservation was accepted by the relay and inbound circuits are accepted.
+    Accepted,
+    /// The reservation was closed after being accepted, e.g. because its duration
+    /// elapsed, it was not renewed or the connection to the relay was closed.
+    Closed,
+    /// The reservation was cancelled via [`ReservationHandle::cancel`].
+    Cancelled,
+    /// The reservation was never accepted, e.g. because it was denied by the relay
+    /// or the relay could not be reached.
+    Failed,
+}
+
+impl ReservationState {
+    /// Returns whether the reservation may still accept inbound circuits.
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Pending | Self::Accepted)
+    }
+}
+
+/// A handle to a reservation made via [`Behaviour::reserve`](super::Behaviour::reserve).
+///
+/// Dropping the handle does not cancel the reservation.
+#[derive(Debug, Clone)]
+pub struct ReservationHandle {
+    relay_peer_id: PeerId,
+    listener_id: ListenerId,
+    state: Arc<Mutex<ReservationState>>,
+    /// Wakes the [`Behaviour`](super::Behaviour) to close a cancelled reservation.
+    waker: Arc<AtomicWaker>,
+}
+
+impl ReservationHandle {
+    /// Returns the relay the reservation is made on.
+    pub fn relay_peer_id(&self) -> PeerId {
+        self.relay_peer_id
+    }
+
+    /// Returns the [`ListenerId`] of the listener accepting the inbound circuits of the
+    /// reservation, as reported in the [`SwarmEvent`](libp2p_swarm::SwarmEvent)s.
+    pub fn listener_id(&self) -> ListenerId {
+        self.listener_id
+    }
+
+    /// Returns the current state of the reservation.
+    pub fn state(&self) -> ReservationState {
+        *self.state.lock().expect("lock not to be poisoned")
+    }
+
+    /// Cancels the reservation, closing its listener.
+    ///
+    /// The relay keeps the reservation until it expires, as the protocol does not
+    /// allow to release it, but inbound circuits are denied. Does nothing if the
+    /// reservation is not active anymore.
+    pub fn cancel(&self) {
+        let mut state = self.state.lock().expect("lock not to be poisoned");
+        if state.is_active() {
+            *state = ReservationState::Cancelled;
+            self.waker.wake();
+        }
+    }
+}
+
+/// The state of an explicit reservation tracked by the [`Behaviour`](super::Behaviour).
+pub(crate) struct Reservation {
+    state: Arc<Mutex<ReservationState>>,
+    pub(crate) auto_renew: bool,
+    /// Elapses once the reservation is to be closed, if it has a duration.
+    pub(crate) deadline: Option<Delay>,
+    /// The connection to the relay the reservation is made on, once known.
+    pub(crate) connection: Option<ConnectionId>,
+    /// Whether the listener of the reservation is being closed.
+    closing: bool,
+}
+
+impl Reservation {
+    pub(crate) fn new(
+        relay_peer_id: PeerId,
+        listener_id: ListenerId,
+        options: &ReservationOptions,
+        waker: Arc<AtomicWaker>,
+    ) -> (Self, ReservationHandle) {
+        let state = Arc::new(Mutex::new(ReservationState::Pending));
+        let reservation = Self {
+            state: state.clone(),
+            auto_renew: options.auto_renew,
+            deadline: options.duration.map(Delay::new),
+            connection: None,
+            closing: false,
+        };
+        let handle = ReservationHandle {
+            relay_peer_id,
+            listener_id,
+            state,
+            waker,
+        };
+        (reservation, handle)
+    }
+
+    /// Returns [`Poll::Ready`] once the reservation is to be closed, i.e. it was cancelled
+    /// or its duration elapsed.
+    pub(crate) fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.closing {
+            return Poll::Pending;
+        }
+        let cancelled =
+            *self.state.lock().expect("lock not to be poisoned") == ReservationState::Cancelled;
+        let elapsed = self
+            .deadline
+            .as_mut()
+            .is_some_and(|deadline| deadline.poll_unpin(cx).is_ready());
+        if !cancelled && !elapsed {
+            return Poll::Pending;
+        }
+        self.transition(ReservationState::Closed);
+        self.closing = true;
+        Poll::Ready(())
+    }
+
+    /// Transitions an active reservation into the given state.
+    pub(crate) fn transition(&self, to: ReservationState) {
+        let mut state = self.state.lock().expect("lock not to be poisoned");
+        if state.is_active() {
+            *state = to;
+        }
+    }
+
+    /// Handles the closing of the listener of the reservation.
+    pub(crate) fn on_listener_closed(&self) {
+        let mut state = self.state.lock().expect("lock not to be poisoned");
+        *state = match *state {
+            ReservationState::Pending => ReservationState::Failed,
+            ReservationState::Accepted => ReservationState::Closed,
+            state => state,
+        };
+    }
+}
@@ -18,6 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use futures::channel::oneshot;
 use futures::executor::LocalPool;
 use futures::future::FutureExt;
 use futures::io::{AsyncRead, AsyncWrite};
@@ -33,6 +34,7 @@ use libp2p_identity::PeerId;
 use libp2p_ping as ping;
 use libp2p_plaintext as plaintext;
 use libp2p_relay as relay;
+use libp2p_relay::client::{ReservationOptions, ReservationState};
 use libp2p_swarm::dial_opts::DialOpts;
 use libp2p_swarm::{Config, DialError, NetworkBehaviour, Swarm, SwarmEvent};
 use libp2p_swarm_test::SwarmExt;
@@ -338,6 +340,327 @@ fn propagate_reservation_error_to_listener() {
     ));
 }
 
+#[test]
+fn cancel_explicit_reservation() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay();
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone());
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut client = build_client();
+    let reservation = client
+        .behaviour_mut()
+        .relay
+        .reserve(relay_peer_id, ReservationOptions::new(relay_addr));
+    assert_eq!(reservation.state(), ReservationState::Pending);
+
+    pool.run_until(client.wait(|e| match e {
+        SwarmEvent::NewListenAddr { listener_id, .. }
+            if listener_id == reservation.listener_id() =>
+        {
+            Some(())
+        }
+        _ => None,
+    }));
+    assert_eq!(reservation.state(), ReservationState::Accepted);
+
+    reservation.cancel();
+    assert_eq!(reservation.state(), ReservationState::Cancelled);
+
+    pool.run_until(client.wait(|e| match e {
+        SwarmEvent::ListenerClosed {
+            listener_id,
+            reason: Ok(()),
+            ..
+        } if listener_id == reservation.listener_id() => Some(()),
+        _ => None,
+    }));
+    assert_eq!(reservation.state(), ReservationState::Cancelled);
+}
+
+#[test]
+fn explicit_reservation_without_auto_renew_is_closed() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay();
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone());
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut client = build_client();
+    let reservation = client.behaviour_mut().relay.reserve(
+        relay_peer_id,
+        ReservationOptions::new(relay_addr).with_auto_renew(false),
+    );
+
+    let renewed = pool.run_until(client.wait(|e| match e {
+        SwarmEvent::Behaviour(ClientEvent::Relay(
+            relay::client::Event::ReservationReqAccepted { renewal, .. },
+        )) if renewal => Some(true),
+        SwarmEvent::ListenerClosed { listener_id, .. }
+            if listener_id == reservation.listener_id() =>
+        {
+            Some(false)
+        }
+        _ => None,
+    }));
+    assert!(!renewed);
+    assert_eq!(reservation.state(), ReservationState::Closed);
+}
+
+#[test]
+fn explicit_reservation_is_closed_after_duration() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay();
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone());
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut client = build_client();
+    let reservation = client.behaviour_mut().relay.reserve(
+        relay_peer_id,
+        ReservationOptions::new(relay_addr).with_duration(Duration::from_secs(3)),
+    );
+
+    // The reservation is renewed within its duration.
+    pool.run_until(wait_for_reservation_renewal(&mut client, relay_peer_id));
+    assert_eq!(reservation.state(), ReservationState::Accepted);
+
+    pool.run_until(client.wait(|e| match e {
+        SwarmEvent::ListenerClosed { listener_id, .. }
+            if listener_id == reservation.listener_id() =>
+        {
+            Some(())
+        }
+        _ => None,
+    }));
+    assert_eq!(reservation.state(), ReservationState::Closed);
+}
+
+#[test]
+fn reservation_denied_by_admission_control() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut client = build_client();
+    let client_peer_id = *client.local_peer_id();
+    let mut relay = build_relay_with_config(relay::Config::default().admission_control(
+        move |request: relay::AdmissionRequest| {
+            assert!(matches!(
+                request,
+                relay::AdmissionRequest::Reservation {
+                    renewed: false,
+                    usage: relay::Usage {
+                        num_reservations: 0,
+                        ..
+                    },
+                    ..
+                }
+            ));
+            let src_peer_id = request.src_peer_id();
+            async move {
+                futures_timer::Delay::new(Duration::from_millis(10)).await;
+                if src_peer_id == client_peer_id {
+                    relay::Admission::Deny
+                } else {
+                    relay::Admission::Accept
+                }
+            }
+        },
+    ));
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone());
+    spawn_swarm_on_pool(&pool, relay);
+
+    let client_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id))
+        .with(Protocol::P2pCircuit);
+    let reservation_listener = client.listen_on(client_addr).unwrap();
+
+    // Wait for connection to relay.
+    assert!(pool.run_until(wait_for_dial(&mut client, relay_peer_id)));
+
+    let error = pool.run_until(client.wait(|e| match e {
+        SwarmEvent::ListenerClosed {
+            listener_id,
+            reason: Err(e),
+            ..
+        } if listener_id == reservation_listener => Some(e),
+        _ => None,
+    }));
+
+    let error = error
+        .source()
+        .unwrap()
+        .downcast_ref::<relay::outbound::hop::ReserveError>()
+        .unwrap();
+
+    assert!(matches!(error, relay::outbound::hop::ReserveError::Refused));
+}
+
+#[test]
+fn circuit_denied_by_admission_control() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay_with_config(relay::Config::default().admission_control(
+        |request: relay::AdmissionRequest| async move {
+            match request {
+                relay::AdmissionRequest::Reservation { .. } => relay::Admission::Accept,
+                relay::AdmissionRequest::Circuit { .. } => relay::Admission::Deny,
+            }
+        },
+    ));
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone());
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut dst = build_client();
+    let dst_peer_id = *dst.local_peer_id();
+    let dst_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id));
+
+    dst.listen_on(dst_addr.clone()).unwrap();
+
+    assert!(pool.run_until(wait_for_dial(&mut dst, relay_peer_id)));
+    pool.run_until(wait_for_reservation(
+        &mut dst,
+        dst_addr.clone(),
+        relay_peer_id,
+        false, // No renewal.
+    ));
+    spawn_swarm_on_pool(&pool, dst);
+
+    let mut src = build_client();
+    let opts = DialOpts::from(dst_addr);
+    let circuit_connection_id = opts.connection_id();
+    src.dial(opts).unwrap();
+
+    let error = pool.run_until(src.wait(|e| match e {
+        SwarmEvent::OutgoingConnectionError {
+            connection_id,
+            error: DialError::Transport(mut errors),
+            ..
+        } if connection_id == circuit_connection_id => Some(errors.remove(0).1),
+        _ => None,
+    }));
+
+    let error = error
+        .source()
+        .unwrap()
+        .source()
+        .unwrap()
+        .downcast_ref::<relay::outbound::hop::ConnectError>()
+        .unwrap();
+
+    assert!(matches!(
+        error,
+        relay::outbound::hop::ConnectError::PermissionDenied
+    ));
+}
+
+#[test]
+fn reservation_of_connection_closed_during_admission_is_dropped() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut pool = LocalPool::new();
+
+    // Hold back the decision on the first request until its connection closed.
+    let (requested_tx, mut requested_rx) = oneshot::channel();
+    let (admit_tx, admit_rx) = oneshot::channel::<()>();
+    let mut first_request = Some((requested_tx, admit_rx));
+    let mut config = relay::Config::default().admission_control(move |_| {
+        let first_request = first_request.take();
+        async move {
+            if let Some((requested, admit)) = first_request {
+                let _ = requested.send(());
+                let _ = admit.await;
+            }
+            relay::Admission::Accept
+        }
+    });
+    config.max_reservations = 1;
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay_with_config(config);
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone());
+
+    let client_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id))
+        .with(Protocol::P2pCircuit);
+    let mut closing_client = build_client();
+    let closing_client_id = *closing_client.local_peer_id();
+    closing_client.listen_on(client_addr.clone()).unwrap();
+
+    pool.run_until(async {
+        loop {
+            futures::select! {
+                _ = requested_rx => break,
+                _ = relay.select_next_some() => {}
+                _ = closing_client.select_next_some() => {}
+            }
+        }
+    });
+    drop(closing_client);
+    pool.run_until(relay.wait(|e| match e {
+        SwarmEvent::ConnectionClosed { peer_id, .. } if peer_id == closing_client_id => Some(()),
+        _ => None,
+    }));
+
+    // The accepted reservation of the closed connection must not take the only slot.
+    admit_tx.send(()).unwrap();
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut client = build_client();
+    let client_peer_id = *client.local_peer_id();
+    client.listen_on(client_addr.clone()).unwrap();
+
+    assert!(pool.run_until(wait_for_dial(&mut client, relay_peer_id)));
+    pool.run_until(wait_for_reservation(
+        &mut client,
+        client_addr.with(Protocol::P2p(client_peer_id)),
+        relay_peer_id,
+        false, // No renewal.
+    ));
+}
+
 #[test]
 fn propagate_connect_error_to_unknown_peer_to_dialer() {
     let _ = tracing_subscriber::fmt()
@@ -554,6 +877,21 @@ async fn wait_for_reservation(
     }
 }
 
+async fn wait_for_reservation_renewal(client: &mut Swarm<Client>, relay_peer_id: PeerId) {
+    client
+        .wait(|e| match e {
+            SwarmEvent::Behaviour(ClientEvent::Relay(
+                relay::client::Event::ReservationReqAccepted {
+                    relay_peer_id: peer_id,
+                    renewal: true,
+                    ..
+                },
+            )) if peer_id == relay_peer_id => Some(()),
+            _ => None,
+        })
+        .await
+}
+
 async fn wait_for_dial(client: &mut Swarm<Client>, remote: PeerId) -> bool {
     loop {
         match client.select_next_some().await {
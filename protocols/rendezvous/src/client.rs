@@ -36,13 +36,17 @@ use std::iter;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
+mod store;
+
+pub use store::{MemoryStore, Store};
+
 pub struct Behaviour {
     inner: libp2p_request_response::Behaviour<crate::codec::Codec>,
 
     keypair: Keypair,
 
     waiting_for_register: HashMap<OutboundRequestId, (PeerId, Namespace)>,
-    waiting_for_discovery: HashMap<OutboundRequestId, (PeerId, Option<Namespace>)>,
+    waiting_for_discovery: HashMap<OutboundRequestId, PendingDiscovery>,
 
     /// Hold addresses of all peers that we have discovered so far.
     ///
@@ -55,11 +59,39 @@ pub struct Behaviour {
     expiring_registrations: FuturesUnordered<BoxFuture<'static, (PeerId, Namespace)>>,
 
     external_addresses: ExternalAddresses,
+
+    /// Persists discovery cookies and registrations across restarts.
+    store: Box<dyn Store + Send>,
+}
+
+/// A DISCOVER request awaiting its response.
+struct PendingDiscovery {
+    rendezvous_node: PeerId,
+    namespace: Option<Namespace>,
+    limit: Option<u64>,
+    /// Whether the request was sent with a cookie.
+    with_cookie: bool,
 }
 
 impl Behaviour {
     /// Create a new instance of the rendezvous [`NetworkBehaviour`].
     pub fn new(keypair: Keypair) -> Self {
+        Self::with_store(keypair, MemoryStore::default())
+    }
+
+    /// Create a new instance of the rendezvous [`NetworkBehaviour`] that persists
+    /// its discovery cookies and registrations in the given [`Store`].
+    ///
+    /// Registrations found in the store are refreshed as soon as the local node
+    /// learns about its external addresses. Cookies found in the store are used
+    /// for subsequent calls to [`Behaviour::discover`] without a cookie.
+    pub fn with_store(keypair: Keypair, store: impl Store + Send + 'static) -> Self {
+        let registered_namespaces = store
+            .registrations()
+            .into_iter()
+            .map(|(rendezvous_node, namespace, ttl)| ((rendezvous_node, namespace), ttl))
+            .collect();
+
         Self {
             inner: libp2p_request_response::Behaviour::with_codec(
                 crate::codec::Codec::default(),
@@ -70,11 +102,12 @@ impl Behaviour {
             waiting_for_register: Default::default(),
             waiting_for_discovery: Default::default(),
             discovered_peers: Default::default(),
-            registered_namespaces: Default::default(),
+            registered_namespaces,
             expiring_registrations: FuturesUnordered::from_iter(vec![
                 futures::future::pending().boxed()
             ]),
             external_addresses: Default::default(),
+            store: Box::new(store),
         }
     }
 
@@ -108,6 +141,7 @@ impl Behaviour {
     pub fn unregister(&mut self, namespace: Namespace, rendezvous_node: PeerId) {
         self.registered_namespaces
             .retain(|(rz_node, ns), _| rz_node.ne(&rendezvous_node) && ns.ne(&namespace));
+        self.store.remove_registration(&rendezvous_node, &namespace);
 
         self.inner
             .send_request(&rendezvous_node, Unregister(namespace));
@@ -120,6 +154,11 @@ impl Behaviour {
     /// A successfully discovery returns a cookie within [`Event::Discovered`].
     /// Such a cookie can be used to only fetch the _delta_ of registrations since
     /// the cookie was acquired.
+    ///
+    /// If no cookie is given, the cookie of the last discovery for the namespace at the
+    /// rendezvous peer is taken from the [`Store`], if any. Should the rendezvous peer
+    /// reject the cookie, e.g. because it forgot about it, the cookie is removed from
+    /// the store and the discovery is retried without a cookie.
     pub fn discover(
         &mut self,
         namespace: Option<Namespace>,
@@ -127,6 +166,19 @@ impl Behaviour {
         limit: Option<u64>,
         rendezvous_node: PeerId,
     ) {
+        let cookie = cookie.or_else(|| self.store.cookie(&rendezvous_node, namespace.as_ref()));
+
+        self.send_discover(namespace, cookie, limit, rendezvous_node);
+    }
+
+    fn send_discover(
+        &mut self,
+        namespace: Option<Namespace>,
+        cookie: Option<Cookie>,
+        limit: Option<u64>,
+        rendezvous_node: PeerId,
+    ) {
+        let with_cookie = cookie.is_some();
         let req_id = self.inner.send_request(
             &rendezvous_node,
             Discover {
@@ -136,8 +188,15 @@ impl Behaviour {
             },
         );
 
-        self.waiting_for_discovery
-            .insert(req_id, (rendezvous_node, namespace));
+        self.waiting_for_discovery.insert(
+            req_id,
+            PendingDiscovery {
+                rendezvous_node,
+                namespace,
+                limit,
+                with_cookie,
+            },
+        );
     }
 }
 
@@ -337,7 +396,12 @@ impl Behaviour {
             });
         };
 
-        if let Some((rendezvous_node, namespace)) = self.waiting_for_discovery.remove(req_id) {
+        if let Some(PendingDiscovery {
+            rendezvous_node,
+            namespace,
+            ..
+        }) = self.waiting_for_discovery.remove(req_id)
+        {
             return Some(Event::DiscoverFailed {
                 rendezvous_node,
                 namespace,
@@ -360,6 +424,8 @@ impl Behaviour {
                 {
                     self.registered_namespaces
                         .insert((rendezvous_node, namespace.clone()), ttl);
+                    self.store
+                        .put_registration(rendezvous_node, namespace.clone(), ttl);
 
                     return Some(Event::Registered {
                         rendezvous_node,
@@ -384,8 +450,14 @@ impl Behaviour {
                 None
             }
             DiscoverResponse(Ok((registrations, cookie))) => {
-                if let Some((rendezvous_node, _ns)) = self.waiting_for_discovery.remove(request_id)
+                if let Some(PendingDiscovery {
+                    rendezvous_node,
+                    namespace,
+                    ..
+                }) = self.waiting_for_discovery.remove(request_id)
                 {
+                    self.store
+                        .put_cookie(rendezvous_node, namespace, cookie.clone());
                     self.discovered_peers
                         .extend(registrations.iter().map(|registration| {
                             let peer_id = registration.record.peer_id();
@@ -418,10 +490,29 @@ impl Behaviour {
                 None
             }
             DiscoverResponse(Err(error_code)) => {
-                if let Some((rendezvous_node, ns)) = self.waiting_for_discovery.remove(request_id) {
+                if let Some(PendingDiscovery {
+                    rendezvous_node,
+                    namespace,
+                    limit,
+                    with_cookie,
+                }) = self.waiting_for_discovery.remove(request_id)
+                {
+                    if with_cookie && error_code == ErrorCode::InvalidCookie {
+                        tracing::debug!(
+                            %rendezvous_node,
+                            ?namespace,
+                            "Cookie was rejected, discovering again without cookie"
+                        );
+                        self.store
+                            .remove_cookie(&rendezvous_node, namespace.as_ref());
+                        self.send_discover(namespace, None, limit, rendezvous_node);
+
+                        return None;
+                    }
+
                     return Some(Event::DiscoverFailed {
                         rendezvous_node,
-                        namespace: ns,
+                        namespace,
                         error: error_code,
                     });
                 }
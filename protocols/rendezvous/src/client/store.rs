@@ -0,0 +1,99 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::codec::{Cookie, Namespace, Ttl};
+use libp2p_identity::PeerId;
+use std::collections::HashMap;
+
+/// Storage for the state of a rendezvous client that should survive restarts.
+///
+/// Persisting the cookies of previous discoveries allows a restarted client to
+/// only fetch the registrations that changed since, instead of re-fetching entire
+/// namespaces. Persisting the registrations allows a restarted client to refresh
+/// them as soon as it learns about its external addresses.
+///
+/// Cookies can be persisted via [`Cookie::into_wire_encoding`] and
+/// [`Cookie::from_wire_encoding`].
+pub trait Store {
+    /// Gets the cookie of the last discovery at the given rendezvous node,
+    /// for the given namespace or for all namespaces.
+    fn cookie(&self, rendezvous_node: &PeerId, namespace: Option<&Namespace>) -> Option<Cookie>;
+
+    /// Stores the cookie of a discovery at the given rendezvous node.
+    fn put_cookie(&mut self, rendezvous_node: PeerId, namespace: Option<Namespace>, cookie: Cookie);
+
+    /// Removes the cookie of the discovery at the given rendezvous node,
+    /// e.g. because the rendezvous node no longer accepts it.
+    fn remove_cookie(&mut self, rendezvous_node: &PeerId, namespace: Option<&Namespace>);
+
+    /// Gets all registrations of the local node.
+    fn registrations(&self) -> Vec<(PeerId, Namespace, Ttl)>;
+
+    /// Stores a registration of the local node in a namespace at the given rendezvous node.
+    fn put_registration(&mut self, rendezvous_node: PeerId, namespace: Namespace, ttl: Ttl);
+
+    /// Removes the registration of the local node in a namespace at the given rendezvous node.
+    fn remove_registration(&mut self, rendezvous_node: &PeerId, namespace: &Namespace);
+}
+
+/// In-memory implementation of a [`Store`], i.e. one that does not persist
+/// any state across restarts.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStore {
+    cookies: HashMap<(PeerId, Option<Namespace>), Cookie>,
+    registrations: HashMap<(PeerId, Namespace), Ttl>,
+}
+
+impl Store for MemoryStore {
+    fn cookie(&self, rendezvous_node: &PeerId, namespace: Option<&Namespace>) -> Option<Cookie> {
+        self.cookies
+            .get(&(*rendezvous_node, namespace.cloned()))
+            .cloned()
+    }
+
+    fn put_cookie(
+        &mut self,
+        rendezvous_node: PeerId,
+        namespace: Option<Namespace>,
+        cookie: Cookie,
+    ) {
+        self.cookies.insert((rendezvous_node, namespace), cookie);
+    }
+
+    fn remove_cookie(&mut self, rendezvous_node: &PeerId, namespace: Option<&Namespace>) {
+        self.cookies.remove(&(*rendezvous_node, namespace.cloned()));
+    }
+
+    fn registrations(&self) -> Vec<(PeerId, Namespace, Ttl)> {
+        self.registrations
+            .iter()
+            .map(|((rendezvous_node, namespace), ttl)| (*rendezvous_node, namespace.clone(), *ttl))
+            .collect()
+    }
+
+    fn put_registration(&mut self, rendezvous_node: PeerId, namespace: Namespace, ttl: Ttl) {
+        self.registrations.insert((rendezvous_node, namespace), ttl);
+    }
+
+    fn remove_registration(&mut self, rendezvous_node: &PeerId, namespace: &Namespace) {
+        self.registrations
+            .remove(&(*rendezvous_node, namespace.clone()));
+    }
+}
@@ -24,7 +24,7 @@ use libp2p_core::multiaddr::Protocol;
 use libp2p_core::Multiaddr;
 use libp2p_identity as identity;
 use libp2p_rendezvous as rendezvous;
-use libp2p_rendezvous::client::RegisterError;
+use libp2p_rendezvous::client::{RegisterError, Store};
 use libp2p_swarm::{DialError, Swarm, SwarmEvent};
 use libp2p_swarm_test::SwarmExt;
 use std::time::Duration;
@@ -419,6 +419,105 @@ async fn registration_on_clients_expire() {
     assert!(matches!(error, DialError::NoAddresses));
 }
 
+#[tokio::test]
+async fn given_stored_cookie_then_discovery_resumes_after_restart() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let namespace = rendezvous::Namespace::from_static("some-namespace");
+    let ([mut alice, mut bob], mut robert) =
+        new_server_with_connected_clients(rendezvous::server::Config::default()).await;
+    let roberts_peer_id = *robert.local_peer_id();
+
+    alice
+        .behaviour_mut()
+        .register(namespace.clone(), roberts_peer_id, None)
+        .unwrap();
+    match libp2p_swarm_test::drive(&mut alice, &mut robert).await {
+        (
+            [rendezvous::client::Event::Registered { .. }],
+            [rendezvous::server::Event::PeerRegistered { .. }],
+        ) => {}
+        events => panic!("Unexpected events: {events:?}"),
+    }
+
+    bob.behaviour_mut()
+        .discover(Some(namespace.clone()), None, None, roberts_peer_id);
+    let cookie = match libp2p_swarm_test::drive(&mut bob, &mut robert).await {
+        (
+            [rendezvous::client::Event::Discovered {
+                registrations,
+                cookie,
+                ..
+            }],
+            [rendezvous::server::Event::DiscoverServed { .. }],
+        ) => {
+            assert_eq!(registrations.len(), 1);
+            cookie
+        }
+        events => panic!("Unexpected events: {events:?}"),
+    };
+
+    // Restart bob with the cookie persisted in its store.
+    let mut store = rendezvous::client::MemoryStore::default();
+    store.put_cookie(roberts_peer_id, Some(namespace.clone()), cookie);
+    let mut bob = new_client_with_store(store).await;
+    bob.connect(&mut robert).await;
+
+    bob.behaviour_mut()
+        .discover(Some(namespace.clone()), None, None, roberts_peer_id);
+    match libp2p_swarm_test::drive(&mut bob, &mut robert).await {
+        (
+            [rendezvous::client::Event::Discovered { registrations, .. }],
+            [rendezvous::server::Event::DiscoverServed { .. }],
+        ) => assert!(registrations.is_empty()),
+        events => panic!("Unexpected events: {events:?}"),
+    }
+}
+
+#[tokio::test]
+async fn given_rejected_cookie_then_discovery_is_retried_without_cookie() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let namespace = rendezvous::Namespace::from_static("some-namespace");
+    let ([mut alice], mut robert) =
+        new_server_with_connected_clients(rendezvous::server::Config::default()).await;
+    let roberts_peer_id = *robert.local_peer_id();
+
+    alice
+        .behaviour_mut()
+        .register(namespace.clone(), roberts_peer_id, None)
+        .unwrap();
+    match libp2p_swarm_test::drive(&mut alice, &mut robert).await {
+        (
+            [rendezvous::client::Event::Registered { .. }],
+            [rendezvous::server::Event::PeerRegistered { .. }],
+        ) => {}
+        events => panic!("Unexpected events: {events:?}"),
+    }
+
+    // A cookie for another namespace is rejected by the server.
+    let mut store = rendezvous::client::MemoryStore::default();
+    store.put_cookie(
+        roberts_peer_id,
+        Some(namespace.clone()),
+        rendezvous::Cookie::for_namespace(rendezvous::Namespace::from_static("other-namespace")),
+    );
+    let mut bob = new_client_with_store(store).await;
+    bob.connect(&mut robert).await;
+
+    bob.behaviour_mut()
+        .discover(Some(namespace.clone()), None, None, roberts_peer_id);
+    match libp2p_swarm_test::drive(&mut bob, &mut robert).await {
+        (
+            [rendezvous::client::Event::Discovered { registrations, .. }],
+            [rendezvous::server::Event::DiscoverNotServed { .. }, rendezvous::server::Event::DiscoverServed { .. }],
+        ) => assert_eq!(registrations.len(), 1),
+        events => panic!("Unexpected events: {events:?}"),
+    }
+}
+
 async fn new_server_with_connected_clients<const N: usize>(
     config: rendezvous::server::Config,
 ) -> (
@@ -452,6 +551,16 @@ async fn new_client() -> Swarm<rendezvous::client::Behaviour> {
     client
 }
 
+async fn new_client_with_store(
+    store: rendezvous::client::MemoryStore,
+) -> Swarm<rendezvous::client::Behaviour> {
+    let mut client =
+        Swarm::new_ephemeral(|identity| rendezvous::client::Behaviour::with_store(identity, store));
+    client.listen().with_memory_addr_external().await;
+
+    client
+}
+
 async fn new_server(config: rendezvous::server::Config) -> Swarm<rendezvous::server::Behaviour> {
     let mut server = Swarm::new_ephemeral(|_| rendezvous::server::Behaviour::new(config));
 
@@ -0,0 +1,456 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Compression of requests and responses, negotiated per stream.
+//!
+//! For every protocol `<protocol>` and every configured [`Compression`], the protocol
+//! `<protocol>/<name>` is negotiated in preference to `<protocol>`, e.g. `/ping/1/zstd`
+//! before `/ping/1`. On a stream with compression, every message is framed as a flag
+//! indicating whether the message is compressed, followed by the length of the message
+//! as a big-endian `u32` and the message itself. Messages smaller than the threshold, see
+//! [`Config::with_compression_threshold`](crate::Config::with_compression_threshold),
+//! and messages which don't shrink are sent uncompressed.
+
+use crate::codec::Codec;
+use crate::handler::protocol::ProtocolName;
+use futures::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::{fmt, io};
+
+/// The name of the `zstd` compression, see [`Compression::name`].
+pub const ZSTD: &str = "zstd";
+/// The name of the `lz4` compression, see [`Compression::name`].
+pub const LZ4: &str = "lz4";
+
+const UNCOMPRESSED: u8 = 0;
+const COMPRESSED: u8 = 1;
+
+/// A compression algorithm for the messages of a request-response protocol,
+/// e.g. an implementation of [`ZSTD`] or [`LZ4`].
+///
+/// See [`Config::with_compression`](crate::Config::with_compression).
+pub trait Compression: Send + Sync + 'static {
+    /// The name of the algorithm, appended to the names of the protocols on which
+    /// it is negotiated, e.g. [`ZSTD`].
+    fn name(&self) -> &'static str;
+
+    /// Compresses the given message.
+    fn compress(&self, message: &[u8]) -> io::Result<Vec<u8>>;
+
+    /// Decompresses the given message, failing if it exceeds `max_size` bytes once
+    /// decompressed.
+    fn decompress(&self, message: &[u8], max_size: usize) -> io::Result<Vec<u8>>;
+}
+
+/// Statistics of the compression of messages, see
+/// [`Behaviour::compression_stats`](crate::Behaviour::compression_stats).
+///
+/// The statistics stay up to date with the [`Behaviour`](crate::Behaviour)
+/// and can be cloned cheaply, e.g. to export them as metrics.
+#[derive(Debug, Clone, Default)]
+pub struct CompressionStats {
+    inner: Arc<StatsInner>,
+}
+
+#[derive(Debug, Default)]
+struct StatsInner {
+    num_compressed: AtomicU64,
+    num_uncompressed: AtomicU64,
+    uncompressed_bytes: AtomicU64,
+    compressed_bytes: AtomicU64,
+}
+
+impl CompressionStats {
+    /// The number of messages sent or received compressed.
+    pub fn num_compressed(&self) -> u64 {
+        self.inner.num_compressed.load(Ordering::Relaxed)
+    }
+
+    /// The number of messages sent or received uncompressed on streams with compression,
+    /// i.e. messages below the threshold or which don't shrink.
+    pub fn num_uncompressed(&self) -> u64 {
+        self.inner.num_uncompressed.load(Ordering::Relaxed)
+    }
+
+    /// The total size of the compressed messages before compression.
+    pub fn uncompressed_bytes(&self) -> u64 {
+        self.inner.uncompressed_bytes.load(Ordering::Relaxed)
+    }
+
+    /// The total size of the compressed messages after compression.
+    pub fn compressed_bytes(&self) -> u64 {
+        self.inner.compressed_bytes.load(Ordering::Relaxed)
+    }
+
+    /// The compression ratio of the compressed messages, i.e. their size before compression
+    /// divided by their size after compression, or `None` if no message was compressed.
+    pub fn ratio(&self) -> Option<f64> {
+        match self.compressed_bytes() {
+            0 => None,
+            compressed => Some(self.uncompressed_bytes() as f64 / compressed as f64),
+        }
+    }
+
+    fn record_compressed(&self, uncompressed: usize, compressed: usize) {
+        self.inner.num_compressed.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .uncompressed_bytes
+            .fetch_add(uncompressed as u64, Ordering::Relaxed);
+        self.inner
+            .compressed_bytes
+            .fetch_add(compressed as u64, Ordering::Relaxed);
+    }
+
+    fn record_uncompressed(&self) {
+        self.inner.num_uncompressed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The configured [`Compression`]s, in order of preference, and their parameters.
+#[derive(Clone)]
+pub(crate) struct Compressions {
+    pub(crate) algorithms: Vec<Arc<dyn Compression>>,
+    pub(crate) threshold: usize,
+    pub(crate) max_message_size: usize,
+    pub(crate) stats: CompressionStats,
+}
+
+impl Default for Compressions {
+    fn default() -> Self {
+        Self {
+            algorithms: Vec::new(),
+            threshold: 1024,
+            max_message_size: 16 * 1024 * 1024,
+            stats: CompressionStats::default(),
+        }
+    }
+}
+
+impl fmt::Debug for Compressions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Compressions")
+            .field(
+                "algorithms",
+                &self.algorithms.iter().map(|c| c.name()).collect::<Vec<_>>(),
+            )
+            .field("threshold", &self.threshold)
+            .field("max_message_size", &self.max_message_size)
+            .finish()
+    }
+}
+
+impl Compressions {
+    /// Returns the names to negotiate for the given protocols, the protocols with
+    /// compression first.
+    pub(crate) fn protocol_names<P>(
+        &self,
+        protocols: impl IntoIterator<Item = P>,
+    ) -> Vec<ProtocolName<P>>
+    where
+        P: AsRef<str> + Clone,
+    {
+        let mut names = Vec::new();
+        for protocol in protocols {
+            for compression in &self.algorithms {
+                names.push(ProtocolName::with_compression(
+                    protocol.clone(),
+                    compression.clone(),
+                ));
+            }
+            names.push(ProtocolName::new(protocol));
+        }
+        names
+    }
+
+    async fn write<T>(
+        &self,
+        compression: &dyn Compression,
+        io: &mut T,
+        message: Vec<u8>,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        if message.len() > self.max_message_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "message too large",
+            ));
+        }
+
+        let (flag, frame) = if message.len() >= self.threshold {
+            let compressed = compression.compress(&message)?;
+            if compressed.len() < message.len() {
+                self.stats
+                    .record_compressed(message.len(), compressed.len());
+                (COMPRESSED, compressed)
+            } else {
+                self.stats.record_uncompressed();
+                (UNCOMPRESSED, message)
+            }
+        } else {
+            self.stats.record_uncompressed();
+            (UNCOMPRESSED, message)
+        };
+
+        let len = u32::try_from(frame.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too large"))?;
+        io.write_all(&[flag]).await?;
+        io.write_all(&len.to_be_bytes()).await?;
+        io.write_all(&frame).await?;
+        io.flush().await
+    }
+
+    async fn read<T>(&self, compression: &dyn Compression, io: &mut T) -> io::Result<Vec<u8>>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut header = [0; 5];
+        io.read_exact(&mut header).await?;
+        let len = u32::from_be_bytes(header[1..].try_into().expect("4 bytes")) as usize;
+        if len > self.max_message_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message too large",
+            ));
+        }
+
+        let mut frame = vec![0; len];
+        io.read_exact(&mut frame).await?;
+
+        match header[0] {
+            UNCOMPRESSED => {
+                self.stats.record_uncompressed();
+                Ok(frame)
+            }
+            COMPRESSED => {
+                let message = compression.decompress(&frame, self.max_message_size)?;
+                if message.len() > self.max_message_size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "message too large",
+                    ));
+                }
+                self.stats.record_compressed(message.len(), frame.len());
+                Ok(message)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid compression flag",
+            )),
+        }
+    }
+}
+
+/// Reads a request from the stream, decompressing it if compression was negotiated.
+pub(crate) async fn read_request<C, T>(
+    codec: &mut C,
+    compressions: &Compressions,
+    protocol: ProtocolName<C::Protocol>,
+    io: &mut T,
+) -> io::Result<C::Request>
+where
+    C: Codec + Send,
+    T: AsyncRead + Unpin + Send,
+{
+    match &protocol.compression {
+        Some(compression) => {
+            let message = compressions.read(compression.as_ref(), io).await?;
+            let mut message = message.as_slice();
+            let read = codec.read_request(&protocol.protocol, &mut message);
+            read.await
+        }
+        None => {
+            let read = codec.read_request(&protocol.protocol, io);
+            read.await
+        }
+    }
+}
+
+/// Reads a response from the stream, decompressing it if compression was negotiated.
+pub(crate) async fn read_response<C, T>(
+    codec: &mut C,
+    compressions: &Compressions,
+    protocol: ProtocolName<C::Protocol>,
+    io: &mut T,
+) -> io::Result<C::Response>
+where
+    C: Codec + Send,
+    T: AsyncRead + Unpin + Send,
+{
+    match &protocol.compression {
+        Some(compression) => {
+            let message = compressions.read(compression.as_ref(), io).await?;
+            let mut message = message.as_slice();
+            let read = codec.read_response(&protocol.protocol, &mut message);
+            read.await
+        }
+        None => {
+            let read = codec.read_response(&protocol.protocol, io);
+            read.await
+        }
+    }
+}
+
+/// Writes a request to the stream, compressing it if compression was negotiated.
+pub(crate) async fn write_request<C, T>(
+    codec: &mut C,
+    compressions: &Compressions,
+    protocol: ProtocolName<C::Protocol>,
+    io: &mut T,
+    request: C::Request,
+) -> io::Result<()>
+where
+    C: Codec + Send,
+    T: AsyncWrite + Unpin + Send,
+{
+    match &protocol.compression {
+        Some(compression) => {
+            let mut message = Vec::new();
+            let write = codec.write_request(&protocol.protocol, &mut message, request);
+            write.await?;
+            compressions.write(compression.as_ref(), io, message).await
+        }
+        None => {
+            let write = codec.write_request(&protocol.protocol, io, request);
+            write.await
+        }
+    }
+}
+
+/// Writes a response to the stream, compressing it if compression was negotiated.
+pub(crate) async fn write_response<C, T>(
+    codec: &mut C,
+    compressions: &Compressions,
+    protocol: ProtocolName<C::Protocol>,
+    io: &mut T,
+    response: C::Response,
+) -> io::Result<()>
+where
+    C: Codec + Send,
+    T: AsyncWrite + Unpin + Send,
+{
+    match &protocol.compression {
+        Some(compression) => {
+            let mut message = Vec::new();
+            let write = codec.write_response(&protocol.protocol, &mut message, response);
+            write.await?;
+            compressions.write(compression.as_ref(), io, message).await
+        }
+        None => {
+            let write = codec.write_response(&protocol.protocol, io, response);
+            write.await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    /// Replaces runs of a byte by the byte and the length of the run.
+    struct RunLength;
+
+    impl Compression for RunLength {
+        fn name(&self) -> &'static str {
+            "rle"
+        }
+
+        fn compress(&self, message: &[u8]) -> io::Result<Vec<u8>> {
+            let mut compressed = Vec::new();
+            for &byte in message {
+                match compressed.as_mut_slice() {
+                    [.., run, len] if *run == byte && *len < u8::MAX => *len += 1,
+                    _ => compressed.extend([byte, 1]),
+                }
+            }
+            Ok(compressed)
+        }
+
+        fn decompress(&self, message: &[u8], max_size: usize) -> io::Result<Vec<u8>> {
+            let mut decompressed = Vec::new();
+            for run in message.chunks(2) {
+                decompressed.extend(std::iter::repeat(run[0]).take(run[1] as usize));
+                if decompressed.len() > max_size {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+            }
+            Ok(decompressed)
+        }
+    }
+
+    fn roundtrip(compressions: &Compressions, message: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut frame = Vec::new();
+        block_on(compressions.write(&RunLength, &mut frame, message))?;
+        block_on(compressions.read(&RunLength, &mut frame.as_slice()))
+    }
+
+    #[test]
+    fn compresses_messages_above_threshold() {
+        let compressions = Compressions {
+            threshold: 8,
+            ..Default::default()
+        };
+
+        assert_eq!(roundtrip(&compressions, vec![1; 4]).unwrap(), vec![1; 4]);
+        assert_eq!(
+            roundtrip(&compressions, vec![1; 100]).unwrap(),
+            vec![1; 100]
+        );
+        // Messages which don't shrink are sent uncompressed.
+        let message = (0..100).collect::<Vec<u8>>();
+        assert_eq!(roundtrip(&compressions, message.clone()).unwrap(), message);
+
+        let stats = &compressions.stats;
+        // Each message is counted when written and when read.
+        assert_eq!(stats.num_compressed(), 2);
+        assert_eq!(stats.num_uncompressed(), 4);
+        assert_eq!(stats.ratio(), Some(50.0));
+    }
+
+    #[test]
+    fn rejects_messages_above_max_size() {
+        let compressions = Compressions {
+            threshold: 8,
+            max_message_size: 100,
+            ..Default::default()
+        };
+
+        assert!(roundtrip(&compressions, vec![1; 101]).is_err());
+
+        // A compressed message exceeding the maximum size once decompressed.
+        let frame = [[COMPRESSED, 0, 0, 0, 2].as_slice(), &[1, 200]].concat();
+        assert!(block_on(compressions.read(&RunLength, &mut frame.as_slice())).is_err());
+    }
+
+    #[test]
+    fn prefers_protocols_with_compression() {
+        let compressions = Compressions {
+            algorithms: vec![Arc::new(RunLength)],
+            ..Default::default()
+        };
+
+        let names = compressions.protocol_names(["/a/1", "/b/1"]);
+        let names = names.iter().map(|n| n.as_ref()).collect::<Vec<_>>();
+        assert_eq!(names, ["/a/1/rle", "/a/1", "/b/1/rle", "/b/1"]);
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Detection of duplicate inbound requests, see [`Behaviour::with_duplicate_detection`].
+//!
+//! [`Behaviour::with_duplicate_detection`]: crate::Behaviour::with_duplicate_detection
+
+use crate::InboundRequestId;
+use futures::channel::oneshot;
+use instant::Instant;
+use libp2p_identity::PeerId;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+
+/// How duplicate inbound requests are handled, see
+/// [`Behaviour::with_duplicate_detection`](crate::Behaviour::with_duplicate_detection).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Duplicate requests are not emitted. Instead, they are answered with
+    /// the response to the original request, once it is sent.
+    Replay,
+    /// Duplicate requests are emitted like any other request and flagged
+    /// via [`ResponseChannel::duplicate_of`](crate::ResponseChannel::duplicate_of).
+    Flag,
+}
+
+/// What to do with an inbound request, as determined by [`Duplicates::on_request`].
+pub(crate) enum Inbound<TResponse> {
+    /// The request is to be emitted, possibly as a duplicate of an earlier request.
+    Emit {
+        duplicate_of: Option<InboundRequestId>,
+        sender: oneshot::Sender<TResponse>,
+    },
+    /// The request is a duplicate answered with the response to the original request.
+    Replayed,
+}
+
+/// Tracks the inbound requests received within a window to detect duplicates.
+pub(crate) struct Duplicates<TRequest, TResponse> {
+    window: Duration,
+    policy: DuplicatePolicy,
+    /// Clones a response for replaying it to duplicate requests.
+    clone_response: fn(&TResponse) -> TResponse,
+    /// Hashes a request, which together with the peer identifies duplicates.
+    hash_request: fn(&TRequest) -> u64,
+    /// The original requests within the window, by peer and request hash.
+    originals: HashMap<(PeerId, u64), Original<TResponse>>,
+    /// The keys of the original requests in `originals`, by request ID.
+    keys: HashMap<InboundRequestId, (PeerId, u64)>,
+    /// The keys of the original requests, in the order they were received.
+    expirations: VecDeque<(Instant, (PeerId, u64))>,
+    /// Duplicate requests that are answered by replaying the response
+    /// to the original request, whose events are not emitted.
+    replayed: HashSet<InboundRequestId>,
+}
+
+struct Original<TResponse> {
+    request_id: InboundRequestId,
+    received: Instant,
+    /// The response to the original request, once sent.
+    response: Option<TResponse>,
+    /// Duplicate requests waiting for the response to the original request.
+    waiting: Vec<oneshot::Sender<TResponse>>,
+}
+
+impl<TRequest, TResponse> Duplicates<TRequest, TResponse> {
+    pub(crate) fn new(window: Duration, policy: DuplicatePolicy) -> Self
+    where
+        TRequest: Hash,
+        TResponse: Clone,
+    {
+        Self {
+            window,
+            policy,
+            clone_response: TResponse::clone,
+            hash_request: |request| {
+                let mut hasher = DefaultHasher::new();
+                request.hash(&mut hasher);
+                hasher.finish()
+            },
+            originals: HashMap::new(),
+            keys: HashMap::new(),
+            expirations: VecDeque::new(),
+            replayed: HashSet::new(),
+        }
+    }
+
+    /// Checks whether the given inbound request is a duplicate of an earlier
+    /// request of the same peer, replaying the response to the earlier request
+    /// if configured.
+    pub(crate) fn on_request(
+        &mut self,
+        peer: PeerId,
+        request_id: InboundRequestId,
+        request: &TRequest,
+        sender: oneshot::Sender<TResponse>,
+        now: Instant,
+    ) -> Inbound<TResponse> {
+        self.remove_expired(now);
+
+        let key = (peer, (self.hash_request)(request));
+        let Some(original) = self.originals.get_mut(&key) else {
+            self.originals.insert(
+                key,
+                Original {
+                    request_id,
+                    received: now,
+                    response: None,
+                    waiting: Vec::new(),
+                },
+            );
+            self.keys.insert(request_id, key);
+            self.expirations.push_back((now, key));
+            return Inbound::Emit {
+                duplicate_of: None,
+                sender,
+            };
+        };
+
+        tracing::debug!(
+            %peer,
+            "Inbound request {request_id} is a duplicate of request {}",
+            original.request_id
+        );
+
+        match self.policy {
+            DuplicatePolicy::Flag => Inbound::Emit {
+                duplicate_of: Some(original.request_id),
+                sender,
+            },
+            DuplicatePolicy::Replay => {
+                match &original.response {
+                    Some(response) => {
+                        let _ = sender.send((self.clone_response)(response));
+                    }
+                    None => original.waiting.push(sender),
+                }
+                self.replayed.insert(request_id);
+                Inbound::Replayed
+            }
+        }
+    }
+
+    /// Records the response to an inbound request, replaying it to the
+    /// duplicates of the request.
+    pub(crate) fn on_response(&mut self, request_id: InboundRequestId, response: &TResponse) {
+        let Some(original) = self
+            .keys
+            .get(&request_id)
+            .and_then(|key| self.originals.get_mut(key))
+        else {
+            return;
+        };
+        if self.policy == DuplicatePolicy::Replay {
+            for sender in original.waiting.drain(..) {
+                let _ = sender.send((self.clone_response)(response));
+            }
+            original.response = Some((self.clone_response)(response));
+        }
+    }
+
+    /// Forgets an inbound request whose response has been omitted,
+    /// such that its duplicates are not answered either.
+    pub(crate) fn on_response_omission(&mut self, request_id: InboundRequestId) {
+        if let Some(key) = self.keys.remove(&request_id) {
+            self.originals.remove(&key);
+        }
+    }
+
+    /// Checks whether the given request is a duplicate answered by replaying
+    /// a response, forgetting about it as its handling has finished.
+    pub(crate) fn finish_replayed(&mut self, request_id: InboundRequestId) -> bool {
+        self.replayed.remove(&request_id)
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        while let Some((received, key)) = self.expirations.front() {
+            if now < *received + self.window {
+                break;
+            }
+            if let Some(original) = self.originals.get(key) {
+                // The entry may have been replaced after an earlier removal.
+                if original.received == *received {
+                    self.keys.remove(&original.request_id);
+                    self.originals.remove(key);
+                }
+            }
+            self.expirations.pop_front();
+        }
+    }
+}
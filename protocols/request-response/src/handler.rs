@@ -23,11 +23,16 @@ pub(crate) mod protocol;
 pub use protocol::ProtocolSupport;
 
 use crate::codec::Codec;
-use crate::handler::protocol::Protocol;
-use crate::{InboundRequestId, OutboundRequestId, EMPTY_QUEUE_SHRINK_THRESHOLD};
+use crate::compression::{self, Compressions};
+use crate::handler::protocol::{Protocol, ProtocolName};
+use crate::{
+    InboundRequestId, InboundTiming, OutboundRequestId, OutboundTiming,
+    EMPTY_QUEUE_SHRINK_THRESHOLD,
+};
 
 use futures::channel::mpsc;
 use futures::{channel::oneshot, prelude::*};
+use instant::Instant;
 use libp2p_swarm::handler::{
     ConnectionEvent, DialUpgradeError, FullyNegotiatedInbound, FullyNegotiatedOutbound,
     ListenUpgradeError,
@@ -57,6 +62,8 @@ where
     inbound_protocols: SmallVec<[TCodec::Protocol; 2]>,
     /// The request/response message codec.
     codec: TCodec,
+    /// The compressions negotiated on streams.
+    compressions: Compressions,
     /// Queue of events to emit in `poll()`.
     pending_events: VecDeque<Event<TCodec>>,
     /// Outbound upgrades waiting to be emitted as an `OutboundSubstreamRequest`.
@@ -94,6 +101,7 @@ where
     pub(super) fn new(
         inbound_protocols: SmallVec<[TCodec::Protocol; 2]>,
         codec: TCodec,
+        compressions: Compressions,
         substream_timeout: Duration,
         inbound_request_id: Arc<AtomicU64>,
         max_concurrent_streams: usize,
@@ -102,6 +110,7 @@ where
         Self {
             inbound_protocols,
             codec,
+            compressions,
             pending_outbound: VecDeque::new(),
             requested_outbound: Default::default(),
             inbound_receiver,
@@ -131,16 +140,20 @@ where
         >,
     ) {
         let mut codec = self.codec.clone();
+        let compressions = self.compressions.clone();
         let request_id = self.next_inbound_request_id();
         let mut sender = self.inbound_sender.clone();
+        let protocol_name = protocol.protocol.as_ref().to_owned();
 
         let recv = async move {
             // A channel for notifying the inbound upgrade when the
             // response is sent.
             let (rs_send, rs_recv) = oneshot::channel();
 
-            let read = codec.read_request(&protocol, &mut stream);
+            let read =
+                compression::read_request(&mut codec, &compressions, protocol.clone(), &mut stream);
             let request = read.await?;
+            let received = Instant::now();
             sender
                 .send((request_id, request, rs_send))
                 .await
@@ -148,11 +161,23 @@ where
             drop(sender);
 
             if let Ok(response) = rs_recv.await {
-                let write = codec.write_response(&protocol, &mut stream, response);
+                let responded = Instant::now();
+                let write = compression::write_response(
+                    &mut codec,
+                    &compressions,
+                    protocol,
+                    &mut stream,
+                    response,
+                );
                 write.await?;
 
                 stream.close().await?;
-                Ok(Event::ResponseSent(request_id))
+                let timing = InboundTiming {
+                    protocol: protocol_name,
+                    queued: responded.duration_since(received),
+                    sending: responded.elapsed(),
+                };
+                Ok(Event::ResponseSent { request_id, timing })
             } else {
                 stream.close().await?;
                 Ok(Event::ResponseOmission(request_id))
@@ -184,18 +209,36 @@ where
             .expect("negotiated a stream without a pending message");
 
         let mut codec = self.codec.clone();
+        let compressions = self.compressions.clone();
         let request_id = message.request_id;
+        let negotiated = Instant::now();
+        let negotiation = negotiated.duration_since(message.created);
+        let protocol_name = protocol.protocol.as_ref().to_owned();
 
         let send = async move {
-            let write = codec.write_request(&protocol, &mut stream, message.request);
+            let write = compression::write_request(
+                &mut codec,
+                &compressions,
+                protocol.clone(),
+                &mut stream,
+                message.request,
+            );
             write.await?;
             stream.close().await?;
-            let read = codec.read_response(&protocol, &mut stream);
+            let sent = Instant::now();
+            let read = compression::read_response(&mut codec, &compressions, protocol, &mut stream);
             let response = read.await?;
 
+            let timing = OutboundTiming {
+                protocol: protocol_name,
+                negotiation,
+                sending: sent.duration_since(negotiated),
+                remote: sent.elapsed(),
+            };
             Ok(Event::Response {
                 request_id,
                 response,
+                timing,
             })
         };
 
@@ -270,9 +313,13 @@ where
     Response {
         request_id: OutboundRequestId,
         response: TCodec::Response,
+        timing: OutboundTiming,
     },
     /// A response to an inbound request has been sent.
-    ResponseSent(InboundRequestId),
+    ResponseSent {
+        request_id: InboundRequestId,
+        timing: InboundTiming,
+    },
     /// A response to an inbound request was omitted as a result
     /// of dropping the response `sender` of an inbound `Request`.
     ResponseOmission(InboundRequestId),
@@ -308,13 +355,16 @@ impl<TCodec: Codec> fmt::Debug for Event<TCodec> {
             Event::Response {
                 request_id,
                 response: _,
+                timing,
             } => f
                 .debug_struct("Event::Response")
                 .field("request_id", request_id)
+                .field("timing", timing)
                 .finish(),
-            Event::ResponseSent(request_id) => f
-                .debug_tuple("Event::ResponseSent")
-                .field(request_id)
+            Event::ResponseSent { request_id, timing } => f
+                .debug_struct("Event::ResponseSent")
+                .field("request_id", request_id)
+                .field("timing", timing)
                 .finish(),
             Event::ResponseOmission(request_id) => f
                 .debug_tuple("Event::ResponseOmission")
@@ -350,6 +400,8 @@ pub struct OutboundMessage<TCodec: Codec> {
     pub(crate) request_id: OutboundRequestId,
     pub(crate) request: TCodec::Request,
     pub(crate) protocols: SmallVec<[TCodec::Protocol; 2]>,
+    /// When the request was passed to [`Behaviour::send_request`](super::Behaviour::send_request).
+    pub(crate) created: Instant,
 }
 
 impl<TCodec> fmt::Debug for OutboundMessage<TCodec>
@@ -367,15 +419,18 @@ where
 {
     type FromBehaviour = OutboundMessage<TCodec>;
     type ToBehaviour = Event<TCodec>;
-    type InboundProtocol = Protocol<TCodec::Protocol>;
-    type OutboundProtocol = Protocol<TCodec::Protocol>;
+    type InboundProtocol = Protocol<ProtocolName<TCodec::Protocol>>;
+    type OutboundProtocol = Protocol<ProtocolName<TCodec::Protocol>>;
     type OutboundOpenInfo = ();
     type InboundOpenInfo = ();
 
     fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
         SubstreamProtocol::new(
             Protocol {
-                protocols: self.inbound_protocols.clone(),
+                protocols: self
+                    .compressions
+                    .protocol_names(self.inbound_protocols.clone())
+                    .into(),
             },
             (),
         )
@@ -389,7 +444,7 @@ where
     fn poll(
         &mut self,
         cx: &mut Context<'_>,
-    ) -> Poll<ConnectionHandlerEvent<Protocol<TCodec::Protocol>, (), Self::ToBehaviour>> {
+    ) -> Poll<ConnectionHandlerEvent<Self::OutboundProtocol, (), Self::ToBehaviour>> {
         match self.worker_streams.poll_unpin(cx) {
             Poll::Ready((_, Ok(Ok(event)))) => {
                 return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(event));
@@ -443,7 +498,10 @@ where
 
         // Emit outbound requests.
         if let Some(request) = self.pending_outbound.pop_front() {
-            let protocols = request.protocols.clone();
+            let protocols = self
+                .compressions
+                .protocol_names(request.protocols.clone())
+                .into();
             self.requested_outbound.push_back(request);
 
             return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
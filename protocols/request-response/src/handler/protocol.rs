@@ -23,10 +23,13 @@
 //! receives a request and sends a response, whereas the
 //! outbound upgrade send a request and receives a response.
 
+use crate::compression::Compression;
 use futures::future::{ready, Ready};
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use libp2p_swarm::Stream;
 use smallvec::SmallVec;
+use std::fmt;
+use std::sync::Arc;
 
 /// The level of support for a particular protocol.
 #[derive(Debug, Clone)]
@@ -57,6 +60,48 @@ impl ProtocolSupport {
     }
 }
 
+/// A protocol of a [`Codec`](crate::Codec) as negotiated on a stream, possibly with compression.
+#[derive(Clone)]
+pub struct ProtocolName<P> {
+    pub(crate) protocol: P,
+    /// The name of the protocol with the suffix of the compression, if any.
+    name: Option<String>,
+    pub(crate) compression: Option<Arc<dyn Compression>>,
+}
+
+impl<P: AsRef<str>> ProtocolName<P> {
+    pub(crate) fn new(protocol: P) -> Self {
+        Self {
+            protocol,
+            name: None,
+            compression: None,
+        }
+    }
+
+    pub(crate) fn with_compression(protocol: P, compression: Arc<dyn Compression>) -> Self {
+        Self {
+            name: Some(format!("{}/{}", protocol.as_ref(), compression.name())),
+            protocol,
+            compression: Some(compression),
+        }
+    }
+}
+
+impl<P: AsRef<str>> AsRef<str> for ProtocolName<P> {
+    fn as_ref(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.protocol.as_ref())
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for ProtocolName<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolName")
+            .field("protocol", &self.protocol)
+            .field("compression", &self.compression.as_ref().map(|c| c.name()))
+            .finish()
+    }
+}
+
 /// Response substream upgrade protocol.
 ///
 /// Receives a request and sends a response.
@@ -69,16 +69,25 @@
 #[cfg(feature = "cbor")]
 pub mod cbor;
 mod codec;
+pub mod compression;
+mod duplicate;
 mod handler;
 #[cfg(feature = "json")]
 pub mod json;
+mod timing;
 
 pub use codec::Codec;
+pub use compression::{Compression, CompressionStats};
+pub use duplicate::DuplicatePolicy;
 pub use handler::ProtocolSupport;
+pub use timing::{InboundTiming, OutboundTiming};
 
+use crate::compression::Compressions;
+use crate::duplicate::{Duplicates, Inbound};
 use crate::handler::OutboundMessage;
 use futures::channel::oneshot;
 use handler::Handler;
+use instant::Instant;
 use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_swarm::{
@@ -90,7 +99,9 @@ use libp2p_swarm::{
 use smallvec::SmallVec;
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    fmt, io,
+    fmt,
+    hash::Hash,
+    io,
     sync::{atomic::AtomicU64, Arc},
     task::{Context, Poll},
     time::Duration,
@@ -161,6 +172,26 @@ pub enum Event<TRequest, TResponse, TChannelResponse = TResponse> {
         /// The ID of the inbound request whose response was sent.
         request_id: InboundRequestId,
     },
+    /// The time spent in the phases of an inbound request, emitted after
+    /// [`Event::ResponseSent`] if enabled via [`Config::with_timing_events`].
+    InboundRequestTiming {
+        /// The peer from whom the request was received.
+        peer: PeerId,
+        /// The ID of the inbound request.
+        request_id: InboundRequestId,
+        /// The time spent in the phases of the request.
+        timing: InboundTiming,
+    },
+    /// The time spent in the phases of an outbound request, emitted after
+    /// the [`Message::Response`] if enabled via [`Config::with_timing_events`].
+    OutboundRequestTiming {
+        /// The peer to whom the request was sent.
+        peer: PeerId,
+        /// The (local) ID of the request.
+        request_id: OutboundRequestId,
+        /// The time spent in the phases of the request.
+        timing: OutboundTiming,
+    },
 }
 
 /// Possible failures occurring in the context of sending
@@ -254,6 +285,8 @@ impl std::error::Error for InboundFailure {}
 /// See [`Behaviour::send_response`].
 #[derive(Debug)]
 pub struct ResponseChannel<TResponse> {
+    request_id: InboundRequestId,
+    duplicate_of: Option<InboundRequestId>,
     sender: oneshot::Sender<TResponse>,
 }
 
@@ -268,6 +301,14 @@ impl<TResponse> ResponseChannel<TResponse> {
     pub fn is_open(&self) -> bool {
         !self.sender.is_canceled()
     }
+
+    /// Returns the ID of an earlier request of the same peer that the request
+    /// of this channel duplicates, if any.
+    ///
+    /// Only detected with [`DuplicatePolicy::Flag`], see [`Behaviour::with_duplicate_detection`].
+    pub fn duplicate_of(&self) -> Option<InboundRequestId> {
+        self.duplicate_of
+    }
 }
 
 /// The ID of an inbound request.
@@ -301,6 +342,8 @@ impl fmt::Display for OutboundRequestId {
 pub struct Config {
     request_timeout: Duration,
     max_concurrent_streams: usize,
+    compressions: Compressions,
+    timing_events: bool,
 }
 
 impl Default for Config {
@@ -308,6 +351,8 @@ impl Default for Config {
         Self {
             request_timeout: Duration::from_secs(10),
             max_concurrent_streams: 100,
+            compressions: Compressions::default(),
+            timing_events: false,
         }
     }
 }
@@ -331,6 +376,42 @@ impl Config {
         self.max_concurrent_streams = num_streams;
         self
     }
+
+    /// Adds a compression to negotiate on streams, see the [`compression`] module.
+    ///
+    /// Compressions are preferred in the order in which they are added, over no compression.
+    pub fn with_compression(mut self, compression: impl Compression) -> Self {
+        self.compressions
+            .algorithms
+            .push(std::sync::Arc::new(compression));
+        self
+    }
+
+    /// Sets the size in bytes from which messages are compressed on streams with compression.
+    ///
+    /// Defaults to 1 KiB.
+    pub fn with_compression_threshold(mut self, num_bytes: usize) -> Self {
+        self.compressions.threshold = num_bytes;
+        self
+    }
+
+    /// Sets the maximum size in bytes of messages on streams with compression, both before
+    /// and after decompression.
+    ///
+    /// Defaults to 16 MiB.
+    pub fn with_max_compressed_message_size(mut self, num_bytes: usize) -> Self {
+        self.compressions.max_message_size = num_bytes;
+        self
+    }
+
+    /// Sets whether [`Event::InboundRequestTiming`] and [`Event::OutboundRequestTiming`]
+    /// are emitted for the requests that completed, e.g. to record them as metrics.
+    ///
+    /// Defaults to `false`.
+    pub fn with_timing_events(mut self, enabled: bool) -> Self {
+        self.timing_events = enabled;
+        self
+    }
 }
 
 /// A request/response protocol for some message codec.
@@ -361,6 +442,8 @@ where
     /// Requests that have not yet been sent and are waiting for a connection
     /// to be established.
     pending_outbound_requests: HashMap<PeerId, SmallVec<[OutboundMessage<TCodec>; 10]>>,
+    /// The detection of duplicate inbound requests, if enabled.
+    duplicates: Option<Duplicates<TCodec::Request, TCodec::Response>>,
 }
 
 impl<TCodec> Behaviour<TCodec>
@@ -376,16 +459,36 @@ where
     }
 }
 
+impl<TCodec> Behaviour<TCodec>
+where
+    TCodec: Codec + Clone + Send + 'static,
+    TCodec::Request: Hash,
+    TCodec::Response: Clone,
+{
+    /// Enables the detection of duplicate inbound requests, e.g. requests retried
+    /// by a client, to avoid executing non-idempotent operations twice.
+    ///
+    /// An inbound request is a duplicate if the same peer sent an equal request,
+    /// as determined by its [`Hash`], within the given window since the original
+    /// request. Duplicates are handled according to the given [`DuplicatePolicy`].
+    pub fn with_duplicate_detection(mut self, window: Duration, policy: DuplicatePolicy) -> Self {
+        self.duplicates = Some(Duplicates::new(window, policy));
+        self
+    }
+}
+
 impl<TCodec> Behaviour<TCodec>
 where
     TCodec: Codec + Clone + Send + 'static,
 {
     /// Creates a new `Behaviour` for the given
     /// protocols, codec and configuration.
-    pub fn with_codec<I>(codec: TCodec, protocols: I, cfg: Config) -> Self
+    pub fn with_codec<I>(codec: TCodec, protocols: I, mut cfg: Config) -> Self
     where
         I: IntoIterator<Item = (TCodec::Protocol, ProtocolSupport)>,
     {
+        // Configurations may be shared, the statistics not.
+        cfg.compressions.stats = CompressionStats::default();
         let mut inbound_protocols = SmallVec::new();
         let mut outbound_protocols = SmallVec::new();
         for (p, s) in protocols {
@@ -407,6 +510,7 @@ where
             connected: HashMap::new(),
             pending_outbound_requests: HashMap::new(),
             addresses: PeerAddresses::default(),
+            duplicates: None,
         }
     }
 
@@ -428,6 +532,7 @@ where
             request_id,
             request,
             protocols: self.outbound_protocols.clone(),
+            created: Instant::now(),
         };
 
         if let Some(request) = self.try_send_request(peer, request) {
@@ -459,6 +564,9 @@ where
         ch: ResponseChannel<TCodec::Response>,
         rs: TCodec::Response,
     ) -> Result<(), TCodec::Response> {
+        if let Some(duplicates) = self.duplicates.as_mut() {
+            duplicates.on_response(ch.request_id, &rs);
+        }
         ch.sender.send(rs)
     }
 
@@ -526,6 +634,12 @@ where
             .unwrap_or(false)
     }
 
+    /// Returns the statistics of the compression of messages on streams with compression,
+    /// see [`Config::with_compression`].
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.config.compressions.stats.clone()
+    }
+
     /// Returns the next outbound request ID.
     fn next_outbound_request_id(&mut self) -> OutboundRequestId {
         let request_id = self.next_outbound_request_id;
@@ -654,6 +768,9 @@ where
         }
 
         for request_id in connection.pending_inbound_responses {
+            if self.is_replayed(request_id) {
+                continue;
+            }
             self.pending_events
                 .push_back(ToSwarm::GenerateEvent(Event::InboundFailure {
                     peer: peer_id,
@@ -693,6 +810,14 @@ where
         }
     }
 
+    /// Checks whether the given inbound request is a duplicate that has been
+    /// answered by replaying a response, whose events are not emitted.
+    fn is_replayed(&mut self, request_id: InboundRequestId) -> bool {
+        self.duplicates
+            .as_mut()
+            .is_some_and(|d| d.finish_replayed(request_id))
+    }
+
     /// Preloads a new [`Handler`] with requests that are waiting to be sent to the newly connected peer.
     fn preload_new_handler(
         &mut self,
@@ -733,6 +858,7 @@ where
         let mut handler = Handler::new(
             self.inbound_protocols.clone(),
             self.codec.clone(),
+            self.config.compressions.clone(),
             self.config.request_timeout,
             self.next_inbound_request_id.clone(),
             self.config.max_concurrent_streams,
@@ -776,6 +902,7 @@ where
         let mut handler = Handler::new(
             self.inbound_protocols.clone(),
             self.codec.clone(),
+            self.config.compressions.clone(),
             self.config.request_timeout,
             self.next_inbound_request_id.clone(),
             self.config.max_concurrent_streams,
@@ -814,6 +941,7 @@ where
             handler::Event::Response {
                 request_id,
                 response,
+                timing,
             } => {
                 let removed = self.remove_pending_outbound_response(&peer, connection, request_id);
                 debug_assert!(
@@ -827,6 +955,15 @@ where
                 };
                 self.pending_events
                     .push_back(ToSwarm::GenerateEvent(Event::Message { peer, message }));
+                if self.config.timing_events {
+                    self.pending_events.push_back(ToSwarm::GenerateEvent(
+                        Event::OutboundRequestTiming {
+                            peer,
+                            request_id,
+                            timing,
+                        },
+                    ));
+                }
             }
             handler::Event::Request {
                 request_id,
@@ -837,7 +974,27 @@ where
                     let inserted = connection.pending_inbound_responses.insert(request_id);
                     debug_assert!(inserted, "Expect id of new request to be unknown.");
 
-                    let channel = ResponseChannel { sender };
+                    let (duplicate_of, sender) = match self.duplicates.as_mut() {
+                        Some(duplicates) => match duplicates.on_request(
+                            peer,
+                            request_id,
+                            &request,
+                            sender,
+                            Instant::now(),
+                        ) {
+                            Inbound::Emit {
+                                duplicate_of,
+                                sender,
+                            } => (duplicate_of, sender),
+                            Inbound::Replayed => return,
+                        },
+                        None => (None, sender),
+                    };
+                    let channel = ResponseChannel {
+                        request_id,
+                        duplicate_of,
+                        sender,
+                    };
                     let message = Message::Request {
                         request_id,
                         request,
@@ -850,18 +1007,30 @@ where
                     tracing::debug!("Connection ({connection}) closed after `Event::Request` ({request_id}) has been emitted.");
                 }
             },
-            handler::Event::ResponseSent(request_id) => {
+            handler::Event::ResponseSent { request_id, timing } => {
                 let removed = self.remove_pending_inbound_response(&peer, connection, request_id);
                 debug_assert!(
                     removed,
                     "Expect request_id to be pending before response is sent."
                 );
+                if self.is_replayed(request_id) {
+                    return;
+                }
 
                 self.pending_events
                     .push_back(ToSwarm::GenerateEvent(Event::ResponseSent {
                         peer,
                         request_id,
                     }));
+                if self.config.timing_events {
+                    self.pending_events.push_back(ToSwarm::GenerateEvent(
+                        Event::InboundRequestTiming {
+                            peer,
+                            request_id,
+                            timing,
+                        },
+                    ));
+                }
             }
             handler::Event::ResponseOmission(request_id) => {
                 let removed = self.remove_pending_inbound_response(&peer, connection, request_id);
@@ -869,6 +1038,12 @@ where
                     removed,
                     "Expect request_id to be pending before response is omitted.",
                 );
+                if self.is_replayed(request_id) {
+                    return;
+                }
+                if let Some(duplicates) = self.duplicates.as_mut() {
+                    duplicates.on_response_omission(request_id);
+                }
 
                 self.pending_events
                     .push_back(ToSwarm::GenerateEvent(Event::InboundFailure {
@@ -919,7 +1094,7 @@ where
             handler::Event::InboundTimeout(request_id) => {
                 let removed = self.remove_pending_inbound_response(&peer, connection, request_id);
 
-                if removed {
+                if removed && !self.is_replayed(request_id) {
                     self.pending_events
                         .push_back(ToSwarm::GenerateEvent(Event::InboundFailure {
                             peer,
@@ -936,7 +1111,7 @@ where
             handler::Event::InboundStreamFailed { request_id, error } => {
                 let removed = self.remove_pending_inbound_response(&peer, connection, request_id);
 
-                if removed {
+                if removed && !self.is_replayed(request_id) {
                     self.pending_events
                         .push_back(ToSwarm::GenerateEvent(Event::InboundFailure {
                             peer,
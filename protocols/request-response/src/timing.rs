@@ -0,0 +1,57 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The time spent in the phases of requests, see [`Config::with_timing_events`].
+//!
+//! [`Config::with_timing_events`]: crate::Config::with_timing_events
+
+use std::time::Duration;
+
+/// The time spent in the phases of an inbound request whose response has been sent,
+/// see [`Event::InboundRequestTiming`](crate::Event::InboundRequestTiming).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboundTiming {
+    /// The protocol of the request, without the suffix of a compression.
+    pub protocol: String,
+    /// The time from the request being read until the response was passed to
+    /// [`Behaviour::send_response`](crate::Behaviour::send_response), i.e. the request
+    /// queueing and the processing by the application.
+    pub queued: Duration,
+    /// The time from the response being passed to
+    /// [`Behaviour::send_response`](crate::Behaviour::send_response) until it was flushed.
+    pub sending: Duration,
+}
+
+/// The time spent in the phases of an outbound request whose response has been received,
+/// see [`Event::OutboundRequestTiming`](crate::Event::OutboundRequestTiming).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboundTiming {
+    /// The protocol of the request, without the suffix of a compression.
+    pub protocol: String,
+    /// The time from [`Behaviour::send_request`](crate::Behaviour::send_request) until a
+    /// stream was negotiated, i.e. dialing the peer if not connected, waiting for stream
+    /// capacity and the protocol negotiation.
+    pub negotiation: Duration,
+    /// The time from the stream being negotiated until the request was flushed.
+    pub sending: Duration,
+    /// The time from the request being flushed until the response was read, i.e. the
+    /// processing by the remote and the transfer of the response.
+    pub remote: Duration,
+}
@@ -28,7 +28,7 @@ use libp2p_swarm::{StreamProtocol, Swarm, SwarmEvent};
 use libp2p_swarm_test::SwarmExt;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::{io, iter};
+use std::{io, iter, time::Duration};
 use tracing_subscriber::EnvFilter;
 
 #[async_std::test]
@@ -297,8 +297,367 @@ async fn emits_inbound_connection_closed_if_channel_is_dropped() {
     ));
 }
 
+#[async_std::test]
+#[cfg(feature = "cbor")]
+async fn replays_response_to_duplicate_requests() {
+    let ping = Ping("ping".to_string().into_bytes());
+    let pong = Pong("pong".to_string().into_bytes());
+
+    let protocols = iter::once((StreamProtocol::new("/ping/1"), ProtocolSupport::Full));
+    let cfg = request_response::Config::default();
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(protocols.clone(), cfg.clone())
+            .with_duplicate_detection(
+                Duration::from_secs(60),
+                request_response::DuplicatePolicy::Replay,
+            )
+    });
+    let peer1_id = *swarm1.local_peer_id();
+    let mut swarm2 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(protocols, cfg)
+    });
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let expected_pong = pong.clone();
+    let peer1 = async move {
+        let channel = loop {
+            match swarm1.next_swarm_event().await.try_into_behaviour_event() {
+                Ok(request_response::Event::Message {
+                    message: request_response::Message::Request { channel, .. },
+                    ..
+                }) => break channel,
+                Ok(e) => panic!("Peer1: Unexpected event: {e:?}"),
+                Err(..) => {}
+            }
+        };
+        assert_eq!(channel.duplicate_of(), None);
+
+        // Let the duplicate request arrive before responding.
+        let _ = async_std::future::timeout(Duration::from_millis(100), async {
+            loop {
+                if let Ok(e) = swarm1.next_swarm_event().await.try_into_behaviour_event() {
+                    panic!("Peer1: Unexpected event: {e:?}");
+                }
+            }
+        })
+        .await;
+        swarm1
+            .behaviour_mut()
+            .send_response(channel, pong.clone())
+            .unwrap();
+
+        loop {
+            match swarm1.next_swarm_event().await.try_into_behaviour_event() {
+                Ok(request_response::Event::ResponseSent { .. }) => {}
+                Ok(e) => panic!("Peer1: Unexpected event: {e:?}"),
+                Err(..) => {}
+            }
+        }
+    };
+    async_std::task::spawn(Box::pin(peer1));
+
+    // The second request waits for the response to the first one,
+    // whereas the third one is answered with the recorded response.
+    let mut pending = vec![
+        swarm2.behaviour_mut().send_request(&peer1_id, ping.clone()),
+        swarm2.behaviour_mut().send_request(&peer1_id, ping.clone()),
+    ];
+    let mut third_sent = false;
+    while !pending.is_empty() {
+        match swarm2.next_swarm_event().await.try_into_behaviour_event() {
+            Ok(request_response::Event::Message {
+                message:
+                    request_response::Message::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            }) => {
+                assert_eq!(response, expected_pong);
+                pending.retain(|id| *id != request_id);
+                if pending.is_empty() && !third_sent {
+                    third_sent = true;
+                    pending.push(swarm2.behaviour_mut().send_request(&peer1_id, ping.clone()));
+                }
+            }
+            Ok(e) => panic!("Peer2: Unexpected event: {e:?}"),
+            Err(..) => {}
+        }
+    }
+}
+
+#[async_std::test]
+#[cfg(feature = "cbor")]
+async fn flags_duplicate_requests() {
+    let ping = Ping("ping".to_string().into_bytes());
+    let pong = Pong("pong".to_string().into_bytes());
+
+    let protocols = iter::once((StreamProtocol::new("/ping/1"), ProtocolSupport::Full));
+    let cfg = request_response::Config::default();
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(protocols.clone(), cfg.clone())
+            .with_duplicate_detection(
+                Duration::from_secs(60),
+                request_response::DuplicatePolicy::Flag,
+            )
+    });
+    let peer1_id = *swarm1.local_peer_id();
+    let mut swarm2 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(protocols, cfg)
+    });
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    swarm2.behaviour_mut().send_request(&peer1_id, ping.clone());
+    swarm2.behaviour_mut().send_request(&peer1_id, ping.clone());
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    let mut first = None;
+    loop {
+        if let Ok(request_response::Event::Message {
+            message:
+                request_response::Message::Request {
+                    request_id,
+                    channel,
+                    ..
+                },
+            ..
+        }) = swarm1.next_swarm_event().await.try_into_behaviour_event()
+        {
+            match first {
+                None => {
+                    assert_eq!(channel.duplicate_of(), None);
+                    first = Some(request_id);
+                }
+                Some(first) => {
+                    assert_eq!(channel.duplicate_of(), Some(first));
+                    break;
+                }
+            }
+            swarm1
+                .behaviour_mut()
+                .send_response(channel, pong.clone())
+                .unwrap();
+        }
+    }
+}
+
+#[async_std::test]
+#[cfg(feature = "cbor")]
+async fn compresses_messages_if_negotiated() {
+    let ping = Ping(vec![1; 4096]);
+    let pong = Pong(vec![2; 4096]);
+
+    let protocols = iter::once((StreamProtocol::new("/ping/1"), ProtocolSupport::Full));
+    let cfg = request_response::Config::default().with_compression(RunLength);
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(protocols.clone(), cfg.clone())
+    });
+    let peer1_id = *swarm1.local_peer_id();
+    let mut swarm2 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(protocols, cfg)
+    });
+    let stats2 = swarm2.behaviour().compression_stats();
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    exchange(swarm1, swarm2, peer1_id, ping, pong).await;
+    // The request is compressed when sent and the response when received.
+    assert_eq!(stats2.num_compressed(), 2);
+    assert!(stats2.ratio().unwrap() > 10.0);
+}
+
+#[async_std::test]
+#[cfg(feature = "cbor")]
+async fn falls_back_to_no_compression() {
+    let ping = Ping(vec![1; 4096]);
+    let pong = Pong(vec![2; 4096]);
+
+    let protocols = iter::once((StreamProtocol::new("/ping/1"), ProtocolSupport::Full));
+    let cfg = request_response::Config::default();
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(protocols.clone(), cfg.clone())
+    });
+    let peer1_id = *swarm1.local_peer_id();
+    let mut swarm2 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(
+            protocols,
+            cfg.with_compression(RunLength),
+        )
+    });
+    let stats2 = swarm2.behaviour().compression_stats();
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    exchange(swarm1, swarm2, peer1_id, ping, pong).await;
+    assert_eq!(stats2.num_compressed(), 0);
+    assert_eq!(stats2.ratio(), None);
+}
+
+#[async_std::test]
+#[cfg(feature = "cbor")]
+async fn emits_timing_events_if_enabled() {
+    let protocols = iter::once((StreamProtocol::new("/ping/1"), ProtocolSupport::Full));
+    let cfg = request_response::Config::default()
+        .with_compression(RunLength)
+        .with_timing_events(true);
+    let delay = Duration::from_millis(50);
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(protocols.clone(), cfg.clone())
+    });
+    let peer1_id = *swarm1.local_peer_id();
+    let mut swarm2 = Swarm::new_ephemeral(|_| {
+        request_response::cbor::Behaviour::<Ping, Pong>::new(protocols, cfg)
+    });
+    let peer2_id = *swarm2.local_peer_id();
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let server = async_std::task::spawn(async move {
+        loop {
+            match swarm1.next_swarm_event().await.try_into_behaviour_event() {
+                Ok(request_response::Event::Message {
+                    message: request_response::Message::Request { channel, .. },
+                    ..
+                }) => {
+                    // The application takes a while to produce the response.
+                    async_std::task::sleep(delay).await;
+                    swarm1
+                        .behaviour_mut()
+                        .send_response(channel, Pong(vec![2]))
+                        .unwrap();
+                }
+                Ok(request_response::Event::InboundRequestTiming { peer, timing, .. }) => {
+                    assert_eq!(peer, peer2_id);
+                    return timing;
+                }
+                Ok(request_response::Event::ResponseSent { .. }) | Err(..) => {}
+                Ok(e) => panic!("Peer1: Unexpected event: {e:?}"),
+            }
+        }
+    });
+
+    let request_id = swarm2
+        .behaviour_mut()
+        .send_request(&peer1_id, Ping(vec![1]));
+    let outbound = loop {
+        match swarm2.next_swarm_event().await.try_into_behaviour_event() {
+            Ok(request_response::Event::Message {
+                message: request_response::Message::Response { .. },
+                ..
+            })
+            | Err(..) => {}
+            Ok(request_response::Event::OutboundRequestTiming {
+                peer,
+                request_id: id,
+                timing,
+            }) => {
+                assert_eq!((peer, id), (peer1_id, request_id));
+                break timing;
+            }
+            Ok(e) => panic!("Peer2: Unexpected event: {e:?}"),
+        }
+    };
+    let inbound = server.await;
+
+    // The protocol is reported without the suffix of the negotiated compression.
+    assert_eq!(inbound.protocol, "/ping/1");
+    assert_eq!(outbound.protocol, "/ping/1");
+    assert!(inbound.queued >= delay);
+    assert!(outbound.remote >= inbound.queued);
+}
+
+/// Sends the ping from the second to the first swarm and awaits the pong in response.
+#[cfg(feature = "cbor")]
+async fn exchange(
+    mut swarm1: Swarm<request_response::cbor::Behaviour<Ping, Pong>>,
+    mut swarm2: Swarm<request_response::cbor::Behaviour<Ping, Pong>>,
+    peer1_id: PeerId,
+    ping: Ping,
+    pong: Pong,
+) {
+    let expected_ping = ping.clone();
+    let expected_pong = pong.clone();
+
+    async_std::task::spawn(async move {
+        loop {
+            if let Ok(request_response::Event::Message {
+                message:
+                    request_response::Message::Request {
+                        request, channel, ..
+                    },
+                ..
+            }) = swarm1.next_swarm_event().await.try_into_behaviour_event()
+            {
+                assert_eq!(request, expected_ping);
+                swarm1
+                    .behaviour_mut()
+                    .send_response(channel, pong.clone())
+                    .unwrap();
+            }
+        }
+    });
+
+    swarm2.behaviour_mut().send_request(&peer1_id, ping);
+    loop {
+        match swarm2.next_swarm_event().await.try_into_behaviour_event() {
+            Ok(request_response::Event::Message {
+                message: request_response::Message::Response { response, .. },
+                ..
+            }) => {
+                assert_eq!(response, expected_pong);
+                return;
+            }
+            Ok(e) => panic!("Peer2: Unexpected event: {e:?}"),
+            Err(..) => {}
+        }
+    }
+}
+
 // Simple Ping-Pong Protocol
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 struct Ping(Vec<u8>);
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct Pong(Vec<u8>);
+
+/// A toy compression replacing runs of a byte by the byte and the length of the run.
+struct RunLength;
+
+impl request_response::Compression for RunLength {
+    fn name(&self) -> &'static str {
+        "rle"
+    }
+
+    fn compress(&self, message: &[u8]) -> io::Result<Vec<u8>> {
+        let mut compressed = Vec::new();
+        for &byte in message {
+            match compressed.as_mut_slice() {
+                [.., run, len] if *run == byte && *len < u8::MAX => *len += 1,
+                _ => compressed.extend([byte, 1]),
+            }
+        }
+        Ok(compressed)
+    }
+
+    fn decompress(&self, message: &[u8], max_size: usize) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        for run in message.chunks(2) {
+            decompressed.extend(iter::repeat(run[0]).take(run[1] as usize));
+            if decompressed.len() > max_size {
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+        }
+        Ok(decompressed)
+    }
+}
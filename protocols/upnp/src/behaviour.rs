@@ -29,7 +29,7 @@ use std::{
     ops::{Deref, DerefMut},
     pin::Pin,
     task::{Context, Poll},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::tokio::{is_addr_global, Gateway};
@@ -39,7 +39,7 @@ use igd_next::PortMappingProtocol;
 use libp2p_core::{multiaddr, transport::ListenerId, Endpoint, Multiaddr};
 use libp2p_swarm::{
     derive_prelude::PeerId, dummy, ConnectionDenied, ConnectionId, ExpiredListenAddr, FromSwarm,
-    NetworkBehaviour, NewListenAddr, ToSwarm,
+    NetworkBehaviour, NewListenAddr, THandlerInEvent, ToSwarm,
 };
 
 /// The duration in seconds of a port mapping on the gateway.
@@ -66,6 +66,8 @@ pub(crate) enum GatewayEvent {
     Removed(Mapping),
     /// There was a failure removing the mapped port.
     RemovalFailure(Mapping, Box<dyn Error + Send + Sync + 'static>),
+    /// The external IP address of the gateway changed.
+    ExternalAddrChanged(IpAddr),
 }
 
 /// Mapping of a Protocol and Port on the gateway.
@@ -118,12 +120,22 @@ enum MappingState {
     Inactive,
     /// Port mapping/removal has been requested on the gateway.
     Pending,
-    /// Port mapping is active with the inner timeout.
-    Active(Delay),
+    /// Port mapping is active with the inner renewal timeout,
+    /// the lease of the mapping on the gateway expires at the given instant.
+    Active { renewal: Delay, expires: Instant },
     /// Port mapping failed, we will try again.
     Failed,
 }
 
+impl MappingState {
+    fn active() -> Self {
+        MappingState::Active {
+            renewal: Delay::new(Duration::from_secs(MAPPING_TIMEOUT)),
+            expires: Instant::now() + Duration::from_secs(MAPPING_DURATION.into()),
+        }
+    }
+}
+
 /// Current state of the UPnP [`Gateway`].
 enum GatewayState {
     Searching(oneshot::Receiver<Result<Gateway, Box<dyn std::error::Error + Send + Sync>>>),
@@ -137,12 +149,72 @@ enum GatewayState {
 pub enum Event {
     /// The multiaddress is reachable externally.
     NewExternalAddr(Multiaddr),
-    /// The renewal of the multiaddress on the gateway failed.
+    /// The renewal of the multiaddress on the gateway failed,
+    /// or the external IP address of the gateway changed.
     ExpiredExternalAddr(Multiaddr),
     /// The IGD gateway was not found.
     GatewayNotFound,
     /// The Gateway is not exposed directly to the public network.
     NonRoutableGateway,
+    /// The external IP address of the gateway changed.
+    ///
+    /// The external addresses of the previous IP address are reported via
+    /// [`Event::ExpiredExternalAddr`] and the ports are mapped again, reporting
+    /// the new external addresses via [`Event::NewExternalAddr`].
+    ExternalIpChanged { old: IpAddr, new: IpAddr },
+}
+
+/// The status of the UPnP gateway, see [`Behaviour::gateway_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GatewayStatus {
+    /// The gateway is being searched for.
+    Searching,
+    /// The gateway is available for port mappings.
+    Available(GatewayInfo),
+    /// The IGD gateway was not found.
+    NotFound,
+    /// The gateway is not exposed directly to the public network.
+    NonRoutable {
+        /// The non-routable external IP address of the gateway.
+        external_ip: IpAddr,
+    },
+}
+
+/// Information about an available UPnP gateway.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayInfo {
+    /// The local address of the gateway.
+    pub addr: SocketAddr,
+    /// The external IP address of the gateway.
+    pub external_ip: IpAddr,
+}
+
+/// A port mapping of a listen address, see [`Behaviour::mappings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortMapping {
+    /// The listener of the mapped address.
+    pub listener_id: ListenerId,
+    /// The mapped listen address.
+    pub listen_addr: Multiaddr,
+    pub status: MappingStatus,
+}
+
+/// The status of a [`PortMapping`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MappingStatus {
+    /// The mapping has been or is about to be requested on the gateway,
+    /// or its removal has been requested.
+    Pending,
+    /// The mapping is active, i.e. the listen address is reachable externally.
+    Active {
+        /// The external address of the mapping.
+        external_addr: Multiaddr,
+        /// The remaining duration of the lease of the mapping on the gateway.
+        /// The mapping is renewed before the lease expires.
+        lease_remaining: Duration,
+    },
+    /// Requesting the mapping failed, it is requested again.
+    Failed,
 }
 
 /// A list of port mappings and its state.
@@ -183,8 +255,8 @@ impl MappingList {
                     }
                     *state = MappingState::Pending;
                 }
-                MappingState::Active(timeout) => {
-                    if Pin::new(timeout).poll(cx).is_ready() {
+                MappingState::Active { renewal, .. } => {
+                    if Pin::new(renewal).poll(cx).is_ready() {
                         let duration = MAPPING_DURATION;
                         if let Err(err) = gateway.sender.try_send(GatewayRequest::AddMapping {
                             mapping: mapping.clone(),
@@ -214,7 +286,7 @@ pub struct Behaviour {
     mappings: MappingList,
 
     /// Pending behaviour events to be emitted.
-    pending_events: VecDeque<Event>,
+    pending_events: VecDeque<ToSwarm<Event, THandlerInEvent<Self>>>,
 }
 
 impl Default for Behaviour {
@@ -227,6 +299,48 @@ impl Default for Behaviour {
     }
 }
 
+impl Behaviour {
+    /// Returns the status of the UPnP gateway.
+    pub fn gateway_status(&self) -> GatewayStatus {
+        match &self.state {
+            GatewayState::Searching(_) => GatewayStatus::Searching,
+            GatewayState::Available(gateway) => GatewayStatus::Available(GatewayInfo {
+                addr: gateway.addr,
+                external_ip: gateway.external_addr,
+            }),
+            GatewayState::GatewayNotFound => GatewayStatus::NotFound,
+            GatewayState::NonRoutableGateway(addr) => {
+                GatewayStatus::NonRoutable { external_ip: *addr }
+            }
+        }
+    }
+
+    /// Returns the port mappings of the listen addresses and their status.
+    pub fn mappings(&self) -> Vec<PortMapping> {
+        let now = Instant::now();
+        self.mappings
+            .iter()
+            .map(|(mapping, state)| {
+                let status = match (state, &self.state) {
+                    (MappingState::Active { expires, .. }, GatewayState::Available(gateway)) => {
+                        MappingStatus::Active {
+                            external_addr: mapping.external_addr(gateway.external_addr),
+                            lease_remaining: expires.saturating_duration_since(now),
+                        }
+                    }
+                    (MappingState::Failed, _) => MappingStatus::Failed,
+                    _ => MappingStatus::Pending,
+                };
+                PortMapping {
+                    listener_id: mapping.listener_id,
+                    listen_addr: mapping.multiaddr.clone(),
+                    status,
+                }
+            })
+            .collect()
+    }
+}
+
 impl NetworkBehaviour for Behaviour {
     type ConnectionHandler = dummy::ConnectionHandler;
 
@@ -369,14 +483,14 @@ impl NetworkBehaviour for Behaviour {
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<ToSwarm<Self::ToSwarm, libp2p_swarm::THandlerInEvent<Self>>> {
-        // If there are pending addresses to be emitted we emit them.
-        if let Some(event) = self.pending_events.pop_front() {
-            return Poll::Ready(ToSwarm::GenerateEvent(event));
-        }
-
         // Loop through the gateway state so that if it changes from `Searching` to `Available`
         // we poll the pending mapping requests.
         loop {
+            // If there are pending addresses to be emitted we emit them.
+            if let Some(event) = self.pending_events.pop_front() {
+                return Poll::Ready(event);
+            }
+
             match self.state {
                 GatewayState::Searching(ref mut fut) => match Pin::new(fut).poll(cx) {
                     Poll::Ready(result) => {
@@ -409,20 +523,16 @@ impl NetworkBehaviour for Behaviour {
                     if let Poll::Ready(Some(result)) = gateway.receiver.poll_next_unpin(cx) {
                         match result {
                             GatewayEvent::Mapped(mapping) => {
-                                let new_state = MappingState::Active(Delay::new(
-                                    Duration::from_secs(MAPPING_TIMEOUT),
-                                ));
-
                                 match self
                                     .mappings
-                                    .insert(mapping.clone(), new_state)
+                                    .insert(mapping.clone(), MappingState::active())
                                     .expect("mapping should exist")
                                 {
                                     MappingState::Pending => {
                                         let external_multiaddr =
                                             mapping.external_addr(gateway.external_addr);
-                                        self.pending_events.push_back(Event::NewExternalAddr(
-                                            external_multiaddr.clone(),
+                                        self.pending_events.push_back(ToSwarm::GenerateEvent(
+                                            Event::NewExternalAddr(external_multiaddr.clone()),
                                         ));
                                         tracing::debug!(
                                             address=%mapping.internal_addr,
@@ -433,7 +543,7 @@ impl NetworkBehaviour for Behaviour {
                                             external_multiaddr,
                                         ));
                                     }
-                                    MappingState::Active(_) => {
+                                    MappingState::Active { .. } => {
                                         tracing::debug!(
                                             address=%mapping.internal_addr,
                                             protocol=%mapping.protocol,
@@ -449,7 +559,7 @@ impl NetworkBehaviour for Behaviour {
                                     .insert(mapping.clone(), MappingState::Failed)
                                     .expect("mapping should exist")
                                 {
-                                    MappingState::Active(_) => {
+                                    MappingState::Active { .. } => {
                                         tracing::debug!(
                                             address=%mapping.internal_addr,
                                             protocol=%mapping.protocol,
@@ -457,8 +567,8 @@ impl NetworkBehaviour for Behaviour {
                                         );
                                         let external_multiaddr =
                                             mapping.external_addr(gateway.external_addr);
-                                        self.pending_events.push_back(Event::ExpiredExternalAddr(
-                                            external_multiaddr.clone(),
+                                        self.pending_events.push_back(ToSwarm::GenerateEvent(
+                                            Event::ExpiredExternalAddr(external_multiaddr.clone()),
                                         ));
                                         return Poll::Ready(ToSwarm::ExternalAddrExpired(
                                             external_multiaddr,
@@ -503,6 +613,49 @@ impl NetworkBehaviour for Behaviour {
                                     );
                                 }
                             }
+                            GatewayEvent::ExternalAddrChanged(new_addr) => {
+                                let old_addr = gateway.external_addr;
+                                tracing::debug!(
+                                    old_address=%old_addr,
+                                    new_address=%new_addr,
+                                    "the external address of the gateway changed"
+                                );
+                                self.pending_events.push_back(ToSwarm::GenerateEvent(
+                                    Event::ExternalIpChanged {
+                                        old: old_addr,
+                                        new: new_addr,
+                                    },
+                                ));
+                                // The mappings of the previous address are void, hence they are
+                                // requested again, reporting the new external addresses once mapped.
+                                for (mapping, state) in self.mappings.iter_mut() {
+                                    if let MappingState::Active { .. } = state {
+                                        let external_multiaddr = mapping.external_addr(old_addr);
+                                        self.pending_events.push_back(ToSwarm::GenerateEvent(
+                                            Event::ExpiredExternalAddr(external_multiaddr.clone()),
+                                        ));
+                                        self.pending_events.push_back(
+                                            ToSwarm::ExternalAddrExpired(external_multiaddr),
+                                        );
+                                        *state = MappingState::Inactive;
+                                    }
+                                }
+                                if !is_addr_global(new_addr) {
+                                    tracing::debug!(
+                                        gateway_address=%new_addr,
+                                        "the gateway is not routable"
+                                    );
+                                    self.mappings.clear();
+                                    self.state = GatewayState::NonRoutableGateway(new_addr);
+                                    self.pending_events.push_back(ToSwarm::GenerateEvent(
+                                        Event::NonRoutableGateway,
+                                    ));
+                                    continue;
+                                }
+                                gateway.external_addr = new_addr;
+                                self.mappings.renew(gateway, cx);
+                                continue;
+                            }
                         }
                     }
 
@@ -34,4 +34,4 @@ mod behaviour;
 pub mod tokio;
 
 #[cfg(feature = "tokio")]
-pub use behaviour::Event;
+pub use behaviour::{Event, GatewayInfo, GatewayStatus, MappingStatus, PortMapping};
@@ -18,15 +18,24 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use std::{error::Error, net::IpAddr};
+use std::{
+    error::Error,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
 
 use crate::behaviour::{GatewayEvent, GatewayRequest};
 use futures::{
     channel::{mpsc, oneshot},
+    future::{self, Either},
     SinkExt, StreamExt,
 };
+use futures_timer::Delay;
 use igd_next::SearchOptions;
 
+/// The interval at which the external IP address of the gateway is checked for changes.
+const EXTERNAL_IP_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
 pub use crate::behaviour::Behaviour;
 
 //TODO: remove when `IpAddr::is_global` stabilizes.
@@ -88,6 +97,8 @@ pub(crate) struct Gateway {
     pub(crate) sender: mpsc::Sender<GatewayRequest>,
     pub(crate) receiver: mpsc::Receiver<GatewayEvent>,
     pub(crate) external_addr: IpAddr,
+    /// The local address of the gateway.
+    pub(crate) addr: SocketAddr,
 }
 
 pub(crate) fn search_gateway() -> oneshot::Receiver<Result<Gateway, Box<dyn Error + Send + Sync>>> {
@@ -105,7 +116,7 @@ pub(crate) fn search_gateway() -> oneshot::Receiver<Result<Gateway, Box<dyn Erro
             }
         };
 
-        let external_addr = match gateway.get_external_ip().await {
+        let mut external_addr = match gateway.get_external_ip().await {
             Ok(addr) => addr,
             Err(err) => {
                 let _ = search_result_sender.send(Err(err.into()));
@@ -119,16 +130,42 @@ pub(crate) fn search_gateway() -> oneshot::Receiver<Result<Gateway, Box<dyn Erro
                 sender: events_sender,
                 receiver: events_queue,
                 external_addr,
+                addr: gateway.addr,
             }))
             .is_err()
         {
             return;
         }
 
+        let mut external_ip_check = Delay::new(EXTERNAL_IP_CHECK_INTERVAL);
         loop {
-            // The task sender has dropped so we can return.
-            let Some(req) = task_receiver.next().await else {
-                return;
+            let req = match future::select(task_receiver.next(), &mut external_ip_check).await {
+                Either::Left((Some(req), _)) => req,
+                // The task sender has dropped so we can return.
+                Either::Left((None, _)) => return,
+                Either::Right(((), _)) => {
+                    external_ip_check.reset(EXTERNAL_IP_CHECK_INTERVAL);
+                    match gateway.get_external_ip().await {
+                        Ok(addr) if addr != external_addr => {
+                            external_addr = addr;
+                            // Gateway was dropped.
+                            if task_sender
+                                .send(GatewayEvent::ExternalAddrChanged(addr))
+                                .await
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            tracing::debug!(
+                                "could not check the external address of the gateway: {err}"
+                            );
+                        }
+                    }
+                    continue;
+                }
             };
             let event = match req {
                 GatewayRequest::AddMapping { mapping, duration } => {
@@ -33,6 +33,8 @@ use std::fmt::Debug;
 use std::future::IntoFuture;
 use std::time::Duration;
 
+pub mod scenario;
+
 /// An extension trait for [`Swarm`] that makes it easier to set up a network of [`Swarm`]s for tests.
 #[async_trait]
 pub trait SwarmExt {
@@ -0,0 +1,343 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Declarative tests of multi-node scenarios.
+//!
+//! A [`Scenario`] declares a number of nodes, the [`Topology`] of the connections
+//! between them, actions scripted at points in time of the scenario and expectations
+//! on the events of the nodes. Running a scenario drives all nodes until every
+//! action has been run and every expectation has been met.
+//!
+//! ```rust,ignore
+//! let outcome = Scenario::new(3, |_, keypair| MyBehaviour::new(keypair))
+//!     .topology(Topology::Chain)
+//!     .at(Duration::ZERO, 0, |swarm, nodes| swarm.behaviour_mut().ping(nodes[2].peer_id))
+//!     .expect(2, "ping received", |event| matches!(event, SwarmEvent::Behaviour(Pinged)))
+//!     .run()
+//!     .await;
+//! ```
+
+use crate::SwarmExt;
+use futures::{FutureExt, StreamExt};
+use futures_timer::Delay;
+use libp2p_core::Multiaddr;
+use libp2p_identity::{Keypair, PeerId};
+use libp2p_swarm::{NetworkBehaviour, Swarm, SwarmEvent};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+/// A node of a [`Scenario`].
+#[derive(Debug, Clone)]
+pub struct Node {
+    /// The peer ID of the node.
+    pub peer_id: PeerId,
+    /// The memory address the node listens on, which is also an external address.
+    pub addr: Multiaddr,
+}
+
+/// The connections established between the nodes of a [`Scenario`] before it starts.
+#[derive(Debug, Clone, Default)]
+pub enum Topology {
+    /// No connections.
+    #[default]
+    Disconnected,
+    /// Every node is connected to the next one.
+    Chain,
+    /// Every node is connected to the given node.
+    Star { center: usize },
+    /// Every node is connected to every other node.
+    Full,
+    /// The given nodes are connected, the first one dialing the second one.
+    Edges(Vec<(usize, usize)>),
+}
+
+impl Topology {
+    /// The connections of the topology for the given number of nodes, as pairs of
+    /// the dialing and the listening node.
+    fn edges(&self, num_nodes: usize) -> Vec<(usize, usize)> {
+        match self {
+            Topology::Disconnected => Vec::new(),
+            Topology::Chain => (1..num_nodes).map(|i| (i - 1, i)).collect(),
+            Topology::Star { center } => (0..num_nodes)
+                .filter(|i| i != center)
+                .map(|i| (i, *center))
+                .collect(),
+            Topology::Full => (0..num_nodes)
+                .flat_map(|i| (i + 1..num_nodes).map(move |j| (i, j)))
+                .collect(),
+            Topology::Edges(edges) => edges.clone(),
+        }
+    }
+}
+
+type ActionFn<B> = Box<dyn FnOnce(&mut Swarm<B>, &[Node]) + Send>;
+type PredicateFn<B> = Box<dyn Fn(&SwarmEvent<<B as NetworkBehaviour>::ToSwarm>) -> bool + Send>;
+
+/// An action scripted at a point in time of a [`Scenario`].
+struct Action<B: NetworkBehaviour> {
+    time: Duration,
+    node: usize,
+    run: ActionFn<B>,
+}
+
+/// An expectation on the events of a node of a [`Scenario`].
+struct Expectation<B: NetworkBehaviour> {
+    node: usize,
+    description: String,
+    predicate: PredicateFn<B>,
+    met: bool,
+}
+
+/// A declarative test of multiple [`Swarm`]s, see the [module-level documentation](self).
+pub struct Scenario<B: NetworkBehaviour> {
+    swarms: Vec<Swarm<B>>,
+    topology: Topology,
+    actions: Vec<Action<B>>,
+    expectations: Vec<Expectation<B>>,
+    timeout: Duration,
+}
+
+impl<B> Scenario<B>
+where
+    B: NetworkBehaviour + Send,
+    B::ToSwarm: Debug,
+{
+    /// Creates a scenario of `num_nodes` nodes with ephemeral identities, see
+    /// [`SwarmExt::new_ephemeral`], and the behaviours created by `behaviour_fn`
+    /// from the index and the identity of each node.
+    pub fn new(num_nodes: usize, mut behaviour_fn: impl FnMut(usize, Keypair) -> B) -> Self {
+        Self {
+            swarms: (0..num_nodes)
+                .map(|i| Swarm::new_ephemeral(|keypair| behaviour_fn(i, keypair)))
+                .collect(),
+            topology: Topology::default(),
+            actions: Vec::new(),
+            expectations: Vec::new(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Sets the connections established between the nodes before the scenario starts.
+    ///
+    /// Defaults to [`Topology::Disconnected`].
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Scripts an action on the given node at the given time after the start of the
+    /// scenario, i.e. after the connections of the [`Topology`] are established.
+    ///
+    /// Actions scripted at the same time run in the order they were added.
+    pub fn at(
+        mut self,
+        time: Duration,
+        node: usize,
+        action: impl FnOnce(&mut Swarm<B>, &[Node]) + Send + 'static,
+    ) -> Self {
+        assert!(node < self.swarms.len(), "unknown node {node}");
+        self.actions.push(Action {
+            time,
+            node,
+            run: Box::new(action),
+        });
+        self
+    }
+
+    /// Expects the given node to emit an event matching the predicate after the start
+    /// of the scenario. The description identifies the expectation if it is not met.
+    pub fn expect(
+        mut self,
+        node: usize,
+        description: impl Into<String>,
+        predicate: impl Fn(&SwarmEvent<B::ToSwarm>) -> bool + Send + 'static,
+    ) -> Self {
+        assert!(node < self.swarms.len(), "unknown node {node}");
+        self.expectations.push(Expectation {
+            node,
+            description: description.into(),
+            predicate: Box::new(predicate),
+            met: false,
+        });
+        self
+    }
+
+    /// Sets the time after the start of the scenario by which all expectations must be met.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs the scenario, driving all nodes until every action has been run
+    /// and every expectation has been met.
+    ///
+    /// Events emitted while establishing the connections of the [`Topology`]
+    /// are not recorded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the expectations are not met within the timeout.
+    pub async fn run(self) -> Outcome<B> {
+        let Scenario {
+            mut swarms,
+            topology,
+            mut actions,
+            mut expectations,
+            timeout,
+        } = self;
+
+        let mut nodes = Vec::with_capacity(swarms.len());
+        for swarm in &mut swarms {
+            let (addr, _) = swarm.listen().with_memory_addr_external().await;
+            nodes.push(Node {
+                peer_id: *swarm.local_peer_id(),
+                addr,
+            });
+        }
+        for (dialer, listener) in topology.edges(swarms.len()) {
+            let (dialer, listener) = pair_mut(&mut swarms, dialer, listener);
+            dialer.connect(listener).await;
+        }
+
+        actions.sort_by_key(|action| action.time);
+        let mut actions = VecDeque::from(actions);
+        let mut events: Vec<Vec<_>> = swarms.iter().map(|_| Vec::new()).collect();
+        let start = Instant::now();
+        let mut deadline = Delay::new(timeout);
+        let mut first = 0;
+
+        loop {
+            while actions
+                .front()
+                .is_some_and(|action| action.time <= start.elapsed())
+            {
+                let action = actions.pop_front().expect("front exists");
+                (action.run)(&mut swarms[action.node], &nodes);
+            }
+            if actions.is_empty() && expectations.iter().all(|e| e.met) {
+                break;
+            }
+
+            let mut next_action = actions
+                .front()
+                .map(|action| Delay::new(action.time.saturating_sub(start.elapsed())));
+            let step = futures::future::poll_fn(|cx| {
+                if deadline.poll_unpin(cx).is_ready() {
+                    return Poll::Ready(None);
+                }
+                if let Some(delay) = next_action.as_mut() {
+                    if delay.poll_unpin(cx).is_ready() {
+                        return Poll::Ready(Some(None));
+                    }
+                }
+                // Rotate the node polled first to not starve any node.
+                let num_nodes = swarms.len();
+                for i in (0..num_nodes).map(|i| (first + i) % num_nodes) {
+                    if let Poll::Ready(Some(event)) = swarms[i].poll_next_unpin(cx) {
+                        first = (i + 1) % num_nodes;
+                        return Poll::Ready(Some(Some((i, event))));
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+
+            match step {
+                None => {
+                    let unmet: Vec<_> = expectations
+                        .iter()
+                        .filter(|e| !e.met)
+                        .map(|e| format!("node {}: {}", e.node, e.description))
+                        .collect();
+                    panic!("Scenario timed out after {timeout:?}, unmet expectations: {unmet:?}");
+                }
+                Some(None) => {}
+                Some(Some((node, event))) => {
+                    tracing::trace!(%node, ?event);
+                    for expectation in expectations.iter_mut().filter(|e| e.node == node && !e.met)
+                    {
+                        expectation.met = (expectation.predicate)(&event);
+                    }
+                    events[node].push(event);
+                }
+            }
+        }
+
+        Outcome {
+            swarms,
+            nodes,
+            events,
+        }
+    }
+}
+
+/// The nodes of a [`Scenario`] that ran and their events.
+pub struct Outcome<B: NetworkBehaviour> {
+    swarms: Vec<Swarm<B>>,
+    nodes: Vec<Node>,
+    events: Vec<Vec<SwarmEvent<B::ToSwarm>>>,
+}
+
+impl<B: NetworkBehaviour> Outcome<B> {
+    /// The nodes of the scenario.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// The events emitted by the given node after the start of the scenario.
+    pub fn events(&self, node: usize) -> &[SwarmEvent<B::ToSwarm>] {
+        &self.events[node]
+    }
+
+    /// The behaviour events emitted by the given node after the start of the scenario.
+    pub fn behaviour_events(&self, node: usize) -> impl Iterator<Item = &B::ToSwarm> {
+        self.events[node].iter().filter_map(|event| match event {
+            SwarmEvent::Behaviour(event) => Some(event),
+            _ => None,
+        })
+    }
+
+    /// The [`Swarm`] of the given node, e.g. to inspect the state of its behaviour.
+    pub fn swarm_mut(&mut self, node: usize) -> &mut Swarm<B> {
+        &mut self.swarms[node]
+    }
+
+    /// Consumes the outcome, returning the [`Swarm`]s of the nodes, e.g. to continue
+    /// driving them.
+    pub fn into_swarms(self) -> Vec<Swarm<B>> {
+        self.swarms
+    }
+}
+
+/// Returns mutable references to two distinct items.
+fn pair_mut<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+    assert_ne!(a, b, "a node cannot connect to itself");
+    if a < b {
+        let (left, right) = items.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = items.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
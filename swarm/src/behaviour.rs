@@ -28,16 +28,16 @@ pub use external_addresses::ExternalAddresses;
 pub use listen_addresses::ListenAddresses;
 pub use peer_addresses::PeerAddresses;
 
-use crate::connection::ConnectionId;
+use crate::connection::{BandwidthEstimate, ConnectionId};
 use crate::dial_opts::DialOpts;
 use crate::listen_opts::ListenOpts;
 use crate::{
-    ConnectionDenied, ConnectionHandler, DialError, ListenError, THandler, THandlerInEvent,
-    THandlerOutEvent,
+    ConnectionDenied, ConnectionHandler, DialError, ListenError, StreamUsage, THandler,
+    THandlerInEvent, THandlerOutEvent,
 };
 use libp2p_core::{transport::ListenerId, ConnectedPoint, Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
-use std::{task::Context, task::Poll};
+use std::{task::Context, task::Poll, time::Duration};
 
 /// A [`NetworkBehaviour`] defines the behaviour of the local node on the network.
 ///
@@ -308,6 +308,16 @@ pub enum ToSwarm<TOutEvent, TInEvent> {
 
     /// Reports external address of a remote peer to the [`Swarm`](crate::Swarm) and through that to other [`NetworkBehaviour`]s.
     NewExternalAddrOfPeer { peer_id: PeerId, address: Multiaddr },
+
+    /// Instructs the `Swarm` to refuse new outbound streams to the given peer for the given duration.
+    ///
+    /// See [`Swarm::ban_streams`](crate::Swarm::ban_streams).
+    BanStreams {
+        /// The peer to ban outbound streams to.
+        peer_id: PeerId,
+        /// How long to ban outbound streams for.
+        duration: Duration,
+    },
 }
 
 impl<TOutEvent, TInEventOld> ToSwarm<TOutEvent, TInEventOld> {
@@ -347,6 +357,7 @@ impl<TOutEvent, TInEventOld> ToSwarm<TOutEvent, TInEventOld> {
                 address: addr,
                 peer_id,
             },
+            ToSwarm::BanStreams { peer_id, duration } => ToSwarm::BanStreams { peer_id, duration },
         }
     }
 }
@@ -385,6 +396,7 @@ impl<TOutEvent, THandlerIn> ToSwarm<TOutEvent, THandlerIn> {
                 address: addr,
                 peer_id,
             },
+            ToSwarm::BanStreams { peer_id, duration } => ToSwarm::BanStreams { peer_id, duration },
         }
     }
 }
@@ -453,6 +465,13 @@ pub enum FromSwarm<'a> {
     ExternalAddrExpired(ExternalAddrExpired<'a>),
     /// Informs the behaviour that we have discovered a new external address for a remote peer.
     NewExternalAddrOfPeer(NewExternalAddrOfPeer<'a>),
+    /// Informs the behaviour that the bandwidth of a connection has been estimated,
+    /// see [`Config::with_bandwidth_estimation`](crate::Config::with_bandwidth_estimation).
+    BandwidthEstimated(BandwidthEstimated),
+    /// Informs the behaviour about the [`StreamUsage`] of a newly established connection.
+    ///
+    /// This event directly follows the [`FromSwarm::ConnectionEstablished`] of the connection.
+    ConnectionStreams(ConnectionStreams<'a>),
 }
 
 /// [`FromSwarm`] variant that informs the behaviour about a newly established connection to a peer.
@@ -571,3 +590,20 @@ pub struct NewExternalAddrOfPeer<'a> {
     pub peer_id: PeerId,
     pub addr: &'a Multiaddr,
 }
+
+/// [`FromSwarm`] variant that informs the behaviour about the [`StreamUsage`] of a newly
+/// established connection.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionStreams<'a> {
+    pub peer_id: PeerId,
+    pub connection_id: ConnectionId,
+    pub usage: &'a StreamUsage,
+}
+
+/// [`FromSwarm`] variant that informs the behaviour that the bandwidth of a connection has been estimated.
+#[derive(Clone, Copy, Debug)]
+pub struct BandwidthEstimated {
+    pub peer_id: PeerId,
+    pub connection_id: ConnectionId,
+    pub estimate: BandwidthEstimate,
+}
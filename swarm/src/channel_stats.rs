@@ -0,0 +1,115 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Saturation statistics of the internal channels between the [`Swarm`](crate::Swarm) and
+/// the tasks driving its connections.
+///
+/// The statistics stay up to date with the [`Swarm`](crate::Swarm) and can be cloned cheaply,
+/// e.g. to export them as metrics. See [`Swarm::channel_stats`](crate::Swarm::channel_stats).
+///
+/// Time spent blocked on a channel indicates that its capacity is too low for the volume of
+/// events, see [`Config::with_notify_handler_buffer_size`](crate::Config::with_notify_handler_buffer_size),
+/// [`Config::with_per_connection_event_buffer_size`](crate::Config::with_per_connection_event_buffer_size)
+/// and [`Config::with_pending_connection_event_buffer_size`](crate::Config::with_pending_connection_event_buffer_size).
+#[derive(Debug, Clone, Default)]
+pub struct ChannelStats {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    notify_handler: Counter,
+    connection_events: Counter,
+    pending_connection_events: Counter,
+}
+
+impl ChannelStats {
+    /// Returns the saturation of the buffers for events sent by the
+    /// [`NetworkBehaviour`](crate::NetworkBehaviour) to the
+    /// [`ConnectionHandler`](crate::ConnectionHandler)s.
+    ///
+    /// While blocked, the [`NetworkBehaviour`](crate::NetworkBehaviour) is not polled.
+    pub fn notify_handler(&self) -> ChannelSaturation {
+        self.inner.notify_handler.get()
+    }
+
+    /// Returns the saturation of the buffers for events sent by the
+    /// [`ConnectionHandler`](crate::ConnectionHandler)s to the
+    /// [`NetworkBehaviour`](crate::NetworkBehaviour).
+    ///
+    /// While blocked, the connection sending the event makes no progress.
+    pub fn connection_events(&self) -> ChannelSaturation {
+        self.inner.connection_events.get()
+    }
+
+    /// Returns the saturation of the buffer for events of pending connections,
+    /// i.e. connections being established or failing to be established.
+    pub fn pending_connection_events(&self) -> ChannelSaturation {
+        self.inner.pending_connection_events.get()
+    }
+
+    pub(crate) fn record_notify_handler(&self, blocked: Duration) {
+        self.inner.notify_handler.record(blocked)
+    }
+
+    pub(crate) fn record_connection_events(&self, blocked: Duration) {
+        self.inner.connection_events.record(blocked)
+    }
+
+    pub(crate) fn record_pending_connection_events(&self, blocked: Duration) {
+        self.inner.pending_connection_events.record(blocked)
+    }
+}
+
+/// The saturation of a channel over the lifetime of the [`Swarm`](crate::Swarm).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelSaturation {
+    /// The number of times a sender found the channel full and had to wait.
+    pub num_blocked: u64,
+    /// The sum of the durations senders waited for the channel to have capacity.
+    pub blocked_duration: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Counter {
+    num_blocked: AtomicU64,
+    blocked_nanos: AtomicU64,
+}
+
+impl Counter {
+    fn record(&self, blocked: Duration) {
+        self.num_blocked.fetch_add(1, Ordering::Relaxed);
+        self.blocked_nanos.fetch_add(
+            u64::try_from(blocked.as_nanos()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    fn get(&self) -> ChannelSaturation {
+        ChannelSaturation {
+            num_blocked: self.num_blocked.load(Ordering::Relaxed),
+            blocked_duration: Duration::from_nanos(self.blocked_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
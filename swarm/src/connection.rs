@@ -18,12 +18,16 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+mod bandwidth;
 mod error;
 
 pub(crate) mod pool;
 mod supported_protocols;
 
-pub use error::ConnectionError;
+pub use bandwidth::BandwidthEstimate;
+pub(crate) use bandwidth::BandwidthEstimator;
+
+pub use error::{ConnectionError, StreamBanned};
 pub(crate) use error::{
     PendingConnectionError, PendingInboundConnectionError, PendingOutboundConnectionError,
 };
@@ -34,7 +38,9 @@ use crate::handler::{
     FullyNegotiatedOutbound, ListenUpgradeError, ProtocolSupport, ProtocolsAdded, ProtocolsChange,
     UpgradeInfoSend,
 };
-use crate::stream::ActiveStreamCounter;
+use crate::stream::{
+    ActiveStreamCounter, ClosingStreams, ProtocolCache, StreamRegistry, StreamUsage,
+};
 use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend};
 use crate::{
     ConnectionHandlerEvent, Stream, StreamProtocol, StreamUpgradeError, SubstreamProtocol,
@@ -56,6 +62,7 @@ use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 use std::future::Future;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::task::Waker;
 use std::time::Duration;
 use std::{fmt, io, mem, pin::Pin, task::Context, task::Poll};
@@ -105,6 +112,12 @@ pub(crate) enum Event<T> {
     Handler(T),
     /// Address of the remote has changed.
     AddressChange(Multiaddr),
+    /// A stream has been negotiated for the given protocol, see [`Connection::report_opened_streams`].
+    StreamOpened(String),
+    /// All streams of a protocol asked to close by [`Connection::close_streams`] are closed.
+    StreamsClosed(StreamProtocol),
+    /// The bandwidth of the connection has been estimated, see [`Connection::estimate_bandwidth`].
+    BandwidthEstimated(BandwidthEstimate),
 }
 
 /// A multiplexed connection to a peer with an associated [`ConnectionHandler`].
@@ -157,6 +170,13 @@ where
     remote_supported_protocols: HashSet<StreamProtocol>,
     idle_timeout: Duration,
     stream_counter: ActiveStreamCounter,
+    /// Until when new outbound streams are refused, if at all.
+    streams_banned_until: Option<Instant>,
+    stream_registry: StreamRegistry,
+    /// Streams asked to close by [`Connection::close_streams`], by protocol.
+    closing_streams: Vec<(StreamProtocol, ClosingStreams)>,
+    /// The estimator of the bandwidth and the end of its estimation window, if any.
+    bandwidth_estimation: Option<(Arc<BandwidthEstimator>, Delay)>,
 }
 
 impl<THandler> fmt::Debug for Connection<THandler>
@@ -205,6 +225,10 @@ where
             remote_supported_protocols: Default::default(),
             idle_timeout,
             stream_counter: ActiveStreamCounter::default(),
+            streams_banned_until: None,
+            stream_registry: StreamRegistry::default(),
+            closing_streams: Vec::new(),
+            bandwidth_estimation: None,
         }
     }
 
@@ -213,6 +237,53 @@ where
         self.handler.on_behaviour_event(event);
     }
 
+    /// Refuses new outbound streams until the given instant.
+    pub(crate) fn ban_streams(&mut self, until: Instant) {
+        self.streams_banned_until = Some(until);
+    }
+
+    /// Returns a view of the active streams of the connection by protocol.
+    pub(crate) fn stream_usage(&self) -> StreamUsage {
+        self.stream_registry.usage()
+    }
+
+    /// Estimates the bandwidth from the transfers of streams within the given window.
+    ///
+    /// Emits [`Event::BandwidthEstimated`] once the window elapsed.
+    pub(crate) fn estimate_bandwidth(&mut self, window: Duration) {
+        let estimator = Arc::new(BandwidthEstimator::new());
+        self.stream_registry
+            .set_bandwidth_estimator(Some(estimator.clone()));
+        self.bandwidth_estimation = Some((estimator, Delay::new(window)));
+    }
+
+    /// Reports new streams once negotiated.
+    ///
+    /// Emits [`Event::StreamOpened`] for every stream negotiated from then on.
+    pub(crate) fn report_opened_streams(&mut self) {
+        self.stream_registry.record_opened();
+    }
+
+    /// Selects the protocol of new outbound streams optimistically if the remote is known to
+    /// support it according to the given cache, saving the round trip of the negotiation.
+    ///
+    /// The cache is updated with the protocols reported by the handler and the outcome of
+    /// negotiations.
+    pub(crate) fn cache_protocols(&mut self, cache: ProtocolCache) {
+        for protocol in &self.remote_supported_protocols {
+            cache.insert(protocol.as_ref());
+        }
+        self.stream_registry.set_protocol_cache(cache);
+    }
+
+    /// Asks all streams negotiated for the given protocol to close.
+    ///
+    /// Emits [`Event::StreamsClosed`] once the handler dropped all of them.
+    pub(crate) fn close_streams(&mut self, protocol: StreamProtocol) {
+        let closing = self.stream_registry.close(protocol.as_ref());
+        self.closing_streams.push((protocol, closing));
+    }
+
     /// Begins an orderly shutdown of the connection, returning a stream of final events and a `Future` that resolves when connection shutdown is complete.
     pub(crate) fn close(
         self,
@@ -252,6 +323,10 @@ where
             remote_supported_protocols,
             idle_timeout,
             stream_counter,
+            streams_banned_until,
+            stream_registry,
+            closing_streams,
+            bandwidth_estimation,
             ..
         } = self.get_mut();
 
@@ -277,6 +352,16 @@ where
                     let timeout = *protocol.timeout();
                     let (upgrade, user_data) = protocol.into_upgrade();
 
+                    if streams_banned_until.is_some_and(|until| Instant::now() < until) {
+                        handler.on_connection_event(ConnectionEvent::DialUpgradeError(
+                            DialUpgradeError {
+                                info: user_data,
+                                error: StreamUpgradeError::Io(StreamBanned.into()),
+                            },
+                        ));
+                        continue;
+                    }
+
                     requested_substreams.push(SubstreamRequested::new(user_data, timeout, upgrade));
                     continue; // Poll handler until exhausted.
                 }
@@ -286,6 +371,11 @@ where
                 Poll::Ready(ConnectionHandlerEvent::ReportRemoteProtocols(
                     ProtocolSupport::Added(protocols),
                 )) => {
+                    if let Some(cache) = stream_registry.protocol_cache() {
+                        for protocol in &protocols {
+                            cache.insert(protocol.as_ref());
+                        }
+                    }
                     if let Some(added) =
                         ProtocolsChange::add(remote_supported_protocols, &protocols)
                     {
@@ -298,6 +388,11 @@ where
                 Poll::Ready(ConnectionHandlerEvent::ReportRemoteProtocols(
                     ProtocolSupport::Removed(protocols),
                 )) => {
+                    if let Some(cache) = stream_registry.protocol_cache() {
+                        for protocol in &protocols {
+                            cache.remove(protocol.as_ref());
+                        }
+                    }
                     if let Some(removed) =
                         ProtocolsChange::remove(remote_supported_protocols, &protocols)
                     {
@@ -357,6 +452,10 @@ where
                 }
             }
 
+            if let Some(protocol) = stream_registry.next_opened() {
+                return Poll::Ready(Ok(Event::StreamOpened(protocol)));
+            }
+
             // Check if the connection (and handler) should be shut down.
             // As long as we're still negotiating substreams or have any active streams shutdown is always postponed.
             if negotiating_in.is_empty()
@@ -407,6 +506,7 @@ where
                             upgrade,
                             *substream_upgrade_protocol_override,
                             stream_counter.clone(),
+                            stream_registry.clone(),
                         ));
 
                         continue; // Go back to the top, handler can potentially make progress again.
@@ -424,6 +524,7 @@ where
                             substream,
                             protocol,
                             stream_counter.clone(),
+                            stream_registry.clone(),
                         ));
 
                         continue; // Go back to the top, handler can potentially make progress again.
@@ -431,6 +532,23 @@ where
                 }
             }
 
+            if let Some((estimator, window)) = bandwidth_estimation {
+                if window.poll_unpin(cx).is_ready() {
+                    let estimate = estimator.finish();
+                    *bandwidth_estimation = None;
+                    stream_registry.set_bandwidth_estimator(None);
+                    return Poll::Ready(Ok(Event::BandwidthEstimated(estimate)));
+                }
+            }
+
+            if let Some(i) = closing_streams
+                .iter_mut()
+                .position(|(_, streams)| streams.poll_closed(cx).is_ready())
+            {
+                let (protocol, _) = closing_streams.swap_remove(i);
+                return Poll::Ready(Ok(Event::StreamsClosed(protocol)));
+            }
+
             let new_protocols = gather_supported_protocols(handler);
             let changes = ProtocolsChange::from_full_sets(supported_protocols, &new_protocols);
 
@@ -528,6 +646,7 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
         upgrade: Upgrade,
         version_override: Option<upgrade::Version>,
         counter: ActiveStreamCounter,
+        registry: StreamRegistry,
     ) -> Self
     where
         Upgrade: OutboundUpgradeSend<Output = TOk, Error = TErr>,
@@ -544,22 +663,55 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
             }
             _ => upgrade::Version::default(),
         };
-        let protocols = upgrade.protocol_info();
+        let protocols = upgrade.protocol_info().collect::<Vec<_>>();
+        let protocol_cache = registry.protocol_cache().cloned();
+        // An explicit override takes precedence over the optimistic selection.
+        let optimistic = protocol_cache
+            .as_ref()
+            .filter(|_| version_override.is_none())
+            .and_then(|cache| protocols.iter().find(|p| cache.contains(p.as_ref())))
+            .cloned();
+        let stats = substream.stats();
 
         Self {
             user_data: Some(user_data),
             timeout,
             upgrade: Box::pin(async move {
-                let (info, stream) = multistream_select::dialer_select_proto(
-                    substream,
-                    protocols,
-                    effective_version,
-                )
-                .await
-                .map_err(to_stream_upgrade_error)?;
+                let (info, stream) = match &optimistic {
+                    Some(protocol) => multistream_select::dialer_select_proto(
+                        substream,
+                        std::iter::once(protocol.clone()),
+                        upgrade::Version::V1Lazy,
+                    )
+                    .await
+                    .map_err(to_stream_upgrade_error)?,
+                    None => {
+                        let (info, stream) = multistream_select::dialer_select_proto(
+                            substream,
+                            protocols,
+                            effective_version,
+                        )
+                        .await
+                        .map_err(to_stream_upgrade_error)?;
+                        if let Some(cache) = &protocol_cache {
+                            cache.insert(info.as_ref());
+                        }
 
+                        (info, stream)
+                    }
+                };
+
+                let mut stream = Stream::new(
+                    stream,
+                    counter,
+                    registry.register(info.as_ref(), stats),
+                    registry.bandwidth_estimator(),
+                );
+                if let (Some(cache), Some(_)) = (protocol_cache, optimistic) {
+                    stream = stream.with_optimistic_negotiation(cache, info.as_ref());
+                }
                 let output = upgrade
-                    .upgrade_outbound(Stream::new(stream, counter), info)
+                    .upgrade_outbound(stream, info)
                     .await
                     .map_err(StreamUpgradeError::Apply)?;
 
@@ -574,6 +726,7 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
         substream: SubstreamBox,
         protocol: SubstreamProtocol<Upgrade, UserData>,
         counter: ActiveStreamCounter,
+        registry: StreamRegistry,
     ) -> Self
     where
         Upgrade: InboundUpgradeSend<Output = TOk, Error = TErr>,
@@ -581,6 +734,7 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
         let timeout = *protocol.timeout();
         let (upgrade, open_info) = protocol.into_upgrade();
         let protocols = upgrade.protocol_info();
+        let stats = substream.stats();
 
         Self {
             user_data: Some(open_info),
@@ -591,8 +745,14 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
                         .await
                         .map_err(to_stream_upgrade_error)?;
 
+                let stream = Stream::new(
+                    stream,
+                    counter,
+                    registry.register(info.as_ref(), stats),
+                    registry.bandwidth_estimator(),
+                );
                 let output = upgrade
-                    .upgrade_inbound(Stream::new(stream, counter), info)
+                    .upgrade_inbound(stream, info)
                     .await
                     .map_err(StreamUpgradeError::Apply)?;
 
@@ -738,12 +898,15 @@ enum Shutdown {
 mod tests {
     use super::*;
     use crate::dummy;
+    use crate::stream::ProtocolStats;
     use futures::future;
     use futures::AsyncRead;
     use futures::AsyncWrite;
+    use libp2p_core::muxing::SubstreamStats;
     use libp2p_core::upgrade::{DeniedUpgrade, InboundUpgrade, OutboundUpgrade, UpgradeInfo};
     use libp2p_core::StreamMuxer;
     use quickcheck::*;
+    use std::collections::HashMap;
     use std::sync::{Arc, Weak};
     use std::time::Instant;
     use tracing_subscriber::EnvFilter;
@@ -806,6 +969,210 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn outbound_stream_refused_while_banned() {
+        let mut connection = Connection::new(
+            StreamMuxerBox::new(PendingStreamMuxer),
+            MockConnectionHandler::new(Duration::from_secs(10)),
+            None,
+            2,
+            Duration::ZERO,
+        );
+
+        connection.ban_streams(Instant::now() + Duration::from_secs(10));
+        connection.handler.open_new_outbound();
+        let _ = connection.poll_noop_waker();
+
+        assert!(matches!(
+            connection.handler.error.take().unwrap(),
+            StreamUpgradeError::Io(e) if StreamBanned::is_stream_banned(&e)
+        ));
+
+        // An elapsed ban no longer refuses streams.
+        connection.ban_streams(Instant::now());
+        connection.handler.open_new_outbound();
+        let _ = connection.poll_noop_waker();
+
+        assert!(connection.handler.error.is_none());
+    }
+
+    #[test]
+    fn close_streams_completes_once_streams_are_dropped() {
+        let mut connection = Connection::new(
+            StreamMuxerBox::new(PendingStreamMuxer),
+            MockConnectionHandler::new(Duration::from_secs(10)),
+            None,
+            2,
+            Duration::ZERO,
+        );
+        let foo = connection
+            .stream_registry
+            .register("/foo", SubstreamStats::new());
+        let bar = connection
+            .stream_registry
+            .register("/bar", SubstreamStats::new());
+
+        connection.close_streams(StreamProtocol::new("/foo"));
+
+        assert!(foo.is_closing(&mut Context::from_waker(futures::task::noop_waker_ref())));
+        assert!(!bar.is_closing(&mut Context::from_waker(futures::task::noop_waker_ref())));
+        assert!(connection.poll_noop_waker().is_pending());
+
+        drop(foo);
+
+        assert!(matches!(
+            connection.poll_noop_waker(),
+            Poll::Ready(Ok(Event::StreamsClosed(protocol))) if protocol == StreamProtocol::new("/foo")
+        ));
+        assert!(connection.poll_noop_waker().is_pending());
+    }
+
+    #[test]
+    fn reports_opened_streams_in_order() {
+        let mut connection = Connection::new(
+            StreamMuxerBox::new(PendingStreamMuxer),
+            MockConnectionHandler::new(Duration::from_secs(10)),
+            None,
+            2,
+            Duration::ZERO,
+        );
+        let _unreported = connection
+            .stream_registry
+            .register("/baz", SubstreamStats::new());
+        connection.report_opened_streams();
+        let _foo = connection
+            .stream_registry
+            .register("/foo", SubstreamStats::new());
+        let _bar = connection
+            .stream_registry
+            .register("/bar", SubstreamStats::new());
+
+        assert!(matches!(
+            connection.poll_noop_waker(),
+            Poll::Ready(Ok(Event::StreamOpened(protocol))) if protocol == "/foo"
+        ));
+        assert!(matches!(
+            connection.poll_noop_waker(),
+            Poll::Ready(Ok(Event::StreamOpened(protocol))) if protocol == "/bar"
+        ));
+        assert!(connection.poll_noop_waker().is_pending());
+    }
+
+    #[test]
+    fn stream_usage_counts_active_streams_by_protocol() {
+        let connection = Connection::new(
+            StreamMuxerBox::new(PendingStreamMuxer),
+            MockConnectionHandler::new(Duration::from_secs(10)),
+            None,
+            2,
+            Duration::ZERO,
+        );
+        let usage = connection.stream_usage();
+        let foo1 = connection
+            .stream_registry
+            .register("/foo", SubstreamStats::new());
+        let foo2 = connection
+            .stream_registry
+            .register("/foo", SubstreamStats::new());
+        let bar = connection
+            .stream_registry
+            .register("/bar", SubstreamStats::new());
+
+        assert_eq!(usage.num_streams("/foo"), 2);
+        assert_eq!(usage.num_streams("/baz"), 0);
+        assert_eq!(
+            usage.protocols(),
+            HashMap::from([("/foo".to_owned(), 2), ("/bar".to_owned(), 1)])
+        );
+
+        drop(foo1);
+        drop(bar);
+
+        assert_eq!(usage.num_streams("/foo"), 1);
+        assert_eq!(usage.protocols(), HashMap::from([("/foo".to_owned(), 1)]));
+
+        drop(foo2);
+        assert!(usage.protocols().is_empty());
+    }
+
+    #[test]
+    fn stream_usage_aggregates_stats_by_protocol() {
+        let connection = Connection::new(
+            StreamMuxerBox::new(PendingStreamMuxer),
+            MockConnectionHandler::new(Duration::from_secs(10)),
+            None,
+            2,
+            Duration::ZERO,
+        );
+        let usage = connection.stream_usage();
+        let foo1_stats = SubstreamStats::new();
+        let foo2_stats = SubstreamStats::new();
+        let foo1 = connection
+            .stream_registry
+            .register("/foo", foo1_stats.clone());
+        let foo2 = connection
+            .stream_registry
+            .register("/foo", foo2_stats.clone());
+
+        foo1_stats.record_received(10);
+        foo1_stats.record_sent(5);
+        foo2_stats.record_sent(7);
+
+        let stats = usage.stats("/foo");
+        assert_eq!(stats.num_streams, 2);
+        assert_eq!(stats.bytes_received, 10);
+        assert_eq!(stats.bytes_sent, 12);
+
+        // Closing and dropping streams keeps their transfers in the statistics.
+        drop(connection.stream_registry.close("/foo"));
+        drop(foo1);
+        foo1_stats.record_closed();
+        let bar = connection
+            .stream_registry
+            .register("/bar", SubstreamStats::new());
+
+        assert_eq!(usage.num_streams("/foo"), 0);
+        let stats = usage.stats("/foo");
+        assert_eq!(stats.num_streams, 2);
+        assert_eq!(stats.bytes_received, 10);
+        assert_eq!(stats.bytes_sent, 12);
+        assert_eq!(usage.stats("/baz"), ProtocolStats::default());
+        assert_eq!(
+            usage.protocol_stats().keys().collect::<HashSet<_>>(),
+            HashSet::from([&"/foo".to_owned(), &"/bar".to_owned()])
+        );
+
+        drop((foo2, bar));
+    }
+
+    #[test]
+    fn reports_bandwidth_estimate_after_window() {
+        let window = Duration::from_millis(50);
+        let mut connection = Connection::new(
+            StreamMuxerBox::new(PendingStreamMuxer),
+            MockConnectionHandler::new(Duration::from_secs(10)),
+            None,
+            2,
+            Duration::ZERO,
+        );
+
+        connection.estimate_bandwidth(window);
+        let estimator = connection.stream_registry.bandwidth_estimator().unwrap();
+        estimator.record_inbound(1_000);
+        assert!(connection.poll_noop_waker().is_pending());
+
+        std::thread::sleep(window);
+
+        assert!(matches!(
+            connection.poll_noop_waker(),
+            Poll::Ready(Ok(Event::BandwidthEstimated(BandwidthEstimate {
+                inbound: 10_000,
+                outbound: 0
+            })))
+        ));
+        assert!(connection.stream_registry.bandwidth_estimator().is_none());
+    }
+
     #[test]
     fn propagates_changes_to_supported_inbound_protocols() {
         let mut connection = Connection::new(
@@ -896,6 +1263,35 @@ mod tests {
         assert_eq!(connection.handler.remote_removed, vec![vec!["/bar"]]);
     }
 
+    #[test]
+    fn protocol_cache_follows_remote_protocols() {
+        let mut connection = Connection::new(
+            StreamMuxerBox::new(PendingStreamMuxer),
+            ConfigurableProtocolConnectionHandler::default(),
+            None,
+            0,
+            Duration::ZERO,
+        );
+        connection.handler.remote_adds_support_for(&["/foo"]);
+        let _ = connection.poll_noop_waker();
+
+        // Protocols reported before the cache is set are not lost.
+        let cache = ProtocolCache::default();
+        connection.cache_protocols(cache.clone());
+        assert!(cache.contains("/foo"));
+
+        connection
+            .handler
+            .remote_adds_support_for(&["/foo", "/bar"]);
+        let _ = connection.poll_noop_waker();
+        assert!(cache.contains("/bar"));
+
+        connection.handler.remote_removes_support_for(&["/foo"]);
+        let _ = connection.poll_noop_waker();
+        assert!(!cache.contains("/foo"));
+        assert!(cache.contains("/bar"));
+    }
+
     #[tokio::test]
     async fn idle_timeout_with_keep_alive_no() {
         let idle_timeout = Duration::from_millis(100);
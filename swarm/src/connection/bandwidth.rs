@@ -0,0 +1,135 @@
+use instant::Instant;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The interval over which transferred bytes are summed up to a throughput sample.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A passive estimate of the bandwidth of a connection, see
+/// [`Config::with_bandwidth_estimation`](crate::Config::with_bandwidth_estimation).
+///
+/// The estimate is the peak throughput observed while the connection's streams
+/// transferred data, thus it is a lower bound of the available bandwidth which
+/// is only as good as the transfers within the estimation window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BandwidthEstimate {
+    /// The estimated inbound bandwidth in bytes per second, zero if nothing was received.
+    pub inbound: u64,
+    /// The estimated outbound bandwidth in bytes per second, zero if nothing was sent.
+    pub outbound: u64,
+}
+
+/// Estimates the bandwidth of a connection from the transfers of its streams.
+#[derive(Debug)]
+pub(crate) struct BandwidthEstimator {
+    state: Mutex<Option<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    inbound: Sampler,
+    outbound: Sampler,
+}
+
+impl BandwidthEstimator {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(Some(State {
+                inbound: Sampler::default(),
+                outbound: Sampler::default(),
+            })),
+        }
+    }
+
+    pub(crate) fn record_inbound(&self, bytes: usize) {
+        if let Some(state) = self.state.lock().expect("lock not to be poisoned").as_mut() {
+            state.inbound.record(Instant::now(), bytes);
+        }
+    }
+
+    pub(crate) fn record_outbound(&self, bytes: usize) {
+        if let Some(state) = self.state.lock().expect("lock not to be poisoned").as_mut() {
+            state.outbound.record(Instant::now(), bytes);
+        }
+    }
+
+    /// Stops recording transfers, returning the estimate.
+    pub(crate) fn finish(&self) -> BandwidthEstimate {
+        let Some(state) = self.state.lock().expect("lock not to be poisoned").take() else {
+            return BandwidthEstimate::default();
+        };
+
+        BandwidthEstimate {
+            inbound: state.inbound.finish(),
+            outbound: state.outbound.finish(),
+        }
+    }
+}
+
+/// Tracks the peak throughput over [`SAMPLE_INTERVAL`]s.
+#[derive(Debug, Default)]
+struct Sampler {
+    current: Option<(Instant, u64)>,
+    peak: u64,
+}
+
+impl Sampler {
+    fn record(&mut self, now: Instant, bytes: usize) {
+        match &mut self.current {
+            Some((start, sum)) if now.duration_since(*start) < SAMPLE_INTERVAL => {
+                *sum += bytes as u64;
+                return;
+            }
+            Some((_, sum)) => self.peak = self.peak.max(throughput(*sum)),
+            None => {}
+        }
+        self.current = Some((now, bytes as u64));
+    }
+
+    fn finish(self) -> u64 {
+        match self.current {
+            Some((_, sum)) => self.peak.max(throughput(sum)),
+            None => self.peak,
+        }
+    }
+}
+
+/// The throughput in bytes per second of `bytes` transferred within a [`SAMPLE_INTERVAL`].
+fn throughput(bytes: u64) -> u64 {
+    (bytes as u128 * 1000 / SAMPLE_INTERVAL.as_millis()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_peak_throughput() {
+        let now = Instant::now();
+        let mut sampler = Sampler::default();
+
+        sampler.record(now, 1_000);
+        sampler.record(now + Duration::from_millis(50), 1_000);
+        sampler.record(now + Duration::from_millis(200), 500);
+        sampler.record(now + Duration::from_millis(400), 100);
+
+        assert_eq!(sampler.finish(), 20_000);
+    }
+
+    #[test]
+    fn ignores_transfers_after_estimation() {
+        let estimator = BandwidthEstimator::new();
+        estimator.record_outbound(1_000);
+
+        assert_eq!(
+            estimator.finish(),
+            BandwidthEstimate {
+                inbound: 0,
+                outbound: 10_000
+            }
+        );
+        // Transfers after the estimation are ignored.
+        estimator.record_inbound(1_000);
+        assert_eq!(estimator.finish(), BandwidthEstimate::default());
+    }
+}
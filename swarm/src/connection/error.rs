@@ -60,6 +60,38 @@ impl From<io::Error> for ConnectionError {
     }
 }
 
+/// The error with which an outbound stream is refused while outbound streams
+/// to the remote peer are banned, see [`Swarm::ban_streams`](crate::Swarm::ban_streams).
+///
+/// Reported to the [`ConnectionHandler`](crate::ConnectionHandler) as the inner error
+/// of a [`StreamUpgradeError::Io`](crate::StreamUpgradeError::Io) of kind
+/// [`io::ErrorKind::PermissionDenied`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamBanned;
+
+impl StreamBanned {
+    /// Checks whether the given error is the result of a stream ban.
+    pub fn is_stream_banned(error: &io::Error) -> bool {
+        error
+            .get_ref()
+            .is_some_and(|e| e.downcast_ref::<StreamBanned>().is_some())
+    }
+}
+
+impl fmt::Display for StreamBanned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Outbound streams to the remote peer are banned.")
+    }
+}
+
+impl std::error::Error for StreamBanned {}
+
+impl From<StreamBanned> for io::Error {
+    fn from(error: StreamBanned) -> Self {
+        io::Error::new(io::ErrorKind::PermissionDenied, error)
+    }
+}
+
 /// Errors that can occur in the context of a pending outgoing `Connection`.
 ///
 /// Note: Addresses for an outbound connection are dialed in parallel. Thus, compared to
@@ -21,11 +21,13 @@
 use crate::connection::{Connection, ConnectionId, PendingPoint};
 use crate::{
     connection::{
-        Connected, ConnectionError, IncomingInfo, PendingConnectionError,
+        BandwidthEstimate, Connected, ConnectionError, IncomingInfo, PendingConnectionError,
         PendingInboundConnectionError, PendingOutboundConnectionError,
     },
+    stream::{ProtocolCache, StreamUsage},
     transport::TransportError,
-    ConnectedPoint, ConnectionHandler, Executor, Multiaddr, PeerId,
+    ChannelStats, ConnectedPoint, ConnectionHandler, Executor, Multiaddr, PeerId, PendingStats,
+    StreamProtocol,
 };
 use concurrent_dial::ConcurrentDial;
 use fnv::FnvHashMap;
@@ -113,9 +115,25 @@ where
     /// See [`Connection::max_negotiating_inbound_streams`].
     max_negotiating_inbound_streams: usize,
 
+    /// The window after establishment to estimate the bandwidth of connections in, if any.
+    bandwidth_estimation_window: Option<Duration>,
+
+    /// Whether new streams are reported once negotiated.
+    stream_opened_events: bool,
+
+    /// The protocols each connected peer is known to support, if outbound streams select
+    /// them optimistically.
+    protocol_caches: Option<FnvHashMap<PeerId, ProtocolCache>>,
+
     /// How many [`task::EstablishedConnectionEvent`]s can be buffered before the connection is back-pressured.
     per_connection_event_buffer_size: usize,
 
+    /// The saturation of the channels between the pool and the connection tasks.
+    channel_stats: ChannelStats,
+
+    /// The number and age of the pending connections.
+    pending_stats: PendingStats,
+
     /// The executor to use for running connection tasks. Can either be a global executor
     /// or a local queue.
     executor: ExecSwitch,
@@ -137,6 +155,12 @@ where
     /// Receivers for [`NewConnection`] objects that are dropped.
     new_connection_dropped_listeners: FuturesUnordered<oneshot::Receiver<StreamMuxerBox>>,
 
+    /// The sequence number of the next connection lifecycle event.
+    next_event_sequence: u64,
+
+    /// Until when new outbound streams to a peer are refused, see [`Pool::ban_streams`].
+    stream_bans: FnvHashMap<PeerId, Instant>,
+
     /// How long a connection should be kept alive once it starts idling.
     idle_connection_timeout: Duration,
 }
@@ -146,6 +170,8 @@ pub(crate) struct EstablishedConnection<TInEvent> {
     endpoint: ConnectedPoint,
     /// Channel endpoint to send commands to the task.
     sender: mpsc::Sender<task::Command<TInEvent>>,
+    /// View of the active streams of the connection by protocol.
+    stream_usage: StreamUsage,
 }
 
 impl<TInEvent> EstablishedConnection<TInEvent> {
@@ -180,6 +206,11 @@ impl<TInEvent> EstablishedConnection<TInEvent> {
         self.sender.poll_ready(cx).map_err(|_| ())
     }
 
+    /// Returns a view of the active streams of the connection by protocol.
+    pub(crate) fn stream_usage(&self) -> &StreamUsage {
+        &self.stream_usage
+    }
+
     /// Initiates a graceful close of the connection.
     ///
     /// Has no effect if the connection is already closing.
@@ -191,6 +222,30 @@ impl<TInEvent> EstablishedConnection<TInEvent> {
             Err(e) => assert!(e.is_disconnected(), "No capacity for close command."),
         };
     }
+
+    /// Refuses new outbound streams on the connection until the given instant.
+    ///
+    /// Has no effect if the connection is already closing.
+    pub(crate) fn ban_streams(&mut self, until: Instant) {
+        // Clone the sender so that we are guaranteed to have
+        // capacity for the command (every sender gets a slot).
+        let _ = self
+            .sender
+            .clone()
+            .try_send(task::Command::BanStreams(until));
+    }
+
+    /// Asks all streams negotiated for the given protocol on the connection to close.
+    ///
+    /// Has no effect if the connection is already closing.
+    pub(crate) fn close_streams(&mut self, protocol: StreamProtocol) {
+        // Clone the sender so that we are guaranteed to have
+        // capacity for the command (every sender gets a slot).
+        let _ = self
+            .sender
+            .clone()
+            .try_send(task::Command::CloseStreams(protocol));
+    }
 }
 
 struct PendingConnection {
@@ -239,6 +294,10 @@ pub(crate) enum PoolEvent<ToBehaviour> {
         concurrent_dial_errors: Option<Vec<(Multiaddr, TransportError<std::io::Error>)>>,
         /// How long it took to establish this connection.
         established_in: std::time::Duration,
+        /// The sequence number of this connection lifecycle event.
+        sequence: u64,
+        /// When the connection was established.
+        timestamp: Instant,
     },
 
     /// An established connection was closed.
@@ -261,6 +320,10 @@ pub(crate) enum PoolEvent<ToBehaviour> {
         error: Option<ConnectionError>,
         /// The remaining established connections to the same peer.
         remaining_established_connection_ids: Vec<ConnectionId>,
+        /// The sequence number of this connection lifecycle event.
+        sequence: u64,
+        /// When the connection was closed.
+        timestamp: Instant,
     },
 
     /// An outbound connection attempt failed.
@@ -302,6 +365,29 @@ pub(crate) enum PoolEvent<ToBehaviour> {
         /// The old endpoint.
         old_endpoint: ConnectedPoint,
     },
+
+    /// A stream has been negotiated on a connection.
+    StreamOpened {
+        id: ConnectionId,
+        peer_id: PeerId,
+        protocol: String,
+        sequence: u64,
+        timestamp: Instant,
+    },
+
+    /// All streams of a protocol asked to close on a connection are closed.
+    StreamsClosed {
+        id: ConnectionId,
+        peer_id: PeerId,
+        protocol: StreamProtocol,
+    },
+
+    /// The bandwidth of a connection has been estimated.
+    BandwidthEstimated {
+        id: ConnectionId,
+        peer_id: PeerId,
+        estimate: BandwidthEstimate,
+    },
 }
 
 impl<THandler> Pool<THandler>
@@ -310,7 +396,8 @@ where
 {
     /// Creates a new empty `Pool`.
     pub(crate) fn new(local_id: PeerId, config: PoolConfig) -> Self {
-        let (pending_connection_events_tx, pending_connection_events_rx) = mpsc::channel(0);
+        let (pending_connection_events_tx, pending_connection_events_rx) =
+            mpsc::channel(config.pending_connection_event_buffer_size);
         let executor = match config.executor {
             Some(exec) => ExecSwitch::Executor(exec),
             None => ExecSwitch::LocalSpawn(Default::default()),
@@ -324,7 +411,14 @@ where
             dial_concurrency_factor: config.dial_concurrency_factor,
             substream_upgrade_protocol_override: config.substream_upgrade_protocol_override,
             max_negotiating_inbound_streams: config.max_negotiating_inbound_streams,
+            bandwidth_estimation_window: config.bandwidth_estimation_window,
+            stream_opened_events: config.stream_opened_events,
+            protocol_caches: config
+                .optimistic_protocol_selection
+                .then(FnvHashMap::default),
             per_connection_event_buffer_size: config.per_connection_event_buffer_size,
+            channel_stats: ChannelStats::default(),
+            pending_stats: PendingStats::default(),
             idle_connection_timeout: config.idle_connection_timeout,
             executor,
             pending_connection_events_tx,
@@ -332,14 +426,35 @@ where
             no_established_connections_waker: None,
             established_connection_events: Default::default(),
             new_connection_dropped_listeners: Default::default(),
+            next_event_sequence: 0,
+            stream_bans: Default::default(),
         }
     }
 
+    /// Returns the sequence number for the next connection lifecycle event.
+    ///
+    /// Sequence numbers are strictly increasing across all connections of the pool.
+    fn next_event_sequence(&mut self) -> u64 {
+        let sequence = self.next_event_sequence;
+        self.next_event_sequence += 1;
+        sequence
+    }
+
     /// Gets the dedicated connection counters.
     pub(crate) fn counters(&self) -> &ConnectionCounters {
         &self.counters
     }
 
+    /// Gets the saturation statistics of the channels to and from the connection tasks.
+    pub(crate) fn channel_stats(&self) -> &ChannelStats {
+        &self.channel_stats
+    }
+
+    /// Gets the number and age of the pending connections.
+    pub(crate) fn pending_stats(&self) -> &PendingStats {
+        &self.pending_stats
+    }
+
     /// Gets an established connection from the pool by ID.
     pub(crate) fn get_established(
         &mut self,
@@ -350,6 +465,14 @@ where
             .find_map(|connections| connections.get_mut(&id))
     }
 
+    /// Returns a view of the active streams of an established connection by protocol.
+    pub(crate) fn stream_usage(&self, id: ConnectionId) -> Option<&StreamUsage> {
+        self.established
+            .values()
+            .find_map(|connections| connections.get(&id))
+            .map(|conn| conn.stream_usage())
+    }
+
     /// Returns true if we are connected to the given peer.
     ///
     /// This will return true only after a `NodeReached` event has been produced by `poll()`.
@@ -363,6 +486,36 @@ where
         self.established.len()
     }
 
+    /// Refuses new outbound streams on all current and future connections
+    /// to the given peer until the given instant.
+    ///
+    /// An instant that has already elapsed lifts a previous ban.
+    pub(crate) fn ban_streams(&mut self, peer: PeerId, until: Instant) {
+        let now = Instant::now();
+        self.stream_bans.retain(|_, until| *until > now);
+        if until > now {
+            self.stream_bans.insert(peer, until);
+        }
+
+        if let Some(conns) = self.established.get_mut(&peer) {
+            for (_, conn) in conns.iter_mut() {
+                conn.ban_streams(until);
+            }
+        }
+    }
+
+    /// Asks all streams negotiated for the given protocol on all established
+    /// connections to close.
+    pub(crate) fn close_streams(&mut self, protocol: &StreamProtocol) {
+        for conn in self
+            .established
+            .values_mut()
+            .flat_map(|conns| conns.values_mut())
+        {
+            conn.close_streams(protocol.clone());
+        }
+    }
+
     /// (Forcefully) close all connections to the given peer.
     ///
     /// All connections to the peer, whether pending or established are
@@ -439,20 +592,24 @@ where
                 ConcurrentDial::new(dials, concurrency_factor),
                 abort_receiver,
                 self.pending_connection_events_tx.clone(),
+                self.channel_stats.clone(),
             )
             .instrument(span),
         );
 
         let endpoint = PendingPoint::Dialer { role_override };
+        let accepted_at = Instant::now();
 
         self.counters.inc_pending(&endpoint);
+        self.pending_stats
+            .on_added(connection_id, false, accepted_at);
         self.pending.insert(
             connection_id,
             PendingConnection {
                 peer_id: peer,
                 endpoint,
                 abort_notifier: Some(abort_notifier),
-                accepted_at: Instant::now(),
+                accepted_at,
             },
         );
     }
@@ -480,18 +637,23 @@ where
                 future,
                 abort_receiver,
                 self.pending_connection_events_tx.clone(),
+                self.channel_stats.clone(),
             )
             .instrument(span),
         );
 
+        let accepted_at = Instant::now();
+
         self.counters.inc_pending_incoming();
+        self.pending_stats
+            .on_added(connection_id, true, accepted_at);
         self.pending.insert(
             connection_id,
             PendingConnection {
                 peer_id: None,
                 endpoint: endpoint.into(),
                 abort_notifier: Some(abort_notifier),
-                accepted_at: Instant::now(),
+                accepted_at,
             },
         );
     }
@@ -511,25 +673,38 @@ where
         let (command_sender, command_receiver) = mpsc::channel(self.task_command_buffer_size);
         let (event_sender, event_receiver) = mpsc::channel(self.per_connection_event_buffer_size);
 
+        let mut connection = Connection::new(
+            connection,
+            handler,
+            self.substream_upgrade_protocol_override,
+            self.max_negotiating_inbound_streams,
+            self.idle_connection_timeout,
+        );
+
         conns.insert(
             id,
             EstablishedConnection {
                 endpoint: endpoint.clone(),
                 sender: command_sender,
+                stream_usage: connection.stream_usage(),
             },
         );
         self.established_connection_events.push(event_receiver);
         if let Some(waker) = self.no_established_connections_waker.take() {
             waker.wake();
         }
-
-        let connection = Connection::new(
-            connection,
-            handler,
-            self.substream_upgrade_protocol_override,
-            self.max_negotiating_inbound_streams,
-            self.idle_connection_timeout,
-        );
+        if let Some(until) = self.stream_bans.get(&obtained_peer_id) {
+            connection.ban_streams(*until);
+        }
+        if let Some(window) = self.bandwidth_estimation_window {
+            connection.estimate_bandwidth(window);
+        }
+        if self.stream_opened_events {
+            connection.report_opened_streams();
+        }
+        if let Some(caches) = self.protocol_caches.as_mut() {
+            connection.cache_protocols(caches.entry(obtained_peer_id).or_default().clone());
+        }
 
         let span = tracing::debug_span!(parent: tracing::Span::none(), "new_established_connection", remote_addr = %endpoint.get_remote_address(), %id, peer = %obtained_peer_id);
         span.follows_from(tracing::Span::current());
@@ -541,6 +716,7 @@ where
                 connection,
                 command_receiver,
                 event_sender,
+                self.channel_stats.clone(),
             )
             .instrument(span),
         )
@@ -566,6 +742,41 @@ where
             Poll::Ready(Some(task::EstablishedConnectionEvent::Notify { id, peer_id, event })) => {
                 return Poll::Ready(PoolEvent::ConnectionEvent { peer_id, id, event });
             }
+            Poll::Ready(Some(task::EstablishedConnectionEvent::BandwidthEstimated {
+                id,
+                peer_id,
+                estimate,
+            })) => {
+                return Poll::Ready(PoolEvent::BandwidthEstimated {
+                    id,
+                    peer_id,
+                    estimate,
+                });
+            }
+            Poll::Ready(Some(task::EstablishedConnectionEvent::StreamOpened {
+                id,
+                peer_id,
+                protocol,
+            })) => {
+                return Poll::Ready(PoolEvent::StreamOpened {
+                    id,
+                    peer_id,
+                    protocol,
+                    sequence: self.next_event_sequence(),
+                    timestamp: Instant::now(),
+                });
+            }
+            Poll::Ready(Some(task::EstablishedConnectionEvent::StreamsClosed {
+                id,
+                peer_id,
+                protocol,
+            })) => {
+                return Poll::Ready(PoolEvent::StreamsClosed {
+                    id,
+                    peer_id,
+                    protocol,
+                });
+            }
             Poll::Ready(Some(task::EstablishedConnectionEvent::AddressChange {
                 id,
                 peer_id,
@@ -601,12 +812,17 @@ where
                     connections.keys().cloned().collect();
                 if remaining_established_connection_ids.is_empty() {
                     self.established.remove(&peer_id);
+                    if let Some(caches) = self.protocol_caches.as_mut() {
+                        caches.remove(&peer_id);
+                    }
                 }
                 return Poll::Ready(PoolEvent::ConnectionClosed {
                     id,
                     connected: Connected { endpoint, peer_id },
                     error,
                     remaining_established_connection_ids,
+                    sequence: self.next_event_sequence(),
+                    timestamp: Instant::now(),
                 });
             }
         }
@@ -647,6 +863,7 @@ where
                         .expect("Entry in `self.pending` for previously pending connection.");
 
                     self.counters.dec_pending(&endpoint);
+                    self.pending_stats.on_removed(id);
 
                     let (endpoint, concurrent_dial_errors) = match (endpoint, outgoing) {
                         (PendingPoint::Dialer { role_override }, Some((address, errors))) => (
@@ -744,6 +961,8 @@ where
                         connection,
                         concurrent_dial_errors,
                         established_in,
+                        sequence: self.next_event_sequence(),
+                        timestamp: Instant::now(),
                     });
                 }
                 task::PendingConnectionEvent::PendingFailed { id, error } => {
@@ -755,6 +974,7 @@ where
                     }) = self.pending.remove(&id)
                     {
                         self.counters.dec_pending(&endpoint);
+                        self.pending_stats.on_removed(id);
 
                         match (endpoint, error) {
                             (PendingPoint::Dialer { .. }, Either::Left(error)) => {
@@ -957,6 +1177,9 @@ pub(crate) struct PoolConfig {
     /// Size of the pending connection task event buffer and the established connection task event
     /// buffer.
     pub(crate) per_connection_event_buffer_size: usize,
+    /// Number of buffered events of pending connections (beyond a guaranteed
+    /// buffer of 1 event per pending connection).
+    pub(crate) pending_connection_event_buffer_size: usize,
     /// Number of addresses concurrently dialed for a single outbound connection attempt.
     pub(crate) dial_concurrency_factor: NonZeroU8,
     /// How long a connection should be kept alive once it is idling.
@@ -968,6 +1191,15 @@ pub(crate) struct PoolConfig {
     ///
     /// See [`Connection::max_negotiating_inbound_streams`].
     max_negotiating_inbound_streams: usize,
+
+    /// The window after establishment to estimate the bandwidth of connections in, if any.
+    bandwidth_estimation_window: Option<Duration>,
+
+    /// Whether outbound streams select protocols the remote is known to support optimistically.
+    optimistic_protocol_selection: bool,
+
+    /// Whether new streams are reported once negotiated.
+    stream_opened_events: bool,
 }
 
 impl PoolConfig {
@@ -976,10 +1208,14 @@ impl PoolConfig {
             executor,
             task_command_buffer_size: 32,
             per_connection_event_buffer_size: 7,
+            pending_connection_event_buffer_size: 0,
             dial_concurrency_factor: NonZeroU8::new(8).expect("8 > 0"),
             idle_connection_timeout: Duration::ZERO,
             substream_upgrade_protocol_override: None,
             max_negotiating_inbound_streams: 128,
+            bandwidth_estimation_window: None,
+            optimistic_protocol_selection: false,
+            stream_opened_events: false,
         }
     }
 
@@ -1006,6 +1242,13 @@ impl PoolConfig {
         self
     }
 
+    /// Sets the maximum number of buffered events of pending connections (beyond a
+    /// guaranteed buffer of 1 event per pending connection).
+    pub(crate) fn with_pending_connection_event_buffer_size(mut self, n: usize) -> Self {
+        self.pending_connection_event_buffer_size = n;
+        self
+    }
+
     /// Number of addresses concurrently dialed for a single outbound connection attempt.
     pub(crate) fn with_dial_concurrency_factor(mut self, factor: NonZeroU8) -> Self {
         self.dial_concurrency_factor = factor;
@@ -1028,4 +1271,22 @@ impl PoolConfig {
         self.max_negotiating_inbound_streams = v;
         self
     }
+
+    /// Estimates the bandwidth of new connections within the given window after establishment.
+    pub(crate) fn with_bandwidth_estimation(mut self, window: Duration) -> Self {
+        self.bandwidth_estimation_window = Some(window);
+        self
+    }
+
+    /// Selects protocols the remote is known to support optimistically on outbound streams.
+    pub(crate) fn with_optimistic_protocol_selection(mut self, enabled: bool) -> Self {
+        self.optimistic_protocol_selection = enabled;
+        self
+    }
+
+    /// Reports new streams once negotiated.
+    pub(crate) fn with_stream_opened_events(mut self, enabled: bool) -> Self {
+        self.stream_opened_events = enabled;
+        self
+    }
 }
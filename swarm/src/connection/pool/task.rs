@@ -24,19 +24,21 @@
 use super::concurrent_dial::ConcurrentDial;
 use crate::{
     connection::{
-        self, ConnectionError, ConnectionId, PendingInboundConnectionError,
+        self, BandwidthEstimate, ConnectionError, ConnectionId, PendingInboundConnectionError,
         PendingOutboundConnectionError,
     },
     transport::TransportError,
-    ConnectionHandler, Multiaddr, PeerId,
+    ChannelStats, ConnectionHandler, Multiaddr, PeerId, StreamProtocol,
 };
 use futures::{
     channel::{mpsc, oneshot},
     future::{poll_fn, Either, Future},
     SinkExt, StreamExt,
 };
+use instant::Instant;
 use libp2p_core::muxing::StreamMuxerBox;
 use std::pin::Pin;
+use std::time::Duration;
 use void::Void;
 
 /// Commands that can be sent to a task driving an established connection.
@@ -47,6 +49,10 @@ pub(crate) enum Command<T> {
     /// Gracefully close the connection (active close) before
     /// terminating the task.
     Close,
+    /// Refuse new outbound streams until the given instant.
+    BanStreams(Instant),
+    /// Close all streams negotiated for the given protocol.
+    CloseStreams(StreamProtocol),
 }
 
 pub(crate) enum PendingConnectionEvent {
@@ -73,6 +79,24 @@ pub(crate) enum EstablishedConnectionEvent<ToBehaviour> {
         peer_id: PeerId,
         new_address: Multiaddr,
     },
+    /// A stream has been negotiated on the connection.
+    StreamOpened {
+        id: ConnectionId,
+        peer_id: PeerId,
+        protocol: String,
+    },
+    /// All streams of a protocol asked to close are closed.
+    StreamsClosed {
+        id: ConnectionId,
+        peer_id: PeerId,
+        protocol: StreamProtocol,
+    },
+    /// The bandwidth of the connection has been estimated.
+    BandwidthEstimated {
+        id: ConnectionId,
+        peer_id: PeerId,
+        estimate: BandwidthEstimate,
+    },
     /// Notify the manager of an event from the connection.
     Notify {
         id: ConnectionId,
@@ -95,33 +119,43 @@ pub(crate) async fn new_for_pending_outgoing_connection(
     dial: ConcurrentDial,
     abort_receiver: oneshot::Receiver<Void>,
     mut events: mpsc::Sender<PendingConnectionEvent>,
+    channel_stats: ChannelStats,
 ) {
     match futures::future::select(abort_receiver, Box::pin(dial)).await {
         Either::Left((Err(oneshot::Canceled), _)) => {
-            let _ = events
-                .send(PendingConnectionEvent::PendingFailed {
+            send(
+                &mut events,
+                PendingConnectionEvent::PendingFailed {
                     id: connection_id,
                     error: Either::Left(PendingOutboundConnectionError::Aborted),
-                })
-                .await;
+                },
+                |blocked| channel_stats.record_pending_connection_events(blocked),
+            )
+            .await;
         }
         Either::Left((Ok(v), _)) => void::unreachable(v),
         Either::Right((Ok((address, output, errors)), _)) => {
-            let _ = events
-                .send(PendingConnectionEvent::ConnectionEstablished {
+            send(
+                &mut events,
+                PendingConnectionEvent::ConnectionEstablished {
                     id: connection_id,
                     output,
                     outgoing: Some((address, errors)),
-                })
-                .await;
+                },
+                |blocked| channel_stats.record_pending_connection_events(blocked),
+            )
+            .await;
         }
         Either::Right((Err(e), _)) => {
-            let _ = events
-                .send(PendingConnectionEvent::PendingFailed {
+            send(
+                &mut events,
+                PendingConnectionEvent::PendingFailed {
                     id: connection_id,
                     error: Either::Left(PendingOutboundConnectionError::Transport(e)),
-                })
-                .await;
+                },
+                |blocked| channel_stats.record_pending_connection_events(blocked),
+            )
+            .await;
         }
     }
 }
@@ -131,37 +165,47 @@ pub(crate) async fn new_for_pending_incoming_connection<TFut>(
     future: TFut,
     abort_receiver: oneshot::Receiver<Void>,
     mut events: mpsc::Sender<PendingConnectionEvent>,
+    channel_stats: ChannelStats,
 ) where
     TFut: Future<Output = Result<(PeerId, StreamMuxerBox), std::io::Error>> + Send + 'static,
 {
     match futures::future::select(abort_receiver, Box::pin(future)).await {
         Either::Left((Err(oneshot::Canceled), _)) => {
-            let _ = events
-                .send(PendingConnectionEvent::PendingFailed {
+            send(
+                &mut events,
+                PendingConnectionEvent::PendingFailed {
                     id: connection_id,
                     error: Either::Right(PendingInboundConnectionError::Aborted),
-                })
-                .await;
+                },
+                |blocked| channel_stats.record_pending_connection_events(blocked),
+            )
+            .await;
         }
         Either::Left((Ok(v), _)) => void::unreachable(v),
         Either::Right((Ok(output), _)) => {
-            let _ = events
-                .send(PendingConnectionEvent::ConnectionEstablished {
+            send(
+                &mut events,
+                PendingConnectionEvent::ConnectionEstablished {
                     id: connection_id,
                     output,
                     outgoing: None,
-                })
-                .await;
+                },
+                |blocked| channel_stats.record_pending_connection_events(blocked),
+            )
+            .await;
         }
         Either::Right((Err(e), _)) => {
-            let _ = events
-                .send(PendingConnectionEvent::PendingFailed {
+            send(
+                &mut events,
+                PendingConnectionEvent::PendingFailed {
                     id: connection_id,
                     error: Either::Right(PendingInboundConnectionError::Transport(
                         TransportError::Other(e),
                     )),
-                })
-                .await;
+                },
+                |blocked| channel_stats.record_pending_connection_events(blocked),
+            )
+            .await;
         }
     }
 }
@@ -172,6 +216,7 @@ pub(crate) async fn new_for_established_connection<THandler>(
     mut connection: crate::connection::Connection<THandler>,
     mut command_receiver: mpsc::Receiver<Command<THandler::FromBehaviour>>,
     mut events: mpsc::Sender<EstablishedConnectionEvent<THandler::ToBehaviour>>,
+    channel_stats: ChannelStats,
 ) where
     THandler: ConnectionHandler,
 {
@@ -184,6 +229,8 @@ pub(crate) async fn new_for_established_connection<THandler>(
         {
             Either::Left((Some(command), _)) => match command {
                 Command::NotifyHandler(event) => connection.on_behaviour_event(event),
+                Command::BanStreams(until) => connection.ban_streams(until),
+                Command::CloseStreams(protocol) => connection.close_streams(protocol),
                 Command::Close => {
                     command_receiver.close();
                     let (remaining_events, closing_muxer) = connection.close();
@@ -200,13 +247,16 @@ pub(crate) async fn new_for_established_connection<THandler>(
 
                     let error = closing_muxer.await.err().map(ConnectionError::IO);
 
-                    let _ = events
-                        .send(EstablishedConnectionEvent::Closed {
+                    send(
+                        &mut events,
+                        EstablishedConnectionEvent::Closed {
                             id: connection_id,
                             peer_id,
                             error,
-                        })
-                        .await;
+                        },
+                        |blocked| channel_stats.record_connection_events(blocked),
+                    )
+                    .await;
                     return;
                 }
             },
@@ -217,22 +267,64 @@ pub(crate) async fn new_for_established_connection<THandler>(
             Either::Right((event, _)) => {
                 match event {
                     Ok(connection::Event::Handler(event)) => {
-                        let _ = events
-                            .send(EstablishedConnectionEvent::Notify {
+                        send(
+                            &mut events,
+                            EstablishedConnectionEvent::Notify {
                                 id: connection_id,
                                 peer_id,
                                 event,
-                            })
-                            .await;
+                            },
+                            |blocked| channel_stats.record_connection_events(blocked),
+                        )
+                        .await;
                     }
                     Ok(connection::Event::AddressChange(new_address)) => {
-                        let _ = events
-                            .send(EstablishedConnectionEvent::AddressChange {
+                        send(
+                            &mut events,
+                            EstablishedConnectionEvent::AddressChange {
                                 id: connection_id,
                                 peer_id,
                                 new_address,
-                            })
-                            .await;
+                            },
+                            |blocked| channel_stats.record_connection_events(blocked),
+                        )
+                        .await;
+                    }
+                    Ok(connection::Event::BandwidthEstimated(estimate)) => {
+                        send(
+                            &mut events,
+                            EstablishedConnectionEvent::BandwidthEstimated {
+                                id: connection_id,
+                                peer_id,
+                                estimate,
+                            },
+                            |blocked| channel_stats.record_connection_events(blocked),
+                        )
+                        .await;
+                    }
+                    Ok(connection::Event::StreamOpened(protocol)) => {
+                        send(
+                            &mut events,
+                            EstablishedConnectionEvent::StreamOpened {
+                                id: connection_id,
+                                peer_id,
+                                protocol,
+                            },
+                            |blocked| channel_stats.record_connection_events(blocked),
+                        )
+                        .await;
+                    }
+                    Ok(connection::Event::StreamsClosed(protocol)) => {
+                        send(
+                            &mut events,
+                            EstablishedConnectionEvent::StreamsClosed {
+                                id: connection_id,
+                                peer_id,
+                                protocol,
+                            },
+                            |blocked| channel_stats.record_connection_events(blocked),
+                        )
+                        .await;
                     }
                     Err(error) => {
                         command_receiver.close();
@@ -249,13 +341,16 @@ pub(crate) async fn new_for_established_connection<THandler>(
                             .await;
 
                         // Terminate the task with the error, dropping the connection.
-                        let _ = events
-                            .send(EstablishedConnectionEvent::Closed {
+                        send(
+                            &mut events,
+                            EstablishedConnectionEvent::Closed {
                                 id: connection_id,
                                 peer_id,
                                 error: Some(error),
-                            })
-                            .await;
+                            },
+                            |blocked| channel_stats.record_connection_events(blocked),
+                        )
+                        .await;
                         return;
                     }
                 }
@@ -263,3 +358,53 @@ pub(crate) async fn new_for_established_connection<THandler>(
         }
     }
 }
+
+/// Sends the event to the [`Pool`](super::Pool), reporting the time spent waiting
+/// if the channel is at capacity.
+async fn send<T>(events: &mut mpsc::Sender<T>, event: T, on_blocked: impl FnOnce(Duration)) {
+    let event = match events.try_send(event) {
+        Ok(()) => return,
+        Err(e) if e.is_full() => e.into_inner(),
+        // The pool has been dropped.
+        Err(_) => return,
+    };
+
+    let started = Instant::now();
+    let _ = events.send(event).await;
+    on_blocked(started.elapsed());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures_timer::Delay;
+
+    #[test]
+    fn send_records_time_blocked_on_full_channel() {
+        let stats = ChannelStats::default();
+        let (mut tx, mut rx) = mpsc::channel::<u8>(0);
+
+        // The guaranteed slot of the sender is free.
+        block_on(send(&mut tx, 1, |blocked| {
+            stats.record_connection_events(blocked)
+        }));
+        assert_eq!(stats.connection_events().num_blocked, 0);
+
+        block_on(futures::future::join(
+            send(&mut tx, 2, |blocked| {
+                stats.record_connection_events(blocked)
+            }),
+            async {
+                Delay::new(Duration::from_millis(50)).await;
+                assert_eq!(rx.next().await, Some(1));
+                assert_eq!(rx.next().await, Some(2));
+            },
+        ));
+
+        let saturation = stats.connection_events();
+        assert_eq!(saturation.num_blocked, 1);
+        assert!(saturation.blocked_duration >= Duration::from_millis(50));
+        assert_eq!(stats.notify_handler().num_blocked, 0);
+    }
+}
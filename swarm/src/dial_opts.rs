@@ -44,6 +44,7 @@ pub struct DialOpts {
     extend_addresses_through_behaviour: bool,
     role_override: Endpoint,
     dial_concurrency_factor_override: Option<NonZeroU8>,
+    local_address: Option<Multiaddr>,
     connection_id: ConnectionId,
 }
 
@@ -65,6 +66,7 @@ impl DialOpts {
             condition: Default::default(),
             role_override: Endpoint::Dialer,
             dial_concurrency_factor_override: Default::default(),
+            local_address: None,
         }
     }
 
@@ -124,6 +126,10 @@ impl DialOpts {
     pub(crate) fn role_override(&self) -> Endpoint {
         self.role_override
     }
+
+    pub(crate) fn local_address(&self) -> Option<&Multiaddr> {
+        self.local_address.as_ref()
+    }
 }
 
 impl From<Multiaddr> for DialOpts {
@@ -144,6 +150,7 @@ pub struct WithPeerId {
     condition: PeerCondition,
     role_override: Endpoint,
     dial_concurrency_factor_override: Option<NonZeroU8>,
+    local_address: Option<Multiaddr>,
 }
 
 impl WithPeerId {
@@ -169,6 +176,7 @@ impl WithPeerId {
             extend_addresses_through_behaviour: false,
             role_override: self.role_override,
             dial_concurrency_factor_override: self.dial_concurrency_factor_override,
+            local_address: self.local_address,
         }
     }
 
@@ -183,6 +191,19 @@ impl WithPeerId {
         self
     }
 
+    /// Bind the outgoing connection to the given local address, e.g. `/ip4/192.168.1.2`.
+    ///
+    /// This selects the network interface or source address used on multi-homed hosts.
+    /// A port may be appended to the IP address, e.g. `/ip4/192.168.1.2/tcp/4001`.
+    /// Dialing fails if the transport does not support binding to a local address or if the
+    /// local address can not reach the remote, e.g. because it is of a different IP family.
+    ///
+    /// See [`Transport::dial_from`](libp2p_core::Transport::dial_from).
+    pub fn local_address(mut self, local_address: Multiaddr) -> Self {
+        self.local_address = Some(local_address);
+        self
+    }
+
     /// Build the final [`DialOpts`].
     pub fn build(self) -> DialOpts {
         DialOpts {
@@ -192,6 +213,7 @@ impl WithPeerId {
             extend_addresses_through_behaviour: true,
             role_override: self.role_override,
             dial_concurrency_factor_override: self.dial_concurrency_factor_override,
+            local_address: self.local_address,
             connection_id: ConnectionId::next(),
         }
     }
@@ -205,6 +227,7 @@ pub struct WithPeerIdWithAddresses {
     extend_addresses_through_behaviour: bool,
     role_override: Endpoint,
     dial_concurrency_factor_override: Option<NonZeroU8>,
+    local_address: Option<Multiaddr>,
 }
 
 impl WithPeerIdWithAddresses {
@@ -239,6 +262,19 @@ impl WithPeerIdWithAddresses {
         self
     }
 
+    /// Bind the outgoing connection to the given local address, e.g. `/ip4/192.168.1.2`.
+    ///
+    /// This selects the network interface or source address used on multi-homed hosts.
+    /// A port may be appended to the IP address, e.g. `/ip4/192.168.1.2/tcp/4001`.
+    /// Dialing fails if the transport does not support binding to a local address or if the
+    /// local address can not reach the remote, e.g. because it is of a different IP family.
+    ///
+    /// See [`Transport::dial_from`](libp2p_core::Transport::dial_from).
+    pub fn local_address(mut self, local_address: Multiaddr) -> Self {
+        self.local_address = Some(local_address);
+        self
+    }
+
     /// Build the final [`DialOpts`].
     pub fn build(self) -> DialOpts {
         DialOpts {
@@ -248,6 +284,7 @@ impl WithPeerIdWithAddresses {
             extend_addresses_through_behaviour: self.extend_addresses_through_behaviour,
             role_override: self.role_override,
             dial_concurrency_factor_override: self.dial_concurrency_factor_override,
+            local_address: self.local_address,
             connection_id: ConnectionId::next(),
         }
     }
@@ -262,6 +299,7 @@ impl WithoutPeerId {
         WithoutPeerIdWithAddress {
             address,
             role_override: Endpoint::Dialer,
+            local_address: None,
         }
     }
 }
@@ -270,6 +308,7 @@ impl WithoutPeerId {
 pub struct WithoutPeerIdWithAddress {
     address: Multiaddr,
     role_override: Endpoint,
+    local_address: Option<Multiaddr>,
 }
 
 impl WithoutPeerIdWithAddress {
@@ -283,6 +322,20 @@ impl WithoutPeerIdWithAddress {
         self.role_override = Endpoint::Listener;
         self
     }
+
+    /// Bind the outgoing connection to the given local address, e.g. `/ip4/192.168.1.2`.
+    ///
+    /// This selects the network interface or source address used on multi-homed hosts.
+    /// A port may be appended to the IP address, e.g. `/ip4/192.168.1.2/tcp/4001`.
+    /// Dialing fails if the transport does not support binding to a local address or if the
+    /// local address can not reach the remote, e.g. because it is of a different IP family.
+    ///
+    /// See [`Transport::dial_from`](libp2p_core::Transport::dial_from).
+    pub fn local_address(mut self, local_address: Multiaddr) -> Self {
+        self.local_address = Some(local_address);
+        self
+    }
+
     /// Build the final [`DialOpts`].
     pub fn build(self) -> DialOpts {
         DialOpts {
@@ -292,6 +345,7 @@ impl WithoutPeerIdWithAddress {
             extend_addresses_through_behaviour: false,
             role_override: self.role_override,
             dial_concurrency_factor_override: None,
+            local_address: self.local_address,
             connection_id: ConnectionId::next(),
         }
     }
@@ -55,8 +55,11 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+mod channel_stats;
 mod connection;
 mod executor;
+mod pending_stats;
+mod self_check;
 mod stream;
 mod stream_protocol;
 #[cfg(test)]
@@ -107,13 +110,17 @@ pub mod derive_prelude {
 }
 
 pub use behaviour::{
-    AddressChange, CloseConnection, ConnectionClosed, DialFailure, ExpiredListenAddr,
-    ExternalAddrExpired, ExternalAddresses, FromSwarm, ListenAddresses, ListenFailure,
-    ListenerClosed, ListenerError, NetworkBehaviour, NewExternalAddrCandidate,
-    NewExternalAddrOfPeer, NewListenAddr, NotifyHandler, PeerAddresses, ToSwarm,
+    AddressChange, BandwidthEstimated, CloseConnection, ConnectionClosed, ConnectionStreams,
+    DialFailure, ExpiredListenAddr, ExternalAddrExpired, ExternalAddresses, FromSwarm,
+    ListenAddresses, ListenFailure, ListenerClosed, ListenerError, NetworkBehaviour,
+    NewExternalAddrCandidate, NewExternalAddrOfPeer, NewListenAddr, NotifyHandler, PeerAddresses,
+    ToSwarm,
 };
+pub use channel_stats::{ChannelSaturation, ChannelStats};
 pub use connection::pool::ConnectionCounters;
-pub use connection::{ConnectionError, ConnectionId, SupportedProtocols};
+pub use connection::{
+    BandwidthEstimate, ConnectionError, ConnectionId, StreamBanned, SupportedProtocols,
+};
 pub use executor::Executor;
 pub use handler::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerSelect, OneShotHandler,
@@ -122,7 +129,8 @@ pub use handler::{
 #[cfg(feature = "macros")]
 pub use libp2p_swarm_derive::NetworkBehaviour;
 pub use listen_opts::ListenOpts;
-pub use stream::Stream;
+pub use pending_stats::{PendingConnections, PendingLimitExceeded, PendingStats};
+pub use stream::{ProtocolStats, Stream, StreamUsage};
 pub use stream_protocol::{InvalidProtocol, StreamProtocol};
 
 use crate::behaviour::ExternalAddrConfirmed;
@@ -134,13 +142,16 @@ use connection::{
 };
 use dial_opts::{DialOpts, PeerCondition};
 use futures::{prelude::*, stream::FusedStream};
+use instant::Instant;
 use libp2p_core::{
     connection::ConnectedPoint,
-    muxing::StreamMuxerBox,
+    muxing::{StreamMuxer, StreamMuxerBox},
     transport::{self, ListenerId, TransportError, TransportEvent},
+    upgrade::{InboundConnectionUpgrade, Negotiated},
     Endpoint, Multiaddr, Transport,
 };
 use libp2p_identity::PeerId;
+use self_check::{SelfCheck, Transition};
 use smallvec::SmallVec;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::num::{NonZeroU32, NonZeroU8, NonZeroUsize};
@@ -189,6 +200,14 @@ pub enum SwarmEvent<TBehaviourOutEvent> {
         concurrent_dial_errors: Option<Vec<(Multiaddr, TransportError<io::Error>)>>,
         /// How long it took to establish this connection
         established_in: std::time::Duration,
+        /// Sequence number of this connection lifecycle event.
+        ///
+        /// Sequence numbers of [`SwarmEvent::ConnectionEstablished`],
+        /// [`SwarmEvent::StreamOpened`] and [`SwarmEvent::ConnectionClosed`] events are
+        /// strictly increasing in the order the events occurred, but not necessarily contiguous.
+        sequence: u64,
+        /// When the connection was established.
+        timestamp: Instant,
     },
     /// A connection with the given peer has been closed,
     /// possibly as a result of an error.
@@ -204,6 +223,12 @@ pub enum SwarmEvent<TBehaviourOutEvent> {
         /// Reason for the disconnection, if it was not a successful
         /// active close.
         cause: Option<ConnectionError>,
+        /// Sequence number of this connection lifecycle event.
+        ///
+        /// See [`SwarmEvent::ConnectionEstablished::sequence`].
+        sequence: u64,
+        /// When the connection was closed.
+        timestamp: Instant,
     },
     /// A new connection arrived on a listener and is in the process of protocol negotiation.
     ///
@@ -300,6 +325,50 @@ pub enum SwarmEvent<TBehaviourOutEvent> {
     ExternalAddrExpired { address: Multiaddr },
     /// We have discovered a new address of a peer.
     NewExternalAddrOfPeer { peer_id: PeerId, address: Multiaddr },
+    /// A self-dial of a confirmed external address failed, i.e. the address is likely not
+    /// reachable from the outside, see [`Config::with_external_address_self_check`].
+    ///
+    /// Only reported once until the address recovers.
+    ExternalAddrDegraded {
+        address: Multiaddr,
+        /// Error of the failed self-dial.
+        error: DialError,
+    },
+    /// A self-dial of a previously degraded external address succeeded again.
+    ExternalAddrRecovered { address: Multiaddr },
+    /// The bandwidth of a connection has been estimated, see [`Config::with_bandwidth_estimation`].
+    BandwidthEstimated {
+        /// Identity of the peer of the connection.
+        peer_id: PeerId,
+        /// Identifier of the connection.
+        connection_id: ConnectionId,
+        /// The estimated bandwidth.
+        estimate: BandwidthEstimate,
+    },
+    /// A stream has been negotiated on a connection, see [`Config::with_stream_opened_events`].
+    StreamOpened {
+        /// Identity of the peer of the connection.
+        peer_id: PeerId,
+        /// Identifier of the connection.
+        connection_id: ConnectionId,
+        /// The negotiated protocol of the stream.
+        protocol: String,
+        /// Sequence number of this connection lifecycle event.
+        ///
+        /// See [`SwarmEvent::ConnectionEstablished::sequence`].
+        sequence: u64,
+        /// When the stream was opened.
+        timestamp: Instant,
+    },
+    /// All streams of a protocol on a connection have been closed, see [`Swarm::close_streams`].
+    StreamsClosed {
+        /// Identity of the peer of the connection.
+        peer_id: PeerId,
+        /// Identifier of the connection.
+        connection_id: ConnectionId,
+        /// The protocol whose streams have been closed.
+        protocol: StreamProtocol,
+    },
 }
 
 impl<TBehaviourOutEvent> SwarmEvent<TBehaviourOutEvent> {
@@ -347,7 +416,20 @@ where
     /// can be polled again.
     pending_handler_event: Option<(PeerId, PendingNotifyHandler, THandlerInEvent<TBehaviour>)>,
 
+    /// Since when the delivery of the `pending_handler_event` has been waiting for
+    /// capacity in the buffers of the connection(s), if at all.
+    pending_handler_event_blocked_since: Option<Instant>,
+
     pending_swarm_events: VecDeque<SwarmEvent<TBehaviour::ToSwarm>>,
+
+    /// Periodic self-dials of the confirmed external addresses, if enabled.
+    self_check: Option<SelfCheck>,
+
+    /// See [`Config::with_max_pending_outgoing`].
+    max_pending_outgoing: Option<u32>,
+
+    /// See [`Config::with_max_pending_incoming`].
+    max_pending_incoming: Option<u32>,
 }
 
 impl<TBehaviour> Unpin for Swarm<TBehaviour> where TBehaviour: NetworkBehaviour {}
@@ -375,7 +457,11 @@ where
             confirmed_external_addr: Default::default(),
             listened_addrs: HashMap::new(),
             pending_handler_event: None,
+            pending_handler_event_blocked_since: None,
             pending_swarm_events: VecDeque::default(),
+            self_check: config.external_address_self_check.map(SelfCheck::new),
+            max_pending_outgoing: config.max_pending_outgoing,
+            max_pending_incoming: config.max_pending_incoming,
         }
     }
 
@@ -389,6 +475,22 @@ where
         }
     }
 
+    /// Returns the saturation statistics of the channels between the [`Swarm`] and its
+    /// connections.
+    ///
+    /// The returned [`ChannelStats`] stay up to date with the [`Swarm`].
+    pub fn channel_stats(&self) -> ChannelStats {
+        self.pool.channel_stats().clone()
+    }
+
+    /// Returns the number and age of the pending connections, i.e. the outgoing connections
+    /// being dialed and the incoming connections being upgraded.
+    ///
+    /// The returned [`PendingStats`] stay up to date with the [`Swarm`].
+    pub fn pending_stats(&self) -> PendingStats {
+        self.pool.pending_stats().clone()
+    }
+
     /// Starts listening on the given address.
     /// Returns an error if the address is not supported.
     ///
@@ -466,6 +568,25 @@ where
             return Err(e);
         }
 
+        if let Some(limit) = self
+            .max_pending_outgoing
+            .filter(|limit| self.pool.counters().num_pending_outgoing() >= *limit)
+        {
+            let error = DialError::Denied {
+                cause: ConnectionDenied::new(PendingLimitExceeded::new(limit)),
+            };
+            self.pool.pending_stats().on_denied(false);
+
+            self.behaviour
+                .on_swarm_event(FromSwarm::DialFailure(DialFailure {
+                    peer_id,
+                    error: &error,
+                    connection_id,
+                }));
+
+            return Err(error);
+        }
+
         let addresses = {
             let mut addresses_from_opts = dial_opts.get_addresses();
 
@@ -528,12 +649,16 @@ where
             .into_iter()
             .map(|a| match peer_id.map_or(Ok(a.clone()), |p| a.with_p2p(p)) {
                 Ok(address) => {
-                    let (dial, span) = match dial_opts.role_override() {
-                        Endpoint::Dialer => (
+                    let (dial, span) = match (dial_opts.local_address(), dial_opts.role_override()) {
+                        (Some(local_address), role_override) => (
+                            self.transport.dial_from(address.clone(), local_address, role_override),
+                            tracing::debug_span!(parent: tracing::Span::none(), "Transport::dial_from", %address, %local_address),
+                        ),
+                        (None, Endpoint::Dialer) => (
                             self.transport.dial(address.clone()),
                             tracing::debug_span!(parent: tracing::Span::none(), "Transport::dial", %address),
                         ),
-                        Endpoint::Listener => (
+                        (None, Endpoint::Listener) => (
                             self.transport.dial_as_listener(address.clone()),
                             tracing::debug_span!(parent: tracing::Span::none(), "Transport::dial_as_listener", %address),
                         ),
@@ -567,6 +692,112 @@ where
         Ok(())
     }
 
+    /// Adds a connection that has been established and authenticated outside of the swarm,
+    /// e.g. a connection accepted by a separate TLS terminator or migrated from another process.
+    ///
+    /// Only the given muxer upgrade is negotiated on the socket, as the listener side of the
+    /// connection. The remote is trusted to be `peer_id` without further authentication.
+    /// Apart from that, the connection is handled like any other incoming connection, i.e. it
+    /// can be denied via [`NetworkBehaviour::handle_pending_inbound_connection`] and
+    /// [`NetworkBehaviour::handle_established_inbound_connection`], and is reported via
+    /// [`SwarmEvent::IncomingConnection`] followed by either
+    /// [`SwarmEvent::ConnectionEstablished`] or [`SwarmEvent::IncomingConnectionError`].
+    ///
+    /// `local_addr` and `send_back_addr` are the addresses of the connection as seen by the
+    /// external acceptor.
+    pub fn add_external_connection<C, U, M>(
+        &mut self,
+        socket: C,
+        peer_id: PeerId,
+        muxer: U,
+        local_addr: Multiaddr,
+        send_back_addr: Multiaddr,
+    ) -> Result<ConnectionId, ListenError>
+    where
+        C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        U: InboundConnectionUpgrade<Negotiated<C>, Output = M> + Send + 'static,
+        U::Info: Send,
+        <U::InfoIter as IntoIterator>::IntoIter: Send,
+        U::Future: Send,
+        U::Error: error::Error + Send + Sync + 'static,
+        M: StreamMuxer + Send + 'static,
+        M::Substream: Send + 'static,
+        M::Error: Send + Sync + 'static,
+    {
+        let upgrade = async move {
+            let (info, stream) =
+                multistream_select::listener_select_proto(socket, muxer.protocol_info())
+                    .await
+                    .map_err(io::Error::other)?;
+            let muxer = muxer
+                .upgrade_inbound(stream, info)
+                .await
+                .map_err(io::Error::other)?;
+            Ok((peer_id, StreamMuxerBox::new(muxer)))
+        };
+
+        let connection_id = ConnectionId::next();
+        self.add_incoming(connection_id, upgrade, local_addr, send_back_addr)?;
+        Ok(connection_id)
+    }
+
+    /// Adds a pending incoming connection to the pool, unless denied by the behaviour.
+    fn add_incoming<TFut>(
+        &mut self,
+        connection_id: ConnectionId,
+        upgrade: TFut,
+        local_addr: Multiaddr,
+        send_back_addr: Multiaddr,
+    ) -> Result<(), ListenError>
+    where
+        TFut: Future<Output = Result<(PeerId, StreamMuxerBox), io::Error>> + Send + 'static,
+    {
+        let denied = match self
+            .max_pending_incoming
+            .filter(|limit| self.pool.counters().num_pending_incoming() >= *limit)
+        {
+            Some(limit) => {
+                self.pool.pending_stats().on_denied(true);
+                Err(ConnectionDenied::new(PendingLimitExceeded::new(limit)))
+            }
+            None => self.behaviour.handle_pending_inbound_connection(
+                connection_id,
+                &local_addr,
+                &send_back_addr,
+            ),
+        };
+        if let Err(cause) = denied {
+            let listen_error = ListenError::Denied { cause };
+
+            self.behaviour
+                .on_swarm_event(FromSwarm::ListenFailure(ListenFailure {
+                    local_addr: &local_addr,
+                    send_back_addr: &send_back_addr,
+                    error: &listen_error,
+                    connection_id,
+                }));
+
+            return Err(listen_error);
+        }
+
+        self.pool.add_incoming(
+            upgrade,
+            IncomingInfo {
+                local_addr: &local_addr,
+                send_back_addr: &send_back_addr,
+            },
+            connection_id,
+        );
+
+        self.pending_swarm_events
+            .push_back(SwarmEvent::IncomingConnection {
+                connection_id,
+                local_addr,
+                send_back_addr,
+            });
+        Ok(())
+    }
+
     /// Returns an iterator that produces the list of addresses we're listening on.
     pub fn listeners(&self) -> impl Iterator<Item = &Multiaddr> {
         self.listened_addrs.values().flatten()
@@ -582,6 +813,53 @@ where
         self.confirmed_external_addr.iter()
     }
 
+    /// List of confirmed external addresses whose last self-check failed, see
+    /// [`Config::with_external_address_self_check`].
+    pub fn degraded_external_addresses(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.self_check.iter().flat_map(|c| c.degraded())
+    }
+
+    /// Dials each confirmed external address with the local peer ID, bypassing the
+    /// [`NetworkBehaviour`].
+    ///
+    /// The dial is expected to reach one of our own listeners and thus to fail with
+    /// [`DialError::LocalPeerId`].
+    fn start_self_checks(&mut self) {
+        let Some(self_check) = self.self_check.as_mut() else {
+            return;
+        };
+
+        for address in self.confirmed_external_addr.iter() {
+            if self_check.is_checking(address) {
+                continue;
+            }
+            let dial = match address.clone().with_p2p(self.local_peer_id) {
+                Ok(target) => match self.transport.dial(target.clone()) {
+                    Ok(fut) => fut
+                        .map(|r| (target, r.map_err(TransportError::Other)))
+                        .boxed(),
+                    Err(err) => futures::future::ready((target, Err(err))).boxed(),
+                },
+                Err(target) => futures::future::ready((
+                    target.clone(),
+                    Err(TransportError::MultiaddrNotSupported(target)),
+                ))
+                .boxed(),
+            };
+
+            let connection_id = ConnectionId::next();
+            tracing::debug!(%address, connection=%connection_id, "Self-checking external address");
+            self_check.on_dial(connection_id, address.clone());
+            self.pool.add_outgoing(
+                vec![dial],
+                Some(self.local_peer_id),
+                Endpoint::Dialer,
+                None,
+                connection_id,
+            );
+        }
+    }
+
     fn add_listener(&mut self, opts: ListenOpts) -> Result<(), TransportError<io::Error>> {
         let addr = opts.address();
         let listener_id = opts.listener_id();
@@ -623,6 +901,9 @@ where
         self.behaviour
             .on_swarm_event(FromSwarm::ExternalAddrExpired(ExternalAddrExpired { addr }));
         self.confirmed_external_addr.remove(addr);
+        if let Some(self_check) = self.self_check.as_mut() {
+            self_check.on_address_removed(addr);
+        }
     }
 
     /// Add a new external address of a remote peer.
@@ -654,6 +935,36 @@ where
         }
     }
 
+    /// Refuses new outbound streams to a peer for the given duration.
+    ///
+    /// Existing connections and streams to the peer are kept, but any [`ConnectionHandler`]
+    /// requesting a new outbound stream is immediately reported a [`StreamUpgradeError::Io`]
+    /// wrapping [`StreamBanned`]. The ban also applies to connections established while it lasts.
+    /// Inbound streams are not affected.
+    ///
+    /// Banning a peer again replaces the previous ban, thus a zero duration lifts a ban.
+    pub fn ban_streams(&mut self, peer_id: PeerId, duration: Duration) {
+        self.pool.ban_streams(peer_id, Instant::now() + duration);
+    }
+
+    /// Gracefully closes all streams negotiated for the given protocol on all established connections.
+    ///
+    /// Each affected stream closes its write side and then reports EOF on reads and
+    /// [`io::ErrorKind::BrokenPipe`] on writes, prompting its [`ConnectionHandler`] to drop it.
+    /// Once a connection has no more of these streams, a [`SwarmEvent::StreamsClosed`] event
+    /// is emitted for it. Streams negotiated after this call are not affected.
+    pub fn close_streams(&mut self, protocol: StreamProtocol) {
+        self.pool.close_streams(&protocol);
+    }
+
+    /// Returns a view of the active streams of an established connection by protocol.
+    ///
+    /// Returns `None` if the connection was not found or is no longer established.
+    /// Behaviours are handed the same view via [`FromSwarm::ConnectionStreams`].
+    pub fn stream_usage(&self, connection_id: ConnectionId) -> Option<StreamUsage> {
+        self.pool.stream_usage(connection_id).cloned()
+    }
+
     /// Attempt to gracefully close a connection.
     ///
     /// Closing a connection is asynchronous but this function will return immediately.
@@ -701,6 +1012,8 @@ where
                 connection,
                 concurrent_dial_errors,
                 established_in,
+                sequence,
+                timestamp,
             } => {
                 let handler = match endpoint.clone() {
                     ConnectedPoint::Dialer {
@@ -814,6 +1127,15 @@ where
                             other_established: other_established_connection_ids.len(),
                         },
                     ));
+                if let Some(usage) = self.pool.stream_usage(id).cloned() {
+                    self.behaviour.on_swarm_event(FromSwarm::ConnectionStreams(
+                        ConnectionStreams {
+                            peer_id,
+                            connection_id: id,
+                            usage: &usage,
+                        },
+                    ));
+                }
                 self.supported_protocols = supported_protocols;
                 self.pending_swarm_events
                     .push_back(SwarmEvent::ConnectionEstablished {
@@ -823,6 +1145,8 @@ where
                         endpoint,
                         concurrent_dial_errors,
                         established_in,
+                        sequence,
+                        timestamp,
                     });
             }
             PoolEvent::PendingOutboundConnectionError {
@@ -832,6 +1156,31 @@ where
             } => {
                 let error = error.into();
 
+                if let Some(self_check) = self
+                    .self_check
+                    .as_mut()
+                    .filter(|c| c.is_self_dial(connection_id))
+                {
+                    let result = match error {
+                        DialError::LocalPeerId { .. } => Ok(()),
+                        error => Err(error),
+                    };
+                    match self_check.on_dial_result(connection_id, result) {
+                        Some(Transition::Degraded { address, error }) => {
+                            tracing::debug!(%address, "External address degraded: {:?}", error);
+                            self.pending_swarm_events
+                                .push_back(SwarmEvent::ExternalAddrDegraded { address, error });
+                        }
+                        Some(Transition::Recovered { address }) => {
+                            tracing::debug!(%address, "External address recovered");
+                            self.pending_swarm_events
+                                .push_back(SwarmEvent::ExternalAddrRecovered { address });
+                        }
+                        None => {}
+                    }
+                    return;
+                }
+
                 self.behaviour
                     .on_swarm_event(FromSwarm::DialFailure(DialFailure {
                         peer_id: peer,
@@ -881,7 +1230,8 @@ where
                 connected,
                 error,
                 remaining_established_connection_ids,
-                ..
+                sequence,
+                timestamp,
             } => {
                 if let Some(error) = error.as_ref() {
                     tracing::debug!(
@@ -916,6 +1266,8 @@ where
                         endpoint,
                         cause: error,
                         num_established,
+                        sequence,
+                        timestamp,
                     });
             }
             PoolEvent::ConnectionEvent { peer_id, id, event } => {
@@ -936,6 +1288,52 @@ where
                         new: &new_endpoint,
                     }));
             }
+            PoolEvent::BandwidthEstimated {
+                peer_id,
+                id,
+                estimate,
+            } => {
+                self.behaviour
+                    .on_swarm_event(FromSwarm::BandwidthEstimated(BandwidthEstimated {
+                        peer_id,
+                        connection_id: id,
+                        estimate,
+                    }));
+                self.pending_swarm_events
+                    .push_back(SwarmEvent::BandwidthEstimated {
+                        peer_id,
+                        connection_id: id,
+                        estimate,
+                    });
+            }
+            PoolEvent::StreamOpened {
+                peer_id,
+                id,
+                protocol,
+                sequence,
+                timestamp,
+            } => {
+                self.pending_swarm_events
+                    .push_back(SwarmEvent::StreamOpened {
+                        peer_id,
+                        connection_id: id,
+                        protocol,
+                        sequence,
+                        timestamp,
+                    });
+            }
+            PoolEvent::StreamsClosed {
+                peer_id,
+                id,
+                protocol,
+            } => {
+                self.pending_swarm_events
+                    .push_back(SwarmEvent::StreamsClosed {
+                        peer_id,
+                        connection_id: id,
+                        protocol,
+                    });
+            }
         }
     }
 
@@ -955,49 +1353,20 @@ where
             } => {
                 let connection_id = ConnectionId::next();
 
-                match self.behaviour.handle_pending_inbound_connection(
+                if let Err(listen_error) = self.add_incoming(
                     connection_id,
-                    &local_addr,
-                    &send_back_addr,
+                    upgrade,
+                    local_addr.clone(),
+                    send_back_addr.clone(),
                 ) {
-                    Ok(()) => {}
-                    Err(cause) => {
-                        let listen_error = ListenError::Denied { cause };
-
-                        self.behaviour
-                            .on_swarm_event(FromSwarm::ListenFailure(ListenFailure {
-                                local_addr: &local_addr,
-                                send_back_addr: &send_back_addr,
-                                error: &listen_error,
-                                connection_id,
-                            }));
-
-                        self.pending_swarm_events
-                            .push_back(SwarmEvent::IncomingConnectionError {
-                                connection_id,
-                                local_addr,
-                                send_back_addr,
-                                error: listen_error,
-                            });
-                        return;
-                    }
+                    self.pending_swarm_events
+                        .push_back(SwarmEvent::IncomingConnectionError {
+                            connection_id,
+                            local_addr,
+                            send_back_addr,
+                            error: listen_error,
+                        });
                 }
-
-                self.pool.add_incoming(
-                    upgrade,
-                    IncomingInfo {
-                        local_addr: &local_addr,
-                        send_back_addr: &send_back_addr,
-                    },
-                    connection_id,
-                );
-
-                self.pending_swarm_events
-                    .push_back(SwarmEvent::IncomingConnection {
-                        connection_id,
-                        local_addr,
-                        send_back_addr,
-                    })
             }
             TransportEvent::NewAddress {
                 listener_id,
@@ -1198,6 +1567,9 @@ where
                 self.pending_swarm_events
                     .push_back(SwarmEvent::NewExternalAddrOfPeer { peer_id, address });
             }
+            ToSwarm::BanStreams { peer_id, duration } => {
+                self.ban_streams(peer_id, duration);
+            }
         }
     }
 
@@ -1230,28 +1602,45 @@ where
             match this.pending_handler_event.take() {
                 // Try to deliver the pending event emitted by the [`NetworkBehaviour`] in the previous
                 // iteration to the connection handler(s).
-                Some((peer_id, handler, event)) => match handler {
-                    PendingNotifyHandler::One(conn_id) => {
-                        match this.pool.get_established(conn_id) {
-                            Some(conn) => match notify_one(conn, event, cx) {
-                                None => continue,
-                                Some(event) => {
+                Some((peer_id, handler, event)) => {
+                    match handler {
+                        PendingNotifyHandler::One(conn_id) => {
+                            if let Some(conn) = this.pool.get_established(conn_id) {
+                                if let Some(event) = notify_one(conn, event, cx) {
                                     this.pending_handler_event = Some((peer_id, handler, event));
                                 }
-                            },
-                            None => continue,
+                            }
                         }
-                    }
-                    PendingNotifyHandler::Any(ids) => {
-                        match notify_any::<_, TBehaviour>(ids, &mut this.pool, event, cx) {
-                            None => continue,
-                            Some((event, ids)) => {
+                        PendingNotifyHandler::Any(ids) => {
+                            if let Some((event, ids)) =
+                                notify_any::<_, TBehaviour>(ids, &mut this.pool, event, cx)
+                            {
                                 let handler = PendingNotifyHandler::Any(ids);
                                 this.pending_handler_event = Some((peer_id, handler, event));
                             }
                         }
                     }
-                },
+
+                    match (
+                        this.pending_handler_event.is_some(),
+                        this.pending_handler_event_blocked_since,
+                    ) {
+                        (true, None) => {
+                            this.pending_handler_event_blocked_since = Some(Instant::now());
+                        }
+                        (false, Some(since)) => {
+                            this.pending_handler_event_blocked_since = None;
+                            this.pool
+                                .channel_stats()
+                                .record_notify_handler(since.elapsed());
+                        }
+                        _ => {}
+                    }
+
+                    if this.pending_handler_event.is_none() {
+                        continue;
+                    }
+                }
                 // No pending event. Allow the [`NetworkBehaviour`] to make progress.
                 None => match this.behaviour.poll(cx) {
                     Poll::Pending => {}
@@ -1281,6 +1670,11 @@ where
                 }
             }
 
+            if let Some(Poll::Ready(())) = this.self_check.as_mut().map(|c| c.poll_due(cx)) {
+                this.start_self_checks();
+                continue;
+            }
+
             return Poll::Pending;
         }
     }
@@ -1402,6 +1796,9 @@ where
 
 pub struct Config {
     pool_config: PoolConfig,
+    external_address_self_check: Option<Duration>,
+    max_pending_outgoing: Option<u32>,
+    max_pending_incoming: Option<u32>,
 }
 
 impl Config {
@@ -1410,6 +1807,9 @@ impl Config {
     pub fn with_executor(executor: impl Executor + Send + 'static) -> Self {
         Self {
             pool_config: PoolConfig::new(Some(Box::new(executor))),
+            external_address_self_check: None,
+            max_pending_outgoing: None,
+            max_pending_incoming: None,
         }
     }
 
@@ -1454,6 +1854,8 @@ impl Config {
     /// volume of events. If this value is too low, then the [`Swarm`] will
     /// be sleeping more often than necessary. Increasing this value increases
     /// the overall memory usage.
+    ///
+    /// See [`ChannelStats::notify_handler`] for how often and how long the [`Swarm`] waited.
     pub fn with_notify_handler_buffer_size(mut self, n: NonZeroUsize) -> Self {
         self.pool_config = self.pool_config.with_notify_handler_buffer_size(n);
         self
@@ -1470,11 +1872,28 @@ impl Config {
     /// usage, and more importantly the latency between the moment when an
     /// event is emitted and the moment when it is received by the
     /// [`NetworkBehaviour`].
+    ///
+    /// See [`ChannelStats::connection_events`] for how often and how long the connections waited.
     pub fn with_per_connection_event_buffer_size(mut self, n: usize) -> Self {
         self.pool_config = self.pool_config.with_per_connection_event_buffer_size(n);
         self
     }
 
+    /// Configures the size of the buffer for events of pending connections, i.e. connections
+    /// that are being established or failed to be established, beyond a guaranteed buffer of
+    /// one event per pending connection.
+    ///
+    /// The buffer is shared by all pending connections. Defaults to 0.
+    ///
+    /// See [`ChannelStats::pending_connection_events`] for how often and how long the pending
+    /// connections waited.
+    pub fn with_pending_connection_event_buffer_size(mut self, n: usize) -> Self {
+        self.pool_config = self
+            .pool_config
+            .with_pending_connection_event_buffer_size(n);
+        self
+    }
+
     /// Number of addresses concurrently dialed for a single outbound connection attempt.
     pub fn with_dial_concurrency_factor(mut self, factor: NonZeroU8) -> Self {
         self.pool_config = self.pool_config.with_dial_concurrency_factor(factor);
@@ -1513,6 +1932,50 @@ impl Config {
         self
     }
 
+    /// Passively estimates the bandwidth of new connections from the transfers of their
+    /// streams within the given window after establishment.
+    ///
+    /// The [`BandwidthEstimate`] is reported via [`FromSwarm::BandwidthEstimated`] and
+    /// [`SwarmEvent::BandwidthEstimated`] once the window elapsed, allowing behaviours to
+    /// e.g. prefer faster connections. No additional traffic is generated, thus the
+    /// estimate is only as good as the transfers within the window.
+    ///
+    /// Disabled by default.
+    pub fn with_bandwidth_estimation(mut self, window: Duration) -> Self {
+        self.pool_config = self.pool_config.with_bandwidth_estimation(window);
+        self
+    }
+
+    /// Selects the protocol of new outbound streams optimistically, i.e. without waiting for
+    /// the remote to confirm it, if the remote is known to support it. This saves the round
+    /// trip of the protocol negotiation for most streams on long-lived connections.
+    ///
+    /// A peer is known to support the protocols reported via
+    /// [`ConnectionHandlerEvent::ReportRemoteProtocols`], e.g. by identify, and the protocols
+    /// previously negotiated on outbound streams to it. The knowledge is shared by all
+    /// connections to a peer and forgotten once the last one closes. If the remote rejects an
+    /// optimistically selected protocol, the first read from the stream fails and the protocol
+    /// is no longer considered supported.
+    ///
+    /// Uses [`Version::V1Lazy`](libp2p_core::upgrade::Version::V1Lazy) for the optimistic
+    /// negotiations, see its documentation for the caveats. Ignored if
+    /// [`Config::with_substream_upgrade_protocol_override`] is set.
+    ///
+    /// Disabled by default.
+    pub fn with_optimistic_protocol_selection(mut self, enabled: bool) -> Self {
+        self.pool_config = self.pool_config.with_optimistic_protocol_selection(enabled);
+        self
+    }
+
+    /// Reports every stream negotiated on a connection via [`SwarmEvent::StreamOpened`],
+    /// sequenced with the connection lifecycle events.
+    ///
+    /// Disabled by default.
+    pub fn with_stream_opened_events(mut self, enabled: bool) -> Self {
+        self.pool_config = self.pool_config.with_stream_opened_events(enabled);
+        self
+    }
+
     /// How long to keep a connection alive once it is idling.
     ///
     /// Defaults to 0.
@@ -1520,6 +1983,46 @@ impl Config {
         self.pool_config.idle_connection_timeout = timeout;
         self
     }
+
+    /// Periodically dials each confirmed external address with the local peer ID to verify
+    /// that it actually reaches one of our listeners, e.g. to detect a broken port forwarding
+    /// between the less frequent probes of AutoNAT.
+    ///
+    /// A failed check is reported via [`SwarmEvent::ExternalAddrDegraded`], a successful check
+    /// of a degraded address via [`SwarmEvent::ExternalAddrRecovered`]. The self-dials are not
+    /// reported to the [`NetworkBehaviour`]. The listening side of a successful self-dial is
+    /// reported as [`SwarmEvent::IncomingConnectionError`] with [`ListenError::LocalPeerId`].
+    ///
+    /// Disabled by default.
+    pub fn with_external_address_self_check(mut self, interval: Duration) -> Self {
+        self.external_address_self_check = Some(interval);
+        self
+    }
+
+    /// Limits the number of outgoing connections being dialed at the same time, bounding the
+    /// memory used by pending dials, e.g. while the network blackholes connection attempts.
+    ///
+    /// Beyond the limit, [`Swarm::dial`] fails with [`DialError::Denied`], caused by a
+    /// [`PendingLimitExceeded`] error. See [`Swarm::pending_stats`].
+    ///
+    /// Unlimited by default.
+    pub fn with_max_pending_outgoing(mut self, limit: u32) -> Self {
+        self.max_pending_outgoing = Some(limit);
+        self
+    }
+
+    /// Limits the number of incoming connections being upgraded at the same time, bounding the
+    /// memory used by pending upgrades, e.g. during an attack.
+    ///
+    /// Beyond the limit, incoming connections are dropped and reported as
+    /// [`SwarmEvent::IncomingConnectionError`] with [`ListenError::Denied`], caused by a
+    /// [`PendingLimitExceeded`] error. See [`Swarm::pending_stats`].
+    ///
+    /// Unlimited by default.
+    pub fn with_max_pending_incoming(mut self, limit: u32) -> Self {
+        self.max_pending_incoming = Some(limit);
+        self
+    }
 }
 
 /// Possible errors when trying to establish or upgrade an outbound connection.
@@ -1773,6 +2276,7 @@ mod tests {
     use crate::test::{CallTraceBehaviour, MockBehaviour};
     use libp2p_core::multiaddr::multiaddr;
     use libp2p_core::transport::memory::MemoryTransportError;
+    use libp2p_core::upgrade::OutboundConnectionUpgrade;
     use libp2p_core::{multiaddr, upgrade};
     use libp2p_identity as identity;
     use libp2p_plaintext as plaintext;
@@ -1909,6 +2413,67 @@ mod tests {
         .await
     }
 
+    /// Establishes and closes multiple connections between two peers.
+    ///
+    /// The test expects the [`SwarmEvent::ConnectionEstablished`] and
+    /// [`SwarmEvent::ConnectionClosed`] events to carry strictly increasing
+    /// sequence numbers and non-decreasing timestamps.
+    #[tokio::test]
+    async fn connection_events_are_sequenced() {
+        let mut swarm1 = new_test_swarm(Config::with_tokio_executor());
+        let mut swarm2 = new_test_swarm(Config::with_tokio_executor());
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+
+        let swarm2_id = *swarm2.local_peer_id();
+        let num_connections = 5;
+
+        for _ in 0..num_connections {
+            swarm1.dial(addr2.clone()).unwrap();
+        }
+
+        let mut events = Vec::new();
+        future::poll_fn(|cx| loop {
+            let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
+            let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
+            let pending = poll1.is_pending() && poll2.is_pending();
+            match poll1 {
+                Poll::Ready(SwarmEvent::ConnectionEstablished {
+                    sequence,
+                    timestamp,
+                    ..
+                }) => {
+                    events.push((sequence, timestamp));
+                    if events.len() == num_connections {
+                        swarm1.disconnect_peer_id(swarm2_id).unwrap();
+                    }
+                }
+                Poll::Ready(SwarmEvent::ConnectionClosed {
+                    sequence,
+                    timestamp,
+                    ..
+                }) => {
+                    events.push((sequence, timestamp));
+                    if events.len() == 2 * num_connections {
+                        return Poll::Ready(());
+                    }
+                }
+                _ => {}
+            }
+
+            if pending {
+                return Poll::Pending;
+            }
+        })
+        .await;
+
+        for pair in events.windows(2) {
+            assert!(pair[0].0 < pair[1].0);
+            assert!(pair[0].1 <= pair[1].1);
+        }
+    }
+
     /// Establishes multiple connections between two peers,
     /// after which one peer disconnects the other
     /// using [`ToSwarm::CloseConnection`] returned by a [`NetworkBehaviour`].
@@ -2175,6 +2740,77 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn external_connection() {
+        // Checks that a connection established outside of the swarm, with the remote
+        // authenticated out of band, is added as an incoming connection.
+
+        let mut swarm = new_test_swarm(Config::with_tokio_executor());
+        let remote = PeerId::random();
+
+        let mut transport = transport::MemoryTransport::default();
+        let address: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+        transport
+            .listen_on(ListenerId::next(), address.clone())
+            .unwrap();
+        let outbound = transport.dial(address.clone()).unwrap().await.unwrap();
+        let inbound = loop {
+            match future::poll_fn(|cx| Pin::new(&mut transport).poll(cx)).await {
+                TransportEvent::Incoming { upgrade, .. } => break upgrade.await.unwrap(),
+                _ => continue,
+            }
+        };
+
+        let connection_id = swarm
+            .add_external_connection(
+                inbound,
+                remote,
+                yamux::Config::default(),
+                address.clone(),
+                Multiaddr::empty(),
+            )
+            .unwrap();
+
+        let remote_muxer = async move {
+            let muxer = yamux::Config::default();
+            let (info, stream) = multistream_select::dialer_select_proto(
+                outbound,
+                muxer.protocol_info(),
+                upgrade::Version::V1,
+            )
+            .await
+            .unwrap();
+            muxer.upgrade_outbound(stream, info).await.unwrap()
+        };
+        let established = async {
+            loop {
+                match swarm.select_next_some().await {
+                    SwarmEvent::IncomingConnection { .. } => {}
+                    SwarmEvent::ConnectionEstablished {
+                        peer_id,
+                        connection_id,
+                        endpoint,
+                        ..
+                    } => break (peer_id, connection_id, endpoint),
+                    e => panic!("Unexpected swarm event {e:?}"),
+                }
+            }
+        };
+        let (_remote_muxer, (peer_id, established_id, endpoint)) =
+            future::join(remote_muxer, established).await;
+
+        assert_eq!(peer_id, remote);
+        assert_eq!(established_id, connection_id);
+        assert_eq!(
+            endpoint,
+            ConnectedPoint::Listener {
+                local_addr: address,
+                send_back_addr: Multiaddr::empty(),
+            }
+        );
+        assert!(swarm.is_connected(&remote));
+    }
+
     #[tokio::test]
     async fn dial_self() {
         // Check whether dialing ourselves correctly fails.
@@ -2336,6 +2972,78 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn dials_beyond_pending_limit_are_denied() {
+        let mut swarm = new_test_swarm(Config::with_tokio_executor().with_max_pending_outgoing(1));
+        let dial = || {
+            DialOpts::unknown_peer_id()
+                .address(multiaddr![Memory(rand::random::<u64>())])
+                .build()
+        };
+
+        swarm.dial(dial()).unwrap();
+        match swarm.dial(dial()) {
+            Err(DialError::Denied { cause }) => {
+                let cause = cause.downcast::<PendingLimitExceeded>().unwrap();
+                assert_eq!(cause.limit(), 1);
+            }
+            r => panic!("Unexpected dial result {r:?}."),
+        }
+
+        let stats = swarm.pending_stats().outgoing();
+        assert_eq!(stats.num_pending, 1);
+        assert_eq!(stats.num_denied, 1);
+        assert!(stats.oldest_age.is_some());
+        assert_eq!(
+            swarm.pending_stats().incoming(),
+            PendingConnections::default()
+        );
+
+        // The pending dial fails as nothing listens on the address, making room for another.
+        match swarm.next().await.unwrap() {
+            SwarmEvent::OutgoingConnectionError { .. } => {}
+            e => panic!("Unexpected swarm event {e:?}."),
+        }
+        assert_eq!(swarm.pending_stats().outgoing().num_pending, 0);
+        swarm.dial(dial()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn external_address_self_check_reports_degraded_and_recovered() {
+        let mut swarm = new_test_swarm(
+            Config::with_tokio_executor()
+                .with_external_address_self_check(Duration::from_millis(50)),
+        );
+        let address: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm.add_external_address(address.clone());
+
+        // Nothing listens on the address yet.
+        match swarm.next().await.unwrap() {
+            SwarmEvent::ExternalAddrDegraded { address: a, .. } => assert_eq!(a, address),
+            e => panic!("Unexpected swarm event {e:?}."),
+        }
+        assert_eq!(
+            swarm.degraded_external_addresses().collect::<Vec<_>>(),
+            vec![&address]
+        );
+
+        swarm.listen_on(address.clone()).unwrap();
+        loop {
+            match swarm.next().await.unwrap() {
+                SwarmEvent::ExternalAddrRecovered { address: a } => {
+                    assert_eq!(a, address);
+                    break;
+                }
+                SwarmEvent::OutgoingConnectionError { .. } | SwarmEvent::Dialing { .. } => {
+                    panic!("Self-dials are not reported as regular dials.")
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(swarm.degraded_external_addresses().count(), 0);
+        assert_eq!(swarm.behaviour().on_dial_failure.len(), 0);
+    }
+
     #[test]
     fn dial_error_prints_sources() {
         // This constitutes a fairly typical error for chained transports.
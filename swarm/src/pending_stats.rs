@@ -0,0 +1,143 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::connection::ConnectionId;
+use instant::Instant;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Statistics of the pending connections of a [`Swarm`](crate::Swarm), i.e. the outgoing
+/// connections being dialed and the incoming connections being upgraded.
+///
+/// The statistics stay up to date with the [`Swarm`](crate::Swarm) and can be cloned cheaply,
+/// e.g. to export them as metrics. See [`Swarm::pending_stats`](crate::Swarm::pending_stats).
+///
+/// Pending connections that grow old or in number indicate a network blackhole or an attack,
+/// see [`Config::with_max_pending_outgoing`](crate::Config::with_max_pending_outgoing) and
+/// [`Config::with_max_pending_incoming`](crate::Config::with_max_pending_incoming).
+#[derive(Debug, Clone, Default)]
+pub struct PendingStats {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// The pending connections, whether they are incoming, and when they were added.
+    pending: Mutex<HashMap<ConnectionId, (bool, Instant)>>,
+    denied_outgoing: AtomicU64,
+    denied_incoming: AtomicU64,
+}
+
+impl PendingStats {
+    /// Returns the statistics of the pending outgoing connections.
+    pub fn outgoing(&self) -> PendingConnections {
+        self.get(false, &self.inner.denied_outgoing)
+    }
+
+    /// Returns the statistics of the pending incoming connections.
+    pub fn incoming(&self) -> PendingConnections {
+        self.get(true, &self.inner.denied_incoming)
+    }
+
+    fn get(&self, incoming: bool, denied: &AtomicU64) -> PendingConnections {
+        let now = Instant::now();
+        let pending = self.inner.pending.lock().expect("lock not to be poisoned");
+        let (num_pending, oldest) = pending.values().filter(|(i, _)| *i == incoming).fold(
+            (0, None),
+            |(num, oldest): (u32, Option<Instant>), (_, at)| {
+                (num + 1, Some(oldest.map_or(*at, |o| o.min(*at))))
+            },
+        );
+
+        PendingConnections {
+            num_pending,
+            oldest_age: oldest.map(|at| now.saturating_duration_since(at)),
+            num_denied: denied.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn on_added(&self, id: ConnectionId, incoming: bool, at: Instant) {
+        self.inner
+            .pending
+            .lock()
+            .expect("lock not to be poisoned")
+            .insert(id, (incoming, at));
+    }
+
+    pub(crate) fn on_removed(&self, id: ConnectionId) {
+        self.inner
+            .pending
+            .lock()
+            .expect("lock not to be poisoned")
+            .remove(&id);
+    }
+
+    pub(crate) fn on_denied(&self, incoming: bool) {
+        let denied = if incoming {
+            &self.inner.denied_incoming
+        } else {
+            &self.inner.denied_outgoing
+        };
+        denied.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The pending connections of one direction, see [`PendingStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PendingConnections {
+    /// The number of connections currently pending.
+    pub num_pending: u32,
+    /// The time the oldest currently pending connection has been pending for, if any.
+    pub oldest_age: Option<Duration>,
+    /// The number of connections denied over the lifetime of the [`Swarm`](crate::Swarm)
+    /// as the limit of pending connections was reached.
+    pub num_denied: u64,
+}
+
+/// The cause of a [`ConnectionDenied`](crate::ConnectionDenied) error for connections denied as
+/// the limit of pending connections was reached, see
+/// [`Config::with_max_pending_outgoing`](crate::Config::with_max_pending_outgoing) and
+/// [`Config::with_max_pending_incoming`](crate::Config::with_max_pending_incoming).
+#[derive(Debug, Clone, Copy)]
+pub struct PendingLimitExceeded {
+    limit: u32,
+}
+
+impl PendingLimitExceeded {
+    pub(crate) fn new(limit: u32) -> Self {
+        Self { limit }
+    }
+
+    /// The exceeded limit of pending connections.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+}
+
+impl fmt::Display for PendingLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pending connection limit of {} exceeded", self.limit)
+    }
+}
+
+impl std::error::Error for PendingLimitExceeded {}
@@ -0,0 +1,132 @@
+//! Periodic self-dials of the confirmed external addresses, see
+//! [`Config::with_external_address_self_check`](crate::Config::with_external_address_self_check).
+
+use crate::connection::ConnectionId;
+use crate::DialError;
+use futures::FutureExt;
+use futures_timer::Delay;
+use libp2p_core::Multiaddr;
+use std::collections::{HashMap, HashSet};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Outcome of a self-check that changed the health of an external address.
+#[derive(Debug)]
+pub(crate) enum Transition {
+    Degraded {
+        address: Multiaddr,
+        error: DialError,
+    },
+    Recovered {
+        address: Multiaddr,
+    },
+}
+
+pub(crate) struct SelfCheck {
+    interval: Duration,
+    timer: Delay,
+    /// Self-dials in flight and the external address they check.
+    in_flight: HashMap<ConnectionId, Multiaddr>,
+    /// External addresses whose last check failed.
+    degraded: HashSet<Multiaddr>,
+}
+
+impl SelfCheck {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            timer: Delay::new(interval),
+            in_flight: HashMap::new(),
+            degraded: HashSet::new(),
+        }
+    }
+
+    /// Polls the check timer, resolving once a new round of checks is due.
+    pub(crate) fn poll_due(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        futures::ready!(self.timer.poll_unpin(cx));
+        self.timer.reset(self.interval);
+        Poll::Ready(())
+    }
+
+    /// Whether a self-dial of the given address is still in flight.
+    ///
+    /// A new check is only started once the previous one of the same address completed.
+    pub(crate) fn is_checking(&self, address: &Multiaddr) -> bool {
+        self.in_flight.values().any(|a| a == address)
+    }
+
+    pub(crate) fn on_dial(&mut self, id: ConnectionId, address: Multiaddr) {
+        self.in_flight.insert(id, address);
+    }
+
+    /// Whether the connection is a self-dial started by [`SelfCheck`].
+    pub(crate) fn is_self_dial(&self, id: ConnectionId) -> bool {
+        self.in_flight.contains_key(&id)
+    }
+
+    /// Records the outcome of a self-dial.
+    ///
+    /// Reaching the local node is a success, any other outcome a failure.
+    pub(crate) fn on_dial_result(
+        &mut self,
+        id: ConnectionId,
+        result: Result<(), DialError>,
+    ) -> Option<Transition> {
+        let address = self.in_flight.remove(&id)?;
+
+        match result {
+            Ok(()) => self
+                .degraded
+                .remove(&address)
+                .then_some(Transition::Recovered { address }),
+            Err(error) => self
+                .degraded
+                .insert(address.clone())
+                .then_some(Transition::Degraded { address, error }),
+        }
+    }
+
+    /// Forgets the health of an address that is no longer a confirmed external address.
+    pub(crate) fn on_address_removed(&mut self, address: &Multiaddr) {
+        self.degraded.remove(address);
+    }
+
+    pub(crate) fn degraded(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.degraded.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transitions_are_reported_once() {
+        let mut check = SelfCheck::new(Duration::from_secs(60));
+        let address = "/ip4/1.2.3.4/tcp/4001".parse::<Multiaddr>().unwrap();
+
+        let mut run = |result: Result<(), DialError>| {
+            let id = ConnectionId::next();
+            check.on_dial(id, address.clone());
+            check.on_dial_result(id, result)
+        };
+
+        assert!(run(Ok(())).is_none());
+        assert!(matches!(
+            run(Err(DialError::Aborted)),
+            Some(Transition::Degraded { .. })
+        ));
+        assert!(run(Err(DialError::Aborted)).is_none());
+        assert!(matches!(run(Ok(())), Some(Transition::Recovered { .. })));
+        assert!(run(Ok(())).is_none());
+    }
+
+    #[test]
+    fn unknown_connections_are_ignored() {
+        let mut check = SelfCheck::new(Duration::from_secs(60));
+
+        assert!(check
+            .on_dial_result(ConnectionId::next(), Err(DialError::Aborted))
+            .is_none());
+    }
+}
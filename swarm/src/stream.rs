@@ -1,11 +1,19 @@
+use crate::connection::BandwidthEstimator;
+use futures::task::AtomicWaker;
 use futures::{AsyncRead, AsyncWrite};
-use libp2p_core::muxing::SubstreamBox;
+use libp2p_core::muxing::{SubstreamBox, SubstreamStats};
 use libp2p_core::Negotiated;
+use multistream_select::NegotiationError;
 use std::{
-    io::{IoSlice, IoSliceMut},
+    collections::{HashMap, HashSet, VecDeque},
+    io::{self, IoSlice, IoSliceMut},
     pin::Pin,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
     task::{Context, Poll},
+    time::Duration,
 };
 
 /// Counter for the number of active streams on a connection.
@@ -26,17 +34,376 @@ impl ActiveStreamCounter {
     }
 }
 
+/// The negotiated streams of a connection by protocol.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StreamRegistry {
+    streams: Arc<Mutex<HashMap<String, ProtocolStreams>>>,
+    /// Woken when a stream that was asked to close is dropped.
+    connection_waker: Arc<AtomicWaker>,
+    /// Set while the bandwidth of the connection is being estimated.
+    bandwidth_estimator: Option<Arc<BandwidthEstimator>>,
+    /// Set if new outbound streams select the protocols known to be supported optimistically.
+    protocol_cache: Option<ProtocolCache>,
+    /// Set if the protocols of newly negotiated streams are reported by the connection.
+    opened: Option<Arc<Mutex<VecDeque<String>>>>,
+}
+
+impl StreamRegistry {
+    /// Registers a new stream negotiated for the given protocol, with the
+    /// statistics of its substream.
+    pub(crate) fn register(&self, protocol: &str, stats: SubstreamStats) -> Arc<CloseSignal> {
+        let signal = Arc::new(CloseSignal {
+            closing: AtomicBool::new(false),
+            stream_waker: AtomicWaker::new(),
+            connection_waker: self.connection_waker.clone(),
+        });
+
+        let mut streams = self.streams.lock().expect("lock not to be poisoned");
+        let protocol_streams = streams.entry(protocol.to_owned()).or_default();
+        protocol_streams.prune();
+        protocol_streams.open.push(RegisteredStream {
+            close_signal: Arc::downgrade(&signal),
+            stats,
+        });
+        if let Some(opened) = &self.opened {
+            opened
+                .lock()
+                .expect("lock not to be poisoned")
+                .push_back(protocol.to_owned());
+        }
+
+        signal
+    }
+
+    /// Records the protocols of new streams, to be taken via [`StreamRegistry::next_opened`].
+    pub(crate) fn record_opened(&mut self) {
+        self.opened = Some(Default::default());
+    }
+
+    /// Takes the protocol of the oldest stream negotiated since last taken.
+    pub(crate) fn next_opened(&self) -> Option<String> {
+        self.opened
+            .as_ref()?
+            .lock()
+            .expect("lock not to be poisoned")
+            .pop_front()
+    }
+
+    /// Sets the estimator that new streams report their transfers to.
+    pub(crate) fn set_bandwidth_estimator(&mut self, estimator: Option<Arc<BandwidthEstimator>>) {
+        self.bandwidth_estimator = estimator;
+    }
+
+    pub(crate) fn bandwidth_estimator(&self) -> Option<Arc<BandwidthEstimator>> {
+        self.bandwidth_estimator.clone()
+    }
+
+    pub(crate) fn set_protocol_cache(&mut self, cache: ProtocolCache) {
+        self.protocol_cache = Some(cache);
+    }
+
+    pub(crate) fn protocol_cache(&self) -> Option<&ProtocolCache> {
+        self.protocol_cache.as_ref()
+    }
+
+    /// Asks all current streams of the given protocol to close.
+    pub(crate) fn close(&self, protocol: &str) -> ClosingStreams {
+        let streams = self
+            .streams
+            .lock()
+            .expect("lock not to be poisoned")
+            .get(protocol)
+            .map(|streams| {
+                streams
+                    .active()
+                    .map(|signal| {
+                        signal.close();
+                        Arc::downgrade(&signal)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ClosingStreams {
+            streams,
+            connection_waker: self.connection_waker.clone(),
+        }
+    }
+
+    /// Returns a view of the streams of the connection by protocol.
+    pub(crate) fn usage(&self) -> StreamUsage {
+        StreamUsage {
+            streams: self.streams.clone(),
+        }
+    }
+}
+
+/// The protocols a peer is known to support, shared by all connections to the peer.
+///
+/// Learned from the protocols reported by the connection handlers, e.g. via identify, and
+/// from prior negotiations, see [`Config::with_optimistic_protocol_selection`](crate::Config::with_optimistic_protocol_selection).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProtocolCache {
+    protocols: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ProtocolCache {
+    pub(crate) fn insert(&self, protocol: &str) {
+        self.protocols
+            .lock()
+            .expect("lock not to be poisoned")
+            .insert(protocol.to_owned());
+    }
+
+    pub(crate) fn remove(&self, protocol: &str) {
+        self.protocols
+            .lock()
+            .expect("lock not to be poisoned")
+            .remove(protocol);
+    }
+
+    pub(crate) fn contains(&self, protocol: &str) -> bool {
+        self.protocols
+            .lock()
+            .expect("lock not to be poisoned")
+            .contains(protocol)
+    }
+}
+
+/// A view of the active streams of a connection by protocol.
+///
+/// The view stays up to date with the connection and can be cloned cheaply, e.g. to share it
+/// between a [`NetworkBehaviour`](crate::NetworkBehaviour) and its connection handlers.
+/// See [`FromSwarm::ConnectionStreams`](crate::FromSwarm::ConnectionStreams) and
+/// [`Swarm::stream_usage`](crate::Swarm::stream_usage).
+///
+/// Streams asked to close by [`Swarm::close_streams`](crate::Swarm::close_streams) are no
+/// longer counted as active, but their transfers still count towards the [`ProtocolStats`].
+#[derive(Debug, Clone)]
+pub struct StreamUsage {
+    streams: Arc<Mutex<HashMap<String, ProtocolStreams>>>,
+}
+
+impl StreamUsage {
+    /// Returns the number of active streams negotiated for the given protocol.
+    pub fn num_streams(&self, protocol: &str) -> usize {
+        self.streams
+            .lock()
+            .expect("lock not to be poisoned")
+            .get(protocol)
+            .map_or(0, |streams| streams.active().count())
+    }
+
+    /// Returns the number of active streams of each protocol with at least one active stream.
+    pub fn protocols(&self) -> HashMap<String, usize> {
+        self.streams
+            .lock()
+            .expect("lock not to be poisoned")
+            .iter()
+            .map(|(protocol, streams)| (protocol.clone(), streams.active().count()))
+            .filter(|(_, active)| *active > 0)
+            .collect()
+    }
+
+    /// Returns the statistics of all streams negotiated for the given protocol
+    /// over the lifetime of the connection.
+    pub fn stats(&self, protocol: &str) -> ProtocolStats {
+        self.streams
+            .lock()
+            .expect("lock not to be poisoned")
+            .get(protocol)
+            .map(ProtocolStreams::stats)
+            .unwrap_or_default()
+    }
+
+    /// Returns the statistics of the streams of each protocol negotiated
+    /// over the lifetime of the connection.
+    pub fn protocol_stats(&self) -> HashMap<String, ProtocolStats> {
+        self.streams
+            .lock()
+            .expect("lock not to be poisoned")
+            .iter()
+            .map(|(protocol, streams)| (protocol.clone(), streams.stats()))
+            .collect()
+    }
+}
+
+/// The statistics of the streams of a protocol on a connection, see [`StreamUsage::stats`].
+///
+/// Unless tracked by the [`StreamMuxer`](libp2p_core::StreamMuxer) of the connection, see
+/// [`StreamMuxer::substream_stats`](libp2p_core::StreamMuxer::substream_stats), the bytes
+/// are those read from and written to the streams, including the protocol negotiation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtocolStats {
+    /// The number of streams negotiated for the protocol, including those since dropped.
+    pub num_streams: usize,
+    /// The number of bytes received on the streams.
+    pub bytes_received: u64,
+    /// The number of bytes sent on the streams.
+    pub bytes_sent: u64,
+    /// The sum of the durations the streams have been open for.
+    pub open_duration: Duration,
+}
+
+impl ProtocolStats {
+    fn add(&mut self, stats: &SubstreamStats) {
+        self.num_streams += 1;
+        self.bytes_received += stats.bytes_received();
+        self.bytes_sent += stats.bytes_sent();
+        self.open_duration += stats.open_duration();
+    }
+}
+
+/// The streams negotiated for a protocol on a connection.
+#[derive(Debug, Default)]
+struct ProtocolStreams {
+    /// The streams that were not dropped as of the last pruning.
+    open: Vec<RegisteredStream>,
+    /// The statistics of the pruned streams.
+    dropped: ProtocolStats,
+}
+
+#[derive(Debug)]
+struct RegisteredStream {
+    close_signal: Weak<CloseSignal>,
+    stats: SubstreamStats,
+}
+
+impl ProtocolStreams {
+    /// Moves the dropped streams into the statistics of the dropped streams.
+    fn prune(&mut self) {
+        let dropped = &mut self.dropped;
+        self.open.retain(|stream| {
+            if stream.close_signal.strong_count() > 0 {
+                return true;
+            }
+            dropped.add(&stream.stats);
+            false
+        });
+    }
+
+    /// The streams that were neither dropped nor asked to close.
+    fn active(&self) -> impl Iterator<Item = Arc<CloseSignal>> + '_ {
+        self.open
+            .iter()
+            .filter_map(|stream| stream.close_signal.upgrade())
+            .filter(|signal| !signal.closing.load(Ordering::Acquire))
+    }
+
+    fn stats(&self) -> ProtocolStats {
+        let mut stats = self.dropped;
+        for stream in &self.open {
+            stats.add(&stream.stats);
+        }
+        stats
+    }
+}
+
+/// Streams that were asked to close, see [`StreamRegistry::close`].
+#[derive(Debug)]
+pub(crate) struct ClosingStreams {
+    streams: Vec<Weak<CloseSignal>>,
+    connection_waker: Arc<AtomicWaker>,
+}
+
+impl ClosingStreams {
+    /// Resolves once all streams have been dropped.
+    pub(crate) fn poll_closed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        self.connection_waker.register(cx.waker());
+        self.streams.retain(|s| s.strong_count() > 0);
+
+        if self.streams.is_empty() {
+            return Poll::Ready(());
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Signal to a single [`Stream`] to close.
+#[derive(Debug)]
+pub(crate) struct CloseSignal {
+    closing: AtomicBool,
+    stream_waker: AtomicWaker,
+    connection_waker: Arc<AtomicWaker>,
+}
+
+impl CloseSignal {
+    fn close(&self) {
+        self.closing.store(true, Ordering::Release);
+        self.stream_waker.wake();
+    }
+
+    pub(crate) fn is_closing(&self, cx: &mut Context<'_>) -> bool {
+        self.stream_waker.register(cx.waker());
+        self.closing.load(Ordering::Acquire)
+    }
+}
+
 #[derive(Debug)]
 pub struct Stream {
     stream: Negotiated<SubstreamBox>,
     counter: Option<ActiveStreamCounter>,
+    close_signal: Arc<CloseSignal>,
+    write_closed: bool,
+    bandwidth_estimator: Option<Arc<BandwidthEstimator>>,
+    /// The cache to invalidate if the optimistically selected protocol turns out to be
+    /// unsupported, until the negotiation is confirmed.
+    optimistic: Option<(ProtocolCache, String)>,
 }
 
 impl Stream {
-    pub(crate) fn new(stream: Negotiated<SubstreamBox>, counter: ActiveStreamCounter) -> Self {
+    pub(crate) fn new(
+        stream: Negotiated<SubstreamBox>,
+        counter: ActiveStreamCounter,
+        close_signal: Arc<CloseSignal>,
+        bandwidth_estimator: Option<Arc<BandwidthEstimator>>,
+    ) -> Self {
         Self {
             stream,
             counter: Some(counter),
+            close_signal,
+            write_closed: false,
+            bandwidth_estimator,
+            optimistic: None,
+        }
+    }
+
+    /// Marks the protocol of the stream as selected optimistically based on the given cache.
+    ///
+    /// The protocol is removed from the cache if the remote rejects it, which is only
+    /// noticed on the first read.
+    pub(crate) fn with_optimistic_negotiation(
+        mut self,
+        cache: ProtocolCache,
+        protocol: &str,
+    ) -> Self {
+        self.optimistic = Some((cache, protocol.to_owned()));
+        self
+    }
+
+    /// Resolves the optimistic negotiation, if any, with the result of the first read.
+    fn on_read<T>(&mut self, poll: &Poll<io::Result<T>>) {
+        let Poll::Ready(result) = poll else {
+            return;
+        };
+        let Some((cache, protocol)) = self.optimistic.take() else {
+            return;
+        };
+
+        let rejected = result
+            .as_ref()
+            .err()
+            .and_then(|e| e.get_ref())
+            .is_some_and(|e| {
+                matches!(
+                    e.downcast_ref::<NegotiationError>(),
+                    Some(NegotiationError::Failed)
+                )
+            });
+        if rejected {
+            tracing::debug!(%protocol, "Optimistically selected protocol was rejected");
+            cache.remove(&protocol);
         }
     }
 
@@ -51,6 +418,54 @@ impl Stream {
     pub fn ignore_for_keep_alive(&mut self) {
         self.counter.take();
     }
+
+    /// Closes the write side of the stream if it was asked to by
+    /// [`Swarm::close_streams`](crate::Swarm::close_streams).
+    ///
+    /// Returns `None` if the stream was not asked to close.
+    fn poll_close_requested(&mut self, cx: &mut Context<'_>) -> Option<Poll<io::Result<()>>> {
+        if !self.close_signal.is_closing(cx) {
+            return None;
+        }
+        if self.write_closed {
+            return Some(Poll::Ready(Ok(())));
+        }
+
+        let Poll::Ready(result) = Pin::new(&mut self.stream).poll_close(cx) else {
+            return Some(Poll::Pending);
+        };
+        self.write_closed = true;
+
+        Some(Poll::Ready(result))
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if self.close_signal.closing.load(Ordering::Acquire) {
+            self.close_signal.connection_waker.wake();
+        }
+    }
+}
+
+/// Reports the bytes transferred by a successful read or write to the estimator, if any.
+fn record_transfer(
+    estimator: &Option<Arc<BandwidthEstimator>>,
+    poll: Poll<io::Result<usize>>,
+    record: impl FnOnce(&BandwidthEstimator, usize),
+) -> Poll<io::Result<usize>> {
+    if let (Some(estimator), Poll::Ready(Ok(bytes))) = (estimator, &poll) {
+        record(estimator, *bytes);
+    }
+
+    poll
+}
+
+fn stream_closed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "stream was closed by the local node",
+    )
 }
 
 impl AsyncRead for Stream {
@@ -59,7 +474,18 @@ impl AsyncRead for Stream {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<std::io::Result<usize>> {
-        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+        let this = self.get_mut();
+        if let Some(closed) = this.poll_close_requested(cx) {
+            return closed.map_ok(|()| 0);
+        }
+
+        let poll = Pin::new(&mut this.stream).poll_read(cx, buf);
+        this.on_read(&poll);
+        record_transfer(
+            &this.bandwidth_estimator,
+            poll,
+            BandwidthEstimator::record_inbound,
+        )
     }
 
     fn poll_read_vectored(
@@ -67,7 +493,18 @@ impl AsyncRead for Stream {
         cx: &mut Context<'_>,
         bufs: &mut [IoSliceMut<'_>],
     ) -> Poll<std::io::Result<usize>> {
-        Pin::new(&mut self.get_mut().stream).poll_read_vectored(cx, bufs)
+        let this = self.get_mut();
+        if let Some(closed) = this.poll_close_requested(cx) {
+            return closed.map_ok(|()| 0);
+        }
+
+        let poll = Pin::new(&mut this.stream).poll_read_vectored(cx, bufs);
+        this.on_read(&poll);
+        record_transfer(
+            &this.bandwidth_estimator,
+            poll,
+            BandwidthEstimator::record_inbound,
+        )
     }
 }
 
@@ -77,7 +514,17 @@ impl AsyncWrite for Stream {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<std::io::Result<usize>> {
-        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+        let this = self.get_mut();
+        if let Some(closed) = this.poll_close_requested(cx) {
+            return closed.map(|r| r.and(Err(stream_closed())));
+        }
+
+        let poll = Pin::new(&mut this.stream).poll_write(cx, buf);
+        record_transfer(
+            &this.bandwidth_estimator,
+            poll,
+            BandwidthEstimator::record_outbound,
+        )
     }
 
     fn poll_write_vectored(
@@ -85,11 +532,26 @@ impl AsyncWrite for Stream {
         cx: &mut Context<'_>,
         bufs: &[IoSlice<'_>],
     ) -> Poll<std::io::Result<usize>> {
-        Pin::new(&mut self.get_mut().stream).poll_write_vectored(cx, bufs)
+        let this = self.get_mut();
+        if let Some(closed) = this.poll_close_requested(cx) {
+            return closed.map(|r| r.and(Err(stream_closed())));
+        }
+
+        let poll = Pin::new(&mut this.stream).poll_write_vectored(cx, bufs);
+        record_transfer(
+            &this.bandwidth_estimator,
+            poll,
+            BandwidthEstimator::record_outbound,
+        )
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
-        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+        let this = self.get_mut();
+        if let Some(closed) = this.poll_close_requested(cx) {
+            return closed;
+        }
+
+        Pin::new(&mut this.stream).poll_flush(cx)
     }
 
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
@@ -0,0 +1,71 @@
+use libp2p_core::transport::MemoryTransport;
+use libp2p_core::upgrade::Version;
+use libp2p_core::Transport;
+use libp2p_identity::Keypair;
+use libp2p_ping as ping;
+use libp2p_swarm::{Config, Swarm, SwarmEvent};
+use libp2p_swarm_test::SwarmExt;
+use std::time::Duration;
+
+#[async_std::test]
+async fn stream_opens_are_sequenced_between_connection_events() {
+    let identity = Keypair::generate_ed25519();
+    let transport = MemoryTransport::default()
+        .upgrade(Version::V1)
+        .authenticate(libp2p_plaintext::Config::new(&identity))
+        .multiplex(libp2p_yamux::Config::default())
+        .boxed();
+    let mut swarm1 = Swarm::new(
+        transport,
+        ping::Behaviour::default(),
+        identity.public().to_peer_id(),
+        Config::with_async_std_executor()
+            .with_idle_connection_timeout(Duration::from_secs(5))
+            .with_stream_opened_events(true),
+    );
+    let mut swarm2 = Swarm::new_ephemeral(|_| ping::Behaviour::default());
+
+    let (memory_addr, _) = swarm2.listen().await;
+    let swarm2_id = *swarm2.local_peer_id();
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    swarm1.dial(memory_addr).unwrap();
+
+    let mut events = Vec::new();
+    loop {
+        match swarm1.next_swarm_event().await {
+            SwarmEvent::ConnectionEstablished {
+                sequence,
+                timestamp,
+                ..
+            } => events.push((sequence, timestamp)),
+            SwarmEvent::StreamOpened {
+                peer_id,
+                protocol,
+                sequence,
+                timestamp,
+                ..
+            } => {
+                assert_eq!(peer_id, swarm2_id);
+                assert_eq!(protocol, ping::PROTOCOL_NAME.as_ref());
+                events.push((sequence, timestamp));
+                swarm1.disconnect_peer_id(swarm2_id).unwrap();
+            }
+            SwarmEvent::ConnectionClosed {
+                sequence,
+                timestamp,
+                ..
+            } => {
+                events.push((sequence, timestamp));
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    assert_eq!(events.len(), 3);
+    for pair in events.windows(2) {
+        assert!(pair[0].0 < pair[1].0);
+        assert!(pair[0].1 <= pair[1].1);
+    }
+}
@@ -231,14 +231,23 @@ where
     }
 
     fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.do_dial(addr, Endpoint::Dialer)
+        self.do_dial(addr, None, Endpoint::Dialer)
     }
 
     fn dial_as_listener(
         &mut self,
         addr: Multiaddr,
     ) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.do_dial(addr, Endpoint::Listener)
+        self.do_dial(addr, None, Endpoint::Listener)
+    }
+
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(addr, Some(local_addr.clone()), role_override)
     }
 
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
@@ -268,6 +277,7 @@ where
     fn do_dial(
         &mut self,
         addr: Multiaddr,
+        local_addr: Option<Multiaddr>,
         role_override: Endpoint,
     ) -> Result<
         <Self as libp2p_core::Transport>::Dial,
@@ -357,9 +367,12 @@ where
                     tracing::debug!(address=%addr, "Dialing address");
 
                     let transport = inner.clone();
-                    let dial = match role_override {
-                        Endpoint::Dialer => transport.lock().dial(addr),
-                        Endpoint::Listener => transport.lock().dial_as_listener(addr),
+                    let dial = match (&local_addr, role_override) {
+                        (Some(local_addr), _) => {
+                            transport.lock().dial_from(addr, local_addr, role_override)
+                        }
+                        (None, Endpoint::Dialer) => transport.lock().dial(addr),
+                        (None, Endpoint::Listener) => transport.lock().dial_as_listener(addr),
                     };
                     let result = match dial {
                         Ok(out) => {
@@ -37,6 +37,7 @@ use libp2p_identity as identity;
 use multihash::Multihash;
 use quick_protobuf::MessageWrite;
 use std::collections::HashSet;
+use std::sync::Arc;
 use std::{io, mem};
 
 //////////////////////////////////////////////////////////////////////////////
@@ -57,11 +58,21 @@ pub(crate) struct State<T> {
     responder_webtransport_certhashes: Option<HashSet<Multihash<64>>>,
     /// The received extensions of the remote, if any.
     remote_extensions: Option<Extensions>,
+    /// The policy deciding whether to accept the extensions of the remote, if any.
+    extensions_policy: Option<ExtensionsPolicy>,
 }
 
-/// Extensions
-struct Extensions {
-    webtransport_certhashes: HashSet<Multihash<64>>,
+/// A policy deciding whether to accept the [`Extensions`] received from the remote.
+pub(crate) type ExtensionsPolicy = Arc<dyn Fn(&Extensions) -> Result<(), String> + Send + Sync>;
+
+/// The extensions of the handshake payload received from the remote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Extensions {
+    /// The WebTransport certhashes reported by the remote.
+    pub webtransport_certhashes: HashSet<Multihash<64>>,
+    /// The stream muxers supported by the remote, in order of preference.
+    pub stream_muxers: Vec<String>,
 }
 
 impl<T> State<T>
@@ -80,6 +91,7 @@ where
         identity: KeypairIdentity,
         expected_remote_key: Option<identity::PublicKey>,
         responder_webtransport_certhashes: Option<HashSet<Multihash<64>>>,
+        extensions_policy: Option<ExtensionsPolicy>,
     ) -> Self {
         Self {
             identity,
@@ -88,6 +100,7 @@ where
             id_remote_pubkey: expected_remote_key,
             responder_webtransport_certhashes,
             remote_extensions: None,
+            extensions_policy,
         }
     }
 }
@@ -175,6 +188,7 @@ impl From<proto::NoiseExtensions> for Extensions {
                 .into_iter()
                 .filter_map(|bytes| Multihash::read(&bytes[..]).ok())
                 .collect(),
+            stream_muxers: value.stream_muxers,
         }
     }
 }
@@ -235,6 +249,13 @@ where
         state.remote_extensions = Some(extensions.into());
     }
 
+    // Consult the policy before the handshake completes, also if the remote sent no extensions.
+    if let Some(policy) = &state.extensions_policy {
+        let no_extensions = Extensions::default();
+        policy(state.remote_extensions.as_ref().unwrap_or(&no_extensions))
+            .map_err(Error::ExtensionsRejected)?;
+    }
+
     Ok(())
 }
 
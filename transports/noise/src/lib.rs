@@ -58,9 +58,10 @@
 mod io;
 mod protocol;
 
+pub use io::handshake::Extensions;
 pub use io::Output;
 
-use crate::handshake::State;
+use crate::handshake::{ExtensionsPolicy, State};
 use crate::io::handshake;
 use crate::protocol::{noise_params_into_builder, AuthenticKeypair, Keypair, PARAMS_XX};
 use futures::prelude::*;
@@ -74,6 +75,7 @@ use snow::params::NoiseParams;
 use std::collections::HashSet;
 use std::fmt::Write;
 use std::pin::Pin;
+use std::sync::Arc;
 
 /// The configuration for the noise handshake.
 #[derive(Clone)]
@@ -81,6 +83,7 @@ pub struct Config {
     dh_keys: AuthenticKeypair,
     params: NoiseParams,
     webtransport_certhashes: Option<HashSet<Multihash<64>>>,
+    extensions_policy: Option<ExtensionsPolicy>,
 
     /// Prologue to use in the noise handshake.
     ///
@@ -100,6 +103,7 @@ impl Config {
             dh_keys: noise_keys,
             params: PARAMS_XX.clone(),
             webtransport_certhashes: None,
+            extensions_policy: None,
             prologue: vec![],
         })
     }
@@ -121,6 +125,19 @@ impl Config {
         self
     }
 
+    /// Set a policy deciding whether to accept the [`Extensions`] received from the remote.
+    ///
+    /// The policy is invoked with the decoded extensions of the remote's handshake payload,
+    /// or with empty extensions if the remote sent none, before the handshake completes.
+    /// Returning an `Err` aborts the handshake with [`Error::ExtensionsRejected`].
+    pub fn with_extensions_policy<F>(mut self, policy: F) -> Self
+    where
+        F: Fn(&Extensions) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.extensions_policy = Some(Arc::new(policy));
+        self
+    }
+
     fn into_responder<S: AsyncRead + AsyncWrite>(self, socket: S) -> Result<State<S>, Error> {
         let session = noise_params_into_builder(
             self.params,
@@ -136,6 +153,7 @@ impl Config {
             self.dh_keys.identity,
             None,
             self.webtransport_certhashes,
+            self.extensions_policy,
         );
 
         Ok(state)
@@ -156,6 +174,7 @@ impl Config {
             self.dh_keys.identity,
             None,
             self.webtransport_certhashes,
+            self.extensions_policy,
         );
 
         Ok(state)
@@ -244,6 +263,8 @@ pub enum Error {
     SigningError(#[from] libp2p_identity::SigningError),
     #[error("Expected WebTransport certhashes ({}) are not a subset of received ones ({})", certhashes_to_string(.0), certhashes_to_string(.1))]
     UnknownWebTransportCerthashes(HashSet<Multihash<64>>, HashSet<Multihash<64>>),
+    #[error("Extensions of the remote rejected: {0}")]
+    ExtensionsRejected(String),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -0,0 +1,58 @@
+use libp2p_core::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade};
+use libp2p_identity as identity;
+use libp2p_noise as noise;
+use multihash::Multihash;
+use std::collections::HashSet;
+
+const SHA_256_MH: u64 = 0x12;
+
+#[test]
+fn policy_accepts_extensions() {
+    let certhash = Multihash::wrap(SHA_256_MH, b"1").unwrap();
+
+    handshake_with_policy(vec![certhash], require_certhashes).unwrap();
+}
+
+#[test]
+fn policy_rejects_extensions() {
+    let Err(noise::Error::ExtensionsRejected(reason)) =
+        handshake_with_policy(vec![], require_certhashes)
+    else {
+        panic!("unexpected result");
+    };
+
+    assert_eq!(reason, "no certhashes");
+}
+
+fn require_certhashes(extensions: &noise::Extensions) -> Result<(), String> {
+    if extensions.webtransport_certhashes.is_empty() {
+        return Err("no certhashes".to_owned());
+    }
+    Ok(())
+}
+
+/// Performs a handshake in which the client applies the given policy to the
+/// extensions reported by the server.
+fn handshake_with_policy(
+    server_certhashes: Vec<Multihash<64>>,
+    policy: fn(&noise::Extensions) -> Result<(), String>,
+) -> Result<(), noise::Error> {
+    let client_id = identity::Keypair::generate_ed25519();
+    let server_id = identity::Keypair::generate_ed25519();
+
+    let (client, server) = futures_ringbuf::Endpoint::pair(100, 100);
+
+    futures::executor::block_on(async move {
+        let client_config = noise::Config::new(&client_id)?.with_extensions_policy(policy);
+        let server_config = noise::Config::new(&server_id)?
+            .with_webtransport_certhashes(server_certhashes.into_iter().collect::<HashSet<_>>());
+
+        futures::future::try_join(
+            server_config.upgrade_inbound(server, ""),
+            client_config.upgrade_outbound(client, ""),
+        )
+        .await?;
+
+        Ok(())
+    })
+}
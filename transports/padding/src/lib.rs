@@ -0,0 +1,250 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Traffic padding and cover traffic for libp2p connections.
+//!
+//! This crate provides a connection upgrade that frames all bytes written to a connection
+//! into frames whose sizes are rounded up to a fixed set of buckets. In addition, idle
+//! connections can emit cover frames at a configurable rate, such that an observer can
+//! neither infer message sizes nor activity patterns from the encrypted byte stream.
+//!
+//! The upgrade must be applied _after_ the security upgrade and _before_ the stream
+//! multiplexer, otherwise the frame headers are visible on the wire:
+//!
+//! ```
+//! # use std::time::Duration;
+//! use libp2p_core::{transport::MemoryTransport, upgrade::Version, Transport};
+//! use libp2p_identity as identity;
+//!
+//! let keypair = identity::Keypair::generate_ed25519();
+//! let padding = libp2p_padding::Config::new()
+//!     .with_buckets([512, 2048, 8192])
+//!     .with_cover_traffic(Duration::from_secs(5), Duration::from_secs(1));
+//! let metrics = padding.metrics();
+//!
+//! let transport = MemoryTransport::default()
+//!     .upgrade(Version::V1)
+//!     .authenticate(libp2p_plaintext::Config::new(&keypair))
+//!     .apply(padding)
+//!     .multiplex(libp2p_yamux::Config::default())
+//!     .boxed();
+//! ```
+//!
+//! Both peers must support the padding protocol. Padding and cover traffic are decided
+//! locally: each side may disable them for a given connection through the [`Control`]
+//! handle without affecting the remote.
+//!
+//! Frames have the following layout:
+//!
+//! ```text
+//! | kind (u8) | payload length (u16, BE) | padding length (u16, BE) | payload | padding |
+//! ```
+
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+mod metrics;
+mod stream;
+
+pub use metrics::Metrics;
+pub use stream::{Control, PaddedStream};
+
+use futures::future;
+use libp2p_core::{
+    upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade},
+    UpgradeInfo,
+};
+use std::{convert::Infallible, iter, sync::Arc, time::Duration};
+
+/// The protocol name used for negotiating the padding upgrade.
+pub const PROTOCOL_NAME: &str = "/padding/1.0.0";
+
+/// Length of the header prepended to every frame.
+pub(crate) const HEADER_LEN: usize = 5;
+
+/// The default frame size buckets.
+const DEFAULT_BUCKETS: [usize; 4] = [256, 1024, 4096, 16384];
+
+/// Configuration for the padding upgrade.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub(crate) buckets: Arc<[usize]>,
+    pub(crate) cover_traffic: Option<CoverTraffic>,
+    pub(crate) enabled: bool,
+    pub(crate) metrics: Metrics,
+}
+
+/// Settings for cover traffic on idle connections.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CoverTraffic {
+    /// The time without outbound payload after which a connection is considered idle.
+    pub(crate) idle_timeout: Duration,
+    /// The interval between two cover frames on an idle connection.
+    pub(crate) interval: Duration,
+}
+
+impl Config {
+    /// Creates a new configuration with the default buckets and without cover traffic.
+    pub fn new() -> Self {
+        Self {
+            buckets: Arc::from(DEFAULT_BUCKETS.as_slice()),
+            cover_traffic: None,
+            enabled: true,
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Sets the frame size buckets.
+    ///
+    /// Every frame is padded to the smallest bucket that fits it. Buckets that are too small
+    /// to hold a frame header or larger than [`u16::MAX`] are ignored. If no valid bucket
+    /// remains, the defaults are kept.
+    pub fn with_buckets(mut self, buckets: impl IntoIterator<Item = usize>) -> Self {
+        let mut buckets = buckets
+            .into_iter()
+            .filter(|b| *b > HEADER_LEN && *b <= u16::MAX as usize)
+            .collect::<Vec<_>>();
+        buckets.sort_unstable();
+        buckets.dedup();
+
+        if buckets.is_empty() {
+            tracing::warn!("No valid padding bucket configured, keeping defaults");
+            return self;
+        }
+
+        self.buckets = Arc::from(buckets);
+        self
+    }
+
+    /// Enables cover traffic.
+    ///
+    /// Once no payload has been written for `idle_timeout`, a cover frame of the smallest
+    /// bucket size is sent every `interval` until the connection becomes active again.
+    pub fn with_cover_traffic(mut self, idle_timeout: Duration, interval: Duration) -> Self {
+        self.cover_traffic = Some(CoverTraffic {
+            idle_timeout,
+            interval,
+        });
+        self
+    }
+
+    /// Sets whether padding and cover traffic are enabled on new connections.
+    ///
+    /// Connections always use the framing of the protocol. This setting only controls
+    /// whether frames are padded and cover traffic is emitted. It can be changed for an
+    /// individual connection through [`PaddedStream::control`].
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Returns the metrics aggregated over all connections upgraded with this configuration.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.clone()
+    }
+
+    /// Wraps an already established connection.
+    ///
+    /// This can be used instead of negotiating the upgrade, e.g. within
+    /// [`Transport::and_then`](libp2p_core::Transport::and_then) to make per-connection
+    /// decisions. The remote must wrap its side of the connection as well.
+    pub fn wrap<S>(&self, socket: S) -> PaddedStream<S> {
+        PaddedStream::new(socket, self.clone())
+    }
+
+    /// Returns the bucket size for a frame carrying `payload_len` bytes.
+    pub(crate) fn bucket_for(&self, payload_len: usize) -> usize {
+        let frame_len = HEADER_LEN + payload_len;
+        self.buckets
+            .iter()
+            .copied()
+            .find(|b| *b >= frame_len)
+            .unwrap_or(frame_len)
+    }
+
+    /// Returns the maximum payload a single frame can carry.
+    pub(crate) fn max_payload(&self) -> usize {
+        let largest = *self.buckets.last().expect("at least one bucket");
+        largest - HEADER_LEN
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UpgradeInfo for Config {
+    type Info = &'static str;
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<C> InboundConnectionUpgrade<C> for Config {
+    type Output = PaddedStream<C>;
+    type Error = Infallible;
+    type Future = future::Ready<Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: C, _: Self::Info) -> Self::Future {
+        future::ready(Ok(self.wrap(socket)))
+    }
+}
+
+impl<C> OutboundConnectionUpgrade<C> for Config {
+    type Output = PaddedStream<C>;
+    type Error = Infallible;
+    type Future = future::Ready<Result<Self::Output, Self::Error>>;
+
+    fn upgrade_outbound(self, socket: C, _: Self::Info) -> Self::Future {
+        future::ready(Ok(self.wrap(socket)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_buckets_are_ignored() {
+        let config = Config::new().with_buckets([0, HEADER_LEN, 100, 70_000, 50, 100]);
+
+        assert_eq!(&*config.buckets, &[50, 100]);
+    }
+
+    #[test]
+    fn empty_buckets_keep_defaults() {
+        let config = Config::new().with_buckets([1, 2]);
+
+        assert_eq!(&*config.buckets, DEFAULT_BUCKETS.as_slice());
+    }
+
+    #[test]
+    fn frame_is_padded_to_smallest_fitting_bucket() {
+        let config = Config::new().with_buckets([64, 128]);
+
+        assert_eq!(config.bucket_for(0), 64);
+        assert_eq!(config.bucket_for(64 - HEADER_LEN), 64);
+        assert_eq!(config.bucket_for(64 - HEADER_LEN + 1), 128);
+        assert_eq!(config.max_payload(), 128 - HEADER_LEN);
+    }
+}
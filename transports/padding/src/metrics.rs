@@ -0,0 +1,108 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// Bandwidth counters of padded connections.
+///
+/// Cloning a [`Metrics`] yields a handle to the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    payload_sent: AtomicU64,
+    overhead_sent: AtomicU64,
+    cover_sent: AtomicU64,
+    payload_received: AtomicU64,
+    overhead_received: AtomicU64,
+}
+
+impl Metrics {
+    /// Number of application bytes sent.
+    pub fn payload_bytes_sent(&self) -> u64 {
+        self.inner.payload_sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes sent for frame headers and padding of data frames.
+    pub fn overhead_bytes_sent(&self) -> u64 {
+        self.inner.overhead_sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes sent as cover traffic.
+    pub fn cover_bytes_sent(&self) -> u64 {
+        self.inner.cover_sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of application bytes received.
+    pub fn payload_bytes_received(&self) -> u64 {
+        self.inner.payload_received.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes received for frame headers, padding and cover traffic.
+    pub fn overhead_bytes_received(&self) -> u64 {
+        self.inner.overhead_received.load(Ordering::Relaxed)
+    }
+
+    /// Ratio of all bytes sent to application bytes sent.
+    ///
+    /// Returns `None` if no application bytes have been sent yet.
+    pub fn outbound_overhead_ratio(&self) -> Option<f64> {
+        let payload = self.payload_bytes_sent();
+        if payload == 0 {
+            return None;
+        }
+        let total = payload + self.overhead_bytes_sent() + self.cover_bytes_sent();
+
+        Some(total as f64 / payload as f64)
+    }
+
+    pub(crate) fn record_data_sent(&self, payload: usize, overhead: usize) {
+        self.inner
+            .payload_sent
+            .fetch_add(payload as u64, Ordering::Relaxed);
+        self.inner
+            .overhead_sent
+            .fetch_add(overhead as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_cover_sent(&self, bytes: usize) {
+        self.inner
+            .cover_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_payload_received(&self, bytes: usize) {
+        self.inner
+            .payload_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_overhead_received(&self, bytes: usize) {
+        self.inner
+            .overhead_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,450 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{Config, Metrics, HEADER_LEN};
+use futures::{prelude::*, ready};
+use futures_timer::Delay;
+use pin_project::pin_project;
+use std::{
+    cmp, io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+const KIND_DATA: u8 = 0;
+const KIND_COVER: u8 = 1;
+
+/// Size of the buffer used to discard padding.
+const DISCARD_BUFFER_SIZE: usize = 1024;
+
+/// A connection whose outbound bytes are framed, padded and complemented with cover traffic.
+///
+/// Created by the padding upgrade or by [`Config::wrap`].
+#[pin_project]
+pub struct PaddedStream<S> {
+    #[pin]
+    inner: S,
+    config: Config,
+    control: Control,
+
+    /// Encoded frames that have not yet been written to `inner`.
+    write_buf: Vec<u8>,
+    /// Whether a cover frame has been queued that still needs to be flushed.
+    cover_pending: bool,
+    /// Fires when the next cover frame is due.
+    cover_timer: Option<Delay>,
+
+    read_header: [u8; HEADER_LEN],
+    read_header_len: usize,
+    read_payload_remaining: usize,
+    read_padding_remaining: usize,
+}
+
+impl<S> PaddedStream<S> {
+    pub(crate) fn new(inner: S, config: Config) -> Self {
+        let cover_timer = config
+            .cover_traffic
+            .map(|cover| Delay::new(cover.idle_timeout));
+        let control = Control {
+            enabled: Arc::new(AtomicBool::new(config.enabled)),
+            metrics: Metrics::default(),
+        };
+
+        Self {
+            inner,
+            config,
+            control,
+            write_buf: Vec::new(),
+            cover_pending: false,
+            cover_timer,
+            read_header: [0; HEADER_LEN],
+            read_header_len: 0,
+            read_payload_remaining: 0,
+            read_padding_remaining: 0,
+        }
+    }
+
+    /// Returns a handle to enable or disable padding on this connection.
+    pub fn control(&self) -> Control {
+        self.control.clone()
+    }
+}
+
+impl<S> PaddedStream<S>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    /// Queues a cover frame if one is due and drives its transmission.
+    fn poll_cover_traffic(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+
+        let (Some(cover), Some(timer)) = (this.config.cover_traffic, this.cover_timer.as_mut())
+        else {
+            return Poll::Ready(Ok(()));
+        };
+
+        if timer.poll_unpin(cx).is_ready() {
+            timer.reset(cover.interval);
+            // Registers the waker for the next tick.
+            let _ = timer.poll_unpin(cx);
+
+            // Skip this tick if the connection is still busy writing previous frames.
+            if this.control.is_enabled() && this.write_buf.is_empty() {
+                let frame_len = this.config.bucket_for(0);
+                encode_frame(this.write_buf, KIND_COVER, &[], frame_len - HEADER_LEN);
+                this.config.metrics.record_cover_sent(frame_len);
+                this.control.metrics.record_cover_sent(frame_len);
+                *this.cover_pending = true;
+                tracing::trace!(bytes=%frame_len, "queued cover frame");
+            }
+        }
+
+        if !*this.cover_pending {
+            return Poll::Ready(Ok(()));
+        }
+
+        ready!(poll_flush_buf(&mut this.inner, this.write_buf, cx))?;
+        ready!(this.inner.poll_flush(cx))?;
+        *this.cover_pending = false;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncRead for PaddedStream<S>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if let Poll::Ready(Err(e)) = self.as_mut().poll_cover_traffic(cx) {
+            return Poll::Ready(Err(e));
+        }
+
+        let mut this = self.project();
+
+        loop {
+            if *this.read_payload_remaining > 0 {
+                if buf.is_empty() {
+                    return Poll::Ready(Ok(0));
+                }
+                let max = cmp::min(*this.read_payload_remaining, buf.len());
+                let n = ready!(this.inner.as_mut().poll_read(cx, &mut buf[..max]))?;
+                if n == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                }
+                *this.read_payload_remaining -= n;
+                this.config.metrics.record_payload_received(n);
+                this.control.metrics.record_payload_received(n);
+
+                return Poll::Ready(Ok(n));
+            }
+
+            if *this.read_padding_remaining > 0 {
+                let mut discard = [0u8; DISCARD_BUFFER_SIZE];
+                let max = cmp::min(*this.read_padding_remaining, DISCARD_BUFFER_SIZE);
+                let n = ready!(this.inner.as_mut().poll_read(cx, &mut discard[..max]))?;
+                if n == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                }
+                *this.read_padding_remaining -= n;
+                this.config.metrics.record_overhead_received(n);
+                this.control.metrics.record_overhead_received(n);
+                continue;
+            }
+
+            let n = ready!(this
+                .inner
+                .as_mut()
+                .poll_read(cx, &mut this.read_header[*this.read_header_len..]))?;
+            if n == 0 {
+                if *this.read_header_len == 0 {
+                    return Poll::Ready(Ok(0));
+                }
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            *this.read_header_len += n;
+            if *this.read_header_len < HEADER_LEN {
+                continue;
+            }
+            *this.read_header_len = 0;
+
+            let header = *this.read_header;
+            let payload_len = u16::from_be_bytes([header[1], header[2]]) as usize;
+            let padding_len = u16::from_be_bytes([header[3], header[4]]) as usize;
+            match header[0] {
+                KIND_DATA => {}
+                KIND_COVER if payload_len == 0 => {}
+                kind => {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid padding frame: kind {kind}, payload length {payload_len}"),
+                    )));
+                }
+            }
+            this.config.metrics.record_overhead_received(HEADER_LEN);
+            this.control.metrics.record_overhead_received(HEADER_LEN);
+            *this.read_payload_remaining = payload_len;
+            *this.read_padding_remaining = padding_len;
+        }
+    }
+}
+
+impl<S> AsyncWrite for PaddedStream<S>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+
+        ready!(poll_flush_buf(&mut this.inner, this.write_buf, cx))?;
+        *this.cover_pending = false;
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let payload = &buf[..cmp::min(buf.len(), this.config.max_payload())];
+        let padding = if this.control.is_enabled() {
+            this.config.bucket_for(payload.len()) - HEADER_LEN - payload.len()
+        } else {
+            0
+        };
+        encode_frame(this.write_buf, KIND_DATA, payload, padding);
+        this.config
+            .metrics
+            .record_data_sent(payload.len(), HEADER_LEN + padding);
+        this.control
+            .metrics
+            .record_data_sent(payload.len(), HEADER_LEN + padding);
+
+        if let (Some(cover), Some(timer)) = (this.config.cover_traffic, this.cover_timer.as_mut()) {
+            timer.reset(cover.idle_timeout);
+        }
+
+        // Try to write out the frame right away, it is flushed by `poll_flush` otherwise.
+        if let Poll::Ready(Err(e)) = poll_flush_buf(&mut this.inner, this.write_buf, cx) {
+            return Poll::Ready(Err(e));
+        }
+
+        Poll::Ready(Ok(payload.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+
+        ready!(poll_flush_buf(&mut this.inner, this.write_buf, cx))?;
+        *this.cover_pending = false;
+
+        this.inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+
+        ready!(poll_flush_buf(&mut this.inner, this.write_buf, cx))?;
+        *this.cover_pending = false;
+        *this.cover_timer = None;
+
+        this.inner.poll_close(cx)
+    }
+}
+
+/// A handle to a single padded connection.
+#[derive(Debug, Clone)]
+pub struct Control {
+    enabled: Arc<AtomicBool>,
+    metrics: Metrics,
+}
+
+impl Control {
+    /// Enables or disables padding and cover traffic on the connection.
+    ///
+    /// While disabled, frames are still exchanged but not padded.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether padding and cover traffic are enabled on the connection.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the metrics of this connection only.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.clone()
+    }
+}
+
+fn encode_frame(dst: &mut Vec<u8>, kind: u8, payload: &[u8], padding: usize) {
+    let payload_len = u16::try_from(payload.len()).expect("payload to fit into bucket");
+    let padding_len = u16::try_from(padding).expect("padding to fit into bucket");
+
+    dst.reserve(HEADER_LEN + payload.len() + padding);
+    dst.push(kind);
+    dst.extend_from_slice(&payload_len.to_be_bytes());
+    dst.extend_from_slice(&padding_len.to_be_bytes());
+    dst.extend_from_slice(payload);
+    dst.resize(dst.len() + padding, 0);
+}
+
+/// Writes the contents of `buf` into `inner`.
+///
+/// If this fn returns `Poll::Ready(Ok(()))`, the buffer has been completely written and is empty.
+fn poll_flush_buf<W: AsyncWrite>(
+    inner: &mut Pin<&mut W>,
+    buf: &mut Vec<u8>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>> {
+    while !buf.is_empty() {
+        match ready!(inner.as_mut().poll_write(cx, buf)) {
+            Ok(0) => {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "Failed to write buffered frames",
+                )));
+            }
+            Ok(n) => {
+                buf.drain(..n);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Poll::Ready(Err(e)),
+        }
+    }
+
+    Poll::Ready(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::Cursor;
+    use quickcheck::*;
+    use std::time::Duration;
+
+    fn encode(config: &Config, data: &[u8]) -> Vec<u8> {
+        let mut stream = config.wrap(Cursor::new(Vec::new()));
+        futures::executor::block_on(async {
+            stream.write_all(data).await.unwrap();
+            stream.flush().await.unwrap();
+        });
+
+        stream.inner.into_inner()
+    }
+
+    fn decode(config: &Config, bytes: Vec<u8>) -> Vec<u8> {
+        let mut stream = config.wrap(Cursor::new(bytes));
+        let mut data = Vec::new();
+        futures::executor::block_on(stream.read_to_end(&mut data)).unwrap();
+
+        data
+    }
+
+    #[test]
+    fn roundtrip() {
+        fn prop(data: Vec<u8>, enabled: bool) -> bool {
+            let config = Config::new().with_buckets([16, 64]).with_enabled(enabled);
+
+            decode(&config, encode(&config, &data)) == data
+        }
+        QuickCheck::new().quickcheck(prop as fn(_, _) -> _);
+    }
+
+    #[test]
+    fn frames_have_bucket_sizes() {
+        let config = Config::new().with_buckets([16, 64]);
+
+        let bytes = encode(&config, &[1; 10]);
+        assert_eq!(bytes.len(), 16);
+
+        let bytes = encode(&config, &[1; 12]);
+        assert_eq!(bytes.len(), 64);
+
+        let bytes = encode(&config, &[1; 100]);
+        assert_eq!(bytes.len(), 2 * 64);
+    }
+
+    #[test]
+    fn disabled_connection_is_not_padded() {
+        let config = Config::new().with_buckets([64]).with_enabled(false);
+
+        let bytes = encode(&config, &[1; 10]);
+        assert_eq!(bytes.len(), HEADER_LEN + 10);
+    }
+
+    #[test]
+    fn metrics_account_for_overhead() {
+        let config = Config::new().with_buckets([16, 64]);
+
+        let bytes = encode(&config, &[1; 10]);
+        let metrics = config.metrics();
+        assert_eq!(metrics.payload_bytes_sent(), 10);
+        assert_eq!(metrics.overhead_bytes_sent(), 6);
+        assert_eq!(metrics.outbound_overhead_ratio(), Some(1.6));
+
+        decode(&config, bytes);
+        assert_eq!(metrics.payload_bytes_received(), 10);
+        assert_eq!(metrics.overhead_bytes_received(), 6);
+    }
+
+    #[test]
+    fn rejects_invalid_frame_kind() {
+        let config = Config::new();
+        let mut stream = config.wrap(Cursor::new(vec![7, 0, 0, 0, 0]));
+
+        let error = futures::executor::block_on(stream.read(&mut [0; 8])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn idle_connection_sends_cover_traffic() {
+        let config = Config::new()
+            .with_buckets([32])
+            .with_cover_traffic(Duration::from_millis(10), Duration::from_millis(10));
+        let (a, b) = futures_ringbuf::Endpoint::pair(1024, 1024);
+        let mut a = config.wrap(a);
+        let mut b = Config::new().wrap(b);
+
+        // Reading drives the cover traffic on `a`, while `b` silently discards it.
+        let _ = tokio::time::timeout(Duration::from_millis(100), a.read(&mut [0; 8])).await;
+        let _ = tokio::time::timeout(Duration::from_millis(10), b.read(&mut [0; 8])).await;
+
+        let metrics = config.metrics();
+        assert!(metrics.cover_bytes_sent() > 0);
+        assert_eq!(metrics.cover_bytes_sent() % 32, 0);
+        assert_eq!(
+            a.control().metrics().cover_bytes_sent(),
+            metrics.cover_bytes_sent()
+        );
+        assert!(b.control().metrics().overhead_bytes_received() > 0);
+        assert_eq!(b.control().metrics().payload_bytes_received(), 0);
+    }
+}
@@ -27,6 +27,8 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
 mod crypt_writer;
+mod stats;
+
 use crypt_writer::CryptWriter;
 use futures::prelude::*;
 use pin_project::pin_project;
@@ -41,12 +43,15 @@ use std::{
     fmt::{self, Write},
     io,
     io::Error as IoError,
+    net::IpAddr,
     num::ParseIntError,
     pin::Pin,
     str::FromStr,
     task::{Context, Poll},
 };
 
+pub use stats::{HandshakeStats, SourcePrefix};
+
 const KEY_SIZE: usize = 32;
 const NONCE_SIZE: usize = 24;
 const WRITE_BUFFER_SIZE: usize = 1024;
@@ -227,6 +232,30 @@ impl PnetConfig {
         let read_cipher = XSalsa20::new(&self.key.0.into(), &remote_nonce.into());
         Ok(PnetOutput::new(socket, write_cipher, read_cipher))
     }
+
+    /// Like [`PnetConfig::handshake`], counting the outcome in the given [`HandshakeStats`].
+    ///
+    /// `source` is the address of the remote of an inbound connection, by whose network
+    /// prefix a failed handshake is counted.
+    pub async fn handshake_observed<TSocket>(
+        self,
+        socket: TSocket,
+        source: Option<IpAddr>,
+        stats: HandshakeStats,
+    ) -> Result<PnetOutput<TSocket>, PnetError>
+    where
+        TSocket: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let result = self.handshake(socket).await;
+        match &result {
+            Ok(_) => stats.on_succeeded(),
+            Err(e) => {
+                tracing::debug!(?source, "Pnet handshake failed: {e}");
+                stats.on_failed(source);
+            }
+        }
+        result
+    }
 }
 
 /// The result of a handshake. This implements AsyncRead and AsyncWrite and can therefore
@@ -0,0 +1,178 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+
+/// The maximum number of source prefixes failed handshakes are counted for.
+///
+/// Failed handshakes from further prefixes are only counted in total.
+const MAX_PREFIXES: usize = 1024;
+
+/// The length of the IPv4 prefixes failed handshakes are counted by.
+const IPV4_PREFIX_LEN: u8 = 24;
+
+/// The length of the IPv6 prefixes failed handshakes are counted by.
+const IPV6_PREFIX_LEN: u8 = 48;
+
+/// Counters of the handshakes of a [`PnetConfig`](crate::PnetConfig), see
+/// [`PnetConfig::handshake_observed`](crate::PnetConfig::handshake_observed).
+///
+/// Failed handshakes are counted by the network prefix of their source, e.g. to detect
+/// scanning of a private network. The counters can be cloned cheaply and are shared
+/// between the clones.
+///
+/// Note that the handshake only exchanges nonces, thus a remote using a different
+/// pre-shared key completes it successfully, failing the subsequent upgrades instead.
+#[derive(Debug, Clone, Default)]
+pub struct HandshakeStats {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    succeeded: u64,
+    failed: u64,
+    failed_by_prefix: HashMap<SourcePrefix, u64>,
+}
+
+impl HandshakeStats {
+    /// Returns the number of completed handshakes.
+    pub fn num_succeeded(&self) -> u64 {
+        self.lock().succeeded
+    }
+
+    /// Returns the number of failed handshakes.
+    pub fn num_failed(&self) -> u64 {
+        self.lock().failed
+    }
+
+    /// Returns the number of failed handshakes by the network prefix of their source.
+    ///
+    /// Only the handshakes of inbound connections have a source, and failed handshakes
+    /// are counted for at most 1024 prefixes.
+    pub fn failed_by_prefix(&self) -> Vec<(SourcePrefix, u64)> {
+        self.lock()
+            .failed_by_prefix
+            .iter()
+            .map(|(prefix, count)| (*prefix, *count))
+            .collect()
+    }
+
+    pub(crate) fn on_succeeded(&self) {
+        self.lock().succeeded += 1;
+    }
+
+    pub(crate) fn on_failed(&self, source: Option<IpAddr>) {
+        let mut inner = self.lock();
+        inner.failed += 1;
+
+        let Some(prefix) = source.map(SourcePrefix::new) else {
+            return;
+        };
+        let num_prefixes = inner.failed_by_prefix.len();
+        match inner.failed_by_prefix.get_mut(&prefix) {
+            Some(count) => *count += 1,
+            None if num_prefixes < MAX_PREFIXES => {
+                inner.failed_by_prefix.insert(prefix, 1);
+            }
+            None => {}
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("lock not to be poisoned")
+    }
+}
+
+/// The network prefix of the source of a handshake, i.e. the first 24 bits of an
+/// IPv4 address or the first 48 bits of an IPv6 address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourcePrefix {
+    network: IpAddr,
+    len: u8,
+}
+
+impl SourcePrefix {
+    fn new(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(addr) => Self {
+                network: Ipv4Addr::from(u32::from(addr) & (u32::MAX << (32 - IPV4_PREFIX_LEN)))
+                    .into(),
+                len: IPV4_PREFIX_LEN,
+            },
+            IpAddr::V6(addr) => Self {
+                network: Ipv6Addr::from(u128::from(addr) & (u128::MAX << (128 - IPV6_PREFIX_LEN)))
+                    .into(),
+                len: IPV6_PREFIX_LEN,
+            },
+        }
+    }
+
+    /// Returns the network address, i.e. the source address with the bits beyond the
+    /// prefix cleared.
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    /// Returns the length of the prefix, in bits.
+    pub fn prefix_len(&self) -> u8 {
+        self.len
+    }
+}
+
+impl fmt::Display for SourcePrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_are_counted_by_prefix() {
+        let stats = HandshakeStats::default();
+        stats.on_succeeded();
+        stats.on_failed(Some("192.0.2.1".parse().unwrap()));
+        stats.on_failed(Some("192.0.2.200".parse().unwrap()));
+        stats.on_failed(Some("2001:db8:1:2::1".parse().unwrap()));
+        stats.on_failed(None);
+
+        assert_eq!(stats.num_succeeded(), 1);
+        assert_eq!(stats.num_failed(), 4);
+        let mut by_prefix = stats
+            .failed_by_prefix()
+            .into_iter()
+            .map(|(prefix, count)| (prefix.to_string(), count))
+            .collect::<Vec<_>>();
+        by_prefix.sort();
+        assert_eq!(
+            by_prefix,
+            [
+                ("192.0.2.0/24".to_owned(), 2),
+                ("2001:db8:1::/48".to_owned(), 1)
+            ]
+        );
+    }
+}
@@ -95,6 +95,14 @@ impl Config {
         self.mtu_discovery_config = None;
         self
     }
+
+    /// Set the ALPN protocols offered to remotes connecting to a listener, in order of preference.
+    ///
+    /// Defaults to the libp2p ALPN only. Remote libp2p nodes require it to be included.
+    pub fn with_server_alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        Arc::make_mut(&mut self.server_tls_config).alpn_protocols = protocols;
+        self
+    }
 }
 
 /// Represents the inner configuration for [`quinn`].
@@ -34,7 +34,7 @@ use if_watch::IfEvent;
 use libp2p_core::{
     multiaddr::{Multiaddr, Protocol},
     transport::{ListenerId, TransportError, TransportEvent},
-    Transport,
+    Endpoint, Transport,
 };
 use libp2p_identity::PeerId;
 use socket2::{Domain, Socket, Type};
@@ -73,6 +73,11 @@ pub struct GenTransport<P: Provider> {
     listeners: SelectAll<Listener<P>>,
     /// Dialer for each socket family if no matching listener exists.
     dialer: HashMap<SocketFamily, quinn::Endpoint>,
+    /// Dialers bound to a specific local address, see [`Transport::dial_from`].
+    bound_dialer: HashMap<SocketAddr, quinn::Endpoint>,
+    /// Configs overriding the transport's config for listeners on specific addresses,
+    /// see [`GenTransport::with_listener_config`].
+    listener_configs: HashMap<SocketAddr, (QuinnConfig, Duration)>,
     /// Waker to poll the transport again when a new dialer or listener is added.
     waker: Option<Waker>,
     /// Holepunching attempts
@@ -90,12 +95,28 @@ impl<P: Provider> GenTransport<P> {
             quinn_config,
             handshake_timeout,
             dialer: HashMap::new(),
+            bound_dialer: HashMap::new(),
+            listener_configs: HashMap::new(),
             waker: None,
             support_draft_29,
             hole_punch_attempts: Default::default(),
         }
     }
 
+    /// Use the given [`Config`] instead of the transport's config for listeners on the given address.
+    ///
+    /// This allows listeners of one node to present different certificates, i.e. a [`Config`]
+    /// created with a different keypair, or different ALPN protocols, see
+    /// [`Config::with_server_alpn_protocols`]. E.g. a public listener and a listener of an
+    /// internal mesh network. The address has to match the address passed to
+    /// [`Transport::listen_on`] exactly. Dialing is not affected.
+    pub fn with_listener_config(mut self, listen_addr: SocketAddr, config: Config) -> Self {
+        let handshake_timeout = config.handshake_timeout;
+        self.listener_configs
+            .insert(listen_addr, (config.into(), handshake_timeout));
+        self
+    }
+
     /// Create a new [`quinn::Endpoint`] with the given configs.
     fn new_endpoint(
         endpoint_config: quinn::EndpointConfig,
@@ -181,6 +202,91 @@ impl<P: Provider> GenTransport<P> {
         }
     }
 
+    /// Find the listener whose socket is bound to the given local address.
+    ///
+    /// A port of `0` matches any port.
+    fn listener_for_local_addr(&mut self, local_addr: &SocketAddr) -> Option<&mut Listener<P>> {
+        self.listeners.iter_mut().find(|l| {
+            let socket_addr = l.socket_addr();
+            !l.is_closed
+                && socket_addr.ip() == local_addr.ip()
+                && (local_addr.port() == 0 || socket_addr.port() == local_addr.port())
+        })
+    }
+
+    /// Connect to `socket_addr` through the given endpoint.
+    fn connect(
+        &self,
+        endpoint: quinn::Endpoint,
+        socket_addr: SocketAddr,
+        version: ProtocolVersion,
+    ) -> <Self as Transport>::Dial {
+        let handshake_timeout = self.handshake_timeout;
+        let mut client_config = self.quinn_config.client_config.clone();
+        if version == ProtocolVersion::Draft29 {
+            client_config.version(0xff00_001d);
+        }
+        Box::pin(async move {
+            // This `"l"` seems necessary because an empty string is an invalid domain
+            // name. While we don't use domain names, the underlying rustls library
+            // is based upon the assumption that we do.
+            let connecting = endpoint
+                .connect_with(client_config, socket_addr, "l")
+                .map_err(ConnectError)?;
+            Connecting::new(connecting, handshake_timeout).await
+        })
+    }
+
+    /// Punch a hole to `socket_addr` from the given socket and wait for the inbound
+    /// connection of `peer_id`.
+    fn hole_punch(
+        &mut self,
+        socket: UdpSocket,
+        socket_addr: SocketAddr,
+        peer_id: PeerId,
+    ) -> Result<<Self as Transport>::Dial, TransportError<<Self as Transport>::Error>> {
+        let hole_puncher = hole_puncher::<P>(socket, socket_addr, self.handshake_timeout);
+
+        let (sender, receiver) = oneshot::channel();
+
+        match self.hole_punch_attempts.entry(socket_addr) {
+            Entry::Occupied(mut sender_entry) => {
+                // Stale senders, i.e. from failed hole punches are not removed.
+                // Thus, we can just overwrite a stale sender.
+                if !sender_entry.get().is_canceled() {
+                    return Err(TransportError::Other(Error::HolePunchInProgress(
+                        socket_addr,
+                    )));
+                }
+                sender_entry.insert(sender);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(sender);
+            }
+        };
+
+        Ok(Box::pin(async move {
+            futures::pin_mut!(hole_puncher);
+            match futures::future::select(receiver, hole_puncher).await {
+                Either::Left((message, _)) => {
+                    let (inbound_peer_id, connection) = message
+                        .expect("hole punch connection sender is never dropped before receiver")
+                        .await?;
+                    if inbound_peer_id != peer_id {
+                        tracing::warn!(
+                            peer=%peer_id,
+                            inbound_peer=%inbound_peer_id,
+                            socket_address=%socket_addr,
+                            "expected inbound connection from socket_address to resolve to peer but got inbound peer"
+                        );
+                    }
+                    Ok((inbound_peer_id, connection))
+                }
+                Either::Right((hole_punch_err, _)) => Err(hole_punch_err),
+            }
+        }))
+    }
+
     fn create_socket(&self, socket_addr: SocketAddr) -> io::Result<UdpSocket> {
         let socket = Socket::new(
             Domain::for_address(socket_addr),
@@ -209,19 +315,17 @@ impl<P: Provider> Transport for GenTransport<P> {
         addr: Multiaddr,
     ) -> Result<(), TransportError<Self::Error>> {
         let (socket_addr, version, _peer_id) = self.remote_multiaddr_to_socketaddr(addr, false)?;
-        let endpoint_config = self.quinn_config.endpoint_config.clone();
-        let server_config = self.quinn_config.server_config.clone();
+        let (quinn_config, handshake_timeout) = match self.listener_configs.get(&socket_addr) {
+            Some((quinn_config, handshake_timeout)) => (quinn_config, *handshake_timeout),
+            None => (&self.quinn_config, self.handshake_timeout),
+        };
+        let endpoint_config = quinn_config.endpoint_config.clone();
+        let server_config = quinn_config.server_config.clone();
         let socket = self.create_socket(socket_addr).map_err(Self::Error::from)?;
 
         let socket_c = socket.try_clone().map_err(Self::Error::from)?;
         let endpoint = Self::new_endpoint(endpoint_config, Some(server_config), socket)?;
-        let listener = Listener::new(
-            listener_id,
-            socket_c,
-            endpoint,
-            self.handshake_timeout,
-            version,
-        )?;
+        let listener = Listener::new(listener_id, socket_c, endpoint, handshake_timeout, version)?;
         self.listeners.push(listener);
 
         if let Some(waker) = self.waker.take() {
@@ -232,6 +336,8 @@ impl<P: Provider> Transport for GenTransport<P> {
         // connection that uses it is closed.
         // New outbound connections will use the bidirectional (listener) endpoint.
         self.dialer.remove(&socket_addr.ip().into());
+        self.bound_dialer
+            .retain(|local_addr, _| local_addr.ip() != socket_addr.ip());
 
         Ok(())
     }
@@ -286,20 +392,7 @@ impl<P: Provider> Transport for GenTransport<P> {
             }
             Some(listener) => listener.endpoint.clone(),
         };
-        let handshake_timeout = self.handshake_timeout;
-        let mut client_config = self.quinn_config.client_config.clone();
-        if version == ProtocolVersion::Draft29 {
-            client_config.version(0xff00_001d);
-        }
-        Ok(Box::pin(async move {
-            // This `"l"` seems necessary because an empty string is an invalid domain
-            // name. While we don't use domain names, the underlying rustls library
-            // is based upon the assumption that we do.
-            let connecting = endpoint
-                .connect_with(client_config, socket_addr, "l")
-                .map_err(ConnectError)?;
-            Connecting::new(connecting, handshake_timeout).await
-        }))
+        Ok(self.connect(endpoint, socket_addr, version))
     }
 
     fn dial_as_listener(
@@ -320,46 +413,67 @@ impl<P: Provider> Transport for GenTransport<P> {
 
         tracing::debug!("Preparing for hole-punch from {addr}");
 
-        let hole_puncher = hole_puncher::<P>(socket, socket_addr, self.handshake_timeout);
-
-        let (sender, receiver) = oneshot::channel();
+        self.hole_punch(socket, socket_addr, peer_id)
+    }
 
-        match self.hole_punch_attempts.entry(socket_addr) {
-            Entry::Occupied(mut sender_entry) => {
-                // Stale senders, i.e. from failed hole punches are not removed.
-                // Thus, we can just overwrite a stale sender.
-                if !sender_entry.get().is_canceled() {
-                    return Err(TransportError::Other(Error::HolePunchInProgress(
-                        socket_addr,
-                    )));
-                }
-                sender_entry.insert(sender);
-            }
-            Entry::Vacant(entry) => {
-                entry.insert(sender);
-            }
+    /// Dials `addr` from the given local IP address.
+    ///
+    /// `local_addr` is either an `/ip4` or `/ip6` address, optionally followed by a `/udp`
+    /// port. If a listener is bound to the local address, its endpoint is used for the
+    /// connection. Otherwise a new endpoint is bound to the local address.
+    ///
+    /// Dialing as listener requires a listener bound to the local address.
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let Some(local_addr) = multiaddr_to_local_socketaddr(local_addr) else {
+            return Err(TransportError::MultiaddrNotSupported(addr));
         };
+        let (socket_addr, version, peer_id) =
+            self.remote_multiaddr_to_socketaddr(addr.clone(), true)?;
+        if !SocketFamily::is_same(&local_addr.ip(), &socket_addr.ip()) {
+            return Err(TransportError::Other(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("local address {local_addr} can not reach {socket_addr}"),
+            ))));
+        }
 
-        Ok(Box::pin(async move {
-            futures::pin_mut!(hole_puncher);
-            match futures::future::select(receiver, hole_puncher).await {
-                Either::Left((message, _)) => {
-                    let (inbound_peer_id, connection) = message
-                        .expect("hole punch connection sender is never dropped before receiver")
-                        .await?;
-                    if inbound_peer_id != peer_id {
-                        tracing::warn!(
-                            peer=%peer_id,
-                            inbound_peer=%inbound_peer_id,
-                            socket_address=%socket_addr,
-                            "expected inbound connection from socket_address to resolve to peer but got inbound peer"
-                        );
+        if role_override == Endpoint::Listener {
+            let peer_id = peer_id.ok_or(TransportError::MultiaddrNotSupported(addr.clone()))?;
+            let socket = self
+                .listener_for_local_addr(&local_addr)
+                .ok_or(TransportError::Other(
+                    Error::NoActiveListenerForDialAsListener,
+                ))?
+                .try_clone_socket()
+                .map_err(Self::Error::from)?;
+
+            tracing::debug!("Preparing for hole-punch from {addr} via {local_addr}");
+
+            return self.hole_punch(socket, socket_addr, peer_id);
+        }
+
+        let endpoint = match self.listener_for_local_addr(&local_addr) {
+            Some(listener) => listener.endpoint.clone(),
+            None => match self.bound_dialer.entry(local_addr) {
+                Entry::Occupied(occupied) => occupied.get().clone(),
+                Entry::Vacant(vacant) => {
+                    if let Some(waker) = self.waker.take() {
+                        waker.wake();
                     }
-                    Ok((inbound_peer_id, connection))
+                    let socket = UdpSocket::bind(local_addr).map_err(Self::Error::from)?;
+                    let endpoint_config = self.quinn_config.endpoint_config.clone();
+                    let endpoint = Self::new_endpoint(endpoint_config, None, socket)?;
+
+                    vacant.insert(endpoint.clone());
+                    endpoint
                 }
-                Either::Right((hole_punch_err, _)) => Err(hole_punch_err),
-            }
-        }))
+            },
+        };
+        Ok(self.connect(endpoint, socket_addr, version))
     }
 
     fn poll(
@@ -712,6 +826,27 @@ fn multiaddr_to_socketaddr(
     }
 }
 
+/// Extracts a UDP [`SocketAddr`] to bind a dialer to from a local address.
+///
+/// The UDP port is optional and defaults to `0`, i.e. an ephemeral port.
+fn multiaddr_to_local_socketaddr(addr: &Multiaddr) -> Option<SocketAddr> {
+    let mut iter = addr.iter();
+    let ip = match iter.next()? {
+        Protocol::Ip4(ip) => IpAddr::from(ip),
+        Protocol::Ip6(ip) => IpAddr::from(ip),
+        _ => return None,
+    };
+    let port = match iter.next() {
+        Some(Protocol::Udp(port)) => port,
+        None => 0,
+        _ => return None,
+    };
+    if iter.next().is_some() {
+        return None;
+    }
+    Some(SocketAddr::new(ip, port))
+}
+
 /// Whether an [`Multiaddr`] is a valid for the QUIC transport.
 fn is_quic_addr(addr: &Multiaddr, support_draft_29: bool) -> bool {
     use Protocol::*;
@@ -927,6 +1062,46 @@ mod tests {
         assert!(!transport.dialer.contains_key(&SocketFamily::Ipv4));
     }
 
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_dial_from() {
+        let keypair = libp2p_identity::Keypair::generate_ed25519();
+        let config = Config::new(&keypair);
+        let mut transport = crate::tokio::Transport::new(config);
+        let local_addr = "/ip4/127.0.0.1".parse().unwrap();
+
+        assert!(matches!(
+            transport.dial_from(
+                "/ip6/::1/udp/1234/quic-v1".parse().unwrap(),
+                &local_addr,
+                Endpoint::Dialer,
+            ),
+            Err(TransportError::Other(Error::Io(_)))
+        ));
+        assert!(transport.bound_dialer.is_empty());
+
+        let _dial = transport
+            .dial_from(
+                "/ip4/127.0.0.1/udp/1234/quic-v1".parse().unwrap(),
+                &local_addr,
+                Endpoint::Dialer,
+            )
+            .unwrap();
+        assert!(transport
+            .bound_dialer
+            .contains_key(&"127.0.0.1:0".parse().unwrap()));
+        assert!(transport.dialer.is_empty());
+
+        // Start listening so that the bound dialer is dropped.
+        transport
+            .listen_on(
+                ListenerId::next(),
+                "/ip4/127.0.0.1/udp/0/quic-v1".parse().unwrap(),
+            )
+            .unwrap();
+        assert!(transport.bound_dialer.is_empty());
+    }
+
     #[cfg(feature = "tokio")]
     #[tokio::test]
     async fn test_listens_ipv4_ipv6_separately() {
@@ -458,6 +458,32 @@ async fn test_local_listener_reuse() {
     assert_eq!(send_back_addr, a_listen_addr);
 }
 
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn per_listener_config() {
+    let listener_keypair = generate_tls_keypair();
+    let listener_peer_id = listener_keypair.public().to_peer_id();
+
+    let (a_peer_id, mut a_transport) = create_default_transport::<quic::tokio::Provider>();
+    let mut b_transport = {
+        let keypair = generate_tls_keypair();
+        quic::tokio::Transport::new(quic::Config::new(&keypair))
+            .with_listener_config(
+                "127.0.0.1:0".parse().unwrap(),
+                quic::Config::new(&listener_keypair),
+            )
+            .map(|(p, c), _| (p, StreamMuxerBox::new(c)))
+            .boxed()
+    };
+
+    let b_addr = start_listening(&mut b_transport, "/ip4/127.0.0.1/udp/0/quic-v1").await;
+    let ((b_connected, _, _), (a_connected, _)) =
+        connect(&mut b_transport, &mut a_transport, b_addr).await;
+
+    assert_eq!(b_connected, a_peer_id);
+    assert_eq!(a_connected, listener_peer_id);
+}
+
 async fn smoke<P: Provider>() {
     let _ = tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
@@ -36,6 +36,8 @@ pub use provider::async_io;
 #[cfg(feature = "tokio")]
 pub use provider::tokio;
 
+pub use socket2::Socket;
+
 use futures::{future::Ready, prelude::*, stream::SelectAll};
 use futures_timer::Delay;
 use if_watch::IfEvent;
@@ -43,12 +45,13 @@ use libp2p_core::{
     address_translation,
     multiaddr::{Multiaddr, Protocol},
     transport::{ListenerId, TransportError, TransportEvent},
+    Endpoint,
 };
 use provider::{Incoming, Provider};
-use socket2::{Domain, Socket, Type};
+use socket2::{Domain, Type};
 use std::{
     collections::{HashSet, VecDeque},
-    io,
+    fmt, io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener},
     pin::Pin,
     sync::{Arc, RwLock},
@@ -63,6 +66,12 @@ pub struct Config {
     ttl: Option<u32>,
     /// `TCP_NODELAY` to set for opened sockets, or `None` to keep default.
     nodelay: Option<bool>,
+    /// `TCP_NODELAY` to set for listening sockets, overriding `nodelay`.
+    listener_nodelay: Option<bool>,
+    /// `TCP_NODELAY` to set for dialing sockets, overriding `nodelay`.
+    dialer_nodelay: Option<bool>,
+    /// Hook invoked with new sockets before they are bound.
+    socket_hook: Option<SocketHook>,
     /// Size of the listen backlog for listen sockets.
     backlog: u32,
     /// Whether port reuse should be enabled.
@@ -71,6 +80,16 @@ pub struct Config {
 
 type Port = u16;
 
+/// A hook invoked with every new socket, see [`Config::socket_hook`].
+#[derive(Clone)]
+struct SocketHook(Arc<dyn Fn(&Socket, Endpoint) -> io::Result<()> + Send + Sync>);
+
+impl fmt::Debug for SocketHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SocketHook").finish()
+    }
+}
+
 /// The configuration for port reuse of listening sockets.
 #[derive(Debug, Clone)]
 enum PortReuse {
@@ -162,6 +181,9 @@ impl Config {
         Self {
             ttl: None,
             nodelay: None,
+            listener_nodelay: None,
+            dialer_nodelay: None,
+            socket_hook: None,
             backlog: 1024,
             enable_port_reuse: false,
         }
@@ -179,6 +201,35 @@ impl Config {
         self
     }
 
+    /// Configures the `TCP_NODELAY` option for new listening sockets, and thereby
+    /// for the connections accepted on them, overriding [`Config::nodelay`].
+    pub fn listener_nodelay(mut self, value: bool) -> Self {
+        self.listener_nodelay = Some(value);
+        self
+    }
+
+    /// Configures the `TCP_NODELAY` option for new sockets of outgoing connections,
+    /// overriding [`Config::nodelay`].
+    pub fn dialer_nodelay(mut self, value: bool) -> Self {
+        self.dialer_nodelay = Some(value);
+        self
+    }
+
+    /// Sets a hook invoked with every new socket after the configured options
+    /// are applied, but before it is bound and starts listening or connecting.
+    ///
+    /// The [`Endpoint`] tells whether the socket is for a listener or for an outgoing
+    /// connection. This allows setting socket options not covered by this configuration,
+    /// e.g. `SO_MARK` for policy routing. An error returned by the hook fails the
+    /// respective `listen_on` or `dial` call.
+    pub fn socket_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Socket, Endpoint) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.socket_hook = Some(SocketHook(Arc::new(hook)));
+        self
+    }
+
     /// Configures the listen backlog for new listen sockets.
     pub fn listen_backlog(mut self, backlog: u32) -> Self {
         self.backlog = backlog;
@@ -342,7 +393,7 @@ where
         }
     }
 
-    fn create_socket(&self, socket_addr: SocketAddr) -> io::Result<Socket> {
+    fn create_socket(&self, socket_addr: SocketAddr, endpoint: Endpoint) -> io::Result<Socket> {
         let socket = Socket::new(
             Domain::for_address(socket_addr),
             Type::STREAM,
@@ -354,7 +405,11 @@ where
         if let Some(ttl) = self.config.ttl {
             socket.set_ttl(ttl)?;
         }
-        if let Some(nodelay) = self.config.nodelay {
+        let nodelay = match endpoint {
+            Endpoint::Listener => self.config.listener_nodelay,
+            Endpoint::Dialer => self.config.dialer_nodelay,
+        };
+        if let Some(nodelay) = nodelay.or(self.config.nodelay) {
             socket.set_nodelay(nodelay)?;
         }
         socket.set_reuse_address(true)?;
@@ -362,6 +417,9 @@ where
         if let PortReuse::Enabled { .. } = &self.port_reuse {
             socket.set_reuse_port(true)?;
         }
+        if let Some(SocketHook(hook)) = &self.config.socket_hook {
+            hook(&socket, endpoint)?;
+        }
         Ok(socket)
     }
 
@@ -370,7 +428,7 @@ where
         id: ListenerId,
         socket_addr: SocketAddr,
     ) -> io::Result<ListenStream<T>> {
-        let socket = self.create_socket(socket_addr)?;
+        let socket = self.create_socket(socket_addr, Endpoint::Listener)?;
         socket.bind(&socket_addr.into())?;
         socket.listen(self.config.backlog as _)?;
         socket.set_nonblocking(true)?;
@@ -421,42 +479,17 @@ where
     }
 }
 
-impl<T> libp2p_core::Transport for Transport<T>
+impl<T> Transport<T>
 where
     T: Provider + Send + 'static,
     T::Listener: Unpin,
     T::Stream: Unpin,
 {
-    type Output = T::Stream;
-    type Error = io::Error;
-    type Dial = Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send>>;
-    type ListenerUpgrade = Ready<Result<Self::Output, Self::Error>>;
-
-    fn listen_on(
+    fn do_dial(
         &mut self,
-        id: ListenerId,
         addr: Multiaddr,
-    ) -> Result<(), TransportError<Self::Error>> {
-        let socket_addr = multiaddr_to_socketaddr(addr.clone())
-            .map_err(|_| TransportError::MultiaddrNotSupported(addr))?;
-        tracing::debug!("listening on {}", socket_addr);
-        let listener = self
-            .do_listen(id, socket_addr)
-            .map_err(TransportError::Other)?;
-        self.listeners.push(listener);
-        Ok(())
-    }
-
-    fn remove_listener(&mut self, id: ListenerId) -> bool {
-        if let Some(listener) = self.listeners.iter_mut().find(|l| l.listener_id == id) {
-            listener.close(Ok(()));
-            true
-        } else {
-            false
-        }
-    }
-
-    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        local_addr: Option<SocketAddr>,
+    ) -> Result<<Self as libp2p_core::Transport>::Dial, TransportError<io::Error>> {
         let socket_addr = if let Ok(socket_addr) = multiaddr_to_socketaddr(addr.clone()) {
             if socket_addr.port() == 0 || socket_addr.ip().is_unspecified() {
                 return Err(TransportError::MultiaddrNotSupported(addr));
@@ -467,11 +500,25 @@ where
         };
         tracing::debug!(address=%socket_addr, "dialing address");
 
+        if let Some(local_addr) = local_addr {
+            if local_addr.is_ipv4() != socket_addr.is_ipv4() {
+                return Err(TransportError::Other(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("local address {local_addr} can not reach {socket_addr}"),
+                )));
+            }
+        }
+
         let socket = self
-            .create_socket(socket_addr)
+            .create_socket(socket_addr, Endpoint::Dialer)
             .map_err(TransportError::Other)?;
 
-        if let Some(addr) = self.port_reuse.local_dial_addr(&socket_addr.ip()) {
+        if let Some(local_addr) = local_addr {
+            tracing::trace!(address=%local_addr, "Binding dial socket to local address");
+            socket
+                .bind(&local_addr.into())
+                .map_err(TransportError::Other)?;
+        } else if let Some(addr) = self.port_reuse.local_dial_addr(&socket_addr.ip()) {
             tracing::trace!(address=%addr, "Binding dial socket to listen socket address");
             socket.bind(&addr.into()).map_err(TransportError::Other)?;
         }
@@ -495,6 +542,46 @@ where
         }
         .boxed())
     }
+}
+
+impl<T> libp2p_core::Transport for Transport<T>
+where
+    T: Provider + Send + 'static,
+    T::Listener: Unpin,
+    T::Stream: Unpin,
+{
+    type Output = T::Stream;
+    type Error = io::Error;
+    type Dial = Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send>>;
+    type ListenerUpgrade = Ready<Result<Self::Output, Self::Error>>;
+
+    fn listen_on(
+        &mut self,
+        id: ListenerId,
+        addr: Multiaddr,
+    ) -> Result<(), TransportError<Self::Error>> {
+        let socket_addr = multiaddr_to_socketaddr(addr.clone())
+            .map_err(|_| TransportError::MultiaddrNotSupported(addr))?;
+        tracing::debug!("listening on {}", socket_addr);
+        let listener = self
+            .do_listen(id, socket_addr)
+            .map_err(TransportError::Other)?;
+        self.listeners.push(listener);
+        Ok(())
+    }
+
+    fn remove_listener(&mut self, id: ListenerId) -> bool {
+        if let Some(listener) = self.listeners.iter_mut().find(|l| l.listener_id == id) {
+            listener.close(Ok(()));
+            true
+        } else {
+            false
+        }
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(addr, None)
+    }
 
     fn dial_as_listener(
         &mut self,
@@ -503,6 +590,23 @@ where
         self.dial(addr)
     }
 
+    /// Dials `addr` from the given local IP address.
+    ///
+    /// `local_addr` is either an `/ip4` or `/ip6` address, optionally followed by a `/tcp`
+    /// port. Without a port, an ephemeral port is used. The connection fails with an error if
+    /// `local_addr` is of a different IP family than `addr`.
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let Some(local_addr) = multiaddr_to_local_socketaddr(local_addr) else {
+            return Err(TransportError::MultiaddrNotSupported(addr));
+        };
+        self.do_dial(addr, Some(local_addr))
+    }
+
     /// When port reuse is disabled and hence ephemeral local ports are
     /// used for outgoing connections, the returned address is the
     /// `observed` address with the port replaced by the port of the
@@ -814,6 +918,27 @@ fn multiaddr_to_socketaddr(mut addr: Multiaddr) -> Result<SocketAddr, ()> {
     Err(())
 }
 
+/// Extracts a [`SocketAddr`] to bind a dial socket to from a local address.
+///
+/// The TCP port is optional and defaults to `0`, i.e. an ephemeral port.
+fn multiaddr_to_local_socketaddr(addr: &Multiaddr) -> Option<SocketAddr> {
+    let mut iter = addr.iter();
+    let ip = match iter.next()? {
+        Protocol::Ip4(ip) => IpAddr::from(ip),
+        Protocol::Ip6(ip) => IpAddr::from(ip),
+        _ => return None,
+    };
+    let port = match iter.next() {
+        Some(Protocol::Tcp(port)) => port,
+        None => 0,
+        _ => return None,
+    };
+    if iter.next().is_some() {
+        return None;
+    }
+    Some(SocketAddr::new(ip, port))
+}
+
 // Create a [`Multiaddr`] from the given IP address and port number.
 fn ip_to_multiaddr(ip: IpAddr, port: u16) -> Multiaddr {
     Multiaddr::empty().with(ip.into()).with(Protocol::Tcp(port))
@@ -1146,6 +1271,164 @@ mod tests {
         test("/ip6/::1/tcp/0".parse().unwrap());
     }
 
+    #[test]
+    fn dial_from_local_address() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        async fn listener<T: Provider>(
+            addr: Multiaddr,
+            mut ready_tx: mpsc::Sender<Multiaddr>,
+            local_addr: Multiaddr,
+        ) {
+            let mut tcp = Transport::<T>::new(Config::new()).boxed();
+            tcp.listen_on(ListenerId::next(), addr).unwrap();
+            loop {
+                match tcp.select_next_some().await {
+                    TransportEvent::NewAddress { listen_addr, .. } => {
+                        ready_tx.send(listen_addr).await.ok();
+                    }
+                    TransportEvent::Incoming { send_back_addr, .. } => {
+                        assert_eq!(send_back_addr, local_addr);
+                        return;
+                    }
+                    e => panic!("Unexpected event: {e:?}"),
+                }
+            }
+        }
+
+        async fn dialer<T: Provider>(
+            mut ready_rx: mpsc::Receiver<Multiaddr>,
+            local_addr: Multiaddr,
+        ) {
+            let dest_addr = ready_rx.next().await.unwrap();
+            let mut tcp = Transport::<T>::default();
+            tcp.dial_from(dest_addr, &local_addr, Endpoint::Dialer)
+                .unwrap()
+                .await
+                .unwrap();
+        }
+
+        fn test(addr: Multiaddr) {
+            let local_addr = {
+                let socket_addr = multiaddr_to_socketaddr(addr.clone()).unwrap();
+                let port = TcpListener::bind(socket_addr)
+                    .unwrap()
+                    .local_addr()
+                    .unwrap()
+                    .port();
+                ip_to_multiaddr(socket_addr.ip(), port)
+            };
+
+            #[cfg(feature = "async-io")]
+            {
+                let (ready_tx, ready_rx) = mpsc::channel(1);
+                let listener =
+                    listener::<async_io::Tcp>(addr.clone(), ready_tx, local_addr.clone());
+                let dialer = dialer::<async_io::Tcp>(ready_rx, local_addr.clone());
+                let listener = async_std::task::spawn(listener);
+                async_std::task::block_on(dialer);
+                async_std::task::block_on(listener);
+            }
+
+            #[cfg(feature = "tokio")]
+            {
+                let (ready_tx, ready_rx) = mpsc::channel(1);
+                let listener = listener::<tokio::Tcp>(addr, ready_tx, local_addr.clone());
+                let dialer = dialer::<tokio::Tcp>(ready_rx, local_addr);
+                let rt = ::tokio::runtime::Builder::new_current_thread()
+                    .enable_io()
+                    .build()
+                    .unwrap();
+                let tasks = ::tokio::task::LocalSet::new();
+                let listener = tasks.spawn_local(listener);
+                tasks.block_on(&rt, dialer);
+                tasks.block_on(&rt, listener).unwrap();
+            }
+        }
+
+        test("/ip4/127.0.0.1/tcp/0".parse().unwrap());
+        test("/ip6/::1/tcp/0".parse().unwrap());
+    }
+
+    #[test]
+    fn dial_from_other_ip_family_fails() {
+        fn test<T: Provider>() {
+            let mut tcp = Transport::<T>::default();
+            let result = tcp.dial_from(
+                "/ip6/::1/tcp/4001".parse().unwrap(),
+                &"/ip4/127.0.0.1".parse().unwrap(),
+                Endpoint::Dialer,
+            );
+            assert!(matches!(result, Err(TransportError::Other(_))));
+        }
+
+        #[cfg(feature = "async-io")]
+        test::<async_io::Tcp>();
+        #[cfg(feature = "tokio")]
+        test::<tokio::Tcp>();
+    }
+
+    #[test]
+    fn socket_hook_sees_per_endpoint_options() {
+        fn test<T: Provider>() {
+            let endpoints = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let config = {
+                let endpoints = endpoints.clone();
+                Config::new()
+                    .listener_nodelay(false)
+                    .dialer_nodelay(true)
+                    .socket_hook(move |socket, endpoint| {
+                        let nodelay = socket.nodelay()?;
+                        endpoints.lock().unwrap().push((endpoint, nodelay));
+                        Ok(())
+                    })
+            };
+            let mut tcp = Transport::<T>::new(config);
+            tcp.listen_on(ListenerId::next(), "/ip4/127.0.0.1/tcp/0".parse().unwrap())
+                .unwrap();
+            drop(
+                tcp.dial("/ip4/127.0.0.1/tcp/4001".parse().unwrap())
+                    .unwrap(),
+            );
+
+            assert_eq!(
+                *endpoints.lock().unwrap(),
+                [(Endpoint::Listener, false), (Endpoint::Dialer, true)]
+            );
+        }
+
+        #[cfg(feature = "async-io")]
+        test::<async_io::Tcp>();
+        #[cfg(feature = "tokio")]
+        {
+            let rt = ::tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()
+                .unwrap();
+            rt.block_on(async { test::<tokio::Tcp>() });
+        }
+    }
+
+    #[test]
+    fn socket_hook_error_fails_dial() {
+        fn test<T: Provider>() {
+            let mut tcp =
+                Transport::<T>::new(Config::new().socket_hook(|_, endpoint| match endpoint {
+                    Endpoint::Dialer => Err(io::Error::other("rejected")),
+                    Endpoint::Listener => Ok(()),
+                }));
+            let result = tcp.dial("/ip4/127.0.0.1/tcp/4001".parse().unwrap());
+            assert!(matches!(result, Err(TransportError::Other(_))));
+        }
+
+        #[cfg(feature = "async-io")]
+        test::<async_io::Tcp>();
+        #[cfg(feature = "tokio")]
+        test::<tokio::Tcp>();
+    }
+
     #[test]
     fn port_reuse_listening() {
         let _ = tracing_subscriber::fmt()
@@ -30,6 +30,9 @@
 //!
 //! The `UdsConfig` structs implements the `Transport` trait of the `core` library. See the
 //! documentation of `core` and of libp2p in general to learn how to use the `Transport` trait.
+//!
+//! Dialing from a local address via `Transport::dial_from` is not supported, as outgoing Unix
+//! domain socket connections are not bound to a local address.
 
 #![cfg(all(
     unix,
@@ -150,14 +150,28 @@ where
     }
 
     fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.do_dial(addr, Endpoint::Dialer)
+        self.do_dial(addr, None, Endpoint::Dialer)
     }
 
     fn dial_as_listener(
         &mut self,
         addr: Multiaddr,
     ) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.do_dial(addr, Endpoint::Listener)
+        self.do_dial(addr, None, Endpoint::Listener)
+    }
+
+    /// Dials `addr` from `local_addr` via the inner transport.
+    ///
+    /// `local_addr` is handed to the inner transport as is, thus e.g. an `/ip4` address
+    /// optionally followed by a `/tcp` port for a TCP transport. Redirects are followed
+    /// from the same local address.
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(addr, Some(local_addr.clone()), role_override)
     }
 
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
@@ -263,6 +277,7 @@ where
     fn do_dial(
         &mut self,
         addr: Multiaddr,
+        local_addr: Option<Multiaddr>,
         role_override: Endpoint,
     ) -> Result<<Self as Transport>::Dial, TransportError<<Self as Transport>::Error>> {
         let mut addr = match parse_ws_dial_addr(addr) {
@@ -282,8 +297,14 @@ where
 
         let future = async move {
             loop {
-                match Self::dial_once(transport.clone(), addr, tls_config.clone(), role_override)
-                    .await
+                match Self::dial_once(
+                    transport.clone(),
+                    addr,
+                    local_addr.as_ref(),
+                    tls_config.clone(),
+                    role_override,
+                )
+                .await
                 {
                     Ok(Either::Left(redirect)) => {
                         if remaining_redirects == 0 {
@@ -306,14 +327,20 @@ where
     async fn dial_once(
         transport: Arc<Mutex<T>>,
         addr: WsAddress,
+        local_addr: Option<&Multiaddr>,
         tls_config: tls::Config,
         role_override: Endpoint,
     ) -> Result<Either<String, Connection<T::Output>>, Error<T::Error>> {
         tracing::trace!(address=?addr, "Dialing websocket address");
 
-        let dial = match role_override {
-            Endpoint::Dialer => transport.lock().dial(addr.tcp_addr),
-            Endpoint::Listener => transport.lock().dial_as_listener(addr.tcp_addr),
+        let dial = match (local_addr, role_override) {
+            (Some(local_addr), _) => {
+                transport
+                    .lock()
+                    .dial_from(addr.tcp_addr, local_addr, role_override)
+            }
+            (None, Endpoint::Dialer) => transport.lock().dial(addr.tcp_addr),
+            (None, Endpoint::Listener) => transport.lock().dial_as_listener(addr.tcp_addr),
         }
         .map_err(|e| match e {
             TransportError::MultiaddrNotSupported(a) => Error::InvalidMultiaddr(a),
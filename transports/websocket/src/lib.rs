@@ -31,7 +31,7 @@ use error::Error;
 use framed::{Connection, Incoming};
 use futures::{future::BoxFuture, prelude::*, ready};
 use libp2p_core::{
-    connection::ConnectedPoint,
+    connection::{ConnectedPoint, Endpoint},
     multiaddr::Multiaddr,
     transport::{map::MapFuture, ListenerId, TransportError, TransportEvent},
     Transport,
@@ -213,6 +213,15 @@ where
         self.transport.dial_as_listener(addr)
     }
 
+    fn dial_from(
+        &mut self,
+        addr: Multiaddr,
+        local_addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.transport.dial_from(addr, local_addr, role_override)
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         self.transport.address_translation(server, observed)
     }
@@ -292,27 +301,36 @@ where
 mod tests {
     use super::WsConfig;
     use futures::prelude::*;
-    use libp2p_core::{multiaddr::Protocol, transport::ListenerId, Multiaddr, Transport};
+    use libp2p_core::{
+        connection::Endpoint, multiaddr::Protocol, transport::ListenerId, Multiaddr, Transport,
+    };
     use libp2p_identity::PeerId;
     use libp2p_tcp as tcp;
 
     #[test]
     fn dialer_connects_to_listener_ipv4() {
         let a = "/ip4/127.0.0.1/tcp/0/ws".parse().unwrap();
-        futures::executor::block_on(connect(a))
+        futures::executor::block_on(connect(a, None))
     }
 
     #[test]
     fn dialer_connects_to_listener_ipv6() {
         let a = "/ip6/::1/tcp/0/ws".parse().unwrap();
-        futures::executor::block_on(connect(a))
+        futures::executor::block_on(connect(a, None))
+    }
+
+    #[test]
+    fn dialer_connects_from_local_address() {
+        let a = "/ip4/127.0.0.1/tcp/0/ws".parse().unwrap();
+        let local_addr = "/ip4/127.0.0.1".parse().unwrap();
+        futures::executor::block_on(connect(a, Some(local_addr)))
     }
 
     fn new_ws_config() -> WsConfig<tcp::async_io::Transport> {
         WsConfig::new(tcp::async_io::Transport::new(tcp::Config::default()))
     }
 
-    async fn connect(listen_addr: Multiaddr) {
+    async fn connect(listen_addr: Multiaddr, local_addr: Option<Multiaddr>) {
         let mut ws_config = new_ws_config().boxed();
         ws_config
             .listen_on(ListenerId::next(), listen_addr)
@@ -337,10 +355,16 @@ mod tests {
             conn.await
         };
 
-        let outbound = new_ws_config()
-            .boxed()
-            .dial(addr.with(Protocol::P2p(PeerId::random())))
-            .unwrap();
+        let addr = addr.with(Protocol::P2p(PeerId::random()));
+        let outbound = match local_addr.as_ref() {
+            Some(local_addr) => {
+                new_ws_config()
+                    .boxed()
+                    .dial_from(addr, local_addr, Endpoint::Dialer)
+            }
+            None => new_ws_config().boxed().dial(addr),
+        }
+        .unwrap();
 
         let (a, b) = futures::join!(inbound, outbound);
         a.and(b).unwrap();